@@ -6,6 +6,8 @@
 
 #![deny(missing_docs)]
 
+pub mod limits;
+
 use std::collections::HashSet;
 use std::sync::Arc;
 
@@ -13,7 +15,10 @@ use serde::Deserialize;
 use serde_json::Value;
 use spin_locked_app::MetadataExt;
 
-use locked::{ContentPath, LockedApp, LockedComponent, LockedComponentSource, LockedTrigger};
+use locked::{
+    BuildMetadata, ComponentSourceKind, ContentPath, LockedApp, LockedComponent,
+    LockedComponentSource, LockedTrigger,
+};
 
 pub use spin_locked_app::locked;
 pub use spin_locked_app::values;
@@ -30,6 +35,12 @@ pub const APP_DESCRIPTION_KEY: MetadataKey = MetadataKey::new("description");
 /// MetadataKey for extracting the OCI image digest.
 pub const OCI_IMAGE_DIGEST_KEY: MetadataKey = MetadataKey::new("oci_image_digest");
 
+const COMPONENT_DESCRIPTION_KEY: MetadataKey = MetadataKey::new("description");
+const COMPONENT_SOURCE_KIND_KEY: MetadataKey<ComponentSourceKind> =
+    MetadataKey::new(locked::SOURCE_KIND_KEY);
+const COMPONENT_BUILD_METADATA_KEY: MetadataKey<BuildMetadata> =
+    MetadataKey::new(locked::BUILD_METADATA_KEY);
+
 /// Validation function type for ensuring that applications meet requirements
 /// even with components filtered out.
 pub type ValidatorFn = dyn Fn(&App, &[&str]) -> anyhow::Result<()>;
@@ -239,6 +250,32 @@ impl<'a> AppComponent<'a> {
         &self.locked.source
     }
 
+    /// Returns this component's description, if it has one.
+    pub fn description(&self) -> Result<Option<String>> {
+        self.get_metadata(COMPONENT_DESCRIPTION_KEY)
+    }
+
+    /// Returns the SHA-256 digest of this component's Wasm source, e.g.
+    /// `sha256:abc...`, if it is known.
+    ///
+    /// The digest is known for sources from a registry or URL, for local
+    /// sources built by `spin build`, and for apps from an OCI registry.
+    pub fn source_digest(&self) -> Option<&str> {
+        self.locked.source.content.digest.as_deref()
+    }
+
+    /// Returns where the manifest says this component's Wasm source comes
+    /// from, if the loader recorded it.
+    pub fn source_kind(&self) -> Result<Option<ComponentSourceKind>> {
+        self.get_metadata(COMPONENT_SOURCE_KIND_KEY)
+    }
+
+    /// Returns how this component's Wasm source was built, if it was built
+    /// by `spin build` and has not changed since.
+    pub fn build_metadata(&self) -> Result<Option<BuildMetadata>> {
+        self.get_metadata(COMPONENT_BUILD_METADATA_KEY)
+    }
+
     /// Returns an iterator of environment variable (key, value) pairs.
     pub fn environment(&self) -> impl IntoIterator<Item = (&str, &str)> {
         self.locked
@@ -369,4 +406,78 @@ mod test {
         assert!(components.contains("empty"));
         assert!(components.len() == 1);
     }
+
+    #[test]
+    fn component_info_accessors_read_locked_metadata() {
+        let locked: LockedApp = serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 1,
+            "triggers": [],
+            "components": [
+                {
+                    "id": "api",
+                    "metadata": {
+                        "description": "The API",
+                        "source_kind": {
+                            "type": "local",
+                            "path": "target/api.wasm",
+                        },
+                        "build_metadata": {
+                            "built_at": "2024-06-01T12:00:00Z",
+                            "spin_version": "3.4.0",
+                            "commands": ["cargo build --release"],
+                            "digest": "sha256:abc",
+                        },
+                    },
+                    "source": {
+                        "content_type": "application/wasm",
+                        "source": "file:///app/target/api.wasm",
+                        "digest": "sha256:abc",
+                    },
+                },
+                {
+                    "id": "fileserver",
+                    "metadata": {
+                        "source_kind": {
+                            "type": "registry",
+                            "package": "spin:fileserver",
+                            "version": "0.3.0",
+                        },
+                    },
+                    "source": {
+                        "content_type": "application/wasm",
+                        "source": "file:///cache/wasm/sha256:def",
+                        "digest": "sha256:def",
+                    },
+                },
+            ],
+        }))
+        .unwrap();
+        let app = App::new("test-app", locked);
+
+        let api = app.get_component("api").unwrap();
+        assert_eq!(api.description().unwrap().as_deref(), Some("The API"));
+        assert_eq!(api.source_digest(), Some("sha256:abc"));
+        assert_eq!(
+            api.source_kind().unwrap(),
+            Some(ComponentSourceKind::Local {
+                path: "target/api.wasm".into()
+            })
+        );
+        let build = api.build_metadata().unwrap().unwrap();
+        assert_eq!(build.built_at, "2024-06-01T12:00:00Z");
+        assert_eq!(build.commands, ["cargo build --release"]);
+
+        let fileserver = app.get_component("fileserver").unwrap();
+        assert_eq!(fileserver.description().unwrap(), None);
+        assert_eq!(fileserver.source_digest(), Some("sha256:def"));
+        assert_eq!(
+            fileserver.source_kind().unwrap(),
+            Some(ComponentSourceKind::Registry {
+                registry: None,
+                package: "spin:fileserver".into(),
+                version: "0.3.0".into(),
+            })
+        );
+        assert_eq!(fileserver.build_metadata().unwrap(), None);
+    }
 }
@@ -0,0 +1,291 @@
+//! Resolution of the resource limits which apply to component instances.
+//!
+//! Limits come from four places. In order of precedence:
+//!
+//! 1. The operator, e.g. on the command line or in the environment.
+//! 2. The component's own `limits` in the manifest.
+//! 3. The application's `[application.limits]` defaults.
+//! 4. The host's caps, from runtime config.
+//!
+//! The first of 1-3 to set a limit wins. Host caps then clamp the winning
+//! value, so no source can raise a limit above its cap, and a cap applies
+//! on its own if no other source sets the limit.
+
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AppComponent, MetadataKey, Result};
+
+/// MetadataKey for extracting the application's default limits.
+pub const APP_LIMITS_KEY: MetadataKey<Limits> = MetadataKey::new("limits");
+/// MetadataKey for extracting a component's limits.
+pub const COMPONENT_LIMITS_KEY: MetadataKey<Limits> = MetadataKey::new("limits");
+
+/// A set of resource limits from one source, any of which may be unset.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// The maximum memory an instance may allocate, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_bytes: Option<u64>,
+    /// The maximum time an instance may run for, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_execution_time_ms: Option<u64>,
+    /// The maximum fuel an instance may consume.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fuel: Option<u64>,
+    /// The maximum number of instances of a component which may run at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u64>,
+    /// The maximum size of a request body passed to a component, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_request_body_bytes: Option<u64>,
+    /// The maximum size of a response body returned by a component, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_response_body_bytes: Option<u64>,
+}
+
+impl Limits {
+    /// Returns true if no limit is set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn limits(&self) -> impl Iterator<Item = (&'static str, Option<u64>)> {
+        [
+            ("max_memory_bytes", self.max_memory_bytes),
+            ("max_execution_time_ms", self.max_execution_time_ms),
+            ("max_fuel", self.max_fuel),
+            ("max_concurrency", self.max_concurrency),
+            ("max_request_body_bytes", self.max_request_body_bytes),
+            ("max_response_body_bytes", self.max_response_body_bytes),
+        ]
+        .into_iter()
+    }
+}
+
+/// Lists the limits which are set, e.g. `max_memory_bytes = 1024, max_fuel = 10`.
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_limits(f, self.limits())
+    }
+}
+
+/// The limits which apply to instances of a component, as resolved by
+/// [`LimitsResolver`]. A limit which is `None` is unlimited.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EffectiveLimits {
+    /// The maximum memory an instance may allocate, in bytes.
+    pub max_memory_bytes: Option<u64>,
+    /// The maximum time an instance may run for.
+    pub max_execution_time: Option<Duration>,
+    /// The maximum fuel an instance may consume.
+    pub max_fuel: Option<u64>,
+    /// The maximum number of instances which may run at once.
+    pub max_concurrency: Option<u64>,
+    /// The maximum size of a request body passed to the component, in bytes.
+    pub max_request_body_bytes: Option<u64>,
+    /// The maximum size of a response body returned by the component, in bytes.
+    pub max_response_body_bytes: Option<u64>,
+    /// The names of the limits which were lowered to the host's cap, in
+    /// declaration order.
+    pub clamped: Vec<&'static str>,
+}
+
+impl EffectiveLimits {
+    /// Returns true if no limit is set.
+    pub fn is_empty(&self) -> bool {
+        self.limits().all(|(_, value)| value.is_none())
+    }
+
+    fn limits(&self) -> impl Iterator<Item = (&'static str, Option<u64>)> {
+        [
+            ("max_memory_bytes", self.max_memory_bytes),
+            (
+                "max_execution_time_ms",
+                self.max_execution_time
+                    .map(|time| time.as_millis().try_into().unwrap_or(u64::MAX)),
+            ),
+            ("max_fuel", self.max_fuel),
+            ("max_concurrency", self.max_concurrency),
+            ("max_request_body_bytes", self.max_request_body_bytes),
+            ("max_response_body_bytes", self.max_response_body_bytes),
+        ]
+        .into_iter()
+    }
+}
+
+/// Lists the limits which are set, e.g. `max_memory_bytes = 1024, max_fuel = 10`.
+impl fmt::Display for EffectiveLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_limits(f, self.limits())
+    }
+}
+
+fn write_limits(
+    f: &mut fmt::Formatter<'_>,
+    limits: impl Iterator<Item = (&'static str, Option<u64>)>,
+) -> fmt::Result {
+    let mut first = true;
+    for (name, value) in limits {
+        let Some(value) = value else { continue };
+        if !first {
+            f.write_str(", ")?;
+        }
+        first = false;
+        write!(f, "{name} = {value}")?;
+    }
+    Ok(())
+}
+
+/// Resolves the [`EffectiveLimits`] of components from their manifest limits,
+/// the operator's limits and the host's caps.
+///
+/// See the [module documentation](self) for the order of precedence.
+#[derive(Clone, Debug, Default)]
+pub struct LimitsResolver {
+    operator: Limits,
+    host_caps: Limits,
+}
+
+impl LimitsResolver {
+    /// Returns a resolver which applies the given operator limits and host
+    /// caps.
+    pub fn new(operator: Limits, host_caps: Limits) -> Self {
+        Self {
+            operator,
+            host_caps,
+        }
+    }
+
+    /// Resolves the limits of the given component from its own and its app's
+    /// metadata.
+    pub fn resolve(&self, component: &AppComponent) -> Result<EffectiveLimits> {
+        let app_defaults = component.app.get_metadata(APP_LIMITS_KEY)?;
+        let component_limits = component.get_metadata(COMPONENT_LIMITS_KEY)?;
+        Ok(self.resolve_from(
+            &app_defaults.unwrap_or_default(),
+            &component_limits.unwrap_or_default(),
+        ))
+    }
+
+    /// Resolves limits from the given app defaults and component limits.
+    pub fn resolve_from(&self, app_defaults: &Limits, component: &Limits) -> EffectiveLimits {
+        let mut clamped = vec![];
+        let mut resolve = |name: &'static str, get: fn(&Limits) -> Option<u64>| {
+            let requested = get(&self.operator).or(get(component)).or(get(app_defaults));
+            match (requested, get(&self.host_caps)) {
+                (Some(requested), Some(cap)) if requested > cap => {
+                    clamped.push(name);
+                    Some(cap)
+                }
+                (requested, cap) => requested.or(cap),
+            }
+        };
+        let max_memory_bytes = resolve("max_memory_bytes", |l| l.max_memory_bytes);
+        let max_execution_time_ms = resolve("max_execution_time_ms", |l| l.max_execution_time_ms);
+        let max_fuel = resolve("max_fuel", |l| l.max_fuel);
+        let max_concurrency = resolve("max_concurrency", |l| l.max_concurrency);
+        let max_request_body_bytes =
+            resolve("max_request_body_bytes", |l| l.max_request_body_bytes);
+        let max_response_body_bytes =
+            resolve("max_response_body_bytes", |l| l.max_response_body_bytes);
+        EffectiveLimits {
+            max_memory_bytes,
+            max_execution_time: max_execution_time_ms.map(Duration::from_millis),
+            max_fuel,
+            max_concurrency,
+            max_request_body_bytes,
+            max_response_body_bytes,
+            clamped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(bytes: Option<u64>) -> Limits {
+        Limits {
+            max_memory_bytes: bytes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn precedence_matrix() {
+        // (operator, component, app default, host cap, expected, clamped)
+        let cases = [
+            (None, None, None, None, None, false),
+            (None, None, Some(10), None, Some(10), false),
+            (None, Some(20), Some(10), None, Some(20), false),
+            (Some(30), Some(20), Some(10), None, Some(30), false),
+            (Some(30), None, None, None, Some(30), false),
+            (None, None, None, Some(50), Some(50), false),
+            (None, None, Some(10), Some(50), Some(10), false),
+            (None, Some(20), Some(10), Some(50), Some(20), false),
+            (Some(30), Some(20), Some(10), Some(50), Some(30), false),
+            (None, Some(80), Some(10), Some(50), Some(50), true),
+            (None, None, Some(80), Some(50), Some(50), true),
+            (Some(80), Some(20), None, Some(50), Some(50), true),
+            (None, Some(50), None, Some(50), Some(50), false),
+        ];
+        for (operator, component, app, cap, expected, clamped) in cases {
+            let resolver = LimitsResolver::new(memory(operator), memory(cap));
+            let limits = resolver.resolve_from(&memory(app), &memory(component));
+            let case = (operator, component, app, cap);
+            assert_eq!(limits.max_memory_bytes, expected, "{case:?}");
+            assert_eq!(
+                limits.clamped == ["max_memory_bytes"],
+                clamped,
+                "{case:?}: {:?}",
+                limits.clamped
+            );
+        }
+    }
+
+    #[test]
+    fn limits_resolve_independently() {
+        let resolver = LimitsResolver::new(
+            Limits {
+                max_fuel: Some(1000),
+                ..Default::default()
+            },
+            Limits {
+                max_concurrency: Some(4),
+                ..Default::default()
+            },
+        );
+        let app = Limits {
+            max_execution_time_ms: Some(1500),
+            max_concurrency: Some(8),
+            ..Default::default()
+        };
+        let component = Limits {
+            max_request_body_bytes: Some(1024),
+            ..Default::default()
+        };
+        let limits = resolver.resolve_from(&app, &component);
+        assert_eq!(
+            limits,
+            EffectiveLimits {
+                max_execution_time: Some(Duration::from_millis(1500)),
+                max_fuel: Some(1000),
+                max_concurrency: Some(4),
+                max_request_body_bytes: Some(1024),
+                clamped: vec!["max_concurrency"],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            limits.to_string(),
+            "max_execution_time_ms = 1500, max_fuel = 1000, max_concurrency = 4, \
+             max_request_body_bytes = 1024"
+        );
+        assert!(LimitsResolver::default()
+            .resolve_from(&Default::default(), &Default::default())
+            .is_empty());
+    }
+}
@@ -2,11 +2,15 @@
 
 //! A library for building Spin components.
 
+mod logs;
 mod manifest;
+mod metadata;
+mod watch;
 
 use anyhow::{anyhow, bail, Context, Result};
 use manifest::ComponentBuildInfo;
 use spin_common::{paths::parent_dir, ui::quoted_path};
+use spin_manifest::schema::v2;
 use std::{
     collections::HashSet,
     path::{Path, PathBuf},
@@ -15,11 +19,15 @@ use subprocess::{Exec, Redirection};
 
 use crate::manifest::component_build_configs;
 
+pub use logs::{LogCapture, CAPTURE_LOGS_ENV};
+pub use watch::{watch, WatchEvent, WatchOptions};
+
 /// If present, run the build command of each component.
 pub async fn build(
     manifest_file: &Path,
     component_ids: &[String],
     target_checks: TargetChecking,
+    log_capture: LogCapture,
     cache_root: Option<PathBuf>,
 ) -> Result<()> {
     let build_info = component_build_configs(manifest_file)
@@ -32,7 +40,12 @@ pub async fn build(
         })?;
     let app_dir = parent_dir(manifest_file)?;
 
-    let build_result = build_components(component_ids, build_info.components(), &app_dir);
+    let build_result = build_components(
+        component_ids,
+        build_info.components(),
+        &app_dir,
+        log_capture,
+    );
 
     // Emit any required warnings now, so that they don't bury any errors.
     if let Some(e) = build_info.load_error() {
@@ -71,6 +84,7 @@ pub async fn build(
             build_info.deployment_targets(),
             cache_root.clone(),
             &app_dir,
+            target_checks.resolution(),
         )
         .await
         .context("unable to check if the application is compatible with deployment targets")?;
@@ -87,16 +101,25 @@ pub async fn build(
 }
 
 /// Run all component build commands, using the default options (build all
-/// components, perform target checking). We run a "default build" in several
+/// components, perform target checking, capture logs if the
+/// `SPIN_BUILD_CAPTURE_LOGS` environment variable is set). We run a "default build" in several
 /// places and this centralises the logic of what such a "default build" means.
 pub async fn build_default(manifest_file: &Path, cache_root: Option<PathBuf>) -> Result<()> {
-    build(manifest_file, &[], TargetChecking::Check, cache_root).await
+    build(
+        manifest_file,
+        &[],
+        TargetChecking::Check,
+        LogCapture::from_env(),
+        cache_root,
+    )
+    .await
 }
 
 fn build_components(
     component_ids: &[String],
     components: Vec<ComponentBuildInfo>,
     app_dir: &Path,
+    log_capture: LogCapture,
 ) -> Result<(), anyhow::Error> {
     let components_to_build = if component_ids.is_empty() {
         components
@@ -126,7 +149,7 @@ fn build_components(
 
     components_to_build
         .into_iter()
-        .map(|c| build_component(c, app_dir))
+        .map(|c| build_component(c, app_dir, log_capture))
         .collect::<Result<Vec<_>, _>>()?;
 
     terminal::step!("Finished", "building all Spin components");
@@ -134,11 +157,23 @@ fn build_components(
 }
 
 /// Run the build command of the component.
-fn build_component(build_info: ComponentBuildInfo, app_dir: &Path) -> Result<()> {
+fn build_component(
+    build_info: ComponentBuildInfo,
+    app_dir: &Path,
+    log_capture: LogCapture,
+) -> Result<()> {
     match build_info.build {
         Some(b) => {
             let command_count = b.commands().len();
 
+            let log_dir = logs::component_log_dir(app_dir, &build_info.id);
+            if log_capture == LogCapture::Capture && log_dir.exists() {
+                // Don't leave logs of earlier builds' commands lying around
+                std::fs::remove_dir_all(&log_dir).with_context(|| {
+                    format!("Cannot clear build logs in {}", quoted_path(&log_dir))
+                })?;
+            }
+
             if command_count > 1 {
                 terminal::step!(
                     "Building",
@@ -167,31 +202,60 @@ fn build_component(build_info: ComponentBuildInfo, app_dir: &Path) -> Result<()>
                     println!("Working directory: {}", quoted_path(&workdir));
                 }
 
-                let exit_status = Exec::shell(command)
-                    .cwd(workdir)
-                    .stdout(Redirection::None)
-                    .stderr(Redirection::None)
-                    .stdin(Redirection::None)
-                    .popen()
-                    .map_err(|err| {
-                        anyhow!(
-                            "Cannot spawn build process '{:?}' for component {}: {}",
-                            &b.command,
-                            build_info.id,
-                            err
-                        )
-                    })?
-                    .wait()?;
+                let exec = Exec::shell(command).cwd(workdir);
+                let spawn_error = |err| {
+                    anyhow!(
+                        "Cannot spawn build process '{:?}' for component {}: {}",
+                        &b.command,
+                        build_info.id,
+                        err
+                    )
+                };
+
+                let (exit_status, captured) = match log_capture {
+                    LogCapture::Capture => {
+                        let log_path = log_dir.join(logs::log_file_name(index + 1, command));
+                        let captured = logs::run_captured(exec, &log_path)
+                            .map_err(|err| spawn_error(format!("{err:#}")))?;
+                        (captured.exit_status, Some((log_path, captured.tail)))
+                    }
+                    LogCapture::Inherit => {
+                        let exit_status = exec
+                            .stdout(Redirection::None)
+                            .stderr(Redirection::None)
+                            .stdin(Redirection::None)
+                            .popen()
+                            .map_err(|err| spawn_error(err.to_string()))?
+                            .wait()?;
+                        (exit_status, None)
+                    }
+                };
 
                 if !exit_status.success() {
-                    bail!(
+                    let mut message = format!(
                         "Build command for component {} failed with status {:?}",
-                        build_info.id,
-                        exit_status,
+                        build_info.id, exit_status,
                     );
+                    if let Some((log_path, tail)) = captured {
+                        if !tail.is_empty() {
+                            message.push_str(&format!(
+                                "\n\nLast {} lines of output:\n{}",
+                                tail.len(),
+                                tail.join("\n")
+                            ));
+                        }
+                        message
+                            .push_str(&format!("\n\nFull build log: {}", quoted_path(&log_path)));
+                    }
+                    bail!(message);
                 }
             }
 
+            if let Some(v2::ComponentSource::Local(path)) = &build_info.source {
+                let commands = b.commands().cloned().collect::<Vec<_>>();
+                metadata::record(app_dir, &build_info.id, &app_dir.join(path), &commands)?;
+            }
+
             Ok(())
         }
         _ => Ok(()),
@@ -219,6 +283,9 @@ fn construct_workdir(app_dir: &Path, workdir: Option<impl AsRef<Path>>) -> Resul
 pub enum TargetChecking {
     /// The build should check that all components are compatible with all target environments.
     Check,
+    /// As `Check`, but target environments should be resolved afresh rather than
+    /// using the versions pinned in the application's lockfile.
+    Refresh,
     /// The build should not check target environments.
     Skip,
 }
@@ -226,7 +293,15 @@ pub enum TargetChecking {
 impl TargetChecking {
     /// Should the build check target environments?
     fn check(&self) -> bool {
-        matches!(self, Self::Check)
+        matches!(self, Self::Check | Self::Refresh)
+    }
+
+    /// How should the build resolve target environments?
+    fn resolution(&self) -> spin_environments::EnvironmentResolution {
+        match self {
+            Self::Refresh => spin_environments::EnvironmentResolution::Refresh,
+            _ => spin_environments::EnvironmentResolution::Locked,
+        }
     }
 }
 
@@ -234,6 +309,31 @@ impl TargetChecking {
 mod tests {
     use super::*;
 
+    const MANIFEST_WITH_BUILD_COMMAND: &str = r#"
+        spin_manifest_version = 2
+        [application]
+        name = "build-logs"
+        [[trigger.http]]
+        route = "/..."
+        component = "hello"
+        [component.hello]
+        source = "hello.wasm"
+        [component.hello.build]
+        command = "COMMAND"
+    "#;
+
+    /// Writes a manifest whose one component builds with `command`, and runs the build.
+    async fn build_with_command(
+        app_dir: &Path,
+        command: &str,
+        log_capture: LogCapture,
+    ) -> Result<()> {
+        let manifest_file = app_dir.join("spin.toml");
+        let manifest = MANIFEST_WITH_BUILD_COMMAND.replace("COMMAND", command);
+        std::fs::write(&manifest_file, manifest).unwrap();
+        build(&manifest_file, &[], TargetChecking::Skip, log_capture, None).await
+    }
+
     fn test_data_root() -> PathBuf {
         let crate_dir = env!("CARGO_MANIFEST_DIR");
         PathBuf::from(crate_dir).join("tests")
@@ -242,26 +342,44 @@ mod tests {
     #[tokio::test]
     async fn can_load_even_if_trigger_invalid() {
         let bad_trigger_file = test_data_root().join("bad_trigger.toml");
-        build(&bad_trigger_file, &[], TargetChecking::Skip, None)
-            .await
-            .unwrap();
+        build(
+            &bad_trigger_file,
+            &[],
+            TargetChecking::Skip,
+            LogCapture::Inherit,
+            None,
+        )
+        .await
+        .unwrap();
     }
 
     #[tokio::test]
     async fn succeeds_if_target_env_matches() {
         let manifest_path = test_data_root().join("good_target_env.toml");
-        build(&manifest_path, &[], TargetChecking::Check, None)
-            .await
-            .unwrap();
+        build(
+            &manifest_path,
+            &[],
+            TargetChecking::Check,
+            LogCapture::Inherit,
+            None,
+        )
+        .await
+        .unwrap();
     }
 
     #[tokio::test]
     async fn fails_if_target_env_does_not_match() {
         let manifest_path = test_data_root().join("bad_target_env.toml");
-        let err = build(&manifest_path, &[], TargetChecking::Check, None)
-            .await
-            .expect_err("should have failed")
-            .to_string();
+        let err = build(
+            &manifest_path,
+            &[],
+            TargetChecking::Check,
+            LogCapture::Inherit,
+            None,
+        )
+        .await
+        .expect_err("should have failed")
+        .to_string();
 
         // build prints validation errors rather than returning them to top level
         // (because there could be multiple errors) - see has_meaningful_error_if_target_env_does_not_match
@@ -270,6 +388,78 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failed_build_error_includes_output_tail_and_log_path() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let command = "for i in $(seq 1 50); do echo line $i; done; echo oops >&2; exit 3";
+        let err = build_with_command(app_dir.path(), command, LogCapture::Capture)
+            .await
+            .expect_err("build should have failed");
+        let message = format!("{err:#}");
+        // stdout and stderr are read separately, so may be interleaved in any order
+        assert!(message.contains("line 50\n"), "{message}");
+        assert!(message.contains("oops"), "{message}");
+        assert!(!message.contains("line 10\n"), "{message}");
+
+        let log_dir = logs::component_log_dir(app_dir.path(), "hello");
+        let log_path = log_dir.join(logs::log_file_name(1, command));
+        assert!(
+            message.contains(&quoted_path(&log_path).to_string()),
+            "{message}"
+        );
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.starts_with("line 1\nline 2\n"), "{log}");
+        assert_eq!(log.lines().count(), 51);
+    }
+
+    #[tokio::test]
+    async fn successful_build_writes_logs_only_if_captured() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let log_dir = logs::component_log_dir(app_dir.path(), "hello");
+
+        build_with_command(app_dir.path(), "echo built", LogCapture::Inherit)
+            .await
+            .unwrap();
+        assert!(!log_dir.exists());
+
+        build_with_command(app_dir.path(), "echo built", LogCapture::Capture)
+            .await
+            .unwrap();
+        let log = std::fs::read_to_string(log_dir.join("1-echo-built.log")).unwrap();
+        assert_eq!(log.trim(), "built");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn successful_build_records_metadata_of_local_source() {
+        let app_dir = tempfile::tempdir().unwrap();
+        let metadata_path =
+            spin_common::paths::component_build_metadata_path(app_dir.path(), "hello");
+
+        build_with_command(app_dir.path(), "echo built", LogCapture::Inherit)
+            .await
+            .unwrap();
+        assert!(!metadata_path.exists(), "no Wasm file was produced");
+
+        build_with_command(
+            app_dir.path(),
+            "printf spin > hello.wasm",
+            LogCapture::Inherit,
+        )
+        .await
+        .unwrap();
+        let metadata: spin_locked_app::locked::BuildMetadata =
+            serde_json::from_slice(&std::fs::read(&metadata_path).unwrap()).unwrap();
+        assert_eq!(metadata.commands, ["printf spin > hello.wasm"]);
+        assert_eq!(metadata.spin_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            metadata.digest,
+            "sha256:a5a2729ffa0eeacc15323a9168807c72d18d1cb375dbde899c44d6803dad2b19"
+        );
+        chrono::DateTime::parse_from_rfc3339(&metadata.built_at).unwrap();
+    }
+
     #[tokio::test]
     async fn has_meaningful_error_if_target_env_does_not_match() {
         let manifest_file = test_data_root().join("bad_target_env.toml");
@@ -287,6 +477,7 @@ mod tests {
             &manifest.application.targets,
             None,
             manifest_file.parent().unwrap(),
+            spin_environments::EnvironmentResolution::Locked,
         )
         .await
         .context("unable to check if the application is compatible with deployment targets")
@@ -0,0 +1,167 @@
+//! Capturing the output of build commands to log files.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Result};
+use spin_common::ui::quoted_path;
+use subprocess::{Exec, ExitStatus, Redirection};
+
+/// The environment variable which turns on build log capture for builds
+/// which use [`LogCapture::from_env`].
+pub const CAPTURE_LOGS_ENV: &str = "SPIN_BUILD_CAPTURE_LOGS";
+
+/// The number of lines of output included in the error when a build command
+/// fails.
+pub(crate) const FAILURE_TAIL_LINES: usize = 20;
+
+/// Specifies whether the output of build commands is saved to log files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogCapture {
+    /// Output is written to the terminal and also saved to
+    /// `.spin/build-logs/<component>/<n>-<command>.log` in the application
+    /// directory.
+    Capture,
+    /// Output is only written to the terminal.
+    Inherit,
+}
+
+impl LogCapture {
+    /// Captures logs if the `SPIN_BUILD_CAPTURE_LOGS` environment variable is
+    /// set to anything other than an empty string, `0` or `false`.
+    pub fn from_env() -> Self {
+        match std::env::var(CAPTURE_LOGS_ENV) {
+            Ok(value) if !matches!(value.trim(), "" | "0" | "false") => Self::Capture,
+            _ => Self::Inherit,
+        }
+    }
+}
+
+/// Returns the directory in which the logs of a component's build commands
+/// are saved.
+pub(crate) fn component_log_dir(app_dir: &Path, component_id: &str) -> PathBuf {
+    app_dir.join(".spin").join("build-logs").join(component_id)
+}
+
+/// Returns the log file name for the `index`th (1-based) build command.
+pub(crate) fn log_file_name(index: usize, command: &str) -> String {
+    let mut slug = String::new();
+    for c in command.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= 40 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        format!("{index}.log")
+    } else {
+        format!("{index}-{slug}.log")
+    }
+}
+
+/// The output of a build command run by [`run_captured`].
+pub(crate) struct CapturedCommand {
+    pub exit_status: ExitStatus,
+    /// The last [`FAILURE_TAIL_LINES`] lines of output, from stdout and stderr
+    /// in the order they were read.
+    pub tail: Vec<String>,
+}
+
+/// Runs the command, writing its output to the terminal and to the log file
+/// at `log_path`.
+pub(crate) fn run_captured(exec: Exec, log_path: &Path) -> Result<CapturedCommand> {
+    if let Some(dir) = log_path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Cannot create build log directory {}", quoted_path(dir)))?;
+    }
+    let log = File::create(log_path)
+        .with_context(|| format!("Cannot create build log {}", quoted_path(log_path)))?;
+    let log = Arc::new(Mutex::new(log));
+    let tail = Arc::new(Mutex::new(VecDeque::with_capacity(FAILURE_TAIL_LINES)));
+
+    let mut popen = exec
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Pipe)
+        .stdin(Redirection::None)
+        .popen()?;
+
+    // Both pipes must be drained at once, or the command could block writing
+    // to one while we wait on the other
+    let stdout = popen.stdout.take().context("build command has no stdout")?;
+    let stderr = popen.stderr.take().context("build command has no stderr")?;
+    let readers = [
+        spawn_tee(stdout, std::io::stdout, log.clone(), tail.clone()),
+        spawn_tee(stderr, std::io::stderr, log.clone(), tail.clone()),
+    ];
+
+    let exit_status = popen.wait()?;
+    for reader in readers {
+        reader
+            .join()
+            .map_err(|_| anyhow!("build log capture thread panicked"))?
+            .with_context(|| format!("Cannot write build log {}", quoted_path(log_path)))?;
+    }
+
+    let tail = std::mem::take(&mut *tail.lock().unwrap()).into();
+    Ok(CapturedCommand { exit_status, tail })
+}
+
+/// Copies lines from `source` to the terminal and the log, keeping the most
+/// recent lines in `tail`.
+fn spawn_tee<W: Write + 'static>(
+    source: impl Read + Send + 'static,
+    terminal: fn() -> W,
+    log: Arc<Mutex<File>>,
+    tail: Arc<Mutex<VecDeque<String>>>,
+) -> std::thread::JoinHandle<std::io::Result<()>> {
+    std::thread::spawn(move || {
+        let mut source = BufReader::new(source);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            if source.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            // A closed terminal shouldn't stop the log from being written
+            let mut terminal = terminal();
+            _ = terminal.write_all(&line).and_then(|()| terminal.flush());
+            log.lock().unwrap().write_all(&line)?;
+
+            let mut tail = tail.lock().unwrap();
+            if tail.len() == FAILURE_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(String::from_utf8_lossy(&line).trim_end().to_owned());
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_file_names_are_slugs() {
+        assert_eq!(
+            log_file_name(1, "cargo build --release"),
+            "1-cargo-build-release.log"
+        );
+        assert_eq!(
+            log_file_name(2, "  npm run build:prod  "),
+            "2-npm-run-build-prod.log"
+        );
+        assert_eq!(log_file_name(3, "&&"), "3.log");
+        let long = log_file_name(1, &"x".repeat(100));
+        assert_eq!(long.len(), "1-.log".len() + 40);
+    }
+}
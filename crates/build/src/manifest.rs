@@ -108,6 +108,7 @@ fn build_configs_from_manifest(
         .map(|(id, c)| ComponentBuildInfo {
             id: id.to_string(),
             build: c.build.clone(),
+            source: Some(c.source.clone()),
         })
         .collect()
 }
@@ -161,6 +162,10 @@ pub struct ComponentBuildInfo {
     #[serde(default)]
     pub id: String,
     pub build: Option<v2::ComponentBuildConfig>,
+    /// Only known if the manifest loaded, since otherwise the app cannot be
+    /// run and there is no use recording how the component was built.
+    #[serde(skip)]
+    pub source: Option<v2::ComponentSource>,
 }
 
 #[derive(Deserialize)]
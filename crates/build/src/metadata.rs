@@ -0,0 +1,40 @@
+//! Recording how components were built, for the loader to include in the
+//! locked app.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use spin_common::{paths::component_build_metadata_path, sha256, ui::quoted_path};
+use spin_locked_app::locked::BuildMetadata;
+
+/// Records that the component's Wasm file at `wasm_path` was built by
+/// running `commands`. Does nothing if there is no such file, e.g. because
+/// the build commands only prepared for some other step to produce it.
+pub(crate) fn record(
+    app_dir: &Path,
+    component_id: &str,
+    wasm_path: &Path,
+    commands: &[String],
+) -> Result<()> {
+    let digest = match sha256::hex_digest_from_file(wasm_path) {
+        Ok(digest) => format!("sha256:{digest}"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", quoted_path(wasm_path))),
+    };
+    let metadata = BuildMetadata {
+        built_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        spin_version: env!("CARGO_PKG_VERSION").to_owned(),
+        commands: commands.to_vec(),
+        digest,
+    };
+
+    let path = component_build_metadata_path(app_dir, component_id);
+    let write = || -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_vec_pretty(&metadata)?)?;
+        Ok(())
+    };
+    write().with_context(|| format!("Cannot record build metadata in {}", quoted_path(&path)))
+}
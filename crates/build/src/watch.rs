@@ -0,0 +1,454 @@
+//! Watching an application's source files and rebuilding the components
+//! affected by each change.
+
+use anyhow::{anyhow, Context, Result};
+use notify::{RecursiveMode, Watcher};
+use spin_common::{paths::parent_dir, ui::quoted_path};
+use spin_manifest::schema::v2;
+use std::{
+    ops::ControlFlow,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+use crate::manifest::{component_build_configs, ComponentBuildInfo};
+
+/// Options for [`watch`].
+#[derive(Clone, Debug)]
+pub struct WatchOptions {
+    /// How long to wait for further changes after a change is detected
+    /// before rebuilding. A burst of changes, such as an editor saving
+    /// several files, results in a single rebuild.
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Something that happened while watching an application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The watcher is monitoring the application's files.
+    Ready,
+    /// A component's files changed and it is being rebuilt.
+    BuildStarted {
+        /// The ID of the component.
+        component_id: String,
+    },
+    /// A component was rebuilt. A component without a build command
+    /// succeeds immediately, so that callers can still react to its change.
+    BuildSucceeded {
+        /// The ID of the component.
+        component_id: String,
+    },
+    /// A component failed to rebuild.
+    BuildFailed {
+        /// The ID of the component.
+        component_id: String,
+        /// The reason the build failed.
+        error: String,
+    },
+    /// The manifest changed, so the application should be reloaded. The
+    /// files to watch have been updated from the new manifest.
+    ManifestChanged,
+    /// The manifest changed but could not be loaded. The watcher carries on
+    /// with the files from the last good manifest.
+    ManifestError {
+        /// The reason the manifest could not be loaded.
+        error: String,
+    },
+}
+
+/// Watches the files of the application in `manifest_file`, rebuilding only
+/// the components whose files change.
+///
+/// A component's files are those matched by its `build.watch` patterns. If
+/// it has none, its `files` are used, along with its `source` if it has no
+/// build command (for a component with a build command, the source is the
+/// build output, and watching it would rebuild in a loop).
+///
+/// `on_event` is called with each [`WatchEvent`]; the watcher runs until it
+/// returns [`ControlFlow::Break`].
+pub async fn watch(
+    manifest_file: &Path,
+    options: WatchOptions,
+    mut on_event: impl FnMut(WatchEvent) -> ControlFlow<()>,
+) -> Result<()> {
+    let manifest_file = manifest_file
+        .canonicalize()
+        .with_context(|| format!("Cannot find manifest file {}", quoted_path(manifest_file)))?;
+    let app_dir = parent_dir(&manifest_file)?;
+
+    let mut watch_set = WatchSet::load(&manifest_file, &app_dir).await?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if !event.kind.is_access() => {
+                let _ = tx.send(event.paths);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Error watching application files: {e}"),
+        })
+        .context("Cannot create file watcher")?;
+    watch_roots(&mut watcher, &[], &watch_set.roots)?;
+
+    if on_event(WatchEvent::Ready).is_break() {
+        return Ok(());
+    }
+
+    loop {
+        let Some(mut changed) = rx.recv().await else {
+            return Err(anyhow!("File watcher stopped unexpectedly"));
+        };
+        // Wait until the changes have settled down.
+        while let Ok(paths) = tokio::time::timeout(options.debounce, rx.recv()).await {
+            let Some(paths) = paths else {
+                return Err(anyhow!("File watcher stopped unexpectedly"));
+            };
+            changed.extend(paths);
+        }
+
+        if changed.iter().any(|path| path == &manifest_file) {
+            match WatchSet::load(&manifest_file, &app_dir).await {
+                Ok(new_watch_set) => {
+                    watch_roots(&mut watcher, &watch_set.roots, &new_watch_set.roots)?;
+                    watch_set = new_watch_set;
+                    if on_event(WatchEvent::ManifestChanged).is_break() {
+                        return Ok(());
+                    }
+                }
+                Err(e) => {
+                    let error = format!("{e:#}");
+                    if on_event(WatchEvent::ManifestError { error }).is_break() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        for component in watch_set.affected_by(&changed) {
+            let component_id = component.info.id.clone();
+            let event = WatchEvent::BuildStarted {
+                component_id: component_id.clone(),
+            };
+            if on_event(event).is_break() {
+                return Ok(());
+            }
+
+            let info = component.info.clone();
+            let build_dir = app_dir.clone();
+            let result =
+                tokio::task::spawn_blocking(move || crate::build_component(info, &build_dir))
+                    .await
+                    .unwrap_or_else(|e| Err(anyhow!("Build task failed: {e}")));
+            let event = match result {
+                Ok(()) => WatchEvent::BuildSucceeded { component_id },
+                Err(e) => WatchEvent::BuildFailed {
+                    component_id,
+                    error: format!("{e:#}"),
+                },
+            };
+            if on_event(event).is_break() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Starts watching the roots in `new` that are not in `old`, and stops
+/// watching those in `old` that are not in `new`.
+fn watch_roots(watcher: &mut impl Watcher, old: &[PathBuf], new: &[PathBuf]) -> Result<()> {
+    for root in old.iter().filter(|root| !new.contains(root)) {
+        // The root may since have been deleted, so errors are expected here.
+        let _ = watcher.unwatch(root);
+    }
+    for root in new.iter().filter(|root| !old.contains(root)) {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Cannot watch {}", quoted_path(root)))?;
+    }
+    Ok(())
+}
+
+/// The files to watch for each component in an application.
+struct WatchSet {
+    components: Vec<ComponentWatch>,
+    /// The directories to watch: the application directory, and any other
+    /// directories that components' patterns refer to.
+    roots: Vec<PathBuf>,
+}
+
+struct ComponentWatch {
+    info: ComponentBuildInfo,
+    patterns: Vec<glob::Pattern>,
+}
+
+impl WatchSet {
+    async fn load(manifest_file: &Path, app_dir: &Path) -> Result<Self> {
+        let build_info = component_build_configs(manifest_file).await?;
+        let manifest = match (build_info.manifest(), build_info.load_error()) {
+            (Some(manifest), _) => manifest,
+            (None, Some(e)) => return Err(anyhow!("{e}")),
+            (None, None) => unreachable!("unloadable manifest must have a load error"),
+        };
+
+        let mut roots = vec![app_dir.to_owned()];
+        let mut components = vec![];
+        for (id, component) in &manifest.components {
+            let info = ComponentBuildInfo {
+                id: id.to_string(),
+                build: component.build.clone(),
+            };
+            let mut patterns = vec![];
+            for glob in component_globs(component) {
+                let glob = normalize_lexically(&app_dir.join(glob));
+                let root = literal_prefix(&glob);
+                if !roots.iter().any(|r| root.starts_with(r)) {
+                    roots.push(root);
+                }
+                let pattern = glob::Pattern::new(&glob.to_string_lossy()).with_context(|| {
+                    format!(
+                        "Invalid watch pattern {} for component {}",
+                        quoted_path(&glob),
+                        info.id
+                    )
+                })?;
+                patterns.push(pattern);
+            }
+            components.push(ComponentWatch { info, patterns });
+        }
+
+        Ok(Self { components, roots })
+    }
+
+    /// The components with files among `paths`, in manifest order.
+    fn affected_by<'a>(&'a self, paths: &'a [PathBuf]) -> impl Iterator<Item = &'a ComponentWatch> {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        self.components.iter().filter(move |component| {
+            paths.iter().any(|path| {
+                component
+                    .patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_path_with(path, options))
+            })
+        })
+    }
+}
+
+/// The glob patterns, relative to the application directory, of the files to
+/// watch for a component.
+fn component_globs(component: &v2::Component) -> Vec<String> {
+    if let Some(build) = &component.build {
+        if !build.watch.is_empty() {
+            let workdir = Path::new(build.workdir.as_deref().unwrap_or_default());
+            return build
+                .watch
+                .iter()
+                .map(|glob| workdir.join(glob).to_string_lossy().into_owned())
+                .collect();
+        }
+    }
+
+    let source = match (&component.source, &component.build) {
+        (v2::ComponentSource::Local(path), None) => Some(path.clone()),
+        _ => None,
+    };
+    let files = component.files.iter().map(|files| match files {
+        v2::WasiFilesMount::Placement { source, .. } => Path::new(source)
+            .join("**/*")
+            .to_string_lossy()
+            .into_owned(),
+        v2::WasiFilesMount::Pattern(pattern) => pattern.clone(),
+    });
+    source.into_iter().chain(files).collect()
+}
+
+/// Resolves `.` and `..` in a path without touching the file system, so that
+/// patterns can be compared with the paths reported by the watcher.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// The directory containing everything a pattern can match: the part of the
+/// pattern before the first wildcard, or its nearest existing ancestor.
+fn literal_prefix(pattern: &Path) -> PathBuf {
+    let mut prefix = PathBuf::new();
+    for component in pattern.components() {
+        if component
+            .as_os_str()
+            .to_string_lossy()
+            .contains(['*', '?', '[', '{'])
+        {
+            break;
+        }
+        prefix.push(component);
+    }
+    while !prefix.is_dir() {
+        if !prefix.pop() {
+            break;
+        }
+    }
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+spin_manifest_version = 2
+
+[application]
+name = "watch-test"
+
+[component.first]
+source = "first.wasm"
+build = { command = "echo building first", watch = ["first/**/*.txt"] }
+
+[component.second]
+source = "second.wasm"
+build = { command = "echo building second", workdir = "second", watch = ["*.txt"] }
+"#;
+
+    struct TestWatch {
+        dir: tempfile::TempDir,
+        events: mpsc::UnboundedReceiver<WatchEvent>,
+        task: tokio::task::JoinHandle<Result<()>>,
+    }
+
+    impl TestWatch {
+        async fn start(debounce: Duration) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir_all(dir.path().join("first")).unwrap();
+            std::fs::create_dir_all(dir.path().join("second")).unwrap();
+            std::fs::write(dir.path().join("spin.toml"), MANIFEST).unwrap();
+
+            let (tx, events) = mpsc::unbounded_channel();
+            let manifest_file = dir.path().join("spin.toml");
+            let task = tokio::spawn(async move {
+                watch(
+                    &manifest_file,
+                    WatchOptions { debounce },
+                    move |event| match tx.send(event) {
+                        Ok(()) => ControlFlow::Continue(()),
+                        Err(_) => ControlFlow::Break(()),
+                    },
+                )
+                .await
+            });
+
+            let mut test = Self { dir, events, task };
+            assert_eq!(test.next().await, Some(WatchEvent::Ready));
+            test
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            std::fs::write(self.dir.path().join(path), contents).unwrap();
+        }
+
+        async fn next(&mut self) -> Option<WatchEvent> {
+            tokio::time::timeout(Duration::from_secs(10), self.events.recv())
+                .await
+                .ok()
+                .flatten()
+        }
+
+        /// Returns the events which arrive until things go quiet.
+        async fn drain(&mut self) -> Vec<WatchEvent> {
+            let mut events = vec![];
+            while let Ok(Some(event)) =
+                tokio::time::timeout(Duration::from_secs(1), self.events.recv()).await
+            {
+                events.push(event);
+            }
+            events
+        }
+    }
+
+    impl Drop for TestWatch {
+        fn drop(&mut self) {
+            self.task.abort();
+        }
+    }
+
+    fn started(component_id: &str) -> WatchEvent {
+        WatchEvent::BuildStarted {
+            component_id: component_id.into(),
+        }
+    }
+
+    fn succeeded(component_id: &str) -> WatchEvent {
+        WatchEvent::BuildSucceeded {
+            component_id: component_id.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn change_rebuilds_only_affected_component() {
+        let mut test = TestWatch::start(Duration::from_millis(100)).await;
+
+        test.write("first/notes.txt", "hello");
+        assert_eq!(test.drain().await, [started("first"), succeeded("first")]);
+
+        test.write("second/notes.txt", "hello");
+        assert_eq!(test.drain().await, [started("second"), succeeded("second")]);
+
+        // Files outside the watch patterns are ignored
+        test.write("first/notes.md", "hello");
+        assert!(test.drain().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rapid_changes_are_debounced() {
+        let mut test = TestWatch::start(Duration::from_millis(500)).await;
+
+        for i in 0..5 {
+            test.write("first/notes.txt", &format!("edit {i}"));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(test.drain().await, [started("first"), succeeded("first")]);
+    }
+
+    #[tokio::test]
+    async fn manifest_change_reloads() {
+        let mut test = TestWatch::start(Duration::from_millis(100)).await;
+
+        // An invalid manifest is reported but doesn't stop the watcher
+        test.write("spin.toml", "spin_manifest_version = 2\n[application");
+        assert!(matches!(
+            test.drain().await.as_slice(),
+            [WatchEvent::ManifestError { .. }]
+        ));
+        test.write("first/notes.txt", "hello");
+        assert_eq!(test.drain().await, [started("first"), succeeded("first")]);
+
+        // A valid manifest is reloaded, and its watch patterns take effect
+        test.write(
+            "spin.toml",
+            &MANIFEST.replace("first/**/*.txt", "first/**/*.md"),
+        );
+        assert_eq!(test.drain().await, [WatchEvent::ManifestChanged]);
+        test.write("first/notes.md", "hello");
+        assert_eq!(test.drain().await, [started("first"), succeeded("first")]);
+    }
+}
@@ -103,6 +103,16 @@ pub fn parent_dir(path: impl AsRef<Path>) -> Result<PathBuf> {
     Ok(parent.into())
 }
 
+/// The path of the file in which `spin build` records how it last built the
+/// given component of the application in `app_dir`, for the loader to include
+/// in the locked app.
+pub fn component_build_metadata_path(app_dir: &Path, component_id: &str) -> PathBuf {
+    app_dir
+        .join(".spin")
+        .join("build-metadata")
+        .join(format!("{component_id}.json"))
+}
+
 fn is_git_root(dir: &Path) -> bool {
     dir.join(".git").is_dir()
 }
@@ -25,6 +25,7 @@ pub use wasmtime::{
     Instance as ModuleInstance, Module, Trap,
 };
 
+pub use limits::MemoryUsage;
 pub use store::{AsState, Store, StoreBuilder};
 
 /// The default [`EngineBuilder::epoch_tick_interval`].
@@ -38,6 +39,7 @@ const GB: usize = 1 << 30;
 /// This is currently only used for advanced (undocumented) use cases.
 pub struct Config {
     inner: wasmtime::Config,
+    consume_fuel: bool,
 }
 
 impl Config {
@@ -61,6 +63,15 @@ impl Config {
         Ok(())
     }
 
+    /// Enables fuel metering, so that the fuel an instance may consume can be
+    /// limited with `set_fuel` on its store. Metering slows execution down,
+    /// so is disabled by default.
+    pub fn consume_fuel(&mut self, enable: bool) -> &mut Self {
+        self.inner.consume_fuel(enable);
+        self.consume_fuel = enable;
+        self
+    }
+
     /// Disable the pooling instance allocator.
     pub fn disable_pooling(&mut self) -> &mut Self {
         self.inner
@@ -174,7 +185,10 @@ impl Default for Config {
             inner.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling_config));
         }
 
-        return Self { inner };
+        return Self {
+            inner,
+            consume_fuel: false,
+        };
 
         fn env<T>(name: &str, default: T) -> T
         where
@@ -260,13 +274,49 @@ impl State {
     pub fn memory_consumed(&self) -> u64 {
         self.store_limits.memory_consumed()
     }
+
+    /// Whether an instance in the store has been denied memory because it
+    /// would exceed the [`StoreBuilder::max_memory_size`].
+    pub fn memory_limit_exceeded(&self) -> bool {
+        self.store_limits.memory_limit_exceeded()
+    }
+
+    /// The memory use of instances in the store so far.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.store_limits.memory_usage()
+    }
+
+    /// Describes how an instance exceeded the [`StoreBuilder::max_memory_size`],
+    /// if it did, e.g. `component "x" exceeded max_instance_memory of 64 MiB;
+    /// peak requested 81 MiB`.
+    pub fn memory_limit_message(&self) -> Option<String> {
+        self.store_limits.memory_limit_message()
+    }
+}
+
+/// An error indicating that execution failed after an instance exceeded a
+/// resource limit, such as the [`StoreBuilder::max_memory_size`].
+///
+/// Guests usually fail in their own way when denied a resource (e.g. by
+/// trapping or exiting), so executors attach this to the resulting error
+/// (e.g. with [`anyhow::Context`]) when [`State::memory_limit_exceeded`].
+#[derive(Debug)]
+pub struct ResourceLimitExceeded;
+
+impl std::fmt::Display for ResourceLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("instance resource limit exceeded")
+    }
 }
 
+impl std::error::Error for ResourceLimitExceeded {}
+
 /// A builder interface for configuring a new [`Engine`].
 ///
 /// A new [`EngineBuilder`] can be obtained with [`Engine::builder`].
 pub struct EngineBuilder<T: 'static> {
     engine: wasmtime::Engine,
+    consume_fuel: bool,
     linker: Linker<T>,
     epoch_tick_interval: Duration,
     epoch_ticker_thread: bool,
@@ -278,6 +328,7 @@ impl<T: 'static> EngineBuilder<T> {
         let linker: Linker<T> = Linker::new(&engine);
         Ok(Self {
             engine,
+            consume_fuel: config.consume_fuel,
             linker,
             epoch_tick_interval: DEFAULT_EPOCH_TICK_INTERVAL,
             epoch_ticker_thread: true,
@@ -331,6 +382,7 @@ impl<T: 'static> EngineBuilder<T> {
         self.maybe_spawn_epoch_ticker();
         Engine {
             inner: self.engine,
+            consume_fuel: self.consume_fuel,
             linker: self.linker,
             epoch_tick_interval: self.epoch_tick_interval,
         }
@@ -341,6 +393,7 @@ impl<T: 'static> EngineBuilder<T> {
 /// Spin components.
 pub struct Engine<T: 'static> {
     inner: wasmtime::Engine,
+    consume_fuel: bool,
     linker: Linker<T>,
     epoch_tick_interval: Duration,
 }
@@ -351,6 +404,11 @@ impl<T: 'static> Engine<T> {
         EngineBuilder::new(config)
     }
 
+    /// Returns whether the engine meters fuel; see [`Config::consume_fuel`].
+    pub fn consumes_fuel(&self) -> bool {
+        self.consume_fuel
+    }
+
     /// Creates a new [`StoreBuilder`].
     pub fn store_builder(&self) -> StoreBuilder {
         StoreBuilder::new(self.inner.clone(), self.epoch_tick_interval)
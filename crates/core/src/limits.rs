@@ -3,12 +3,38 @@ use async_trait::async_trait;
 use wasmtime::ResourceLimiterAsync;
 
 /// Async implementation of wasmtime's `StoreLimits`: https://github.com/bytecodealliance/wasmtime/blob/main/crates/wasmtime/src/limits.rs
-/// Used to limit the memory use and table size of each Instance
+/// Used to limit the memory use and table size of each Instance, and to track
+/// its memory use.
 #[derive(Default)]
 pub struct StoreLimitsAsync {
-    max_memory_size: Option<usize>,
+    pub(crate) max_memory_size: Option<usize>,
     max_table_elements: Option<usize>,
+    /// Warn when memory use reaches this percentage of the `max_memory_size`.
+    pub(crate) memory_warning_percent: Option<u8>,
+    /// Identifies the instance in warnings and errors.
+    pub(crate) component_id: Option<String>,
     memory_consumed: u64,
+    memory_peak: u64,
+    memory_grow_failures: u32,
+    memory_peak_requested: Option<usize>,
+    memory_limit_exceeded: bool,
+    memory_warning_emitted: bool,
+}
+
+/// The linear memory use of the instances in a [`crate::Store`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The memory currently allocated, in bytes.
+    pub consumed: u64,
+    /// The most memory allocated at any one time, in bytes.
+    pub peak: u64,
+    /// The number of requests to grow memory which were denied because they
+    /// would exceed the maximum memory size.
+    pub grow_failures: u32,
+    /// The largest memory size requested by a denied request, in bytes.
+    pub peak_requested: Option<u64>,
+    /// The maximum memory size, in bytes, if limited.
+    pub limit: Option<u64>,
 }
 
 #[async_trait]
@@ -27,9 +53,15 @@ impl ResourceLimiterAsync for StoreLimitsAsync {
         if can_grow {
             self.memory_consumed =
                 (self.memory_consumed as i64 + (desired as i64 - current as i64)) as u64;
+            self.memory_peak = self.memory_peak.max(self.memory_consumed);
+            self.check_memory_warning();
         } else {
+            self.memory_limit_exceeded = true;
+            self.memory_grow_failures += 1;
+            self.memory_peak_requested = self.memory_peak_requested.max(Some(desired));
             tracing::warn!(
                 "error.type" = "memory_limit_exceeded",
+                component_id = self.component_id.as_deref(),
                 current,
                 desired,
                 maximum,
@@ -56,18 +88,85 @@ impl ResourceLimiterAsync for StoreLimitsAsync {
 }
 
 impl StoreLimitsAsync {
-    pub fn new(max_memory_size: Option<usize>, max_table_elements: Option<usize>) -> Self {
-        Self {
-            max_memory_size,
-            max_table_elements,
-            memory_consumed: 0,
-        }
-    }
-
     /// How much memory has been consumed in bytes
     pub fn memory_consumed(&self) -> u64 {
         self.memory_consumed
     }
+
+    /// Whether a request to grow memory has been denied because it would
+    /// exceed the maximum memory size
+    pub fn memory_limit_exceeded(&self) -> bool {
+        self.memory_limit_exceeded
+    }
+
+    /// The memory use so far.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            consumed: self.memory_consumed,
+            peak: self.memory_peak,
+            grow_failures: self.memory_grow_failures,
+            peak_requested: self.memory_peak_requested.map(|size| size as u64),
+            limit: self.max_memory_size.map(|size| size as u64),
+        }
+    }
+
+    /// Describes the denied request to grow memory, if any.
+    pub fn memory_limit_message(&self) -> Option<String> {
+        let limit = self
+            .max_memory_size
+            .filter(|_| self.memory_limit_exceeded)?;
+        let mut message = format!(
+            "{} exceeded max_instance_memory of {}",
+            self.instance_name(),
+            format_mib(limit as u64)
+        );
+        if let Some(requested) = self.memory_peak_requested {
+            message.push_str(&format!(
+                "; peak requested {}",
+                format_mib(requested as u64)
+            ));
+        }
+        Some(message)
+    }
+
+    /// Warns the first time memory use reaches the warning threshold.
+    fn check_memory_warning(&mut self) {
+        let (Some(limit), Some(percent)) = (self.max_memory_size, self.memory_warning_percent)
+        else {
+            return;
+        };
+        let threshold = limit as u64 * percent as u64 / 100;
+        if self.memory_warning_emitted || self.memory_consumed < threshold {
+            return;
+        }
+        self.memory_warning_emitted = true;
+        tracing::warn!(
+            component_id = self.component_id.as_deref(),
+            memory_consumed = self.memory_consumed,
+            max_memory_size = limit,
+            "{} is using {} of its max_instance_memory of {} (warning threshold {percent}%)",
+            self.instance_name(),
+            format_mib(self.memory_consumed),
+            format_mib(limit as u64),
+        );
+    }
+
+    fn instance_name(&self) -> String {
+        match &self.component_id {
+            Some(id) => format!("component {id:?}"),
+            None => "instance".into(),
+        }
+    }
+}
+
+/// Formats a number of bytes in MiB, e.g. `64 MiB` or `80.5 MiB`.
+fn format_mib(bytes: u64) -> String {
+    let mib = bytes as f64 / (1 << 20) as f64;
+    if mib.fract() == 0.0 {
+        format!("{mib} MiB")
+    } else {
+        format!("{mib:.1} MiB")
+    }
 }
 
 #[cfg(test)]
@@ -82,8 +181,52 @@ mod tests {
         };
         assert!(limits.memory_growing(0, 65536, None).await.unwrap());
         assert_eq!(limits.memory_consumed, 65536);
+        assert!(!limits.memory_limit_exceeded());
         assert!(!limits.memory_growing(65536, 131072, None).await.unwrap());
         assert_eq!(limits.memory_consumed, 65536);
+        assert!(limits.memory_limit_exceeded());
+    }
+
+    #[tokio::test]
+    async fn test_store_limits_memory_usage() {
+        const MIB: usize = 1 << 20;
+        let mut limits = StoreLimitsAsync {
+            max_memory_size: Some(64 * MIB),
+            memory_warning_percent: Some(75),
+            component_id: Some("hungry".into()),
+            ..Default::default()
+        };
+        assert!(limits.memory_growing(0, 40 * MIB, None).await.unwrap());
+        assert!(!limits.memory_warning_emitted);
+        assert!(limits
+            .memory_growing(40 * MIB, 48 * MIB, None)
+            .await
+            .unwrap());
+        assert!(limits.memory_warning_emitted);
+        assert_eq!(limits.memory_limit_message(), None);
+
+        assert!(!limits
+            .memory_growing(48 * MIB, 81 * MIB, None)
+            .await
+            .unwrap());
+        assert!(!limits
+            .memory_growing(48 * MIB, 72 * MIB, None)
+            .await
+            .unwrap());
+        assert_eq!(
+            limits.memory_usage(),
+            MemoryUsage {
+                consumed: 48 * MIB as u64,
+                peak: 48 * MIB as u64,
+                grow_failures: 2,
+                peak_requested: Some(81 * MIB as u64),
+                limit: Some(64 * MIB as u64),
+            }
+        );
+        assert_eq!(
+            limits.memory_limit_message().unwrap(),
+            "component \"hungry\" exceeded max_instance_memory of 64 MiB; peak requested 81 MiB"
+        );
     }
 
     #[tokio::test]
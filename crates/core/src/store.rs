@@ -100,7 +100,22 @@ impl StoreBuilder {
     /// See [`wasmtime::ResourceLimiter::memory_growing`] (`maximum`) for
     /// details on how this limit is enforced.
     pub fn max_memory_size(&mut self, max_memory_size: usize) {
-        self.store_limits = StoreLimitsAsync::new(Some(max_memory_size), None);
+        self.store_limits.max_memory_size = Some(max_memory_size);
+    }
+
+    /// Sets a warning threshold as a percentage of the
+    /// [`StoreBuilder::max_memory_size`].
+    ///
+    /// A warning is logged the first time an instance's memory use reaches
+    /// the threshold.
+    pub fn memory_warning_threshold(&mut self, percent: u8) {
+        self.store_limits.memory_warning_percent = Some(percent);
+    }
+
+    /// Sets the ID of the component being instantiated, which identifies the
+    /// instance in memory warnings and errors.
+    pub fn component_id(&mut self, component_id: impl Into<String>) {
+        self.store_limits.component_id = Some(component_id.into());
     }
 
     /// Builds a [`Store`] from this builder with given host state data.
@@ -17,16 +17,19 @@ fn main() -> Result {
             std::io::copy(&mut std::io::stdin(), &mut std::io::stdout())?;
         }
         "alloc" => {
-            let size: usize = args.next().expect("size").parse().expect("size");
-            eprintln!("alloc {size}");
-            let layout = std::alloc::Layout::from_size_align(size, 8).expect("layout");
-            unsafe {
-                let p = std::alloc::alloc(layout);
-                if p.is_null() {
-                    return Err("allocation failed".into());
+            // Each size is allocated in turn, without freeing earlier allocations
+            let sizes = args.map(|size| size.parse::<usize>().expect("size"));
+            for size in sizes {
+                eprintln!("alloc {size}");
+                let layout = std::alloc::Layout::from_size_align(size, 8).expect("layout");
+                unsafe {
+                    let p = std::alloc::alloc(layout);
+                    if p.is_null() {
+                        return Err("allocation failed".into());
+                    }
+                    // Force allocation to actually happen
+                    p.read_volatile();
                 }
-                // Force allocation to actually happen
-                p.read_volatile();
             }
         }
         "read" => {
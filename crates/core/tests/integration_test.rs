@@ -1,5 +1,6 @@
 use std::{
     path::PathBuf,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
@@ -10,6 +11,11 @@ use spin_factor_wasi::{DummyFilesMounter, WasiFactor};
 use spin_factors::{App, AsInstanceState, RuntimeFactors};
 use spin_locked_app::locked::LockedApp;
 use tokio::{fs, io::AsyncWrite};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context as LayerContext, prelude::*, Layer};
 use wasmtime_wasi::I32Exit;
 
 #[tokio::test(flavor = "multi_thread")]
@@ -47,6 +53,80 @@ async fn test_max_memory_size_violated() {
     assert_eq!(trap.0, 1);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_memory_warning_then_limit_exceeded() {
+    let warnings = CapturedWarnings::default();
+    let _guard = tracing_subscriber::registry()
+        .with(warnings.clone())
+        .set_default();
+
+    let max = 10 << 20;
+    let (store, result) = run_test_with_store(
+        ["alloc", &format!("{}", 6 << 20), &format!("{}", 20 << 20)],
+        |store_builder| {
+            store_builder.max_memory_size(max);
+            store_builder.memory_warning_threshold(50);
+            store_builder.component_id("test-component");
+        },
+        |_| {},
+    )
+    .await
+    .unwrap();
+    result.unwrap_err();
+
+    let warnings = warnings.messages();
+    let threshold_warnings = warnings
+        .iter()
+        .filter(|message| message.contains("warning threshold 50%"))
+        .collect::<Vec<_>>();
+    assert_eq!(threshold_warnings.len(), 1, "warnings: {warnings:?}");
+    assert!(
+        threshold_warnings[0].starts_with("component \"test-component\" is using"),
+        "unexpected warning: {}",
+        threshold_warnings[0]
+    );
+
+    let state = &store.data().core;
+    assert!(state.memory_limit_exceeded());
+    let usage = state.memory_usage();
+    assert!(usage.grow_failures > 0);
+    assert!(usage.peak >= 6 << 20 && usage.peak <= max as u64);
+    let message = state.memory_limit_message().unwrap();
+    assert!(
+        message.starts_with(
+            "component \"test-component\" exceeded max_instance_memory of 10 MiB; peak requested "
+        ),
+        "unexpected message: {message}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_memory_usage_reports_peak() {
+    let alloc = 1 << 20;
+    let (store, result) = run_test_with_store(
+        ["alloc", &format!("{alloc}")],
+        |store_builder| {
+            store_builder.max_memory_size(10 << 20);
+        },
+        |_| {},
+    )
+    .await
+    .unwrap();
+    result.unwrap();
+
+    let usage = store.data().core.memory_usage();
+    assert!(usage.peak >= alloc, "peak {} below allocation", usage.peak);
+    assert!(
+        usage.peak < 4 << 20,
+        "peak {} unexpectedly large",
+        usage.peak
+    );
+    assert!(usage.consumed <= usage.peak);
+    assert_eq!(usage.grow_failures, 0);
+    assert_eq!(usage.peak_requested, None);
+    assert_eq!(usage.limit, Some(10 << 20));
+}
+
 // FIXME: racy timing test
 #[tokio::test(flavor = "multi_thread")]
 async fn test_set_deadline_obeyed() {
@@ -110,6 +190,17 @@ async fn run_test(
     update_store_builder: impl FnOnce(&mut StoreBuilder),
     update_store: impl FnOnce(&mut Store<TestState>),
 ) -> anyhow::Result<()> {
+    let (_store, result) = run_test_with_store(args, update_store_builder, update_store).await?;
+    result
+}
+
+/// Like [`run_test`], but also returns the store so that its state can be
+/// inspected. Setup errors are returned as the outer error.
+async fn run_test_with_store(
+    args: impl IntoIterator<Item = &'_ str>,
+    update_store_builder: impl FnOnce(&mut StoreBuilder),
+    update_store: impl FnOnce(&mut Store<TestState>),
+) -> anyhow::Result<(Store<TestState>, anyhow::Result<()>)> {
     let mut factors = TestFactors {
         wasi: WasiFactor::new(DummyFilesMounter),
     };
@@ -164,10 +255,37 @@ async fn run_test(
         instance.get_typed_func::<(), (Result<(), ()>,)>(&mut store, &func)?
     };
 
-    func.call_async(&mut store, ())
-        .await?
-        .0
-        .map_err(|()| anyhow::anyhow!("command failed"))
+    let result = match func.call_async(&mut store, ()).await {
+        Ok((result,)) => result.map_err(|()| anyhow::anyhow!("command failed")),
+        Err(err) => Err(err),
+    };
+    Ok((store, result))
+}
+
+/// A tracing layer which captures the messages of `WARN` events.
+#[derive(Clone, Default)]
+struct CapturedWarnings(Arc<Mutex<Vec<String>>>);
+
+impl CapturedWarnings {
+    fn messages(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Visit for CapturedWarnings {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0.lock().unwrap().push(format!("{value:?}"));
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for CapturedWarnings {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        if *event.metadata().level() == Level::WARN {
+            event.record(&mut self.clone());
+        }
+    }
 }
 
 // Write with `print!`, required for test output capture
@@ -11,6 +11,8 @@ use crate::Treatment;
 pub mod trigger;
 /// Diagnose old app manifest versions.
 pub mod upgrade;
+/// Diagnose app manifest variable grant problems.
+pub mod variables;
 /// Diagnose upgradable app manifest versions.
 pub mod version;
 
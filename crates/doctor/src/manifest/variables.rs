@@ -0,0 +1,145 @@
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use spin_expressions::Template;
+use spin_manifest::{schema::v2::AppManifest, ManifestVersion};
+use toml_edit::{de::from_document, Array, DocumentMut, Item};
+
+use crate::{Diagnosis, Diagnostic, PatientApp, Treatment};
+
+use super::ManifestTreatment;
+
+const ALLOWED_APPLICATION_VARIABLES: &str = "allowed_application_variables";
+
+/// SecretVariablesDiagnostic detects components whose variables refer to
+/// secret application variables without an `allowed_application_variables`
+/// grant.
+#[derive(Default)]
+pub struct SecretVariablesDiagnostic;
+
+#[async_trait]
+impl Diagnostic for SecretVariablesDiagnostic {
+    type Diagnosis = SecretVariablesDiagnosis;
+
+    async fn diagnose(&self, patient: &PatientApp) -> Result<Vec<Self::Diagnosis>> {
+        if ManifestVersion::detect(&patient.manifest_doc.to_string())? != ManifestVersion::V2 {
+            return Ok(vec![]);
+        }
+        let manifest: AppManifest =
+            from_document(patient.manifest_doc.clone()).context("failed to decode manifest")?;
+
+        let mut diags = vec![];
+        for (id, component) in &manifest.components {
+            // Components with grants are checked when the app is loaded
+            if component.allowed_application_variables.is_some() {
+                continue;
+            }
+            let referenced: BTreeSet<String> = component
+                .variables
+                .values()
+                .filter_map(|value| Template::new(value.as_str()).ok())
+                .flat_map(|template| template.variables().map(str::to_owned).collect::<Vec<_>>())
+                .collect();
+            let secrets: Vec<String> = referenced
+                .iter()
+                .filter(|name| {
+                    manifest
+                        .variables
+                        .iter()
+                        .any(|(var_name, var)| var_name.as_ref() == name.as_str() && var.secret)
+                })
+                .cloned()
+                .collect();
+            if !secrets.is_empty() {
+                diags.push(SecretVariablesDiagnosis {
+                    component_id: id.to_string(),
+                    secrets,
+                    referenced: referenced.into_iter().collect(),
+                });
+            }
+        }
+        Ok(diags)
+    }
+}
+
+/// SecretVariablesDiagnosis represents a component which refers to secret
+/// application variables without a grant.
+#[derive(Debug)]
+pub struct SecretVariablesDiagnosis {
+    component_id: String,
+    /// The secret application variables the component refers to.
+    secrets: Vec<String>,
+    /// All the application variables the component refers to.
+    referenced: Vec<String>,
+}
+
+impl Diagnosis for SecretVariablesDiagnosis {
+    fn description(&self) -> String {
+        format!(
+            "Component '{}' refers to secret variable(s) {} without granting them in '{ALLOWED_APPLICATION_VARIABLES}'",
+            self.component_id,
+            quoted_list(&self.secrets),
+        )
+    }
+
+    fn is_critical(&self) -> bool {
+        false
+    }
+
+    fn treatment(&self) -> Option<&dyn Treatment> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl ManifestTreatment for SecretVariablesDiagnosis {
+    fn summary(&self) -> String {
+        format!(
+            "Grant component '{}' only the application variables it refers to",
+            self.component_id
+        )
+    }
+
+    async fn treat_manifest(&self, doc: &mut DocumentMut) -> Result<()> {
+        let component = doc
+            .get_mut("component")
+            .and_then(|components| components.get_mut(&self.component_id))
+            .and_then(Item::as_table_like_mut)
+            .with_context(|| format!("component '{}' not found", self.component_id))?;
+        component.insert(
+            ALLOWED_APPLICATION_VARIABLES,
+            toml_edit::value(Array::from_iter(self.referenced.iter().map(String::as_str))),
+        );
+        Ok(())
+    }
+}
+
+fn quoted_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| format!("'{name}'"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{run_broken_test, run_correct_test};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_correct() {
+        run_correct_test::<SecretVariablesDiagnostic>("manifest_variables").await;
+    }
+
+    #[tokio::test]
+    async fn test_ungranted_secret() {
+        let diag =
+            run_broken_test::<SecretVariablesDiagnostic>("manifest_variables", "ungranted_secret")
+                .await;
+        assert_eq!(diag.component_id, "web");
+        assert_eq!(diag.secrets, ["admin_token"]);
+    }
+}
@@ -6,8 +6,11 @@ use spin_manifest::schema::v2::TargetEnvironmentRef;
 
 mod definition;
 mod env_loader;
+mod local;
 mod lockfile;
 
+pub use local::{register_local_trigger, LOCAL_ENVIRONMENT_ID};
+
 use definition::WorldName;
 
 /// A fully realised deployment environment, e.g. Spin 2.7,
@@ -27,14 +30,16 @@ impl TargetEnvironment {
     /// Loads the specified list of environments. This fetches all required
     /// environment definitions from their references, and then chases packages
     /// references until the entire target environment is fully loaded.
-    /// The function also caches registry references in the application directory,
-    /// to avoid loading from the network when the app is validated again.
+    /// The function also pins the resolved environments in a lockfile in the
+    /// application directory, so that the app is validated against the same
+    /// content (and without loading from the network) when it is validated again.
     pub async fn load_all(
         env_ids: &[TargetEnvironmentRef],
         cache_root: Option<std::path::PathBuf>,
         app_dir: &std::path::Path,
+        resolution: EnvironmentResolution,
     ) -> anyhow::Result<Vec<Self>> {
-        env_loader::load_environments(env_ids, cache_root, app_dir).await
+        env_loader::load_environments(env_ids, cache_root, app_dir, resolution).await
     }
 
     /// The environment name for UI purposes
@@ -63,6 +68,18 @@ impl TargetEnvironment {
     }
 }
 
+/// Whether to use the environment content pinned in the application's lockfile,
+/// or to resolve the environments afresh.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnvironmentResolution {
+    /// Use pinned content, failing if it can no longer be obtained. Environments
+    /// which are not yet pinned are resolved and added to the lockfile.
+    #[default]
+    Locked,
+    /// Resolve all environments again, and update the lockfile to match.
+    Refresh,
+}
+
 /// How a `TargetEnvironment` should validate components associated with trigger types
 /// not listed in the/ environment definition. This is used for best-effort validation in
 /// extensible environments.
@@ -455,8 +472,100 @@ mod test {
         assert!(err.contains("nice_cup_of_tea"), "unexpected error {err}");
     }
 
-    fn generate_dummy_component(wit: &str, world: &str) -> Vec<u8> {
+    const SPIN_WIT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../wit");
+
+    /// Registers the embedded Spin HTTP world with the local environment.
+    fn register_local_http() {
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        REGISTER.call_once(|| {
+            let package = spin_world::wit_package("spin:up@3.5.0").unwrap();
+            register_local_trigger(
+                "http",
+                package,
+                &["spin:up/http-trigger@3.5.0"],
+                &["local_service_chaining"],
+            )
+            .expect("should have registered local HTTP trigger");
+        });
+    }
+
+    /// Generates a component for the given world, where the world may use
+    /// the packages in the Spin WIT directory.
+    fn generate_dummy_spin_component(wit: &str, world: &str) -> Vec<u8> {
         let mut resolve = wit_parser::Resolve::default();
+        resolve
+            .push_dir(SPIN_WIT_DIR)
+            .expect("should have pushed Spin WIT dir");
+        generate_dummy_component_in(resolve, wit, world)
+    }
+
+    #[tokio::test]
+    async fn can_validate_component_against_local_environment() {
+        register_local_http();
+        let wasm = generate_dummy_spin_component(
+            "package test:local;\nworld app { include spin:up/http-trigger@3.5.0; }",
+            "test:local/app",
+        );
+
+        let env = local::load_local_environment().unwrap();
+        assert_eq!(LOCAL_ENVIRONMENT_ID, env.name());
+        assert!(env.supports_trigger_type(&"http".to_owned()));
+        assert!(!env.supports_trigger_type(&"farmer-buckleys-trousers".to_owned()));
+
+        let component = crate::ComponentToValidate::new("lcomp", "lcomp.wasm", wasm, vec![]);
+        let errs =
+            crate::validate_component_against_environments(&[env], &"http".to_owned(), &component)
+                .await;
+        assert!(
+            errs.is_empty(),
+            "{}",
+            errs.iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn unavailable_import_invalidates_component_in_local_environment() {
+        register_local_http();
+        let wasm = generate_dummy_spin_component(
+            "package test:local;\ninterface warp-drive { engage: func(); }\nworld app { include spin:up/http-trigger@3.5.0; import warp-drive; }",
+            "test:local/app",
+        );
+
+        let env = local::load_local_environment().unwrap();
+
+        let component = crate::ComponentToValidate::new("wcomp", "wcomp.wasm", wasm, vec![]);
+        let errs =
+            crate::validate_component_against_environments(&[env], &"http".to_owned(), &component)
+                .await;
+        assert!(!errs.is_empty());
+
+        let err = errs[0].to_string();
+        assert!(
+            err.contains("Component wcomp (wcomp.wasm) can't run in environment spin-up:local"),
+            "unexpected error {err}"
+        );
+        assert!(
+            err.contains("requires imports named"),
+            "unexpected error {err}"
+        );
+        assert!(
+            err.contains("test:local/warp-drive"),
+            "unexpected error {err}"
+        );
+    }
+
+    fn generate_dummy_component(wit: &str, world: &str) -> Vec<u8> {
+        generate_dummy_component_in(wit_parser::Resolve::default(), wit, world)
+    }
+
+    fn generate_dummy_component_in(
+        mut resolve: wit_parser::Resolve,
+        wit: &str,
+        world: &str,
+    ) -> Vec<u8> {
         let package_id = resolve.push_str("test", wit).expect("should parse WIT");
         let world_id = resolve
             .select_world(package_id, Some(world))
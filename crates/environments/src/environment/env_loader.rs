@@ -2,7 +2,10 @@
 //! a fully realised collection of WIT packages with their worlds and
 //! mappings.
 
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 use anyhow::{anyhow, Context};
 use futures::future::try_join_all;
@@ -10,20 +13,41 @@ use spin_common::ui::quoted_path;
 use spin_manifest::schema::v2::TargetEnvironmentRef;
 
 use super::definition::{EnvironmentDefinition, WorldName, WorldRef};
-use super::lockfile::TargetEnvironmentLockfile;
-use super::{is_versioned, CandidateWorld, CandidateWorlds, TargetEnvironment, UnknownTrigger};
+use super::local::{load_local_environment, LOCAL_ENVIRONMENT_ID};
+use super::lockfile::{
+    content_digest, LockedEnvironment, LockedPackage, TargetEnvironmentLockfile, LOCKFILE_NAME,
+};
+use super::{
+    is_versioned, CandidateWorld, CandidateWorlds, EnvironmentResolution, TargetEnvironment,
+    UnknownTrigger,
+};
 
 const DEFAULT_ENV_DEF_REGISTRY_PREFIX: &str = "ghcr.io/spinframework/environments";
 const DEFAULT_PACKAGE_REGISTRY: &str = "spinframework.dev";
 
 /// Load all the listed environments from their registries or paths.
-/// Registry data will be cached, with a lockfile under `.spin` mapping
-/// environment IDs to digests (to allow cache lookup without needing
-/// to fetch the digest from the registry).
+///
+/// Each environment is pinned in a lockfile next to the manifest, recording
+/// the digests of its definition and of every package it pulls in. Pinned
+/// environments are loaded from cache if possible, without network access,
+/// and any content which has to be fetched again must match its pinned digest.
+/// Environments are re-resolved, and their pins updated, only when
+/// `resolution` asks for a refresh.
 pub async fn load_environments(
     env_ids: &[TargetEnvironmentRef],
     cache_root: Option<std::path::PathBuf>,
     app_dir: &std::path::Path,
+    resolution: EnvironmentResolution,
+) -> anyhow::Result<Vec<TargetEnvironment>> {
+    load_environments_with(env_ids, cache_root, app_dir, resolution, &RegistryClient).await
+}
+
+async fn load_environments_with(
+    env_ids: &[TargetEnvironmentRef],
+    cache_root: Option<std::path::PathBuf>,
+    app_dir: &std::path::Path,
+    resolution: EnvironmentResolution,
+    fetcher: &dyn RegistryFetcher,
 ) -> anyhow::Result<Vec<TargetEnvironment>> {
     if env_ids.is_empty() {
         return Ok(Default::default());
@@ -32,176 +56,434 @@ pub async fn load_environments(
     let cache = spin_loader::cache::Cache::new(cache_root)
         .await
         .context("Unable to create cache")?;
-    let lockfile_dir = app_dir.join(".spin");
-    let lockfile_path = lockfile_dir.join("target-environments.lock");
+    let lockfile_path = app_dir.join(LOCKFILE_NAME);
 
-    let orig_lockfile: TargetEnvironmentLockfile = tokio::fs::read_to_string(&lockfile_path)
-        .await
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
-        .unwrap_or_default();
-    let lockfile = std::sync::Arc::new(tokio::sync::RwLock::new(orig_lockfile.clone()));
-
-    let envs = try_join_all(
-        env_ids
-            .iter()
-            .map(|e| load_environment(e, app_dir, &cache, &lockfile)),
-    )
-    .await?;
-
-    let final_lockfile = &*lockfile.read().await;
-    if *final_lockfile != orig_lockfile {
-        if let Ok(lockfile_json) = serde_json::to_string_pretty(&final_lockfile) {
-            _ = tokio::fs::create_dir_all(lockfile_dir).await;
-            _ = tokio::fs::write(&lockfile_path, lockfile_json).await; // failure to update lockfile is not an error
+    let orig_lockfile = match resolution {
+        EnvironmentResolution::Locked => read_lockfile(&lockfile_path).await?,
+        EnvironmentResolution::Refresh => None,
+    };
+    let pins = orig_lockfile.clone().unwrap_or_default();
+    let loader = EnvironmentLoader {
+        app_dir,
+        cache: &cache,
+        fetcher,
+        pins: &pins,
+    };
+
+    let loaded = try_join_all(env_ids.iter().map(|e| loader.load_environment(e))).await?;
+
+    let mut lockfile = TargetEnvironmentLockfile::default();
+    let mut envs = Vec::with_capacity(loaded.len());
+    for (env, locked) in loaded {
+        if let Some((env_key, locked)) = locked {
+            lockfile.set_environment(&env_key, locked);
         }
+        envs.push(env);
     }
 
-    Ok(envs)
-}
-
-/// Loads the given `TargetEnvironment` from a registry or directory.
-async fn load_environment(
-    env_id: &TargetEnvironmentRef,
-    app_dir: &Path,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<TargetEnvironment> {
-    match env_id {
-        TargetEnvironmentRef::DefaultRegistry(id) => {
-            load_environment_from_registry(DEFAULT_ENV_DEF_REGISTRY_PREFIX, id, cache, lockfile)
+    let has_pins = lockfile != TargetEnvironmentLockfile::default();
+    if orig_lockfile.as_ref() != Some(&lockfile) && (has_pins || lockfile_path.exists()) {
+        // Failure to update the lockfile is not an error: the environments
+        // are still valid, they just aren't pinned
+        let written = match lockfile.to_json() {
+            Ok(json) => tokio::fs::write(&lockfile_path, json)
                 .await
-        }
-        TargetEnvironmentRef::Registry { registry, id } => {
-            load_environment_from_registry(registry, id, cache, lockfile).await
-        }
-        TargetEnvironmentRef::File { path } => {
-            load_environment_from_file(app_dir.join(path), cache, lockfile).await
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            tracing::warn!(
+                "Unable to write target environment lockfile {}: {e:#}",
+                quoted_path(&lockfile_path)
+            );
         }
     }
-}
 
-/// Loads a `TargetEnvironment` from the environment definition at the given
-/// registry location. The environment and any remote packages it references will be used
-/// from cache if available; otherwise, they will be saved to the cache, and the
-/// in-memory lockfile object updated.
-async fn load_environment_from_registry(
-    registry: &str,
-    env_id: &str,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<TargetEnvironment> {
-    let env_def_toml = load_env_def_toml_from_registry(registry, env_id, cache, lockfile).await?;
-    load_environment_from_toml(env_id, &env_def_toml, None, cache, lockfile).await
+    Ok(envs)
 }
 
-/// Loads a `TargetEnvironment` from the given TOML file. Any remote packages
-/// it references will be used from cache if available; otherwise, they will be saved
-/// to the cache, and the in-memory lockfile object updated.
-async fn load_environment_from_file(
-    path: impl AsRef<Path>,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<TargetEnvironment> {
-    let path = path.as_ref();
-    let env_def_dir = path.parent();
-    let name = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .map(|s| s.to_owned())
-        .unwrap();
-    let toml_text = tokio::fs::read_to_string(path).await.with_context(|| {
+/// Reads the lockfile at the given path, if there is one.
+async fn read_lockfile(path: &Path) -> anyhow::Result<Option<TargetEnvironmentLockfile>> {
+    let json = match tokio::fs::read_to_string(path).await {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!(
+                    "unable to read target environment lockfile {}",
+                    quoted_path(path)
+                )
+            })
+        }
+    };
+    let lockfile = TargetEnvironmentLockfile::from_json(&json).with_context(|| {
         format!(
-            "unable to read target environment from {}",
+            "invalid target environment lockfile {}: refresh the target environments to recreate it",
             quoted_path(path)
         )
     })?;
-    load_environment_from_toml(&name, &toml_text, env_def_dir, cache, lockfile).await
+    Ok(Some(lockfile))
 }
 
-/// Loads a `TargetEnvironment` from the given TOML text. Any remote packages
-/// it references will be used from cache if available; otherwise, they will be saved
-/// to the cache, and the in-memory lockfile object updated.
-async fn load_environment_from_toml(
-    name: &str,
-    toml_text: &str,
-    relative_to_dir: Option<&Path>,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<TargetEnvironment> {
-    let env: EnvironmentDefinition = toml::from_str(toml_text)?;
-
-    let mut trigger_worlds = HashMap::new();
-    let mut trigger_capabilities = HashMap::new();
-
-    // TODO: parallel all the things
-    // TODO: this loads _all_ triggers not just the ones we need
-    for (trigger_type, trigger_env) in env.triggers() {
-        trigger_worlds.insert(
-            trigger_type.to_owned(),
-            load_worlds(trigger_env.world_refs(), relative_to_dir, cache, lockfile).await?,
-        );
-        trigger_capabilities.insert(trigger_type.to_owned(), trigger_env.capabilities());
-    }
-
-    let unknown_trigger = match env.default() {
-        None => UnknownTrigger::Deny,
-        Some(env) => UnknownTrigger::Allow(
-            load_worlds(env.world_refs(), relative_to_dir, cache, lockfile).await?,
-        ),
-    };
-    let unknown_capabilities = match env.default() {
-        None => vec![],
-        Some(env) => env.capabilities(),
-    };
+/// Fetches environment definitions and WIT packages from registries.
+#[async_trait::async_trait]
+trait RegistryFetcher: Send + Sync {
+    /// Fetches the environment definition document at the given OCI
+    /// reference. Returns the document, and the reference pinned to the
+    /// manifest digest it resolved to.
+    async fn fetch_env_def(&self, reference: &str) -> anyhow::Result<(Vec<u8>, String)>;
+
+    /// Fetches the Wasm-encoded package defining the given world.
+    async fn fetch_package(
+        &self,
+        registry: &str,
+        world_name: &WorldName,
+    ) -> anyhow::Result<Vec<u8>>;
+}
 
-    Ok(TargetEnvironment {
-        name: name.to_owned(),
-        trigger_worlds,
-        trigger_capabilities,
-        unknown_trigger,
-        unknown_capabilities,
-    })
+/// Loads environments, pinned by a lockfile.
+struct EnvironmentLoader<'a> {
+    app_dir: &'a Path,
+    cache: &'a spin_loader::cache::Cache,
+    fetcher: &'a dyn RegistryFetcher,
+    /// The pinned environments, which is empty when refreshing.
+    pins: &'a TargetEnvironmentLockfile,
 }
 
-/// Loads the text (assumed to be TOML) from the environment definition at the given
-/// registry location. The environment will be used from cache if available; otherwise,
-/// it be saved to the cache, and the in-memory lockfile object updated.
-async fn load_env_def_toml_from_registry(
-    registry: &str,
-    env_id: &str,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<String> {
-    if let Some(digest) = lockfile.read().await.env_digest(registry, env_id) {
-        if let Ok(cache_file) = cache.data_file(digest) {
-            if let Ok(bytes) = tokio::fs::read(&cache_file).await {
-                return Ok(String::from_utf8_lossy(&bytes).to_string());
+impl EnvironmentLoader<'_> {
+    /// Loads the given `TargetEnvironment` from a registry or directory,
+    /// along with its key and pin in the lockfile.
+    async fn load_environment(
+        &self,
+        env_id: &TargetEnvironmentRef,
+    ) -> anyhow::Result<(TargetEnvironment, Option<(String, LockedEnvironment)>)> {
+        let (env_key, loaded) = match env_id {
+            TargetEnvironmentRef::DefaultRegistry(id) if id == LOCAL_ENVIRONMENT_ID => {
+                return Ok((load_local_environment()?, None));
+            }
+            TargetEnvironmentRef::DefaultRegistry(id) => (
+                id.clone(),
+                self.load_environment_from_registry(DEFAULT_ENV_DEF_REGISTRY_PREFIX, id, id)
+                    .await,
+            ),
+            TargetEnvironmentRef::Registry { registry, id } => {
+                let env_key = format!("{registry}/{id}");
+                let loaded = self
+                    .load_environment_from_registry(registry, id, &env_key)
+                    .await;
+                (env_key, loaded)
+            }
+            TargetEnvironmentRef::File { path } => {
+                let env_key = format!("file:{}", path.display());
+                let loaded = self.load_environment_from_file(path, &env_key).await;
+                (env_key, loaded)
+            }
+        };
+        let (env, locked) = loaded?;
+        Ok((env, Some((env_key, locked))))
+    }
+
+    /// Loads a `TargetEnvironment` from the environment definition at the given
+    /// registry location. The environment and any remote packages it references
+    /// will be used from cache if available; otherwise, they will be saved to the
+    /// cache.
+    async fn load_environment_from_registry(
+        &self,
+        registry: &str,
+        env_id: &str,
+        env_key: &str,
+    ) -> anyhow::Result<(TargetEnvironment, LockedEnvironment)> {
+        let pinned = self.pins.environment(env_key);
+        let (bytes, source) = match pinned {
+            Some(pinned) => {
+                let cached = self
+                    .read_cached(self.cache.data_file(&pinned.digest).ok(), &pinned.digest)
+                    .await;
+                let bytes = match cached {
+                    Some(bytes) => bytes,
+                    None => {
+                        let (bytes, _) = self
+                            .fetcher
+                            .fetch_env_def(&pinned.source)
+                            .await
+                            .with_context(|| {
+                                format!("downloading target environment {env_id} from {registry}")
+                            })?;
+                        verify_digest(env_key, "definition", &pinned.digest, &bytes)?;
+                        _ = self.cache.write_data(&bytes, &pinned.digest).await;
+                        bytes
+                    }
+                };
+                (bytes, pinned.source.clone())
             }
+            None => {
+                let (bytes, source) = self
+                    .fetcher
+                    .fetch_env_def(&env_def_reference(registry, env_id))
+                    .await
+                    .with_context(|| {
+                        format!("downloading target environment {env_id} from {registry}")
+                    })?;
+                _ = self.cache.write_data(&bytes, content_digest(&bytes)).await;
+                (bytes, source)
+            }
+        };
+
+        let mut locked = LockedEnvironment {
+            source,
+            digest: content_digest(&bytes),
+            packages: Default::default(),
+        };
+        let toml_text = String::from_utf8_lossy(&bytes);
+        let env = self
+            .load_environment_from_toml(env_id, env_key, &toml_text, None, pinned, &mut locked)
+            .await?;
+        Ok((env, locked))
+    }
+
+    /// Loads a `TargetEnvironment` from the given TOML file. Any remote packages
+    /// it references will be used from cache if available; otherwise, they will
+    /// be saved to the cache.
+    async fn load_environment_from_file(
+        &self,
+        rel_path: &Path,
+        env_key: &str,
+    ) -> anyhow::Result<(TargetEnvironment, LockedEnvironment)> {
+        let path = &self.app_dir.join(rel_path);
+        let env_def_dir = path.parent();
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_owned())
+            .unwrap();
+        let bytes = tokio::fs::read(path).await.with_context(|| {
+            format!(
+                "unable to read target environment from {}",
+                quoted_path(path)
+            )
+        })?;
+
+        let pinned = self.pins.environment(env_key);
+        if let Some(pinned) = pinned {
+            verify_digest(env_key, "definition", &pinned.digest, &bytes)?;
         }
+
+        let mut locked = LockedEnvironment {
+            // Relative to the app, so that the lockfile is portable
+            source: rel_path.display().to_string(),
+            digest: content_digest(&bytes),
+            packages: Default::default(),
+        };
+        let toml_text = String::from_utf8_lossy(&bytes);
+        let env = self
+            .load_environment_from_toml(
+                &name,
+                env_key,
+                &toml_text,
+                env_def_dir,
+                pinned,
+                &mut locked,
+            )
+            .await?;
+        Ok((env, locked))
     }
 
-    let (bytes, digest) = download_env_def_file(registry, env_id)
-        .await
-        .with_context(|| format!("downloading target environment {env_id} from {registry}"))?;
+    /// Loads a `TargetEnvironment` from the given TOML text. Any remote packages
+    /// it references will be used from cache if available; otherwise, they will
+    /// be saved to the cache. The packages are recorded in `locked`.
+    async fn load_environment_from_toml(
+        &self,
+        name: &str,
+        env_key: &str,
+        toml_text: &str,
+        relative_to_dir: Option<&Path>,
+        pinned: Option<&LockedEnvironment>,
+        locked: &mut LockedEnvironment,
+    ) -> anyhow::Result<TargetEnvironment> {
+        let env: EnvironmentDefinition = toml::from_str(toml_text)?;
+        let mut worlds = WorldLoader {
+            loader: self,
+            env_key,
+            relative_to_dir,
+            pinned,
+            packages: &mut locked.packages,
+        };
+
+        let mut trigger_worlds = HashMap::new();
+        let mut trigger_capabilities = HashMap::new();
+
+        // TODO: parallel all the things
+        // TODO: this loads _all_ triggers not just the ones we need
+        for (trigger_type, trigger_env) in env.triggers() {
+            trigger_worlds.insert(
+                trigger_type.to_owned(),
+                worlds.load_worlds(trigger_env.world_refs()).await?,
+            );
+            trigger_capabilities.insert(trigger_type.to_owned(), trigger_env.capabilities());
+        }
 
-    let toml_text = String::from_utf8_lossy(&bytes).to_string();
+        let unknown_trigger = match env.default() {
+            None => UnknownTrigger::Deny,
+            Some(env) => UnknownTrigger::Allow(worlds.load_worlds(env.world_refs()).await?),
+        };
+        let unknown_capabilities = match env.default() {
+            None => vec![],
+            Some(env) => env.capabilities(),
+        };
+
+        Ok(TargetEnvironment {
+            name: name.to_owned(),
+            trigger_worlds,
+            trigger_capabilities,
+            unknown_trigger,
+            unknown_capabilities,
+        })
+    }
 
-    _ = cache.write_data(bytes, &digest).await;
-    lockfile
-        .write()
-        .await
-        .set_env_digest(registry, env_id, &digest);
+    /// Reads content from the cache, ignoring it if it doesn't have the
+    /// expected digest.
+    async fn read_cached(&self, path: Option<std::path::PathBuf>, digest: &str) -> Option<Vec<u8>> {
+        let path = path?;
+        let bytes = tokio::fs::read(&path).await.ok()?;
+        if content_digest(&bytes) != digest {
+            tracing::warn!(
+                "Ignoring cached content {} which does not match its digest",
+                quoted_path(&path)
+            );
+            return None;
+        }
+        Some(bytes)
+    }
+}
 
-    Ok(toml_text)
+/// Loads the worlds referenced by an environment definition.
+struct WorldLoader<'a> {
+    loader: &'a EnvironmentLoader<'a>,
+    env_key: &'a str,
+    relative_to_dir: Option<&'a Path>,
+    /// The pinned environment, if any.
+    pinned: Option<&'a LockedEnvironment>,
+    /// The packages the environment has pulled in so far.
+    packages: &'a mut BTreeMap<String, LockedPackage>,
 }
 
-/// Downloads a single-layer document from the given registry.
-/// (You can create a suitable document with e.g. `oras push ghcr.io/my/envs/sample:1.0 sample.toml`.)
-/// The image must be publicly accessible (which is *NOT* the default with GHCR).
-///
-/// The return value is a tuple of (content, digest).
-async fn download_env_def_file(registry: &str, env_id: &str) -> anyhow::Result<(Vec<u8>, String)> {
+impl WorldLoader<'_> {
+    async fn load_worlds(&mut self, world_refs: &[WorldRef]) -> anyhow::Result<CandidateWorlds> {
+        let mut worlds = vec![];
+
+        for world_ref in world_refs {
+            worlds.push(self.load_world(world_ref).await?);
+        }
+
+        Ok(CandidateWorlds { worlds })
+    }
+
+    async fn load_world(&mut self, world_ref: &WorldRef) -> anyhow::Result<CandidateWorld> {
+        match world_ref {
+            WorldRef::DefaultRegistry(world) => {
+                self.load_world_from_registry(DEFAULT_PACKAGE_REGISTRY, world)
+                    .await
+            }
+            WorldRef::Registry { registry, world } => {
+                self.load_world_from_registry(registry, world).await
+            }
+            WorldRef::WitDirectory { path, world } => {
+                let path = match self.relative_to_dir {
+                    Some(dir) => dir.join(path),
+                    None => path.to_owned(),
+                };
+                load_world_from_dir(&path, world)
+            }
+        }
+    }
+
+    /// Loads the given world from the given registry, or from cache if
+    /// available. If the package is not in cache, the encoded WIT will be
+    /// cached. Either way, the package is recorded in the environment's pins.
+    async fn load_world_from_registry(
+        &mut self,
+        registry: &str,
+        world_name: &WorldName,
+    ) -> anyhow::Result<CandidateWorld> {
+        let package_name = world_name.package().to_string();
+        // A package already pulled in for another world, or pinned by the lockfile
+        let pinned = self
+            .packages
+            .get(&package_name)
+            .or_else(|| {
+                self.pinned
+                    .and_then(|env| env.package(world_name.package()))
+            })
+            .filter(|package| package.registry == registry)
+            .cloned();
+
+        let cache = self.loader.cache;
+        let bytes = match pinned {
+            Some(pinned) => {
+                let cached = self
+                    .loader
+                    .read_cached(cache.wasm_file(&pinned.digest).ok(), &pinned.digest)
+                    .await;
+                match cached {
+                    Some(bytes) => bytes,
+                    None => {
+                        let bytes = self
+                            .loader
+                            .fetcher
+                            .fetch_package(registry, world_name)
+                            .await?;
+                        verify_digest(
+                            self.env_key,
+                            &format!("package {package_name}"),
+                            &pinned.digest,
+                            &bytes,
+                        )?;
+                        _ = cache.write_wasm(&bytes, &pinned.digest).await; // Failure to cache is not fatal
+                        bytes
+                    }
+                }
+            }
+            None => {
+                let bytes = self
+                    .loader
+                    .fetcher
+                    .fetch_package(registry, world_name)
+                    .await?;
+                _ = cache.write_wasm(&bytes, content_digest(&bytes)).await; // Failure to cache is not fatal
+                bytes
+            }
+        };
+
+        self.packages.insert(
+            package_name,
+            LockedPackage {
+                registry: registry.to_owned(),
+                digest: content_digest(&bytes),
+            },
+        );
+        CandidateWorld::from_package_bytes(world_name, bytes)
+    }
+}
+
+/// Checks that content fetched for a pinned environment matches its pin.
+fn verify_digest(
+    env_key: &str,
+    what: &str,
+    locked_digest: &str,
+    bytes: &[u8],
+) -> anyhow::Result<()> {
+    let digest = content_digest(bytes);
+    anyhow::ensure!(
+        digest == locked_digest,
+        "The {what} of target environment {env_key} does not match the lockfile: \
+         the lockfile has digest {locked_digest}, but the content has digest {digest}. \
+         If this change is expected, refresh the target environments to update the lockfile."
+    );
+    Ok(())
+}
+
+/// The OCI reference of the environment definition with the given ID.
+fn env_def_reference(registry: &str, env_id: &str) -> String {
     // This implies env_id is in the format spin-up:3.2
     let registry_id = if is_versioned(env_id) {
         env_id.to_string()
@@ -212,151 +494,347 @@ async fn download_env_def_file(registry: &str, env_id: &str) -> anyhow::Result<(
         format!("{env_id}:latest")
     };
 
-    let reference = format!("{registry}/{registry_id}");
-    let reference = oci_distribution::Reference::try_from(reference)?;
+    format!("{registry}/{registry_id}")
+}
+
+fn load_world_from_dir(
+    path: impl AsRef<Path>,
+    world: &WorldName,
+) -> anyhow::Result<CandidateWorld> {
+    let path = path.as_ref();
+    let mut resolve = wit_parser::Resolve::default();
+    let (pkg_id, _) = resolve.push_dir(path)?;
+    let decoded = wit_parser::decoding::DecodedWasm::WitPackage(resolve, pkg_id);
+    CandidateWorld::from_decoded_wasm(world, path, decoded)
+}
+
+/// Fetches from OCI registries (for environment definitions) and WebAssembly
+/// package registries (for WIT packages).
+struct RegistryClient;
+
+#[async_trait::async_trait]
+impl RegistryFetcher for RegistryClient {
+    /// Downloads a single-layer document from the given registry.
+    /// (You can create a suitable document with e.g. `oras push ghcr.io/my/envs/sample:1.0 sample.toml`.)
+    /// The image must be publicly accessible (which is *NOT* the default with GHCR).
+    async fn fetch_env_def(&self, reference: &str) -> anyhow::Result<(Vec<u8>, String)> {
+        let reference = oci_distribution::Reference::try_from(reference)?;
 
-    let config = oci_distribution::client::ClientConfig::default();
-    let client = oci_distribution::client::Client::new(config);
-    let auth = oci_distribution::secrets::RegistryAuth::Anonymous;
+        let config = oci_distribution::client::ClientConfig::default();
+        let client = oci_distribution::client::Client::new(config);
+        let auth = oci_distribution::secrets::RegistryAuth::Anonymous;
 
-    let (manifest, digest) = client.pull_manifest(&reference, &auth).await?;
+        let (manifest, digest) = client.pull_manifest(&reference, &auth).await?;
 
-    let im = match manifest {
-        oci_distribution::manifest::OciManifest::Image(im) => im,
-        oci_distribution::manifest::OciManifest::ImageIndex(_) => {
-            anyhow::bail!("unexpected registry format for {reference}")
+        let im = match manifest {
+            oci_distribution::manifest::OciManifest::Image(im) => im,
+            oci_distribution::manifest::OciManifest::ImageIndex(_) => {
+                anyhow::bail!("unexpected registry format for {reference}")
+            }
+        };
+
+        let count = im.layers.len();
+
+        if count != 1 {
+            anyhow::bail!("artifact {reference} should have had exactly one layer");
         }
-    };
 
-    let count = im.layers.len();
+        let the_layer = &im.layers[0];
+        let mut out = Vec::with_capacity(the_layer.size.try_into().unwrap_or_default());
+        client.pull_blob(&reference, the_layer, &mut out).await?;
 
-    if count != 1 {
-        anyhow::bail!("artifact {reference} should have had exactly one layer");
+        let pinned_reference = match reference.digest() {
+            Some(_) => reference.whole(),
+            None => format!("{}@{digest}", reference.whole()),
+        };
+        Ok((out, pinned_reference))
     }
 
-    let the_layer = &im.layers[0];
-    let mut out = Vec::with_capacity(the_layer.size.try_into().unwrap_or_default());
-    client.pull_blob(&reference, the_layer, &mut out).await?;
+    async fn fetch_package(
+        &self,
+        registry: &str,
+        world_name: &WorldName,
+    ) -> anyhow::Result<Vec<u8>> {
+        use futures_util::TryStreamExt;
 
-    Ok((out, digest))
-}
+        let pkg_name = world_name.package_namespaced_name();
+        let pkg_ref = world_name.package_ref()?;
 
-async fn load_worlds(
-    world_refs: &[WorldRef],
-    relative_to_dir: Option<&Path>,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<CandidateWorlds> {
-    let mut worlds = vec![];
+        let wkg_registry: wasm_pkg_client::Registry = registry
+            .parse()
+            .with_context(|| format!("Registry {registry} is not a valid registry name"))?;
 
-    for world_ref in world_refs {
-        worlds.push(load_world(world_ref, relative_to_dir, cache, lockfile).await?);
-    }
+        let mut wkg_config = wasm_pkg_client::Config::global_defaults().await?;
+        wkg_config.set_package_registry_override(
+            pkg_ref,
+            wasm_pkg_client::RegistryMapping::Registry(wkg_registry),
+        );
 
-    Ok(CandidateWorlds { worlds })
+        let client = wasm_pkg_client::Client::new(wkg_config);
+
+        let package = pkg_name.to_owned().try_into().with_context(|| {
+            format!("Failed to parse environment name {pkg_name} as package name")
+        })?;
+        let version = world_name
+            .package_version() // TODO: surely we can cope with worlds from unversioned packages? surely?
+            .ok_or_else(|| {
+                anyhow!("{world_name} is unversioned: this is not currently supported")
+            })?;
+
+        let release = client
+            .get_release(&package, version)
+            .await
+            .with_context(|| format!("Failed to get {} from registry", world_name.package()))?;
+        let stm = client
+            .stream_content(&package, &release)
+            .await
+            .with_context(|| format!("Failed to get {} from registry", world_name.package()))?;
+        let bytes = stm
+            .try_collect::<bytes::BytesMut>()
+            .await
+            .with_context(|| format!("Failed to get {} from registry", world_name.package()))?
+            .to_vec();
+
+        Ok(bytes)
+    }
 }
 
-async fn load_world(
-    world_ref: &WorldRef,
-    relative_to_dir: Option<&Path>,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<CandidateWorld> {
-    match world_ref {
-        WorldRef::DefaultRegistry(world) => {
-            load_world_from_registry(DEFAULT_PACKAGE_REGISTRY, world, cache, lockfile).await
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    use super::*;
+
+    const SIMPLE_WIT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/simple-wit");
+    const TEST_ENV_ID: &str = "test-env:1.0";
+    const TEST_ENV_DEF: &str = r#"[triggers]
+s = { worlds = ["spin:test/simple@1.0.0"] }
+"#;
+
+    /// A registry which serves canned content, counting fetches.
+    #[derive(Default)]
+    struct FakeRegistry {
+        env_defs: Mutex<HashMap<String, Vec<u8>>>,
+        packages: HashMap<String, Vec<u8>>,
+        fetches: AtomicUsize,
+    }
+
+    impl FakeRegistry {
+        /// A registry serving the test environment and the package it uses.
+        fn with_test_env(env_def: &str) -> Self {
+            let mut resolve = wit_parser::Resolve::default();
+            let (id, _) = resolve.push_dir(SIMPLE_WIT_DIR).unwrap();
+            let package = wit_component::encode(&resolve, id).unwrap();
+            let registry = Self {
+                packages: [("spin:test@1.0.0".to_owned(), package)]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            };
+            registry.set_env_def(env_def);
+            registry
         }
-        WorldRef::Registry { registry, world } => {
-            load_world_from_registry(registry, world, cache, lockfile).await
+
+        fn set_env_def(&self, env_def: &str) {
+            let reference = env_def_reference(DEFAULT_ENV_DEF_REGISTRY_PREFIX, TEST_ENV_ID);
+            self.env_defs
+                .lock()
+                .unwrap()
+                .insert(reference, env_def.as_bytes().to_vec());
         }
-        WorldRef::WitDirectory { path, world } => {
-            let path = match relative_to_dir {
-                Some(dir) => dir.join(path),
-                None => path.to_owned(),
-            };
-            load_world_from_dir(&path, world)
+
+        fn fetches(&self) -> usize {
+            self.fetches.load(Ordering::SeqCst)
         }
     }
-}
 
-fn load_world_from_dir(
-    path: impl AsRef<Path>,
-    world: &WorldName,
-) -> anyhow::Result<CandidateWorld> {
-    let path = path.as_ref();
-    let mut resolve = wit_parser::Resolve::default();
-    let (pkg_id, _) = resolve.push_dir(path)?;
-    let decoded = wit_parser::decoding::DecodedWasm::WitPackage(resolve, pkg_id);
-    CandidateWorld::from_decoded_wasm(world, path, decoded)
-}
+    #[async_trait::async_trait]
+    impl RegistryFetcher for FakeRegistry {
+        async fn fetch_env_def(&self, reference: &str) -> anyhow::Result<(Vec<u8>, String)> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            let (tag_reference, _) = reference.split_once('@').unwrap_or((reference, ""));
+            let bytes = self
+                .env_defs
+                .lock()
+                .unwrap()
+                .get(tag_reference)
+                .cloned()
+                .with_context(|| format!("no such document {reference}"))?;
+            // Real registries pin the manifest digest, but any digest will do here
+            let pinned = format!("{tag_reference}@{}", content_digest(&bytes));
+            Ok((bytes, pinned))
+        }
 
-/// Loads the given `TargetEnvironment` from the given registry, or
-/// from cache if available. If the environment is not in cache, the
-/// encoded WIT will be cached, and the in-memory lockfile object
-/// updated.
-async fn load_world_from_registry(
-    registry: &str,
-    world_name: &WorldName,
-    cache: &spin_loader::cache::Cache,
-    lockfile: &std::sync::Arc<tokio::sync::RwLock<TargetEnvironmentLockfile>>,
-) -> anyhow::Result<CandidateWorld> {
-    use futures_util::TryStreamExt;
+        async fn fetch_package(
+            &self,
+            _registry: &str,
+            world_name: &WorldName,
+        ) -> anyhow::Result<Vec<u8>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            self.packages
+                .get(&world_name.package().to_string())
+                .cloned()
+                .with_context(|| format!("no such package {world_name}"))
+        }
+    }
 
-    if let Some(digest) = lockfile
-        .read()
-        .await
-        .package_digest(registry, world_name.package())
-    {
-        if let Ok(cache_file) = cache.wasm_file(digest) {
-            if let Ok(bytes) = tokio::fs::read(&cache_file).await {
-                return CandidateWorld::from_package_bytes(world_name, bytes);
+    struct TestApp {
+        app_dir: tempfile::TempDir,
+        cache_dir: tempfile::TempDir,
+    }
+
+    impl TestApp {
+        fn new() -> Self {
+            Self {
+                app_dir: tempfile::tempdir().unwrap(),
+                cache_dir: tempfile::tempdir().unwrap(),
             }
         }
-    }
 
-    let pkg_name = world_name.package_namespaced_name();
-    let pkg_ref = world_name.package_ref()?;
+        async fn load(
+            &self,
+            env_ref: TargetEnvironmentRef,
+            resolution: EnvironmentResolution,
+            registry: &FakeRegistry,
+        ) -> anyhow::Result<Vec<TargetEnvironment>> {
+            load_environments_with(
+                &[env_ref],
+                Some(self.cache_dir.path().to_owned()),
+                self.app_dir.path(),
+                resolution,
+                registry,
+            )
+            .await
+        }
 
-    let wkg_registry: wasm_pkg_client::Registry = registry
-        .parse()
-        .with_context(|| format!("Registry {registry} is not a valid registry name"))?;
+        fn lockfile(&self) -> TargetEnvironmentLockfile {
+            let json = std::fs::read_to_string(self.app_dir.path().join(LOCKFILE_NAME))
+                .expect("lockfile should have been written");
+            TargetEnvironmentLockfile::from_json(&json).unwrap()
+        }
 
-    let mut wkg_config = wasm_pkg_client::Config::global_defaults().await?;
-    wkg_config.set_package_registry_override(
-        pkg_ref,
-        wasm_pkg_client::RegistryMapping::Registry(wkg_registry),
-    );
+        fn clear_cache(&mut self) {
+            self.cache_dir = tempfile::tempdir().unwrap();
+        }
+    }
 
-    let client = wasm_pkg_client::Client::new(wkg_config);
+    fn test_env_ref() -> TargetEnvironmentRef {
+        TargetEnvironmentRef::DefaultRegistry(TEST_ENV_ID.to_owned())
+    }
 
-    let package = pkg_name
-        .to_owned()
-        .try_into()
-        .with_context(|| format!("Failed to parse environment name {pkg_name} as package name"))?;
-    let version = world_name
-        .package_version() // TODO: surely we can cope with worlds from unversioned packages? surely?
-        .ok_or_else(|| anyhow!("{world_name} is unversioned: this is not currently supported"))?;
+    #[tokio::test]
+    async fn lockfile_is_written_on_first_load() {
+        let app = TestApp::new();
+        let registry = FakeRegistry::with_test_env(TEST_ENV_DEF);
+
+        let envs = app
+            .load(test_env_ref(), EnvironmentResolution::Locked, &registry)
+            .await
+            .unwrap();
+        assert!(envs[0].supports_trigger_type(&"s".to_owned()));
+
+        let lockfile = app.lockfile();
+        let locked = lockfile
+            .environment(TEST_ENV_ID)
+            .expect("should have pinned the environment");
+        assert_eq!(content_digest(TEST_ENV_DEF), locked.digest);
+        assert!(locked
+            .source
+            .starts_with("ghcr.io/spinframework/environments/test-env:1.0@sha256:"));
+        let package = &locked.packages["spin:test@1.0.0"];
+        assert_eq!(DEFAULT_PACKAGE_REGISTRY, package.registry);
+        assert_eq!(
+            content_digest(&registry.packages["spin:test@1.0.0"]),
+            package.digest
+        );
+    }
 
-    let release = client
-        .get_release(&package, version)
-        .await
-        .with_context(|| format!("Failed to get {} from registry", world_name.package()))?;
-    let stm = client
-        .stream_content(&package, &release)
-        .await
-        .with_context(|| format!("Failed to get {} from registry", world_name.package()))?;
-    let bytes = stm
-        .try_collect::<bytes::BytesMut>()
-        .await
-        .with_context(|| format!("Failed to get {} from registry", world_name.package()))?
-        .to_vec();
+    #[tokio::test]
+    async fn pinned_environments_load_without_fetching() {
+        let app = TestApp::new();
+        let registry = FakeRegistry::with_test_env(TEST_ENV_DEF);
+        app.load(test_env_ref(), EnvironmentResolution::Locked, &registry)
+            .await
+            .unwrap();
+        assert_eq!(2, registry.fetches());
+
+        // Even with the registry's content gone, the pinned environment loads from cache
+        let offline = FakeRegistry::default();
+        let envs = app
+            .load(test_env_ref(), EnvironmentResolution::Locked, &offline)
+            .await
+            .unwrap();
+        assert!(envs[0].supports_trigger_type(&"s".to_owned()));
+        assert_eq!(0, offline.fetches());
+    }
 
-    let digest = release.content_digest.to_string();
-    _ = cache.write_wasm(&bytes, &digest).await; // Failure to cache is not fatal
-    lockfile
-        .write()
-        .await
-        .set_package_digest(registry, world_name.package(), &digest);
+    #[tokio::test]
+    async fn changed_registry_content_is_rejected() {
+        let mut app = TestApp::new();
+        let registry = FakeRegistry::with_test_env(TEST_ENV_DEF);
+        app.load(test_env_ref(), EnvironmentResolution::Locked, &registry)
+            .await
+            .unwrap();
+        let pinned = app.lockfile();
+
+        // The tag now points at different content, and the cache has gone
+        let changed_env_def = format!("{TEST_ENV_DEF}default = {{ worlds = [] }}\n");
+        registry.set_env_def(&changed_env_def);
+        app.clear_cache();
+
+        let err = app
+            .load(test_env_ref(), EnvironmentResolution::Locked, &registry)
+            .await
+            .err()
+            .expect("changed content should have been rejected")
+            .to_string();
+        assert!(err.contains(TEST_ENV_ID), "{err}");
+        assert!(err.contains(&content_digest(TEST_ENV_DEF)), "{err}");
+        assert!(err.contains(&content_digest(&changed_env_def)), "{err}");
+        assert_eq!(pinned, app.lockfile());
+
+        // Refreshing accepts the new content
+        app.load(test_env_ref(), EnvironmentResolution::Refresh, &registry)
+            .await
+            .unwrap();
+        assert_eq!(
+            content_digest(&changed_env_def),
+            app.lockfile().environment(TEST_ENV_ID).unwrap().digest
+        );
+    }
 
-    CandidateWorld::from_package_bytes(world_name, bytes)
+    #[tokio::test]
+    async fn changed_file_environment_is_rejected() {
+        let app = TestApp::new();
+        let registry = FakeRegistry::with_test_env(TEST_ENV_DEF);
+        let env_path = app.app_dir.path().join("env.toml");
+        std::fs::write(&env_path, TEST_ENV_DEF).unwrap();
+        let env_ref = || TargetEnvironmentRef::File {
+            path: "env.toml".into(),
+        };
+
+        app.load(env_ref(), EnvironmentResolution::Locked, &registry)
+            .await
+            .unwrap();
+        let locked = app.lockfile();
+        let locked = locked
+            .environment("file:env.toml")
+            .expect("should have pinned the file environment");
+        assert_eq!(content_digest(TEST_ENV_DEF), locked.digest);
+        assert!(locked.packages.contains_key("spin:test@1.0.0"));
+
+        std::fs::write(&env_path, "[triggers]\n").unwrap();
+        let err = app
+            .load(env_ref(), EnvironmentResolution::Locked, &registry)
+            .await
+            .err()
+            .expect("changed file should have been rejected")
+            .to_string();
+        assert!(err.contains("file:env.toml"), "{err}");
+        assert!(err.contains(&content_digest(TEST_ENV_DEF)), "{err}");
+        assert!(err.contains(&content_digest("[triggers]\n")), "{err}");
+    }
 }
@@ -0,0 +1,106 @@
+//! The local environment, made up of the worlds which the running host has
+//! registered as supported. This is resolved in-process, so it never needs
+//! network access and always matches the running version of Spin.
+
+use std::{collections::HashMap, sync::Arc, sync::RwLock};
+
+use anyhow::Context;
+
+use super::definition::WorldName;
+use super::{CandidateWorld, CandidateWorlds, TargetEnvironment, TriggerType, UnknownTrigger};
+
+/// The target environment ID which refers to the worlds registered with
+/// [register_local_trigger], rather than to a published environment definition.
+pub const LOCAL_ENVIRONMENT_ID: &str = "spin-up:local";
+
+static LOCAL_TRIGGERS: RwLock<Vec<LocalTrigger>> = RwLock::new(Vec::new());
+
+struct LocalTrigger {
+    trigger_type: TriggerType,
+    worlds: Vec<(WorldName, Arc<[u8]>)>,
+    capabilities: Vec<String>,
+}
+
+/// Registers worlds which components for the given trigger type may target
+/// in the [local](LOCAL_ENVIRONMENT_ID) environment, together with the host
+/// capabilities the trigger supports. `package_bytes` is a Wasm-encoded WIT
+/// package which defines each of the named `worlds` (e.g. `spin:up/http-trigger@3.5.0`).
+///
+/// Registering the same trigger type again adds to its worlds and capabilities.
+/// The package is not decoded until the local environment is loaded, so
+/// registration is cheap enough to do on every startup.
+pub fn register_local_trigger(
+    trigger_type: &str,
+    package_bytes: &[u8],
+    worlds: &[&str],
+    capabilities: &[&str],
+) -> anyhow::Result<()> {
+    let package_bytes: Arc<[u8]> = package_bytes.into();
+
+    let world_entries = worlds
+        .iter()
+        .map(|world| {
+            Ok((
+                WorldName::try_from(world.to_string())?,
+                package_bytes.clone(),
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let capabilities = capabilities.iter().map(|c| c.to_string());
+
+    let mut triggers = LOCAL_TRIGGERS.write().unwrap();
+    match triggers.iter_mut().find(|t| t.trigger_type == trigger_type) {
+        Some(trigger) => {
+            trigger.worlds.extend(world_entries);
+            trigger.capabilities.extend(capabilities);
+        }
+        None => triggers.push(LocalTrigger {
+            trigger_type: trigger_type.to_owned(),
+            worlds: world_entries,
+            capabilities: capabilities.collect(),
+        }),
+    }
+    Ok(())
+}
+
+/// Builds the local environment from the currently registered triggers.
+/// Trigger types which have not been registered are not supported.
+pub(super) fn load_local_environment() -> anyhow::Result<TargetEnvironment> {
+    let triggers = LOCAL_TRIGGERS.read().unwrap();
+
+    let mut trigger_worlds = HashMap::new();
+    let mut trigger_capabilities = HashMap::new();
+    for trigger in triggers.iter() {
+        let worlds = trigger
+            .worlds
+            .iter()
+            .map(|(world, bytes)| load_local_world(world, bytes))
+            .collect::<anyhow::Result<_>>()
+            .with_context(|| {
+                format!(
+                    "Failed to load local worlds for trigger type {}",
+                    trigger.trigger_type
+                )
+            })?;
+        trigger_worlds.insert(trigger.trigger_type.clone(), CandidateWorlds { worlds });
+        trigger_capabilities.insert(trigger.trigger_type.clone(), trigger.capabilities.clone());
+    }
+
+    Ok(TargetEnvironment {
+        name: LOCAL_ENVIRONMENT_ID.to_owned(),
+        trigger_worlds,
+        trigger_capabilities,
+        unknown_trigger: UnknownTrigger::Deny,
+        unknown_capabilities: vec![],
+    })
+}
+
+fn load_local_world(world: &WorldName, package_bytes: &[u8]) -> anyhow::Result<CandidateWorld> {
+    let candidate = CandidateWorld::from_package_bytes(world, package_bytes.to_vec())?;
+    anyhow::ensure!(
+        candidate.package.name == *world.package()
+            && candidate.package.worlds.contains_key(world.name()),
+        "The registered package does not define world {world}"
+    );
+    Ok(candidate)
+}
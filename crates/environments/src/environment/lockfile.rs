@@ -1,210 +1,170 @@
-use std::collections::HashMap;
+//! The target environment lockfile, which pins each environment an
+//! application targets to the exact content it resolved to.
+//!
+//! The lockfile lives next to the application manifest, so that it can be
+//! checked in: an application validated against a pinned environment is
+//! validated against the same definition and WIT packages on every machine,
+//! until the environments are explicitly refreshed.
 
-use super::is_versioned;
+use std::collections::BTreeMap;
 
-const DIGEST_TTL_HOURS: i64 = 24;
+/// The name of the lockfile, in the application directory.
+pub const LOCKFILE_NAME: &str = "target-environments.lock";
 
-/// Serialisation format for the lockfile: registry -> env|pkg -> { name -> digest }
-#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-pub struct TargetEnvironmentLockfile(HashMap<String, Digests>);
+const LOCKFILE_VERSION: u32 = 1;
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-struct Digests {
-    env: HashMap<String, ExpirableDigest>,
-    package: HashMap<String, String>,
+/// Serialisation format for the lockfile: environment key -> pinned environment
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetEnvironmentLockfile {
+    version: u32,
+    #[serde(default)]
+    environments: BTreeMap<String, LockedEnvironment>,
 }
 
+/// An environment definition pinned to the content it resolved to, along with
+/// the packages it pulled in.
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-#[serde(untagged)]
-enum ExpirableDigest {
-    Forever(String),
-    Expiring {
-        digest: String,
-        correct_at: chrono::DateTime<chrono::Utc>,
-    },
+#[serde(deny_unknown_fields)]
+pub struct LockedEnvironment {
+    /// Where the definition was resolved from: a registry reference including
+    /// the manifest digest, or a file path.
+    pub source: String,
+    /// The content digest of the definition document.
+    pub digest: String,
+    /// The packages the definition pulled in from registries, by package name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<String, LockedPackage>,
 }
 
-impl TargetEnvironmentLockfile {
-    pub fn env_digest(&self, registry: &str, env_id: &str) -> Option<&str> {
-        self.0
-            .get(registry)
-            .and_then(|ds| ds.env.get(env_id))
-            .and_then(|s| s.current())
-    }
-
-    pub fn set_env_digest(&mut self, registry: &str, env_id: &str, digest: &str) {
-        // If the environment is versioned, we assume it will not change (that is, any changes will
-        // be reflected as a new version).  If the environment is *not* versioned, it represents
-        // a hosted service which may change over time: allow the cached definition to expire every day or
-        // so that we do not use a definition that is out of sync with the actual service.
-        let expirable_digest = if is_versioned(env_id) {
-            ExpirableDigest::forever(digest)
-        } else {
-            ExpirableDigest::expiring(digest)
-        };
+/// A WIT package pinned to the content it resolved to.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockedPackage {
+    /// The registry the package was fetched from.
+    pub registry: String,
+    /// The content digest of the package.
+    pub digest: String,
+}
 
-        match self.0.get_mut(registry) {
-            Some(ds) => {
-                ds.env.insert(env_id.to_string(), expirable_digest);
-            }
-            None => {
-                let map = vec![(env_id.to_string(), expirable_digest)]
-                    .into_iter()
-                    .collect();
-                let ds = Digests {
-                    env: map,
-                    package: Default::default(),
-                };
-                self.0.insert(registry.to_string(), ds);
-            }
+impl Default for TargetEnvironmentLockfile {
+    fn default() -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            environments: Default::default(),
         }
     }
+}
 
-    pub fn package_digest(
-        &self,
-        registry: &str,
-        package: &wit_parser::PackageName,
-    ) -> Option<&str> {
-        self.0
-            .get(registry)
-            .and_then(|ds| ds.package.get(&package.to_string()))
-            .map(|s| s.as_str())
+impl TargetEnvironmentLockfile {
+    /// Parses a lockfile.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let lockfile: Self = serde_json::from_str(json)?;
+        anyhow::ensure!(
+            lockfile.version == LOCKFILE_VERSION,
+            "unsupported lockfile version {}",
+            lockfile.version
+        );
+        Ok(lockfile)
     }
 
-    pub fn set_package_digest(
-        &mut self,
-        registry: &str,
-        package: &wit_parser::PackageName,
-        digest: &str,
-    ) {
-        match self.0.get_mut(registry) {
-            Some(ds) => {
-                ds.package.insert(package.to_string(), digest.to_string());
-            }
-            None => {
-                let map = vec![(package.to_string(), digest.to_string())]
-                    .into_iter()
-                    .collect();
-                let ds = Digests {
-                    env: Default::default(),
-                    package: map,
-                };
-                self.0.insert(registry.to_string(), ds);
-            }
-        }
+    /// Serialises the lockfile, with a stable ordering so that it diffs cleanly.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
     }
-}
 
-impl ExpirableDigest {
-    fn current(&self) -> Option<&str> {
-        match self {
-            Self::Forever(digest) => Some(digest),
-            Self::Expiring { digest, correct_at } => {
-                let now = chrono::Utc::now();
-                let time_since = now - correct_at;
-                if time_since.abs().num_hours() > DIGEST_TTL_HOURS {
-                    None
-                } else {
-                    Some(digest)
-                }
-            }
-        }
+    /// The pinned environment with the given key.
+    pub fn environment(&self, env_key: &str) -> Option<&LockedEnvironment> {
+        self.environments.get(env_key)
     }
 
-    fn forever(digest: &str) -> Self {
-        Self::Forever(digest.to_string())
+    /// Pins the environment with the given key.
+    pub fn set_environment(&mut self, env_key: &str, env: LockedEnvironment) {
+        self.environments.insert(env_key.to_owned(), env);
     }
+}
 
-    fn expiring(digest: &str) -> Self {
-        Self::Expiring {
-            digest: digest.to_string(),
-            correct_at: chrono::Utc::now(),
-        }
+impl LockedEnvironment {
+    /// The pinned package with the given name.
+    pub fn package(&self, package: &wit_parser::PackageName) -> Option<&LockedPackage> {
+        self.packages.get(&package.to_string())
     }
 }
 
+/// The content digest of the given bytes, in the same form as registry digests.
+pub fn content_digest(bytes: impl AsRef<[u8]>) -> String {
+    format!(
+        "sha256:{}",
+        spin_common::sha256::hex_digest_from_bytes(bytes)
+    )
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const DUMMY_REG: &str = "reggy-mc-regface";
+    const TEST_ENV: &str = "spin-up:3.3";
+
+    fn locked_env() -> LockedEnvironment {
+        LockedEnvironment {
+            source: "ghcr.io/spinframework/environments/spin-up:3.3@sha256:abc".into(),
+            digest: content_digest("[triggers]"),
+            packages: [(
+                "spin:up@3.3.0".to_owned(),
+                LockedPackage {
+                    registry: "spinframework.dev".into(),
+                    digest: "sha256:def".into(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        }
+    }
 
     #[test]
-    fn versioned_envs_have_no_expiry() {
-        const TEST_ENV: &str = "my-env:1.0";
-        const TEST_DIGEST: &str = "12345";
-
+    fn lockfile_round_trips() {
         let mut lockfile = TargetEnvironmentLockfile::default();
-        lockfile.set_env_digest(DUMMY_REG, TEST_ENV, TEST_DIGEST);
-
-        let json = serde_json::to_value(&lockfile).unwrap();
-
-        let saved_digest = json
-            .get(DUMMY_REG)
-            .and_then(|j| j.get("env"))
-            .and_then(|j| j.get(TEST_ENV))
-            .expect("should have had recorded a digest");
-        let saved_digest = saved_digest
-            .as_str()
-            .expect("saved digest should have been a string");
-        assert_eq!(TEST_DIGEST, saved_digest);
+        lockfile.set_environment(TEST_ENV, locked_env());
+
+        let json = lockfile.to_json().unwrap();
+        let parsed = TargetEnvironmentLockfile::from_json(&json).unwrap();
+        assert_eq!(lockfile, parsed);
+
+        let package = wit_parser::PackageName {
+            namespace: "spin".into(),
+            name: "up".into(),
+            version: Some(semver::Version::new(3, 3, 0)),
+        };
+        let env = parsed
+            .environment(TEST_ENV)
+            .expect("should have pinned env");
+        assert_eq!("sha256:def", env.package(&package).unwrap().digest);
     }
 
     #[test]
-    fn unversioned_envs_expire() {
-        const TEST_ENV: &str = "my-env";
-        const TEST_DIGEST: &str = "12345";
-
+    fn unversioned_envs_are_pinned_too() {
         let mut lockfile = TargetEnvironmentLockfile::default();
-        lockfile.set_env_digest(DUMMY_REG, TEST_ENV, TEST_DIGEST);
-
-        let json = serde_json::to_value(&lockfile).unwrap();
-
-        let saved_digest = json
-            .get(DUMMY_REG)
-            .and_then(|j| j.get("env"))
-            .and_then(|j| j.get(TEST_ENV))
-            .expect("should have recorded a digest");
-        let saved_digest = saved_digest
-            .as_object()
-            .expect("saved digest should have been an object");
-        assert_eq!(TEST_DIGEST, saved_digest.get("digest").unwrap());
-        assert!(saved_digest
-            .get("correct_at")
-            .is_some_and(|v| v.is_string()));
+        lockfile.set_environment("my-host", locked_env());
+
+        // Pins don't expire: an unversioned environment is re-resolved only
+        // when refreshed
+        let json: serde_json::Value = serde_json::from_str(&lockfile.to_json().unwrap()).unwrap();
+        let saved = &json["environments"]["my-host"];
+        assert_eq!(locked_env().digest, saved["digest"].as_str().unwrap());
+        assert!(saved.get("correct_at").is_none());
     }
 
     #[test]
-    fn expired_env_digests_are_not_returned() {
-        const TEST_ENV: &str = "my-env";
-        const TEST_DIGEST: &str = "12345";
+    fn unknown_lockfile_versions_are_rejected() {
+        let json = r#"{ "version": 2, "environments": {} }"#;
+        assert!(TargetEnvironmentLockfile::from_json(json).is_err());
+    }
 
-        let mut lockfile = TargetEnvironmentLockfile::default();
-        lockfile.set_env_digest(DUMMY_REG, TEST_ENV, TEST_DIGEST);
+    #[test]
+    fn content_digests_are_sha256() {
         assert_eq!(
-            TEST_DIGEST,
-            lockfile
-                .env_digest(DUMMY_REG, TEST_ENV)
-                .expect("should have returned env digest")
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            content_digest(b"")
         );
-
-        // Pass this legit lockfile through JSON and massage the digest date to be old. NEARLY AS OLD AS ME
-        let mut json = serde_json::to_value(&lockfile).unwrap();
-        let digest = json
-            .get_mut(DUMMY_REG)
-            .and_then(|j| j.get_mut("env"))
-            .and_then(|j| j.get_mut(TEST_ENV))
-            .expect("should have recorded a digest");
-        let digest = digest
-            .as_object_mut()
-            .expect("saved digest should have been an object");
-        digest.insert(
-            "correct_at".to_string(),
-            serde_json::to_value("1969-12-31T01:01:01.001001001Z").unwrap(),
-        );
-        let stale_lockfile: TargetEnvironmentLockfile = serde_json::from_value(json).unwrap();
-
-        // It should not give us the potentially stale digest
-        assert!(stale_lockfile.env_digest(DUMMY_REG, TEST_ENV).is_none());
     }
 }
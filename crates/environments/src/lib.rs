@@ -3,6 +3,7 @@ use anyhow::{anyhow, Context};
 mod environment;
 mod loader;
 
+pub use environment::{register_local_trigger, EnvironmentResolution, LOCAL_ENVIRONMENT_ID};
 use environment::{CandidateWorld, CandidateWorlds, TargetEnvironment, TriggerType};
 pub use loader::ApplicationToValidate;
 use loader::ComponentToValidate;
@@ -39,12 +40,13 @@ pub async fn validate_application_against_environment_ids(
     env_ids: &[TargetEnvironmentRef],
     cache_root: Option<std::path::PathBuf>,
     app_dir: &std::path::Path,
+    resolution: EnvironmentResolution,
 ) -> anyhow::Result<TargetEnvironmentValidation> {
     if env_ids.is_empty() {
         return Ok(Default::default());
     }
 
-    let envs = TargetEnvironment::load_all(env_ids, cache_root, app_dir).await?;
+    let envs = TargetEnvironment::load_all(env_ids, cache_root, app_dir, resolution).await?;
     validate_application_against_environments(application, &envs).await
 }
 
@@ -41,11 +41,36 @@ impl ProviderResolver {
             .add_component_variables(component_id, variables)
     }
 
+    /// Adds component variable values to the Resolver, which may only refer
+    /// to the application variables granted by `allowed_variables`, if given.
+    /// See [`Resolver::add_component_variables_with_grants`].
+    pub fn add_component_variables_with_grants(
+        &mut self,
+        component_id: impl Into<String>,
+        variables: impl IntoIterator<Item = (String, String)>,
+        allowed_variables: Option<&[String]>,
+    ) -> Result<()> {
+        self.internal.add_component_variables_with_grants(
+            component_id,
+            variables,
+            allowed_variables,
+        )
+    }
+
     /// Adds a variable Provider to the Resolver.
     pub fn add_provider(&mut self, provider: Box<dyn Provider>) {
         self.providers.push(provider);
     }
 
+    /// Returns a copy of this Resolver which uses the given Providers in
+    /// place of this Resolver's Providers.
+    pub fn with_providers(&self, providers: impl IntoIterator<Item = Box<dyn Provider>>) -> Self {
+        Self {
+            internal: self.internal.clone(),
+            providers: providers.into_iter().collect(),
+        }
+    }
+
     /// Resolves a variable value for the given path.
     pub async fn resolve(&self, component_id: &str, key: Key<'_>) -> Result<String> {
         let template = self.internal.get_template(component_id, key)?;
@@ -101,7 +126,7 @@ impl ProviderResolver {
 }
 
 /// A variable resolver.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Resolver {
     // variable key -> variable
     variables: HashMap<String, Variable>,
@@ -126,14 +151,35 @@ impl Resolver {
         &mut self,
         component_id: impl Into<String>,
         variables: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<()> {
+        self.add_component_variables_with_grants(component_id, variables, None)
+    }
+
+    /// Adds component variable values to the Resolver.
+    ///
+    /// If `allowed_variables` is given, the values may only refer to
+    /// application variables whose names match one of its glob patterns
+    /// (e.g. `"public_*"`); referring to any other is an error. If it is not
+    /// given, the values may refer to any application variable.
+    pub fn add_component_variables_with_grants(
+        &mut self,
+        component_id: impl Into<String>,
+        variables: impl IntoIterator<Item = (String, String)>,
+        allowed_variables: Option<&[String]>,
     ) -> Result<()> {
         let component_id = component_id.into();
+        let grants = allowed_variables
+            .map(|patterns| VariableGrants::new(&component_id, patterns))
+            .transpose()?;
         let templates = variables
             .into_iter()
             .map(|(key, val)| {
                 // Validate variable keys so that we can rely on them during resolution
                 Key::validate(&key)?;
                 let template = self.validate_template(val)?;
+                if let Some(grants) = &grants {
+                    grants.check(&component_id, &key, &template)?;
+                }
                 Ok((key, template))
             })
             .collect::<Result<_>>()?;
@@ -200,6 +246,44 @@ impl Resolver {
     }
 }
 
+/// The application variables a component's variables may refer to.
+struct VariableGrants {
+    patterns: Vec<glob::Pattern>,
+}
+
+impl VariableGrants {
+    fn new(component_id: &str, patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|err| {
+                    Error::NotAllowed(format!(
+                        "component {component_id:?} has invalid allowed_application_variables pattern {pattern:?}: {err}"
+                    ))
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Checks that the template of the component variable `key` refers only
+    /// to granted application variables.
+    fn check(&self, component_id: &str, key: &str, template: &Template) -> Result<()> {
+        match template.variables().find(|var| !self.allows(var)) {
+            Some(var) => Err(Error::NotAllowed(format!(
+                "component {component_id:?} variable {key:?} refers to application variable {var:?}, \
+                 which is not granted by its allowed_application_variables {:?}",
+                self.patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn allows(&self, var: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(var))
+    }
+}
+
 /// A resolver who has resolved all variables.
 #[derive(Default)]
 pub struct PreparedResolver {
@@ -303,6 +387,11 @@ pub enum Error {
     /// Undefined variable.
     #[error("undefined variable: {0}")]
     Undefined(String),
+
+    /// Reference to an application variable which a component has not been
+    /// granted.
+    #[error("variable not allowed: {0}")]
+    NotAllowed(String),
 }
 
 #[cfg(test)]
@@ -387,6 +476,95 @@ mod tests {
         }
     }
 
+    fn granted_resolver() -> Resolver {
+        let variable = |default: &str| Variable {
+            description: None,
+            default: Some(default.into()),
+            secret: false,
+        };
+        Resolver::new([
+            ("api_host".into(), variable("api.example.com")),
+            ("public_name".into(), variable("Example")),
+            ("admin_token".into(), variable("s3cr3t")),
+        ])
+        .unwrap()
+    }
+
+    fn grants() -> Vec<String> {
+        vec!["api_host".into(), "public_*".into()]
+    }
+
+    #[test]
+    fn granted_variables_resolve() {
+        let mut resolver = granted_resolver();
+        resolver
+            .add_component_variables_with_grants(
+                "web",
+                [
+                    ("url".into(), "https://{{ api_host }}/".into()),
+                    ("title".into(), "{{ public_name }}".into()),
+                    ("literal".into(), "no references".into()),
+                ],
+                Some(&grants()),
+            )
+            .unwrap();
+        assert_eq!(
+            resolver.resolve("web", Key("url")).unwrap(),
+            "https://api.example.com/"
+        );
+        assert_eq!(resolver.resolve("web", Key("title")).unwrap(), "Example");
+    }
+
+    #[test]
+    fn ungranted_variables_are_rejected() {
+        let mut resolver = granted_resolver();
+        let err = resolver
+            .add_component_variables_with_grants(
+                "web",
+                [("token".into(), "Bearer {{ admin_token }}".into())],
+                Some(&grants()),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::NotAllowed(_)), "{err}");
+        let message = err.to_string();
+        for expected in [
+            "\"web\"",
+            "\"token\"",
+            "\"admin_token\"",
+            "[\"api_host\", \"public_*\"]",
+        ] {
+            assert!(message.contains(expected), "{message}");
+        }
+
+        // An empty grant list allows no references
+        let err = resolver
+            .add_component_variables_with_grants(
+                "web",
+                [("url".into(), "{{ api_host }}".into())],
+                Some(&[]),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::NotAllowed(_)), "{err}");
+    }
+
+    #[test]
+    fn variables_are_unrestricted_without_grants() {
+        let mut resolver = granted_resolver();
+        resolver
+            .add_component_variables("web", [("token".into(), "{{ admin_token }}".into())])
+            .unwrap();
+        assert_eq!(resolver.resolve("web", Key("token")).unwrap(), "s3cr3t");
+    }
+
+    #[test]
+    fn invalid_grant_patterns_are_rejected() {
+        let mut resolver = granted_resolver();
+        let err = resolver
+            .add_component_variables_with_grants("web", [], Some(&["public_[".into()]))
+            .unwrap_err();
+        assert!(err.to_string().contains("public_["), "{err}");
+    }
+
     #[test]
     fn template_literal() {
         assert!(Template::new("hello").unwrap().is_literal());
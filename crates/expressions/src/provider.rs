@@ -9,4 +9,12 @@ use crate::Key;
 pub trait Provider: Debug + Send + Sync {
     /// Returns the value at the given config path, if it exists.
     async fn get(&self, key: &Key) -> anyhow::Result<Option<String>>;
+
+    /// Returns a name identifying the kind of provider, e.g. in logs.
+    ///
+    /// This must not reveal any of the provider's configuration, which may
+    /// include credentials.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
 }
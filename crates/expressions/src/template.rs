@@ -46,6 +46,14 @@ impl Template {
         self.parts.iter().all(|p| matches!(p, Part::Lit(_)))
     }
 
+    /// The names of the variables the template refers to.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts().filter_map(|part| match part {
+            Part::Expr(var) => Some(var.as_ref()),
+            Part::Lit(_) => None,
+        })
+    }
+
     pub(crate) fn parts(&self) -> std::slice::Iter<'_, Part> {
         self.parts.iter()
     }
@@ -106,6 +114,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn template_variables() {
+        let template = Template::new("{{ a }}-b-{{ c }}").unwrap();
+        assert_eq!(template.variables().collect::<Vec<_>>(), ["a", "c"]);
+        assert_eq!(Template::new("a").unwrap().variables().count(), 0);
+    }
+
     #[test]
     fn template_parts_bad() {
         Template::new("{{ matched }} {{ unmatched").unwrap_err();
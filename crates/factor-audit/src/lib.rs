@@ -0,0 +1,162 @@
+//! An opt-in audit log of the privileged host operations made by components,
+//! such as key-value writes and outbound HTTP requests.
+//!
+//! Factors which perform such operations get an [`Auditor`] for each instance
+//! with [`Auditor::for_instance`], and record each operation with it. The
+//! [`AuditFactor`] must come before those factors in a
+//! [`RuntimeFactors`] collection; without it, nothing is recorded.
+
+mod log;
+pub mod runtime_config;
+
+use std::sync::{Arc, OnceLock};
+
+use chrono::Utc;
+use spin_factors::{
+    ConfigureAppContext, Error, Factor, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
+};
+use spin_locked_app::APP_NAME_KEY;
+
+pub use log::{AuditLog, AuditRecord, Outcome};
+pub use runtime_config::RuntimeConfig;
+
+/// The operations which may be audited.
+pub mod operation {
+    /// Setting a key-value pair, including by increment, compare-and-swap or
+    /// transaction.
+    pub const KEY_VALUE_SET: &str = "key_value.set";
+    /// Deleting a key-value pair, including by transaction.
+    pub const KEY_VALUE_DELETE: &str = "key_value.delete";
+    /// Sending an outbound HTTP request.
+    pub const OUTBOUND_HTTP_REQUEST: &str = "outbound_http.request";
+    /// Executing a SQLite statement.
+    pub const SQLITE_EXECUTE: &str = "sqlite.execute";
+
+    /// Every operation which may be audited.
+    pub const ALL: &[&str] = &[
+        KEY_VALUE_SET,
+        KEY_VALUE_DELETE,
+        OUTBOUND_HTTP_REQUEST,
+        SQLITE_EXECUTE,
+    ];
+}
+
+/// A factor which writes an audit log of privileged host operations, if
+/// enabled by runtime config.
+#[derive(Default)]
+pub struct AuditFactor {
+    _priv: (),
+}
+
+impl AuditFactor {
+    /// Create a new `AuditFactor`.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for AuditFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = Auditor;
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let Some(config) = ctx.take_runtime_config() else {
+            return Ok(AppState { log: None });
+        };
+        let app = ctx
+            .app()
+            .get_metadata(APP_NAME_KEY)?
+            .unwrap_or_else(|| "<unnamed>".into());
+        Ok(AppState {
+            log: Some(AuditLog::new(app, &config)?),
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<Self::InstanceBuilder> {
+        Ok(Auditor {
+            log: ctx.app_state().log.clone(),
+            component_id: ctx.app_component().id().into(),
+            request_id: Default::default(),
+        })
+    }
+}
+
+pub struct AppState {
+    /// The app's audit log, if auditing is enabled.
+    log: Option<AuditLog>,
+}
+
+impl AppState {
+    /// The app's audit log, if auditing is enabled.
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.log.as_ref()
+    }
+}
+
+/// Records the operations of one component instance in the app's audit log.
+///
+/// Clones share the instance's request ID, so that it may be set after the
+/// auditor has been handed to other factors.
+#[derive(Clone, Default)]
+pub struct Auditor {
+    log: Option<AuditLog>,
+    component_id: Arc<str>,
+    request_id: Arc<OnceLock<String>>,
+}
+
+impl Auditor {
+    /// Returns an auditor which records nothing.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Returns the auditor for the instance being prepared, which records
+    /// nothing if the collection has no [`AuditFactor`].
+    pub fn for_instance<T: RuntimeFactors, F: Factor>(
+        ctx: &mut PrepareContext<T, F>,
+    ) -> anyhow::Result<Self> {
+        match ctx.instance_builder::<AuditFactor>() {
+            Ok(auditor) => Ok(auditor.clone()),
+            Err(Error::NoSuchFactor(_)) => Ok(Self::disabled()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Whether the given operation is recorded, e.g. to skip preparing a
+    /// record's target.
+    pub fn includes(&self, operation: &str) -> bool {
+        self.log.as_ref().is_some_and(|log| log.includes(operation))
+    }
+
+    /// Sets the ID of the request the instance is handling. Only the first ID
+    /// set is used.
+    pub fn set_request_id(&self, request_id: impl Into<String>) {
+        _ = self.request_id.set(request_id.into());
+    }
+
+    /// Records an operation on the given target, if the operation is
+    /// included in the audit log.
+    pub fn record(&self, operation: &'static str, target: &str, outcome: impl Into<Outcome>) {
+        let Some(log) = self.log.as_ref().filter(|log| log.includes(operation)) else {
+            return;
+        };
+        log.record(AuditRecord {
+            timestamp: Utc::now(),
+            app: log.app().to_owned(),
+            component_id: self.component_id.to_string(),
+            operation,
+            target: target.to_owned(),
+            outcome: outcome.into(),
+            request_id: self.request_id.get().cloned(),
+        });
+    }
+}
+
+impl SelfInstanceBuilder for Auditor {}
@@ -0,0 +1,324 @@
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+};
+
+use anyhow::Context as _;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::runtime_config::{AuditSink, RuntimeConfig};
+
+/// A record of one privileged host operation.
+///
+/// Records identify what was operated on, such as a key or a host, but never
+/// carry the data involved, such as values or query parameters.
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditRecord {
+    /// When the operation completed.
+    pub timestamp: DateTime<Utc>,
+    /// The name of the app.
+    pub app: String,
+    /// The ID of the component which performed the operation.
+    pub component_id: String,
+    /// The operation, e.g. `key_value.set`; see [`crate::operation`].
+    pub operation: &'static str,
+    /// What the operation acted on: a key, a host or a database label.
+    pub target: String,
+    /// Whether the operation succeeded.
+    pub outcome: Outcome,
+    /// The ID of the request the component was handling, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Whether an audited operation succeeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl<T, E> From<&Result<T, E>> for Outcome {
+    fn from(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(_) => Self::Failure,
+        }
+    }
+}
+
+/// An app's audit log.
+///
+/// Records are queued and written to the sinks by a background thread, so
+/// that a slow sink never holds up the operations being audited. If the
+/// queue is full, records are dropped and counted in
+/// [`AuditLog::dropped_records`].
+#[derive(Clone)]
+pub struct AuditLog {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    app: String,
+    include: HashSet<String>,
+    sender: SyncSender<Message>,
+    dropped: AtomicU64,
+}
+
+enum Message {
+    Record(Box<AuditRecord>),
+    /// Flushes the sinks, then acknowledges on the given channel.
+    Flush(mpsc::Sender<()>),
+}
+
+impl AuditLog {
+    /// Opens the configured sinks and starts writing records for the given
+    /// app to them.
+    pub fn new(app: impl Into<String>, config: &RuntimeConfig) -> anyhow::Result<Self> {
+        let sinks = config
+            .sinks
+            .iter()
+            .map(|sink| -> anyhow::Result<Box<dyn Write + Send>> {
+                Ok(match sink {
+                    AuditSink::Stdout => Box::new(BufWriter::new(io::stdout())),
+                    AuditSink::Stderr => Box::new(BufWriter::new(io::stderr())),
+                    AuditSink::File(path) => {
+                        let file = OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(path)
+                            .with_context(|| {
+                                format!("failed to open audit log file {}", path.display())
+                            })?;
+                        Box::new(BufWriter::new(file))
+                    }
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Self::with_sinks(app, config, sinks)
+    }
+
+    /// Starts writing records for the given app to the given sinks, which
+    /// are flushed whenever the queue empties.
+    pub(crate) fn with_sinks(
+        app: impl Into<String>,
+        config: &RuntimeConfig,
+        sinks: Vec<Box<dyn Write + Send>>,
+    ) -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity);
+        std::thread::Builder::new()
+            .name("spin-audit-log".into())
+            .spawn(move || write_records(receiver, sinks))
+            .context("failed to start audit log writer")?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                app: app.into(),
+                include: config.include.clone(),
+                sender,
+                dropped: AtomicU64::new(0),
+            }),
+        })
+    }
+
+    /// The name of the audited app.
+    pub fn app(&self) -> &str {
+        &self.inner.app
+    }
+
+    /// Whether the given operation is recorded.
+    pub fn includes(&self, operation: &str) -> bool {
+        self.inner.include.contains(operation)
+    }
+
+    /// Queues a record to be written, or drops it if the queue is full. Never
+    /// blocks.
+    pub fn record(&self, record: AuditRecord) {
+        match self
+            .inner
+            .sender
+            .try_send(Message::Record(Box::new(record)))
+        {
+            Ok(()) => (),
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => {
+                // Only warn the first time, as a flooded sink drops many
+                if self.inner.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    tracing::warn!(
+                        "Audit log records are being dropped because the audit log can't keep up"
+                    );
+                }
+            }
+        }
+    }
+
+    /// The number of records which have been dropped because the queue was
+    /// full.
+    pub fn dropped_records(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the records queued so far to be written and the sinks to be
+    /// flushed, e.g. at shutdown.
+    pub fn flush(&self) {
+        let (done_sender, done) = mpsc::channel();
+        if self.inner.sender.send(Message::Flush(done_sender)).is_ok() {
+            _ = done.recv();
+        }
+    }
+}
+
+/// Writes queued records until every [`AuditLog`] handle has been dropped.
+fn write_records(receiver: Receiver<Message>, mut sinks: Vec<Box<dyn Write + Send>>) {
+    let flush = |sinks: &mut Vec<Box<dyn Write + Send>>| {
+        for sink in sinks {
+            if let Err(err) = sink.flush() {
+                tracing::warn!("Failed to write audit log: {err}");
+            }
+        }
+    };
+    while let Ok(message) = receiver.recv() {
+        // Write everything which is already queued before flushing
+        let mut next = Some(message);
+        while let Some(message) = next.take().or_else(|| receiver.try_recv().ok()) {
+            match message {
+                Message::Record(record) => {
+                    let line = serde_json::to_string(&record)
+                        .expect("audit records are always serializable");
+                    for sink in &mut sinks {
+                        if let Err(err) = writeln!(sink, "{line}") {
+                            tracing::warn!("Failed to write audit log: {err}");
+                        }
+                    }
+                }
+                Message::Flush(done) => {
+                    flush(&mut sinks);
+                    _ = done.send(());
+                }
+            }
+        }
+        flush(&mut sinks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A sink which writes to a shared buffer, once each write is released.
+    #[derive(Clone)]
+    struct TestSink {
+        written: Arc<Mutex<Vec<u8>>>,
+        release: Arc<Mutex<Receiver<()>>>,
+    }
+
+    impl Write for TestSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            // Once the release sender is dropped, writes are no longer held up
+            _ = self.release.lock().unwrap().recv();
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn config(queue_capacity: usize) -> RuntimeConfig {
+        RuntimeConfig {
+            sinks: vec![],
+            include: ["key_value.set".to_owned()].into(),
+            queue_capacity,
+        }
+    }
+
+    fn record(target: &str) -> AuditRecord {
+        AuditRecord {
+            timestamp: Utc::now(),
+            app: "test-app".into(),
+            component_id: "test-component".into(),
+            operation: "key_value.set",
+            target: target.into(),
+            outcome: Outcome::Success,
+            request_id: None,
+        }
+    }
+
+    fn lines(written: &Mutex<Vec<u8>>) -> Vec<serde_json::Value> {
+        let written = written.lock().unwrap();
+        std::str::from_utf8(&written)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn records_are_written_as_json_lines() {
+        let (release_sender, release) = mpsc::channel();
+        drop(release_sender);
+        let sink = TestSink {
+            written: Default::default(),
+            release: Arc::new(Mutex::new(release)),
+        };
+        let log =
+            AuditLog::with_sinks("test-app", &config(8), vec![Box::new(sink.clone())]).unwrap();
+        assert!(log.includes("key_value.set"));
+        assert!(!log.includes("key_value.delete"));
+
+        log.record(record("first"));
+        log.record(AuditRecord {
+            outcome: Outcome::Failure,
+            request_id: Some("req-1".into()),
+            ..record("second")
+        });
+        log.flush();
+
+        let lines = lines(&sink.written);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["target"], "first");
+        assert_eq!(lines[0]["outcome"], "success");
+        assert!(lines[0].get("request_id").is_none());
+        assert_eq!(lines[1]["target"], "second");
+        assert_eq!(lines[1]["outcome"], "failure");
+        assert_eq!(lines[1]["request_id"], "req-1");
+        assert_eq!(log.dropped_records(), 0);
+    }
+
+    #[test]
+    fn records_are_dropped_when_the_sink_is_flooded() {
+        let (release_sender, release) = mpsc::channel();
+        let sink = TestSink {
+            written: Default::default(),
+            release: Arc::new(Mutex::new(release)),
+        };
+        let capacity = 2;
+        let log = AuditLog::with_sinks("test-app", &config(capacity), vec![Box::new(sink.clone())])
+            .unwrap();
+
+        // The writer holds at most one record while blocked on the sink, and
+        // the queue holds `capacity` more
+        let count = 10;
+        for i in 0..count {
+            log.record(record(&i.to_string()));
+        }
+        let dropped = log.dropped_records();
+        assert!(
+            dropped >= (count - capacity - 1) as u64,
+            "only {dropped} records dropped"
+        );
+
+        drop(release_sender);
+        log.flush();
+        assert_eq!(lines(&sink.written).len() as u64, count as u64 - dropped);
+    }
+}
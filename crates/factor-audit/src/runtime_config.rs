@@ -0,0 +1,53 @@
+#[cfg(feature = "spin-cli")]
+pub mod spin;
+
+use std::{collections::HashSet, path::PathBuf, str::FromStr};
+
+/// Runtime configuration for the audit log.
+///
+/// An app without runtime config for the audit factor isn't audited.
+#[derive(Clone, Debug)]
+pub struct RuntimeConfig {
+    /// Where records are written.
+    pub sinks: Vec<AuditSink>,
+    /// The operations which are recorded; see [`crate::operation`].
+    pub include: HashSet<String>,
+    /// The number of records which may wait to be written before further
+    /// records are dropped.
+    pub queue_capacity: usize,
+}
+
+impl RuntimeConfig {
+    /// The default [`RuntimeConfig::queue_capacity`].
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+}
+
+/// A destination for audit records, which are written one JSON object per
+/// line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditSink {
+    /// The standard output of the Spin process.
+    Stdout,
+    /// The standard error of the Spin process.
+    Stderr,
+    /// A file, which records are appended to.
+    File(PathBuf),
+}
+
+impl FromStr for AuditSink {
+    type Err = anyhow::Error;
+
+    /// Parses `stdout`, `stderr` or `file:<path>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdout" => Ok(Self::Stdout),
+            "stderr" => Ok(Self::Stderr),
+            _ => match s.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(Self::File(path.into())),
+                _ => anyhow::bail!(
+                    "invalid audit sink {s:?}: expected \"stdout\", \"stderr\" or \"file:<path>\""
+                ),
+            },
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::Context as _;
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+use super::{AuditSink, RuntimeConfig};
+use crate::operation;
+
+/// Get the runtime configuration for the audit log from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [audit]
+/// enabled = true
+/// sinks = ["file:/var/log/spin-audit.jsonl"]
+/// # Optional; defaults to every operation
+/// include = ["key_value.set", "key_value.delete", "outbound_http.request", "sqlite.execute"]
+/// # Optional; defaults to 1024
+/// queue_capacity = 1024
+/// ```
+///
+/// Returns `None` if there is no `[audit]` section or it isn't enabled.
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(audit) = table.get("audit") else {
+        return Ok(None);
+    };
+    let audit = audit
+        .clone()
+        .try_into::<AuditToml>()
+        .context("invalid `[audit]` runtime config")?;
+    if !audit.enabled {
+        return Ok(None);
+    }
+
+    anyhow::ensure!(
+        !audit.sinks.is_empty(),
+        "`audit.sinks` must list at least one sink when auditing is enabled"
+    );
+    let sinks = audit
+        .sinks
+        .iter()
+        .map(|sink| sink.parse())
+        .collect::<anyhow::Result<Vec<AuditSink>>>()?;
+
+    let include = match audit.include {
+        Some(include) => {
+            for op in &include {
+                anyhow::ensure!(
+                    operation::ALL.contains(&op.as_str()),
+                    "unknown operation {op:?} in `audit.include`; expected one of {}",
+                    operation::ALL.join(", ")
+                );
+            }
+            include.into_iter().collect()
+        }
+        None => operation::ALL.iter().map(|op| op.to_string()).collect(),
+    };
+
+    let queue_capacity = audit
+        .queue_capacity
+        .unwrap_or(RuntimeConfig::DEFAULT_QUEUE_CAPACITY);
+    anyhow::ensure!(
+        queue_capacity > 0,
+        "`audit.queue_capacity` must be greater than zero"
+    );
+
+    Ok(Some(RuntimeConfig {
+        sinks,
+        include,
+        queue_capacity,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuditToml {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    sinks: Vec<String>,
+    include: Option<Vec<String>>,
+    queue_capacity: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: toml::Table) -> anyhow::Result<Option<RuntimeConfig>> {
+        config_from_table(&toml)
+    }
+
+    #[test]
+    fn audit_is_opt_in() {
+        assert!(config(toml::Table::new()).unwrap().is_none());
+        let toml = toml::toml! {
+            [audit]
+            sinks = ["stdout"]
+        };
+        assert!(config(toml).unwrap().is_none());
+    }
+
+    #[test]
+    fn config_is_parsed() {
+        let toml = toml::toml! {
+            [audit]
+            enabled = true
+            sinks = ["file:/var/log/spin-audit.jsonl", "stderr"]
+            include = ["key_value.set", "sqlite.execute"]
+        };
+        let config = config(toml).unwrap().unwrap();
+        assert_eq!(
+            config.sinks,
+            [
+                AuditSink::File("/var/log/spin-audit.jsonl".into()),
+                AuditSink::Stderr
+            ]
+        );
+        let mut include = config
+            .include
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        include.sort();
+        assert_eq!(include, ["key_value.set", "sqlite.execute"]);
+        assert_eq!(config.queue_capacity, RuntimeConfig::DEFAULT_QUEUE_CAPACITY);
+
+        let toml = toml::toml! {
+            [audit]
+            enabled = true
+            sinks = ["stdout"]
+        };
+        assert_eq!(
+            config(toml).unwrap().unwrap().include.len(),
+            operation::ALL.len()
+        );
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let invalid = [
+            toml::toml! {
+                [audit]
+                enabled = true
+            },
+            toml::toml! {
+                [audit]
+                enabled = true
+                sinks = ["syslog"]
+            },
+            toml::toml! {
+                [audit]
+                enabled = true
+                sinks = ["stdout"]
+                include = ["key_value.get"]
+            },
+            toml::toml! {
+                [audit]
+                enabled = true
+                sinks = ["stdout"]
+                queue_capacity = 0
+            },
+        ];
+        for toml in invalid {
+            assert!(config(toml.clone()).is_err(), "{toml}");
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::bail;
+use spin_core::async_trait;
+
+use crate::{
+    Cas, CasSupport, Error, KeysPage, Store, StoreManager, StoreStats, SwapError, TxError, TxOp,
+};
+
+/// A store label which refers to the store of another label, as configured by
+/// `type = "alias"` in runtime config.
+#[derive(Clone, Debug)]
+pub struct StoreAlias {
+    /// The label of the store the alias refers to. This may itself be an
+    /// alias.
+    pub target: String,
+    /// Whether the alias only allows reading the target store.
+    pub read_only: bool,
+}
+
+/// Adds a [`StoreManager`] for each alias which opens the store at the end of
+/// its chain of aliases, returning the chain of labels for each alias.
+///
+/// Errors if an alias refers to an undefined label or, directly or through
+/// other aliases, to itself.
+pub(crate) fn resolve_aliases(
+    store_managers: &mut HashMap<String, Arc<dyn StoreManager>>,
+    aliases: &HashMap<String, StoreAlias>,
+) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let mut resolved = Vec::with_capacity(aliases.len());
+    let mut chains = HashMap::with_capacity(aliases.len());
+    for label in aliases.keys() {
+        let mut chain = vec![label.clone()];
+        let mut read_only = false;
+        let mut current = label;
+        while let Some(alias) = aliases.get(current) {
+            read_only |= alias.read_only;
+            if chain.contains(&alias.target) {
+                chain.push(alias.target.clone());
+                bail!(
+                    "key-value store aliases form a cycle: {}",
+                    quoted_chain(&chain)
+                );
+            }
+            chain.push(alias.target.clone());
+            current = &alias.target;
+        }
+        let Some(target_manager) = store_managers.get(current) else {
+            bail!(
+                "key-value store alias {label:?} refers to undefined store {current:?}: {}",
+                quoted_chain(&chain)
+            );
+        };
+        resolved.push((
+            label.clone(),
+            Arc::new(AliasStoreManager {
+                target: current.clone(),
+                target_manager: target_manager.clone(),
+                chain: chain.clone(),
+                read_only,
+            }) as Arc<dyn StoreManager>,
+        ));
+        chains.insert(label.clone(), chain);
+    }
+    store_managers.extend(resolved);
+    Ok(chains)
+}
+
+/// Formats a chain of labels as `"a" -> "b" -> "c"`.
+pub(crate) fn quoted_chain(chain: &[String]) -> String {
+    chain
+        .iter()
+        .map(|label| format!("{label:?}"))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// A [`StoreManager`] for an alias, which opens the store of its target label.
+pub(crate) struct AliasStoreManager {
+    /// The label of the store at the end of the alias chain.
+    target: String,
+    target_manager: Arc<dyn StoreManager>,
+    /// The labels from the alias to the target, inclusive.
+    chain: Vec<String>,
+    /// Whether any alias in the chain is read-only.
+    read_only: bool,
+}
+
+#[async_trait]
+impl StoreManager for AliasStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let _ = name;
+        let store = self.target_manager.get(&self.target).await?;
+        if self.read_only {
+            Ok(Arc::new(ReadOnlyStore { inner: store }))
+        } else {
+            Ok(store)
+        }
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        let _ = store_name;
+        self.target_manager.is_defined(&self.target)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        let _ = store_name;
+        let access = if self.read_only { "read-only " } else { "" };
+        let alias = format!("{access}alias {}", quoted_chain(&self.chain));
+        Some(match self.target_manager.summary(&self.target) {
+            Some(summary) => format!("{summary} (via {alias})"),
+            None => alias,
+        })
+    }
+
+    async fn stats(&self, store_name: &str) -> Option<StoreStats> {
+        let _ = store_name;
+        self.target_manager.stats(&self.target).await
+    }
+}
+
+/// A [`Store`] which rejects every write with [`Error::AccessDenied`].
+struct ReadOnlyStore {
+    inner: Arc<dyn Store>,
+}
+
+#[async_trait]
+impl Store for ReadOnlyStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.get(key).await
+    }
+
+    async fn set(&self, _key: &str, _value: &[u8]) -> Result<(), Error> {
+        Err(Error::AccessDenied)
+    }
+
+    async fn delete(&self, _key: &str) -> Result<(), Error> {
+        Err(Error::AccessDenied)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.inner.get_keys().await
+    }
+
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        self.inner.get_keys_page(cursor, limit).await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.inner.get_many(keys).await
+    }
+
+    async fn set_many(&self, _key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        Err(Error::AccessDenied)
+    }
+
+    async fn delete_many(&self, _keys: Vec<String>) -> Result<(), Error> {
+        Err(Error::AccessDenied)
+    }
+
+    async fn increment(&self, _key: String, _delta: i64) -> Result<i64, Error> {
+        Err(Error::AccessDenied)
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        let inner = self.inner.new_compare_and_swap(bucket_rep, key).await?;
+        Ok(Arc::new(ReadOnlyCas { inner }))
+    }
+
+    fn compare_and_swap_support(&self) -> CasSupport {
+        self.inner.compare_and_swap_support()
+    }
+
+    fn supports_atomic_increment(&self) -> bool {
+        self.inner.supports_atomic_increment()
+    }
+
+    fn supports_transactions(&self) -> bool {
+        self.inner.supports_transactions()
+    }
+
+    async fn transact(&self, _ops: Vec<TxOp>) -> Result<(), TxError> {
+        Err(TxError::Store(Error::AccessDenied))
+    }
+}
+
+/// A [`Cas`] which can read the current value but not swap it.
+struct ReadOnlyCas {
+    inner: Arc<dyn Cas>,
+}
+
+#[async_trait]
+impl Cas for ReadOnlyCas {
+    async fn current(&self) -> anyhow::Result<Option<Vec<u8>>, Error> {
+        self.inner.current().await
+    }
+
+    async fn swap(&self, _value: Vec<u8>) -> anyhow::Result<(), SwapError> {
+        Err(SwapError::Other(
+            "access denied: the store is read-only".into(),
+        ))
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.inner.bucket_rep().await
+    }
+
+    async fn key(&self) -> String {
+        self.inner.key().await
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use spin_core::async_trait;
+use spin_factor_audit::{operation, Auditor, Outcome};
+
+use crate::{Cas, CasSupport, Error, KeysPage, Store, SwapError, TxError, TxOp, TxWrite};
+
+/// A [`Store`] which records its writes in the audit log. Only keys are
+/// recorded, never values.
+pub(crate) struct AuditedStore {
+    inner: Arc<dyn Store>,
+    auditor: Auditor,
+}
+
+impl AuditedStore {
+    /// Wraps the store if the auditor records any key-value operations.
+    pub(crate) fn wrap(inner: Arc<dyn Store>, auditor: &Auditor) -> Arc<dyn Store> {
+        if auditor.includes(operation::KEY_VALUE_SET)
+            || auditor.includes(operation::KEY_VALUE_DELETE)
+        {
+            Arc::new(Self {
+                inner,
+                auditor: auditor.clone(),
+            })
+        } else {
+            inner
+        }
+    }
+
+    fn record_all(&self, operation: &'static str, keys: &[String], outcome: Outcome) {
+        for key in keys {
+            self.auditor.record(operation, key, outcome);
+        }
+    }
+}
+
+#[async_trait]
+impl Store for AuditedStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.get(key).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        let result = self.inner.set(key, value).await;
+        self.auditor.record(operation::KEY_VALUE_SET, key, &result);
+        result
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let result = self.inner.delete(key).await;
+        self.auditor
+            .record(operation::KEY_VALUE_DELETE, key, &result);
+        result
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.inner.get_keys().await
+    }
+
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        self.inner.get_keys_page(cursor, limit).await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.inner.get_many(keys).await
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        let keys = key_values
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        let result = self.inner.set_many(key_values).await;
+        self.record_all(operation::KEY_VALUE_SET, &keys, (&result).into());
+        result
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        let audited_keys = keys.clone();
+        let result = self.inner.delete_many(keys).await;
+        self.record_all(operation::KEY_VALUE_DELETE, &audited_keys, (&result).into());
+        result
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let result = self.inner.increment(key.clone(), delta).await;
+        self.auditor.record(operation::KEY_VALUE_SET, &key, &result);
+        result
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        let inner = self.inner.new_compare_and_swap(bucket_rep, key).await?;
+        Ok(Arc::new(AuditedCas {
+            inner,
+            key: key.to_owned(),
+            auditor: self.auditor.clone(),
+        }))
+    }
+
+    fn compare_and_swap_support(&self) -> CasSupport {
+        self.inner.compare_and_swap_support()
+    }
+
+    fn supports_atomic_increment(&self) -> bool {
+        self.inner.supports_atomic_increment()
+    }
+
+    fn supports_transactions(&self) -> bool {
+        self.inner.supports_transactions()
+    }
+
+    async fn transact(&self, ops: Vec<TxOp>) -> Result<(), TxError> {
+        let writes = ops
+            .iter()
+            .map(|op| {
+                let operation = match op.write {
+                    TxWrite::Set(_) => operation::KEY_VALUE_SET,
+                    TxWrite::Delete => operation::KEY_VALUE_DELETE,
+                };
+                (operation, op.key.clone())
+            })
+            .collect::<Vec<_>>();
+        let result = self.inner.transact(ops).await;
+        let outcome = Outcome::from(&result);
+        for (operation, key) in writes {
+            self.auditor.record(operation, &key, outcome);
+        }
+        result
+    }
+}
+
+/// A [`Cas`] which records its swaps in the audit log.
+struct AuditedCas {
+    inner: Arc<dyn Cas>,
+    key: String,
+    auditor: Auditor,
+}
+
+#[async_trait]
+impl Cas for AuditedCas {
+    async fn current(&self) -> anyhow::Result<Option<Vec<u8>>, Error> {
+        self.inner.current().await
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> anyhow::Result<(), SwapError> {
+        let result = self.inner.swap(value).await;
+        self.auditor
+            .record(operation::KEY_VALUE_SET, &self.key, &result);
+        result
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.inner.bucket_rep().await
+    }
+
+    async fn key(&self) -> String {
+        self.inner.key().await
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use crate::{Error, Store, TxCondition, TxError, TxOp, TxWrite};
+
+/// `BatchError` are errors that occur when reading or writing several keys in
+/// one call.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum BatchError {
+    /// The operation failed as a whole, and no keys were changed.
+    #[error("{0:?}")]
+    Store(Error),
+
+    /// The operation succeeded for some keys but failed for these.
+    #[error("batch operation failed for {} keys", .0.len())]
+    PartialFailure(Vec<(String, Error)>),
+}
+
+/// Gets the values of the given keys.
+///
+/// If the store fails to get them together, they are retried one by one to
+/// find out which keys failed.
+pub(crate) async fn get_many(
+    store: &dyn Store,
+    keys: Vec<String>,
+) -> Result<Vec<(String, Option<Vec<u8>>)>, BatchError> {
+    if keys.is_empty() {
+        return Ok(vec![]);
+    }
+    let batch_err = match store.get_many(keys.clone()).await {
+        Ok(values) => return Ok(values),
+        Err(err) => err,
+    };
+    let total = keys.len();
+    let mut values = Vec::with_capacity(total);
+    let mut failed = vec![];
+    for key in keys {
+        match store.get(&key).await {
+            Ok(value) => values.push((key, value)),
+            Err(err) => failed.push((key, err)),
+        }
+    }
+    outcome(values, total, failed, batch_err)
+}
+
+/// Sets the given keys. If a key is given more than once, its last value is
+/// kept.
+///
+/// Stores which support transactions set all of the keys or none of them.
+/// Otherwise, if the store fails to set the keys together, they are retried
+/// one by one to find out which keys failed.
+pub(crate) async fn set_many(
+    store: &dyn Store,
+    key_values: Vec<(String, Vec<u8>)>,
+) -> Result<(), BatchError> {
+    if key_values.is_empty() {
+        return Ok(());
+    }
+    if store.supports_transactions() {
+        let writes = key_values
+            .into_iter()
+            .map(|(key, value)| (key, TxWrite::Set(value)));
+        return transact(store, writes).await;
+    }
+    let Err(batch_err) = store.set_many(key_values.clone()).await else {
+        return Ok(());
+    };
+    let total = key_values.len();
+    let mut failed = vec![];
+    for (key, value) in key_values {
+        if let Err(err) = store.set(&key, &value).await {
+            failed.push((key, err));
+        }
+    }
+    outcome((), total, failed, batch_err)
+}
+
+/// Deletes the given keys.
+///
+/// Stores which support transactions delete all of the keys or none of them.
+/// Otherwise, if the store fails to delete the keys together, they are
+/// retried one by one to find out which keys failed.
+pub(crate) async fn delete_many(store: &dyn Store, keys: Vec<String>) -> Result<(), BatchError> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    if store.supports_transactions() {
+        let writes = keys.into_iter().map(|key| (key, TxWrite::Delete));
+        return transact(store, writes).await;
+    }
+    let Err(batch_err) = store.delete_many(keys.clone()).await else {
+        return Ok(());
+    };
+    let total = keys.len();
+    let mut failed = vec![];
+    for key in keys {
+        if let Err(err) = store.delete(&key).await {
+            failed.push((key, err));
+        }
+    }
+    outcome((), total, failed, batch_err)
+}
+
+/// Applies the writes as a single unconditional transaction. A key written
+/// more than once keeps its last write.
+async fn transact(
+    store: &dyn Store,
+    writes: impl Iterator<Item = (String, TxWrite)>,
+) -> Result<(), BatchError> {
+    let mut ops: Vec<TxOp> = vec![];
+    let mut op_indices = HashMap::new();
+    for (key, write) in writes {
+        match op_indices.get(&key) {
+            Some(&index) => ops[index].write = write,
+            None => {
+                op_indices.insert(key.clone(), ops.len());
+                ops.push(TxOp {
+                    key,
+                    condition: TxCondition::Unconditional,
+                    write,
+                });
+            }
+        }
+    }
+    store.transact(ops).await.map_err(|err| match err {
+        TxError::Store(err) => BatchError::Store(err),
+        err => BatchError::Store(Error::Other(err.to_string())),
+    })
+}
+
+/// Reports the outcome of retrying a failed batch one key at a time: if
+/// every key failed again, the batch's own error is returned.
+fn outcome<T>(
+    value: T,
+    total: usize,
+    failed: Vec<(String, Error)>,
+    batch_err: Error,
+) -> Result<T, BatchError> {
+    if failed.is_empty() {
+        Ok(value)
+    } else if failed.len() == total {
+        Err(BatchError::Store(batch_err))
+    } else {
+        Err(BatchError::PartialFailure(failed))
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+
+use spin_core::async_trait;
+
+use crate::{
+    Cas, DelegatingStoreManager, Error, KeysPage, Store, StoreManager, StoreStats, SwapError,
+    TxError, TxOp,
+};
+
+/// How a store supports compare-and-swap, as reported by
+/// [`Store::compare_and_swap_support`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CasSupport {
+    /// The backend implements compare-and-swap, so swaps are atomic with
+    /// respect to every writer of the store.
+    Native,
+    /// Spin compares and writes the value under a per-key lock. Swaps are only
+    /// atomic with respect to other swaps made by this process.
+    Emulated,
+    /// Compare-and-swap is not available.
+    None,
+}
+
+/// A [`StoreManager`] which gives every store compare-and-swap, emulating it
+/// for backends which don't support it natively.
+pub(crate) struct CasStoreManager {
+    inner: DelegatingStoreManager,
+    /// The compare-and-swap support chosen for each store label opened so far.
+    chosen: Mutex<HashMap<String, CasSupport>>,
+    /// The locks held by emulated swaps, by store label and key.
+    locks: Arc<KeyLocks>,
+}
+
+impl CasStoreManager {
+    pub(crate) fn new(inner: DelegatingStoreManager) -> Self {
+        Self {
+            inner,
+            chosen: Default::default(),
+            locks: Default::default(),
+        }
+    }
+
+    /// Returns the compare-and-swap support for the given store label,
+    /// opening the store to choose it if necessary.
+    pub(crate) async fn cas_support(&self, name: &str) -> Option<CasSupport> {
+        let chosen = self.chosen.lock().unwrap().get(name).copied();
+        if chosen.is_some() {
+            return chosen;
+        }
+        let store = self.inner.get(name).await.ok()?;
+        Some(self.choose(name, store.as_ref()).await)
+    }
+
+    /// Chooses native compare-and-swap if the store declares it and can create
+    /// one, falling back to emulation otherwise.
+    async fn choose(&self, name: &str, store: &dyn Store) -> CasSupport {
+        let support = match store.compare_and_swap_support() {
+            CasSupport::Native => match store.new_compare_and_swap(u32::MAX, "").await {
+                Ok(_) => CasSupport::Native,
+                Err(err) => {
+                    tracing::warn!(
+                        "key-value store {name:?} failed to create a compare-and-swap ({err}); \
+                         falling back to emulation"
+                    );
+                    CasSupport::Emulated
+                }
+            },
+            support => support,
+        };
+        let mut chosen = self.chosen.lock().unwrap();
+        *chosen.entry(name.to_owned()).or_insert_with(|| {
+            tracing::info!("key-value store {name:?} uses {support:?} compare-and-swap");
+            support
+        })
+    }
+}
+
+#[async_trait]
+impl StoreManager for CasStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let store = self.inner.get(name).await?;
+        let chosen = self.chosen.lock().unwrap().get(name).copied();
+        let cas_support = match chosen {
+            Some(support) => support,
+            None => self.choose(name, store.as_ref()).await,
+        };
+        Ok(Arc::new(CasStore {
+            inner: store,
+            name: name.to_owned(),
+            cas_support,
+            locks: self.locks.clone(),
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.inner.summary(store_name)
+    }
+
+    async fn stats(&self, store_name: &str) -> Option<StoreStats> {
+        self.inner.stats(store_name).await
+    }
+}
+
+/// The locks held by emulated swaps.
+///
+/// Entries are weak so that the locks of keys which are no longer being
+/// swapped are freed.
+#[derive(Default)]
+struct KeyLocks(Mutex<HashMap<(String, String), Weak<tokio::sync::Mutex<()>>>>);
+
+impl KeyLocks {
+    fn get(&self, name: &str, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.0.lock().unwrap();
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        let lock = locks.entry((name.to_owned(), key.to_owned())).or_default();
+        lock.upgrade().unwrap_or_else(|| {
+            let new_lock = Arc::default();
+            *lock = Arc::downgrade(&new_lock);
+            new_lock
+        })
+    }
+}
+
+/// A [`Store`] whose compare-and-swaps use the support chosen by
+/// [`CasStoreManager`].
+struct CasStore {
+    inner: Arc<dyn Store>,
+    name: String,
+    cas_support: CasSupport,
+    locks: Arc<KeyLocks>,
+}
+
+#[async_trait]
+impl Store for CasStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.get(key).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.inner.set(key, value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.inner.get_keys().await
+    }
+
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        self.inner.get_keys_page(cursor, limit).await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.inner.get_many(keys).await
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        self.inner.set_many(key_values).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        self.inner.delete_many(keys).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        self.inner.increment(key, delta).await
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        match self.cas_support {
+            CasSupport::Native => self.inner.new_compare_and_swap(bucket_rep, key).await,
+            CasSupport::Emulated => Ok(Arc::new(EmulatedCas {
+                store: self.inner.clone(),
+                lock: self.locks.get(&self.name, key),
+                key: key.to_owned(),
+                bucket_rep,
+                expected: Mutex::new(None),
+            })),
+            CasSupport::None => Err(Error::Other(format!(
+                "key-value store {:?} does not support compare-and-swap",
+                self.name
+            ))),
+        }
+    }
+
+    fn compare_and_swap_support(&self) -> CasSupport {
+        self.cas_support
+    }
+
+    fn supports_atomic_increment(&self) -> bool {
+        self.inner.supports_atomic_increment()
+    }
+
+    fn supports_transactions(&self) -> bool {
+        self.inner.supports_transactions()
+    }
+
+    async fn transact(&self, ops: Vec<TxOp>) -> Result<(), TxError> {
+        self.inner.transact(ops).await
+    }
+}
+
+/// A compare-and-swap which compares and writes the value while holding a
+/// lock shared by all emulated swaps of the key.
+struct EmulatedCas {
+    store: Arc<dyn Store>,
+    lock: Arc<tokio::sync::Mutex<()>>,
+    key: String,
+    bucket_rep: u32,
+    /// The value read by `current`, which `swap` expects to replace. A swap
+    /// made without reading expects the key to be absent.
+    expected: Mutex<Option<Vec<u8>>>,
+}
+
+#[async_trait]
+impl Cas for EmulatedCas {
+    async fn current(&self) -> Result<Option<Vec<u8>>, Error> {
+        let value = self.store.get(&self.key).await?;
+        self.expected.lock().unwrap().clone_from(&value);
+        Ok(value)
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        let _guard = self.lock.lock().await;
+        let current = self
+            .store
+            .get(&self.key)
+            .await
+            .map_err(|err| SwapError::Other(err.to_string()))?;
+        if current != *self.expected.lock().unwrap() {
+            return Err(SwapError::CasFailed(format!(
+                "value of {:?} changed since it was read",
+                self.key
+            )));
+        }
+        self.store
+            .set(&self.key, &value)
+            .await
+            .map_err(|err| SwapError::Other(err.to_string()))
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.bucket_rep
+    }
+
+    async fn key(&self) -> String {
+        self.key.clone()
+    }
+}
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use anyhow::{ensure, Context};
+
+use crate::{AppState, Error, Store};
+
+/// How often (in keys examined) progress of a copy is logged.
+const PROGRESS_LOG_INTERVAL: usize = 1000;
+
+/// Options for [`AppState::copy_store`].
+#[derive(Clone, Debug)]
+pub struct CopyOptions {
+    /// Only keys starting with this prefix are copied.
+    pub prefix: Option<String>,
+    /// Whether keys already present in the destination store are overwritten.
+    /// If false, they are skipped.
+    pub overwrite: bool,
+    /// The number of keys listed, read and written at a time.
+    pub batch_size: usize,
+    /// Whether keys are deleted from the source store once copied, turning the
+    /// copy into a move.
+    pub delete_source: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            prefix: None,
+            overwrite: false,
+            batch_size: 100,
+            delete_source: false,
+        }
+    }
+}
+
+/// The outcome of [`AppState::copy_store`].
+#[derive(Debug, Default)]
+pub struct CopyReport {
+    /// The number of keys copied (and, when moving, deleted from the source
+    /// store).
+    pub copied: usize,
+    /// The number of keys skipped, either because they already existed in the
+    /// destination store or because they were deleted from the source store
+    /// during the copy.
+    pub skipped: usize,
+    /// The keys which could not be copied (or, when moving, deleted from the
+    /// source store), along with the reason. Each key is either counted as
+    /// copied or skipped, or listed here, but never both.
+    pub failed: Vec<(String, Error)>,
+}
+
+impl AppState {
+    /// Copies keys from the store labeled `from` into the store labeled `to`.
+    ///
+    /// This is a host-side operation intended for migrating data between
+    /// stores (e.g. from the default local store to a remote one) without
+    /// passing values through a guest. Both stores must be granted to at least
+    /// one component of the app.
+    ///
+    /// Failures reading or writing individual keys are reported in the
+    /// returned [`CopyReport`] rather than aborting the copy; an error is only
+    /// returned if a store can't be opened or listed.
+    pub async fn copy_store(
+        &self,
+        from: &str,
+        to: &str,
+        options: CopyOptions,
+    ) -> anyhow::Result<CopyReport> {
+        ensure!(from != to, "cannot copy key-value store {from:?} to itself");
+        let source = self.granted_store(from).await?;
+        let destination = self.granted_store(to).await?;
+        let prefix = options.prefix.as_deref().unwrap_or_default();
+        let batch_size = options.batch_size.max(1);
+
+        let mut report = CopyReport::default();
+        let mut examined = 0;
+        let mut cursor = None;
+        loop {
+            let page = source
+                .get_keys_page(cursor.take(), batch_size)
+                .await
+                .map_err(|err| anyhow::anyhow!("{err:?}"))
+                .with_context(|| format!("failed to list keys in key-value store {from:?}"))?;
+            let keys: Vec<String> = page
+                .keys
+                .into_iter()
+                .filter(|key| key.starts_with(prefix))
+                .collect();
+            let logged = examined / PROGRESS_LOG_INTERVAL;
+            examined += keys.len();
+
+            let keys = if options.overwrite {
+                keys
+            } else {
+                missing_keys(&*destination, keys, &mut report).await
+            };
+            let copied = copy_batch(&*source, &*destination, keys, &mut report).await;
+            if options.delete_source && !copied.is_empty() {
+                if let Err(err) = source.delete_many(copied.clone()).await {
+                    // The keys weren't moved, so they count as failed rather
+                    // than copied
+                    report.copied -= copied.len();
+                    report
+                        .failed
+                        .extend(copied.into_iter().map(|key| (key, err.clone())));
+                }
+            }
+
+            if examined / PROGRESS_LOG_INTERVAL > logged {
+                tracing::info!(
+                    "Copying key-value store {from:?} to {to:?}: {examined} keys examined, {} copied, {} skipped, {} failed",
+                    report.copied,
+                    report.skipped,
+                    report.failed.len()
+                );
+            }
+            match page.cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        tracing::info!(
+            "Copied key-value store {from:?} to {to:?}: {} copied, {} skipped, {} failed",
+            report.copied,
+            report.skipped,
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// Opens the given store if it is granted to any component of the app.
+    async fn granted_store(&self, label: &str) -> anyhow::Result<Arc<dyn Store>> {
+        ensure!(
+            self.store_is_used(label),
+            "key-value store {label:?} is not used by any component of the app"
+        );
+        self.store_manager
+            .get(label)
+            .await
+            .map_err(|err| anyhow::anyhow!("{err:?}"))
+            .with_context(|| format!("failed to open key-value store {label:?}"))
+    }
+}
+
+/// Returns the keys not present in the destination store, counting the others
+/// as skipped.
+async fn missing_keys(
+    destination: &dyn Store,
+    keys: Vec<String>,
+    report: &mut CopyReport,
+) -> Vec<String> {
+    let mut missing = Vec::with_capacity(keys.len());
+    for key in keys {
+        match destination.exists(&key).await {
+            Ok(true) => report.skipped += 1,
+            Ok(false) => missing.push(key),
+            Err(err) => report.failed.push((key, err)),
+        }
+    }
+    missing
+}
+
+/// Copies the given keys, returning the keys that were copied.
+async fn copy_batch(
+    source: &dyn Store,
+    destination: &dyn Store,
+    keys: Vec<String>,
+    report: &mut CopyReport,
+) -> Vec<String> {
+    if keys.is_empty() {
+        return keys;
+    }
+    let values = match source.get_many(keys.clone()).await {
+        Ok(values) => values,
+        Err(err) => {
+            report
+                .failed
+                .extend(keys.into_iter().map(|key| (key, err.clone())));
+            return Vec::new();
+        }
+    };
+    let key_values: Vec<(String, Vec<u8>)> = values
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            Some(value) => Some((key, value)),
+            None => {
+                // Deleted since it was listed
+                report.skipped += 1;
+                None
+            }
+        })
+        .collect();
+
+    let copied = if destination.set_many(key_values.clone()).await.is_ok() {
+        key_values.into_iter().map(|(key, _)| key).collect()
+    } else {
+        // Retry individually to find out which keys failed
+        let mut copied = Vec::with_capacity(key_values.len());
+        for (key, value) in key_values {
+            match destination.set(&key, &value).await {
+                Ok(()) => copied.push(key),
+                Err(err) => report.failed.push((key, err)),
+            }
+        }
+        copied
+    };
+    report.copied += copied.len();
+    copied
+}
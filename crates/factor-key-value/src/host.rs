@@ -1,6 +1,10 @@
-use super::{Cas, SwapError};
+use super::{
+    audit::AuditedStore, batch::BatchError, check_unique_keys, Cas, CasSupport, StoreStats,
+    SwapError, TxCondition, TxError, TxOp, TxWrite,
+};
 use anyhow::{Context, Result};
 use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_factor_audit::Auditor;
 use spin_resource_table::Table;
 use spin_telemetry::traces::{self, Blame};
 use spin_world::v2::key_value;
@@ -24,6 +28,15 @@ pub trait StoreManager: Sync + Send {
         let _ = store_name;
         None
     }
+
+    /// The amount of data in the given store, if the backend can report it
+    /// cheaply.
+    ///
+    /// Failures to gather the statistics are logged rather than returned.
+    async fn stats(&self, store_name: &str) -> Option<StoreStats> {
+        let _ = store_name;
+        None
+    }
 }
 
 #[async_trait]
@@ -36,12 +49,72 @@ pub trait Store: Sync + Send {
     async fn delete(&self, key: &str) -> Result<(), Error>;
     async fn exists(&self, key: &str) -> Result<bool, Error>;
     async fn get_keys(&self) -> Result<Vec<String>, Error>;
+    /// Returns a page of keys following `cursor`, along with the cursor for the
+    /// next page (`None` if there are no more keys). Cursors are opaque to
+    /// callers, who should only pass back those returned by the store.
+    ///
+    /// Pages hold up to `limit` keys, although stores which page natively may
+    /// treat it as a hint, and may return a key written during the listing in
+    /// more than one page.
+    ///
+    /// The default implementation lists all keys with `get_keys` on every
+    /// call, so listing a whole store this way is quadratic; stores should
+    /// override it where they can page natively.
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        let mut keys = self.get_keys().await?;
+        keys.sort_unstable();
+        let start = match &cursor {
+            Some(cursor) => keys.partition_point(|key| key <= cursor),
+            None => 0,
+        };
+        let end = keys.len().min(start + limit.max(1));
+        let cursor = (end < keys.len()).then(|| keys[end - 1].clone());
+        keys.truncate(end);
+        keys.drain(..start);
+        Ok(KeysPage { keys, cursor })
+    }
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error>;
     async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error>;
     async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error>;
     async fn increment(&self, key: String, delta: i64) -> Result<i64, Error>;
     async fn new_compare_and_swap(&self, bucket_rep: u32, key: &str)
         -> Result<Arc<dyn Cas>, Error>;
+    /// How the store supports [`Store::new_compare_and_swap`].
+    ///
+    /// Stores which declare native support but fail to create a
+    /// compare-and-swap have it emulated by the key-value factor.
+    fn compare_and_swap_support(&self) -> CasSupport {
+        CasSupport::Native
+    }
+    /// Whether [`Store::increment`] is atomic with respect to other writers.
+    fn supports_atomic_increment(&self) -> bool {
+        true
+    }
+    /// Whether the store implements [`Store::transact`].
+    fn supports_transactions(&self) -> bool {
+        false
+    }
+    /// Applies the writes of all `ops` if, and only if, all of their
+    /// conditions hold, such that no other writer can interleave with the
+    /// transaction. If a condition fails, the error names its key.
+    ///
+    /// Callers must ensure each key appears in at most one op (see
+    /// [`check_unique_keys`]). Stores which don't override this report
+    /// [`TxError::Unsupported`].
+    async fn transact(&self, ops: Vec<TxOp>) -> Result<(), TxError> {
+        let _ = ops;
+        Err(TxError::Unsupported)
+    }
+}
+
+/// A page of keys returned by [`Store::get_keys_page`].
+#[derive(Debug, Default)]
+pub struct KeysPage {
+    /// The keys in this page.
+    pub keys: Vec<String>,
+    /// The cursor to pass to `get_keys_page` for the next page, or `None` if
+    /// this is the last page.
+    pub cursor: Option<String>,
 }
 
 pub struct KeyValueDispatch {
@@ -49,6 +122,8 @@ pub struct KeyValueDispatch {
     manager: Arc<dyn StoreManager>,
     stores: Table<Arc<dyn Store>>,
     compare_and_swaps: Table<Arc<dyn Cas>>,
+    /// Records writes to the stores in the audit log.
+    auditor: Auditor,
 }
 
 impl KeyValueDispatch {
@@ -66,9 +141,23 @@ impl KeyValueDispatch {
             manager,
             stores: Table::new(capacity),
             compare_and_swaps: Table::new(capacity),
+            auditor: Auditor::disabled(),
         }
     }
 
+    /// Sets the auditor which records writes to the stores.
+    pub fn with_auditor(mut self, auditor: Auditor) -> Self {
+        self.auditor = auditor;
+        self
+    }
+
+    /// Gets an allowed store from the manager, wrapped for auditing.
+    async fn open_store(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let store = self.manager.get(name).await?;
+        store.after_open().await?;
+        Ok(AuditedStore::wrap(store, &self.auditor))
+    }
+
     pub fn get_store<T: 'static>(&self, store: Resource<T>) -> anyhow::Result<&Arc<dyn Store>> {
         let res = self.stores.get(store.rep()).context("invalid store");
         if let Err(err) = &res {
@@ -115,8 +204,7 @@ impl key_value::HostStore for KeyValueDispatch {
     async fn open(&mut self, name: String) -> Result<Result<Resource<key_value::Store>, Error>> {
         Ok(async {
             if self.allowed_stores.contains(&name) {
-                let store = self.manager.get(&name).await?;
-                store.after_open().await?;
+                let store = self.open_store(&name).await?;
                 let store_idx = self
                     .stores
                     .push(store)
@@ -211,8 +299,7 @@ impl wasi_keyvalue::store::Host for KeyValueDispatch {
         identifier: String,
     ) -> Result<Resource<wasi_keyvalue::store::Bucket>, wasi_keyvalue::store::Error> {
         if self.allowed_stores.contains(&identifier) {
-            let store = self.manager.get(&identifier).await.map_err(to_wasi_err)?;
-            store.after_open().await.map_err(to_wasi_err)?;
+            let store = self.open_store(&identifier).await.map_err(to_wasi_err)?;
             let store_idx = self
                 .stores
                 .push(store)
@@ -433,6 +520,146 @@ impl wasi_keyvalue::atomics::Host for KeyValueDispatch {
     }
 }
 
+use spin_world::spin::key_value::transactions;
+
+impl From<transactions::Op> for TxOp {
+    fn from(op: transactions::Op) -> Self {
+        Self {
+            key: op.key,
+            condition: match op.condition {
+                transactions::Condition::Unconditional => TxCondition::Unconditional,
+                transactions::Condition::Absent => TxCondition::Absent,
+                transactions::Condition::Equals(value) => TxCondition::Equals(value),
+            },
+            write: match op.mutation {
+                transactions::Mutation::Set(value) => TxWrite::Set(value),
+                transactions::Mutation::Delete => TxWrite::Delete,
+            },
+        }
+    }
+}
+
+impl transactions::Host for KeyValueDispatch {
+    fn convert_transaction_error(
+        &mut self,
+        error: transactions::TransactionError,
+    ) -> std::result::Result<transactions::TransactionError, anyhow::Error> {
+        Ok(error)
+    }
+
+    async fn supports_transactions(
+        &mut self,
+        bucket: Resource<transactions::Bucket>,
+    ) -> Result<bool> {
+        let store = self.get_store(bucket)?;
+        Ok(store.supports_transactions())
+    }
+
+    #[instrument(name = "spin_key_value.transact", skip_all, fields(otel.kind = "client"))]
+    async fn transact(
+        &mut self,
+        bucket: Resource<transactions::Bucket>,
+        ops: Vec<transactions::Op>,
+    ) -> Result<(), transactions::TransactionError> {
+        let store = self
+            .get_store_wasi(bucket)
+            .map_err(transactions::TransactionError::StoreError)?;
+        let ops = ops.into_iter().map(TxOp::from).collect::<Vec<_>>();
+        let result = match check_unique_keys(&ops) {
+            Ok(()) if ops.is_empty() => Ok(()),
+            Ok(()) => store.transact(ops).await,
+            Err(err) => Err(err),
+        };
+        result.map_err(|err| match err {
+            TxError::ConditionFailed(key) => transactions::TransactionError::ConditionFailed(key),
+            TxError::Unsupported => transactions::TransactionError::Unsupported,
+            TxError::Store(err) => transactions::TransactionError::StoreError(to_wasi_err(err)),
+        })
+    }
+}
+
+use spin_world::spin::key_value::capabilities;
+
+impl capabilities::Host for KeyValueDispatch {
+    async fn capabilities(
+        &mut self,
+        bucket: Resource<capabilities::Bucket>,
+    ) -> Result<capabilities::StoreCapabilities> {
+        let store = self.get_store(bucket)?;
+        Ok(capabilities::StoreCapabilities {
+            cas: match store.compare_and_swap_support() {
+                CasSupport::Native => capabilities::CasSupport::Native,
+                CasSupport::Emulated => capabilities::CasSupport::Emulated,
+                CasSupport::None => capabilities::CasSupport::None,
+            },
+            atomic_increment: store.supports_atomic_increment(),
+        })
+    }
+}
+
+use spin_world::spin::key_value::batch;
+
+fn to_batch_err(err: BatchError) -> batch::BatchError {
+    match err {
+        BatchError::Store(err) => batch::BatchError::StoreError(to_wasi_err(err)),
+        BatchError::PartialFailure(failed) => batch::BatchError::PartialFailure(
+            failed
+                .into_iter()
+                .map(|(key, err)| batch::KeyError {
+                    key,
+                    error: to_wasi_err(err),
+                })
+                .collect(),
+        ),
+    }
+}
+
+impl batch::Host for KeyValueDispatch {
+    fn convert_batch_error(
+        &mut self,
+        error: batch::BatchError,
+    ) -> std::result::Result<batch::BatchError, anyhow::Error> {
+        Ok(error)
+    }
+
+    #[instrument(name = "spin_key_value.batch_get_many", skip_all, fields(otel.kind = "client"))]
+    #[allow(clippy::type_complexity)]
+    async fn get_many(
+        &mut self,
+        bucket: Resource<batch::Bucket>,
+        keys: Vec<String>,
+    ) -> Result<Vec<(String, Option<Vec<u8>>)>, batch::BatchError> {
+        let store = self.get_store_wasi(bucket)?;
+        crate::batch::get_many(store.as_ref(), keys)
+            .await
+            .map_err(to_batch_err)
+    }
+
+    #[instrument(name = "spin_key_value.batch_set_many", skip_all, fields(otel.kind = "client"))]
+    async fn set_many(
+        &mut self,
+        bucket: Resource<batch::Bucket>,
+        key_values: Vec<(String, Vec<u8>)>,
+    ) -> Result<(), batch::BatchError> {
+        let store = self.get_store_wasi(bucket)?;
+        crate::batch::set_many(store.as_ref(), key_values)
+            .await
+            .map_err(to_batch_err)
+    }
+
+    #[instrument(name = "spin_key_value.batch_delete_many", skip_all, fields(otel.kind = "client"))]
+    async fn delete_many(
+        &mut self,
+        bucket: Resource<batch::Bucket>,
+        keys: Vec<String>,
+    ) -> Result<(), batch::BatchError> {
+        let store = self.get_store_wasi(bucket)?;
+        crate::batch::delete_many(store.as_ref(), keys)
+            .await
+            .map_err(to_batch_err)
+    }
+}
+
 pub fn log_error(err: impl std::fmt::Debug) -> Error {
     tracing::warn!("key-value error: {err:?}");
     Error::Other(format!("{err:?}"))
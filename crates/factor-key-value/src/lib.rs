@@ -1,5 +1,12 @@
+mod alias;
+mod audit;
+mod batch;
+mod cas;
+mod copy;
 mod host;
 pub mod runtime_config;
+mod stats;
+mod transaction;
 mod util;
 
 use std::{
@@ -7,18 +14,27 @@ use std::{
     sync::Arc,
 };
 
-use anyhow::ensure;
+use anyhow::bail;
 use spin_factors::{
-    ConfigureAppContext, Factor, FactorData, FactorInstanceBuilder, InitContext, PrepareContext,
-    RuntimeFactors,
+    ConfigureAppContext, Factor, FactorData, FactorInstanceBuilder, InitContext, LabelReport,
+    PrepareContext, RuntimeFactors,
 };
 use spin_locked_app::MetadataKey;
 
 /// Metadata key for key-value stores.
 pub const KEY_VALUE_STORES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("key_value_stores");
-pub use host::{log_cas_error, log_error, Error, KeyValueDispatch, Store, StoreManager};
+pub use alias::StoreAlias;
+use cas::CasStoreManager;
+pub use cas::CasSupport;
+pub use copy::{CopyOptions, CopyReport};
+pub use host::{log_cas_error, log_error, Error, KeyValueDispatch, KeysPage, Store, StoreManager};
 pub use runtime_config::RuntimeConfig;
 use spin_core::async_trait;
+use spin_factor_audit::Auditor;
+use spin_factors::ConfiguredApp;
+use stats::CountingStoreManager;
+pub use stats::{LabelStats, OperationCounts, StoreStats};
+pub use transaction::{check_unique_keys, TxCondition, TxError, TxOp, TxWrite};
 pub use util::DelegatingStoreManager;
 
 /// A factor that provides key-value storage.
@@ -32,6 +48,14 @@ impl KeyValueFactor {
     pub fn new() -> Self {
         Self { _priv: () }
     }
+
+    /// Returns the statistics for each store label used by a configured app,
+    /// e.g. for a stats endpoint or startup summary to render.
+    pub async fn app_stats<T: RuntimeFactors>(
+        configured_app: &ConfiguredApp<T>,
+    ) -> anyhow::Result<Vec<LabelStats>> {
+        Ok(configured_app.app_state::<Self>()?.stats().await)
+    }
 }
 
 impl Factor for KeyValueFactor {
@@ -47,6 +71,15 @@ impl Factor for KeyValueFactor {
         ctx.link_bindings(
             spin_world::wasi::keyvalue::atomics::add_to_linker::<_, FactorData<Self>>,
         )?;
+        ctx.link_bindings(
+            spin_world::spin::key_value::transactions::add_to_linker::<_, FactorData<Self>>,
+        )?;
+        ctx.link_bindings(
+            spin_world::spin::key_value::batch::add_to_linker::<_, FactorData<Self>>,
+        )?;
+        ctx.link_bindings(
+            spin_world::spin::key_value::capabilities::add_to_linker::<_, FactorData<Self>>,
+        )?;
         Ok(())
     }
 
@@ -55,9 +88,13 @@ impl Factor for KeyValueFactor {
         mut ctx: ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
         let store_managers = ctx.take_runtime_config().unwrap_or_default();
+        let defined_labels = store_managers
+            .labels()
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
 
+        let (store_managers, aliases) = store_managers.resolve_aliases()?;
         let delegating_manager = DelegatingStoreManager::new(store_managers);
-        let store_manager = Arc::new(delegating_manager);
 
         // Build component -> allowed stores map
         let mut component_allowed_stores = HashMap::new();
@@ -68,27 +105,56 @@ impl Factor for KeyValueFactor {
                 .unwrap_or_default()
                 .into_iter()
                 .collect::<HashSet<_>>();
-            for label in &key_value_stores {
-                // TODO: port nicer errors from KeyValueComponent (via error type?)
-                ensure!(
-                    store_manager.is_defined(label),
-                    "unknown key_value_stores label {label:?} for component {component_id:?}"
-                );
-            }
             component_allowed_stores.insert(component_id, key_value_stores);
-            // TODO: warn (?) on unused store?
         }
 
+        // Report every problem at once, rather than one per restart
+        let mut report = LabelReport::new(
+            component_allowed_stores
+                .iter()
+                .flat_map(|(component_id, labels)| {
+                    labels
+                        .iter()
+                        .map(move |label| (component_id.as_str(), label.as_str()))
+                }),
+            defined_labels.iter().map(String::as_str),
+        );
+        ensure_stores_are_defined(&report)?;
+        // Stores used through an alias are used
+        report.unused.retain(|label| {
+            !aliases.iter().any(|(alias, chain)| {
+                chain.contains(label)
+                    && component_allowed_stores
+                        .values()
+                        .any(|stores| stores.contains(alias))
+            })
+        });
+        if !report.unused.is_empty() {
+            tracing::warn!(
+                "The runtime configuration defines key-value stores which no component uses: {}",
+                quoted_list(&report.unused)
+            );
+        }
+
+        let used_labels = component_allowed_stores.values().flatten().cloned();
+        let store_manager = Arc::new(CountingStoreManager::new(
+            CasStoreManager::new(delegating_manager),
+            used_labels.collect::<HashSet<_>>(),
+        ));
+
         Ok(AppState {
             store_manager,
             component_allowed_stores,
+            unused_stores: report.unused,
+            aliases,
         })
     }
 
     fn prepare<T: RuntimeFactors>(
         &self,
-        ctx: PrepareContext<T, Self>,
+        mut ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<InstanceBuilder> {
+        let auditor = Auditor::for_instance(&mut ctx)?;
         let app_state = ctx.app_state();
         let allowed_stores = app_state
             .component_allowed_stores
@@ -98,11 +164,51 @@ impl Factor for KeyValueFactor {
         Ok(InstanceBuilder {
             store_manager: app_state.store_manager.clone(),
             allowed_stores,
+            auditor,
         })
     }
 }
 
-type AppStoreManager = DelegatingStoreManager;
+/// Errors listing every store label which components use but runtime config
+/// doesn't define.
+fn ensure_stores_are_defined(report: &LabelReport) -> anyhow::Result<()> {
+    if report.undefined.is_empty() {
+        return Ok(());
+    }
+    let mut lines = vec![
+        "One or more components use key-value stores which are not defined.".to_owned(),
+        "Check the spelling, or pass a runtime configuration file that defines these stores."
+            .to_owned(),
+        "See https://spinframework.dev/dynamic-configuration#key-value-store-runtime-configuration"
+            .to_owned(),
+        "Details:".to_owned(),
+    ];
+    for undefined in &report.undefined {
+        let suggestion = undefined
+            .suggestion
+            .as_ref()
+            .map(|closest| format!(" Did you mean {closest:?}?"))
+            .unwrap_or_default();
+        for component_id in &undefined.components {
+            lines.push(format!(
+                "- Component {component_id:?} uses unknown key_value_stores label {:?}.{suggestion}",
+                undefined.label
+            ));
+        }
+    }
+    bail!(lines.join("\n"))
+}
+
+/// Formats labels as a comma-separated list of quoted labels.
+fn quoted_list(labels: &[String]) -> String {
+    labels
+        .iter()
+        .map(|label| format!("{label:?}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+type AppStoreManager = CountingStoreManager;
 
 pub struct AppState {
     /// The store manager for the app.
@@ -116,6 +222,12 @@ pub struct AppState {
     /// This is a map from component ID to the set of store labels that the
     /// component is allowed to use.
     component_allowed_stores: HashMap<String, HashSet<String>>,
+    /// The labels of the stores defined by runtime config which no component
+    /// uses, other than the default store.
+    unused_stores: Vec<String>,
+    /// The chain of labels each alias resolves through, from the alias to
+    /// the label of the store it refers to.
+    aliases: HashMap<String, Vec<String>>,
 }
 
 impl AppState {
@@ -131,10 +243,42 @@ impl AppState {
             .any(|stores| stores.contains(label))
     }
 
+    /// Returns the chain of labels each alias used by any component resolves
+    /// through, from the alias to the label of the store it refers to, in
+    /// alias label order.
+    pub fn used_store_aliases(&self) -> Vec<&[String]> {
+        let mut chains = self
+            .aliases
+            .iter()
+            .filter(|(label, _)| self.store_is_used(label))
+            .map(|(_, chain)| chain.as_slice())
+            .collect::<Vec<_>>();
+        chains.sort_unstable();
+        chains
+    }
+
+    /// Returns the compare-and-swap support chosen for the given store label,
+    /// or `None` if the store can't be opened.
+    pub async fn cas_support(&self, label: &str) -> Option<CasSupport> {
+        self.store_manager.cas_support(label).await
+    }
+
+    /// Returns the labels of the stores defined by runtime config which no
+    /// component uses, other than the default store, in label order.
+    pub fn unused_stores(&self) -> &[String] {
+        &self.unused_stores
+    }
+
     /// Get a store by label.
     pub async fn get_store(&self, label: &str) -> Option<Arc<dyn Store>> {
         self.store_manager.get(label).await.ok()
     }
+
+    /// Returns the statistics for each store label used by any component, in
+    /// label order.
+    pub async fn stats(&self) -> Vec<LabelStats> {
+        self.store_manager.stats().await
+    }
 }
 
 /// `SwapError` are errors that occur during compare and swap operations
@@ -177,6 +321,8 @@ pub struct InstanceBuilder {
     store_manager: Arc<AppStoreManager>,
     /// The allowed stores for this component instance.
     allowed_stores: HashSet<String>,
+    /// Records writes made by this component instance in the audit log.
+    auditor: Auditor,
 }
 
 impl FactorInstanceBuilder for InstanceBuilder {
@@ -186,11 +332,11 @@ impl FactorInstanceBuilder for InstanceBuilder {
         let Self {
             store_manager,
             allowed_stores,
+            auditor,
         } = self;
-        Ok(KeyValueDispatch::new_with_capacity(
-            allowed_stores,
-            store_manager,
-            u32::MAX,
-        ))
+        Ok(
+            KeyValueDispatch::new_with_capacity(allowed_stores, store_manager, u32::MAX)
+                .with_auditor(auditor),
+        )
     }
 }
@@ -2,34 +2,68 @@ pub mod spin;
 
 use std::{collections::HashMap, sync::Arc};
 
-use crate::StoreManager;
+use crate::{alias, StoreAlias, StoreManager};
 
 /// Runtime configuration for all key value stores.
 #[derive(Default, Clone)]
 pub struct RuntimeConfig {
     /// Map of store names to store managers.
     store_managers: HashMap<String, Arc<dyn StoreManager>>,
+    /// Map of alias labels to the labels they refer to.
+    aliases: HashMap<String, StoreAlias>,
 }
 
 impl RuntimeConfig {
     /// Adds a store manager for the store with the given label to the runtime configuration.
     ///
-    /// If a store manager already exists for the given label, it will be replaced.
+    /// If a store manager or alias already exists for the given label, it will be replaced.
     pub fn add_store_manager(&mut self, label: String, store_manager: Arc<dyn StoreManager>) {
+        self.aliases.remove(&label);
         self.store_managers.insert(label, store_manager);
     }
 
-    /// Returns whether a store manager exists for the store with the given label.
+    /// Adds an alias, so that the store with the given label is the store of
+    /// the alias's target label.
+    ///
+    /// If a store manager or alias already exists for the given label, it will
+    /// be replaced. Aliases are resolved when the app is configured.
+    pub fn add_alias(&mut self, label: String, alias: StoreAlias) {
+        self.store_managers.remove(&label);
+        self.aliases.insert(label, alias);
+    }
+
+    /// Returns whether a store manager or alias exists for the store with the given label.
     pub fn has_store_manager(&self, label: &str) -> bool {
-        self.store_managers.contains_key(label)
+        self.store_managers.contains_key(label) || self.aliases.contains_key(label)
+    }
+
+    /// Returns the labels of the stores which have store managers or aliases.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.store_managers
+            .keys()
+            .chain(self.aliases.keys())
+            .map(String::as_str)
     }
 
     /// Returns the store manager for the store with the given label.
     pub fn get_store_manager(&self, label: &str) -> Option<Arc<dyn StoreManager>> {
         self.store_managers.get(label).cloned()
     }
+
+    /// Resolves the aliases, returning a store manager for every label along
+    /// with the chain of labels each alias resolves through.
+    pub(crate) fn resolve_aliases(
+        mut self,
+    ) -> anyhow::Result<(
+        HashMap<String, Arc<dyn StoreManager>>,
+        HashMap<String, Vec<String>>,
+    )> {
+        let chains = alias::resolve_aliases(&mut self.store_managers, &self.aliases)?;
+        Ok((self.store_managers, chains))
+    }
 }
 
+/// Iterates over the store managers, without the aliases.
 impl IntoIterator for RuntimeConfig {
     type Item = (String, Arc<dyn StoreManager>);
     type IntoIter = std::collections::hash_map::IntoIter<String, Arc<dyn StoreManager>>;
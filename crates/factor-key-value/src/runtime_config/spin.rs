@@ -1,6 +1,6 @@
 //! Runtime configuration implementation used by Spin CLI.
 
-use crate::{RuntimeConfig, StoreManager};
+use crate::{RuntimeConfig, StoreAlias, StoreManager};
 use anyhow::Context as _;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
@@ -21,6 +21,21 @@ pub trait MakeKeyValueStore: 'static + Send + Sync {
         -> anyhow::Result<Self::StoreManager>;
 }
 
+/// The store type of aliases, which refer to the store of another label rather
+/// than configuring a store of their own.
+const ALIAS_STORE_TYPE: &str = "alias";
+
+/// Runtime configuration for an alias.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AliasConfig {
+    /// The label of the store the alias refers to.
+    target: String,
+    /// Whether the alias only allows reading the target store.
+    #[serde(default)]
+    read_only: bool,
+}
+
 /// A function that creates a store manager from a TOML table.
 type StoreFromToml =
     Arc<dyn Fn(toml::Table) -> anyhow::Result<Arc<dyn StoreManager>> + Send + Sync>;
@@ -42,7 +57,9 @@ fn store_from_toml_fn<T: MakeKeyValueStore>(provider_type: T) -> StoreFromToml {
 ///
 /// The various store types (i.e., the "type" field in the toml field) are
 /// registered with the resolver using `add_store_type`. The default store for a
-/// label is registered using `add_default_store`.
+/// label is registered using `add_default_store`. A label with the type
+/// `"alias"` refers to the store of its `target` label instead, optionally
+/// allowing only reads with `read_only = true`.
 #[derive(Default, Clone)]
 pub struct RuntimeConfigResolver {
     /// A map of store types to a function that returns the appropriate store
@@ -83,6 +100,9 @@ impl RuntimeConfigResolver {
         &mut self,
         store_type: T,
     ) -> anyhow::Result<()> {
+        if T::RUNTIME_CONFIG_TYPE == ALIAS_STORE_TYPE {
+            anyhow::bail!("the key value store type {ALIAS_STORE_TYPE:?} is reserved for aliases");
+        }
         if self
             .store_types
             .insert(T::RUNTIME_CONFIG_TYPE, store_from_toml_fn(store_type))
@@ -103,7 +123,7 @@ impl RuntimeConfigResolver {
         let mut runtime_config = self.resolve_from_toml(table)?.unwrap_or_default();
 
         for (&label, config) in &self.defaults {
-            if !runtime_config.store_managers.contains_key(label) {
+            if !runtime_config.has_store_manager(label) {
                 let store_manager = self
                     .store_manager_from_config(config.clone())
                     .with_context(|| {
@@ -126,6 +146,19 @@ impl RuntimeConfigResolver {
 
         let mut runtime_config = RuntimeConfig::default();
         for (label, config) in table {
+            if config.type_ == ALIAS_STORE_TYPE {
+                let alias: AliasConfig = config.config.try_into().with_context(|| {
+                    format!("could not parse key-value store alias with label '{label}'")
+                })?;
+                runtime_config.add_alias(
+                    label,
+                    StoreAlias {
+                        target: alias.target,
+                        read_only: alias.read_only,
+                    },
+                );
+                continue;
+            }
             let store_manager = self.store_manager_from_config(config).with_context(|| {
                 format!("could not configure key-value store with label '{label}'")
             })?;
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use spin_core::async_trait;
+
+use crate::{
+    cas::CasStoreManager, Cas, CasSupport, Error, KeysPage, Store, StoreManager, TxError, TxOp,
+    TxWrite,
+};
+
+/// The amount of data held by a store, as reported by [`StoreManager::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    /// The number of keys in the store.
+    pub key_count: u64,
+    /// The approximate number of bytes used by the store's keys and values.
+    ///
+    /// How this is measured depends on the backend, so it is only comparable
+    /// between stores with the same backend.
+    pub approx_bytes: u64,
+}
+
+/// The number of operations made on a store since the app was configured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OperationCounts {
+    /// The number of keys read, singly or in batches.
+    pub gets: u64,
+    /// The number of keys read which had a value.
+    pub hits: u64,
+    /// The number of keys read which had no value.
+    pub misses: u64,
+    /// The number of keys written, singly or in batches.
+    pub sets: u64,
+    /// The number of keys deleted, singly or in batches.
+    pub deletes: u64,
+    /// The number of increments.
+    pub increments: u64,
+}
+
+/// The statistics for a store label, as returned by [`crate::AppState::stats`].
+#[derive(Clone, Debug)]
+pub struct LabelStats {
+    /// The store label.
+    pub label: String,
+    /// The operations made on the store by the app.
+    pub operations: OperationCounts,
+    /// The amount of data in the store, if the backend reports it.
+    pub store: Option<StoreStats>,
+    /// The compare-and-swap support chosen for the store, or `None` if the
+    /// store couldn't be opened.
+    pub cas: Option<CasSupport>,
+}
+
+/// Counters for the operations made on a store.
+#[derive(Default)]
+struct OperationCounters {
+    gets: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    sets: AtomicU64,
+    deletes: AtomicU64,
+    increments: AtomicU64,
+}
+
+impl OperationCounters {
+    /// Counts reads of `requested` keys, of which `found` had values.
+    fn count_reads(&self, requested: usize, found: usize) {
+        let (requested, found) = (requested as u64, found as u64);
+        self.gets.fetch_add(requested, Ordering::Relaxed);
+        self.hits.fetch_add(found, Ordering::Relaxed);
+        self.misses
+            .fetch_add(requested.saturating_sub(found), Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> OperationCounts {
+        OperationCounts {
+            gets: self.gets.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            sets: self.sets.load(Ordering::Relaxed),
+            deletes: self.deletes.load(Ordering::Relaxed),
+            increments: self.increments.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A [`StoreManager`] which counts the operations made on the stores it opens.
+pub(crate) struct CountingStoreManager {
+    inner: CasStoreManager,
+    /// Counters for each store label used by the app.
+    counters: HashMap<String, Arc<OperationCounters>>,
+}
+
+impl CountingStoreManager {
+    pub(crate) fn new(inner: CasStoreManager, labels: impl IntoIterator<Item = String>) -> Self {
+        let counters = labels
+            .into_iter()
+            .map(|label| (label, Default::default()))
+            .collect();
+        Self { inner, counters }
+    }
+
+    /// The compare-and-swap support chosen for the given store label.
+    pub(crate) async fn cas_support(&self, label: &str) -> Option<CasSupport> {
+        self.inner.cas_support(label).await
+    }
+
+    /// The statistics for each store label used by the app, in label order.
+    pub(crate) async fn stats(&self) -> Vec<LabelStats> {
+        let mut labels: Vec<_> = self.counters.iter().collect();
+        labels.sort_by_key(|(label, _)| *label);
+        let mut stats = Vec::with_capacity(labels.len());
+        for (label, counters) in labels {
+            stats.push(LabelStats {
+                label: label.clone(),
+                operations: counters.snapshot(),
+                store: self.inner.stats(label).await,
+                cas: self.inner.cas_support(label).await,
+            });
+        }
+        stats
+    }
+}
+
+#[async_trait]
+impl StoreManager for CountingStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let store = self.inner.get(name).await?;
+        Ok(match self.counters.get(name) {
+            Some(counters) => Arc::new(CountingStore {
+                inner: store,
+                counters: counters.clone(),
+            }),
+            None => store,
+        })
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.inner.summary(store_name)
+    }
+
+    async fn stats(&self, store_name: &str) -> Option<StoreStats> {
+        self.inner.stats(store_name).await
+    }
+}
+
+/// A [`Store`] which counts the operations made on it.
+struct CountingStore {
+    inner: Arc<dyn Store>,
+    counters: Arc<OperationCounters>,
+}
+
+#[async_trait]
+impl Store for CountingStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let value = self.inner.get(key).await?;
+        self.counters.count_reads(1, value.is_some().into());
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.inner.set(key, value).await?;
+        self.counters.sets.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.inner.delete(key).await?;
+        self.counters.deletes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.inner.get_keys().await
+    }
+
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        self.inner.get_keys_page(cursor, limit).await
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        // Some stores omit missing keys from the results
+        let requested = keys.len();
+        let values = self.inner.get_many(keys).await?;
+        let found = values.iter().filter(|(_, value)| value.is_some()).count();
+        self.counters.count_reads(requested, found);
+        Ok(values)
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        let count = key_values.len() as u64;
+        self.inner.set_many(key_values).await?;
+        self.counters.sets.fetch_add(count, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        let count = keys.len() as u64;
+        self.inner.delete_many(keys).await?;
+        self.counters.deletes.fetch_add(count, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let value = self.inner.increment(key, delta).await?;
+        self.counters.increments.fetch_add(1, Ordering::Relaxed);
+        Ok(value)
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        self.inner.new_compare_and_swap(bucket_rep, key).await
+    }
+
+    fn compare_and_swap_support(&self) -> CasSupport {
+        self.inner.compare_and_swap_support()
+    }
+
+    fn supports_atomic_increment(&self) -> bool {
+        self.inner.supports_atomic_increment()
+    }
+
+    fn supports_transactions(&self) -> bool {
+        self.inner.supports_transactions()
+    }
+
+    async fn transact(&self, ops: Vec<TxOp>) -> Result<(), TxError> {
+        let (sets, deletes) = ops
+            .iter()
+            .fold((0, 0), |(sets, deletes), op| match op.write {
+                TxWrite::Set(_) => (sets + 1, deletes),
+                TxWrite::Delete => (sets, deletes + 1),
+            });
+        self.inner.transact(ops).await?;
+        self.counters.sets.fetch_add(sets, Ordering::Relaxed);
+        self.counters.deletes.fetch_add(deletes, Ordering::Relaxed);
+        Ok(())
+    }
+}
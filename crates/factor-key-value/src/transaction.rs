@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use crate::Error;
+
+/// The state a key must be in for a transaction to be applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxCondition {
+    /// The key may be in any state.
+    Unconditional,
+    /// The key must not exist.
+    Absent,
+    /// The key must exist and have exactly this value.
+    Equals(Vec<u8>),
+}
+
+impl TxCondition {
+    /// Returns whether the condition holds for a key's current value.
+    pub fn holds(&self, current: Option<&[u8]>) -> bool {
+        match self {
+            Self::Unconditional => true,
+            Self::Absent => current.is_none(),
+            Self::Equals(expected) => current == Some(expected.as_slice()),
+        }
+    }
+}
+
+/// The change a transaction makes to a key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxWrite {
+    /// Set the key to this value.
+    Set(Vec<u8>),
+    /// Delete the key, if it exists.
+    Delete,
+}
+
+/// A conditional write to one key, applied as part of a [`Store::transact`](crate::Store::transact).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxOp {
+    pub key: String,
+    pub condition: TxCondition,
+    pub write: TxWrite,
+}
+
+/// `TxError` are errors that occur when applying a transaction. In every case,
+/// none of the transaction's writes were applied.
+#[derive(Debug, thiserror::Error)]
+pub enum TxError {
+    /// The condition on this key did not hold.
+    #[error("transaction condition failed for key {0:?}")]
+    ConditionFailed(String),
+
+    /// The store does not support transactions.
+    #[error("the store does not support transactions")]
+    Unsupported,
+
+    #[error("{0:?}")]
+    Store(Error),
+}
+
+impl From<Error> for TxError {
+    fn from(err: Error) -> Self {
+        Self::Store(err)
+    }
+}
+
+/// Checks that no key appears in more than one op of a transaction, as the
+/// outcome of such a transaction would depend on the order the store applies
+/// its writes in.
+pub fn check_unique_keys(ops: &[TxOp]) -> Result<(), TxError> {
+    let mut keys = HashSet::with_capacity(ops.len());
+    for op in ops {
+        if !keys.insert(op.key.as_str()) {
+            return Err(TxError::Store(Error::Other(format!(
+                "key {:?} appears more than once in the transaction",
+                op.key
+            ))));
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::{Error, Store, StoreManager};
+use crate::{Error, Store, StoreManager, StoreStats};
 use spin_core::async_trait;
 use std::{collections::HashMap, sync::Arc};
 
@@ -33,4 +33,8 @@ impl StoreManager for DelegatingStoreManager {
         }
         None
     }
+
+    async fn stats(&self, store_name: &str) -> Option<StoreStats> {
+        self.delegates.get(store_name)?.stats(store_name).await
+    }
 }
@@ -1,10 +1,18 @@
 use anyhow::bail;
 use spin_core::async_trait;
-use spin_factor_key_value::{Cas, KeyValueFactor, RuntimeConfig, Store, StoreManager};
-use spin_factors::RuntimeFactors;
+use spin_core::wasmtime::component::Resource;
+use spin_factor_audit::{runtime_config::AuditSink, AuditFactor};
+use spin_factor_key_value::{
+    Cas, CasSupport, CopyOptions, KeyValueFactor, OperationCounts, RuntimeConfig, Store,
+    StoreAlias, StoreManager, SwapError,
+};
+use spin_factors::{HasInstanceBuilder, RuntimeFactors};
 use spin_factors_test::{toml, TestEnvironment};
 use spin_world::v2::key_value::{Error, HostStore};
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+};
 
 #[derive(RuntimeFactors)]
 struct TestFactors {
@@ -45,6 +53,277 @@ async fn works_when_allowed_store_is_defined() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn transactions_are_unsupported_by_default() -> anyhow::Result<()> {
+    use spin_world::spin::key_value::transactions::{
+        self, Condition, Host as _, Mutation, Op, TransactionError,
+    };
+
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), mock_store_manager());
+    let factors = TestFactors {
+        key_value: KeyValueFactor::new(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default"]
+    });
+    let mut state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+
+    let bucket =
+        spin_world::wasi::keyvalue::store::Host::open(&mut state.key_value, "default".to_owned())
+            .await?;
+    let rep = bucket.rep();
+    let bucket = || Resource::<transactions::Bucket>::new_own(rep);
+    let op = |key: &str| Op {
+        key: key.to_owned(),
+        condition: Condition::Absent,
+        mutation: Mutation::Set(b"value".to_vec()),
+    };
+
+    assert!(!state.key_value.supports_transactions(bucket()).await?);
+    assert!(matches!(
+        state.key_value.transact(bucket(), vec![op("a")]).await,
+        Err(TransactionError::Unsupported)
+    ));
+    // Ops are validated before reaching the store
+    assert!(matches!(
+        state
+            .key_value
+            .transact(bucket(), vec![op("a"), op("a")])
+            .await,
+        Err(TransactionError::StoreError(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_operations_round_trip() -> anyhow::Result<()> {
+    use spin_world::spin::key_value::batch::{self, Host as _};
+
+    let store = MemoryStore::default();
+    let mut state = memory_store_instance_state(&store).await?;
+    let bucket =
+        spin_world::wasi::keyvalue::store::Host::open(&mut state.key_value, "default".to_owned())
+            .await?;
+    let rep = bucket.rep();
+    let bucket = || Resource::<batch::Bucket>::new_own(rep);
+
+    state
+        .key_value
+        .set_many(
+            bucket(),
+            vec![
+                ("a".into(), b"1".to_vec()),
+                ("b".into(), b"2".to_vec()),
+                ("a".into(), b"3".to_vec()),
+            ],
+        )
+        .await?;
+    assert_eq!(store.value("a").as_deref(), Some(&b"3"[..]));
+
+    let values = state
+        .key_value
+        .get_many(bucket(), vec!["a".into(), "b".into(), "missing".into()])
+        .await?;
+    assert_eq!(
+        values,
+        [
+            ("a".into(), Some(b"3".to_vec())),
+            ("b".into(), Some(b"2".to_vec())),
+            ("missing".into(), None),
+        ]
+    );
+
+    state
+        .key_value
+        .delete_many(bucket(), vec!["a".into(), "missing".into()])
+        .await?;
+    assert_eq!(store.keys(), ["b"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_operations_report_failed_keys() -> anyhow::Result<()> {
+    use spin_world::spin::key_value::batch::{self, BatchError, Host as _};
+
+    let store = MemoryStore::default();
+    store.fail_on("b");
+    let mut state = memory_store_instance_state(&store).await?;
+    let bucket =
+        spin_world::wasi::keyvalue::store::Host::open(&mut state.key_value, "default".to_owned())
+            .await?;
+    let rep = bucket.rep();
+    let bucket = || Resource::<batch::Bucket>::new_own(rep);
+    let key_values = |keys: &[&str]| {
+        keys.iter()
+            .map(|key| (key.to_string(), key.as_bytes().to_vec()))
+            .collect::<Vec<_>>()
+    };
+
+    // The store isn't transactional, so the other keys are still set
+    let err = state
+        .key_value
+        .set_many(bucket(), key_values(&["a", "b", "c"]))
+        .await
+        .unwrap_err();
+    let BatchError::PartialFailure(failed) = err else {
+        panic!("expected a partial failure, got {err:?}");
+    };
+    let failed_keys: Vec<_> = failed.iter().map(|failure| failure.key.as_str()).collect();
+    assert_eq!(failed_keys, ["b"]);
+    assert_eq!(store.keys(), ["a", "c"]);
+
+    // When every key fails, nothing was changed
+    let err = state
+        .key_value
+        .set_many(bucket(), key_values(&["b"]))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, BatchError::StoreError(_)),
+        "expected a store error, got {err:?}"
+    );
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct AuditedFactors {
+    audit: AuditFactor,
+    key_value: KeyValueFactor,
+}
+
+#[tokio::test]
+async fn writes_are_audited_without_values() -> anyhow::Result<()> {
+    use spin_world::wasi::keyvalue::{atomics::Host as _, batch::Host as _};
+
+    let dir = tempfile::tempdir()?;
+    let log_path = dir.path().join("audit.jsonl");
+    let store = MemoryStore::default();
+    store.fail_on("locked");
+    let mut key_value = RuntimeConfig::default();
+    key_value.add_store_manager("default".into(), store.manager());
+    let env = TestEnvironment::new(AuditedFactors {
+        audit: AuditFactor::new(),
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default"]
+    })
+    .runtime_config(AuditedFactorsRuntimeConfig {
+        audit: Some(spin_factor_audit::RuntimeConfig {
+            sinks: vec![AuditSink::File(log_path.clone())],
+            include: ["key_value.set".into(), "key_value.delete".into()].into(),
+            queue_capacity: 16,
+        }),
+        key_value: Some(key_value),
+    })?;
+    let (factors, configured_app) = env.build_configured_app().await?;
+    let mut builders = factors.prepare(&configured_app, "test-component")?;
+    builders
+        .for_factor::<AuditFactor>()
+        .unwrap()
+        .set_request_id("req-42");
+    let mut state = factors.build_instance_state(builders)?;
+
+    let store_handle = state.key_value.open("default".to_owned()).await??;
+    let rep = store_handle.rep();
+    let kv = &mut state.key_value;
+    kv.set(
+        Resource::new_borrow(rep),
+        "secret-key".into(),
+        b"hunter2".to_vec(),
+    )
+    .await??;
+    kv.get(Resource::new_borrow(rep), "secret-key".into())
+        .await??;
+    assert!(kv
+        .set(
+            Resource::new_borrow(rep),
+            "locked".into(),
+            b"hunter2".to_vec()
+        )
+        .await?
+        .is_err());
+    kv.set_many(
+        Resource::new_borrow(rep),
+        vec![
+            ("a".into(), b"hunter2".to_vec()),
+            ("b".into(), b"hunter2".to_vec()),
+        ],
+    )
+    .await?;
+    kv.increment(Resource::new_borrow(rep), "counter".into(), 2)
+        .await?;
+    kv.delete(Resource::new_borrow(rep), "secret-key".into())
+        .await??;
+
+    configured_app
+        .app_state::<AuditFactor>()?
+        .audit_log()
+        .unwrap()
+        .flush();
+    let log = std::fs::read_to_string(&log_path)?;
+    assert!(!log.contains("hunter2"), "values must not be logged: {log}");
+    let records = log
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let summary = records
+        .iter()
+        .map(|record| {
+            assert_eq!(record["app"], "test-app");
+            assert_eq!(record["component_id"], "test-component");
+            assert_eq!(record["request_id"], "req-42");
+            assert!(record["timestamp"].is_string());
+            format!(
+                "{} {} {}",
+                record["operation"].as_str().unwrap(),
+                record["target"].as_str().unwrap(),
+                record["outcome"].as_str().unwrap()
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            "key_value.set secret-key success",
+            "key_value.set locked failure",
+            "key_value.set a success",
+            "key_value.set b success",
+            "key_value.set counter success",
+            "key_value.delete secret-key success",
+        ]
+    );
+    Ok(())
+}
+
+/// Builds the instance state of a component granted the given store as
+/// `default`.
+async fn memory_store_instance_state(
+    store: &MemoryStore,
+) -> anyhow::Result<TestFactorsInstanceState> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), store.manager());
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default"]
+    });
+    env.runtime_config(runtime_config)?
+        .build_instance_state()
+        .await
+}
+
 #[tokio::test]
 async fn errors_when_store_is_not_defined() -> anyhow::Result<()> {
     let runtime_config = RuntimeConfig::default();
@@ -71,6 +350,225 @@ async fn errors_when_store_is_not_defined() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn undefined_stores_are_reported_together_with_suggestions() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), MemoryStore::default().manager());
+    runtime_config.add_store_manager("customers".into(), MemoryStore::default().manager());
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [[trigger.test-trigger]]
+        component = { source = "does-not-exist.wasm", key_value_stores = ["customer"] }
+
+        [component.web]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["customer", "sessions"]
+    })
+    .runtime_config(runtime_config)?;
+    let Err(err) = env.build_configured_app().await else {
+        bail!("expected app configuration to fail but it didn't");
+    };
+
+    let err = err.to_string();
+    let typo = r#"uses unknown key_value_stores label "customer". Did you mean "customers"?"#;
+    // Both the inline component and `web` use the misspelled label
+    assert_eq!(err.matches(typo).count(), 2, "{err}");
+    // Labels with no similar definition get no suggestion
+    assert!(
+        err.lines()
+            .any(|line| line
+                == r#"- Component "web" uses unknown key_value_stores label "sessions"."#),
+        "{err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn unused_stores_are_reported() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    for label in ["default", "shared", "orphan"] {
+        runtime_config.add_store_manager(label.into(), MemoryStore::default().manager());
+    }
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.first]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["shared"]
+
+        [component.second]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["shared"]
+    })
+    .runtime_config(runtime_config)?;
+    let (_, configured_app) = env.build_configured_app().await?;
+
+    // The default store is always defined, so isn't reported
+    let app_state = configured_app.app_state::<KeyValueFactor>()?;
+    assert_eq!(app_state.unused_stores(), ["orphan"]);
+    Ok(())
+}
+
+/// Returns a test environment for the given manifest, with a "default" store
+/// backed by `store` and the given `(label, target, read_only)` aliases.
+fn alias_env(
+    store: &MemoryStore,
+    aliases: &[(&str, &str, bool)],
+    manifest: toml::Table,
+) -> anyhow::Result<TestEnvironment<TestFactors>> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), store.manager());
+    for &(label, target, read_only) in aliases {
+        runtime_config.add_alias(
+            label.into(),
+            StoreAlias {
+                target: target.into(),
+                read_only,
+            },
+        );
+    }
+    TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(manifest)
+    .runtime_config(runtime_config)
+}
+
+#[tokio::test]
+async fn aliases_use_their_target_store() -> anyhow::Result<()> {
+    let store = MemoryStore::default();
+    let aliases = [("cache", "default", false), ("replica", "cache", true)];
+    let manifest = toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["cache", "replica"]
+    };
+    let mut state = alias_env(&store, &aliases, manifest.clone())?
+        .build_instance_state()
+        .await?;
+
+    let cache = state.key_value.open("cache".into()).await??;
+    state
+        .key_value
+        .set(
+            Resource::new_borrow(cache.rep()),
+            "key".into(),
+            b"value".to_vec(),
+        )
+        .await??;
+    assert_eq!(store.value("key").as_deref(), Some(&b"value"[..]));
+
+    // The read-only alias sees the write, but can't write itself
+    let replica = state.key_value.open("replica".into()).await??;
+    let value = state
+        .key_value
+        .get(Resource::new_borrow(replica.rep()), "key".into())
+        .await??;
+    assert_eq!(value.as_deref(), Some(&b"value"[..]));
+    let result = state
+        .key_value
+        .set(
+            Resource::new_borrow(replica.rep()),
+            "key".into(),
+            b"other".to_vec(),
+        )
+        .await?;
+    assert!(matches!(result, Err(Error::AccessDenied)), "{result:?}");
+    let result = state
+        .key_value
+        .delete(Resource::new_borrow(replica.rep()), "key".into())
+        .await?;
+    assert!(matches!(result, Err(Error::AccessDenied)), "{result:?}");
+    assert_eq!(store.value("key").as_deref(), Some(&b"value"[..]));
+
+    let (_, configured_app) = alias_env(&store, &aliases, manifest)?
+        .build_configured_app()
+        .await?;
+    let app_state = configured_app.app_state::<KeyValueFactor>()?;
+    assert_eq!(
+        app_state.store_summary("replica").as_deref(),
+        Some(r#"read-only alias "replica" -> "cache" -> "default""#)
+    );
+    assert_eq!(
+        app_state.used_store_aliases(),
+        [&[
+            "replica".to_owned(),
+            "cache".to_owned(),
+            "default".to_owned()
+        ][..]]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn alias_cycles_and_undefined_targets_fail_configuration() -> anyhow::Result<()> {
+    let store = MemoryStore::default();
+    let aliases = [
+        ("a", "b", false),
+        ("b", "c", false),
+        ("c", "a", false),
+        ("cache", "default", false),
+    ];
+    let manifest = toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["a", "cache"]
+    };
+    let Err(err) = alias_env(&store, &aliases, manifest.clone())?
+        .build_configured_app()
+        .await
+    else {
+        bail!("expected app configuration to fail but it didn't");
+    };
+    let err = err.to_string();
+    assert!(
+        err.contains("key-value store aliases form a cycle"),
+        "{err}"
+    );
+
+    let aliases = [("a", "default", false), ("cache", "missing", false)];
+    let Err(err) = alias_env(&store, &aliases, manifest)?
+        .build_configured_app()
+        .await
+    else {
+        bail!("expected app configuration to fail but it didn't");
+    };
+    assert_eq!(
+        err.to_string(),
+        r#"key-value store alias "cache" refers to undefined store "missing": "cache" -> "missing""#
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn consistent_store_labels_pass() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    for label in ["default", "shared"] {
+        runtime_config.add_store_manager(label.into(), MemoryStore::default().manager());
+    }
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.first]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default", "shared"]
+
+        [component.second]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["shared"]
+    })
+    .runtime_config(runtime_config)?;
+    let (_, configured_app) = env.build_configured_app().await?;
+
+    let app_state = configured_app.app_state::<KeyValueFactor>()?;
+    assert!(app_state.unused_stores().is_empty());
+    Ok(())
+}
+
 #[tokio::test]
 async fn errors_when_store_is_not_allowed() -> anyhow::Result<()> {
     let mut runtime_config = RuntimeConfig::default();
@@ -98,6 +596,477 @@ async fn errors_when_store_is_not_allowed() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn copy_store_copies_keys_with_prefix() -> anyhow::Result<()> {
+    let source = MemoryStore::with_keys(&["app/a", "app/b", "app/c", "other/d"]);
+    let destination = MemoryStore::default();
+    let report = copy_store(
+        &source,
+        &destination,
+        CopyOptions {
+            prefix: Some("app/".into()),
+            batch_size: 2,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    assert_eq!(report.copied, 3);
+    assert_eq!(report.skipped, 0);
+    assert!(report.failed.is_empty());
+    assert_eq!(destination.keys(), ["app/a", "app/b", "app/c"]);
+    assert_eq!(destination.value("app/b").as_deref(), Some(&b"app/b"[..]));
+    assert_eq!(source.keys().len(), 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_store_skips_existing_keys_unless_overwriting() -> anyhow::Result<()> {
+    let source = MemoryStore::with_keys(&["a", "b"]);
+    let destination = MemoryStore::default();
+    destination.insert("a", b"existing");
+
+    let report = copy_store(&source, &destination, CopyOptions::default()).await?;
+    assert_eq!((report.copied, report.skipped), (1, 1));
+    assert_eq!(destination.value("a").as_deref(), Some(&b"existing"[..]));
+
+    let report = copy_store(
+        &source,
+        &destination,
+        CopyOptions {
+            overwrite: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+    assert_eq!((report.copied, report.skipped), (2, 0));
+    assert_eq!(destination.value("a").as_deref(), Some(&b"a"[..]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_store_reports_failed_keys() -> anyhow::Result<()> {
+    let source = MemoryStore::with_keys(&["a", "b", "c", "d"]);
+    let destination = MemoryStore::default();
+    destination.fail_on("b");
+    destination.fail_on("d");
+
+    let report = copy_store(
+        &source,
+        &destination,
+        CopyOptions {
+            batch_size: 3,
+            delete_source: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    assert_eq!(report.copied, 2);
+    let failed: Vec<_> = report.failed.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(failed, ["b", "d"]);
+    assert_eq!(destination.keys(), ["a", "c"]);
+    // Only the copied keys are moved
+    assert_eq!(source.keys(), ["b", "d"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_store_counts_keys_which_could_not_be_moved_once() -> anyhow::Result<()> {
+    let source = MemoryStore::with_keys(&["a", "b", "c", "d"]);
+    source.fail_on("c");
+    let destination = MemoryStore::default();
+
+    let report = copy_store(
+        &source,
+        &destination,
+        CopyOptions {
+            batch_size: 2,
+            delete_source: true,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    // The second batch was copied, but couldn't be deleted from the source
+    assert_eq!(report.copied, 2);
+    let failed: Vec<_> = report.failed.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(failed, ["c", "d"]);
+    assert_eq!(destination.keys(), ["a", "b", "c", "d"]);
+    assert_eq!(source.keys(), ["c", "d"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_store_requires_granted_stores() -> anyhow::Result<()> {
+    let source = MemoryStore::with_keys(&["a"]);
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("source".into(), source.manager());
+    runtime_config.add_store_manager("ungranted".into(), MemoryStore::default().manager());
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["source"]
+    })
+    .runtime_config(runtime_config)?;
+    let (_, configured_app) = env.build_configured_app().await?;
+    let app_state = configured_app.app_state::<KeyValueFactor>()?;
+
+    let err = app_state
+        .copy_store("source", "ungranted", Default::default())
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("not used by any component"),
+        "{err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn app_stats_count_operations_per_label() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), MemoryStore::default().manager());
+    runtime_config.add_store_manager("other".into(), MemoryStore::default().manager());
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default", "other"]
+    })
+    .runtime_config(runtime_config)?;
+    let (factors, configured_app) = env.build_configured_app().await?;
+    let builders = factors.prepare(&configured_app, "test-component")?;
+    let mut state = factors.build_instance_state(builders)?;
+
+    let store = state.key_value.open("default".into()).await??;
+    let rep = store.rep();
+    state
+        .key_value
+        .set(Resource::new_own(rep), "a".into(), b"1".to_vec())
+        .await??;
+    state
+        .key_value
+        .get(Resource::new_own(rep), "a".into())
+        .await??;
+    state
+        .key_value
+        .get(Resource::new_own(rep), "missing".into())
+        .await??;
+    state
+        .key_value
+        .delete(Resource::new_own(rep), "a".into())
+        .await??;
+
+    let store = configured_app
+        .app_state::<KeyValueFactor>()?
+        .get_store("default")
+        .await
+        .unwrap();
+    store.increment("counter".into(), 2).await?;
+    store
+        .get_many(vec!["counter".into(), "missing".into()])
+        .await?;
+
+    let stats = KeyValueFactor::app_stats(&configured_app).await?;
+    let labels: Vec<_> = stats.iter().map(|stats| stats.label.as_str()).collect();
+    assert_eq!(labels, ["default", "other"]);
+    assert_eq!(
+        stats[0].operations,
+        OperationCounts {
+            gets: 4,
+            hits: 2,
+            misses: 2,
+            sets: 1,
+            deletes: 1,
+            increments: 1,
+        }
+    );
+    assert_eq!(stats[1].operations, OperationCounts::default());
+    // The in-memory test store doesn't report its size
+    assert_eq!(stats[0].store, None);
+    assert_eq!(stats[0].cas, Some(CasSupport::Emulated));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cas_is_emulated_for_stores_without_native_support() -> anyhow::Result<()> {
+    use spin_world::spin::key_value::capabilities::{self, Host as _};
+    use spin_world::wasi::keyvalue::atomics::{self, CasError, Host as _, HostCas as _};
+
+    let store = MemoryStore::default();
+    let mut state = memory_store_instance_state(&store).await?;
+    let bucket =
+        spin_world::wasi::keyvalue::store::Host::open(&mut state.key_value, "default".to_owned())
+            .await?;
+    let rep = bucket.rep();
+
+    let capabilities = state
+        .key_value
+        .capabilities(Resource::<capabilities::Bucket>::new_own(rep))
+        .await?;
+    assert!(matches!(
+        capabilities.cas,
+        capabilities::CasSupport::Emulated
+    ));
+    assert!(!capabilities.atomic_increment);
+
+    let cas = state
+        .key_value
+        .new(Resource::<atomics::Bucket>::new_own(rep), "key".into())
+        .await?;
+    assert_eq!(
+        state
+            .key_value
+            .current(Resource::new_own(cas.rep()))
+            .await?,
+        None
+    );
+    assert!(state
+        .key_value
+        .swap(Resource::new_own(cas.rep()), b"1".to_vec())
+        .await
+        .is_ok());
+    assert_eq!(store.value("key").as_deref(), Some(b"1" as &[u8]));
+
+    // A write between reading and swapping fails the swap
+    let cas = state
+        .key_value
+        .new(Resource::<atomics::Bucket>::new_own(rep), "key".into())
+        .await?;
+    state
+        .key_value
+        .current(Resource::new_own(cas.rep()))
+        .await?;
+    store.insert("key", b"2");
+    assert!(matches!(
+        state
+            .key_value
+            .swap(Resource::new_own(cas.rep()), b"3".to_vec())
+            .await,
+        Err(CasError::CasFailed(_))
+    ));
+    assert_eq!(store.value("key").as_deref(), Some(b"2" as &[u8]));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn emulated_cas_serializes_concurrent_swaps() -> anyhow::Result<()> {
+    let memory = MemoryStore::default();
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), memory.manager());
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default"]
+    })
+    .runtime_config(runtime_config)?;
+    let (_, configured_app) = env.build_configured_app().await?;
+    let store = configured_app
+        .app_state::<KeyValueFactor>()?
+        .get_store("default")
+        .await
+        .unwrap();
+    assert_eq!(store.compare_and_swap_support(), CasSupport::Emulated);
+
+    // Each swapper increments the counter, retrying when another swap gets
+    // there first
+    let swappers = (0..8)
+        .map(|_| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                for _ in 0..25 {
+                    loop {
+                        let cas = store.new_compare_and_swap(0, "counter").await?;
+                        let count = match cas.current().await? {
+                            Some(value) => String::from_utf8(value)?.parse::<u64>()?,
+                            None => 0,
+                        };
+                        match cas.swap((count + 1).to_string().into_bytes()).await {
+                            Ok(()) => break,
+                            Err(SwapError::CasFailed(_)) => continue,
+                            Err(err) => return Err(err.into()),
+                        }
+                    }
+                }
+                anyhow::Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for swapper in swappers {
+        swapper.await??;
+    }
+    assert_eq!(memory.value("counter").as_deref(), Some(b"200" as &[u8]));
+    Ok(())
+}
+
+/// Copies between the given stores through the key-value factor's app state.
+async fn copy_store(
+    source: &MemoryStore,
+    destination: &MemoryStore,
+    options: CopyOptions,
+) -> anyhow::Result<spin_factor_key_value::CopyReport> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("source".into(), source.manager());
+    runtime_config.add_store_manager("destination".into(), destination.manager());
+    let env = TestEnvironment::new(TestFactors {
+        key_value: KeyValueFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["source", "destination"]
+    })
+    .runtime_config(runtime_config)?;
+    let (_, configured_app) = env.build_configured_app().await?;
+    configured_app
+        .app_state::<KeyValueFactor>()?
+        .copy_store("source", "destination", options)
+        .await
+}
+
+/// An in-memory store which can be made to fail writes and deletes of
+/// specific keys.
+#[derive(Clone, Default)]
+struct MemoryStore {
+    values: Arc<Mutex<BTreeMap<String, Vec<u8>>>>,
+    failing_keys: Arc<Mutex<HashSet<String>>>,
+}
+
+impl MemoryStore {
+    /// Creates a store holding the given keys, each with itself as its value.
+    fn with_keys(keys: &[&str]) -> Self {
+        let store = Self::default();
+        for key in keys {
+            store.insert(key, key.as_bytes());
+        }
+        store
+    }
+
+    fn insert(&self, key: &str, value: &[u8]) {
+        self.values
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), value.to_vec());
+    }
+
+    fn fail_on(&self, key: &str) {
+        self.failing_keys.lock().unwrap().insert(key.to_owned());
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.values.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn value(&self, key: &str) -> Option<Vec<u8>> {
+        self.values.lock().unwrap().get(key).cloned()
+    }
+
+    fn manager(&self) -> Arc<dyn StoreManager> {
+        Arc::new(self.clone())
+    }
+
+    fn check_writable(&self, key: &str) -> Result<(), Error> {
+        if self.failing_keys.lock().unwrap().contains(key) {
+            return Err(Error::Other(format!("cannot write {key:?}")));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StoreManager for MemoryStore {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let _ = name;
+        Ok(Arc::new(self.clone()))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        let _ = store_name;
+        true
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.value(key))
+    }
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.check_writable(key)?;
+        self.insert(key, value);
+        Ok(())
+    }
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.check_writable(key)?;
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.values.lock().unwrap().contains_key(key))
+    }
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        Ok(self.keys())
+    }
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let value = self.value(&key);
+                (key, value)
+            })
+            .collect())
+    }
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        for (key, _) in &key_values {
+            self.check_writable(key)?;
+        }
+        for (key, value) in key_values {
+            self.insert(&key, &value);
+        }
+        Ok(())
+    }
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        for key in &keys {
+            self.check_writable(key)?;
+        }
+        for key in keys {
+            self.values.lock().unwrap().remove(&key);
+        }
+        Ok(())
+    }
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let current = match self.value(&key) {
+            Some(value) => String::from_utf8_lossy(&value)
+                .parse::<i64>()
+                .map_err(|err| Error::Other(err.to_string()))?,
+            None => 0,
+        };
+        let value = current + delta;
+        self.insert(&key, value.to_string().as_bytes());
+        Ok(value)
+    }
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        let (_, _) = (key, bucket_rep);
+        Err(Error::Other("compare-and-swap is not implemented".into()))
+    }
+    fn supports_atomic_increment(&self) -> bool {
+        false
+    }
+}
+
 fn mock_store_manager() -> Arc<dyn StoreManager> {
     Arc::new(MockStoreManager)
 }
@@ -170,6 +1139,6 @@ impl Store for MockStore {
         key: &str,
     ) -> anyhow::Result<Arc<dyn Cas>, Error> {
         let (_, _) = (key, bucket_rep);
-        todo!()
+        Err(Error::Other("compare-and-swap is not implemented".into()))
     }
 }
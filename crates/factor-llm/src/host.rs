@@ -1,3 +1,4 @@
+use spin_world::spin::llm::llm::{self as v3};
 use spin_world::v1::llm::{self as v1};
 use spin_world::v2::llm::{self as v2};
 use tracing::field::Empty;
@@ -19,18 +20,7 @@ impl v2::Host for InstanceState {
         let mut engine = self.engine.lock().await;
         tracing::Span::current().record("llm.backend", engine.summary());
         engine
-            .infer(
-                model,
-                prompt,
-                params.unwrap_or(v2::InferencingParams {
-                    max_tokens: 100,
-                    repeat_penalty: 1.1,
-                    repeat_penalty_last_n_token_count: 64,
-                    temperature: 0.8,
-                    top_k: 40,
-                    top_p: 0.9,
-                }),
-            )
+            .infer(model, prompt, params.unwrap_or_else(default_params))
             .await
     }
 
@@ -53,6 +43,104 @@ impl v2::Host for InstanceState {
     }
 }
 
+impl v3::Host for InstanceState {
+    #[instrument(name = "spin_llm.infer", skip(self, prompt), err(level = Level::INFO), fields(otel.kind = "client", llm.backend = Empty, llm.cache_hit_tokens = Empty))]
+    async fn infer(
+        &mut self,
+        model: v3::InferencingModel,
+        prompt: String,
+        params: Option<v3::InferencingParams>,
+    ) -> Result<v3::InferencingResult, v3::Error> {
+        if !self.allowed_models.contains(&model) {
+            return Err(access_denied_error(&model).into());
+        }
+        let mut engine = self.engine.lock().await;
+        tracing::Span::current().record("llm.backend", engine.summary());
+        let cache_prompt_prefix = params.as_ref().is_some_and(|p| p.cache_prompt_prefix);
+        let params = params.map(Into::into).unwrap_or_else(default_params);
+        let result = if cache_prompt_prefix {
+            let result = engine
+                .infer_with_prompt_cache(model, prompt, params)
+                .await?;
+            tracing::Span::current().record("llm.cache_hit_tokens", result.usage.cache_hit_tokens);
+            result
+        } else {
+            engine.infer(model, prompt, params).await?.into()
+        };
+        Ok(result)
+    }
+
+    #[instrument(name = "spin_llm.infer_with_image", skip(self, prompt, images), err(level = Level::INFO), fields(otel.kind = "client", llm.backend = Empty, llm.image_count = images.len()))]
+    async fn infer_with_image(
+        &mut self,
+        model: v3::InferencingModel,
+        prompt: String,
+        images: Vec<v3::ImageData>,
+        params: Option<v3::InferencingParams>,
+    ) -> Result<v3::InferencingResult, v3::Error> {
+        if !self.allowed_models.contains(&model) {
+            return Err(access_denied_error(&model).into());
+        }
+        let mut engine = self.engine.lock().await;
+        tracing::Span::current().record("llm.backend", engine.summary());
+        if !engine.supports_images(&model).await? {
+            return Err(v3::Error::ModelNotSupported);
+        }
+        let params = params.map(Into::into).unwrap_or_else(default_params);
+        Ok(engine
+            .infer_with_image(model, prompt, images, params)
+            .await?)
+    }
+
+    #[instrument(name = "spin_llm.infer_with_tools", skip(self, messages, tools), err(level = Level::INFO), fields(otel.kind = "client", llm.backend = Empty))]
+    async fn infer_with_tools(
+        &mut self,
+        model: v3::InferencingModel,
+        messages: Vec<v3::Message>,
+        tools: Vec<v3::ToolDef>,
+        params: Option<v3::InferencingParams>,
+    ) -> Result<v3::ToolCallResult, v3::Error> {
+        if !self.allowed_models.contains(&model) {
+            return Err(access_denied_error(&model).into());
+        }
+        let mut engine = self.engine.lock().await;
+        tracing::Span::current().record("llm.backend", engine.summary());
+        let params = params.map(Into::into).unwrap_or_else(default_params);
+        Ok(engine
+            .infer_with_tools(model, messages, tools, params)
+            .await?)
+    }
+
+    async fn generate_embeddings(
+        &mut self,
+        model: v3::EmbeddingModel,
+        data: Vec<String>,
+    ) -> Result<v3::EmbeddingsResult, v3::Error> {
+        <Self as v2::Host>::generate_embeddings(self, model, data)
+            .await
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    #[instrument(name = "spin_llm.tokenize", skip(self, text), err(level = Level::INFO), fields(otel.kind = "client", llm.backend = Empty))]
+    async fn tokenize(
+        &mut self,
+        model: v3::EmbeddingModel,
+        text: String,
+    ) -> Result<u32, v3::Error> {
+        if !self.allowed_models.contains(&model) {
+            return Err(access_denied_error(&model).into());
+        }
+        let mut engine = self.engine.lock().await;
+        tracing::Span::current().record("llm.backend", engine.summary());
+        Ok(engine.tokenize(model, text).await?)
+    }
+
+    fn convert_error(&mut self, error: v3::Error) -> anyhow::Result<v3::Error> {
+        Ok(error)
+    }
+}
+
 impl v1::Host for InstanceState {
     async fn infer(
         &mut self,
@@ -82,6 +170,17 @@ impl v1::Host for InstanceState {
     }
 }
 
+fn default_params() -> v2::InferencingParams {
+    v2::InferencingParams {
+        max_tokens: 100,
+        repeat_penalty: 1.1,
+        repeat_penalty_last_n_token_count: 64,
+        temperature: 0.8,
+        top_k: 40,
+        top_p: 0.9,
+    }
+}
+
 fn access_denied_error(model: &str) -> v2::Error {
     v2::Error::InvalidInput(format!(
         "The component does not have access to use '{model}'. To give the component access, add '{model}' to the 'ai_models' key for the component in your spin.toml manifest"
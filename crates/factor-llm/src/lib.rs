@@ -9,6 +9,7 @@ use spin_factors::{
     ConfigureAppContext, Factor, FactorData, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
 };
 use spin_locked_app::MetadataKey;
+use spin_world::spin::llm::llm::{self as v3};
 use spin_world::v1::llm::{self as v1};
 use spin_world::v2::llm::{self as v2};
 use tokio::sync::Mutex;
@@ -39,6 +40,7 @@ impl Factor for LlmFactor {
     fn init(&mut self, ctx: &mut impl spin_factors::InitContext<Self>) -> anyhow::Result<()> {
         ctx.link_bindings(spin_world::v1::llm::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(spin_world::v2::llm::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(spin_world::spin::llm::llm::add_to_linker::<_, FactorData<Self>>)?;
         Ok(())
     }
 
@@ -61,10 +63,16 @@ impl Factor for LlmFactor {
                 ))
             })
             .collect::<anyhow::Result<_>>()?;
-        let engine = ctx
-            .take_runtime_config()
-            .map(|c| c.engine)
-            .unwrap_or_else(|| self.default_engine_creator.create());
+        let (engine, warm_models) = match ctx.take_runtime_config() {
+            Some(RuntimeConfig {
+                engine,
+                warm_models,
+            }) => (engine, warm_models),
+            None => (self.default_engine_creator.create(), Vec::new()),
+        };
+        if !warm_models.is_empty() {
+            spawn_warm_up(engine.clone(), warm_models);
+        }
         Ok(AppState {
             engine,
             component_allowed_models,
@@ -105,6 +113,39 @@ pub struct InstanceState {
 /// The runtime configuration for the LLM factor.
 pub struct RuntimeConfig {
     engine: Arc<Mutex<dyn LlmEngine>>,
+    /// Models to load into memory when the app is configured, rather than on
+    /// first use.
+    pub warm_models: Vec<String>,
+}
+
+impl RuntimeConfig {
+    /// Creates a new runtime configuration using the given engine.
+    pub fn new(engine: Arc<Mutex<dyn LlmEngine>>) -> Self {
+        Self {
+            engine,
+            warm_models: Vec::new(),
+        }
+    }
+}
+
+/// Warms up the given models in the background.
+///
+/// The engine lock is held while warming up, so any inference requests made in
+/// the meantime wait for loading to finish rather than loading models again.
+fn spawn_warm_up(engine: Arc<Mutex<dyn LlmEngine>>, models: Vec<String>) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        tracing::warn!("No async runtime available; not warming up LLM models {models:?}");
+        return;
+    };
+    runtime.spawn(async move {
+        let mut engine = engine.lock().await;
+        for model in models {
+            tracing::debug!("Warming up LLM model {model:?}");
+            if let Err(err) = engine.warm_up(&model).await {
+                tracing::warn!("Failed to warm up LLM model {model:?}: {err:#}");
+            }
+        }
+    });
 }
 
 impl SelfInstanceBuilder for InstanceState {}
@@ -125,6 +166,82 @@ pub trait LlmEngine: Send + Sync {
         data: Vec<String>,
     ) -> Result<v2::EmbeddingsResult, v2::Error>;
 
+    /// Returns the number of tokens the given embedding model's tokenizer
+    /// splits the text into, including any special tokens the model adds.
+    async fn tokenize(
+        &mut self,
+        model: v2::EmbeddingModel,
+        text: String,
+    ) -> Result<u32, v2::Error> {
+        let _ = (model, text);
+        Err(v2::Error::RuntimeError(
+            "tokenization is not supported by this LLM engine".into(),
+        ))
+    }
+
+    /// Performs inferencing like [`LlmEngine::infer`], reusing the model state
+    /// (KV cache) from a previous request whose prompt shares a prefix with
+    /// this one.
+    ///
+    /// The returned usage reports how many prompt tokens were served from the
+    /// cache. Engines without prompt caching fall back to `infer`, reporting
+    /// every prompt token as a cache miss.
+    async fn infer_with_prompt_cache(
+        &mut self,
+        model: v1::InferencingModel,
+        prompt: String,
+        params: v2::InferencingParams,
+    ) -> Result<v3::InferencingResult, v2::Error> {
+        self.infer(model, prompt, params).await.map(Into::into)
+    }
+
+    /// Returns whether the given inferencing model can accept images with its
+    /// prompt.
+    async fn supports_images(&mut self, model: &str) -> Result<bool, v2::Error> {
+        let _ = model;
+        Ok(false)
+    }
+
+    /// Performs inferencing like [`LlmEngine::infer`], with images placed in
+    /// the prompt.
+    ///
+    /// Only called for models for which [`LlmEngine::supports_images`]
+    /// returns true.
+    async fn infer_with_image(
+        &mut self,
+        model: v1::InferencingModel,
+        prompt: String,
+        images: Vec<v3::ImageData>,
+        params: v2::InferencingParams,
+    ) -> Result<v3::InferencingResult, v2::Error> {
+        let _ = (model, prompt, images, params);
+        Err(v2::Error::ModelNotSupported)
+    }
+
+    /// Performs inferencing over a conversation, allowing the model to answer
+    /// with a call to one of the given tools rather than with text.
+    async fn infer_with_tools(
+        &mut self,
+        model: v1::InferencingModel,
+        messages: Vec<v3::Message>,
+        tools: Vec<v3::ToolDef>,
+        params: v2::InferencingParams,
+    ) -> Result<v3::ToolCallResult, v2::Error> {
+        let _ = (model, messages, tools, params);
+        Err(v2::Error::RuntimeError(
+            "tool use is not supported by this LLM engine".into(),
+        ))
+    }
+
+    /// Loads the given model into memory without running inference, so that
+    /// the first request using it doesn't pay the cost of loading.
+    ///
+    /// Engines with nothing to load may leave this as a no-op.
+    async fn warm_up(&mut self, model: &str) -> anyhow::Result<()> {
+        let _ = model;
+        Ok(())
+    }
+
     /// A human-readable summary of the given engine's configuration
     ///
     /// Example: "local model"
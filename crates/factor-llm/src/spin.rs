@@ -15,6 +15,7 @@ use crate::{LlmEngine, LlmEngineCreator, RuntimeConfig};
 mod local {
     use super::*;
     pub use spin_llm_local::LocalLlmEngine;
+    use spin_world::spin::llm::llm as v3;
 
     #[async_trait]
     impl LlmEngine for LocalLlmEngine {
@@ -27,6 +28,39 @@ mod local {
             self.infer(model, prompt, params).await
         }
 
+        async fn infer_with_prompt_cache(
+            &mut self,
+            model: v2::InferencingModel,
+            prompt: String,
+            params: v2::InferencingParams,
+        ) -> Result<v3::InferencingResult, v2::Error> {
+            self.infer_with_prompt_cache(model, prompt, params).await
+        }
+
+        async fn supports_images(&mut self, model: &str) -> Result<bool, v2::Error> {
+            self.supports_images(model).await
+        }
+
+        async fn infer_with_image(
+            &mut self,
+            model: v2::InferencingModel,
+            prompt: String,
+            images: Vec<v3::ImageData>,
+            params: v2::InferencingParams,
+        ) -> Result<v3::InferencingResult, v2::Error> {
+            self.infer_with_image(model, prompt, images, params).await
+        }
+
+        async fn infer_with_tools(
+            &mut self,
+            model: v2::InferencingModel,
+            messages: Vec<v3::Message>,
+            tools: Vec<v3::ToolDef>,
+            params: v2::InferencingParams,
+        ) -> Result<v3::ToolCallResult, v2::Error> {
+            self.infer_with_tools(model, messages, tools, params).await
+        }
+
         async fn generate_embeddings(
             &mut self,
             model: v2::EmbeddingModel,
@@ -35,6 +69,18 @@ mod local {
             self.generate_embeddings(model, data).await
         }
 
+        async fn tokenize(
+            &mut self,
+            model: v2::EmbeddingModel,
+            text: String,
+        ) -> Result<u32, v2::Error> {
+            self.tokenize(model, text).await
+        }
+
+        async fn warm_up(&mut self, model: &str) -> anyhow::Result<()> {
+            Ok(self.warm_up(model).await?)
+        }
+
         fn summary(&self) -> Option<String> {
             Some("local model".to_string())
         }
@@ -96,12 +142,21 @@ pub fn runtime_config_from_toml(
         return Ok(None);
     };
     let config: LlmCompute = value.clone().try_into()?;
+    let WarmModels { warm_models } = value.clone().try_into()?;
 
     Ok(Some(RuntimeConfig {
         engine: config.into_engine(state_dir)?,
+        warm_models,
     }))
 }
 
+/// The `warm_models` list, which may be given for any `llm_compute` type.
+#[derive(Debug, serde::Deserialize)]
+struct WarmModels {
+    #[serde(default)]
+    warm_models: Vec<String>,
+}
+
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum LlmCompute {
@@ -1,9 +1,10 @@
 use std::collections::HashSet;
 use std::sync::Arc;
 
-use spin_factor_llm::{LlmEngine, LlmFactor};
+use spin_factor_llm::{LlmEngine, LlmFactor, RuntimeConfig};
 use spin_factors::{anyhow, RuntimeFactors};
 use spin_factors_test::{toml, TestEnvironment};
+use spin_world::spin::llm::llm::{self as v3};
 use spin_world::v1::llm::{self as v1};
 use spin_world::v2::llm::{self as v2, Host};
 use tokio::sync::Mutex;
@@ -41,6 +42,7 @@ async fn llm_works() -> anyhow::Result<()> {
         llm: LlmFactor::new(move || {
             Arc::new(Mutex::new(FakeLLm {
                 handle: handle.clone(),
+                warmed_up: Default::default(),
             })) as _
         }),
     };
@@ -73,8 +75,195 @@ async fn llm_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn prompt_caching_falls_back_to_uncached_inference() -> anyhow::Result<()> {
+    let handle = Box::new(|op| match op {
+        Operation::Inference { params, .. } => {
+            assert_eq!(params.max_tokens, 10);
+            Ok(v2::InferencingResult {
+                text: "response".to_owned(),
+                usage: v2::InferencingUsage {
+                    prompt_token_count: 5,
+                    generated_token_count: 1,
+                },
+            }
+            .into())
+        }
+        Operation::Embedding { .. } => Err(v2::Error::RuntimeError("unexpected".into())),
+    });
+    let factors = TestFactors {
+        llm: LlmFactor::new(move || {
+            Arc::new(Mutex::new(FakeLLm {
+                handle: handle.clone(),
+                warmed_up: Default::default(),
+            })) as _
+        }),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        ai_models = ["llama2-chat"]
+    });
+    let mut state = env.build_instance_state().await?;
+
+    let params = v3::InferencingParams {
+        max_tokens: 10,
+        repeat_penalty: 1.1,
+        repeat_penalty_last_n_token_count: 64,
+        temperature: 0.8,
+        top_k: 40,
+        top_p: 0.9,
+        cache_prompt_prefix: true,
+    };
+    let result = v3::Host::infer(
+        &mut state.llm,
+        "llama2-chat".into(),
+        "some prompt".into(),
+        Some(params),
+    )
+    .await?;
+    // The engine doesn't support prompt caching, so every prompt token misses
+    assert_eq!(result.usage.prompt_token_count, 5);
+    assert_eq!(result.usage.cache_hit_tokens, 0);
+    assert_eq!(result.usage.cache_miss_tokens, 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tokenize_works() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        llm: LlmFactor::new(|| {
+            Arc::new(Mutex::new(FakeLLm {
+                handle: Box::new(|_| Err(v2::Error::RuntimeError("unexpected operation".into()))),
+                warmed_up: Default::default(),
+            })) as _
+        }),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        ai_models = ["all-minilm-l6-v2"]
+    });
+    let mut state = env.build_instance_state().await?;
+
+    let count = v3::Host::tokenize(
+        &mut state.llm,
+        "all-minilm-l6-v2".into(),
+        "count these four words".into(),
+    )
+    .await?;
+    assert_eq!(count, 4);
+
+    assert!(matches!(
+        v3::Host::tokenize(&mut state.llm, "unknown-model".into(), "text".into()).await,
+        Err(v3::Error::InvalidInput(msg)) if msg.contains("The component does not have access to use")
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn infer_with_tools_checks_model_access() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        llm: LlmFactor::new(|| {
+            Arc::new(Mutex::new(FakeLLm {
+                handle: Box::new(|_| Err(v2::Error::RuntimeError("unexpected operation".into()))),
+                warmed_up: Default::default(),
+            })) as _
+        }),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        ai_models = ["llama2-chat"]
+    });
+    let mut state = env.build_instance_state().await?;
+    let messages = vec![v3::Message {
+        role: v3::MessageRole::User,
+        content: "What's the weather in Paris?".into(),
+    }];
+
+    assert!(matches!(
+        v3::Host::infer_with_tools(&mut state.llm, "unknown-model".into(), messages.clone(), vec![], None).await,
+        Err(v3::Error::InvalidInput(msg)) if msg.contains("The component does not have access to use")
+    ));
+    // The fake engine doesn't implement tool use
+    assert!(matches!(
+        v3::Host::infer_with_tools(&mut state.llm, "llama2-chat".into(), messages, vec![], None).await,
+        Err(v3::Error::RuntimeError(msg)) if msg.contains("tool use is not supported")
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn infer_with_image_requires_vision_model() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        llm: LlmFactor::new(|| {
+            Arc::new(Mutex::new(FakeLLm {
+                handle: Box::new(|_| Err(v2::Error::RuntimeError("unexpected operation".into()))),
+                warmed_up: Default::default(),
+            })) as _
+        }),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        ai_models = ["llama2-chat"]
+    });
+    let mut state = env.build_instance_state().await?;
+    let images = vec![v3::ImageData {
+        format: v3::ImageFormat::Png,
+        bytes: vec![],
+    }];
+
+    // The fake engine's models don't accept images
+    assert!(matches!(
+        v3::Host::infer_with_image(
+            &mut state.llm,
+            "llama2-chat".into(),
+            "<image> what is this?".into(),
+            images,
+            None
+        )
+        .await,
+        Err(v3::Error::ModelNotSupported)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn warm_models_are_warmed_up() -> anyhow::Result<()> {
+    let warmed_up = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let engine = Arc::new(Mutex::new(FakeLLm {
+        handle: Box::new(|_| Err(v2::Error::RuntimeError("unexpected operation".into()))),
+        warmed_up: warmed_up.clone(),
+    }));
+    let factors = TestFactors {
+        llm: LlmFactor::new(|| -> Arc<Mutex<dyn LlmEngine>> {
+            unreachable!("runtime config provides the engine")
+        }),
+    };
+    let mut runtime_config = RuntimeConfig::new(engine.clone());
+    runtime_config.warm_models = vec!["llama2-chat".into(), "all-minilm-l6-v2".into()];
+    let env = TestEnvironment::new(factors).runtime_config(TestFactorsRuntimeConfig {
+        llm: Some(runtime_config),
+    })?;
+    env.build_instance_state().await?;
+
+    // Warm-up happens in the background while holding the engine lock
+    while warmed_up.lock().unwrap().len() < 2 {
+        tokio::task::yield_now().await;
+    }
+    let _engine = engine.lock().await;
+    assert_eq!(
+        *warmed_up.lock().unwrap(),
+        ["llama2-chat".to_owned(), "all-minilm-l6-v2".to_owned()]
+    );
+    Ok(())
+}
+
 struct FakeLLm {
     handle: Box<dyn Fn(Operation) -> Result<OperationResult, v2::Error> + Sync + Send>,
+    warmed_up: Arc<std::sync::Mutex<Vec<String>>>,
 }
 
 #[allow(dead_code)]
@@ -137,4 +326,17 @@ impl LlmEngine for FakeLLm {
         };
         Ok(e)
     }
+
+    async fn tokenize(
+        &mut self,
+        _model: v2::EmbeddingModel,
+        text: String,
+    ) -> Result<u32, v2::Error> {
+        Ok(text.split_whitespace().count() as u32)
+    }
+
+    async fn warm_up(&mut self, model: &str) -> anyhow::Result<()> {
+        self.warmed_up.lock().unwrap().push(model.to_owned());
+        Ok(())
+    }
 }
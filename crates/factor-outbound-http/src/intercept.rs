@@ -1,8 +1,17 @@
-use http::{Request, Response};
+mod caching;
+mod circuit_breaker;
+mod signing;
+
+use http::{HeaderMap, Request, Response, StatusCode, Uri};
 use http_body_util::{BodyExt, Full};
+use hyper::body::Body;
 use spin_world::async_trait;
 use wasmtime_wasi_http::{body::HyperOutgoingBody, HttpResult};
 
+pub use caching::CachingInterceptor;
+pub use circuit_breaker::CircuitBreakerInterceptor;
+pub use signing::SigningInterceptor;
+
 pub type HyperBody = HyperOutgoingBody;
 
 /// An outbound HTTP request interceptor to be used with
@@ -19,6 +28,53 @@ pub trait OutboundHttpInterceptor: Send + Sync {
     /// will be returned as the result of the request, bypassing the default
     /// handler. The `request` will also be dropped immediately.
     async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome>;
+
+    /// Observe the outcome of a request which this interceptor passed on to
+    /// the default outgoing request handler with [`InterceptOutcome::Continue`].
+    ///
+    /// This is called once the response head has been received or the request
+    /// has failed. It is not called for requests completed by the interceptor.
+    fn observe(&self, uri: &Uri, outcome: RequestOutcome) {
+        let _ = (uri, outcome);
+    }
+
+    /// Intercept the response to a request which this interceptor passed on
+    /// to the default outgoing request handler with
+    /// [`InterceptOutcome::Continue`].
+    ///
+    /// `request` is the envelope of the request as it was sent, including any
+    /// extensions set by [`Self::intercept`]. The returned response will be
+    /// returned as the result of the request in place of `response`.
+    async fn intercept_response(
+        &self,
+        request: &Request<()>,
+        response: Response<HyperBody>,
+    ) -> HttpResult<Response<HyperBody>> {
+        let _ = request;
+        Ok(response)
+    }
+}
+
+/// The outcome of a request passed to [`OutboundHttpInterceptor::observe`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestOutcome {
+    /// A response with the given status was received.
+    Response(StatusCode),
+    /// The request failed without receiving a response.
+    Failed,
+}
+
+/// Splits the envelope off of a request that is about to be sent, for use
+/// with [`OutboundHttpInterceptor::intercept_response`]. Extensions are
+/// moved to the envelope.
+pub(crate) fn take_envelope<B>(request: &mut Request<B>) -> Request<()> {
+    let mut envelope = Request::new(());
+    *envelope.method_mut() = request.method().clone();
+    *envelope.uri_mut() = request.uri().clone();
+    *envelope.version_mut() = request.version();
+    *envelope.headers_mut() = request.headers().clone();
+    *envelope.extensions_mut() = std::mem::take(request.extensions_mut());
+    envelope
 }
 
 /// The type returned by an [`OutboundHttpInterceptor`].
@@ -39,6 +95,8 @@ pub enum InterceptOutcome {
 pub struct InterceptRequest {
     inner: Request<()>,
     body: InterceptBody,
+    /// The trailers of a body which has been buffered.
+    trailers: Option<HeaderMap>,
     pub(crate) override_connect_host: Option<String>,
 }
 
@@ -62,7 +120,42 @@ impl InterceptRequest {
 
     pub fn into_hyper_request(self) -> Request<HyperBody> {
         let (parts, ()) = self.inner.into_parts();
-        Request::from_parts(parts, self.body.into())
+        let body = HyperBody::from(self.body);
+        let body = match self.trailers {
+            Some(trailers) => body
+                .with_trailers(std::future::ready(Some(Ok(trailers))))
+                .boxed(),
+            None => body,
+        };
+        Request::from_parts(parts, body)
+    }
+
+    /// Returns the body if it is already in memory, or is known to be empty.
+    pub(crate) fn buffered_body(&self) -> Option<&[u8]> {
+        match &self.body {
+            InterceptBody::Vec(bytes) => Some(bytes),
+            InterceptBody::Hyper(body)
+                if body.is_end_stream() || body.size_hint().exact() == Some(0) =>
+            {
+                Some(&[])
+            }
+            InterceptBody::Hyper(_) => None,
+        }
+    }
+
+    /// Reads the whole body into memory, returning it. Any trailers are kept
+    /// to be sent after the buffered body.
+    pub(crate) async fn buffer_body(&mut self) -> HttpResult<&[u8]> {
+        if let InterceptBody::Hyper(body) = &mut self.body {
+            let body = std::mem::take(body);
+            let collected = body.collect().await?;
+            self.trailers = collected.trailers().cloned();
+            self.body = InterceptBody::Vec(collected.to_bytes().into());
+        }
+        let InterceptBody::Vec(bytes) = &self.body else {
+            unreachable!("body was just buffered");
+        };
+        Ok(bytes)
     }
 
     pub(crate) fn into_vec_request(self) -> Option<Request<Vec<u8>>> {
@@ -94,6 +187,7 @@ impl From<Request<HyperBody>> for InterceptRequest {
         Self {
             inner: Request::from_parts(parts, ()),
             body: InterceptBody::Hyper(body),
+            trailers: None,
             override_connect_host: None,
         }
     }
@@ -105,6 +199,7 @@ impl From<Request<Vec<u8>>> for InterceptRequest {
         Self {
             inner: Request::from_parts(parts, ()),
             body: InterceptBody::Vec(body),
+            trailers: None,
             override_connect_host: None,
         }
     }
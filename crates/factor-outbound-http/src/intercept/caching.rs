@@ -0,0 +1,302 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http::{header, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Body;
+use lru::LruCache;
+use spin_world::async_trait;
+use wasmtime_wasi_http::HttpResult;
+
+use super::{HyperBody, InterceptOutcome, InterceptRequest, OutboundHttpInterceptor};
+
+/// The largest response body that will be cached.
+const MAX_CACHED_BODY_SIZE: u64 = 1024 * 1024;
+
+/// Request headers which are not part of the cache key.
+///
+/// Trace context headers are injected per request and would otherwise prevent
+/// any request from ever matching a cached response.
+const UNKEYED_HEADERS: [&str; 2] = ["traceparent", "tracestate"];
+
+/// An [`OutboundHttpInterceptor`] which caches responses to `GET` requests.
+///
+/// Responses are cached according to their `Cache-Control` header: a response
+/// with `max-age` is served from the cache until it becomes stale. Stale
+/// responses (and responses without `max-age`) which carry an `ETag` or
+/// `Last-Modified` validator are revalidated with a conditional request; a
+/// `304 Not Modified` response refreshes the cached response, which is
+/// returned in its place. Responses marked `no-store` are never cached.
+///
+/// Responses are keyed on the full request URL and headers, so requests which
+/// differ in any header are cached separately. Requests which are already
+/// conditional are passed through untouched.
+///
+/// The cache is shared between clones, so a single interceptor can be cloned
+/// into each instance to share cached responses across the whole app.
+#[derive(Clone)]
+pub struct CachingInterceptor {
+    cache: Arc<Mutex<LruCache<String, CachedResponse>>>,
+}
+
+/// A response stored by a [`CachingInterceptor`].
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored_at: Instant,
+    freshness_lifetime: Duration,
+}
+
+/// The cache key of a request, passed from `intercept` to `intercept_response`.
+#[derive(Clone)]
+struct CacheKey(String);
+
+impl CachingInterceptor {
+    /// Creates a new `CachingInterceptor` holding up to `capacity` responses.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            cache: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    /// Returns the number of responses currently cached.
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    /// Returns true if no responses are currently cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Buffers and caches the given response if it is cacheable.
+    async fn store(
+        &self,
+        key: &str,
+        response: Response<HyperBody>,
+    ) -> HttpResult<Response<HyperBody>> {
+        let cache_control = CacheControl::parse(response.headers());
+        if cache_control.no_store {
+            return Ok(response);
+        }
+        let freshness_lifetime = freshness_lifetime(response.headers(), &cache_control);
+        if freshness_lifetime.is_zero() && !has_validator(response.headers()) {
+            return Ok(response);
+        }
+        if response
+            .body()
+            .size_hint()
+            .upper()
+            .is_none_or(|size| size > MAX_CACHED_BODY_SIZE)
+        {
+            return Ok(response);
+        }
+
+        let (parts, body) = response.into_parts();
+        let collected = body.collect().await?;
+        // Trailers aren't cached, so a response with them is passed on as is
+        if let Some(trailers) = collected.trailers().cloned() {
+            let body = Full::new(collected.to_bytes())
+                .map_err(|err| match err {})
+                .with_trailers(std::future::ready(Some(Ok(trailers))))
+                .boxed();
+            return Ok(Response::from_parts(parts, body));
+        }
+        let body = collected.to_bytes();
+        let cached = CachedResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+            stored_at: Instant::now(),
+            freshness_lifetime,
+        };
+        let response = cached.to_response();
+        self.cache.lock().unwrap().put(key.to_owned(), cached);
+        Ok(response)
+    }
+}
+
+impl CachedResponse {
+    /// Returns true if the response may be served without revalidation.
+    fn is_fresh(&self) -> bool {
+        self.stored_at.elapsed() < self.freshness_lifetime
+    }
+
+    /// Adds conditional headers for revalidating this response to a request.
+    fn add_validators(&self, headers: &mut HeaderMap) {
+        if let Some(etag) = self.headers.get(header::ETAG) {
+            headers.insert(header::IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = self.headers.get(header::LAST_MODIFIED) {
+            headers.insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+        }
+    }
+
+    /// Refreshes this response with the headers of a `304 Not Modified`
+    /// response to a revalidation request.
+    fn revalidate(&mut self, not_modified: &HeaderMap) {
+        for name in not_modified.keys() {
+            if matches!(*name, header::CONTENT_LENGTH | header::TRANSFER_ENCODING) {
+                continue;
+            }
+            self.headers.remove(name);
+            for value in not_modified.get_all(name) {
+                self.headers.append(name.clone(), value.clone());
+            }
+        }
+        let cache_control = CacheControl::parse(&self.headers);
+        self.freshness_lifetime = freshness_lifetime(&self.headers, &cache_control);
+        self.stored_at = Instant::now();
+    }
+
+    fn to_response(&self) -> Response<HyperBody> {
+        let mut response = Response::new(
+            Full::new(self.body.clone())
+                .map_err(|err| match err {})
+                .boxed(),
+        );
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for CachingInterceptor {
+    async fn intercept(&self, mut request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        if request.method() != Method::GET || is_conditional(request.headers()) {
+            return Ok(InterceptOutcome::Continue(request));
+        }
+        let cache_control = CacheControl::parse(request.headers());
+        if cache_control.no_store {
+            return Ok(InterceptOutcome::Continue(request));
+        }
+
+        let key = cache_key(&request);
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(cached) = cache.get(&key) {
+                if cached.is_fresh() && !cache_control.no_cache {
+                    tracing::debug!("Serving {} from outbound HTTP cache", request.uri());
+                    return Ok(InterceptOutcome::Complete(cached.to_response()));
+                }
+                cached.add_validators(request.headers_mut());
+            }
+        }
+        request.extensions_mut().insert(CacheKey(key));
+        Ok(InterceptOutcome::Continue(request))
+    }
+
+    async fn intercept_response(
+        &self,
+        request: &Request<()>,
+        response: Response<HyperBody>,
+    ) -> HttpResult<Response<HyperBody>> {
+        let Some(CacheKey(key)) = request.extensions().get() else {
+            return Ok(response);
+        };
+        match response.status() {
+            StatusCode::NOT_MODIFIED => {
+                let mut cache = self.cache.lock().unwrap();
+                let Some(cached) = cache.get_mut(key) else {
+                    return Ok(response);
+                };
+                tracing::debug!("Revalidated {} in outbound HTTP cache", request.uri());
+                cached.revalidate(response.headers());
+                Ok(cached.to_response())
+            }
+            StatusCode::OK => self.store(key, response).await,
+            _ => Ok(response),
+        }
+    }
+}
+
+/// The `Cache-Control` directives relevant to a private cache.
+#[derive(Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(headers: &HeaderMap) -> Self {
+        let mut cache_control = Self::default();
+        let directives = headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            if name.eq_ignore_ascii_case("no-store") {
+                cache_control.no_store = true;
+            } else if name.eq_ignore_ascii_case("no-cache") {
+                cache_control.no_cache = true;
+            } else if name.eq_ignore_ascii_case("max-age") {
+                cache_control.max_age = value.and_then(|value| value.parse().ok());
+            }
+        }
+        cache_control
+    }
+}
+
+/// Returns how long a response may be served from the cache without
+/// revalidation.
+fn freshness_lifetime(headers: &HeaderMap, cache_control: &CacheControl) -> Duration {
+    if cache_control.no_cache {
+        return Duration::ZERO;
+    }
+    let Some(max_age) = cache_control.max_age else {
+        return Duration::ZERO;
+    };
+    let age = headers
+        .get(header::AGE)
+        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+        .unwrap_or(0);
+    Duration::from_secs(max_age.saturating_sub(age))
+}
+
+fn has_validator(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::ETAG) || headers.contains_key(header::LAST_MODIFIED)
+}
+
+fn is_conditional(headers: &HeaderMap) -> bool {
+    [
+        header::IF_MATCH,
+        header::IF_NONE_MATCH,
+        header::IF_MODIFIED_SINCE,
+        header::IF_UNMODIFIED_SINCE,
+        header::IF_RANGE,
+    ]
+    .iter()
+    .any(|name| headers.contains_key(name))
+}
+
+/// Returns the cache key for a request: its full URL and headers.
+fn cache_key(request: &Request<()>) -> String {
+    let mut headers: Vec<(&str, &HeaderValue)> = request
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.as_str(), value))
+        .filter(|(name, _)| !UNKEYED_HEADERS.contains(name))
+        .collect();
+    headers.sort_by(|a, b| (a.0, a.1.as_bytes()).cmp(&(b.0, b.1.as_bytes())));
+
+    let mut key = request.uri().to_string();
+    for (name, value) in headers {
+        key.push('\n');
+        key.push_str(name);
+        key.push(':');
+        key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+    }
+    key
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use http::{Response, StatusCode, Uri};
+use http_body_util::{BodyExt, Empty};
+use spin_world::async_trait;
+use wasmtime_wasi_http::HttpResult;
+
+use super::{
+    HyperBody, InterceptOutcome, InterceptRequest, OutboundHttpInterceptor, RequestOutcome,
+};
+use crate::runtime_config::CircuitBreakerConfig;
+
+/// An [`OutboundHttpInterceptor`] which stops sending requests to hosts that
+/// are failing.
+///
+/// Consecutive failures (connection errors or `5xx` responses) are tracked per
+/// host. Once `failure_threshold` is reached the circuit for that host opens
+/// and requests are completed with a synthetic `503 Service Unavailable`
+/// without touching the network. After `open_duration` the circuit goes
+/// half-open: a limited number of trial requests are let through, and their
+/// outcome decides whether the circuit closes again or re-opens.
+///
+/// Only hosts with recent failures are tracked: a success forgets the host,
+/// and hosts whose failures are all older than the configured window are
+/// evicted.
+///
+/// State is shared between clones, so a single interceptor can be cloned into
+/// each instance to track hosts across the whole app.
+#[derive(Clone)]
+pub struct CircuitBreakerInterceptor {
+    config: Arc<CircuitBreakerConfig>,
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    /// Requests are sent; the times of recent failures are tracked.
+    Closed { failures: VecDeque<Instant> },
+    /// Requests fail fast until the given time.
+    Open { until: Instant },
+    /// A limited number of trial requests are sent to test the host.
+    HalfOpen { since: Instant, probes: u32 },
+}
+
+impl Circuit {
+    /// Returns true if the circuit has seen no activity for long enough that
+    /// forgetting it wouldn't change how requests are treated.
+    fn is_idle(&self, now: Instant, config: &CircuitBreakerConfig) -> bool {
+        let last_active = match self {
+            Circuit::Closed { failures } => match failures.back() {
+                Some(&failed_at) => failed_at,
+                None => return true,
+            },
+            Circuit::Open { until } => *until,
+            Circuit::HalfOpen { since, .. } => *since + config.open_duration,
+        };
+        now.saturating_duration_since(last_active) > config.window
+    }
+}
+
+impl CircuitBreakerInterceptor {
+    /// Creates a new `CircuitBreakerInterceptor` which allows a single trial
+    /// request at a time to a half-open circuit.
+    ///
+    /// A `failure_threshold` of zero is treated as one.
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self::with_config(CircuitBreakerConfig {
+            failure_threshold,
+            open_duration,
+            half_open_max: 1,
+            ..Default::default()
+        })
+    }
+
+    /// Creates a new `CircuitBreakerInterceptor` with the given config.
+    ///
+    /// A `failure_threshold` or `half_open_max` of zero is treated as one.
+    pub fn with_config(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config: Arc::new(CircuitBreakerConfig {
+                failure_threshold: config.failure_threshold.max(1),
+                half_open_max: config.half_open_max.max(1),
+                ..config
+            }),
+            circuits: Default::default(),
+        }
+    }
+
+    /// Returns the number of consecutive failures needed to open a circuit.
+    pub fn failure_threshold(&self) -> u32 {
+        self.config.failure_threshold
+    }
+
+    /// Returns how long a circuit stays open before a trial request is allowed.
+    pub fn open_duration(&self) -> Duration {
+        self.config.open_duration
+    }
+
+    /// Returns true if requests to the given host are currently short-circuited.
+    pub fn is_open(&self, host: &str) -> bool {
+        let circuits = self.circuits.lock().unwrap();
+        match circuits.get(&host.to_ascii_lowercase()) {
+            Some(Circuit::Open { until }) => Instant::now() < *until,
+            _ => false,
+        }
+    }
+
+    /// Asks to send a request to the given host.
+    ///
+    /// Returns `None` if the circuit is open, in which case the request should
+    /// fail without being sent. Otherwise the outcome of the request should be
+    /// recorded with the returned [`Permit`].
+    pub(crate) fn acquire(&self, host: &str) -> Option<Permit> {
+        let host = host.to_ascii_lowercase();
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let probe = match circuits.get_mut(&host) {
+            None | Some(Circuit::Closed { .. }) => false,
+            Some(Circuit::Open { until }) if now < *until => return None,
+            Some(circuit @ Circuit::Open { .. }) => {
+                *circuit = Circuit::HalfOpen {
+                    since: now,
+                    probes: 1,
+                };
+                true
+            }
+            Some(Circuit::HalfOpen { since, probes }) => {
+                // Trials which never reported back (e.g. because the guest
+                // dropped them) stop counting once another open duration
+                // has passed
+                if now.duration_since(*since) >= self.config.open_duration {
+                    *since = now;
+                    *probes = 0;
+                }
+                if *probes >= self.config.half_open_max {
+                    return None;
+                }
+                *probes += 1;
+                true
+            }
+        };
+        Some(Permit {
+            breaker: self.clone(),
+            host,
+            probe,
+            recorded: false,
+        })
+    }
+
+    fn record(&self, host: &str, status: Option<StatusCode>, probe: bool) {
+        let failed = status.is_none_or(|status| self.config.is_failure_status(status));
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        if !failed {
+            match circuits.get(host) {
+                Some(Circuit::Closed { .. }) => {
+                    circuits.remove(host);
+                }
+                Some(Circuit::HalfOpen { .. }) if probe => {
+                    tracing::info!("Closing outbound HTTP circuit for {host:?}");
+                    circuits.remove(host);
+                }
+                _ => (),
+            }
+            return;
+        }
+        if !circuits.contains_key(host) {
+            // Only hosts with recent failures are tracked, so sweep out those
+            // which have recovered before tracking another
+            circuits.retain(|_, circuit| !circuit.is_idle(now, &self.config));
+            circuits.insert(
+                host.to_owned(),
+                Circuit::Closed {
+                    failures: VecDeque::new(),
+                },
+            );
+        }
+        let circuit = circuits.get_mut(host).unwrap();
+        let open = Circuit::Open {
+            until: now + self.config.open_duration,
+        };
+        match circuit {
+            Circuit::Closed { failures } => {
+                failures.push_back(now);
+                while failures
+                    .front()
+                    .is_some_and(|&failed_at| now.duration_since(failed_at) > self.config.window)
+                {
+                    failures.pop_front();
+                }
+                if failures.len() >= self.config.failure_threshold as usize {
+                    tracing::warn!("Opening outbound HTTP circuit for {host:?}");
+                    *circuit = open;
+                }
+            }
+            Circuit::HalfOpen { .. } if probe => {
+                tracing::warn!(
+                    "Trial request failed; re-opening outbound HTTP circuit for {host:?}"
+                );
+                *circuit = open;
+            }
+            // Outcomes of requests sent before the circuit last changed state
+            // are ignored
+            _ => (),
+        }
+    }
+
+    #[cfg(test)]
+    fn tracked_hosts(&self) -> usize {
+        self.circuits.lock().unwrap().len()
+    }
+}
+
+/// Permission to send a single request through a [`CircuitBreakerInterceptor`].
+pub(crate) struct Permit {
+    breaker: CircuitBreakerInterceptor,
+    host: String,
+    // Whether this request is a half-open trial
+    probe: bool,
+    recorded: bool,
+}
+
+impl Permit {
+    /// Records the response status of the request, or `None` if it failed to
+    /// produce a response.
+    pub(crate) fn record(mut self, status: Option<StatusCode>) {
+        self.recorded = true;
+        self.breaker.record(&self.host, status, self.probe);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        // A trial which never completed (e.g. because the guest dropped the
+        // pending response) frees its slot for another trial
+        if self.probe && !self.recorded {
+            let mut circuits = self.breaker.circuits.lock().unwrap();
+            if let Some(Circuit::HalfOpen { probes, .. }) = circuits.get_mut(&self.host) {
+                *probes = probes.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for CircuitBreakerInterceptor {
+    async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        let Some(host) = request.uri().host() else {
+            return Ok(InterceptOutcome::Continue(request));
+        };
+        if let Some(mut permit) = self.acquire(host) {
+            // The outcome is recorded by `observe`, which can't be given the
+            // permit, so a trial's slot is held until then
+            permit.recorded = true;
+            return Ok(InterceptOutcome::Continue(request));
+        }
+        tracing::debug!("Outbound HTTP circuit for {host:?} is open; short-circuiting request");
+        let body: HyperBody = Empty::<Bytes>::new().map_err(|err| match err {}).boxed();
+        let resp = Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(body)
+            .unwrap();
+        Ok(InterceptOutcome::Complete(resp))
+    }
+
+    fn observe(&self, uri: &Uri, outcome: RequestOutcome) {
+        let Some(host) = uri.host() else {
+            return;
+        };
+        let status = match outcome {
+            RequestOutcome::Response(status) => Some(status),
+            RequestOutcome::Failed => None,
+        };
+        // Any outcome observed while half-open is that of a trial request
+        self.record(&host.to_ascii_lowercase(), status, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(window: Duration, open_duration: Duration) -> CircuitBreakerInterceptor {
+        CircuitBreakerInterceptor::with_config(CircuitBreakerConfig {
+            failure_threshold: 2,
+            window,
+            open_duration,
+            half_open_max: 1,
+            failure_statuses: None,
+        })
+    }
+
+    #[test]
+    fn half_open_probes_are_limited() {
+        let breaker = breaker(Duration::from_secs(60), Duration::from_millis(50));
+        for _ in 0..2 {
+            breaker.acquire("a.test").unwrap().record(None);
+        }
+        assert!(breaker.acquire("a.test").is_none());
+        std::thread::sleep(Duration::from_millis(60));
+
+        // The open duration has elapsed, so a single probe is allowed
+        let probe = breaker.acquire("a.test").expect("probe should be allowed");
+        assert!(breaker.acquire("a.test").is_none());
+
+        // Dropping an unfinished probe allows another
+        drop(probe);
+        let probe = breaker.acquire("a.test").expect("probe should be allowed");
+        probe.record(Some(StatusCode::OK));
+        assert!(breaker.acquire("a.test").is_some());
+        assert!(breaker.acquire("a.test").is_some());
+    }
+
+    #[test]
+    fn only_failures_within_window_count() {
+        let breaker = breaker(Duration::ZERO, Duration::from_secs(60));
+        for _ in 0..3 {
+            breaker.acquire("a.test").unwrap().record(None);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(breaker.acquire("a.test").is_some());
+    }
+
+    #[test]
+    fn authorities_are_case_insensitive() {
+        let breaker = breaker(Duration::from_secs(60), Duration::from_secs(60));
+        breaker.acquire("A.test").unwrap().record(None);
+        breaker.acquire("a.TEST").unwrap().record(None);
+        assert!(breaker.is_open("a.test"));
+        assert!(breaker.acquire("A.TEST").is_none());
+    }
+
+    #[test]
+    fn healthy_and_recovered_hosts_are_not_tracked() {
+        let breaker = breaker(Duration::from_millis(10), Duration::from_secs(60));
+        for n in 0..100 {
+            let host = format!("host-{n}.test");
+            breaker.acquire(&host).unwrap().record(Some(StatusCode::OK));
+        }
+        assert_eq!(breaker.tracked_hosts(), 0);
+
+        breaker.acquire("a.test").unwrap().record(None);
+        breaker.acquire("b.test").unwrap().record(None);
+        breaker
+            .acquire("b.test")
+            .unwrap()
+            .record(Some(StatusCode::OK));
+        assert_eq!(breaker.tracked_hosts(), 1);
+
+        // Once its failure is outside the window, a host is swept out when
+        // another starts failing
+        std::thread::sleep(Duration::from_millis(20));
+        breaker.acquire("c.test").unwrap().record(None);
+        assert_eq!(breaker.tracked_hosts(), 1);
+    }
+}
@@ -0,0 +1,517 @@
+use std::sync::Arc;
+
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use http::{header, HeaderMap, HeaderValue, Method, Uri};
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use spin_expressions::{ProviderResolver, Template};
+use spin_world::async_trait;
+use wasmtime_wasi_http::{bindings::http::types::ErrorCode, HttpResult};
+
+use super::{InterceptOutcome, InterceptRequest, OutboundHttpInterceptor};
+use crate::runtime_config::{HmacEncoding, SigningRule, SigningScheme};
+
+/// The payload hash used for SigV4 requests whose body is streamed.
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Characters which SigV4 leaves unencoded.
+const SIGV4_UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// An [`OutboundHttpInterceptor`] which signs requests to configured hosts.
+///
+/// Credentials are application variables, resolved when each request is
+/// signed. The signing interceptor runs after any other request interceptor,
+/// so the request that is signed is the one that is sent.
+///
+/// SigV4 signs buffered bodies; streamed bodies are sent with an
+/// `UNSIGNED-PAYLOAD` content hash. The `hmac-header` scheme always signs the
+/// body, so streamed bodies are read into memory first.
+pub struct SigningInterceptor {
+    rules: Vec<SigningRule>,
+    resolver: Arc<ProviderResolver>,
+}
+
+impl SigningInterceptor {
+    /// Creates a new `SigningInterceptor` which resolves credentials with
+    /// `resolver`.
+    pub fn new(rules: Vec<SigningRule>, resolver: Arc<ProviderResolver>) -> Self {
+        Self { rules, resolver }
+    }
+
+    /// Signs `request` if its host matches one of the rules.
+    pub(crate) async fn sign(&self, request: &mut InterceptRequest) -> HttpResult<()> {
+        let Some(host) = request.uri().host() else {
+            return Ok(());
+        };
+        let Some(rule) = self.rules.iter().find(|rule| rule.matches(host)) else {
+            return Ok(());
+        };
+        match &rule.scheme {
+            SigningScheme::SigV4 {
+                region,
+                service,
+                access_key_variable,
+                secret_key_variable,
+                session_token_variable,
+            } => {
+                let access_key = self.resolve(access_key_variable).await?;
+                let secret_key = self.resolve(secret_key_variable).await?;
+                let session_token = match session_token_variable {
+                    Some(variable) => Some(self.resolve(variable).await?),
+                    None => None,
+                };
+                let payload_hash = match request.buffered_body() {
+                    Some(body) => hex::encode(Sha256::digest(body)),
+                    None => UNSIGNED_PAYLOAD.to_owned(),
+                };
+                let signer = SigV4 {
+                    access_key: &access_key,
+                    secret_key: &secret_key,
+                    session_token: session_token.as_deref(),
+                    region,
+                    service,
+                };
+                signer
+                    .sign(request, &payload_hash, Utc::now())
+                    .map_err(|err| internal_error(format!("invalid SigV4 header: {err}")))?;
+            }
+            SigningScheme::HmacHeader {
+                header,
+                encoding,
+                key_variable,
+            } => {
+                let key = self.resolve(key_variable).await?;
+                let signature = hmac_sha256(key.as_bytes(), request.buffer_body().await?);
+                let signature = match encoding {
+                    HmacEncoding::Hex => hex::encode(signature),
+                    HmacEncoding::Base64 => {
+                        base64::engine::general_purpose::STANDARD.encode(signature)
+                    }
+                };
+                request.headers_mut().insert(
+                    header.clone(),
+                    HeaderValue::from_str(&signature).expect("encoded signature is ASCII"),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn resolve(&self, variable: &str) -> HttpResult<String> {
+        let template = Template::new(format!("{{{{ {variable} }}}}"))
+            .map_err(|err| internal_error(format!("invalid variable {variable:?}: {err}")))?;
+        self.resolver
+            .resolve_template(&template)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    %err, "error.type" = "variable_resolution_failed",
+                    "Error resolving signing credentials for outbound HTTP request",
+                );
+                internal_error(format!("failed to resolve signing credentials: {err}"))
+            })
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for SigningInterceptor {
+    async fn intercept(&self, mut request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        self.sign(&mut request).await?;
+        Ok(InterceptOutcome::Continue(request))
+    }
+}
+
+fn internal_error(message: String) -> wasmtime_wasi_http::HttpError {
+    ErrorCode::InternalError(Some(message)).into()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Signs requests with AWS Signature Version 4.
+struct SigV4<'a> {
+    access_key: &'a str,
+    secret_key: &'a str,
+    session_token: Option<&'a str>,
+    region: &'a str,
+    service: &'a str,
+}
+
+impl SigV4<'_> {
+    /// Adds the SigV4 headers, including `Authorization`, to `request`.
+    ///
+    /// The `Host`, `Content-Type`, `Content-MD5`, and `X-Amz-*` headers are
+    /// signed; other headers may be changed on the way to the server.
+    fn sign(
+        &self,
+        request: &mut http::Request<()>,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), http::header::InvalidHeaderValue> {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let headers = request.headers_mut();
+        headers.insert("x-amz-date", HeaderValue::from_str(&amz_date)?);
+        headers.insert("x-amz-content-sha256", HeaderValue::from_str(payload_hash)?);
+        if let Some(token) = self.session_token {
+            headers.insert("x-amz-security-token", HeaderValue::from_str(token)?);
+        }
+
+        let mut signed_headers = Vec::new();
+        // The host header is set from the URI when the request is sent
+        if !request.headers().contains_key(header::HOST) {
+            if let Some(host) = host_header_value(request.uri()) {
+                signed_headers.push(("host".to_owned(), host));
+            }
+        }
+        signed_headers.extend(signed_header_values(request.headers()));
+
+        let authorization = self.authorization(
+            request.method(),
+            request.uri(),
+            &signed_headers,
+            payload_hash,
+            &amz_date,
+        );
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&authorization)?,
+        );
+        Ok(())
+    }
+
+    /// Returns the `Authorization` header value for a request.
+    fn authorization(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &[(String, String)],
+        payload_hash: &str,
+        amz_date: &str,
+    ) -> String {
+        let (canonical_request, signed_headers) =
+            canonical_request(method, uri, headers, payload_hash, self.service == "s3");
+        let date = &amz_date[..8];
+        let scope = format!("{date}/{}/{}/aws4_request", self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request))
+        );
+
+        let key = hmac_sha256(
+            format!("AWS4{}", self.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let key = hmac_sha256(&key, self.region.as_bytes());
+        let key = hmac_sha256(&key, self.service.as_bytes());
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        )
+    }
+}
+
+/// The `Host` header which will be sent for `uri`, which omits the port if it
+/// is the scheme's default.
+fn host_header_value(uri: &Uri) -> Option<String> {
+    let host = uri.host()?;
+    let default_port = match uri.scheme_str() {
+        Some("http") => Some(80),
+        Some("https") => Some(443),
+        _ => None,
+    };
+    Some(match uri.port_u16() {
+        Some(port) if Some(port) != default_port => format!("{host}:{port}"),
+        _ => host.to_owned(),
+    })
+}
+
+/// The headers of a request which SigV4 signs, as lower-case name and value.
+fn signed_header_values(headers: &HeaderMap) -> impl Iterator<Item = (String, String)> + '_ {
+    headers.iter().filter_map(|(name, value)| {
+        let name = name.as_str();
+        let signed =
+            matches!(name, "host" | "content-type" | "content-md5") || name.starts_with("x-amz-");
+        signed.then(|| {
+            (
+                name.to_owned(),
+                String::from_utf8_lossy(value.as_bytes()).into(),
+            )
+        })
+    })
+}
+
+/// Returns the SigV4 canonical request and the list of signed headers.
+///
+/// `headers` are lower-case name and value pairs. S3 paths are encoded once;
+/// other services' paths are encoded twice, i.e. the request's (already
+/// encoded) path is encoded again.
+fn canonical_request(
+    method: &Method,
+    uri: &Uri,
+    headers: &[(String, String)],
+    payload_hash: &str,
+    is_s3: bool,
+) -> (String, String) {
+    let path = match uri.path() {
+        "" => "/",
+        path => path,
+    };
+    let canonical_uri = path
+        .split('/')
+        .map(|segment| {
+            if is_s3 {
+                let decoded: Vec<u8> = percent_decode_str(segment).collect();
+                percent_encode(&decoded, SIGV4_UNRESERVED).to_string()
+            } else {
+                percent_encode(segment.as_bytes(), SIGV4_UNRESERVED).to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let mut query: Vec<(String, String)> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            let encode = |s: &str| {
+                let decoded: Vec<u8> = percent_decode_str(s).collect();
+                percent_encode(&decoded, SIGV4_UNRESERVED).to_string()
+            };
+            (encode(name), encode(value))
+        })
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    // Values of repeated headers are joined with commas
+    let mut canonical_headers = std::collections::BTreeMap::<&str, Vec<String>>::new();
+    for (name, value) in headers {
+        canonical_headers
+            .entry(name.as_str())
+            .or_default()
+            .push(value.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+    let signed_headers = canonical_headers
+        .keys()
+        .copied()
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = canonical_headers
+        .iter()
+        .map(|(name, values)| format!("{name}:{}\n", values.join(",")))
+        .collect();
+
+    let canonical_request = format!(
+        "{method}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}"
+    );
+    (canonical_request, signed_headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_PAYLOAD_HASH: &str =
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    /// The credentials used by the AWS SigV4 test suite.
+    fn test_suite_signer(service: &str) -> SigV4<'_> {
+        SigV4 {
+            access_key: "AKIDEXAMPLE",
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            session_token: None,
+            region: "us-east-1",
+            service,
+        }
+    }
+
+    fn headers(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn canonical_request_matches_test_suite() {
+        // get-vanilla-query-order-key-case
+        let (canonical_request, signed_headers) = canonical_request(
+            &Method::GET,
+            &Uri::from_static("https://example.amazonaws.com/?Param2=value2&Param1=value1"),
+            &headers(&[
+                ("host", "example.amazonaws.com"),
+                ("x-amz-date", "20150830T123600Z"),
+            ]),
+            EMPTY_PAYLOAD_HASH,
+            false,
+        );
+        assert_eq!(
+            canonical_request,
+            format!(
+                "GET\n/\nParam1=value1&Param2=value2\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\n\nhost;x-amz-date\n{EMPTY_PAYLOAD_HASH}"
+            )
+        );
+        assert_eq!(signed_headers, "host;x-amz-date");
+    }
+
+    #[test]
+    fn signatures_match_test_suite() {
+        let signer = test_suite_signer("service");
+        let vanilla = [
+            ("host", "example.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ];
+        for (name, method, uri, extra_headers, body, signature) in [
+            (
+                "get-vanilla",
+                Method::GET,
+                "/",
+                vec![],
+                "",
+                "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+            ),
+            (
+                "post-vanilla",
+                Method::POST,
+                "/",
+                vec![],
+                "",
+                "5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b",
+            ),
+            (
+                "get-vanilla-query-order-key-case",
+                Method::GET,
+                "/?Param2=value2&Param1=value1",
+                vec![],
+                "",
+                "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500",
+            ),
+            (
+                "get-header-value-trim",
+                Method::GET,
+                "/",
+                vec![("my-header1", " value1"), ("my-header2", " \"a   b   c\"")],
+                "",
+                "acc3ed3afb60bb290fc8d2dd0098b9911fcaa05412b367055dee359757a9c736",
+            ),
+            (
+                "post-x-www-form-urlencoded",
+                Method::POST,
+                "/",
+                vec![("content-type", "application/x-www-form-urlencoded")],
+                "Param1=value1",
+                "ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a",
+            ),
+        ] {
+            let mut all_headers = headers(&vanilla);
+            all_headers.extend(headers(&extra_headers));
+            let authorization = signer.authorization(
+                &method,
+                &Uri::try_from(format!("https://example.amazonaws.com{uri}")).unwrap(),
+                &all_headers,
+                &hex::encode(Sha256::digest(body)),
+                "20150830T123600Z",
+            );
+            let signed_headers = {
+                let mut names: Vec<_> = all_headers.iter().map(|(n, _)| n.as_str()).collect();
+                names.sort();
+                names.join(";")
+            };
+            assert_eq!(
+                authorization,
+                format!(
+                    "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders={signed_headers}, Signature={signature}"
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn signature_matches_documented_example() {
+        // The IAM ListUsers example from the SigV4 documentation
+        let authorization = test_suite_signer("iam").authorization(
+            &Method::GET,
+            &Uri::from_static("https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08"),
+            &headers(&[
+                ("host", "iam.amazonaws.com"),
+                (
+                    "content-type",
+                    "application/x-www-form-urlencoded; charset=utf-8",
+                ),
+                ("x-amz-date", "20150830T123600Z"),
+            ]),
+            EMPTY_PAYLOAD_HASH,
+            "20150830T123600Z",
+        );
+        assert!(authorization.ends_with(
+            "Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        ));
+    }
+
+    #[test]
+    fn paths_are_encoded_per_service() {
+        let uri = Uri::from_static("https://a.test/my%20file~.txt");
+        let path = |is_s3| {
+            let (canonical_request, _) =
+                canonical_request(&Method::GET, &uri, &[], UNSIGNED_PAYLOAD, is_s3);
+            canonical_request.lines().nth(1).unwrap().to_owned()
+        };
+        assert_eq!(path(true), "/my%20file~.txt");
+        assert_eq!(path(false), "/my%2520file~.txt");
+    }
+
+    #[test]
+    fn sign_adds_headers() {
+        let mut request = http::Request::put("https://bucket.s3.amazonaws.com:443/key")
+            .header("content-type", "text/plain")
+            .header("user-agent", "spin")
+            .body(())
+            .unwrap();
+        let signer = SigV4 {
+            session_token: Some("token"),
+            ..test_suite_signer("s3")
+        };
+        let now = DateTime::parse_from_rfc3339("2015-08-30T12:36:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        signer.sign(&mut request, UNSIGNED_PAYLOAD, now).unwrap();
+
+        let headers = request.headers();
+        assert_eq!(headers["x-amz-date"], "20150830T123600Z");
+        assert_eq!(headers["x-amz-content-sha256"], UNSIGNED_PAYLOAD);
+        assert_eq!(headers["x-amz-security-token"], "token");
+        assert!(!headers.contains_key(header::HOST));
+        assert_eq!(
+            host_header_value(request.uri()).unwrap(),
+            "bucket.s3.amazonaws.com"
+        );
+        assert_eq!(
+            host_header_value(&Uri::from_static("http://127.0.0.1:8080/")).unwrap(),
+            "127.0.0.1:8080"
+        );
+        let authorization = headers[header::AUTHORIZATION].to_str().unwrap();
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/s3/aws4_request, SignedHeaders=content-type;host;x-amz-content-sha256;x-amz-date;x-amz-security-token, "
+        ));
+    }
+}
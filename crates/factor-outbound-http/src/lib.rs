@@ -1,23 +1,26 @@
 pub mod intercept;
+mod redirect;
 pub mod runtime_config;
 mod spin;
 mod wasi;
 pub mod wasi_2023_10_18;
 pub mod wasi_2023_11_10;
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Context;
 use http::{
     uri::{Authority, Parts, PathAndQuery, Scheme},
     HeaderValue, Uri,
 };
-use intercept::OutboundHttpInterceptor;
-use runtime_config::RuntimeConfig;
+use intercept::{CircuitBreakerInterceptor, OutboundHttpInterceptor, SigningInterceptor};
+use runtime_config::{RedirectPolicy, RuntimeConfig, SigningRule};
+use spin_factor_audit::Auditor;
 use spin_factor_outbound_networking::{
     config::{allowed_hosts::OutboundAllowedHosts, blocked_networks::BlockedNetworks},
     ComponentTlsClientConfigs, OutboundNetworkingFactor,
 };
+use spin_factor_variables::VariablesFactor;
 use spin_factors::{
     anyhow, ConfigureAppContext, Factor, FactorData, PrepareContext, RuntimeFactors,
     SelfInstanceBuilder,
@@ -51,13 +54,31 @@ impl Factor for OutboundHttpFactor {
         &self,
         mut ctx: ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        let connection_pooling = ctx
-            .take_runtime_config()
-            .unwrap_or_default()
-            .connection_pooling;
+        let RuntimeConfig {
+            connection_pooling,
+            follow_redirects,
+            signing,
+            circuit_breaker,
+        } = ctx.take_runtime_config().unwrap_or_default();
+        // Each component gets its own breaker, so one component's failing
+        // upstream never affects requests made by another
+        let circuit_breakers = match circuit_breaker {
+            Some(config) => ctx
+                .app()
+                .components()
+                .map(|component| {
+                    let breaker = CircuitBreakerInterceptor::with_config(config.clone());
+                    (component.id().to_owned(), breaker)
+                })
+                .collect(),
+            None => HashMap::new(),
+        };
         Ok(AppState {
             wasi_http_clients: wasi::HttpClients::new(connection_pooling),
             connection_pooling,
+            follow_redirects,
+            signing_rules: signing,
+            circuit_breakers,
         })
     }
 
@@ -69,6 +90,25 @@ impl Factor for OutboundHttpFactor {
         let allowed_hosts = outbound_networking.allowed_hosts();
         let blocked_networks = outbound_networking.blocked_networks();
         let component_tls_configs = outbound_networking.component_tls_configs();
+        let signing_rules = &ctx.app_state().signing_rules;
+        let request_signer = if signing_rules.is_empty() {
+            None
+        } else {
+            let resolver = ctx
+                .instance_builder::<VariablesFactor>()?
+                .expression_resolver()
+                .clone();
+            Some(Arc::new(SigningInterceptor::new(
+                signing_rules.clone(),
+                resolver,
+            )))
+        };
+        let circuit_breaker = ctx
+            .app_state()
+            .circuit_breakers
+            .get(ctx.app_component().id())
+            .cloned();
+        let auditor = Auditor::for_instance(&mut ctx)?;
         Ok(InstanceState {
             wasi_http_ctx: WasiHttpCtx::new(),
             allowed_hosts,
@@ -76,9 +116,13 @@ impl Factor for OutboundHttpFactor {
             component_tls_configs,
             self_request_origin: None,
             request_interceptor: None,
+            request_signer,
             spin_http_client: None,
             wasi_http_clients: ctx.app_state().wasi_http_clients.clone(),
             connection_pooling: ctx.app_state().connection_pooling,
+            follow_redirects: ctx.app_state().follow_redirects,
+            circuit_breaker,
+            auditor,
         })
     }
 }
@@ -90,6 +134,9 @@ pub struct InstanceState {
     component_tls_configs: ComponentTlsClientConfigs,
     self_request_origin: Option<SelfRequestOrigin>,
     request_interceptor: Option<Arc<dyn OutboundHttpInterceptor>>,
+    // Signs requests according to the runtime config, after any request
+    // interceptor has run
+    request_signer: Option<Arc<SigningInterceptor>>,
     // Connection-pooling client for 'fermyon:spin/http' interface
     //
     // TODO: We could move this to `AppState` to like the
@@ -102,6 +149,13 @@ pub struct InstanceState {
     // among all instances of the app.
     wasi_http_clients: wasi::HttpClients,
     connection_pooling: bool,
+    // Redirect policy for `wasi:http/outgoing-handler` requests
+    follow_redirects: Option<RedirectPolicy>,
+    // Circuit breaker for this instance's component, shared among all
+    // instances of the app
+    circuit_breaker: Option<CircuitBreakerInterceptor>,
+    // Records sent requests in the audit log, if enabled
+    auditor: Auditor,
 }
 
 impl InstanceState {
@@ -130,6 +184,16 @@ impl InstanceState {
 
 impl SelfInstanceBuilder for InstanceState {}
 
+/// The target recorded in the audit log for a request to the given URI: its
+/// host, or `self` for a request to the app itself. Paths and queries are
+/// never recorded, as they may carry secrets.
+fn audit_target(uri: &Uri) -> String {
+    uri.host()
+        .filter(|host| !host.is_empty() && *host != "self.alt")
+        .unwrap_or("self")
+        .to_owned()
+}
+
 pub type Request = http::Request<wasmtime_wasi_http::body::HyperOutgoingBody>;
 pub type Response = http::Response<wasmtime_wasi_http::body::HyperIncomingBody>;
 
@@ -184,4 +248,8 @@ pub struct AppState {
     // Connection pooling clients for `wasi:http/outgoing-handler` interface
     wasi_http_clients: wasi::HttpClients,
     connection_pooling: bool,
+    follow_redirects: Option<RedirectPolicy>,
+    signing_rules: Vec<SigningRule>,
+    // Circuit breakers from runtime config, by component ID
+    circuit_breakers: HashMap<String, CircuitBreakerInterceptor>,
 }
@@ -0,0 +1,290 @@
+//! Helpers for host-side following of HTTP redirects.
+
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+};
+
+use bytes::Bytes;
+use http::{header, HeaderMap, Method, StatusCode, Uri};
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Body, Frame, SizeHint};
+use wasmtime_wasi_http::{bindings::http::types::ErrorCode, body::HyperOutgoingBody};
+
+/// The largest request body that will be retained for replay on redirect.
+const MAX_REPLAY_BODY_SIZE: usize = 64 * 1024;
+
+/// Headers which must not be forwarded to a different origin.
+const SENSITIVE_HEADERS: [header::HeaderName; 3] = [
+    header::AUTHORIZATION,
+    header::COOKIE,
+    header::PROXY_AUTHORIZATION,
+];
+
+/// Headers describing a request body, which are dropped along with the body.
+const BODY_HEADERS: [header::HeaderName; 4] = [
+    header::CONTENT_ENCODING,
+    header::CONTENT_LENGTH,
+    header::CONTENT_TYPE,
+    header::TRANSFER_ENCODING,
+];
+
+/// How a redirect should be followed.
+pub(crate) struct Redirect {
+    /// The resolved redirect target.
+    pub target: Uri,
+    /// The method to use for the redirected request.
+    pub method: Method,
+    /// Whether the original request body must be replayed.
+    pub replay_body: bool,
+}
+
+impl Redirect {
+    /// Returns how to follow the given response, or `None` if it isn't a
+    /// followable redirect.
+    pub fn from_response(
+        status: StatusCode,
+        headers: &HeaderMap,
+        method: &Method,
+        current: &Uri,
+    ) -> Option<Self> {
+        let (method, replay_body) = match status {
+            // 303 See Other always switches to GET (HEAD stays HEAD)
+            StatusCode::SEE_OTHER if method == Method::HEAD => (Method::HEAD, false),
+            StatusCode::SEE_OTHER => (Method::GET, false),
+            // For historical reasons, user agents switch POST to GET for 301/302
+            StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND if method == Method::POST => {
+                (Method::GET, false)
+            }
+            StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT => (method.clone(), true),
+            _ => return None,
+        };
+        let location = headers.get(header::LOCATION)?.to_str().ok()?;
+        let target = resolve_location(current, location)?;
+        Some(Self {
+            target,
+            method,
+            replay_body,
+        })
+    }
+}
+
+/// Resolves a `Location` header value relative to the URI that was requested.
+fn resolve_location(current: &Uri, location: &str) -> Option<Uri> {
+    let base = url::Url::parse(&current.to_string()).ok()?;
+    let target = base.join(location).ok()?;
+    if !matches!(target.scheme(), "http" | "https") {
+        return None;
+    }
+    target.as_str().parse().ok()
+}
+
+/// Returns whether two URIs share an origin (scheme, host, and port).
+pub(crate) fn is_same_origin(a: &Uri, b: &Uri) -> bool {
+    fn port(uri: &Uri) -> Option<u16> {
+        uri.port_u16().or(match uri.scheme_str() {
+            Some("http") => Some(80),
+            Some("https") => Some(443),
+            _ => None,
+        })
+    }
+    a.scheme() == b.scheme()
+        && a.host().map(str::to_ascii_lowercase) == b.host().map(str::to_ascii_lowercase)
+        && port(a) == port(b)
+}
+
+/// Removes headers which must not be sent to a different origin.
+pub(crate) fn strip_sensitive_headers(headers: &mut HeaderMap) {
+    for name in SENSITIVE_HEADERS {
+        headers.remove(name);
+    }
+}
+
+/// Removes headers describing a request body which is not being sent.
+pub(crate) fn strip_body_headers(headers: &mut HeaderMap) {
+    for name in BODY_HEADERS {
+        headers.remove(name);
+    }
+}
+
+/// A request body wrapper which records the body as it is sent so that it can
+/// be replayed for a redirected request.
+pub(crate) struct RecordingBody {
+    inner: HyperOutgoingBody,
+    recording: Arc<Mutex<Recording>>,
+}
+
+/// A handle to the body recorded by a [`RecordingBody`].
+#[derive(Clone)]
+pub(crate) struct BodyRecording(Arc<Mutex<Recording>>);
+
+struct Recording {
+    /// The recorded bytes, or `None` if the body can't be replayed.
+    bytes: Option<Vec<u8>>,
+    /// Whether the entire body has been sent.
+    complete: bool,
+}
+
+impl RecordingBody {
+    pub fn new(inner: HyperOutgoingBody) -> (Self, BodyRecording) {
+        let recording = Arc::new(Mutex::new(Recording {
+            bytes: Some(Vec::new()),
+            complete: false,
+        }));
+        let body = Self {
+            inner,
+            recording: recording.clone(),
+        };
+        (body, BodyRecording(recording))
+    }
+}
+
+impl BodyRecording {
+    /// Returns the recorded body if it was sent in full and is small enough
+    /// to have been retained.
+    pub fn bytes(&self) -> Option<Bytes> {
+        let recording = self.0.lock().unwrap();
+        if !recording.complete {
+            return None;
+        }
+        recording.bytes.clone().map(Bytes::from)
+    }
+}
+
+impl Body for RecordingBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let result = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        let mut recording = this.recording.lock().unwrap();
+        match &result {
+            Some(Ok(frame)) => {
+                let retained = match (frame.data_ref(), recording.bytes.as_mut()) {
+                    (Some(data), Some(bytes))
+                        if bytes.len() + data.len() <= MAX_REPLAY_BODY_SIZE =>
+                    {
+                        bytes.extend_from_slice(data);
+                        true
+                    }
+                    // Too large to retain, or trailers which we don't replay
+                    _ => false,
+                };
+                if !retained {
+                    recording.bytes = None;
+                }
+            }
+            Some(Err(_)) => recording.bytes = None,
+            None => recording.complete = true,
+        }
+        Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        let end = self.inner.is_end_stream();
+        if end {
+            self.recording.lock().unwrap().complete = true;
+        }
+        end
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Creates a request body from replayed bytes.
+pub(crate) fn replay_body(bytes: Bytes) -> HyperOutgoingBody {
+    Full::new(bytes).map_err(|err| match err {}).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(status: u16, method: Method, location: &str) -> Option<Redirect> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::LOCATION, location.parse().unwrap());
+        Redirect::from_response(
+            StatusCode::from_u16(status).unwrap(),
+            &headers,
+            &method,
+            &Uri::from_static("https://example.test/a/b?c=d"),
+        )
+    }
+
+    #[test]
+    fn resolves_locations() {
+        for (location, expected) in [
+            ("https://other.test/x", "https://other.test/x"),
+            ("//other.test/x", "https://other.test/x"),
+            ("/x", "https://example.test/x"),
+            ("x?y=z", "https://example.test/a/x?y=z"),
+        ] {
+            let redirect = redirect(302, Method::GET, location).unwrap();
+            assert_eq!(redirect.target, expected, "{location}");
+        }
+        assert!(redirect(302, Method::GET, "ftp://other.test/x").is_none());
+    }
+
+    #[test]
+    fn rewrites_methods() {
+        let r = redirect(303, Method::PUT, "/x").unwrap();
+        assert_eq!((r.method, r.replay_body), (Method::GET, false));
+        let r = redirect(303, Method::HEAD, "/x").unwrap();
+        assert_eq!((r.method, r.replay_body), (Method::HEAD, false));
+        let r = redirect(302, Method::POST, "/x").unwrap();
+        assert_eq!((r.method, r.replay_body), (Method::GET, false));
+        let r = redirect(307, Method::POST, "/x").unwrap();
+        assert_eq!((r.method, r.replay_body), (Method::POST, true));
+        let r = redirect(308, Method::PUT, "/x").unwrap();
+        assert_eq!((r.method, r.replay_body), (Method::PUT, true));
+        assert!(redirect(304, Method::GET, "/x").is_none());
+        assert!(redirect(200, Method::GET, "/x").is_none());
+    }
+
+    #[test]
+    fn compares_origins() {
+        let uri = |s: &'static str| Uri::from_static(s);
+        assert!(is_same_origin(
+            &uri("https://a.test/x"),
+            &uri("https://A.test:443/y")
+        ));
+        assert!(!is_same_origin(
+            &uri("https://a.test/"),
+            &uri("http://a.test/")
+        ));
+        assert!(!is_same_origin(
+            &uri("https://a.test/"),
+            &uri("https://b.test/")
+        ));
+        assert!(!is_same_origin(
+            &uri("http://a.test/"),
+            &uri("http://a.test:8080/")
+        ));
+    }
+
+    #[tokio::test]
+    async fn records_complete_body() {
+        let (body, recording) = RecordingBody::new(replay_body(Bytes::from_static(b"hello")));
+        assert!(recording.bytes().is_none());
+        body.collect().await.unwrap();
+        assert_eq!(recording.bytes().unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn does_not_record_large_body() {
+        let large = Bytes::from(vec![0; MAX_REPLAY_BODY_SIZE + 1]);
+        let (body, recording) = RecordingBody::new(replay_body(large));
+        body.collect().await.unwrap();
+        assert!(recording.bytes().is_none());
+    }
+}
@@ -1,17 +1,172 @@
 #[cfg(feature = "spin-cli")]
 pub mod spin;
 
+use std::time::Duration;
+
+use http::StatusCode;
+use serde::Deserialize;
+
 /// Runtime configuration for outbound HTTP.
 #[derive(Debug)]
 pub struct RuntimeConfig {
     /// If true, enable connection pooling and reuse.
     pub connection_pooling: bool,
+    /// If set, redirects are followed by the host according to this policy
+    /// rather than being returned to the guest.
+    pub follow_redirects: Option<RedirectPolicy>,
+    /// Requests to hosts matching one of these rules are signed by the host.
+    /// The first matching rule is used.
+    pub signing: Vec<SigningRule>,
+    /// If set, requests to failing upstream hosts are failed fast for a while
+    /// rather than sent.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
 }
 
 impl Default for RuntimeConfig {
     fn default() -> Self {
         Self {
             connection_pooling: true,
+            follow_redirects: None,
+            signing: Vec::new(),
+            circuit_breaker: None,
+        }
+    }
+}
+
+/// Policy for host-side following of HTTP redirects.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RedirectPolicy {
+    /// The maximum number of redirects to follow for a single request.
+    #[serde(default = "RedirectPolicy::default_max")]
+    pub max: u32,
+    /// If true, redirects to a different origin (scheme, host, and port) are
+    /// followed. Sensitive headers are stripped from such requests.
+    #[serde(default)]
+    pub allow_cross_host: bool,
+}
+
+impl RedirectPolicy {
+    fn default_max() -> u32 {
+        5
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max: Self::default_max(),
+            allow_cross_host: false,
+        }
+    }
+}
+
+/// Configuration of the circuit breaker for outbound requests.
+///
+/// Circuits are tracked per component and upstream authority. Once
+/// `failure_threshold` consecutive failures happen within `window`, the
+/// circuit opens and requests fail fast for `open_duration`. Then up to
+/// `half_open_max` probe requests are sent: the circuit closes if one succeeds
+/// and opens again if one fails. Self requests and service chaining requests
+/// are never affected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// The number of consecutive failures which opens a circuit.
+    pub failure_threshold: u32,
+    /// The period over which failures are counted.
+    pub window: Duration,
+    /// How long a circuit stays open before probe requests are allowed.
+    pub open_duration: Duration,
+    /// The maximum number of concurrent probe requests to a half-open circuit.
+    pub half_open_max: u32,
+    /// The response statuses counted as failures, or `None` to count every
+    /// `5xx` status. Requests which fail without a response always count.
+    pub failure_statuses: Option<Vec<StatusCode>>,
+}
+
+impl CircuitBreakerConfig {
+    /// Returns true if a response with the given status counts as a failure.
+    pub fn is_failure_status(&self, status: StatusCode) -> bool {
+        match &self.failure_statuses {
+            Some(statuses) => statuses.contains(&status),
+            None => status.is_server_error(),
+        }
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            window: Duration::from_secs(30),
+            open_duration: Duration::from_secs(20),
+            half_open_max: 2,
+            failure_statuses: None,
+        }
+    }
+}
+
+/// A rule for signing outbound requests to matching hosts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SigningRule {
+    /// The host to sign requests for, e.g. `api.example.com`. A leading `*.`
+    /// matches any subdomain, and `*` matches every host.
+    pub host: String,
+    /// How to sign requests.
+    pub scheme: SigningScheme,
+}
+
+impl SigningRule {
+    /// Returns true if requests to `host` should be signed by this rule.
+    pub fn matches(&self, host: &str) -> bool {
+        let pattern = self.host.as_str();
+        if pattern == "*" {
+            return true;
+        }
+        match pattern.strip_prefix("*.") {
+            Some(domain) => host.len().checked_sub(domain.len() + 1).is_some_and(|dot| {
+                host.as_bytes()[dot] == b'.' && host[dot + 1..].eq_ignore_ascii_case(domain)
+            }),
+            None => host.eq_ignore_ascii_case(pattern),
         }
     }
 }
+
+/// A scheme for signing outbound requests.
+///
+/// Secrets are named by application variable so that they are resolved on
+/// the host and never appear in the manifest or the guest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SigningScheme {
+    /// AWS Signature Version 4.
+    SigV4 {
+        /// The AWS region, e.g. `us-east-1`.
+        region: String,
+        /// The AWS service, e.g. `s3`.
+        service: String,
+        /// The variable holding the access key ID.
+        access_key_variable: String,
+        /// The variable holding the secret access key.
+        secret_key_variable: String,
+        /// The variable holding a session token, for temporary credentials.
+        session_token_variable: Option<String>,
+    },
+    /// An HMAC-SHA256 of the request body, sent in a header.
+    HmacHeader {
+        /// The header to send the signature in.
+        header: http::HeaderName,
+        /// How the signature is encoded in the header.
+        encoding: HmacEncoding,
+        /// The variable holding the HMAC key.
+        key_variable: String,
+    },
+}
+
+/// The encoding of an HMAC signature header.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HmacEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
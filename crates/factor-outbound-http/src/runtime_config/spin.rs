@@ -1,22 +1,63 @@
+use std::time::Duration;
+
+use anyhow::Context as _;
+use http::StatusCode;
 use serde::Deserialize;
 use spin_factors::runtime_config::toml::GetTomlValue;
 
+use super::{CircuitBreakerConfig, HmacEncoding, RedirectPolicy, SigningRule, SigningScheme};
+
 /// Get the runtime configuration for outbound HTTP from a TOML table.
 ///
 /// Expects table to be in the format:
 /// ```toml
 /// [outbound_http]
 /// connection_pooling = true
+/// follow_redirects = { max = 5, allow_cross_host = false }
+///
+/// [outbound_http.circuit_breaker]
+/// enabled = true
+/// failure_threshold = 5
+/// window_secs = 30
+/// open_secs = 20
+/// half_open_max = 2
+/// failure_statuses = [502, 503, 504]
+///
+/// [[outbound_http.sign]]
+/// host = "*.amazonaws.com"
+/// scheme = "sigv4"
+/// region = "us-east-1"
+/// service = "s3"
+/// credentials = { access_key_variable = "aws_key", secret_key_variable = "aws_secret" }
+///
+/// [[outbound_http.sign]]
+/// host = "hooks.example.com"
+/// scheme = "hmac-header"
+/// header = "x-signature"
+/// encoding = "base64"
+/// credentials = { key_variable = "hook_secret" }
 /// ```
 pub fn config_from_table(
     table: &impl GetTomlValue,
 ) -> anyhow::Result<Option<super::RuntimeConfig>> {
     if let Some(outbound_http) = table.get("outbound_http") {
+        let outbound_http = outbound_http.clone().try_into::<OutboundHttpToml>()?;
+        let signing = outbound_http
+            .sign
+            .into_iter()
+            .map(SigningRule::try_from)
+            .collect::<anyhow::Result<_>>()?;
+        let circuit_breaker = outbound_http
+            .circuit_breaker
+            .filter(|circuit_breaker| circuit_breaker.enabled)
+            .map(CircuitBreakerConfig::try_from)
+            .transpose()
+            .context("invalid `outbound_http.circuit_breaker` config")?;
         Ok(Some(super::RuntimeConfig {
-            connection_pooling: outbound_http
-                .clone()
-                .try_into::<OutboundHttpToml>()?
-                .connection_pooling,
+            connection_pooling: outbound_http.connection_pooling,
+            follow_redirects: outbound_http.follow_redirects,
+            signing,
+            circuit_breaker,
         }))
     } else {
         Ok(None)
@@ -28,4 +69,271 @@ pub fn config_from_table(
 struct OutboundHttpToml {
     #[serde(default)]
     connection_pooling: bool,
+    #[serde(default)]
+    follow_redirects: Option<RedirectPolicy>,
+    #[serde(default)]
+    sign: Vec<SigningRuleToml>,
+    #[serde(default)]
+    circuit_breaker: Option<CircuitBreakerToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CircuitBreakerToml {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    failure_threshold: Option<u32>,
+    #[serde(default)]
+    window_secs: Option<u64>,
+    #[serde(default)]
+    open_secs: Option<u64>,
+    #[serde(default)]
+    half_open_max: Option<u32>,
+    #[serde(default)]
+    failure_statuses: Option<Vec<u16>>,
+}
+
+impl TryFrom<CircuitBreakerToml> for CircuitBreakerConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(toml: CircuitBreakerToml) -> anyhow::Result<Self> {
+        let default = CircuitBreakerConfig::default();
+        let failure_threshold = toml.failure_threshold.unwrap_or(default.failure_threshold);
+        anyhow::ensure!(
+            failure_threshold > 0,
+            "`failure_threshold` must be at least 1"
+        );
+        let half_open_max = toml.half_open_max.unwrap_or(default.half_open_max);
+        anyhow::ensure!(half_open_max > 0, "`half_open_max` must be at least 1");
+        let failure_statuses = toml
+            .failure_statuses
+            .map(|statuses| {
+                statuses
+                    .into_iter()
+                    .map(|status| {
+                        StatusCode::from_u16(status)
+                            .with_context(|| format!("invalid failure status {status}"))
+                    })
+                    .collect::<anyhow::Result<_>>()
+            })
+            .transpose()?;
+        Ok(Self {
+            failure_threshold,
+            window: toml.window_secs.map_or(default.window, Duration::from_secs),
+            open_duration: toml
+                .open_secs
+                .map_or(default.open_duration, Duration::from_secs),
+            half_open_max,
+            failure_statuses,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "scheme", rename_all = "kebab-case", deny_unknown_fields)]
+enum SigningRuleToml {
+    Sigv4 {
+        host: String,
+        region: String,
+        service: String,
+        credentials: SigV4CredentialsToml,
+    },
+    HmacHeader {
+        host: String,
+        header: String,
+        #[serde(default)]
+        encoding: HmacEncoding,
+        credentials: HmacCredentialsToml,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SigV4CredentialsToml {
+    access_key_variable: String,
+    secret_key_variable: String,
+    #[serde(default)]
+    session_token_variable: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HmacCredentialsToml {
+    key_variable: String,
+}
+
+impl TryFrom<SigningRuleToml> for SigningRule {
+    type Error = anyhow::Error;
+
+    fn try_from(rule: SigningRuleToml) -> anyhow::Result<Self> {
+        let (host, scheme) = match rule {
+            SigningRuleToml::Sigv4 {
+                host,
+                region,
+                service,
+                credentials,
+            } => (
+                host,
+                SigningScheme::SigV4 {
+                    region,
+                    service,
+                    access_key_variable: variable_name(credentials.access_key_variable)?,
+                    secret_key_variable: variable_name(credentials.secret_key_variable)?,
+                    session_token_variable: credentials
+                        .session_token_variable
+                        .map(variable_name)
+                        .transpose()?,
+                },
+            ),
+            SigningRuleToml::HmacHeader {
+                host,
+                header,
+                encoding,
+                credentials,
+            } => (
+                host,
+                SigningScheme::HmacHeader {
+                    header: header
+                        .parse()
+                        .with_context(|| format!("invalid signature header name {header:?}"))?,
+                    encoding,
+                    key_variable: variable_name(credentials.key_variable)?,
+                },
+            ),
+        };
+        let valid_host = match host.strip_prefix("*.") {
+            Some(domain) => !domain.is_empty() && !domain.contains('*'),
+            None => !host.is_empty() && (host == "*" || !host.contains('*')),
+        };
+        anyhow::ensure!(valid_host, "invalid signing host pattern {host:?}");
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            scheme,
+        })
+    }
+}
+
+fn variable_name(name: String) -> anyhow::Result<String> {
+    spin_expressions::Key::new(&name)
+        .with_context(|| format!("invalid signing credentials variable name {name:?}"))?;
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> anyhow::Result<Option<super::super::RuntimeConfig>> {
+        config_from_table(&toml::from_str::<toml::Table>(toml)?)
+    }
+
+    #[test]
+    fn signing_rules_are_parsed() -> anyhow::Result<()> {
+        let config = config(
+            r#"
+            [[outbound_http.sign]]
+            host = "*.amazonaws.com"
+            scheme = "sigv4"
+            region = "us-east-1"
+            service = "s3"
+            credentials = { access_key_variable = "aws_key", secret_key_variable = "aws_secret" }
+
+            [[outbound_http.sign]]
+            host = "Hooks.Example.com"
+            scheme = "hmac-header"
+            header = "X-Signature"
+            credentials = { key_variable = "hook_secret" }
+            "#,
+        )?
+        .unwrap();
+        assert_eq!(
+            config.signing,
+            [
+                SigningRule {
+                    host: "*.amazonaws.com".into(),
+                    scheme: SigningScheme::SigV4 {
+                        region: "us-east-1".into(),
+                        service: "s3".into(),
+                        access_key_variable: "aws_key".into(),
+                        secret_key_variable: "aws_secret".into(),
+                        session_token_variable: None,
+                    },
+                },
+                SigningRule {
+                    host: "hooks.example.com".into(),
+                    scheme: SigningScheme::HmacHeader {
+                        header: http::HeaderName::from_static("x-signature"),
+                        encoding: HmacEncoding::Hex,
+                        key_variable: "hook_secret".into(),
+                    },
+                },
+            ]
+        );
+        assert!(config.signing[0].matches("s3.us-east-1.amazonaws.com"));
+        assert!(!config.signing[0].matches("amazonaws.com"));
+        assert!(!config.signing[0].matches("evilamazonaws.com"));
+        assert!(config.signing[1].matches("HOOKS.example.com"));
+        Ok(())
+    }
+
+    #[test]
+    fn circuit_breaker_is_parsed() -> anyhow::Result<()> {
+        let enabled = config(
+            r#"
+            [outbound_http.circuit_breaker]
+            enabled = true
+            failure_threshold = 3
+            open_secs = 5
+            failure_statuses = [502, 503]
+            "#,
+        )?
+        .unwrap();
+        assert_eq!(
+            enabled.circuit_breaker,
+            Some(CircuitBreakerConfig {
+                failure_threshold: 3,
+                window: Duration::from_secs(30),
+                open_duration: Duration::from_secs(5),
+                half_open_max: 2,
+                failure_statuses: Some(vec![
+                    StatusCode::BAD_GATEWAY,
+                    StatusCode::SERVICE_UNAVAILABLE
+                ]),
+            })
+        );
+
+        let disabled = config("[outbound_http.circuit_breaker]\nfailure_threshold = 3")?.unwrap();
+        assert_eq!(disabled.circuit_breaker, None);
+
+        for invalid in [
+            "failure_threshold = 0",
+            "half_open_max = 0",
+            "failure_statuses = [1000]",
+            "open_ms = 5",
+        ] {
+            let toml = format!("[outbound_http.circuit_breaker]\nenabled = true\n{invalid}");
+            assert!(config(&toml).is_err(), "{invalid}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_signing_rules_are_rejected() {
+        for invalid in [
+            // Unknown scheme
+            "host = 'a.test'\nscheme = 'sigv2'",
+            // Invalid variable name
+            "host = 'a.test'\nscheme = 'hmac-header'\nheader = 'x-sig'\ncredentials = { key_variable = 'Hook-Secret' }",
+            // Invalid header name
+            "host = 'a.test'\nscheme = 'hmac-header'\nheader = 'x sig'\ncredentials = { key_variable = 'hook_secret' }",
+            // Invalid host pattern
+            "host = 'a.*.test'\nscheme = 'hmac-header'\nheader = 'x-sig'\ncredentials = { key_variable = 'hook_secret' }",
+            // Secrets must not be inline
+            "host = 'a.test'\nscheme = 'hmac-header'\nheader = 'x-sig'\ncredentials = { key = 'hunter2' }",
+        ] {
+            let toml = format!("[[outbound_http.sign]]\n{invalid}");
+            assert!(config(&toml).is_err(), "{invalid}");
+        }
+    }
 }
@@ -1,17 +1,39 @@
-use http_body_util::BodyExt;
+use http_body_util::{BodyExt, Full};
+use spin_factor_audit::operation;
 use spin_world::v1::{
     http as spin_http,
     http_types::{self, HttpError, Method, Request, Response},
 };
 use tracing::{field::Empty, instrument, Span};
 
-use crate::intercept::InterceptOutcome;
+use crate::{
+    audit_target,
+    intercept::{self, InterceptOutcome, InterceptRequest, RequestOutcome},
+};
 
 impl spin_http::Host for crate::InstanceState {
     #[instrument(name = "spin_outbound_http.send_request", skip_all,
         fields(otel.kind = "client", url.full = Empty, http.request.method = Empty,
         http.response.status_code = Empty, otel.name = Empty, server.address = Empty, server.port = Empty))]
     async fn send_request(&mut self, req: Request) -> Result<Response, HttpError> {
+        let audit_target = self
+            .auditor
+            .includes(operation::OUTBOUND_HTTP_REQUEST)
+            .then(|| match req.uri.parse::<http::Uri>() {
+                Ok(uri) => audit_target(&uri),
+                Err(_) => "<invalid>".to_owned(),
+            });
+        let result = self.send_request_unaudited(req).await;
+        if let Some(target) = audit_target {
+            self.auditor
+                .record(operation::OUTBOUND_HTTP_REQUEST, &target, &result);
+        }
+        result
+    }
+}
+
+impl crate::InstanceState {
+    async fn send_request_unaudited(&mut self, req: Request) -> Result<Response, HttpError> {
         let span = Span::current();
         record_request_fields(&span, &req);
 
@@ -85,6 +107,21 @@ impl spin_http::Host for crate::InstanceState {
             }
         }
 
+        // Sign the request as it will be sent
+        if let Some(signer) = &self.request_signer {
+            let mut signed_request: InterceptRequest = std::mem::take(&mut req).into();
+            if let Err(err) = signer.sign(&mut signed_request).await {
+                tracing::error!("Error signing outbound HTTP request: {err}");
+                return Err(HttpError::RuntimeError);
+            }
+            req = signed_request.into_vec_request().unwrap();
+        }
+
+        let envelope = self
+            .request_interceptor
+            .is_some()
+            .then(|| intercept::take_envelope(&mut req));
+
         // Convert http::Request to reqwest::Request
         let req = reqwest::Request::try_from(req).map_err(|_| HttpError::InvalidUrl)?;
 
@@ -98,11 +135,29 @@ impl spin_http::Host for crate::InstanceState {
             builder.build().unwrap()
         });
 
-        let resp = client.execute(req).await.map_err(log_reqwest_error)?;
+        let resp = client.execute(req).await;
+        if let Some(interceptor) = &self.request_interceptor {
+            let outcome = match &resp {
+                Ok(resp) => RequestOutcome::Response(resp.status()),
+                Err(_) => RequestOutcome::Failed,
+            };
+            interceptor.observe(&req_url, outcome);
+        }
+        let resp = resp.map_err(log_reqwest_error)?;
 
         tracing::trace!("Returning response from outbound request to {req_url}");
         span.record("http.response.status_code", resp.status().as_u16());
-        response_from_reqwest(resp).await
+        let (Some(interceptor), Some(envelope)) = (&self.request_interceptor, envelope) else {
+            return response_from_reqwest(resp).await;
+        };
+        let resp = hyper_response_from_reqwest(resp).await?;
+        match interceptor.intercept_response(&envelope, resp).await {
+            Ok(resp) => response_from_hyper(resp).await,
+            Err(err) => {
+                tracing::error!("Error in outbound HTTP interceptor: {err}");
+                Err(HttpError::RuntimeError)
+            }
+        }
     }
 }
 
@@ -210,6 +265,17 @@ async fn response_from_reqwest(res: reqwest::Response) -> Result<Response, HttpE
     })
 }
 
+async fn hyper_response_from_reqwest(res: reqwest::Response) -> Result<crate::Response, HttpError> {
+    let mut builder = http::Response::builder().status(res.status());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = res.headers().clone();
+    }
+    let body = res.bytes().await.map_err(|_| HttpError::RuntimeError)?;
+    builder
+        .body(Full::new(body).map_err(|err| match err {}).boxed())
+        .map_err(|_| HttpError::RuntimeError)
+}
+
 fn headers_from_map(map: &http::HeaderMap) -> Vec<(String, String)> {
     map.iter()
         .filter_map(|(key, val)| {
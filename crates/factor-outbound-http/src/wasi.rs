@@ -8,7 +8,8 @@ use std::{
     time::Duration,
 };
 
-use http::{header::HOST, Uri};
+use bytes::Bytes;
+use http::{header::HOST, uri::Scheme, Uri};
 use http_body_util::BodyExt;
 use hyper_util::{
     client::legacy::{
@@ -17,8 +18,14 @@ use hyper_util::{
     },
     rt::{TokioExecutor, TokioIo},
 };
+use spin_factor_audit::operation;
 use spin_factor_outbound_networking::{
-    config::{allowed_hosts::OutboundAllowedHosts, blocked_networks::BlockedNetworks},
+    config::{
+        allowed_hosts::{
+            is_service_chaining_host, parse_cross_app_chaining_target, OutboundAllowedHosts,
+        },
+        blocked_networks::BlockedNetworks,
+    },
     ComponentTlsClientConfigs, TlsClientConfig,
 };
 use spin_factors::{wasmtime::component::ResourceTable, RuntimeFactorsInstanceState};
@@ -39,7 +46,16 @@ use wasmtime_wasi_http::{
 };
 
 use crate::{
-    intercept::{InterceptOutcome, OutboundHttpInterceptor},
+    audit_target,
+    intercept::{
+        self, CircuitBreakerInterceptor, InterceptOutcome, InterceptRequest,
+        OutboundHttpInterceptor, RequestOutcome, SigningInterceptor,
+    },
+    redirect::{
+        is_same_origin, replay_body, strip_body_headers, strip_sensitive_headers, RecordingBody,
+        Redirect,
+    },
+    runtime_config::RedirectPolicy,
     wasi_2023_10_18, wasi_2023_11_10, InstanceState, OutboundHttpFactor, SelfRequestOrigin,
 };
 
@@ -113,6 +129,9 @@ impl WasiHttpView for WasiHttpImplInner<'_> {
             http.response.status_code = Empty,
             server.address = Empty,
             server.port = Empty,
+            spin.redirect_chain = Empty,
+            spin.h2_reset_reason = Empty,
+            circuit = Empty,
         ),
     )]
     fn send_request(
@@ -124,20 +143,38 @@ impl WasiHttpView for WasiHttpImplInner<'_> {
             allowed_hosts: self.state.allowed_hosts.clone(),
             component_tls_configs: self.state.component_tls_configs.clone(),
             request_interceptor: self.state.request_interceptor.clone(),
+            request_signer: self.state.request_signer.clone(),
             self_request_origin: self.state.self_request_origin.clone(),
             blocked_networks: self.state.blocked_networks.clone(),
             http_clients: self.state.wasi_http_clients.clone(),
+            follow_redirects: self.state.follow_redirects,
+            circuit_breaker: self.state.circuit_breaker.clone(),
         };
+        let audit = self
+            .state
+            .auditor
+            .includes(operation::OUTBOUND_HTTP_REQUEST)
+            .then(|| (self.state.auditor.clone(), audit_target(request.uri())));
+        // Dropping the pending response aborts this task, which drops the
+        // in-flight request; hyper then closes its connection rather than
+        // returning it to the pool.
         Ok(HostFutureIncomingResponse::Pending(
             wasmtime_wasi::runtime::spawn(
-                async {
-                    match request_sender.send(request, config).await {
+                async move {
+                    let cancelled = CancellationCounter::arm();
+                    let result = request_sender.send(request, config).await;
+                    if let Some((auditor, target)) = audit {
+                        auditor.record(operation::OUTBOUND_HTTP_REQUEST, &target, &result);
+                    }
+                    let result = match result {
                         Ok(resp) => Ok(Ok(resp)),
                         Err(http_error) => match http_error.downcast() {
                             Ok(error_code) => Ok(Err(error_code)),
                             Err(trap) => Err(trap),
                         },
-                    }
+                    };
+                    cancelled.disarm();
+                    result
                 }
                 .in_current_span(),
             ),
@@ -145,22 +182,175 @@ impl WasiHttpView for WasiHttpImplInner<'_> {
     }
 }
 
+/// Counts an outbound request as cancelled if dropped before being disarmed,
+/// i.e. if the guest drops the pending response before it completes.
+struct CancellationCounter {
+    armed: bool,
+}
+
+impl CancellationCounter {
+    fn arm() -> Self {
+        Self { armed: true }
+    }
+
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for CancellationCounter {
+    fn drop(&mut self) {
+        if self.armed {
+            tracing::debug!("Outbound HTTP request cancelled before completion");
+            spin_telemetry::monotonic_counter!(spin.outbound_http_cancelled = 1);
+        }
+    }
+}
+
 struct RequestSender {
     allowed_hosts: OutboundAllowedHosts,
     blocked_networks: BlockedNetworks,
     component_tls_configs: ComponentTlsClientConfigs,
     self_request_origin: Option<SelfRequestOrigin>,
     request_interceptor: Option<Arc<dyn OutboundHttpInterceptor>>,
+    request_signer: Option<Arc<SigningInterceptor>>,
     http_clients: HttpClients,
+    follow_redirects: Option<RedirectPolicy>,
+    circuit_breaker: Option<CircuitBreakerInterceptor>,
 }
 
 impl RequestSender {
     async fn send(
         self,
+        request: OutgoingRequest,
+        config: OutgoingRequestConfig,
+    ) -> Result<IncomingResponse, HttpError> {
+        match self.follow_redirects {
+            Some(policy) => self.send_following_redirects(request, config, policy).await,
+            None => Ok(self.send_once(request, config).await?.0),
+        }
+    }
+
+    /// Sends a request, following redirects according to the given policy.
+    ///
+    /// Every redirected request goes through the same allowed hosts,
+    /// interceptor, and blocked networks checks as the original request.
+    async fn send_following_redirects(
+        &self,
+        request: OutgoingRequest,
+        config: OutgoingRequestConfig,
+        policy: RedirectPolicy,
+    ) -> Result<IncomingResponse, HttpError> {
+        let OutgoingRequestConfig {
+            use_tls,
+            connect_timeout,
+            first_byte_timeout,
+            between_bytes_timeout,
+        } = config;
+        let hop_config = |use_tls| OutgoingRequestConfig {
+            use_tls,
+            connect_timeout,
+            first_byte_timeout,
+            between_bytes_timeout,
+        };
+
+        // Keep what we need to build redirected requests
+        let (parts, body) = request.into_parts();
+        let mut method = parts.method.clone();
+        let mut headers = parts.headers.clone();
+        let version = parts.version;
+        let (body, recording) = RecordingBody::new(body);
+        let mut replay_bytes: Option<Bytes> = None;
+
+        let mut request = OutgoingRequest::from_parts(parts, body.boxed());
+        let mut config = hop_config(use_tls);
+        let mut redirect_chain = Vec::new();
+
+        let resp = loop {
+            let (resp, uri) = self.send_once(request, config).await?;
+            let Some(redirect) =
+                Redirect::from_response(resp.resp.status(), resp.resp.headers(), &method, &uri)
+            else {
+                if !redirect_chain.is_empty() {
+                    redirect_chain.push(uri.to_string());
+                }
+                break resp;
+            };
+            redirect_chain.push(uri.to_string());
+
+            if redirect_chain.len() > policy.max as usize {
+                tracing::warn!(
+                    "Outbound HTTP request exceeded the limit of {} redirects",
+                    policy.max
+                );
+                return Err(ErrorCode::LoopDetected.into());
+            }
+
+            let mut target = redirect.target;
+            if !is_same_origin(&uri, &target) {
+                if !policy.allow_cross_host {
+                    tracing::warn!("Denied outbound HTTP redirect from {uri} to {target}");
+                    return Err(ErrorCode::HttpRequestDenied.into());
+                }
+                strip_sensitive_headers(&mut headers);
+            }
+
+            let body = if redirect.replay_body {
+                match replay_bytes.clone().or_else(|| recording.bytes()) {
+                    Some(bytes) => {
+                        replay_bytes = Some(bytes.clone());
+                        replay_body(bytes)
+                    }
+                    None => {
+                        tracing::debug!(
+                            "Request body can't be replayed; not following redirect to {target}"
+                        );
+                        break resp;
+                    }
+                }
+            } else {
+                strip_body_headers(&mut headers);
+                replay_bytes = Some(Bytes::new());
+                replay_body(Bytes::new())
+            };
+
+            let use_tls = target.scheme() == Some(&Scheme::HTTPS);
+            // Redirects back to our own origin are treated as self requests
+            if let Some(origin) = &self.self_request_origin {
+                if target.scheme() == Some(&origin.scheme)
+                    && target.authority() == Some(&origin.authority)
+                {
+                    target = target
+                        .path_and_query()
+                        .map(|paq| Uri::from(paq.clone()))
+                        .unwrap_or_else(|| Uri::from_static("/"));
+                }
+            }
+
+            method = redirect.method;
+            let mut next = OutgoingRequest::new(body);
+            *next.method_mut() = method.clone();
+            *next.uri_mut() = target;
+            *next.version_mut() = version;
+            *next.headers_mut() = headers.clone();
+            request = next;
+            config = hop_config(use_tls);
+        };
+
+        if !redirect_chain.is_empty() {
+            tracing::Span::current().record("spin.redirect_chain", redirect_chain.join(" -> "));
+        }
+        Ok(resp)
+    }
+
+    /// Sends a single request, returning the response along with the URI the
+    /// request was made to.
+    async fn send_once(
+        &self,
         mut request: OutgoingRequest,
         mut config: OutgoingRequestConfig,
-    ) -> Result<IncomingResponse, HttpError> {
-        self.prepare_request(&mut request, &mut config).await?;
+    ) -> Result<(IncomingResponse, Uri), HttpError> {
+        let is_self_request = self.prepare_request(&mut request, &mut config).await?;
 
         // If the current span has opentelemetry trace context, inject it into the request
         spin_telemetry::inject_trace_context(&mut request);
@@ -168,6 +358,7 @@ impl RequestSender {
         // Run any configured request interceptor
         let mut override_connect_host = None;
         if let Some(interceptor) = &self.request_interceptor {
+            let uri = request.uri().clone();
             let intercept_request = std::mem::take(&mut request).into();
             match interceptor.intercept(intercept_request).await? {
                 InterceptOutcome::Continue(mut req) => {
@@ -180,11 +371,18 @@ impl RequestSender {
                         worker: None,
                         between_bytes_timeout: config.between_bytes_timeout,
                     };
-                    return Ok(resp);
+                    return Ok((resp, uri));
                 }
             }
         }
 
+        // Sign the request as it will be sent
+        if let Some(signer) = &self.request_signer {
+            let mut signed_request: InterceptRequest = std::mem::take(&mut request).into();
+            signer.sign(&mut signed_request).await?;
+            request = signed_request.into_hyper_request();
+        }
+
         // Backfill span fields after potentially updating the URL in the interceptor
         if let Some(authority) = request.uri().authority() {
             let span = tracing::Span::current();
@@ -195,16 +393,58 @@ impl RequestSender {
             }
         }
 
-        Ok(self
+        // Fail fast if the circuit to the upstream is open; self requests and
+        // service chaining aren't subject to circuit breaking
+        let uri = request.uri().clone();
+        let circuit_permit = match (&self.circuit_breaker, uri.authority()) {
+            (Some(breaker), Some(authority))
+                if !is_self_request
+                    && !is_service_chaining_host(authority.host())
+                    && parse_cross_app_chaining_target(&uri).is_none() =>
+            {
+                let Some(permit) = breaker.acquire(authority.as_str()) else {
+                    tracing::Span::current().record("circuit", "open");
+                    tracing::debug!(
+                        "Outbound HTTP request to {authority} failed fast: circuit open"
+                    );
+                    return Err(ErrorCode::ConnectionRefused.into());
+                };
+                Some(permit)
+            }
+            _ => None,
+        };
+
+        let envelope = self
+            .request_interceptor
+            .is_some()
+            .then(|| intercept::take_envelope(&mut request));
+        let result = self
             .send_request(request, config, override_connect_host)
-            .await?)
+            .await;
+        if let Some(permit) = circuit_permit {
+            permit.record(result.as_ref().ok().map(|resp| resp.resp.status()));
+        }
+        let (Some(interceptor), Some(envelope)) = (&self.request_interceptor, envelope) else {
+            return Ok((result?, uri));
+        };
+        let outcome = match &result {
+            Ok(resp) => RequestOutcome::Response(resp.resp.status()),
+            Err(_) => RequestOutcome::Failed,
+        };
+        interceptor.observe(&uri, outcome);
+        let mut resp = result?;
+        resp.resp = interceptor.intercept_response(&envelope, resp.resp).await?;
+        Ok((resp, uri))
     }
 
+    /// Checks the request against the allowed hosts and resolves self
+    /// requests against the self request origin, returning whether the
+    /// request is a self request.
     async fn prepare_request(
         &self,
         request: &mut OutgoingRequest,
         config: &mut OutgoingRequestConfig,
-    ) -> Result<(), ErrorCode> {
+    ) -> Result<bool, ErrorCode> {
         // wasmtime-wasi-http fills in scheme and authority for relative URLs
         // (e.g. https://:443/<path>), which makes them hard to reason about.
         // Undo that here.
@@ -268,11 +508,11 @@ impl RequestSender {
         // decide to add the `host` header back in, regardless of the nginx bug, in
         // which case we'll let it do so without interferring.
         request.headers_mut().remove(HOST);
-        Ok(())
+        Ok(is_self_request)
     }
 
     async fn send_request(
-        self,
+        &self,
         request: OutgoingRequest,
         config: OutgoingRequestConfig,
         override_connect_host: Option<String>,
@@ -293,7 +533,7 @@ impl RequestSender {
 
         let resp = CONNECT_OPTIONS.scope(
             ConnectOptions {
-                blocked_networks: self.blocked_networks,
+                blocked_networks: self.blocked_networks.clone(),
                 connect_timeout,
                 tls_client_config,
                 override_connect_host,
@@ -317,11 +557,17 @@ impl RequestSender {
             },
         );
 
+        // Body errors are translated as the guest reads the body, outside of
+        // this span, so keep it to record any HTTP/2 reset reason on
+        let span = tracing::Span::current();
         let resp = timeout(first_byte_timeout, resp)
             .await
             .map_err(|_| ErrorCode::ConnectionReadTimeout)?
             .map_err(hyper_legacy_request_error)?
-            .map(|body| body.map_err(hyper_request_error).boxed());
+            .map(|body| {
+                body.map_err(move |err| span.in_scope(|| hyper_request_error(err)))
+                    .boxed()
+            });
 
         tracing::Span::current().record("http.response.status_code", resp.status().as_u16());
 
@@ -554,6 +800,9 @@ fn hyper_request_error(err: hyper::Error) -> ErrorCode {
             return err.clone();
         }
     }
+    if let Some(err) = h2_reset_error(&err) {
+        return err;
+    }
 
     tracing::warn!("hyper request error: {err:?}");
 
@@ -568,12 +817,36 @@ fn hyper_legacy_request_error(err: hyper_util::client::legacy::Error) -> ErrorCo
             return err.clone();
         }
     }
+    if let Some(err) = h2_reset_error(&err) {
+        return err;
+    }
 
     tracing::warn!("hyper request error: {err:?}");
 
     ErrorCode::HttpProtocolError
 }
 
+/// Translate an HTTP/2 stream reset (or connection `GOAWAY`) found in the
+/// source chain of `err` to the most specific wasi-http `ErrorCode`, recording
+/// the reset reason on the current span.
+fn h2_reset_error(err: &(dyn Error + 'static)) -> Option<ErrorCode> {
+    let reason = std::iter::successors(Some(err), |err| err.source())
+        .find_map(|err| err.downcast_ref::<h2::Error>()?.reason())?;
+
+    tracing::Span::current().record("spin.h2_reset_reason", format!("{reason:?}"));
+    tracing::debug!("HTTP/2 stream reset: {reason}");
+
+    Some(match reason {
+        // The server stopped sending before the response was complete
+        h2::Reason::NO_ERROR | h2::Reason::CANCEL => ErrorCode::HttpResponseIncomplete,
+        h2::Reason::REFUSED_STREAM => ErrorCode::ConnectionRefused,
+        h2::Reason::ENHANCE_YOUR_CALM => ErrorCode::ConnectionLimitReached,
+        h2::Reason::CONNECT_ERROR => ErrorCode::ConnectionTerminated,
+        h2::Reason::INADEQUATE_SECURITY => ErrorCode::TlsProtocolError,
+        _ => ErrorCode::HttpProtocolError,
+    })
+}
+
 fn dns_error(rcode: String, info_code: u16) -> ErrorCode {
     ErrorCode::DnsError(wasmtime_wasi_http::bindings::http::types::DnsErrorPayload {
         rcode: Some(rcode),
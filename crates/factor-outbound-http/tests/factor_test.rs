@@ -1,19 +1,47 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use anyhow::bail;
-use http::{Request, Uri};
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode, Uri};
+use http_body_util::{BodyExt, Collected, Full};
+use hyper::{body::Incoming, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
 use spin_common::{assert_matches, assert_not_matches};
 use spin_factor_outbound_http::{
-    intercept::{InterceptOutcome, InterceptRequest, OutboundHttpInterceptor},
+    intercept::{
+        CachingInterceptor, CircuitBreakerInterceptor, InterceptOutcome, InterceptRequest,
+        OutboundHttpInterceptor, RequestOutcome,
+    },
+    runtime_config::{
+        CircuitBreakerConfig, HmacEncoding, RedirectPolicy, RuntimeConfig, SigningRule,
+        SigningScheme,
+    },
     ErrorCode, HostFutureIncomingResponse, OutboundHttpFactor, SelfRequestOrigin,
 };
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_variables::VariablesFactor;
-use spin_factors::{anyhow, RuntimeFactors};
+use spin_factors::{anyhow, ConfiguredApp, RuntimeFactors};
 use spin_factors_test::{toml, TestEnvironment};
 use spin_world::async_trait;
+use tokio::io::AsyncReadExt;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
 use wasmtime_wasi::p2::Pollable;
-use wasmtime_wasi_http::{types::OutgoingRequestConfig, WasiHttpView};
+use wasmtime_wasi_http::{body::HyperOutgoingBody, types::OutgoingRequestConfig, WasiHttpView};
 
 #[derive(RuntimeFactors)]
 struct TestFactors {
@@ -128,9 +156,925 @@ async fn override_connect_host_disallowed_private_ip_fails() -> anyhow::Result<(
     Ok(())
 }
 
+#[tokio::test]
+async fn circuit_breaker_opens_after_threshold() -> anyhow::Result<()> {
+    let breaker = CircuitBreakerInterceptor::new(2, Duration::from_secs(60));
+    let uri = Uri::from_static("https://flaky.test/");
+
+    breaker.observe(&uri, RequestOutcome::Response(StatusCode::BAD_GATEWAY));
+    assert!(!breaker.is_open("flaky.test"));
+    assert_continues(&breaker, &uri).await?;
+
+    breaker.observe(&uri, RequestOutcome::Failed);
+    assert!(breaker.is_open("flaky.test"));
+    assert_short_circuited(&breaker, &uri).await?;
+
+    // Other hosts are unaffected
+    assert_continues(&breaker, &Uri::from_static("https://healthy.test/")).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn circuit_breaker_success_resets_failures() -> anyhow::Result<()> {
+    let breaker = CircuitBreakerInterceptor::new(2, Duration::from_secs(60));
+    let uri = Uri::from_static("https://flaky.test/");
+
+    breaker.observe(&uri, RequestOutcome::Failed);
+    breaker.observe(&uri, RequestOutcome::Response(StatusCode::NOT_FOUND));
+    breaker.observe(&uri, RequestOutcome::Failed);
+    assert!(!breaker.is_open("flaky.test"));
+    assert_continues(&breaker, &uri).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn circuit_breaker_half_open_allows_single_trial() -> anyhow::Result<()> {
+    let breaker = CircuitBreakerInterceptor::new(1, Duration::from_millis(50));
+    let uri = Uri::from_static("https://flaky.test/");
+
+    breaker.observe(&uri, RequestOutcome::Failed);
+    assert_short_circuited(&breaker, &uri).await?;
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    // One trial request goes through; others wait for its outcome
+    assert_continues(&breaker, &uri).await?;
+    assert_short_circuited(&breaker, &uri).await?;
+
+    // A failed trial re-opens the circuit
+    breaker.observe(&uri, RequestOutcome::Failed);
+    assert!(breaker.is_open("flaky.test"));
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    // A successful trial closes it
+    assert_continues(&breaker, &uri).await?;
+    breaker.observe(&uri, RequestOutcome::Response(StatusCode::OK));
+    assert_continues(&breaker, &uri).await?;
+    assert_continues(&breaker, &uri).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn circuit_breaker_short_circuits_real_requests() -> anyhow::Result<()> {
+    let mut state = test_instance_state("http://*", true).await?;
+    let breaker = CircuitBreakerInterceptor::new(1, Duration::from_secs(60));
+    state.http.set_request_interceptor(breaker.clone())?;
+    let mut wasi_http = OutboundHttpFactor::get_wasi_http_impl(&mut state).unwrap();
+
+    // [100::] is the IPv6 "Discard Prefix", which should always fail
+    let req = Request::get("http://[100::1]:80").body(Default::default())?;
+    let mut future_resp = wasi_http.send_request(req, test_request_config())?;
+    future_resp.ready().await;
+    assert_discard_prefix_error(future_resp);
+    assert!(breaker.is_open("[100::1]"));
+
+    let req = Request::get("http://[100::1]:80").body(Default::default())?;
+    let mut future_resp = wasi_http.send_request(req, test_request_config())?;
+    future_resp.ready().await;
+    let resp = future_resp.unwrap_ready().unwrap()?;
+    assert_eq!(resp.resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    Ok(())
+}
+
+#[tokio::test]
+async fn caching_interceptor_serves_fresh_responses() -> anyhow::Result<()> {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let addr = start_server({
+        let hits = hits.clone();
+        move |_| {
+            let n = hits.fetch_add(1, Ordering::SeqCst);
+            Response::builder()
+                .header("cache-control", "max-age=60")
+                .body(Full::new(Bytes::from(format!("response {n}"))))
+                .unwrap()
+        }
+    })
+    .await?;
+    let mut state = test_instance_state("http://127.0.0.1:*", true).await?;
+    let cache = CachingInterceptor::new(NonZeroUsize::new(10).unwrap());
+    state.http.set_request_interceptor(cache.clone())?;
+
+    for _ in 0..2 {
+        let resp = send_and_collect(&mut state, Request::get(format!("http://{addr}/"))).await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.body(), "response 0");
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.len(), 1);
+
+    // Requests with different headers are cached separately
+    let req = Request::get(format!("http://{addr}/")).header("accept", "text/plain");
+    let resp = send_and_collect(&mut state, req).await?;
+    assert_eq!(resp.body(), "response 1");
+    assert_eq!(cache.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn caching_interceptor_revalidates_stale_responses() -> anyhow::Result<()> {
+    let full = Arc::new(AtomicUsize::new(0));
+    let not_modified = Arc::new(AtomicUsize::new(0));
+    let addr = start_server({
+        let (full, not_modified) = (full.clone(), not_modified.clone());
+        move |req| {
+            let builder = Response::builder()
+                .header("cache-control", "max-age=0")
+                .header("etag", "\"v1\"");
+            if req
+                .headers()
+                .get("if-none-match")
+                .is_some_and(|v| v == "\"v1\"")
+            {
+                not_modified.fetch_add(1, Ordering::SeqCst);
+                builder
+                    .status(StatusCode::NOT_MODIFIED)
+                    .header("x-revalidated", "true")
+                    .body(Default::default())
+                    .unwrap()
+            } else {
+                full.fetch_add(1, Ordering::SeqCst);
+                builder.body(Full::new(Bytes::from("hello"))).unwrap()
+            }
+        }
+    })
+    .await?;
+    let mut state = test_instance_state("http://127.0.0.1:*", true).await?;
+    state
+        .http
+        .set_request_interceptor(CachingInterceptor::new(NonZeroUsize::new(10).unwrap()))?;
+
+    let resp = send_and_collect(&mut state, Request::get(format!("http://{addr}/"))).await?;
+    assert_eq!(resp.body(), "hello");
+    assert!(resp.headers().get("x-revalidated").is_none());
+
+    // The stale response is revalidated and the cached body returned
+    let resp = send_and_collect(&mut state, Request::get(format!("http://{addr}/"))).await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.body(), "hello");
+    assert_eq!(resp.headers()["x-revalidated"], "true");
+    assert_eq!(full.load(Ordering::SeqCst), 1);
+    assert_eq!(not_modified.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+async fn assert_continues(breaker: &CircuitBreakerInterceptor, uri: &Uri) -> anyhow::Result<()> {
+    let req: InterceptRequest = Request::get(uri).body(Vec::new())?.into();
+    match breaker.intercept(req).await? {
+        InterceptOutcome::Continue(_) => Ok(()),
+        InterceptOutcome::Complete(resp) => bail!("expected Continue, got {}", resp.status()),
+    }
+}
+
+async fn assert_short_circuited(
+    breaker: &CircuitBreakerInterceptor,
+    uri: &Uri,
+) -> anyhow::Result<()> {
+    let req: InterceptRequest = Request::get(uri).body(Vec::new())?.into();
+    match breaker.intercept(req).await? {
+        InterceptOutcome::Continue(_) => bail!("expected Complete, got Continue"),
+        InterceptOutcome::Complete(resp) => {
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+            Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+async fn app_circuit_opens_after_threshold_and_fails_fast() -> anyhow::Result<()> {
+    let captured = CapturedFields::default();
+    let _guard = tracing_subscriber::registry()
+        .with(captured.clone())
+        .set_default();
+    let upstream = ToggledServer::start().await?;
+    upstream.set_up(false);
+    let app = CircuitBreakerApp::new(Duration::from_secs(60)).await?;
+    let mut state = app.instance("test-component")?;
+
+    for _ in 0..2 {
+        let resp = send_and_collect(&mut state, upstream.get()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+    assert_eq!(captured.get("circuit"), None);
+
+    // The circuit is open, so requests fail without reaching the upstream
+    upstream.set_up(true);
+    let err = send_and_collect(&mut state, upstream.get())
+        .await
+        .unwrap_err();
+    assert_matches!(err, ErrorCode::ConnectionRefused);
+    assert_eq!(upstream.hits(), 2);
+    assert_eq!(captured.get("circuit").as_deref(), Some("open"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn app_circuit_half_open_probe_closes_circuit() -> anyhow::Result<()> {
+    let upstream = ToggledServer::start().await?;
+    upstream.set_up(false);
+    let app = CircuitBreakerApp::new(Duration::from_millis(50)).await?;
+    let mut state = app.instance("test-component")?;
+
+    for _ in 0..2 {
+        send_and_collect(&mut state, upstream.get()).await.unwrap();
+    }
+    assert!(send_and_collect(&mut state, upstream.get()).await.is_err());
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    // A failed probe re-opens the circuit
+    let resp = send_and_collect(&mut state, upstream.get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(send_and_collect(&mut state, upstream.get()).await.is_err());
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    // A successful probe closes it
+    upstream.set_up(true);
+    for _ in 0..3 {
+        let resp = send_and_collect(&mut state, upstream.get()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    assert_eq!(upstream.hits(), 6);
+    Ok(())
+}
+
+#[tokio::test]
+async fn app_circuits_are_isolated_per_authority_and_component() -> anyhow::Result<()> {
+    let bad = ToggledServer::start().await?;
+    bad.set_up(false);
+    let good = ToggledServer::start().await?;
+    let app = CircuitBreakerApp::new(Duration::from_secs(60)).await?;
+    let mut state = app.instance("test-component")?;
+
+    for _ in 0..2 {
+        send_and_collect(&mut state, bad.get()).await.unwrap();
+    }
+    assert!(send_and_collect(&mut state, bad.get()).await.is_err());
+
+    // Other hosts are unaffected
+    let resp = send_and_collect(&mut state, good.get()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Other instances of the component share its circuits...
+    let mut other_instance = app.instance("test-component")?;
+    assert!(send_and_collect(&mut other_instance, bad.get())
+        .await
+        .is_err());
+
+    // ...but other components don't
+    let mut other_component = app.instance("other-component")?;
+    let resp = send_and_collect(&mut other_component, bad.get())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(bad.hits(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_requests_are_exempt_from_app_circuit_breaker() -> anyhow::Result<()> {
+    let upstream = ToggledServer::start().await?;
+    upstream.set_up(false);
+    let app = CircuitBreakerApp::new(Duration::from_secs(60)).await?;
+    let mut state = app.instance("test-component")?;
+    let origin = SelfRequestOrigin::create(http::uri::Scheme::HTTP, &upstream.addr.to_string())?;
+    state.http.set_self_request_origin(origin);
+
+    for _ in 0..3 {
+        let resp = send_and_collect(&mut state, Request::get("/self-request"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+    assert_eq!(upstream.hits(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn redirects_are_followed_up_to_limit() -> anyhow::Result<()> {
+    let addr = start_server(|req| {
+        let hop: u32 = req
+            .uri()
+            .path()
+            .trim_start_matches("/hop/")
+            .parse()
+            .unwrap();
+        redirect(StatusCode::FOUND, &format!("/hop/{}", hop + 1))
+    })
+    .await?;
+    let policy = RedirectPolicy {
+        max: 3,
+        allow_cross_host: false,
+    };
+    let err = send_with_redirects(policy, Request::get(format!("http://{addr}/hop/0")))
+        .await
+        .unwrap_err();
+    assert_matches!(err, ErrorCode::LoopDetected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn redirect_chain_within_limit_succeeds() -> anyhow::Result<()> {
+    let addr = start_server(|req| match req.uri().path() {
+        "/start" => redirect(StatusCode::MOVED_PERMANENTLY, "/middle"),
+        "/middle" => redirect(StatusCode::TEMPORARY_REDIRECT, "/end"),
+        _ => echo(req),
+    })
+    .await?;
+    let resp = send_with_redirects(
+        RedirectPolicy::default(),
+        Request::get(format!("http://{addr}/start")),
+    )
+    .await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["x-path"], "/end");
+    Ok(())
+}
+
+#[tokio::test]
+async fn see_other_redirect_changes_method_to_get() -> anyhow::Result<()> {
+    let addr = start_server(|req| match req.uri().path() {
+        "/submit" => redirect(StatusCode::SEE_OTHER, "/result"),
+        _ => echo(req),
+    })
+    .await?;
+    let resp = send_with_redirects(
+        RedirectPolicy::default(),
+        Request::post(format!("http://{addr}/submit")).header("content-type", "text/plain"),
+    )
+    .await?;
+    assert_eq!(resp.headers()["x-method"], "GET");
+    assert!(!resp.headers().contains_key("x-content-type"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cross_host_redirect_is_denied_by_default() -> anyhow::Result<()> {
+    let other = start_server(echo).await?;
+    let addr =
+        start_server(move |_| redirect(StatusCode::FOUND, &format!("http://{other}/"))).await?;
+    let err = send_with_redirects(
+        RedirectPolicy::default(),
+        Request::get(format!("http://{addr}/")),
+    )
+    .await
+    .unwrap_err();
+    assert_matches!(err, ErrorCode::HttpRequestDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cross_host_redirect_strips_sensitive_headers() -> anyhow::Result<()> {
+    let other = start_server(echo).await?;
+    let addr = start_server(move |req| match req.uri().path() {
+        "/same" => redirect(StatusCode::FOUND, "/cross"),
+        "/cross" => redirect(StatusCode::FOUND, &format!("http://{other}/")),
+        _ => echo(req),
+    })
+    .await?;
+    let policy = RedirectPolicy {
+        allow_cross_host: true,
+        ..Default::default()
+    };
+
+    // Same-origin redirects keep credentials
+    let addr_same = start_server(|req| match req.uri().path() {
+        "/same" => redirect(StatusCode::FOUND, "/final"),
+        _ => echo(req),
+    })
+    .await?;
+    let resp = send_with_redirects(
+        policy,
+        Request::get(format!("http://{addr_same}/same")).header("authorization", "Bearer s3cret"),
+    )
+    .await?;
+    assert_eq!(resp.headers()["x-authorization"], "Bearer s3cret");
+
+    let resp = send_with_redirects(
+        policy,
+        Request::get(format!("http://{addr}/same"))
+            .header("authorization", "Bearer s3cret")
+            .header("cookie", "session=abc"),
+    )
+    .await?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!resp.headers().contains_key("x-authorization"));
+    assert!(!resp.headers().contains_key("x-cookie"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn redirects_to_disallowed_hosts_are_denied() -> anyhow::Result<()> {
+    let addr = start_server(|_| redirect(StatusCode::FOUND, "http://denied.test/")).await?;
+    let policy = RedirectPolicy {
+        allow_cross_host: true,
+        ..Default::default()
+    };
+    let err = send_with_redirects(policy, Request::get(format!("http://{addr}/")))
+        .await
+        .unwrap_err();
+    assert_matches!(err, ErrorCode::HttpRequestDenied);
+    Ok(())
+}
+
+#[tokio::test]
+async fn sigv4_signs_matching_requests() -> anyhow::Result<()> {
+    let addr = start_server(echo).await?;
+    let mut state = signing_instance_state(SigningRule {
+        host: "127.0.0.1".into(),
+        scheme: SigningScheme::SigV4 {
+            region: "us-east-1".into(),
+            service: "s3".into(),
+            access_key_variable: "aws_key".into(),
+            secret_key_variable: "aws_secret".into(),
+            session_token_variable: None,
+        },
+    })
+    .await?;
+
+    let resp = send_and_collect(
+        &mut state,
+        Request::get(format!("http://{addr}/bucket/key?list-type=2")),
+    )
+    .await?;
+    let authorization = resp.headers()["x-authorization"].to_str()?;
+    assert!(
+        authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"),
+        "{authorization}"
+    );
+    assert!(
+        authorization.contains(
+            "/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="
+        ),
+        "{authorization}"
+    );
+    // The empty body is hashed rather than sent unsigned
+    assert_eq!(
+        resp.headers()["x-amz-content-sha256"],
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn hmac_header_signs_matching_requests() -> anyhow::Result<()> {
+    let addr = start_server(echo).await?;
+    let rule = |encoding| SigningRule {
+        host: "127.0.0.1".into(),
+        scheme: SigningScheme::HmacHeader {
+            header: http::HeaderName::from_static("x-signature"),
+            encoding,
+            key_variable: "hook_secret".into(),
+        },
+    };
+
+    let mut state = signing_instance_state(rule(HmacEncoding::Hex)).await?;
+    let resp = send_and_collect(&mut state, Request::post(format!("http://{addr}/"))).await?;
+    assert_eq!(
+        resp.headers()["x-signature"],
+        "f9e66e179b6747ae54108f82f8ade8b3c25d76fd30afde6c395822c530196169"
+    );
+
+    let mut state = signing_instance_state(rule(HmacEncoding::Base64)).await?;
+    let resp = send_and_collect(&mut state, Request::post(format!("http://{addr}/"))).await?;
+    assert_eq!(
+        resp.headers()["x-signature"],
+        "+eZuF5tnR65UEI+C+K3os8Jddv0wr95sOVgixTAZYWk="
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn requests_to_other_hosts_are_not_signed() -> anyhow::Result<()> {
+    let addr = start_server(echo).await?;
+    let mut state = signing_instance_state(SigningRule {
+        host: "*.amazonaws.com".into(),
+        scheme: SigningScheme::HmacHeader {
+            header: http::HeaderName::from_static("x-signature"),
+            encoding: HmacEncoding::Hex,
+            key_variable: "hook_secret".into(),
+        },
+    })
+    .await?;
+    let resp = send_and_collect(&mut state, Request::get(format!("http://{addr}/"))).await?;
+    assert!(!resp.headers().contains_key("x-signature"));
+    Ok(())
+}
+
+/// Builds instance state which signs requests with `rule`, with credentials
+/// in application variables.
+async fn signing_instance_state(rule: SigningRule) -> anyhow::Result<TestFactorsInstanceState> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        http: OutboundHttpFactor::default(),
+    };
+    TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [variables]
+            aws_key = { default = "AKIDEXAMPLE" }
+            aws_secret = { default = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", secret = true }
+            hook_secret = { default = "secret", secret = true }
+
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["http://127.0.0.1:*"]
+        })
+        .runtime_config(TestFactorsRuntimeConfig {
+            networking: Some(
+                spin_factor_outbound_networking::runtime_config::RuntimeConfig {
+                    block_private_networks: false,
+                    ..Default::default()
+                },
+            ),
+            http: Some(RuntimeConfig {
+                signing: vec![rule],
+                ..Default::default()
+            }),
+            ..Default::default()
+        })?
+        .build_instance_state()
+        .await
+}
+
+#[tokio::test]
+async fn dropping_pending_response_closes_connection() -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (received_tx, received_rx) = tokio::sync::oneshot::channel();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await?;
+        // Read the request head, then never respond
+        let mut buf = [0; 1024];
+        let mut head = vec![];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await?;
+            anyhow::ensure!(n > 0, "connection closed before request was sent");
+            head.extend_from_slice(&buf[..n]);
+        }
+        _ = received_tx.send(());
+        // Returns 0 once the client closes the connection
+        Ok(stream.read(&mut buf).await?)
+    });
+
+    let mut state = test_instance_state("http://127.0.0.1:*", true).await?;
+    let mut wasi_http = OutboundHttpFactor::get_wasi_http_impl(&mut state).unwrap();
+    let req = Request::get(format!("http://{addr}/slow")).body(Default::default())?;
+    let config = OutgoingRequestConfig {
+        first_byte_timeout: Duration::from_secs(30),
+        ..test_request_config()
+    };
+    let future_resp = wasi_http.send_request(req, config)?;
+
+    received_rx.await?;
+    drop(future_resp);
+
+    let read = tokio::time::timeout(Duration::from_secs(5), server).await???;
+    assert_eq!(read, 0, "server should see the connection closed");
+    Ok(())
+}
+
+/// Serializes tests which point `SPIN_OUTBOUND_H2C_PRIOR_KNOWLEDGE` at their
+/// own server.
+static H2C_HOST: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[tokio::test]
+async fn h2_trailers_are_forwarded_both_ways() -> anyhow::Result<()> {
+    let _h2c_host = H2C_HOST.lock().await;
+    let addr = start_h2c_server().await?;
+
+    let mut trailers = HeaderMap::new();
+    trailers.insert("x-checksum", HeaderValue::from_static("abc123"));
+    let body = Full::new(Bytes::from_static(b"hello"))
+        .map_err(|err| match err {})
+        .with_trailers(std::future::ready(Some(Ok(trailers))))
+        .boxed();
+    let req = Request::post(format!("http://{addr}/trailers")).body(body)?;
+    let Ok(collected) = send_h2c(addr, req).await else {
+        bail!("request with trailers failed");
+    };
+
+    let trailers = collected.trailers().expect("response should have trailers");
+    assert_eq!(trailers["grpc-status"], "0");
+    assert_eq!(trailers["x-checksum"], "abc123");
+    assert_eq!(collected.to_bytes(), "ok");
+    Ok(())
+}
+
+#[tokio::test]
+async fn h2_stream_reset_is_reported() -> anyhow::Result<()> {
+    let captured = CapturedFields::default();
+    let _guard = tracing_subscriber::registry()
+        .with(captured.clone())
+        .set_default();
+    let _h2c_host = H2C_HOST.lock().await;
+    let addr = start_h2c_server().await?;
+
+    let req = Request::get(format!("http://{addr}/reset")).body(Default::default())?;
+    let result = send_h2c(addr, req).await;
+
+    assert_matches!(result, Err(ErrorCode::HttpResponseIncomplete));
+    assert_eq!(
+        captured.get("spin.h2_reset_reason").as_deref(),
+        Some("CANCEL")
+    );
+    Ok(())
+}
+
+/// Sends a request over plaintext HTTP/2 to a server started by
+/// [`start_h2c_server`], collecting the response body.
+async fn send_h2c(
+    addr: SocketAddr,
+    req: Request<HyperOutgoingBody>,
+) -> Result<Collected<Bytes>, ErrorCode> {
+    std::env::set_var("SPIN_OUTBOUND_H2C_PRIOR_KNOWLEDGE", addr.to_string());
+    let mut state = test_instance_state("http://127.0.0.1:*", true)
+        .await
+        .unwrap();
+    let mut wasi_http = OutboundHttpFactor::get_wasi_http_impl(&mut state).unwrap();
+    let config = OutgoingRequestConfig {
+        first_byte_timeout: Duration::from_secs(5),
+        between_bytes_timeout: Duration::from_secs(5),
+        ..test_request_config()
+    };
+    let mut future_resp = wasi_http.send_request(req, config).unwrap();
+    future_resp.ready().await;
+    let resp = future_resp.unwrap_ready().unwrap()?.resp;
+    resp.into_body().collect().await
+}
+
+/// Starts a local plaintext HTTP/2 server. Requests to `/trailers` are
+/// answered with a `grpc-status` trailer along with the request's trailers;
+/// any other request has its stream reset after the response head is sent.
+async fn start_h2c_server() -> anyhow::Result<SocketAddr> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut conn = h2::server::handshake(stream).await?;
+                while let Some(request) = conn.accept().await {
+                    let (request, respond) = request?;
+                    tokio::spawn(respond_h2c(request, respond));
+                }
+                anyhow::Ok(())
+            });
+        }
+    });
+    Ok(addr)
+}
+
+async fn respond_h2c(
+    request: Request<h2::RecvStream>,
+    mut respond: h2::server::SendResponse<Bytes>,
+) -> anyhow::Result<()> {
+    let mut send = respond.send_response(Response::new(()), false)?;
+    if request.uri().path() != "/trailers" {
+        send.send_data(Bytes::from_static(b"partial"), false)?;
+        send.send_reset(h2::Reason::CANCEL);
+        return Ok(());
+    }
+    let mut body = request.into_body();
+    while let Some(data) = body.data().await {
+        let data = data?;
+        body.flow_control().release_capacity(data.len())?;
+    }
+    let mut trailers = body.trailers().await?.unwrap_or_default();
+    trailers.insert("grpc-status", HeaderValue::from_static("0"));
+    send.send_data(Bytes::from_static(b"ok"), false)?;
+    send.send_trailers(trailers)?;
+    Ok(())
+}
+
+/// Captures the string fields recorded on spans.
+#[derive(Clone, Default)]
+struct CapturedFields(Arc<Mutex<HashMap<String, String>>>);
+
+impl CapturedFields {
+    fn get(&self, name: &str) -> Option<String> {
+        self.0.lock().unwrap().get(name).cloned()
+    }
+}
+
+impl Visit for CapturedFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+impl<S: Subscriber> Layer<S> for CapturedFields {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        attrs.record(&mut self.clone());
+    }
+
+    fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        values.record(&mut self.clone());
+    }
+}
+
+/// A local server which responds `200 OK` while up and `503 Service
+/// Unavailable` while down, counting the requests it receives.
+struct ToggledServer {
+    addr: SocketAddr,
+    up: Arc<AtomicBool>,
+    hits: Arc<AtomicUsize>,
+}
+
+impl ToggledServer {
+    async fn start() -> anyhow::Result<Self> {
+        let up = Arc::new(AtomicBool::new(true));
+        let hits = Arc::new(AtomicUsize::new(0));
+        let addr = start_server({
+            let up = up.clone();
+            let hits = hits.clone();
+            move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                let status = if up.load(Ordering::SeqCst) {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                };
+                Response::builder()
+                    .status(status)
+                    .body(Default::default())
+                    .unwrap()
+            }
+        })
+        .await?;
+        Ok(Self { addr, up, hits })
+    }
+
+    fn set_up(&self, up: bool) {
+        self.up.store(up, Ordering::SeqCst);
+    }
+
+    fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+
+    fn get(&self) -> http::request::Builder {
+        Request::get(format!("http://{}/", self.addr))
+    }
+}
+
+/// An app with two components and a circuit breaker which opens after two
+/// failures, from which any number of instances can be built.
+struct CircuitBreakerApp {
+    factors: TestFactors,
+    app: ConfiguredApp<TestFactors>,
+}
+
+impl CircuitBreakerApp {
+    async fn new(open_duration: Duration) -> anyhow::Result<Self> {
+        let factors = TestFactors {
+            variables: VariablesFactor::default(),
+            networking: OutboundNetworkingFactor::new(),
+            http: OutboundHttpFactor::default(),
+        };
+        let (factors, app) = TestEnvironment::new(factors)
+            .extend_manifest(toml! {
+                [component.test-component]
+                source = "does-not-exist.wasm"
+                allowed_outbound_hosts = ["http://self", "http://127.0.0.1:*"]
+
+                [component.other-component]
+                source = "does-not-exist.wasm"
+                allowed_outbound_hosts = ["http://127.0.0.1:*"]
+            })
+            .runtime_config(TestFactorsRuntimeConfig {
+                networking: Some(
+                    spin_factor_outbound_networking::runtime_config::RuntimeConfig {
+                        block_private_networks: false,
+                        ..Default::default()
+                    },
+                ),
+                http: Some(RuntimeConfig {
+                    circuit_breaker: Some(CircuitBreakerConfig {
+                        failure_threshold: 2,
+                        window: Duration::from_secs(60),
+                        open_duration,
+                        half_open_max: 1,
+                        failure_statuses: None,
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })?
+            .build_configured_app()
+            .await?;
+        Ok(Self { factors, app })
+    }
+
+    fn instance(&self, component_id: &str) -> anyhow::Result<TestFactorsInstanceState> {
+        let builders = self.factors.prepare(&self.app, component_id)?;
+        Ok(self.factors.build_instance_state(builders)?)
+    }
+}
+
+/// Sends a request through the outbound HTTP factor with the given redirect policy.
+async fn send_with_redirects(
+    policy: RedirectPolicy,
+    request: http::request::Builder,
+) -> Result<Response<Bytes>, ErrorCode> {
+    let mut state = test_instance_state_with_http_config(
+        "http://127.0.0.1:*",
+        true,
+        RuntimeConfig {
+            follow_redirects: Some(policy),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    send_and_collect(&mut state, request).await
+}
+
+/// Sends a request through the outbound HTTP factor, collecting the response body.
+async fn send_and_collect(
+    state: &mut TestFactorsInstanceState,
+    request: http::request::Builder,
+) -> Result<Response<Bytes>, ErrorCode> {
+    let mut wasi_http = OutboundHttpFactor::get_wasi_http_impl(state).unwrap();
+    let body = Full::new(Bytes::new()).map_err(|err| match err {}).boxed();
+    let req = request.body(body).unwrap();
+    let config = OutgoingRequestConfig {
+        use_tls: false,
+        connect_timeout: Duration::from_secs(5),
+        first_byte_timeout: Duration::from_secs(5),
+        between_bytes_timeout: Duration::from_secs(5),
+    };
+    let mut future_resp = wasi_http.send_request(req, config).unwrap();
+    future_resp.ready().await;
+    let resp = future_resp.unwrap_ready().unwrap()?.resp;
+    let (parts, body) = resp.into_parts();
+    let body = body.collect().await?.to_bytes();
+    Ok(Response::from_parts(parts, body))
+}
+
+/// Starts a local HTTP server which responds to each request with `handler`.
+async fn start_server(
+    handler: impl Fn(Request<Incoming>) -> Response<Full<Bytes>> + Clone + Send + Sync + 'static,
+) -> anyhow::Result<SocketAddr> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            let service = service_fn(move |req| {
+                let resp = handler(req);
+                async move { Ok::<_, Infallible>(resp) }
+            });
+            tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+        }
+    });
+    Ok(addr)
+}
+
+fn redirect(status: StatusCode, location: &str) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header("location", location)
+        .body(Default::default())
+        .unwrap()
+}
+
+/// Responds with details of the request as `x-` headers.
+fn echo(req: Request<Incoming>) -> Response<Full<Bytes>> {
+    let mut builder = Response::builder()
+        .header("x-method", req.method().as_str())
+        .header("x-path", req.uri().path());
+    for name in [
+        "authorization",
+        "cookie",
+        "content-type",
+        "x-amz-content-sha256",
+        "x-signature",
+    ] {
+        if let Some(value) = req.headers().get(name) {
+            builder = builder.header(format!("x-{name}"), value);
+        }
+    }
+    builder.body(Default::default()).unwrap()
+}
+
 async fn test_instance_state(
     allowed_outbound_hosts: &str,
     allow_private_ips: bool,
+) -> anyhow::Result<TestFactorsInstanceState> {
+    test_instance_state_with_http_config(
+        allowed_outbound_hosts,
+        allow_private_ips,
+        RuntimeConfig::default(),
+    )
+    .await
+}
+
+async fn test_instance_state_with_http_config(
+    allowed_outbound_hosts: &str,
+    allow_private_ips: bool,
+    http_config: RuntimeConfig,
 ) -> anyhow::Result<TestFactorsInstanceState> {
     let factors = TestFactors {
         variables: VariablesFactor::default(),
@@ -150,6 +1094,7 @@ async fn test_instance_state(
                     ..Default::default()
                 },
             ),
+            http: Some(http_config),
             ..Default::default()
         })?;
     env.build_instance_state().await
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use spin_core::wasmtime::component::Resource;
+use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
+use spin_world::spin::kafka::kafka::{self, Error, Header, Producer};
+use tracing::{instrument, Level};
+
+use crate::{BootstrapServers, KafkaProducer, ProducerPool};
+
+pub struct InstanceState {
+    allowed_hosts: OutboundAllowedHosts,
+    pool: Arc<ProducerPool>,
+    producers: spin_resource_table::Table<Arc<dyn KafkaProducer>>,
+}
+
+impl InstanceState {
+    pub(crate) fn new(allowed_hosts: OutboundAllowedHosts, pool: Arc<ProducerPool>) -> Self {
+        Self {
+            allowed_hosts,
+            pool,
+            producers: spin_resource_table::Table::new(1024),
+        }
+    }
+
+    /// Checks that every bootstrap server is allowed.
+    ///
+    /// Brokers which the bootstrap servers advertise are not checked.
+    async fn is_allowed(&self, servers: &BootstrapServers) -> Result<bool, Error> {
+        for broker in servers.brokers() {
+            let allowed = self
+                .allowed_hosts
+                .check_url(&format!("kafka://{broker}"), "kafka")
+                .await
+                .map_err(other_error)?;
+            if !allowed {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn get_producer(&self, producer: &Resource<Producer>) -> Result<&dyn KafkaProducer, Error> {
+        self.producers
+            .get(producer.rep())
+            .map(|p| p.as_ref())
+            .ok_or(Error::Other("could not find producer for resource".into()))
+    }
+}
+
+impl kafka::Host for InstanceState {
+    fn convert_error(&mut self, error: Error) -> Result<Error> {
+        Ok(error)
+    }
+}
+
+impl kafka::HostProducer for InstanceState {
+    #[instrument(name = "spin_outbound_kafka.open_producer", skip(self), err(level = Level::INFO), fields(otel.kind = "client"))]
+    async fn open(&mut self, bootstrap_servers: String) -> Result<Resource<Producer>, Error> {
+        let servers = BootstrapServers::parse(&bootstrap_servers)?;
+        if !self.is_allowed(&servers).await? {
+            return Err(Error::ConnectionFailed(format!(
+                "bootstrap servers {bootstrap_servers} are not permitted"
+            )));
+        }
+        let producer = self.pool.get(&servers)?;
+        self.producers
+            .push(producer)
+            .map(Resource::new_own)
+            .map_err(|_| Error::TooManyProducers)
+    }
+
+    #[instrument(name = "spin_outbound_kafka.publish", skip(self, producer, key, value, headers), err(level = Level::INFO),
+        fields(otel.kind = "producer", otel.name = format!("{} publish", topic), messaging.operation = "publish",
+        messaging.system = "kafka", messaging.destination.name = %topic))]
+    async fn publish(
+        &mut self,
+        producer: Resource<Producer>,
+        topic: String,
+        key: Option<Vec<u8>>,
+        value: Vec<u8>,
+        headers: Vec<Header>,
+    ) -> Result<(), Error> {
+        self.get_producer(&producer)?
+            .publish(topic, key, value, headers)
+            .await
+    }
+
+    async fn drop(&mut self, producer: Resource<Producer>) -> anyhow::Result<()> {
+        // The producer stays in the app's pool for other instances to use.
+        self.producers.remove(producer.rep());
+        Ok(())
+    }
+}
+
+fn other_error(e: impl std::fmt::Display) -> Error {
+    Error::Other(e.to_string())
+}
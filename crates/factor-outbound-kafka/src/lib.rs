@@ -0,0 +1,277 @@
+mod host;
+pub mod runtime_config;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use host::InstanceState;
+use rdkafka::message::{Header as KafkaHeader, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use spin_core::async_trait;
+use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorData, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
+};
+use spin_world::spin::kafka::kafka::{self, Error, Header};
+
+pub use runtime_config::{ConnectionConfig, RuntimeConfig};
+
+pub struct KafkaFactor {
+    create_producer: Arc<dyn ProducerCreator>,
+}
+
+impl KafkaFactor {
+    pub fn new(create_producer: Arc<dyn ProducerCreator>) -> Self {
+        Self { create_producer }
+    }
+}
+
+impl Factor for KafkaFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceState;
+
+    fn init(&mut self, ctx: &mut impl spin_factors::InitContext<Self>) -> anyhow::Result<()> {
+        ctx.link_bindings(kafka::add_to_linker::<_, FactorData<Self>>)?;
+        Ok(())
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let runtime_config = ctx.take_runtime_config().unwrap_or_default();
+        Ok(AppState {
+            producers: Arc::new(ProducerPool {
+                create_producer: self.create_producer.clone(),
+                clusters: runtime_config.clusters,
+                producers: Default::default(),
+            }),
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<Self::InstanceBuilder> {
+        let allowed_hosts = ctx
+            .instance_builder::<OutboundNetworkingFactor>()?
+            .allowed_hosts();
+        Ok(InstanceState::new(
+            allowed_hosts,
+            ctx.app_state().producers.clone(),
+        ))
+    }
+}
+
+impl SelfInstanceBuilder for InstanceState {}
+
+pub struct AppState {
+    producers: Arc<ProducerPool>,
+}
+
+/// The app's producers, shared by all of its instances.
+///
+/// There is one producer for each set of bootstrap servers.
+pub(crate) struct ProducerPool {
+    create_producer: Arc<dyn ProducerCreator>,
+    /// Connection settings, keyed by normalized bootstrap servers.
+    clusters: HashMap<String, ConnectionConfig>,
+    producers: Mutex<HashMap<String, Arc<dyn KafkaProducer>>>,
+}
+
+impl ProducerPool {
+    /// Returns the producer for `servers`, creating it if there isn't one.
+    pub(crate) fn get(&self, servers: &BootstrapServers) -> Result<Arc<dyn KafkaProducer>, Error> {
+        let key = servers.to_string();
+        let mut producers = self.producers.lock().unwrap();
+        if let Some(producer) = producers.get(&key) {
+            return Ok(producer.clone());
+        }
+        let config = self.clusters.get(&key).cloned().unwrap_or_default();
+        let producer = self.create_producer.create(&key, &config)?;
+        producers.insert(key, producer.clone());
+        Ok(producer)
+    }
+}
+
+/// A comma-separated list of `host:port` broker addresses, sorted so that the
+/// same brokers in a different order share a producer.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct BootstrapServers(Vec<String>);
+
+impl BootstrapServers {
+    pub(crate) fn parse(servers: &str) -> Result<Self, Error> {
+        let mut brokers = servers
+            .split(',')
+            .map(|broker| {
+                let broker = broker.trim();
+                match broker.rsplit_once(':') {
+                    Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                        Ok(broker.to_owned())
+                    }
+                    _ => Err(Error::InvalidAddress),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        brokers.sort();
+        brokers.dedup();
+        Ok(Self(brokers))
+    }
+
+    pub(crate) fn brokers(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BootstrapServers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0.join(","))
+    }
+}
+
+/// A Kafka producer, shared by all of an app's instances.
+#[async_trait]
+pub trait KafkaProducer: Send + Sync {
+    /// Publishes a record, returning once the brokers have acknowledged it.
+    async fn publish(
+        &self,
+        topic: String,
+        key: Option<Vec<u8>>,
+        value: Vec<u8>,
+        headers: Vec<Header>,
+    ) -> Result<(), Error>;
+}
+
+/// A trait for creating Kafka producers.
+pub trait ProducerCreator: Send + Sync {
+    /// Creates a producer for the cluster at `bootstrap_servers`.
+    fn create(
+        &self,
+        bootstrap_servers: &str,
+        config: &ConnectionConfig,
+    ) -> Result<Arc<dyn KafkaProducer>, Error>;
+}
+
+impl<F> ProducerCreator for F
+where
+    F: Fn(&str, &ConnectionConfig) -> Result<Arc<dyn KafkaProducer>, Error> + Send + Sync,
+{
+    fn create(
+        &self,
+        bootstrap_servers: &str,
+        config: &ConnectionConfig,
+    ) -> Result<Arc<dyn KafkaProducer>, Error> {
+        self(bootstrap_servers, config)
+    }
+}
+
+/// How long a record may wait to be delivered before publishing fails.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A concrete implementation of [`KafkaProducer`] using rdkafka.
+pub struct NetworkedKafkaProducer {
+    inner: FutureProducer,
+}
+
+impl NetworkedKafkaProducer {
+    /// Create a [`ProducerCreator`] that creates a [`NetworkedKafkaProducer`].
+    pub fn creator() -> Arc<dyn ProducerCreator> {
+        Arc::new(NetworkedProducerCreator)
+    }
+
+    /// Create a new [`NetworkedKafkaProducer`] for the given bootstrap servers.
+    ///
+    /// Brokers are connected to lazily, so connection errors are reported when
+    /// publishing.
+    pub fn create(bootstrap_servers: &str, config: &ConnectionConfig) -> Result<Self, Error> {
+        let mut client_config = rdkafka::ClientConfig::new();
+        client_config
+            .set("bootstrap.servers", bootstrap_servers)
+            .set(
+                "message.timeout.ms",
+                MESSAGE_TIMEOUT.as_millis().to_string(),
+            );
+        config.apply(&mut client_config);
+        let inner = client_config.create().map_err(|e| {
+            tracing::error!("Kafka producer creation error: {e:?}");
+            Error::ConnectionFailed(e.to_string())
+        })?;
+        Ok(Self { inner })
+    }
+}
+
+struct NetworkedProducerCreator;
+
+impl ProducerCreator for NetworkedProducerCreator {
+    fn create(
+        &self,
+        bootstrap_servers: &str,
+        config: &ConnectionConfig,
+    ) -> Result<Arc<dyn KafkaProducer>, Error> {
+        Ok(Arc::new(NetworkedKafkaProducer::create(
+            bootstrap_servers,
+            config,
+        )?))
+    }
+}
+
+#[async_trait]
+impl KafkaProducer for NetworkedKafkaProducer {
+    async fn publish(
+        &self,
+        topic: String,
+        key: Option<Vec<u8>>,
+        value: Vec<u8>,
+        headers: Vec<Header>,
+    ) -> Result<(), Error> {
+        let headers =
+            headers
+                .iter()
+                .fold(OwnedHeaders::new_with_capacity(headers.len()), |all, h| {
+                    all.insert(KafkaHeader {
+                        key: &h.key,
+                        value: Some(&h.value),
+                    })
+                });
+        let mut record = FutureRecord::<[u8], [u8]>::to(&topic)
+            .payload(&value)
+            .headers(headers);
+        if let Some(key) = &key {
+            record = record.key(key);
+        }
+        // Records are queued until delivered or `MESSAGE_TIMEOUT` passes
+        self.inner
+            .send(record, Timeout::Never)
+            .await
+            .map(|_| ())
+            .map_err(|(err, _)| Error::Other(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bootstrap_servers_are_normalized() {
+        let servers = BootstrapServers::parse(" b.test:9092,a.test:9093,b.test:9092 ").unwrap();
+        assert_eq!(servers.to_string(), "a.test:9093,b.test:9092");
+        assert_eq!(
+            BootstrapServers::parse("[::1]:9092").unwrap().brokers(),
+            ["[::1]:9092"]
+        );
+        for invalid in [
+            "",
+            "a.test",
+            "a.test:",
+            ":9092",
+            "a.test:9092,",
+            "a.test:big",
+        ] {
+            assert!(BootstrapServers::parse(invalid).is_err(), "{invalid:?}");
+        }
+    }
+}
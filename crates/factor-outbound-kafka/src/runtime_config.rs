@@ -0,0 +1,133 @@
+pub mod spin;
+
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::Deserialize;
+
+/// Runtime configuration for outbound Kafka.
+#[derive(Debug, Default)]
+pub struct RuntimeConfig {
+    /// Connection settings for clusters, keyed by bootstrap servers.
+    ///
+    /// Producers for clusters not listed here connect in plaintext without
+    /// authentication.
+    pub clusters: HashMap<String, ConnectionConfig>,
+}
+
+/// How to connect and authenticate to a Kafka cluster.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionConfig {
+    /// The protocol used to communicate with brokers.
+    pub security_protocol: SecurityProtocol,
+    /// SASL credentials, required by the `sasl_*` security protocols.
+    pub sasl: Option<SaslConfig>,
+    /// A PEM file of CA certificates for verifying the brokers' certificates,
+    /// used instead of the system's certificates.
+    pub ca_cert_path: Option<PathBuf>,
+}
+
+impl ConnectionConfig {
+    /// Checks that the settings are consistent with the security protocol.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let protocol = self.security_protocol;
+        match (protocol.uses_sasl(), &self.sasl) {
+            (true, None) => anyhow::bail!(
+                "security protocol '{}' requires `sasl` credentials",
+                protocol.as_str()
+            ),
+            (false, Some(_)) => anyhow::bail!(
+                "`sasl` credentials require a `sasl_plaintext` or `sasl_ssl` security protocol"
+            ),
+            _ => {}
+        }
+        if self.ca_cert_path.is_some() && !protocol.uses_tls() {
+            anyhow::bail!("`ca_cert_path` requires an `ssl` or `sasl_ssl` security protocol");
+        }
+        Ok(())
+    }
+
+    /// Sets the corresponding properties on an rdkafka client config.
+    pub fn apply(&self, client_config: &mut rdkafka::ClientConfig) {
+        client_config.set("security.protocol", self.security_protocol.as_str());
+        if let Some(sasl) = &self.sasl {
+            client_config
+                .set("sasl.mechanism", sasl.mechanism.as_str())
+                .set("sasl.username", &sasl.username)
+                .set("sasl.password", &sasl.password);
+        }
+        if let Some(ca_cert_path) = &self.ca_cert_path {
+            client_config.set("ssl.ca.location", ca_cert_path.to_string_lossy());
+        }
+    }
+}
+
+/// The protocol used to communicate with Kafka brokers.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityProtocol {
+    #[default]
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+impl SecurityProtocol {
+    /// The protocol's name in librdkafka configuration.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Plaintext => "plaintext",
+            Self::Ssl => "ssl",
+            Self::SaslPlaintext => "sasl_plaintext",
+            Self::SaslSsl => "sasl_ssl",
+        }
+    }
+
+    fn uses_sasl(&self) -> bool {
+        matches!(self, Self::SaslPlaintext | Self::SaslSsl)
+    }
+
+    fn uses_tls(&self) -> bool {
+        matches!(self, Self::Ssl | Self::SaslSsl)
+    }
+}
+
+/// SASL credentials for a Kafka cluster.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SaslConfig {
+    pub mechanism: SaslMechanism,
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for SaslConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SaslConfig")
+            .field("mechanism", &self.mechanism)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A SASL mechanism supported for Kafka authentication.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub enum SaslMechanism {
+    #[serde(rename = "PLAIN")]
+    Plain,
+    #[serde(rename = "SCRAM-SHA-256")]
+    ScramSha256,
+    #[serde(rename = "SCRAM-SHA-512")]
+    ScramSha512,
+}
+
+impl SaslMechanism {
+    /// The mechanism's name in librdkafka configuration.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Plain => "PLAIN",
+            Self::ScramSha256 => "SCRAM-SHA-256",
+            Self::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
+}
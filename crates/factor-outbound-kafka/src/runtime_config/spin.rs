@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+use super::{ConnectionConfig, RuntimeConfig, SaslConfig, SecurityProtocol};
+use crate::BootstrapServers;
+
+/// Get the runtime configuration for outbound Kafka from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [[outbound_kafka.cluster]]
+/// bootstrap_servers = "broker-1:9093,broker-2:9093"
+/// security_protocol = "sasl_ssl"
+/// ca_cert_path = "/etc/kafka/ca.pem"
+/// sasl = { mechanism = "SCRAM-SHA-512", username = "spin", password = "..." }
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(outbound_kafka) = table.get("outbound_kafka") else {
+        return Ok(None);
+    };
+    let outbound_kafka: OutboundKafkaToml = outbound_kafka.clone().try_into()?;
+
+    let mut clusters = std::collections::HashMap::new();
+    for cluster in outbound_kafka.cluster {
+        let servers = BootstrapServers::parse(&cluster.bootstrap_servers)
+            .ok()
+            .with_context(|| {
+                format!(
+                    "invalid Kafka bootstrap servers '{}'",
+                    cluster.bootstrap_servers
+                )
+            })?;
+        let connection = ConnectionConfig {
+            security_protocol: cluster.security_protocol,
+            sasl: cluster.sasl,
+            ca_cert_path: cluster.ca_cert_path,
+        };
+        connection.validate().with_context(|| {
+            format!(
+                "invalid Kafka cluster config for '{}'",
+                cluster.bootstrap_servers
+            )
+        })?;
+        if clusters.insert(servers.to_string(), connection).is_some() {
+            anyhow::bail!(
+                "duplicate Kafka cluster config for '{}'",
+                cluster.bootstrap_servers
+            );
+        }
+    }
+    Ok(Some(RuntimeConfig { clusters }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutboundKafkaToml {
+    #[serde(default)]
+    cluster: Vec<ClusterToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClusterToml {
+    bootstrap_servers: String,
+    #[serde(default)]
+    security_protocol: SecurityProtocol,
+    #[serde(default)]
+    sasl: Option<SaslConfig>,
+    #[serde(default)]
+    ca_cert_path: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime_config::SaslMechanism;
+
+    fn config(toml: &str) -> anyhow::Result<Option<RuntimeConfig>> {
+        config_from_table(&toml::from_str::<toml::Table>(toml)?)
+    }
+
+    #[test]
+    fn clusters_are_keyed_by_normalized_servers() -> anyhow::Result<()> {
+        let config = config(
+            r#"
+            [[outbound_kafka.cluster]]
+            bootstrap_servers = "b.test:9093, a.test:9093"
+            security_protocol = "sasl_ssl"
+            sasl = { mechanism = "SCRAM-SHA-512", username = "spin", password = "secret" }
+            "#,
+        )?
+        .unwrap();
+        let connection = &config.clusters["a.test:9093,b.test:9093"];
+        assert_eq!(connection.security_protocol, SecurityProtocol::SaslSsl);
+        assert_eq!(
+            connection.sasl.as_ref().unwrap().mechanism,
+            SaslMechanism::ScramSha512
+        );
+        assert!(!format!("{connection:?}").contains("secret"));
+        Ok(())
+    }
+
+    #[test]
+    fn inconsistent_clusters_are_rejected() {
+        for invalid in [
+            // No port
+            "bootstrap_servers = 'a.test'",
+            // Missing credentials
+            "bootstrap_servers = 'a.test:9092'\nsecurity_protocol = 'sasl_plaintext'",
+            // CA certs without TLS
+            "bootstrap_servers = 'a.test:9092'\nca_cert_path = 'ca.pem'",
+        ] {
+            let toml = format!("[[outbound_kafka.cluster]]\n{invalid}");
+            assert!(config(&toml).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Tests against a real Kafka broker.
+//!
+//! These are ignored by default. To run them, start a broker, e.g. with
+//! `docker run -d -p 9092:9092 apache/kafka:3.9.0`, then run
+//! `cargo test -p spin-factor-outbound-kafka -- --ignored`. Set
+//! `KAFKA_BOOTSTRAP_SERVERS` if the broker isn't at `localhost:9092`.
+
+use std::time::Duration;
+
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::{Headers, Message};
+use rdkafka::ClientConfig;
+use spin_factor_outbound_kafka::{ConnectionConfig, KafkaProducer, NetworkedKafkaProducer};
+use spin_factors::anyhow;
+use spin_world::spin::kafka::kafka::Header;
+
+fn bootstrap_servers() -> String {
+    std::env::var("KAFKA_BOOTSTRAP_SERVERS").unwrap_or_else(|_| "localhost:9092".to_string())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a local Kafka broker"]
+async fn published_records_are_consumed() -> anyhow::Result<()> {
+    let servers = bootstrap_servers();
+    // A fresh topic, which the broker auto-creates, so that earlier runs
+    // don't interfere.
+    let topic = format!(
+        "spin-test-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_millis()
+    );
+
+    let producer = NetworkedKafkaProducer::create(&servers, &ConnectionConfig::default())?;
+    producer
+        .publish(
+            topic.clone(),
+            Some(b"key".to_vec()),
+            b"value".to_vec(),
+            vec![Header {
+                key: "source".to_string(),
+                value: b"spin".to_vec(),
+            }],
+        )
+        .await?;
+
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &servers)
+        .set("group.id", &topic)
+        .set("auto.offset.reset", "earliest")
+        .create()?;
+    consumer.subscribe(&[&topic])?;
+    let message = tokio::time::timeout(Duration::from_secs(30), consumer.recv()).await??;
+
+    assert_eq!(message.key(), Some(&b"key"[..]));
+    assert_eq!(message.payload(), Some(&b"value"[..]));
+    let header = message.headers().unwrap().get(0);
+    assert_eq!(header.key, "source");
+    assert_eq!(header.value, Some(&b"spin"[..]));
+
+    Ok(())
+}
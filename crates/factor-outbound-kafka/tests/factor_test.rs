@@ -0,0 +1,222 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use spin_core::async_trait;
+use spin_factor_outbound_kafka::{
+    runtime_config::{SaslConfig, SaslMechanism, SecurityProtocol},
+    ConnectionConfig, KafkaFactor, KafkaProducer, ProducerCreator, RuntimeConfig,
+};
+use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_variables::VariablesFactor;
+use spin_factors::{anyhow, RuntimeFactors};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_world::spin::kafka::kafka::{Error, Header, HostProducer};
+
+/// A record published to a [`MockProducer`].
+#[derive(Debug, PartialEq)]
+struct Record {
+    topic: String,
+    key: Option<Vec<u8>>,
+    value: Vec<u8>,
+    headers: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Default)]
+struct MockProducer {
+    records: Mutex<Vec<Record>>,
+}
+
+#[async_trait]
+impl KafkaProducer for MockProducer {
+    async fn publish(
+        &self,
+        topic: String,
+        key: Option<Vec<u8>>,
+        value: Vec<u8>,
+        headers: Vec<Header>,
+    ) -> Result<(), Error> {
+        self.records.lock().unwrap().push(Record {
+            topic,
+            key,
+            value,
+            headers: headers.into_iter().map(|h| (h.key, h.value)).collect(),
+        });
+        Ok(())
+    }
+}
+
+/// Records the producers created, with the servers and config they were
+/// created for.
+#[derive(Default)]
+struct MockProducerCreator {
+    created: Mutex<Vec<(String, ConnectionConfig, Arc<MockProducer>)>>,
+}
+
+impl MockProducerCreator {
+    fn created(&self) -> Vec<(String, ConnectionConfig, Arc<MockProducer>)> {
+        self.created.lock().unwrap().clone()
+    }
+}
+
+impl ProducerCreator for MockProducerCreator {
+    fn create(
+        &self,
+        bootstrap_servers: &str,
+        config: &ConnectionConfig,
+    ) -> Result<Arc<dyn KafkaProducer>, Error> {
+        let producer = Arc::new(MockProducer::default());
+        self.created.lock().unwrap().push((
+            bootstrap_servers.to_owned(),
+            config.clone(),
+            producer.clone(),
+        ));
+        Ok(producer)
+    }
+}
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    variables: VariablesFactor,
+    networking: OutboundNetworkingFactor,
+    kafka: KafkaFactor,
+}
+
+fn factors(creator: Arc<MockProducerCreator>) -> TestFactors {
+    TestFactors {
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        kafka: KafkaFactor::new(creator),
+    }
+}
+
+fn test_env(creator: Arc<MockProducerCreator>) -> TestEnvironment<TestFactors> {
+    TestEnvironment::new(factors(creator)).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["kafka://*.kafka.test:9092"]
+    })
+}
+
+#[tokio::test]
+async fn disallowed_host_fails() -> anyhow::Result<()> {
+    let creator = Arc::new(MockProducerCreator::default());
+    let mut state = test_env(creator.clone()).build_instance_state().await?;
+
+    // Every bootstrap server must be allowed
+    let res = state
+        .kafka
+        .open("a.kafka.test:9092,kafka.other.test:9092".to_string())
+        .await;
+    let Err(err) = res else {
+        bail!("expected Err, got Ok");
+    };
+    assert!(matches!(err, Error::ConnectionFailed(_)));
+    assert!(creator.created().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_bootstrap_servers_fail() -> anyhow::Result<()> {
+    let mut state = test_env(Default::default()).build_instance_state().await?;
+
+    let res = state.kafka.open("a.kafka.test".to_string()).await;
+    let Err(err) = res else {
+        bail!("expected Err, got Ok");
+    };
+    assert!(matches!(err, Error::InvalidAddress));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn exercise_publish() -> anyhow::Result<()> {
+    let creator = Arc::new(MockProducerCreator::default());
+    let mut state = test_env(creator.clone()).build_instance_state().await?;
+
+    let producer = state.kafka.open("a.kafka.test:9092".to_string()).await?;
+    state
+        .kafka
+        .publish(
+            producer,
+            "orders".to_string(),
+            Some(b"order-1".to_vec()),
+            b"test message".to_vec(),
+            vec![Header {
+                key: "source".to_string(),
+                value: b"spin".to_vec(),
+            }],
+        )
+        .await?;
+
+    let created = creator.created();
+    assert_eq!(created.len(), 1);
+    assert_eq!(
+        *created[0].2.records.lock().unwrap(),
+        [Record {
+            topic: "orders".to_string(),
+            key: Some(b"order-1".to_vec()),
+            value: b"test message".to_vec(),
+            headers: vec![("source".to_string(), b"spin".to_vec())],
+        }]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn producers_are_pooled_per_bootstrap_servers() -> anyhow::Result<()> {
+    let creator = Arc::new(MockProducerCreator::default());
+    let (factors, configured_app) = test_env(creator.clone()).build_configured_app().await?;
+
+    // Producers are shared between instances, and between orderings of the
+    // same servers.
+    for servers in [
+        "a.kafka.test:9092,b.kafka.test:9092",
+        "b.kafka.test:9092, a.kafka.test:9092",
+        "c.kafka.test:9092",
+    ] {
+        let builders = factors.prepare(&configured_app, "test-component")?;
+        let mut state = factors.build_instance_state(builders)?;
+        state.kafka.open(servers.to_string()).await?;
+    }
+
+    let servers: Vec<_> = creator.created().into_iter().map(|(s, ..)| s).collect();
+    assert_eq!(
+        servers,
+        ["a.kafka.test:9092,b.kafka.test:9092", "c.kafka.test:9092"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cluster_config_is_used_for_its_servers() -> anyhow::Result<()> {
+    let creator = Arc::new(MockProducerCreator::default());
+    let sasl_ssl = ConnectionConfig {
+        security_protocol: SecurityProtocol::SaslSsl,
+        sasl: Some(SaslConfig {
+            mechanism: SaslMechanism::Plain,
+            username: "spin".to_string(),
+            password: "secret".to_string(),
+        }),
+        ca_cert_path: None,
+    };
+    let mut state = test_env(creator.clone())
+        .runtime_config(TestFactorsRuntimeConfig {
+            kafka: Some(RuntimeConfig {
+                clusters: [("a.kafka.test:9092".to_string(), sasl_ssl.clone())].into(),
+            }),
+            ..Default::default()
+        })?
+        .build_instance_state()
+        .await?;
+
+    state.kafka.open("a.kafka.test:9092".to_string()).await?;
+    state.kafka.open("b.kafka.test:9092".to_string()).await?;
+
+    let configs: Vec<_> = creator.created().into_iter().map(|(_, c, _)| c).collect();
+    assert_eq!(configs, [sasl_ssl, ConnectionConfig::default()]);
+
+    Ok(())
+}
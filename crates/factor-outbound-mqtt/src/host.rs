@@ -1,29 +1,60 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::Duration,
+};
 
 use anyhow::Result;
 use spin_core::{async_trait, wasmtime::component::Resource};
-use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
+use spin_factor_outbound_networking::config::allowed_hosts::{OutboundAllowedHosts, QosLevel};
+use spin_world::spin::mqtt::subscribe::{self, Message};
 use spin_world::v2::mqtt::{self as v2, Connection, Error, Qos};
 use tracing::{instrument, Level};
 
-use crate::ClientCreator;
+use crate::subscription::{validate_topic_filter, SubscriberConnection, Subscription};
+use crate::{ClientCreator, LastWillConfig, MessageRouter};
 
 pub struct InstanceState {
     allowed_hosts: OutboundAllowedHosts,
-    connections: spin_resource_table::Table<Arc<dyn MqttClient>>,
+    /// Each connection's client, and the highest QoS it may publish at.
+    connections: spin_resource_table::Table<(Arc<dyn MqttClient>, Option<QosLevel>)>,
     create_client: Arc<dyn ClientCreator>,
+    last_will: Option<Arc<LastWillConfig>>,
+    /// Subscriber connections, shared by the instance's subscriptions which
+    /// use the same address and connection options.
+    subscriber_connections: HashMap<SubscriberOptions, Weak<SubscriberConnection>>,
+    subscriptions: spin_resource_table::Table<Subscription>,
+    subscription_buffer_size: usize,
 }
 
 impl InstanceState {
-    pub fn new(allowed_hosts: OutboundAllowedHosts, create_client: Arc<dyn ClientCreator>) -> Self {
+    pub fn new(
+        allowed_hosts: OutboundAllowedHosts,
+        create_client: Arc<dyn ClientCreator>,
+        last_will: Option<Arc<LastWillConfig>>,
+        subscription_buffer_size: usize,
+    ) -> Self {
         Self {
             allowed_hosts,
             create_client,
+            last_will,
             connections: spin_resource_table::Table::new(1024),
+            subscriber_connections: HashMap::new(),
+            subscriptions: spin_resource_table::Table::new(1024),
+            subscription_buffer_size,
         }
     }
 }
 
+/// The address and connection options of a subscriber connection.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct SubscriberOptions {
+    address: String,
+    username: String,
+    password: String,
+    keep_alive_interval: Duration,
+}
+
 #[async_trait]
 pub trait MqttClient: Send + Sync {
     async fn publish_bytes(&self, topic: String, qos: Qos, payload: Vec<u8>) -> Result<(), Error>;
@@ -34,6 +65,15 @@ impl InstanceState {
         self.allowed_hosts.check_url(address, "mqtt").await
     }
 
+    /// Returns the highest QoS the allowed hosts permit for `address`, if
+    /// limited.
+    async fn max_qos(&self, address: &str) -> Result<Option<QosLevel>, Error> {
+        self.allowed_hosts
+            .max_qos(address)
+            .await
+            .map_err(other_error)
+    }
+
     async fn establish_connection(
         &mut self,
         address: String,
@@ -41,19 +81,71 @@ impl InstanceState {
         password: String,
         keep_alive_interval: Duration,
     ) -> Result<Resource<Connection>, Error> {
+        let max_qos = self.max_qos(&address).await?;
+        let client = (self.create_client).create(
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            self.last_will.as_deref(),
+        )?;
         self.connections
-            .push((self.create_client).create(address, username, password, keep_alive_interval)?)
+            .push((client, max_qos))
             .map(Resource::new_own)
             .map_err(|_| Error::TooManyConnections)
     }
 
-    async fn get_conn(&self, connection: Resource<Connection>) -> Result<&dyn MqttClient, Error> {
+    /// Returns the instance's subscriber connection with the given options,
+    /// creating it if there isn't one.
+    fn subscriber_connection(
+        &mut self,
+        options: SubscriberOptions,
+    ) -> Result<Arc<SubscriberConnection>, Error> {
+        self.subscriber_connections
+            .retain(|_, connection| connection.strong_count() > 0);
+        if let Some(connection) = self
+            .subscriber_connections
+            .get(&options)
+            .and_then(Weak::upgrade)
+        {
+            return Ok(connection);
+        }
+        let router = Arc::new(MessageRouter::default());
+        let subscriber = self.create_client.create_subscriber(
+            options.address.clone(),
+            options.username.clone(),
+            options.password.clone(),
+            options.keep_alive_interval,
+            self.last_will.as_deref(),
+            router.clone(),
+        )?;
+        let connection = Arc::new(SubscriberConnection { subscriber, router });
+        self.subscriber_connections
+            .insert(options, Arc::downgrade(&connection));
+        Ok(connection)
+    }
+
+    fn get_subscription(
+        &self,
+        subscription: &Resource<subscribe::Subscription>,
+    ) -> Result<&Subscription, Error> {
+        self.subscriptions
+            .get(subscription.rep())
+            .ok_or(Error::Other(
+                "could not find subscription for resource".into(),
+            ))
+    }
+
+    async fn get_conn(
+        &self,
+        connection: Resource<Connection>,
+    ) -> Result<(&dyn MqttClient, Option<QosLevel>), Error> {
         self.connections
             .get(connection.rep())
             .ok_or(Error::Other(
                 "could not find connection for resource".into(),
             ))
-            .map(|c| c.as_ref())
+            .map(|(client, max_qos)| (client.as_ref(), *max_qos))
     }
 }
 
@@ -105,7 +197,8 @@ impl v2::HostConnection for InstanceState {
         payload: Vec<u8>,
         qos: Qos,
     ) -> Result<(), Error> {
-        let conn = self.get_conn(connection).await.map_err(other_error)?;
+        let (conn, max_qos) = self.get_conn(connection).await.map_err(other_error)?;
+        check_qos(qos, max_qos)?;
 
         conn.publish_bytes(topic, qos, payload).await?;
 
@@ -118,6 +211,94 @@ impl v2::HostConnection for InstanceState {
     }
 }
 
+impl subscribe::Host for InstanceState {
+    #[instrument(name = "spin_outbound_mqtt.subscribe", skip(self, password, qos), err(level = Level::INFO),
+        fields(otel.kind = "consumer", otel.name = format!("{} subscribe", topic_filter), messaging.operation = "subscribe",
+        messaging.system = "mqtt"))]
+    async fn subscribe(
+        &mut self,
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: u64,
+        topic_filter: String,
+        qos: Qos,
+    ) -> Result<Resource<subscribe::Subscription>, Error> {
+        if !self
+            .is_address_allowed(&address)
+            .await
+            .map_err(|e| v2::Error::Other(e.to_string()))?
+        {
+            return Err(v2::Error::ConnectionFailed(format!(
+                "address {address} is not permitted"
+            )));
+        }
+        validate_topic_filter(&topic_filter)?;
+        check_qos(qos, self.max_qos(&address).await?)?;
+        let connection = self.subscriber_connection(SubscriberOptions {
+            address,
+            username,
+            password,
+            keep_alive_interval: Duration::from_secs(keep_alive_interval),
+        })?;
+        let subscription =
+            Subscription::new(connection, topic_filter, qos, self.subscription_buffer_size).await?;
+        self.subscriptions
+            .push(subscription)
+            .map(Resource::new_own)
+            .map_err(|_| Error::TooManyConnections)
+    }
+}
+
+impl subscribe::HostSubscription for InstanceState {
+    #[instrument(name = "spin_outbound_mqtt.next_message", skip(self, subscription), err(level = Level::INFO),
+        fields(otel.kind = "consumer", messaging.operation = "receive", messaging.system = "mqtt"))]
+    async fn next_message(
+        &mut self,
+        subscription: Resource<subscribe::Subscription>,
+        timeout_ms: u64,
+    ) -> Result<Option<Message>, Error> {
+        self.get_subscription(&subscription)?
+            .next_message(Duration::from_millis(timeout_ms))
+            .await
+    }
+
+    async fn dropped_messages(
+        &mut self,
+        subscription: Resource<subscribe::Subscription>,
+    ) -> Result<u64> {
+        Ok(self.get_subscription(&subscription)?.dropped_messages())
+    }
+
+    async fn drop(
+        &mut self,
+        subscription: Resource<subscribe::Subscription>,
+    ) -> anyhow::Result<()> {
+        if let Some(subscription) = self.subscriptions.remove(subscription.rep()) {
+            if let Err(err) = subscription.unsubscribe().await {
+                tracing::warn!("failed to unsubscribe from MQTT topic filter: {err:?}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Denies using `qos` with a broker whose allowed_outbound_hosts entry limits
+/// it to `max_qos`.
+fn check_qos(qos: Qos, max_qos: Option<QosLevel>) -> Result<(), Error> {
+    let qos = match qos {
+        Qos::AtMostOnce => QosLevel::AtMostOnce,
+        Qos::AtLeastOnce => QosLevel::AtLeastOnce,
+        Qos::ExactlyOnce => QosLevel::ExactlyOnce,
+    };
+    match max_qos {
+        Some(max_qos) if qos > max_qos => Err(Error::Other(format!(
+            "QoS {qos} is not permitted for this broker: its allowed_outbound_hosts entry sets max_qos={max_qos}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
 pub fn other_error(e: impl std::fmt::Display) -> Error {
     Error::Other(e.to_string())
 }
@@ -1,5 +1,8 @@
 mod host;
+pub mod runtime_config;
+mod subscription;
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -15,32 +18,49 @@ use spin_world::v2::mqtt::{self as v2, Error, Qos};
 use tokio::sync::Mutex;
 
 pub use host::MqttClient;
+pub use runtime_config::{LastWillConfig, RuntimeConfig};
+pub use spin_factor_outbound_networking::config::allowed_hosts::QosLevel;
+pub use subscription::{MessageRouter, MqttSubscriber, DEFAULT_SUBSCRIPTION_BUFFER_SIZE};
 
 pub struct OutboundMqttFactor {
     create_client: Arc<dyn ClientCreator>,
+    subscription_buffer_size: usize,
 }
 
 impl OutboundMqttFactor {
     pub fn new(create_client: Arc<dyn ClientCreator>) -> Self {
-        Self { create_client }
+        Self {
+            create_client,
+            subscription_buffer_size: DEFAULT_SUBSCRIPTION_BUFFER_SIZE,
+        }
+    }
+
+    /// Sets the number of received messages buffered for each subscription.
+    /// When the buffer is full, the oldest message is dropped.
+    pub fn set_subscription_buffer_size(&mut self, size: usize) {
+        self.subscription_buffer_size = size;
     }
 }
 
 impl Factor for OutboundMqttFactor {
-    type RuntimeConfig = ();
-    type AppState = ();
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
     type InstanceBuilder = InstanceState;
 
     fn init(&mut self, ctx: &mut impl spin_factors::InitContext<Self>) -> anyhow::Result<()> {
         ctx.link_bindings(spin_world::v2::mqtt::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(spin_world::spin::mqtt::subscribe::add_to_linker::<_, FactorData<Self>>)?;
         Ok(())
     }
 
     fn configure_app<T: RuntimeFactors>(
         &self,
-        _ctx: ConfigureAppContext<T, Self>,
+        mut ctx: ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        Ok(())
+        let runtime_config = ctx.take_runtime_config().unwrap_or_default();
+        Ok(AppState {
+            last_will: runtime_config.last_will.map(Arc::new),
+        })
     }
 
     fn prepare<T: RuntimeFactors>(
@@ -53,10 +73,17 @@ impl Factor for OutboundMqttFactor {
         Ok(InstanceState::new(
             allowed_hosts,
             self.create_client.clone(),
+            ctx.app_state().last_will.clone(),
+            self.subscription_buffer_size,
         ))
     }
 }
 
+pub struct AppState {
+    /// The last-will message registered for each connection, if configured.
+    last_will: Option<Arc<LastWillConfig>>,
+}
+
 impl SelfInstanceBuilder for InstanceState {}
 
 // This is a concrete implementation of the MQTT client using rumqttc.
@@ -68,31 +95,22 @@ pub struct NetworkedMqttClient {
 const MQTT_CHANNEL_CAP: usize = 1000;
 
 impl NetworkedMqttClient {
-    /// Create a [`ClientCreator`] that creates a [`NetworkedMqttClient`].
+    /// Create a [`ClientCreator`] that creates a [`NetworkedMqttClient`], and
+    /// a [`NetworkedMqttSubscriber`] for subscriptions.
     pub fn creator() -> Arc<dyn ClientCreator> {
-        Arc::new(|address, username, password, keep_alive_interval| {
-            Ok(Arc::new(NetworkedMqttClient::create(
-                address,
-                username,
-                password,
-                keep_alive_interval,
-            )?) as _)
-        })
+        Arc::new(NetworkedClientCreator)
     }
 
-    /// Create a new [`NetworkedMqttClient`] with the given address, username, password, and keep alive interval.
+    /// Create a new [`NetworkedMqttClient`] with the given address, username, password, and keep alive interval,
+    /// registering `last_will` with the broker if given.
     pub fn create(
         address: String,
         username: String,
         password: String,
         keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
     ) -> Result<Self, Error> {
-        let mut conn_opts = rumqttc::MqttOptions::parse_url(address).map_err(|e| {
-            tracing::error!("MQTT URL parse error: {e:?}");
-            Error::InvalidAddress
-        })?;
-        conn_opts.set_credentials(username, password);
-        conn_opts.set_keep_alive(keep_alive_interval);
+        let conn_opts = mqtt_options(address, username, password, keep_alive_interval, last_will)?;
         let (client, event_loop) = AsyncClient::new(conn_opts, MQTT_CHANNEL_CAP);
         Ok(Self {
             inner: client,
@@ -104,11 +122,7 @@ impl NetworkedMqttClient {
 #[async_trait]
 impl MqttClient for NetworkedMqttClient {
     async fn publish_bytes(&self, topic: String, qos: Qos, payload: Vec<u8>) -> Result<(), Error> {
-        let qos = match qos {
-            Qos::AtMostOnce => rumqttc::QoS::AtMostOnce,
-            Qos::AtLeastOnce => rumqttc::QoS::AtLeastOnce,
-            Qos::ExactlyOnce => rumqttc::QoS::ExactlyOnce,
-        };
+        let qos = to_rumqttc_qos(qos);
         // Message published to EventLoop (not MQTT Broker)
         self.inner
             .publish_bytes(topic, qos, false, payload.into())
@@ -136,21 +150,270 @@ impl MqttClient for NetworkedMqttClient {
     }
 }
 
+struct NetworkedClientCreator;
+
+impl ClientCreator for NetworkedClientCreator {
+    fn create(
+        &self,
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
+    ) -> Result<Arc<dyn MqttClient>, Error> {
+        Ok(Arc::new(NetworkedMqttClient::create(
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            last_will,
+        )?))
+    }
+
+    fn create_subscriber(
+        &self,
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
+        router: Arc<MessageRouter>,
+    ) -> Result<Arc<dyn MqttSubscriber>, Error> {
+        Ok(Arc::new(NetworkedMqttSubscriber::create(
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            last_will,
+            router,
+        )?))
+    }
+}
+
+/// Builds the options for a connection to the broker at `address`.
+fn mqtt_options(
+    address: String,
+    username: String,
+    password: String,
+    keep_alive_interval: Duration,
+    last_will: Option<&LastWillConfig>,
+) -> Result<rumqttc::MqttOptions, Error> {
+    let mut conn_opts = rumqttc::MqttOptions::parse_url(address).map_err(|e| {
+        tracing::error!("MQTT URL parse error: {e:?}");
+        Error::InvalidAddress
+    })?;
+    conn_opts.set_credentials(username, password);
+    conn_opts.set_keep_alive(keep_alive_interval);
+    if let Some(last_will) = last_will {
+        conn_opts.set_last_will(rumqttc::LastWill::new(
+            &last_will.topic,
+            last_will.payload.clone(),
+            qos_level_to_rumqttc_qos(last_will.qos),
+            last_will.retain,
+        ));
+    }
+    Ok(conn_opts)
+}
+
+/// The number of times in a row a subscriber tries to reconnect to the broker
+/// before giving up.
+const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+/// The delay before a subscriber first tries to reconnect, which doubles with
+/// each failed attempt.
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// An MQTT subscriber connection using rumqttc.
+///
+/// The connection's event loop runs in a background task, delivering received
+/// messages to the [`MessageRouter`], until the subscriber is dropped.
+///
+/// If the connection is lost, the subscriber reconnects with exponential
+/// backoff and subscribes to its topic filters again. If the broker refuses
+/// the connection, or the subscriber can't reconnect after
+/// [`MAX_RECONNECT_ATTEMPTS`] attempts, the failure is reported to the router.
+pub struct NetworkedMqttSubscriber {
+    inner: rumqttc::AsyncClient,
+    /// The topic filters subscribed to, to subscribe to again on reconnecting.
+    topic_filters: Arc<std::sync::Mutex<HashMap<String, QoS>>>,
+    event_loop_task: tokio::task::JoinHandle<()>,
+}
+
+impl NetworkedMqttSubscriber {
+    /// Create a new [`NetworkedMqttSubscriber`] with the given address, username, password, and keep alive interval,
+    /// registering `last_will` with the broker if given.
+    pub fn create(
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
+        router: Arc<MessageRouter>,
+    ) -> Result<Self, Error> {
+        let conn_opts = mqtt_options(address, username, password, keep_alive_interval, last_will)?;
+        let (client, event_loop) = AsyncClient::new(conn_opts, MQTT_CHANNEL_CAP);
+        let topic_filters = Arc::<std::sync::Mutex<HashMap<_, _>>>::default();
+        let event_loop_task = tokio::spawn(Self::run_event_loop(
+            event_loop,
+            client.clone(),
+            topic_filters.clone(),
+            router,
+        ));
+        Ok(Self {
+            inner: client,
+            topic_filters,
+            event_loop_task,
+        })
+    }
+
+    async fn run_event_loop(
+        mut event_loop: rumqttc::EventLoop,
+        client: AsyncClient,
+        topic_filters: Arc<std::sync::Mutex<HashMap<String, QoS>>>,
+        router: Arc<MessageRouter>,
+    ) {
+        let mut connected_before = false;
+        let mut failed_attempts = 0;
+        loop {
+            match event_loop.poll().await {
+                Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                    router.deliver(&publish.topic, &publish.payload)
+                }
+                Ok(Event::Incoming(Incoming::ConnAck(conn_ack))) => {
+                    failed_attempts = 0;
+                    if connected_before && !conn_ack.session_present {
+                        tracing::info!("MQTT subscriber reconnected; subscribing again");
+                        // The event loop is polled by this task, so queue the
+                        // requests without waiting for room in the channel
+                        for (topic_filter, qos) in topic_filters.lock().unwrap().iter() {
+                            if let Err(err) = client.try_subscribe(topic_filter, *qos) {
+                                tracing::error!(
+                                    "failed to subscribe again to MQTT topic filter {topic_filter:?}: {err}"
+                                );
+                            }
+                        }
+                    }
+                    connected_before = true;
+                }
+                Ok(_) => {}
+                Err(err @ rumqttc::ConnectionError::ConnectionRefused(_)) => {
+                    tracing::error!("MQTT subscriber connection refused: {err}");
+                    router.fail(Error::ConnectionFailed(err.to_string()));
+                    break;
+                }
+                Err(err) if failed_attempts < MAX_RECONNECT_ATTEMPTS => {
+                    let delay = INITIAL_RECONNECT_DELAY * 2u32.pow(failed_attempts);
+                    failed_attempts += 1;
+                    tracing::warn!(
+                        "MQTT subscriber connection failed: {err}; reconnecting in {delay:?}"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "MQTT subscriber connection failed: {err}; giving up after {failed_attempts} attempts to reconnect"
+                    );
+                    router.fail(Error::ConnectionFailed(err.to_string()));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for NetworkedMqttSubscriber {
+    fn drop(&mut self) {
+        self.event_loop_task.abort();
+    }
+}
+
+#[async_trait]
+impl MqttSubscriber for NetworkedMqttSubscriber {
+    async fn subscribe(&self, topic_filter: String, qos: Qos) -> Result<(), Error> {
+        let qos = to_rumqttc_qos(qos);
+        self.topic_filters
+            .lock()
+            .unwrap()
+            .insert(topic_filter.clone(), qos);
+        self.inner
+            .subscribe(topic_filter, qos)
+            .await
+            .map_err(other_error)
+    }
+
+    async fn unsubscribe(&self, topic_filter: String) -> Result<(), Error> {
+        self.topic_filters.lock().unwrap().remove(&topic_filter);
+        self.inner
+            .unsubscribe(topic_filter)
+            .await
+            .map_err(other_error)
+    }
+}
+
+fn to_rumqttc_qos(qos: Qos) -> QoS {
+    match qos {
+        Qos::AtMostOnce => QoS::AtMostOnce,
+        Qos::AtLeastOnce => QoS::AtLeastOnce,
+        Qos::ExactlyOnce => QoS::ExactlyOnce,
+    }
+}
+
+fn qos_level_to_rumqttc_qos(qos: QosLevel) -> QoS {
+    match qos {
+        QosLevel::AtMostOnce => QoS::AtMostOnce,
+        QosLevel::AtLeastOnce => QoS::AtLeastOnce,
+        QosLevel::ExactlyOnce => QoS::ExactlyOnce,
+    }
+}
+
 /// A trait for creating MQTT client.
 #[async_trait]
 pub trait ClientCreator: Send + Sync {
+    /// Creates a connection to the broker at `address`, which must register
+    /// `last_will` with the broker if given.
     fn create(
         &self,
         address: String,
         username: String,
         password: String,
         keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
     ) -> Result<Arc<dyn MqttClient>, Error>;
+
+    /// Creates a connection for receiving messages from the broker at
+    /// `address`, which must register `last_will` with the broker if given.
+    /// Messages received on the connection must be delivered to `router`.
+    fn create_subscriber(
+        &self,
+        address: String,
+        username: String,
+        password: String,
+        keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
+        router: Arc<MessageRouter>,
+    ) -> Result<Arc<dyn MqttSubscriber>, Error> {
+        _ = (
+            address,
+            username,
+            password,
+            keep_alive_interval,
+            last_will,
+            router,
+        );
+        Err(Error::Other("MQTT subscriptions are not supported".into()))
+    }
 }
 
 impl<F> ClientCreator for F
 where
-    F: Fn(String, String, String, Duration) -> Result<Arc<dyn MqttClient>, Error> + Send + Sync,
+    F: Fn(
+            String,
+            String,
+            String,
+            Duration,
+            Option<&LastWillConfig>,
+        ) -> Result<Arc<dyn MqttClient>, Error>
+        + Send
+        + Sync,
 {
     fn create(
         &self,
@@ -158,7 +421,8 @@ where
         username: String,
         password: String,
         keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
     ) -> Result<Arc<dyn MqttClient>, Error> {
-        self(address, username, password, keep_alive_interval)
+        self(address, username, password, keep_alive_interval, last_will)
     }
 }
@@ -0,0 +1,23 @@
+pub mod spin;
+
+use spin_factor_outbound_networking::config::allowed_hosts::QosLevel;
+
+/// Runtime configuration for outbound MQTT.
+#[derive(Debug, Default)]
+pub struct RuntimeConfig {
+    /// The message the broker publishes on a connection's behalf if the
+    /// connection is lost without being closed.
+    pub last_will: Option<LastWillConfig>,
+}
+
+/// An MQTT last-will message, registered with the broker when a connection
+/// is established.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastWillConfig {
+    /// The topic the message is published to.
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: QosLevel,
+    /// Whether the broker retains the message for future subscribers.
+    pub retain: bool,
+}
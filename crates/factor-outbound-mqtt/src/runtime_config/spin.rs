@@ -0,0 +1,132 @@
+use anyhow::Context as _;
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+use super::{LastWillConfig, RuntimeConfig};
+use crate::QosLevel;
+
+/// Get the runtime configuration for outbound MQTT from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [outbound_mqtt.last_will]
+/// topic = "devices/sensor-1/status"
+/// payload = "offline"
+/// qos = 1
+/// retain = true
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(outbound_mqtt) = table.get("outbound_mqtt") else {
+        return Ok(None);
+    };
+    let outbound_mqtt: OutboundMqttToml = outbound_mqtt.clone().try_into()?;
+
+    let last_will = outbound_mqtt
+        .last_will
+        .map(|last_will| last_will.into_config())
+        .transpose()
+        .context("invalid MQTT `last_will` config")?;
+    Ok(Some(RuntimeConfig { last_will }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutboundMqttToml {
+    #[serde(default)]
+    last_will: Option<LastWillToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LastWillToml {
+    topic: String,
+    #[serde(default)]
+    payload: String,
+    #[serde(default)]
+    qos: u8,
+    #[serde(default)]
+    retain: bool,
+}
+
+impl LastWillToml {
+    fn into_config(self) -> anyhow::Result<LastWillConfig> {
+        if self.topic.is_empty() {
+            anyhow::bail!("`topic` must not be empty");
+        }
+        // Messages are published to topics, not topic filters
+        if self.topic.contains(['+', '#', '\0']) {
+            anyhow::bail!(
+                "`topic` {:?} must not contain wildcards or null characters",
+                self.topic
+            );
+        }
+        let qos = self
+            .qos
+            .to_string()
+            .parse::<QosLevel>()
+            .context("invalid `qos`")?;
+        Ok(LastWillConfig {
+            topic: self.topic,
+            payload: self.payload.into_bytes(),
+            qos,
+            retain: self.retain,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> anyhow::Result<Option<RuntimeConfig>> {
+        config_from_table(&toml::from_str::<toml::Table>(toml)?)
+    }
+
+    #[test]
+    fn last_will_is_read_from_table() -> anyhow::Result<()> {
+        let config = config(
+            r#"
+            [outbound_mqtt.last_will]
+            topic = "devices/sensor-1/status"
+            payload = "offline"
+            qos = 1
+            retain = true
+            "#,
+        )?
+        .unwrap();
+        assert_eq!(
+            config.last_will,
+            Some(LastWillConfig {
+                topic: "devices/sensor-1/status".to_string(),
+                payload: b"offline".to_vec(),
+                qos: QosLevel::AtLeastOnce,
+                retain: true,
+            })
+        );
+
+        let config = config("[outbound_mqtt.last_will]\ntopic = 'status'")?.unwrap();
+        let last_will = config.last_will.unwrap();
+        assert_eq!(last_will.payload, b"");
+        assert_eq!(last_will.qos, QosLevel::AtMostOnce);
+        assert!(!last_will.retain);
+
+        assert!(config("[outbound_mqtt]")?.unwrap().last_will.is_none());
+        assert!(config("")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_last_will_is_rejected() {
+        for invalid in [
+            "topic = ''",
+            "topic = 'devices/+/status'",
+            "topic = 'devices/#'",
+            "topic = 'status'\nqos = 3",
+            "payload = 'offline'",
+            "topic = 'status'\nretained = true",
+        ] {
+            let toml = format!("[outbound_mqtt.last_will]\n{invalid}");
+            assert!(config(&toml).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use spin_core::async_trait;
+use spin_world::{
+    spin::mqtt::subscribe::Message,
+    v2::mqtt::{Error, Qos},
+};
+use tokio::sync::Notify;
+
+/// The default number of messages buffered for each subscription.
+pub const DEFAULT_SUBSCRIPTION_BUFFER_SIZE: usize = 100;
+
+/// A connection to an MQTT broker for receiving messages.
+///
+/// Messages received on the connection must be passed to the connection's
+/// [`MessageRouter`], which delivers them to the matching subscriptions.
+#[async_trait]
+pub trait MqttSubscriber: Send + Sync {
+    /// Asks the broker to send messages published to topics matching `topic_filter`.
+    async fn subscribe(&self, topic_filter: String, qos: Qos) -> Result<(), Error>;
+
+    /// Asks the broker to stop sending messages for `topic_filter`.
+    async fn unsubscribe(&self, topic_filter: String) -> Result<(), Error>;
+}
+
+/// Delivers the messages received on a subscriber connection to the
+/// subscriptions with matching topic filters.
+#[derive(Default)]
+pub struct MessageRouter {
+    routes: Mutex<Vec<Route>>,
+}
+
+struct Route {
+    topic_filter: String,
+    buffer: Weak<SubscriptionBuffer>,
+}
+
+impl MessageRouter {
+    /// Delivers a message received from the broker.
+    pub fn deliver(&self, topic: &str, payload: &[u8]) {
+        let mut routes = self.routes.lock().unwrap();
+        routes.retain(|route| route.buffer.strong_count() > 0);
+        for route in routes.iter() {
+            if !topic_matches(&route.topic_filter, topic) {
+                continue;
+            }
+            if let Some(buffer) = route.buffer.upgrade() {
+                buffer.push(Message {
+                    topic: topic.to_owned(),
+                    payload: payload.to_vec(),
+                });
+            }
+        }
+    }
+
+    /// Reports that the connection to the broker failed. Subscriptions return
+    /// the error once they have no more buffered messages.
+    pub fn fail(&self, error: Error) {
+        for route in self.routes.lock().unwrap().iter() {
+            if let Some(buffer) = route.buffer.upgrade() {
+                buffer.fail(error.clone());
+            }
+        }
+    }
+
+    fn add(&self, topic_filter: &str, buffer: &Arc<SubscriptionBuffer>) {
+        self.routes.lock().unwrap().push(Route {
+            topic_filter: topic_filter.to_owned(),
+            buffer: Arc::downgrade(buffer),
+        });
+    }
+
+    /// Whether any live subscription other than `buffer` uses `topic_filter`.
+    fn is_shared(&self, topic_filter: &str, buffer: &Arc<SubscriptionBuffer>) -> bool {
+        self.routes.lock().unwrap().iter().any(|route| {
+            route.topic_filter == topic_filter
+                && route.buffer.strong_count() > 0
+                && !Weak::ptr_eq(&route.buffer, &Arc::downgrade(buffer))
+        })
+    }
+}
+
+/// A connection to a broker, shared by an instance's subscriptions to the
+/// same address.
+pub(crate) struct SubscriberConnection {
+    pub(crate) subscriber: Arc<dyn MqttSubscriber>,
+    pub(crate) router: Arc<MessageRouter>,
+}
+
+/// A component's subscription to a topic filter.
+pub(crate) struct Subscription {
+    topic_filter: String,
+    buffer: Arc<SubscriptionBuffer>,
+    connection: Arc<SubscriberConnection>,
+}
+
+impl Subscription {
+    pub(crate) async fn new(
+        connection: Arc<SubscriberConnection>,
+        topic_filter: String,
+        qos: Qos,
+        buffer_size: usize,
+    ) -> Result<Self, Error> {
+        let buffer = Arc::new(SubscriptionBuffer::new(buffer_size));
+        // Route messages before subscribing so that none are missed.
+        connection.router.add(&topic_filter, &buffer);
+        connection
+            .subscriber
+            .subscribe(topic_filter.clone(), qos)
+            .await?;
+        Ok(Self {
+            topic_filter,
+            buffer,
+            connection,
+        })
+    }
+
+    pub(crate) async fn next_message(&self, timeout: Duration) -> Result<Option<Message>, Error> {
+        self.buffer.next(timeout).await
+    }
+
+    pub(crate) fn dropped_messages(&self) -> u64 {
+        self.buffer.dropped()
+    }
+
+    /// Unsubscribes from the topic filter, unless another of the instance's
+    /// subscriptions on the same connection uses it.
+    pub(crate) async fn unsubscribe(self) -> Result<(), Error> {
+        if self
+            .connection
+            .router
+            .is_shared(&self.topic_filter, &self.buffer)
+        {
+            return Ok(());
+        }
+        self.connection
+            .subscriber
+            .unsubscribe(self.topic_filter.clone())
+            .await
+    }
+}
+
+/// The messages received for a subscription which the component has not yet read.
+struct SubscriptionBuffer {
+    capacity: usize,
+    state: Mutex<BufferState>,
+    message_arrived: Notify,
+}
+
+#[derive(Default)]
+struct BufferState {
+    messages: VecDeque<Message>,
+    dropped: u64,
+    error: Option<Error>,
+}
+
+impl SubscriptionBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Default::default(),
+            message_arrived: Notify::new(),
+        }
+    }
+
+    fn push(&self, message: Message) {
+        let mut state = self.state.lock().unwrap();
+        if state.messages.len() >= self.capacity {
+            state.messages.pop_front();
+            state.dropped += 1;
+        }
+        state.messages.push_back(message);
+        drop(state);
+        self.message_arrived.notify_one();
+    }
+
+    fn fail(&self, error: Error) {
+        self.state.lock().unwrap().error = Some(error);
+        self.message_arrived.notify_one();
+    }
+
+    fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+
+    async fn next(&self, timeout: Duration) -> Result<Option<Message>, Error> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(message) = state.messages.pop_front() {
+                    return Ok(Some(message));
+                }
+                if let Some(error) = &state.error {
+                    return Err(error.clone());
+                }
+            }
+            let arrived = self.message_arrived.notified();
+            if tokio::time::timeout_at(deadline, arrived).await.is_err() {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Checks that a topic filter is valid: each level may be `+`, and the last
+/// level may be `#`, but wildcards cannot be combined with other characters
+/// in a level.
+pub(crate) fn validate_topic_filter(topic_filter: &str) -> Result<(), Error> {
+    let invalid = |reason: &str| {
+        Err(Error::Other(format!(
+            "invalid topic filter {topic_filter:?}: {reason}"
+        )))
+    };
+    if topic_filter.is_empty() {
+        return invalid("must not be empty");
+    }
+    if topic_filter.contains('\0') {
+        return invalid("must not contain null characters");
+    }
+    let levels: Vec<_> = topic_filter.split('/').collect();
+    for (index, level) in levels.iter().enumerate() {
+        if level.contains('#') && (*level != "#" || index != levels.len() - 1) {
+            return invalid("'#' must be the whole of the last level");
+        }
+        if level.contains('+') && *level != "+" {
+            return invalid("'+' must be the whole of a level");
+        }
+    }
+    Ok(())
+}
+
+/// Whether a topic matches a (valid) topic filter.
+fn topic_matches(topic_filter: &str, topic: &str) -> bool {
+    // Topics beginning with '$' are reserved for the broker, and are not
+    // matched by filters beginning with a wildcard.
+    if topic.starts_with('$') && topic_filter.starts_with(['+', '#']) {
+        return false;
+    }
+    let mut topic_levels = topic.split('/');
+    for filter_level in topic_filter.split('/') {
+        match (filter_level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (filter_level, Some(topic_level)) if filter_level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topic_filters_are_validated() {
+        for valid in ["a", "a/b", "+", "#", "a/+/c", "a/#", "+/+/#", "/a"] {
+            assert!(validate_topic_filter(valid).is_ok(), "{valid}");
+        }
+        for invalid in ["", "a#", "a/#/c", "a/b+", "+a/b", "#/a", "a\0b"] {
+            assert!(validate_topic_filter(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn topics_match_filters() {
+        assert!(topic_matches("a/b", "a/b"));
+        assert!(!topic_matches("a/b", "a/b/c"));
+        assert!(topic_matches("a/+", "a/b"));
+        assert!(!topic_matches("a/+", "a"));
+        assert!(!topic_matches("a/+", "a/b/c"));
+        assert!(topic_matches("a/#", "a"));
+        assert!(topic_matches("a/#", "a/b/c"));
+        assert!(topic_matches("#", "a/b"));
+        assert!(!topic_matches("#", "$SYS/uptime"));
+        assert!(topic_matches("$SYS/#", "$SYS/uptime"));
+    }
+}
@@ -1,16 +1,75 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{bail, Result};
-use spin_core::async_trait;
-use spin_factor_outbound_mqtt::{ClientCreator, MqttClient, OutboundMqttFactor};
+use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_factor_outbound_mqtt::{
+    ClientCreator, LastWillConfig, MessageRouter, MqttClient, MqttSubscriber, OutboundMqttFactor,
+    QosLevel, RuntimeConfig,
+};
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_variables::VariablesFactor;
 use spin_factors::{anyhow, RuntimeFactors};
 use spin_factors_test::{toml, TestEnvironment};
+use spin_world::spin::mqtt::subscribe::{Host as _, HostSubscription};
 use spin_world::v2::mqtt::{self as v2, Error, HostConnection, Qos};
 
-pub struct MockMqttClient {}
+#[derive(Default)]
+pub struct MockMqttClient {
+    broker: Arc<MockBroker>,
+    /// The last-will message each connection was created with.
+    last_wills: Mutex<Vec<Option<LastWillConfig>>>,
+    /// The username and keep-alive interval each subscriber connection was
+    /// created with.
+    subscriber_options: Mutex<Vec<(String, Duration)>>,
+}
+
+/// An in-process broker which delivers published messages to subscribers.
+#[derive(Default)]
+pub struct MockBroker {
+    subscriptions: Mutex<Vec<(String, Arc<MessageRouter>)>>,
+}
+
+impl MockBroker {
+    fn publish(&self, topic: &str, payload: &[u8]) {
+        // The routers do their own topic matching
+        for (_, router) in self.subscriptions.lock().unwrap().iter() {
+            router.deliver(topic, payload);
+        }
+    }
+
+    fn subscription_count(&self) -> usize {
+        self.subscriptions.lock().unwrap().len()
+    }
+}
+
+struct MockSubscriber {
+    broker: Arc<MockBroker>,
+    router: Arc<MessageRouter>,
+}
+
+#[async_trait]
+impl MqttSubscriber for MockSubscriber {
+    async fn subscribe(&self, topic_filter: String, _qos: Qos) -> Result<(), Error> {
+        self.broker
+            .subscriptions
+            .lock()
+            .unwrap()
+            .push((topic_filter, self.router.clone()));
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, topic_filter: String) -> Result<(), Error> {
+        self.broker
+            .subscriptions
+            .lock()
+            .unwrap()
+            .retain(|(filter, router)| {
+                filter != &topic_filter || !Arc::ptr_eq(router, &self.router)
+            });
+        Ok(())
+    }
+}
 
 #[async_trait]
 impl MqttClient for MockMqttClient {
@@ -31,8 +90,30 @@ impl ClientCreator for MockMqttClient {
         _username: String,
         _password: String,
         _keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
     ) -> Result<Arc<dyn MqttClient>, Error> {
-        Ok(Arc::new(MockMqttClient {}))
+        self.last_wills.lock().unwrap().push(last_will.cloned());
+        Ok(Arc::new(MockMqttClient::default()))
+    }
+
+    fn create_subscriber(
+        &self,
+        _address: String,
+        username: String,
+        _password: String,
+        keep_alive_interval: Duration,
+        last_will: Option<&LastWillConfig>,
+        router: Arc<MessageRouter>,
+    ) -> Result<Arc<dyn MqttSubscriber>, Error> {
+        self.last_wills.lock().unwrap().push(last_will.cloned());
+        self.subscriber_options
+            .lock()
+            .unwrap()
+            .push((username, keep_alive_interval));
+        Ok(Arc::new(MockSubscriber {
+            broker: self.broker.clone(),
+            router,
+        }))
     }
 }
 
@@ -44,10 +125,21 @@ struct TestFactors {
 }
 
 fn factors() -> TestFactors {
+    factors_with_broker(Default::default())
+}
+
+fn factors_with_broker(broker: Arc<MockBroker>) -> TestFactors {
+    factors_with_creator(Arc::new(MockMqttClient {
+        broker,
+        ..Default::default()
+    }))
+}
+
+fn factors_with_creator(creator: Arc<MockMqttClient>) -> TestFactors {
     TestFactors {
         variables: VariablesFactor::default(),
         networking: OutboundNetworkingFactor::new(),
-        mqtt: OutboundMqttFactor::new(Arc::new(MockMqttClient {})),
+        mqtt: OutboundMqttFactor::new(creator),
     }
 }
 
@@ -130,3 +222,306 @@ async fn exercise_publish() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn subscription_receives_matching_messages() -> anyhow::Result<()> {
+    let broker = Arc::new(MockBroker::default());
+    let mut state = TestEnvironment::new(factors_with_broker(broker.clone()))
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["mqtt://*:*"]
+        })
+        .build_instance_state()
+        .await?;
+
+    let subscription = state
+        .mqtt
+        .subscribe(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+            "sensors/+/temperature".to_string(),
+            Qos::AtLeastOnce,
+        )
+        .await?;
+    broker.publish("sensors/kitchen/humidity", b"40");
+    broker.publish("sensors/kitchen/temperature", b"21");
+
+    let message = state
+        .mqtt
+        .next_message(Resource::new_borrow(subscription.rep()), 1000)
+        .await?
+        .expect("expected a message");
+    assert_eq!(message.topic, "sensors/kitchen/temperature");
+    assert_eq!(message.payload, b"21");
+
+    // No more messages arrive before the timeout
+    let message = state
+        .mqtt
+        .next_message(Resource::new_borrow(subscription.rep()), 10)
+        .await?;
+    assert!(message.is_none());
+
+    // Dropping the subscription unsubscribes
+    HostSubscription::drop(&mut state.mqtt, subscription).await?;
+    assert_eq!(broker.subscription_count(), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscription_buffer_drops_oldest_messages() -> anyhow::Result<()> {
+    let broker = Arc::new(MockBroker::default());
+    let mut factors = factors_with_broker(broker.clone());
+    factors.mqtt.set_subscription_buffer_size(2);
+    let mut state = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["mqtt://*:*"]
+        })
+        .build_instance_state()
+        .await?;
+
+    let subscription = state
+        .mqtt
+        .subscribe(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+            "events/#".to_string(),
+            Qos::AtMostOnce,
+        )
+        .await?;
+    for payload in [b"1", b"2", b"3"] {
+        broker.publish("events/test", payload);
+    }
+
+    let dropped = state
+        .mqtt
+        .dropped_messages(Resource::new_borrow(subscription.rep()))
+        .await?;
+    assert_eq!(dropped, 1);
+    for expected in [b"2", b"3"] {
+        let message = state
+            .mqtt
+            .next_message(Resource::new_borrow(subscription.rep()), 1000)
+            .await?
+            .expect("expected a message");
+        assert_eq!(message.payload, expected);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_to_disallowed_host_fails() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+    });
+    let mut state = env.build_instance_state().await?;
+
+    let Err(err) = state
+        .mqtt
+        .subscribe(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+            "events/#".to_string(),
+            Qos::AtMostOnce,
+        )
+        .await
+    else {
+        bail!("expected Err, got Ok");
+    };
+    assert!(matches!(err, v2::Error::ConnectionFailed(_)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_with_invalid_topic_filter_fails() -> anyhow::Result<()> {
+    let mut state = test_env().build_instance_state().await?;
+
+    let Err(err) = state
+        .mqtt
+        .subscribe(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+            "events/#/more".to_string(),
+            Qos::AtMostOnce,
+        )
+        .await
+    else {
+        bail!("expected Err, got Ok");
+    };
+    assert!(matches!(err, v2::Error::Other(_)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn qos_above_max_qos_of_allowed_host_fails() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["mqtt://mqtt.test:1883?max_qos=1"]
+    });
+    let mut state = env.build_instance_state().await?;
+
+    let connection = state
+        .mqtt
+        .open(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+        )
+        .await?;
+    state
+        .mqtt
+        .publish(
+            Resource::new_borrow(connection.rep()),
+            "message".to_string(),
+            b"test message".to_vec(),
+            Qos::AtLeastOnce,
+        )
+        .await?;
+    let Err(err) = state
+        .mqtt
+        .publish(
+            connection,
+            "message".to_string(),
+            b"test message".to_vec(),
+            Qos::ExactlyOnce,
+        )
+        .await
+    else {
+        bail!("expected Err, got Ok");
+    };
+    let v2::Error::Other(message) = err else {
+        bail!("expected Other, got {err:?}");
+    };
+    assert!(message.contains("max_qos=1"), "{message}");
+
+    let Err(err) = state
+        .mqtt
+        .subscribe(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+            "events/#".to_string(),
+            Qos::ExactlyOnce,
+        )
+        .await
+    else {
+        bail!("expected Err, got Ok");
+    };
+    assert!(matches!(err, v2::Error::Other(_)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn last_will_is_registered_when_connecting() -> anyhow::Result<()> {
+    let creator = Arc::new(MockMqttClient::default());
+    let last_will = LastWillConfig {
+        topic: "devices/sensor-1/status".to_string(),
+        payload: b"offline".to_vec(),
+        qos: QosLevel::AtLeastOnce,
+        retain: true,
+    };
+    let mut state = TestEnvironment::new(factors_with_creator(creator.clone()))
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["mqtt://*:*"]
+        })
+        .runtime_config(TestFactorsRuntimeConfig {
+            mqtt: Some(RuntimeConfig {
+                last_will: Some(last_will.clone()),
+            }),
+            ..Default::default()
+        })?
+        .build_instance_state()
+        .await?;
+
+    state
+        .mqtt
+        .open(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+        )
+        .await?;
+    assert_eq!(*creator.last_wills.lock().unwrap(), [Some(last_will)]);
+
+    // Without runtime config, connections have no last will
+    let creator = Arc::new(MockMqttClient::default());
+    let mut state = TestEnvironment::new(factors_with_creator(creator.clone()))
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["mqtt://*:*"]
+        })
+        .build_instance_state()
+        .await?;
+    state
+        .mqtt
+        .open(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+        )
+        .await?;
+    assert_eq!(*creator.last_wills.lock().unwrap(), [None]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscriber_connections_use_the_given_options() -> anyhow::Result<()> {
+    let creator = Arc::new(MockMqttClient::default());
+    let mut state = TestEnvironment::new(factors_with_creator(creator.clone()))
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            allowed_outbound_hosts = ["mqtt://*:*"]
+        })
+        .build_instance_state()
+        .await?;
+
+    for (username, topic_filter) in [("alice", "a/#"), ("alice", "b/#"), ("bob", "a/#")] {
+        state
+            .mqtt
+            .subscribe(
+                "mqtt://mqtt.test:1883".to_string(),
+                username.to_string(),
+                "password".to_string(),
+                30,
+                topic_filter.to_string(),
+                Qos::AtMostOnce,
+            )
+            .await?;
+    }
+
+    // Subscriptions with the same options share a connection
+    assert_eq!(
+        *creator.subscriber_options.lock().unwrap(),
+        [
+            ("alice".to_string(), Duration::from_secs(30)),
+            ("bob".to_string(), Duration::from_secs(30)),
+        ]
+    );
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use mysql_async::consts::ColumnType;
 use mysql_async::prelude::{FromValue, Queryable as _};
 use mysql_async::{from_value_opt, Conn as MysqlClient, Opts, OptsBuilder, SslOpts};
@@ -11,11 +11,64 @@ use spin_world::v2::rdbms_types::{
 };
 use url::Url;
 
+/// Max addresses for which to keep pools in cache.
+const CONNECTION_POOL_CACHE_CAPACITY: u64 = 16;
+
+/// A factory object for MySQL clients. This abstracts
+/// details of client creation such as pooling.
+#[async_trait]
+pub trait ClientFactory: Default + Send + Sync + 'static {
+    /// The type of client produced by `get_client`.
+    type Client: Client;
+    /// Gets a client from the factory.
+    async fn get_client(&self, address: &str) -> Result<Self::Client>;
+}
+
+/// A `ClientFactory` that uses a connection pool per address, shared by all
+/// instances of the app.
+///
+/// The size of each pool may be set with the `pool_min` and `pool_max`
+/// parameters of the address.
+pub struct PooledMysqlClientFactory {
+    pools: moka::sync::Cache<String, mysql_async::Pool>,
+}
+
+impl Default for PooledMysqlClientFactory {
+    fn default() -> Self {
+        Self {
+            pools: moka::sync::Cache::new(CONNECTION_POOL_CACHE_CAPACITY),
+        }
+    }
+}
+
+#[async_trait]
+impl ClientFactory for PooledMysqlClientFactory {
+    type Client = MysqlClient;
+
+    async fn get_client(&self, address: &str) -> Result<Self::Client> {
+        let pool = self
+            .pools
+            .try_get_with_by_ref(address, || create_connection_pool(address))
+            .map_err(|e| anyhow!("{e}"))
+            .context("establishing MySQL connection pool")?;
+
+        // The connection returns to the pool when dropped
+        Ok(pool.get_conn().await?)
+    }
+}
+
+/// Creates a MySQL connection pool for the given address. Connections are
+/// only made as they are needed.
+fn create_connection_pool(address: &str) -> Result<mysql_async::Pool, mysql_async::Error> {
+    tracing::debug!("Build new connection pool: {}", address);
+
+    Ok(mysql_async::Pool::new(build_opts(address)?))
+}
+
 #[async_trait]
 pub trait Client: Send + Sync + 'static {
-    async fn build_client(address: &str) -> Result<Self>
-    where
-        Self: Sized;
+    /// Checks that the connection is still alive.
+    async fn ping(&mut self) -> Result<(), v2::Error>;
 
     async fn execute(
         &mut self,
@@ -28,21 +81,21 @@ pub trait Client: Send + Sync + 'static {
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<RowSet, v2::Error>;
+
+    /// Executes each statement in order, returning the number of rows
+    /// affected by each. Stops at the first statement which fails.
+    async fn execute_batch(
+        &mut self,
+        statements: Vec<(String, Vec<ParameterValue>)>,
+    ) -> Result<Vec<u64>, v2::Error>;
 }
 
 #[async_trait]
 impl Client for MysqlClient {
-    async fn build_client(address: &str) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        tracing::debug!("Build new connection: {}", address);
-
-        let opts = build_opts(address)?;
-
-        let connection_pool = mysql_async::Pool::new(opts);
-
-        connection_pool.get_conn().await.map_err(|e| anyhow!(e))
+    async fn ping(&mut self) -> Result<(), v2::Error> {
+        mysql_async::prelude::Queryable::ping(self)
+            .await
+            .map_err(|e| v2::Error::ConnectionFailed(format!("{e:?}")))
     }
 
     async fn execute(
@@ -86,6 +139,26 @@ impl Client for MysqlClient {
             }
         }
     }
+
+    async fn execute_batch(
+        &mut self,
+        statements: Vec<(String, Vec<ParameterValue>)>,
+    ) -> Result<Vec<u64>, v2::Error> {
+        let mut affected_rows = Vec::with_capacity(statements.len());
+        for (statement, params) in statements {
+            let db_params = params.into_iter().map(to_sql_parameter).collect::<Vec<_>>();
+            // The connection caches prepared statements, so a repeated
+            // statement is only prepared once
+            self.exec_drop(
+                statement.as_str(),
+                mysql_async::Params::Positional(db_params),
+            )
+            .await
+            .map_err(|e| v2::Error::QueryFailed(format!("{e:?}")))?;
+            affected_rows.push(self.affected_rows());
+        }
+        Ok(affected_rows)
+    }
 }
 
 fn to_sql_parameter(value: ParameterValue) -> mysql_async::Value {
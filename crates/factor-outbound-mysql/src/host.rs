@@ -1,5 +1,7 @@
 use anyhow::Result;
 use spin_core::wasmtime::component::Resource;
+use spin_factor_outbound_networking::reconnect::{sql_sets_session_state, ManagedConnection};
+use spin_world::spin::mysql::batch;
 use spin_world::v1::mysql as v1;
 use spin_world::v2::mysql::{self as v2, Connection};
 use spin_world::v2::rdbms_types as v2_types;
@@ -7,25 +9,54 @@ use spin_world::v2::rdbms_types::ParameterValue;
 use tracing::field::Empty;
 use tracing::{instrument, Level};
 
-use crate::client::Client;
+use crate::client::{Client, ClientFactory};
 use crate::InstanceState;
 
-impl<C: Client> InstanceState<C> {
+impl<CF: ClientFactory> InstanceState<CF> {
     async fn open_connection(&mut self, address: &str) -> Result<Resource<Connection>, v2::Error> {
+        let client = self
+            .client_factory
+            .get_client(address)
+            .await
+            .map_err(|e| v2::Error::ConnectionFailed(format!("{e:?}")))?;
         self.connections
-            .push(
-                C::build_client(address)
-                    .await
-                    .map_err(|e| v2::Error::ConnectionFailed(format!("{e:?}")))?,
-            )
+            .push(ManagedConnection::new(address, client))
             .map_err(|_| v2::Error::ConnectionFailed("too many connections".into()))
             .map(Resource::new_own)
     }
 
-    async fn get_client(&mut self, connection: Resource<Connection>) -> Result<&mut C, v2::Error> {
-        self.connections
+    /// The client of the given connection for running `statements`,
+    /// reconnecting first if the connection has been lost, e.g. because the
+    /// server restarted.
+    ///
+    /// Reconnecting fails the statements if the guest may have left state in
+    /// the lost session, such as an open transaction; see
+    /// [`ManagedConnection::note_session_state`].
+    async fn get_client<'s>(
+        &mut self,
+        connection: Resource<Connection>,
+        statements: impl IntoIterator<Item = &'s str>,
+    ) -> Result<&mut CF::Client, v2::Error> {
+        let managed = self
+            .connections
             .get_mut(connection.rep())
-            .ok_or_else(|| v2::Error::ConnectionFailed("no connection found".into()))
+            .ok_or_else(|| v2::Error::ConnectionFailed("no connection found".into()))?;
+        if managed.is_stale() {
+            if let Some(client) = managed.connection() {
+                if client.ping().await.is_err() {
+                    managed.mark_lost();
+                }
+            }
+        }
+        let client_factory = &self.client_factory;
+        managed
+            .get_or_reconnect(|address| async move { client_factory.get_client(&address).await })
+            .await
+            .map_err(|e| v2::Error::ConnectionFailed(e.to_string()))?;
+        if statements.into_iter().any(sql_sets_session_state) {
+            managed.note_session_state();
+        }
+        Ok(managed.connection().expect("connection was established"))
     }
 
     async fn is_address_allowed(&self, address: &str) -> Result<bool> {
@@ -33,9 +64,9 @@ impl<C: Client> InstanceState<C> {
     }
 }
 
-impl<C: Client> v2::Host for InstanceState<C> {}
+impl<CF: ClientFactory> v2::Host for InstanceState<CF> {}
 
-impl<C: Client> v2::HostConnection for InstanceState<C> {
+impl<CF: ClientFactory> v2::HostConnection for InstanceState<CF> {
     #[instrument(name = "spin_outbound_mysql.open", skip(self, address), err(level = Level::INFO), fields(otel.kind = "client", db.system = "mysql", db.address = Empty, server.port = Empty, db.namespace = Empty))]
     async fn open(&mut self, address: String) -> Result<Resource<Connection>, v2::Error> {
         spin_factor_outbound_networking::record_address_fields(&address);
@@ -59,7 +90,7 @@ impl<C: Client> v2::HostConnection for InstanceState<C> {
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<(), v2::Error> {
-        self.get_client(connection)
+        self.get_client(connection, [statement.as_str()])
             .await?
             .execute(statement, params)
             .await
@@ -72,7 +103,7 @@ impl<C: Client> v2::HostConnection for InstanceState<C> {
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<v2_types::RowSet, v2::Error> {
-        self.get_client(connection)
+        self.get_client(connection, [statement.as_str()])
             .await?
             .query(statement, params)
             .await
@@ -84,7 +115,24 @@ impl<C: Client> v2::HostConnection for InstanceState<C> {
     }
 }
 
-impl<C: Send> v2_types::Host for InstanceState<C> {
+impl<CF: ClientFactory> batch::Host for InstanceState<CF> {
+    #[instrument(name = "spin_outbound_mysql.execute_batch", skip(self, connection, statements), err(level = Level::INFO), fields(otel.kind = "client", db.system = "mysql", db.operation.batch.size = statements.len()))]
+    async fn execute_batch(
+        &mut self,
+        connection: Resource<Connection>,
+        statements: Vec<(String, Vec<ParameterValue>)>,
+    ) -> Result<Vec<u64>, v2::Error> {
+        self.get_client(
+            connection,
+            statements.iter().map(|(statement, _)| statement.as_str()),
+        )
+        .await?
+        .execute_batch(statements)
+        .await
+    }
+}
+
+impl<CF: ClientFactory> v2_types::Host for InstanceState<CF> {
     fn convert_error(&mut self, error: v2::Error) -> Result<v2::Error> {
         Ok(error)
     }
@@ -108,7 +156,7 @@ macro_rules! delegate {
     }};
 }
 
-impl<C: Client> v1::Host for InstanceState<C> {
+impl<CF: ClientFactory> v1::Host for InstanceState<CF> {
     async fn execute(
         &mut self,
         address: String,
@@ -1,27 +1,30 @@
 pub mod client;
 mod host;
 
-use client::Client;
-use mysql_async::Conn as MysqlClient;
+use std::sync::Arc;
+
+use client::ClientFactory;
 use spin_factor_outbound_networking::{
-    config::allowed_hosts::OutboundAllowedHosts, OutboundNetworkingFactor,
+    config::allowed_hosts::OutboundAllowedHosts, reconnect::ManagedConnection,
+    OutboundNetworkingFactor,
 };
 use spin_factors::{Factor, FactorData, InitContext, RuntimeFactors, SelfInstanceBuilder};
 use spin_world::v1::mysql as v1;
 use spin_world::v2::mysql::{self as v2};
 
-pub struct OutboundMysqlFactor<C = MysqlClient> {
-    _phantom: std::marker::PhantomData<C>,
+pub struct OutboundMysqlFactor<CF = client::PooledMysqlClientFactory> {
+    _phantom: std::marker::PhantomData<CF>,
 }
 
-impl<C: Send + Sync + Client + 'static> Factor for OutboundMysqlFactor<C> {
+impl<CF: ClientFactory> Factor for OutboundMysqlFactor<CF> {
     type RuntimeConfig = ();
-    type AppState = ();
-    type InstanceBuilder = InstanceState<C>;
+    type AppState = Arc<CF>;
+    type InstanceBuilder = InstanceState<CF>;
 
     fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
         ctx.link_bindings(v1::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(v2::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(spin_world::spin::mysql::batch::add_to_linker::<_, FactorData<Self>>)?;
         Ok(())
     }
 
@@ -29,7 +32,7 @@ impl<C: Send + Sync + Client + 'static> Factor for OutboundMysqlFactor<C> {
         &self,
         _ctx: spin_factors::ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        Ok(())
+        Ok(Arc::new(CF::default()))
     }
 
     fn prepare<T: spin_factors::RuntimeFactors>(
@@ -41,6 +44,7 @@ impl<C: Send + Sync + Client + 'static> Factor for OutboundMysqlFactor<C> {
             .allowed_hosts();
         Ok(InstanceState {
             allowed_hosts,
+            client_factory: ctx.app_state().clone(),
             connections: Default::default(),
         })
     }
@@ -60,9 +64,11 @@ impl<C> OutboundMysqlFactor<C> {
     }
 }
 
-pub struct InstanceState<C> {
+pub struct InstanceState<CF: ClientFactory> {
     allowed_hosts: OutboundAllowedHosts,
-    connections: spin_resource_table::Table<C>,
+    // Shared by all instances of the app, so that they share connection pools
+    client_factory: Arc<CF>,
+    connections: spin_resource_table::Table<ManagedConnection<CF::Client>>,
 }
 
-impl<C: Send + 'static> SelfInstanceBuilder for InstanceState<C> {}
+impl<CF: ClientFactory> SelfInstanceBuilder for InstanceState<CF> {}
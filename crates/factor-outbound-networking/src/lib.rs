@@ -1,4 +1,5 @@
 mod allowed_hosts;
+pub mod reconnect;
 pub mod runtime_config;
 mod tls;
 
@@ -11,7 +12,9 @@ use spin_factors::{
     anyhow::{self, Context},
     ConfigureAppContext, Error, Factor, FactorInstanceBuilder, PrepareContext, RuntimeFactors,
 };
-use spin_outbound_networking_config::allowed_hosts::{DisallowedHostHandler, OutboundAllowedHosts};
+use spin_outbound_networking_config::allowed_hosts::{
+    DisallowedHostHandler, OutboundAllowedHosts, RefreshingAllowedHosts,
+};
 use url::Url;
 
 use crate::{
@@ -19,7 +22,7 @@ use crate::{
 };
 pub use allowed_hosts::validate_service_chaining_for_components;
 
-pub use crate::tls::{ComponentTlsClientConfigs, TlsClientConfig};
+pub use crate::tls::{ComponentTlsClientConfigs, TlsClientConfig, TlsVersion};
 use config::allowed_hosts::AllowedHostsConfig;
 use config::blocked_networks::BlockedNetworks;
 pub use spin_outbound_networking_config as config;
@@ -39,6 +42,33 @@ impl OutboundNetworkingFactor {
     pub fn set_disallowed_host_handler(&mut self, handler: impl DisallowedHostHandler + 'static) {
         self.disallowed_host_handler = Some(Arc::new(handler));
     }
+
+    /// Returns allowed hosts which are resolved once, on first use.
+    fn allowed_hosts_once(
+        &self,
+        hosts: Arc<[String]>,
+        resolver: Arc<spin_expressions::ProviderResolver>,
+    ) -> OutboundAllowedHosts {
+        let allowed_hosts_future = async move {
+            let prepared = resolver.prepare().await.inspect_err(|err| {
+                tracing::error!(
+                    %err, "error.type" = "variable_resolution_failed",
+                    "Error resolving variables when checking request against allowed outbound hosts",
+                );
+            })?;
+            let allowed_hosts = AllowedHostsConfig::parse(&hosts, &prepared).inspect_err(|err| {
+                tracing::error!(
+                    %err, "error.type" = "invalid_allowed_hosts",
+                    "Error parsing allowed outbound hosts",
+                );
+            })?;
+            anyhow::Ok(allowed_hosts)
+        }
+        .map(|res| res.map(Arc::new).map_err(Arc::new))
+        .boxed()
+        .shared();
+        OutboundAllowedHosts::new(allowed_hosts_future, self.disallowed_host_handler.clone())
+    }
 }
 
 impl Factor for OutboundNetworkingFactor {
@@ -51,7 +81,7 @@ impl Factor for OutboundNetworkingFactor {
         mut ctx: ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
         // Extract allowed_outbound_hosts for all components
-        let component_allowed_hosts = ctx
+        let component_allowed_hosts: HashMap<String, Arc<[String]>> = ctx
             .app()
             .components()
             .map(|component| {
@@ -66,15 +96,30 @@ impl Factor for OutboundNetworkingFactor {
 
         let RuntimeConfig {
             client_tls_configs,
+            outbound_tls,
             blocked_ip_networks: block_networks,
             block_private_networks,
+            allowed_hosts_refresh,
         } = ctx.take_runtime_config().unwrap_or_default();
 
         let blocked_networks = BlockedNetworks::new(block_networks, block_private_networks);
-        let tls_client_configs = TlsClientConfigs::new(client_tls_configs)?;
+        let tls_client_configs = TlsClientConfigs::new(client_tls_configs, outbound_tls)?;
+
+        // Shared between instances so that refreshed configs outlive them
+        let component_refreshing_allowed_hosts = match allowed_hosts_refresh {
+            Some(interval) => component_allowed_hosts
+                .iter()
+                .map(|(id, hosts)| {
+                    let refreshing = RefreshingAllowedHosts::new(hosts.clone(), interval);
+                    (id.clone(), Arc::new(refreshing))
+                })
+                .collect(),
+            None => HashMap::new(),
+        };
 
         Ok(AppState {
             component_allowed_hosts,
+            component_refreshing_allowed_hosts,
             blocked_networks,
             tls_client_configs,
         })
@@ -94,27 +139,19 @@ impl Factor for OutboundNetworkingFactor {
             .instance_builder::<VariablesFactor>()?
             .expression_resolver()
             .clone();
-        let allowed_hosts_future = async move {
-            let prepared = resolver.prepare().await.inspect_err(|err| {
-                tracing::error!(
-                    %err, "error.type" = "variable_resolution_failed",
-                    "Error resolving variables when checking request against allowed outbound hosts",
-                );
-            })?;
-            AllowedHostsConfig::parse(&hosts, &prepared).inspect_err(|err| {
-                tracing::error!(
-                    %err, "error.type" = "invalid_allowed_hosts",
-                    "Error parsing allowed outbound hosts",
-                );
-            })
-        }
-        .map(|res| res.map(Arc::new).map_err(Arc::new))
-        .boxed()
-        .shared();
-        let allowed_hosts = OutboundAllowedHosts::new(
-            allowed_hosts_future.clone(),
-            self.disallowed_host_handler.clone(),
-        );
+        let refreshing_allowed_hosts = ctx
+            .app_state()
+            .component_refreshing_allowed_hosts
+            .get(ctx.app_component().id())
+            .cloned();
+        let allowed_hosts = match refreshing_allowed_hosts {
+            Some(refreshing) => OutboundAllowedHosts::refreshing(
+                refreshing,
+                resolver,
+                self.disallowed_host_handler.clone(),
+            ),
+            None => self.allowed_hosts_once(hosts, resolver),
+        };
         let blocked_networks = ctx.app_state().blocked_networks.clone();
 
         match ctx.instance_builder::<WasiFactor>() {
@@ -174,6 +211,8 @@ impl Factor for OutboundNetworkingFactor {
 pub struct AppState {
     /// Component ID -> Allowed host list
     component_allowed_hosts: HashMap<String, Arc<[String]>>,
+    /// Component ID -> Shared allowed hosts, if refreshing is enabled
+    component_refreshing_allowed_hosts: HashMap<String, Arc<RefreshingAllowedHosts>>,
     /// Blocked IP networks
     blocked_networks: BlockedNetworks,
     /// TLS client configs
@@ -0,0 +1,330 @@
+//! Keeping guests' connections to outbound services usable when the
+//! connection behind a handle is lost, e.g. because the server restarted.
+//!
+//! Outbound database factors keep each connection a guest opens in a
+//! [`ManagedConnection`]. Before reusing the connection, the factor checks
+//! that it is still alive - on every use if that is free, otherwise if it has
+//! been idle for [`STALE_AFTER`] - and marks it lost if not. A lost
+//! connection is re-established to the same address on its next use, which
+//! was already checked against the component's allowed hosts when the guest
+//! opened it. If reconnecting fails, further attempts back off, and uses in
+//! the meantime fail without trying.
+//!
+//! Reconnecting is only silent between sessions with no state of their own.
+//! If the guest may have left state in the lost session - an open
+//! transaction, a session setting, a temporary table - the first use after
+//! reconnecting fails with [`ReconnectError::SessionLost`], so that the guest
+//! doesn't carry on as if that state were still there.
+
+use std::{
+    fmt::Display,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+/// How long a connection can be idle before it should be checked before
+/// reuse, if checking it costs a round trip to the server.
+pub const STALE_AFTER: Duration = Duration::from_secs(1);
+
+/// How long to wait before trying again after the first failed attempt to
+/// reconnect. The wait doubles with each further failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// The longest wait between attempts to reconnect.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A connection, with what is needed to re-establish it if it is lost.
+pub struct ManagedConnection<C> {
+    address: String,
+    // `None` once the connection is found to be lost
+    connection: Option<C>,
+    last_used: Instant,
+    // Whether the session may have state which reconnecting would lose
+    session_state: bool,
+    failed_attempts: u32,
+    retry_at: Option<Instant>,
+}
+
+impl<C> ManagedConnection<C> {
+    /// Manages a newly established connection to `address`.
+    pub fn new(address: impl Into<String>, connection: C) -> Self {
+        Self {
+            address: address.into(),
+            connection: Some(connection),
+            last_used: Instant::now(),
+            session_state: false,
+            failed_attempts: 0,
+            retry_at: None,
+        }
+    }
+
+    /// The address the connection was opened to.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// The connection, for checking it, unless it has been found to be lost.
+    pub fn connection(&mut self) -> Option<&mut C> {
+        self.connection.as_mut()
+    }
+
+    /// Whether the connection has been idle for long enough that it should
+    /// be checked before reuse.
+    pub fn is_stale(&self) -> bool {
+        self.connection.is_some() && self.last_used.elapsed() >= STALE_AFTER
+    }
+
+    /// Records that the connection was lost, so that it is re-established
+    /// before its next use.
+    pub fn mark_lost(&mut self) {
+        if self.connection.take().is_some() {
+            tracing::info!("Outbound connection lost; reconnecting before next use");
+        }
+    }
+
+    /// Records that the session may now have state which reconnecting would
+    /// lose, e.g. because the guest opened a transaction. This holds for the
+    /// rest of the session, as there's no telling when the state is gone.
+    pub fn note_session_state(&mut self) {
+        self.session_state = true;
+    }
+
+    /// Returns the connection for use, first re-establishing it with
+    /// `connect`, which is given the address, if it was lost.
+    ///
+    /// If the lost session may have had state, the new connection is kept
+    /// but this use fails with [`ReconnectError::SessionLost`].
+    pub async fn get_or_reconnect<F, Fut, E>(
+        &mut self,
+        connect: F,
+    ) -> Result<&mut C, ReconnectError>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<C, E>>,
+        E: Display,
+    {
+        if self.connection.is_none() {
+            if let Some(retry_in) = self
+                .retry_at
+                .and_then(|at| at.checked_duration_since(Instant::now()))
+            {
+                return Err(ReconnectError::BackingOff {
+                    failed_attempts: self.failed_attempts,
+                    retry_in,
+                });
+            }
+            match connect(self.address.clone()).await {
+                Ok(connection) => {
+                    self.connection = Some(connection);
+                    self.last_used = Instant::now();
+                    self.failed_attempts = 0;
+                    self.retry_at = None;
+                    if std::mem::take(&mut self.session_state) {
+                        return Err(ReconnectError::SessionLost);
+                    }
+                }
+                Err(e) => {
+                    self.failed_attempts += 1;
+                    let backoff = INITIAL_BACKOFF
+                        .saturating_mul(1u32 << (self.failed_attempts - 1).min(16))
+                        .min(MAX_BACKOFF);
+                    self.retry_at = Some(Instant::now() + backoff);
+                    return Err(ReconnectError::Failed(e.to_string()));
+                }
+            }
+        }
+        self.last_used = Instant::now();
+        Ok(self
+            .connection
+            .as_mut()
+            .expect("connection was established"))
+    }
+}
+
+/// Why a lost connection could not be re-established.
+#[derive(Debug)]
+pub enum ReconnectError {
+    /// Trying to reconnect failed.
+    Failed(String),
+    /// Reconnecting failed recently, so was not tried again yet.
+    BackingOff {
+        failed_attempts: u32,
+        retry_in: Duration,
+    },
+    /// The connection was re-established, but the lost session may have had
+    /// state which the new one doesn't. The connection can be used again.
+    SessionLost,
+}
+
+impl Display for ReconnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failed(e) => write!(f, "connection lost, and reconnecting failed: {e}"),
+            Self::BackingOff {
+                failed_attempts,
+                retry_in,
+            } => write!(
+                f,
+                "connection lost, and reconnecting failed {failed_attempts} time(s); \
+                 not trying again for {}ms",
+                retry_in.as_millis()
+            ),
+            Self::SessionLost => f.write_str(
+                "connection lost and re-established; the lost session's state, such as \
+                 any open transaction, session settings and temporary tables, is gone",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReconnectError {}
+
+/// Whether an SQL statement may leave state in the session which a new
+/// session wouldn't have, by opening a transaction, changing a setting,
+/// creating a temporary table and the like.
+pub fn sql_sets_session_state(statement: &str) -> bool {
+    let mut words = statement
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_uppercase);
+    match words.next().as_deref() {
+        Some(
+            "BEGIN" | "START" | "SET" | "SAVEPOINT" | "LOCK" | "PREPARE" | "DECLARE" | "LISTEN"
+            | "USE",
+        ) => true,
+        // e.g. `CREATE TEMP TABLE` or `CREATE OR REPLACE TEMPORARY VIEW`
+        Some("CREATE") => words
+            .find(|word| !matches!(word.as_str(), "OR" | "REPLACE" | "GLOBAL" | "LOCAL"))
+            .is_some_and(|word| matches!(word.as_str(), "TEMP" | "TEMPORARY")),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn lost_connection_is_reestablished_to_same_address() {
+        let mut managed = ManagedConnection::new("redis://db.test", 1);
+        managed.mark_lost();
+        assert!(managed.connection().is_none());
+
+        let connection = managed
+            .get_or_reconnect(|address| async move {
+                assert_eq!(address, "redis://db.test");
+                anyhow::Ok(2)
+            })
+            .await
+            .unwrap();
+        assert_eq!(*connection, 2);
+    }
+
+    #[tokio::test]
+    async fn live_connection_is_reused() {
+        let mut managed = ManagedConnection::new("redis://db.test", 1);
+        let connection = managed
+            .get_or_reconnect(|_| async { anyhow::Ok(2) })
+            .await
+            .unwrap();
+        assert_eq!(*connection, 1);
+    }
+
+    #[tokio::test]
+    async fn losing_a_session_with_state_fails_once() {
+        let mut managed = ManagedConnection::new("postgres://db.test", 1);
+        managed.note_session_state();
+        managed.mark_lost();
+
+        let err = managed
+            .get_or_reconnect(|_| async { anyhow::Ok(2) })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ReconnectError::SessionLost), "{err}");
+        // The new session has no state, so is used from then on
+        let connection = managed
+            .get_or_reconnect(|_| async { anyhow::Ok(3) })
+            .await
+            .unwrap();
+        assert_eq!(*connection, 2);
+        managed.mark_lost();
+        let connection = managed
+            .get_or_reconnect(|_| async { anyhow::Ok(4) })
+            .await
+            .unwrap();
+        assert_eq!(*connection, 4);
+    }
+
+    #[test]
+    fn statements_which_set_session_state_are_recognized() {
+        for statement in [
+            "BEGIN",
+            "begin;",
+            "  START TRANSACTION",
+            "SET search_path TO app",
+            "set @total = 0",
+            "CREATE TEMP TABLE t (id int)",
+            "create global temporary table t (id int)",
+            "USE inventory",
+        ] {
+            assert!(sql_sets_session_state(statement), "{statement}");
+        }
+        for statement in [
+            "SELECT * FROM settings",
+            "UPDATE t SET x = 1",
+            "CREATE TABLE temperatures (c int)",
+            "CREATE TABLE temp (id int)",
+            "INSERT INTO t VALUES ('BEGIN')",
+            "",
+        ] {
+            assert!(!sql_sets_session_state(statement), "{statement}");
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_reconnects_back_off() {
+        let attempts = Cell::new(0);
+        let connect = |_| async {
+            attempts.set(attempts.get() + 1);
+            Err::<i32, _>("connection refused")
+        };
+        let mut managed = ManagedConnection::new("redis://db.test", 1);
+        managed.mark_lost();
+
+        let err = managed.get_or_reconnect(connect).await.unwrap_err();
+        assert!(matches!(err, ReconnectError::Failed(_)), "{err}");
+        assert!(err.to_string().contains("connection refused"), "{err}");
+        // Fails promptly without trying again
+        let err = managed.get_or_reconnect(connect).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ReconnectError::BackingOff {
+                    failed_attempts: 1,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+        assert_eq!(attempts.get(), 1);
+
+        std::thread::sleep(INITIAL_BACKOFF);
+        managed.get_or_reconnect(connect).await.unwrap_err();
+        assert_eq!(attempts.get(), 2);
+        // The wait has doubled
+        let Err(ReconnectError::BackingOff { retry_in, .. }) =
+            managed.get_or_reconnect(connect).await
+        else {
+            panic!("expected to back off");
+        };
+        assert!(retry_in > INITIAL_BACKOFF, "{retry_in:?}");
+
+        std::thread::sleep(INITIAL_BACKOFF * 2);
+        let connection = managed
+            .get_or_reconnect(|_| async { anyhow::Ok(3) })
+            .await
+            .unwrap();
+        assert_eq!(*connection, 3);
+    }
+}
@@ -3,6 +3,8 @@ pub mod spin;
 
 pub use rustls_pki_types::{CertificateDer, PrivateKeyDer};
 
+use crate::tls::TlsVersion;
+
 /// Runtime configuration for outbound networking.
 #[derive(Debug, Default)]
 pub struct RuntimeConfig {
@@ -12,6 +14,11 @@ pub struct RuntimeConfig {
     pub block_private_networks: bool,
     /// TLS client configs
     pub client_tls_configs: Vec<ClientTlsRuntimeConfig>,
+    /// TLS settings which apply to all components
+    pub outbound_tls: OutboundTlsRuntimeConfig,
+    /// If set, templated `allowed_outbound_hosts` are re-resolved at this
+    /// interval, rather than once per instance.
+    pub allowed_hosts_refresh: Option<std::time::Duration>,
 }
 
 /// TLS configuration for one or more component(s) and host(s).
@@ -29,6 +36,13 @@ pub struct ClientTlsRuntimeConfig {
     /// A certificate and private key to be used as the client certificate for
     /// "mutual TLS" (mTLS).
     pub client_cert: Option<ClientCertRuntimeConfig>,
+    /// If true, the Server Name Indication (SNI) extension will not be sent,
+    /// for servers which reject connections that include it.
+    pub disable_sni: bool,
+    /// The lowest TLS protocol version which may be negotiated.
+    pub min_tls_version: TlsVersion,
+    /// The highest TLS protocol version which may be negotiated.
+    pub max_tls_version: TlsVersion,
 }
 
 impl Default for ClientTlsRuntimeConfig {
@@ -40,10 +54,40 @@ impl Default for ClientTlsRuntimeConfig {
             // Use webpki roots by default
             use_webpki_roots: true,
             client_cert: None,
+            disable_sni: false,
+            min_tls_version: TlsVersion::Tls12,
+            max_tls_version: TlsVersion::Tls13,
         }
     }
 }
 
+/// TLS configuration which applies to all components.
+#[derive(Debug, Default)]
+pub struct OutboundTlsRuntimeConfig {
+    /// CA certs which are considered valid roots wherever the "standard"
+    /// `webpki-roots` CA certs are.
+    pub ca_bundle: Vec<CertificateDer<'static>>,
+    /// Overrides for particular hosts. The first override matching a host
+    /// applies to it, unless a [`ClientTlsRuntimeConfig`] for the component
+    /// names the host.
+    pub host_overrides: Vec<HostTlsRuntimeConfig>,
+}
+
+/// TLS configuration for one or more host(s), for all components.
+#[derive(Debug, Default)]
+pub struct HostTlsRuntimeConfig {
+    /// The host(s) this configuration applies to. A host may be a wildcard
+    /// such as `*.example.com`, which matches any subdomain of `example.com`.
+    pub hosts: Vec<String>,
+    /// CA certs which are considered valid roots for these hosts, in
+    /// addition to the default roots.
+    pub root_certificates: Vec<CertificateDer<'static>>,
+    /// If true, server certificates from these hosts are not verified.
+    /// Only allowed if all of the hosts are explicitly listed, i.e. are not
+    /// wildcards.
+    pub insecure_skip_verify: bool,
+}
+
 #[derive(Debug)]
 pub struct ClientCertRuntimeConfig {
     pub cert_chain: Vec<CertificateDer<'static>>,
@@ -6,9 +6,11 @@ use spin_factors::runtime_config::toml::GetTomlValue;
 use std::{
     borrow::Cow,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use super::ClientTlsRuntimeConfig;
+use super::{ClientTlsRuntimeConfig, HostTlsRuntimeConfig, OutboundTlsRuntimeConfig};
+use crate::tls::TlsVersion;
 
 /// Spin's default handling of the runtime configuration for outbound networking.
 pub struct SpinRuntimeConfig {
@@ -26,12 +28,18 @@ impl SpinRuntimeConfig {
         }
     }
 
+    /// The directory relative paths are resolved against.
+    pub fn runtime_config_dir(&self) -> &Path {
+        &self.runtime_config_dir
+    }
+
     /// Get the runtime configuration for client TLS from a TOML table.
     ///
     /// Expects table to be in the format:
     /// ````toml
     /// [outbound_networking]
     /// block_networks = ["1.1.1.1/32", "private"]
+    /// allowed_hosts_refresh_secs = 60
     ///
     /// [[client_tls]]
     /// component_ids = ["example-component"]
@@ -40,6 +48,20 @@ impl SpinRuntimeConfig {
     /// ca_roots_file = "path/to/roots.crt"
     /// client_cert_file = "path/to/client.crt"
     /// client_private_key_file = "path/to/client.key"
+    /// disable_sni = false
+    /// min_tls_version = "1.2"
+    /// max_tls_version = "1.3"
+    ///
+    /// [outbound_tls]
+    /// ca_bundle_files = ["path/to/internal-ca.pem"]
+    ///
+    /// [[outbound_tls.hosts]]
+    /// hosts = ["*.corp.internal"]
+    /// ca_file = "path/to/corp-ca.pem"
+    ///
+    /// [[outbound_tls.hosts]]
+    /// hosts = ["legacy.corp.internal"]
+    /// insecure_skip_verify = true
     /// ```
     pub fn config_from_table(
         &self,
@@ -51,30 +73,39 @@ impl SpinRuntimeConfig {
         let maybe_tls_configs = self
             .tls_configs_from_table(table)
             .context("failed to parse [[client_tls]] table")?;
-
-        if maybe_blocked_networks.is_none() && maybe_tls_configs.is_none() {
+        let maybe_outbound_tls = self
+            .outbound_tls_from_table(table)
+            .context("failed to parse [outbound_tls] table")?;
+
+        if maybe_blocked_networks.is_none()
+            && maybe_tls_configs.is_none()
+            && maybe_outbound_tls.is_none()
+        {
             return Ok(None);
         }
 
-        let (blocked_ip_networks, block_private_networks) =
+        let (blocked_ip_networks, block_private_networks, allowed_hosts_refresh) =
             maybe_blocked_networks.unwrap_or_default();
 
         let client_tls_configs = maybe_tls_configs.unwrap_or_default();
+        let outbound_tls = maybe_outbound_tls.unwrap_or_default();
 
         let runtime_config = super::RuntimeConfig {
             blocked_ip_networks,
             block_private_networks,
             client_tls_configs,
+            outbound_tls,
+            allowed_hosts_refresh,
         };
         Ok(Some(runtime_config))
     }
 
-    /// Attempts to parse (blocked_ip_networks, block_private_networks) from a
-    /// `[outbound_networking]` table.
+    /// Attempts to parse (blocked_ip_networks, block_private_networks,
+    /// allowed_hosts_refresh) from a `[outbound_networking]` table.
     fn blocked_networks_from_table(
         &self,
         table: &impl GetTomlValue,
-    ) -> anyhow::Result<Option<(Vec<ip_network::IpNetwork>, bool)>> {
+    ) -> anyhow::Result<Option<(Vec<ip_network::IpNetwork>, bool, Option<Duration>)>> {
         let Some(value) = table.get("outbound_networking") else {
             return Ok(None);
         };
@@ -90,7 +121,12 @@ impl SpinRuntimeConfig {
                 }
             }
         }
-        Ok(Some((ip_networks, private_networks)))
+        let allowed_hosts_refresh = match outbound_networking.allowed_hosts_refresh_secs {
+            Some(0) => bail!("'allowed_hosts_refresh_secs' must be greater than zero"),
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => None,
+        };
+        Ok(Some((ip_networks, private_networks, allowed_hosts_refresh)))
     }
 
     fn tls_configs_from_table<T: GetTomlValue>(
@@ -121,12 +157,21 @@ impl SpinRuntimeConfig {
             ca_roots_file,
             client_cert_file,
             client_private_key_file,
+            disable_sni,
+            min_tls_version,
+            max_tls_version,
         } = toml_config;
         ensure!(
             !component_ids.is_empty(),
             "'component_ids' list may not be empty"
         );
         ensure!(!hosts.is_empty(), "'hosts' list may not be empty");
+        let min_tls_version = min_tls_version.map_or(TlsVersion::Tls12, Into::into);
+        let max_tls_version = max_tls_version.map_or(TlsVersion::Tls13, Into::into);
+        ensure!(
+            min_tls_version <= max_tls_version,
+            "'max_tls_version' {max_tls_version} is lower than 'min_tls_version' {min_tls_version}"
+        );
 
         let components = component_ids.into_iter().map(Into::into).collect();
 
@@ -166,9 +211,78 @@ impl SpinRuntimeConfig {
             root_certificates,
             use_webpki_roots,
             client_cert,
+            disable_sni,
+            min_tls_version,
+            max_tls_version,
         })
     }
 
+    fn outbound_tls_from_table(
+        &self,
+        table: &impl GetTomlValue,
+    ) -> anyhow::Result<Option<OutboundTlsRuntimeConfig>> {
+        let Some(value) = table.get("outbound_tls") else {
+            return Ok(None);
+        };
+        let OutboundTlsToml {
+            ca_bundle_files,
+            hosts,
+        } = value.clone().try_into()?;
+
+        let mut ca_bundle = vec![];
+        for path in ca_bundle_files {
+            ca_bundle.extend(self.load_ca_certs(path)?);
+        }
+
+        let host_overrides = hosts
+            .into_iter()
+            .map(|host_config| {
+                let HostTlsToml {
+                    hosts,
+                    ca_file,
+                    insecure_skip_verify,
+                } = host_config;
+                ensure!(!hosts.is_empty(), "'hosts' list may not be empty");
+                let root_certificates = ca_file
+                    .map(|path| self.load_ca_certs(path))
+                    .transpose()?
+                    .unwrap_or_default();
+                Ok(HostTlsRuntimeConfig {
+                    hosts,
+                    root_certificates,
+                    insecure_skip_verify,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Some(OutboundTlsRuntimeConfig {
+            ca_bundle,
+            host_overrides,
+        }))
+    }
+
+    // Parse CA certs from the provided file, checking that each can be used
+    // as a root
+    fn load_ca_certs(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<Vec<rustls_pki_types::CertificateDer<'static>>> {
+        let certs = self.load_certs(&path)?;
+        let path = self.runtime_config_dir.join(path);
+        ensure!(
+            !certs.is_empty(),
+            "no certificates found in '{}'",
+            path.display()
+        );
+        let mut root_store = rustls::RootCertStore::empty();
+        for cert in &certs {
+            root_store
+                .add(cert.clone())
+                .with_context(|| format!("invalid CA certificate in '{}'", path.display()))?;
+        }
+        Ok(certs)
+    }
+
     // Parse certs from the provided file
     fn load_certs(
         &self,
@@ -201,6 +315,45 @@ struct ClientTlsToml {
     ca_roots_file: Option<PathBuf>,
     client_cert_file: Option<PathBuf>,
     client_private_key_file: Option<PathBuf>,
+    #[serde(default)]
+    disable_sni: bool,
+    min_tls_version: Option<TlsVersionToml>,
+    max_tls_version: Option<TlsVersionToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutboundTlsToml {
+    #[serde(default)]
+    ca_bundle_files: Vec<PathBuf>,
+    #[serde(default)]
+    hosts: Vec<HostTlsToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HostTlsToml {
+    hosts: Vec<String>,
+    ca_file: Option<PathBuf>,
+    #[serde(default)]
+    insecure_skip_verify: bool,
+}
+
+#[derive(Debug, Deserialize)]
+enum TlsVersionToml {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl From<TlsVersionToml> for TlsVersion {
+    fn from(version: TlsVersionToml) -> Self {
+        match version {
+            TlsVersionToml::Tls12 => Self::Tls12,
+            TlsVersionToml::Tls13 => Self::Tls13,
+        }
+    }
 }
 
 fn deserialize_hosts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
@@ -216,6 +369,7 @@ fn deserialize_hosts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<S
 struct OutboundNetworkingToml {
     #[serde(default)]
     block_networks: Vec<CidrOrPrivate>,
+    allowed_hosts_refresh_secs: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -295,6 +449,26 @@ mod tests {
         // Networks get normalized ("truncated")
         assert!(config.blocked_ip_networks.contains(&cidr("8.8.0.0/16")));
         assert!(config.block_private_networks, "{config:?}");
+        assert!(config.allowed_hosts_refresh.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_allowed_hosts_refresh() -> anyhow::Result<()> {
+        let config = SpinRuntimeConfig::new("")
+            .config_from_table(&toml::toml! {
+                [outbound_networking]
+                allowed_hosts_refresh_secs = 60
+            })?
+            .context("expected config, got None")?;
+        assert_eq!(config.allowed_hosts_refresh, Some(Duration::from_secs(60)));
+
+        SpinRuntimeConfig::new("")
+            .config_from_table(&toml::toml! {
+                [outbound_networking]
+                allowed_hosts_refresh_secs = 0
+            })
+            .unwrap_err();
         Ok(())
     }
 
@@ -315,6 +489,7 @@ mod tests {
         assert_eq!(tls_configs[0].components, ["test-component"]);
         assert_eq!(tls_configs[0].hosts[0].as_str(), "test-host");
         assert!(tls_configs[0].use_webpki_roots);
+        assert!(!tls_configs[0].disable_sni);
         Ok(())
     }
 
@@ -331,6 +506,9 @@ mod tests {
                 ca_roots_file = "valid-cert.pem"
                 client_cert_file = "valid-cert.pem"
                 client_private_key_file = "valid-private-key.pem"
+                disable_sni = true
+                min_tls_version = "1.3"
+                max_tls_version = "1.3"
             })?
             .context("missing config section")?;
         assert_eq!(tls_configs.len(), 1);
@@ -338,6 +516,9 @@ mod tests {
         assert!(tls_configs[0].use_webpki_roots);
         assert_eq!(tls_configs[0].root_certificates.len(), 2);
         assert!(tls_configs[0].client_cert.is_some());
+        assert!(tls_configs[0].disable_sni);
+        assert_eq!(tls_configs[0].min_tls_version, TlsVersion::Tls13);
+        assert_eq!(tls_configs[0].max_tls_version, TlsVersion::Tls13);
         Ok(())
     }
 
@@ -358,6 +539,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_invalid_tls_versions() {
+        let config = SpinRuntimeConfig::new("/doesnt-matter");
+
+        for table in [
+            toml::toml! {
+                [[client_tls]]
+                component_ids = ["test-component"]
+                hosts = ["test-host"]
+                min_tls_version = "1.3"
+                max_tls_version = "1.2"
+            },
+            toml::toml! {
+                [[client_tls]]
+                component_ids = ["test-component"]
+                hosts = ["test-host"]
+                min_tls_version = "1.1"
+            },
+        ] {
+            config.tls_configs_from_table(&table).unwrap_err();
+        }
+    }
+
     #[test]
     fn test_invalid_cert() {
         let config = SpinRuntimeConfig::new(TESTDATA_DIR);
@@ -372,6 +576,70 @@ mod tests {
             .unwrap_err();
     }
 
+    #[test]
+    fn test_outbound_tls_config() -> anyhow::Result<()> {
+        let config = SpinRuntimeConfig::new(TESTDATA_DIR)
+            .config_from_table(&toml::toml! {
+                [outbound_tls]
+                ca_bundle_files = ["private-ca-cert.pem"]
+
+                [[outbound_tls.hosts]]
+                hosts = ["*.corp.internal"]
+                ca_file = "private-ca-cert.pem"
+
+                [[outbound_tls.hosts]]
+                hosts = ["legacy.corp.internal"]
+                insecure_skip_verify = true
+            })?
+            .context("expected config, got None")?;
+        let outbound_tls = config.outbound_tls;
+        assert_eq!(outbound_tls.ca_bundle.len(), 1);
+        assert_eq!(outbound_tls.host_overrides.len(), 2);
+        assert_eq!(outbound_tls.host_overrides[0].hosts, ["*.corp.internal"]);
+        assert_eq!(outbound_tls.host_overrides[0].root_certificates.len(), 1);
+        assert!(!outbound_tls.host_overrides[0].insecure_skip_verify);
+        assert!(outbound_tls.host_overrides[1].root_certificates.is_empty());
+        assert!(outbound_tls.host_overrides[1].insecure_skip_verify);
+        Ok(())
+    }
+
+    #[test]
+    fn test_outbound_tls_errors_name_file() {
+        let config = SpinRuntimeConfig::new(TESTDATA_DIR);
+
+        for (table, file) in [
+            (
+                toml::toml! {
+                    [outbound_tls]
+                    ca_bundle_files = ["private-ca-cert.pem", "invalid-cert.pem"]
+                },
+                "invalid-cert.pem",
+            ),
+            (
+                toml::toml! {
+                    [[outbound_tls.hosts]]
+                    hosts = ["db.corp.internal"]
+                    ca_file = "does-not-exist.pem"
+                },
+                "does-not-exist.pem",
+            ),
+            (
+                // Not a certificate at all
+                toml::toml! {
+                    [outbound_tls]
+                    ca_bundle_files = ["valid-private-key.pem"]
+                },
+                "valid-private-key.pem",
+            ),
+        ] {
+            let err = config.config_from_table(&table).unwrap_err();
+            assert!(
+                format!("{err:#}").contains(file),
+                "unexpected error: {err:#}"
+            );
+        }
+    }
+
     #[test]
     fn test_invalid_private_key() {
         let config = SpinRuntimeConfig::new(TESTDATA_DIR);
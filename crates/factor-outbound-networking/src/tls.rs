@@ -1,14 +1,24 @@
 use std::{collections::HashMap, ops::Deref, sync::Arc};
 
-use anyhow::{ensure, Context};
+use anyhow::{bail, ensure, Context};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{CryptoProvider, WebPkiSupportedAlgorithms},
+    DigitallySignedStruct, SignatureScheme,
+};
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
 
-use crate::runtime_config::{ClientCertRuntimeConfig, ClientTlsRuntimeConfig};
+use crate::runtime_config::{
+    ClientCertRuntimeConfig, ClientTlsRuntimeConfig, HostTlsRuntimeConfig, OutboundTlsRuntimeConfig,
+};
 
 /// TLS client configs
 #[derive(Default)]
 pub struct TlsClientConfigs {
     /// Shared map of component ID -> HostTlsClientConfigs
     component_host_tls_client_configs: HashMap<String, HostTlsClientConfigs>,
+    /// Host overrides which apply to all components
+    host_overrides: Arc<[HostOverride]>,
     /// The default [`ClientConfig`] for a host if one is not explicitly configured for it.
     default_tls_client_config: TlsClientConfig,
 }
@@ -16,7 +26,21 @@ pub struct TlsClientConfigs {
 impl TlsClientConfigs {
     pub(crate) fn new(
         client_tls_configs: impl IntoIterator<Item = ClientTlsRuntimeConfig>,
+        outbound_tls: OutboundTlsRuntimeConfig,
     ) -> anyhow::Result<Self> {
+        let OutboundTlsRuntimeConfig {
+            ca_bundle,
+            host_overrides,
+        } = outbound_tls;
+        // The CA bundle is trusted wherever the webpki roots are
+        let with_ca_bundle = |mut root_certificates: Vec<CertificateDer<'static>>,
+                              use_webpki_roots| {
+            if use_webpki_roots {
+                root_certificates.extend(ca_bundle.iter().cloned());
+            }
+            root_certificates
+        };
+
         // Construct nested map of <component ID> -> <host authority> -> TLS client config
         let mut component_host_tls_client_configs = HashMap::<String, HostTlsClientConfigs>::new();
         for ClientTlsRuntimeConfig {
@@ -25,6 +49,9 @@ impl TlsClientConfigs {
             root_certificates,
             use_webpki_roots,
             client_cert,
+            disable_sni,
+            min_tls_version,
+            max_tls_version,
         } in client_tls_configs
         {
             ensure!(
@@ -35,9 +62,17 @@ impl TlsClientConfigs {
                 !hosts.is_empty(),
                 "client TLS 'hosts' list may not be empty"
             );
-            let tls_client_config =
-                TlsClientConfig::new(root_certificates, use_webpki_roots, client_cert)
-                    .context("error building TLS client config")?;
+            let mut tls_client_config = TlsClientConfig::new(
+                with_ca_bundle(root_certificates, use_webpki_roots),
+                use_webpki_roots,
+                client_cert,
+                min_tls_version,
+                max_tls_version,
+            )
+            .context("error building TLS client config")?;
+            if disable_sni {
+                tls_client_config = tls_client_config.with_sni_disabled();
+            }
             for component in components {
                 let host_configs = component_host_tls_client_configs
                     .entry(component.clone())
@@ -53,9 +88,25 @@ impl TlsClientConfigs {
             }
         }
 
+        let host_overrides = host_overrides
+            .into_iter()
+            .map(|host_override| {
+                let root_certificates = with_ca_bundle(host_override.root_certificates, true);
+                HostOverride::new(HostTlsRuntimeConfig {
+                    root_certificates,
+                    ..host_override
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let default_tls_client_config =
+            TlsClientConfig::new(ca_bundle, true, None, TlsVersion::Tls12, TlsVersion::Tls13)
+                .context("error building default TLS client config")?;
+
         Ok(Self {
             component_host_tls_client_configs,
-            ..Default::default()
+            host_overrides,
+            default_tls_client_config,
         })
     }
 
@@ -67,11 +118,91 @@ impl TlsClientConfigs {
             .cloned();
         ComponentTlsClientConfigs {
             host_client_configs,
+            host_overrides: self.host_overrides.clone(),
             default_client_config: self.default_tls_client_config.clone(),
         }
     }
 }
 
+/// A TLS client config for all hosts matching any of a set of patterns.
+struct HostOverride {
+    patterns: Vec<HostPattern>,
+    config: TlsClientConfig,
+}
+
+impl HostOverride {
+    fn new(host_config: HostTlsRuntimeConfig) -> anyhow::Result<Self> {
+        let HostTlsRuntimeConfig {
+            hosts,
+            root_certificates,
+            insecure_skip_verify,
+        } = host_config;
+        ensure!(
+            !hosts.is_empty(),
+            "outbound TLS 'hosts' list may not be empty"
+        );
+        let patterns = hosts
+            .iter()
+            .map(|host| HostPattern::parse(host))
+            .collect::<anyhow::Result<_>>()?;
+        let mut config = TlsClientConfig::new(
+            root_certificates,
+            true,
+            None,
+            TlsVersion::Tls12,
+            TlsVersion::Tls13,
+        )
+        .context("error building outbound TLS config")?;
+        if insecure_skip_verify {
+            if let Some(host) = hosts.iter().find(|host| host.starts_with("*.")) {
+                bail!("outbound TLS 'insecure_skip_verify' may only be used with explicitly listed hosts, not {host:?}");
+            }
+            tracing::warn!(
+                ?hosts,
+                "TLS certificate verification is DISABLED for outbound connections to {}; \
+                 these connections are not protected against impersonation",
+                hosts.join(", ")
+            );
+            config = config.with_insecure_skip_verify();
+        }
+        Ok(Self { patterns, config })
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(host))
+    }
+}
+
+/// A host name, or a wildcard such as `*.example.com` which matches any
+/// subdomain of `example.com`.
+enum HostPattern {
+    Exact(String),
+    /// The suffix shared by all matching hosts, e.g. `.example.com`
+    Subdomains(String),
+}
+
+impl HostPattern {
+    fn parse(host: &str) -> anyhow::Result<Self> {
+        match host.strip_prefix("*.") {
+            Some(domain) => {
+                validate_host(domain)?;
+                Ok(Self::Subdomains(format!(".{domain}")))
+            }
+            None => {
+                validate_host(host)?;
+                Ok(Self::Exact(host.to_owned()))
+            }
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            Self::Exact(exact) => host == exact,
+            Self::Subdomains(suffix) => host.len() > suffix.len() && host.ends_with(suffix),
+        }
+    }
+}
+
 /// Shared maps of host authority -> TlsClientConfig
 type HostTlsClientConfigs = Arc<HashMap<String, TlsClientConfig>>;
 
@@ -79,29 +210,81 @@ type HostTlsClientConfigs = Arc<HashMap<String, TlsClientConfig>>;
 #[derive(Clone)]
 pub struct ComponentTlsClientConfigs {
     pub(crate) host_client_configs: Option<HostTlsClientConfigs>,
+    host_overrides: Arc<[HostOverride]>,
     pub(crate) default_client_config: TlsClientConfig,
 }
 
 impl ComponentTlsClientConfigs {
     /// Returns a [`ClientConfig`] for the given host authority.
+    ///
+    /// A config for the component and host takes priority over the first
+    /// matching `[outbound_tls]` host override, which in turn takes priority
+    /// over the default config.
     pub fn get_client_config(&self, host: &str) -> &TlsClientConfig {
         self.host_client_configs
             .as_ref()
             .and_then(|configs| configs.get(host))
+            .or_else(|| {
+                self.host_overrides
+                    .iter()
+                    .find(|host_override| host_override.matches(host))
+                    .map(|host_override| &host_override.config)
+            })
             .unwrap_or(&self.default_client_config)
     }
+
+    /// Returns these configs with `overlay` layered on top.
+    ///
+    /// Configs are keyed by host authority. For each host, the overlay's
+    /// config takes priority if it has one; otherwise the config from `self`
+    /// is used. Hosts configured in neither fall back to the default config
+    /// from `self`.
+    pub fn merge(&self, overlay: &ComponentTlsClientConfigs) -> ComponentTlsClientConfigs {
+        let host_client_configs = match (&self.host_client_configs, &overlay.host_client_configs) {
+            (base, None) => base.clone(),
+            (None, overlay) => overlay.clone(),
+            (Some(base), Some(overlay)) => {
+                let mut merged = HashMap::clone(base);
+                merged.extend(
+                    overlay
+                        .iter()
+                        .map(|(host, config)| (host.clone(), config.clone())),
+                );
+                Some(Arc::new(merged))
+            }
+        };
+        ComponentTlsClientConfigs {
+            host_client_configs,
+            host_overrides: self.host_overrides.clone(),
+            default_client_config: self.default_client_config.clone(),
+        }
+    }
 }
 
 /// Shared TLS client configuration
 #[derive(Clone)]
-pub struct TlsClientConfig(Arc<rustls::ClientConfig>);
+pub struct TlsClientConfig {
+    config: Arc<rustls::ClientConfig>,
+    /// Retained so that the config can be rebuilt with different protocol versions
+    root_store: Arc<rustls::RootCertStore>,
+    min_version: TlsVersion,
+    max_version: TlsVersion,
+    insecure_skip_verify: bool,
+}
 
 impl TlsClientConfig {
     fn new(
         root_certificates: Vec<rustls_pki_types::CertificateDer<'static>>,
         use_webpki_roots: bool,
         client_cert: Option<ClientCertRuntimeConfig>,
+        min_version: TlsVersion,
+        max_version: TlsVersion,
     ) -> anyhow::Result<Self> {
+        ensure!(
+            min_version <= max_version,
+            "TLS max version {max_version} is lower than min version {min_version}"
+        );
+
         let mut root_store = rustls::RootCertStore::empty();
         if use_webpki_roots {
             root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
@@ -109,8 +292,13 @@ impl TlsClientConfig {
         for cert in root_certificates {
             root_store.add(cert)?;
         }
+        let root_store = Arc::new(root_store);
 
-        let builder = rustls::ClientConfig::builder().with_root_certificates(root_store);
+        let builder = rustls::ClientConfig::builder_with_protocol_versions(&TlsVersion::range(
+            min_version,
+            max_version,
+        ))
+        .with_root_certificates(root_store.clone());
 
         let client_config = if let Some(ClientCertRuntimeConfig {
             cert_chain,
@@ -121,12 +309,78 @@ impl TlsClientConfig {
         } else {
             builder.with_no_client_auth()
         };
-        Ok(Self(client_config.into()))
+        Ok(Self {
+            config: client_config.into(),
+            root_store,
+            min_version,
+            max_version,
+            insecure_skip_verify: false,
+        })
+    }
+
+    /// Returns this config with the Server Name Indication (SNI) extension
+    /// disabled, for servers which reject connections that include it.
+    pub fn with_sni_disabled(mut self) -> Self {
+        Arc::make_mut(&mut self.config).enable_sni = false;
+        self
+    }
+
+    /// Returns this config with server certificates accepted without
+    /// verification.
+    fn with_insecure_skip_verify(mut self) -> Self {
+        let verifier = SkipServerVerification::new(self.config.crypto_provider());
+        Arc::make_mut(&mut self.config)
+            .dangerous()
+            .set_certificate_verifier(verifier);
+        self.insecure_skip_verify = true;
+        self
+    }
+
+    /// Returns this config restricted to TLS protocol versions no lower than
+    /// `version`, which must not be higher than the maximum version.
+    pub fn with_min_version(self, version: TlsVersion) -> anyhow::Result<Self> {
+        let max_version = self.max_version;
+        self.with_versions(version, max_version)
+    }
+
+    /// Returns this config restricted to TLS protocol versions no higher than
+    /// `version`, which must not be lower than the minimum version.
+    pub fn with_max_version(self, version: TlsVersion) -> anyhow::Result<Self> {
+        let min_version = self.min_version;
+        self.with_versions(min_version, version)
+    }
+
+    fn with_versions(
+        mut self,
+        min_version: TlsVersion,
+        max_version: TlsVersion,
+    ) -> anyhow::Result<Self> {
+        ensure!(
+            min_version <= max_version,
+            "TLS max version {max_version} is lower than min version {min_version}"
+        );
+        let mut config = rustls::ClientConfig::builder_with_protocol_versions(&TlsVersion::range(
+            min_version,
+            max_version,
+        ))
+        .with_root_certificates(self.root_store.clone())
+        .with_no_client_auth();
+        // Carry over the settings which may differ from a freshly built config
+        config.client_auth_cert_resolver = self.config.client_auth_cert_resolver.clone();
+        config.enable_sni = self.config.enable_sni;
+        if self.insecure_skip_verify {
+            let verifier = SkipServerVerification::new(config.crypto_provider());
+            config.dangerous().set_certificate_verifier(verifier);
+        }
+        self.config = Arc::new(config);
+        self.min_version = min_version;
+        self.max_version = max_version;
+        Ok(self)
     }
 
     /// Returns the inner [`rustls::ClientConfig`] for consumption by rustls APIs.
     pub fn inner(&self) -> Arc<rustls::ClientConfig> {
-        self.0.clone()
+        self.config.clone()
     }
 }
 
@@ -134,13 +388,93 @@ impl Deref for TlsClientConfig {
     type Target = rustls::ClientConfig;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.config
     }
 }
 
 impl Default for TlsClientConfig {
     fn default() -> Self {
-        Self::new(vec![], true, None).expect("default client config should be valid")
+        Self::new(vec![], true, None, TlsVersion::Tls12, TlsVersion::Tls13)
+            .expect("default client config should be valid")
+    }
+}
+
+/// A server certificate verifier which accepts any certificate, for hosts
+/// configured with `insecure_skip_verify`. Handshake signatures are still
+/// checked against the presented certificate.
+#[derive(Debug)]
+struct SkipServerVerification(WebPkiSupportedAlgorithms);
+
+impl SkipServerVerification {
+    fn new(provider: &CryptoProvider) -> Arc<Self> {
+        Arc::new(Self(provider.signature_verification_algorithms))
+    }
+}
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_schemes()
+    }
+}
+
+/// A TLS protocol version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    Tls12,
+    /// TLS 1.3
+    Tls13,
+}
+
+impl TlsVersion {
+    /// Returns the rustls protocol versions from `min` to `max` inclusive.
+    fn range(min: Self, max: Self) -> Vec<&'static rustls::SupportedProtocolVersion> {
+        [Self::Tls12, Self::Tls13]
+            .into_iter()
+            .filter(|version| (min..=max).contains(version))
+            .map(|version| match version {
+                Self::Tls12 => &rustls::version::TLS12,
+                Self::Tls13 => &rustls::version::TLS13,
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Tls12 => "1.2",
+            Self::Tls13 => "1.3",
+        })
     }
 }
 
@@ -161,7 +495,7 @@ mod tests {
     use std::path::Path;
 
     use anyhow::Context;
-    use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+    use rustls_pki_types::{pem::PemObject, PrivateKeyDer};
 
     use super::*;
 
@@ -175,17 +509,21 @@ mod tests {
 
     #[test]
     fn test_minimal_config() -> anyhow::Result<()> {
-        let configs = TlsClientConfigs::new([ClientTlsRuntimeConfig {
-            components: vec!["test-component".into()],
-            hosts: vec!["test-host".into()],
-            root_certificates: vec![],
-            use_webpki_roots: false,
-            client_cert: None,
-        }])?;
+        let configs = TlsClientConfigs::new(
+            [ClientTlsRuntimeConfig {
+                components: vec!["test-component".into()],
+                hosts: vec!["test-host".into()],
+                root_certificates: vec![],
+                use_webpki_roots: false,
+                client_cert: None,
+                ..Default::default()
+            }],
+            Default::default(),
+        )?;
         let config = configs.get_tls_client_config("test-component", "test-host");
         // Check that we didn't just get the default
         let default_config = configs.get_tls_client_config("other_component", "test-host");
-        assert!(!Arc::ptr_eq(&config.0, &default_config.0));
+        assert!(!Arc::ptr_eq(&config.config, &default_config.config));
         Ok(())
     }
 
@@ -193,16 +531,20 @@ mod tests {
     fn test_maximal_config() -> anyhow::Result<()> {
         let test_certs = test_certs()?;
         let test_key = test_key()?;
-        let configs = TlsClientConfigs::new([ClientTlsRuntimeConfig {
-            components: vec!["test-component".into()],
-            hosts: vec!["test-host".into()],
-            root_certificates: vec![test_certs[0].clone()],
-            use_webpki_roots: false,
-            client_cert: Some(ClientCertRuntimeConfig {
-                cert_chain: test_certs,
-                key_der: test_key,
-            }),
-        }])?;
+        let configs = TlsClientConfigs::new(
+            [ClientTlsRuntimeConfig {
+                components: vec!["test-component".into()],
+                hosts: vec!["test-host".into()],
+                root_certificates: vec![test_certs[0].clone()],
+                use_webpki_roots: false,
+                client_cert: Some(ClientCertRuntimeConfig {
+                    cert_chain: test_certs,
+                    key_der: test_key,
+                }),
+                ..Default::default()
+            }],
+            Default::default(),
+        )?;
         let config = configs.get_tls_client_config("test-component", "test-host");
         assert!(config.client_auth_cert_resolver.has_certs());
         Ok(())
@@ -212,22 +554,25 @@ mod tests {
     fn test_config_overrides() -> anyhow::Result<()> {
         let test_certs = test_certs()?;
         let test_key = test_key()?;
-        let configs = TlsClientConfigs::new([
-            ClientTlsRuntimeConfig {
-                components: vec!["test-component1".into()],
-                hosts: vec!["test-host".into()],
-                client_cert: Some(ClientCertRuntimeConfig {
-                    cert_chain: test_certs,
-                    key_der: test_key,
-                }),
-                ..Default::default()
-            },
-            ClientTlsRuntimeConfig {
-                components: vec!["test-component1".into(), "test-component2".into()],
-                hosts: vec!["test-host".into()],
-                ..Default::default()
-            },
-        ])?;
+        let configs = TlsClientConfigs::new(
+            [
+                ClientTlsRuntimeConfig {
+                    components: vec!["test-component1".into()],
+                    hosts: vec!["test-host".into()],
+                    client_cert: Some(ClientCertRuntimeConfig {
+                        cert_chain: test_certs,
+                        key_der: test_key,
+                    }),
+                    ..Default::default()
+                },
+                ClientTlsRuntimeConfig {
+                    components: vec!["test-component1".into(), "test-component2".into()],
+                    hosts: vec!["test-host".into()],
+                    ..Default::default()
+                },
+            ],
+            Default::default(),
+        )?;
         // First match wins
         let config1 = configs.get_tls_client_config("test-component1", "test-host");
         assert!(config1.client_auth_cert_resolver.has_certs());
@@ -238,6 +583,276 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_disable_sni() -> anyhow::Result<()> {
+        let configs = TlsClientConfigs::new(
+            [ClientTlsRuntimeConfig {
+                components: vec!["test-component".into()],
+                hosts: vec!["legacy-host".into()],
+                disable_sni: true,
+                ..Default::default()
+            }],
+            Default::default(),
+        )?;
+        let component_configs = configs.get_component_tls_configs("test-component");
+        assert!(
+            !component_configs
+                .get_client_config("legacy-host")
+                .enable_sni
+        );
+        assert!(component_configs.get_client_config("other-host").enable_sni);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tls_versions() -> anyhow::Result<()> {
+        use rustls::ProtocolVersion::{TLSv1_2, TLSv1_3};
+
+        let configs = TlsClientConfigs::new(
+            [ClientTlsRuntimeConfig {
+                components: vec!["test-component".into()],
+                hosts: vec!["modern-host".into()],
+                min_tls_version: TlsVersion::Tls13,
+                ..Default::default()
+            }],
+            Default::default(),
+        )?;
+        let config = configs.get_tls_client_config("test-component", "modern-host");
+        assert!(!config.supports_version(TLSv1_2));
+        assert!(config.supports_version(TLSv1_3));
+
+        let config = TlsClientConfig::default();
+        assert!(config.supports_version(TLSv1_2) && config.supports_version(TLSv1_3));
+        let config = config.with_max_version(TlsVersion::Tls12)?;
+        assert!(config.supports_version(TLSv1_2) && !config.supports_version(TLSv1_3));
+        // The minimum may not be raised above the maximum, nor the maximum
+        // lowered below the minimum
+        assert!(config.clone().with_min_version(TlsVersion::Tls13).is_err());
+        let config = config
+            .with_sni_disabled()
+            .with_max_version(TlsVersion::Tls13)?
+            .with_min_version(TlsVersion::Tls13)?;
+        assert!(!config.supports_version(TLSv1_2) && config.supports_version(TLSv1_3));
+        assert!(!config.enable_sni);
+        assert!(config.with_max_version(TlsVersion::Tls12).is_err());
+
+        let invalid = TlsClientConfigs::new(
+            [ClientTlsRuntimeConfig {
+                components: vec!["test-component".into()],
+                hosts: vec!["test-host".into()],
+                min_tls_version: TlsVersion::Tls13,
+                max_tls_version: TlsVersion::Tls12,
+                ..Default::default()
+            }],
+            Default::default(),
+        );
+        assert!(invalid.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_prefers_overlay() -> anyhow::Result<()> {
+        let configs = TlsClientConfigs::new(
+            [
+                ClientTlsRuntimeConfig {
+                    components: vec!["base".into()],
+                    hosts: vec!["shared-host".into(), "base-host".into()],
+                    ..Default::default()
+                },
+                ClientTlsRuntimeConfig {
+                    components: vec!["overlay".into()],
+                    hosts: vec!["shared-host".into(), "overlay-host".into()],
+                    ..Default::default()
+                },
+            ],
+            Default::default(),
+        )?;
+        let base = configs.get_component_tls_configs("base");
+        let overlay = configs.get_component_tls_configs("overlay");
+        let merged = base.merge(&overlay);
+
+        let is_from = |configs: &ComponentTlsClientConfigs, host: &str| {
+            Arc::ptr_eq(
+                &merged.get_client_config(host).config,
+                &configs.get_client_config(host).config,
+            )
+        };
+        assert!(is_from(&overlay, "shared-host"));
+        assert!(!is_from(&base, "shared-host"));
+        assert!(is_from(&overlay, "overlay-host"));
+        assert!(is_from(&base, "base-host"));
+        assert!(is_from(&base, "other-host"));
+
+        // Merging with an unconfigured component changes nothing
+        let unconfigured = configs.get_component_tls_configs("other");
+        let merged = base.merge(&unconfigured);
+        assert!(Arc::ptr_eq(
+            &merged.get_client_config("base-host").config,
+            &base.get_client_config("base-host").config
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_private_ca_server_reachable_only_with_ca() -> anyhow::Result<()> {
+        let addr = start_private_ca_server().await?;
+        let host = "service.corp.internal";
+
+        let configs = TlsClientConfigs::default();
+        connect(addr, &configs, host).await.unwrap_err();
+
+        let configs = TlsClientConfigs::new(
+            [],
+            OutboundTlsRuntimeConfig {
+                ca_bundle: private_ca_certs()?,
+                ..Default::default()
+            },
+        )?;
+        connect(addr, &configs, host).await?;
+
+        let configs = TlsClientConfigs::new(
+            [],
+            OutboundTlsRuntimeConfig {
+                host_overrides: vec![HostTlsRuntimeConfig {
+                    hosts: vec!["*.corp.internal".into()],
+                    root_certificates: private_ca_certs()?,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )?;
+        connect(addr, &configs, host).await?;
+        // The wildcard matches subdomains only
+        let component_configs = configs.get_component_tls_configs("test-component");
+        assert!(Arc::ptr_eq(
+            &component_configs.get_client_config("corp.internal").config,
+            &component_configs.default_client_config.config
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insecure_skip_verify_only_affects_listed_hosts() -> anyhow::Result<()> {
+        let addr = start_private_ca_server().await?;
+        let configs = TlsClientConfigs::new(
+            [],
+            OutboundTlsRuntimeConfig {
+                host_overrides: vec![HostTlsRuntimeConfig {
+                    hosts: vec!["legacy.corp.internal".into()],
+                    insecure_skip_verify: true,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )?;
+        connect(addr, &configs, "legacy.corp.internal").await?;
+        connect(addr, &configs, "service.corp.internal")
+            .await
+            .unwrap_err();
+
+        // Protocol version changes keep verification disabled
+        let config = configs
+            .get_tls_client_config("test-component", "legacy.corp.internal")
+            .with_min_version(TlsVersion::Tls13);
+        connect_with(addr, &config, "legacy.corp.internal").await?;
+
+        let wildcard = TlsClientConfigs::new(
+            [],
+            OutboundTlsRuntimeConfig {
+                host_overrides: vec![HostTlsRuntimeConfig {
+                    hosts: vec!["*.corp.internal".into()],
+                    insecure_skip_verify: true,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+        assert!(wildcard.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_component_config_overrides_host_override() -> anyhow::Result<()> {
+        let configs = TlsClientConfigs::new(
+            [ClientTlsRuntimeConfig {
+                components: vec!["test-component".into()],
+                hosts: vec!["legacy.corp.internal".into()],
+                disable_sni: true,
+                ..Default::default()
+            }],
+            OutboundTlsRuntimeConfig {
+                host_overrides: vec![HostTlsRuntimeConfig {
+                    hosts: vec!["legacy.corp.internal".into()],
+                    insecure_skip_verify: true,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )?;
+        let config = configs.get_tls_client_config("test-component", "legacy.corp.internal");
+        assert!(!config.insecure_skip_verify && !config.enable_sni);
+        let config = configs.get_tls_client_config("other-component", "legacy.corp.internal");
+        assert!(config.insecure_skip_verify);
+        Ok(())
+    }
+
+    /// Starts a TLS server on localhost which presents a `*.corp.internal`
+    /// certificate issued by a private CA.
+    async fn start_private_ca_server() -> anyhow::Result<std::net::SocketAddr> {
+        let cert_chain = CertificateDer::pem_file_iter(testdata("private-ca-server-cert.pem"))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let key = PrivateKeyDer::from_pem_file(testdata("private-ca-server-key.pem"))?;
+        let config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(cert_chain, key)?;
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    _ = acceptor.accept(stream).await;
+                });
+            }
+        });
+        Ok(addr)
+    }
+
+    /// Completes a TLS handshake with the server at `addr` using the config
+    /// for `host` from `configs`.
+    async fn connect(
+        addr: std::net::SocketAddr,
+        configs: &TlsClientConfigs,
+        host: &str,
+    ) -> anyhow::Result<()> {
+        let config = configs.get_tls_client_config("test-component", host);
+        connect_with(addr, &config, host).await
+    }
+
+    async fn connect_with(
+        addr: std::net::SocketAddr,
+        config: &TlsClientConfig,
+        host: &str,
+    ) -> anyhow::Result<()> {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        let server_name = ServerName::try_from(host.to_owned())?;
+        tokio_rustls::TlsConnector::from(config.inner())
+            .connect(server_name, stream)
+            .await?;
+        Ok(())
+    }
+
+    fn private_ca_certs() -> anyhow::Result<Vec<CertificateDer<'static>>> {
+        CertificateDer::pem_file_iter(testdata("private-ca-cert.pem"))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("certs")
+    }
+
+    fn testdata(file: &str) -> std::path::PathBuf {
+        Path::new(TESTDATA_DIR).join(file)
+    }
+
     const TESTDATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
 
     fn test_certs() -> anyhow::Result<Vec<CertificateDer<'static>>> {
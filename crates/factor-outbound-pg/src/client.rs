@@ -5,8 +5,9 @@ use spin_world::async_trait;
 use spin_world::spin::postgres4_0_0::postgres::{
     self as v4, Column, DbValue, ParameterValue, RowSet,
 };
+use tokio_postgres::error::SqlState;
 use tokio_postgres::types::ToSql;
-use tokio_postgres::{config::SslMode, NoTls, Row};
+use tokio_postgres::{config::SslMode, CancelToken, NoTls, Row};
 
 use crate::types::{convert_data_type, convert_entry, to_sql_parameter};
 
@@ -86,6 +87,10 @@ fn create_connection_pool(address: &str) -> Result<deadpool_postgres::Pool> {
 
 #[async_trait]
 pub trait Client: Send + Sync + 'static {
+    /// Whether the connection has been closed, e.g. because the server
+    /// restarted, so that the client can no longer be used.
+    fn is_closed(&self) -> bool;
+
     async fn execute(
         &self,
         statement: String,
@@ -97,6 +102,21 @@ pub trait Client: Send + Sync + 'static {
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<RowSet, v4::Error>;
+
+    /// Begins a read-only transaction and declares a cursor with the given
+    /// name over the rows of a query, returning the query's columns.
+    async fn declare_cursor(
+        &self,
+        name: &str,
+        statement: String,
+        params: Vec<ParameterValue>,
+    ) -> Result<Vec<Column>, v4::Error>;
+
+    /// Fetches up to `max_rows` more rows from a declared cursor.
+    async fn fetch(&self, name: &str, max_rows: u32) -> Result<Vec<v4::Row>, v4::Error>;
+
+    /// Closes a declared cursor by ending its transaction.
+    async fn close_cursor(&self, name: &str) -> Result<(), v4::Error>;
 }
 
 /// Extract weak-typed error data for WIT purposes
@@ -141,28 +161,52 @@ fn query_failed(e: tokio_postgres::error::Error) -> v4::Error {
     v4::Error::QueryFailed(query_error)
 }
 
+/// Like `query_failed`, but reports Postgres rejecting JSON parameter text as
+/// a bad parameter: JSON parameters are passed through for the database to
+/// validate rather than being parsed on the host.
+fn statement_failed(e: tokio_postgres::error::Error, params: &[ParameterValue]) -> v4::Error {
+    if let Some(dbe) = e.as_db_error() {
+        if *dbe.code() == SqlState::INVALID_TEXT_REPRESENTATION
+            && dbe.message().contains("type json")
+            && params.iter().any(|p| matches!(p, ParameterValue::Jsonb(_)))
+        {
+            return v4::Error::BadParameter(dbe.message().to_owned());
+        }
+    }
+    query_failed(e)
+}
+
 #[async_trait]
 impl Client for deadpool_postgres::Object {
+    fn is_closed(&self) -> bool {
+        let client: &tokio_postgres::Client = self;
+        client.is_closed()
+    }
+
     async fn execute(
         &self,
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<u64, v4::Error> {
-        let params = params
+        let sql_params = params
             .iter()
             .map(to_sql_parameter)
             .collect::<Result<Vec<_>>>()
             .map_err(|e| v4::Error::ValueConversionFailed(format!("{e:?}")))?;
 
-        let params_refs: Vec<&(dyn ToSql + Sync)> = params
+        let params_refs: Vec<&(dyn ToSql + Sync)> = sql_params
             .iter()
             .map(|b| b.as_ref() as &(dyn ToSql + Sync))
             .collect();
 
-        self.as_ref()
+        let cancel = CancelOnDrop::arm(self.as_ref());
+        let result = self
+            .as_ref()
             .execute(&statement, params_refs.as_slice())
             .await
-            .map_err(query_failed)
+            .map_err(|e| statement_failed(e, &params));
+        cancel.disarm();
+        result
     }
 
     async fn query(
@@ -170,31 +214,38 @@ impl Client for deadpool_postgres::Object {
         statement: String,
         params: Vec<ParameterValue>,
     ) -> Result<RowSet, v4::Error> {
-        let params = params
+        let sql_params = params
             .iter()
             .map(to_sql_parameter)
             .collect::<Result<Vec<_>>>()
             .map_err(|e| v4::Error::BadParameter(format!("{e:?}")))?;
 
-        let params_refs: Vec<&(dyn ToSql + Sync)> = params
+        let params_refs: Vec<&(dyn ToSql + Sync)> = sql_params
             .iter()
             .map(|b| b.as_ref() as &(dyn ToSql + Sync))
             .collect();
 
-        let results = self
-            .as_ref()
-            .query(&statement, params_refs.as_slice())
-            .await
-            .map_err(query_failed)?;
-
-        if results.is_empty() {
-            return Ok(RowSet {
-                columns: vec![],
-                rows: vec![],
-            });
+        let cancel = CancelOnDrop::arm(self.as_ref());
+        let result = async {
+            // Prepare explicitly so that column types are known even if no rows
+            // are returned.
+            let statement = self
+                .as_ref()
+                .prepare(&statement)
+                .await
+                .map_err(query_failed)?;
+            let results = self
+                .as_ref()
+                .query(&statement, params_refs.as_slice())
+                .await
+                .map_err(|e| statement_failed(e, &params))?;
+            Ok::<_, v4::Error>((statement, results))
         }
+        .await;
+        cancel.disarm();
+        let (statement, results) = result?;
 
-        let columns = infer_columns(&results[0]);
+        let columns = infer_columns(statement.columns());
         let rows = results
             .iter()
             .map(convert_row)
@@ -203,18 +254,133 @@ impl Client for deadpool_postgres::Object {
 
         Ok(RowSet { columns, rows })
     }
+
+    async fn declare_cursor(
+        &self,
+        name: &str,
+        statement: String,
+        params: Vec<ParameterValue>,
+    ) -> Result<Vec<Column>, v4::Error> {
+        let sql_params = params
+            .iter()
+            .map(to_sql_parameter)
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| v4::Error::BadParameter(format!("{e:?}")))?;
+
+        let params_refs: Vec<&(dyn ToSql + Sync)> = sql_params
+            .iter()
+            .map(|b| b.as_ref() as &(dyn ToSql + Sync))
+            .collect();
+
+        let cancel = CancelOnDrop::arm(self.as_ref());
+        let result = async {
+            // Prepare the query itself so that column types are known even if
+            // no rows are fetched.
+            let prepared = self
+                .as_ref()
+                .prepare(&statement)
+                .await
+                .map_err(query_failed)?;
+            self.as_ref()
+                .batch_execute("BEGIN READ ONLY")
+                .await
+                .map_err(query_failed)?;
+            let declared = self
+                .as_ref()
+                .execute(
+                    &format!("DECLARE {name} NO SCROLL CURSOR FOR {statement}"),
+                    params_refs.as_slice(),
+                )
+                .await
+                .map_err(|e| statement_failed(e, &params));
+            if let Err(err) = declared {
+                _ = self.as_ref().batch_execute("ROLLBACK").await;
+                return Err(err);
+            }
+            Ok(infer_columns(prepared.columns()))
+        }
+        .await;
+        cancel.disarm();
+        result
+    }
+
+    async fn fetch(&self, name: &str, max_rows: u32) -> Result<Vec<v4::Row>, v4::Error> {
+        let cancel = CancelOnDrop::arm(self.as_ref());
+        let result = self
+            .as_ref()
+            .query(&format!("FETCH FORWARD {max_rows} FROM {name}"), &[])
+            .await
+            .map_err(query_failed);
+        cancel.disarm();
+        result?
+            .iter()
+            .map(convert_row)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| v4::Error::QueryFailed(v4::QueryError::Text(format!("{e:?}"))))
+    }
+
+    async fn close_cursor(&self, _name: &str) -> Result<(), v4::Error> {
+        // The transaction only reads, so rolling it back loses nothing
+        self.as_ref()
+            .batch_execute("ROLLBACK")
+            .await
+            .map_err(query_failed)
+    }
 }
 
-fn infer_columns(row: &Row) -> Vec<Column> {
-    let mut result = Vec::with_capacity(row.len());
-    for index in 0..row.len() {
-        result.push(infer_column(row, index));
+/// Asks the server to cancel whatever is running on a connection if dropped
+/// before being disarmed, i.e. if the host call running a statement is
+/// dropped because the guest was torn down or interrupted mid-call.
+///
+/// Without this the server would run the abandoned statement to completion.
+struct CancelOnDrop {
+    token: Option<CancelToken>,
+}
+
+impl CancelOnDrop {
+    fn arm(client: &tokio_postgres::Client) -> Self {
+        Self {
+            token: Some(client.cancel_token()),
+        }
+    }
+
+    fn disarm(mut self) {
+        self.token = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        let Some(token) = self.token.take() else {
+            return;
+        };
+        spin_telemetry::monotonic_counter!(spin.outbound_pg_cancelled = 1);
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("Postgres statement abandoned outside of a runtime; not cancelling it");
+            return;
+        };
+        runtime.spawn(async move {
+            if let Err(err) = cancel_query(token).await {
+                tracing::warn!("Failed to cancel abandoned Postgres statement: {err:#}");
+            }
+        });
     }
-    result
 }
 
-fn infer_column(row: &Row, index: usize) -> Column {
-    let column = &row.columns()[index];
+async fn cancel_query(token: CancelToken) -> Result<()> {
+    // TLS is only used if the connection's SSL mode asks for it
+    let connector = MakeTlsConnector::new(TlsConnector::builder().build()?);
+    token
+        .cancel_query(connector)
+        .await
+        .context("sending cancel request")
+}
+
+fn infer_columns(columns: &[tokio_postgres::Column]) -> Vec<Column> {
+    columns.iter().map(infer_column).collect()
+}
+
+fn infer_column(column: &tokio_postgres::Column) -> Column {
     let name = column.name().to_owned();
     let data_type = convert_data_type(column.type_());
     Column { name, data_type }
@@ -249,3 +415,86 @@ impl std::fmt::Display for ArcError {
         std::fmt::Display::fmt(&self.0, f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    const BACKEND_PID: i32 = 42;
+    const BACKEND_SECRET: i32 = 1234;
+    const CANCEL_REQUEST_CODE: i32 = 80877102;
+
+    /// Reads an untagged startup-phase message, returning its code and body.
+    async fn read_startup_message(stream: &mut TcpStream) -> Result<(i32, Vec<u8>)> {
+        let len = stream.read_i32().await?;
+        let code = stream.read_i32().await?;
+        let mut body = vec![0; len as usize - 8];
+        stream.read_exact(&mut body).await?;
+        Ok((code, body))
+    }
+
+    /// Reads a tagged message, returning its tag.
+    async fn read_message(stream: &mut TcpStream) -> Result<u8> {
+        let tag = stream.read_u8().await?;
+        let len = stream.read_i32().await?;
+        let mut body = vec![0; len as usize - 4];
+        stream.read_exact(&mut body).await?;
+        Ok(tag)
+    }
+
+    #[tokio::test]
+    async fn abandoned_query_is_cancelled_on_server() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let (query_sent_tx, query_sent_rx) = tokio::sync::oneshot::channel();
+        // A mock server which accepts a connection and never answers queries
+        let server = tokio::spawn(async move {
+            let (mut conn, _) = listener.accept().await?;
+            read_startup_message(&mut conn).await?;
+            // AuthenticationOk
+            conn.write_all(&[b'R', 0, 0, 0, 8, 0, 0, 0, 0]).await?;
+            // BackendKeyData
+            let mut key_data = vec![b'K', 0, 0, 0, 12];
+            key_data.extend(BACKEND_PID.to_be_bytes());
+            key_data.extend(BACKEND_SECRET.to_be_bytes());
+            conn.write_all(&key_data).await?;
+            // ReadyForQuery (idle)
+            conn.write_all(&[b'Z', 0, 0, 0, 5, b'I']).await?;
+
+            // Wait for the statement to be parsed
+            while read_message(&mut conn).await? != b'P' {}
+            _ = query_sent_tx.send(());
+
+            // Cancel requests arrive on a new connection
+            let (mut cancel, _) = listener.accept().await?;
+            let (code, body) = read_startup_message(&mut cancel).await?;
+            anyhow::Ok((code, body, conn))
+        });
+
+        let client = PooledTokioClientFactory::default()
+            .get_client(&format!(
+                "postgres://test@127.0.0.1:{port}/test?sslmode=disable"
+            ))
+            .await?;
+        let query =
+            tokio::spawn(async move { client.query("SELECT pg_sleep(60)".into(), vec![]).await });
+        query_sent_rx.await?;
+        // As if the instance making the query were torn down
+        query.abort();
+
+        let (code, body, _conn) = tokio::time::timeout(Duration::from_secs(5), server).await???;
+        assert_eq!(code, CANCEL_REQUEST_CODE);
+        assert_eq!(
+            body,
+            [BACKEND_PID.to_be_bytes(), BACKEND_SECRET.to_be_bytes()].concat()
+        );
+        Ok(())
+    }
+}
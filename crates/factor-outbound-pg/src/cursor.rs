@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use spin_core::wasmtime::component::Resource;
+use spin_world::spin::postgres4_0_0::postgres::{self as v4};
+use spin_world::spin::postgres4_1_0::cursor;
+use tracing::instrument;
+use tracing::Level;
+
+use crate::client::{Client, ClientFactory};
+use crate::InstanceState;
+
+/// The name of the cursor behind each row stream. Every stream has a session
+/// of its own, so the name never clashes.
+const CURSOR_NAME: &str = "spin_row_stream";
+
+/// The state of a `row-stream` resource: a cursor declared in a session of
+/// its own, which is closed when the stream is dropped.
+///
+/// If the stream is dropped without being closed, e.g. because the instance
+/// was torn down, the cursor is closed in the background before the session
+/// is released.
+pub(crate) struct RowStream<C: Client> {
+    // Only taken when closing
+    client: Option<C>,
+    columns: Vec<v4::Column>,
+}
+
+impl<C: Client> RowStream<C> {
+    async fn fetch(&self, max_rows: u32) -> Result<Vec<v4::Row>, v4::Error> {
+        let client = self.client.as_ref().expect("row stream is closed");
+        client.fetch(CURSOR_NAME, max_rows).await
+    }
+
+    async fn close(mut self) -> Result<(), v4::Error> {
+        let client = self.client.take().expect("row stream is closed");
+        client.close_cursor(CURSOR_NAME).await
+    }
+}
+
+impl<C: Client> Drop for RowStream<C> {
+    fn drop(&mut self) {
+        let Some(client) = self.client.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("Postgres row stream dropped outside of a runtime; not closing it");
+            return;
+        };
+        runtime.spawn(async move {
+            if let Err(err) = client.close_cursor(CURSOR_NAME).await {
+                tracing::warn!("Failed to close abandoned Postgres row stream: {err:?}");
+            }
+        });
+    }
+}
+
+impl<CF: ClientFactory> InstanceState<CF> {
+    fn get_row_stream(
+        &self,
+        stream: &Resource<cursor::RowStream>,
+    ) -> Result<&RowStream<CF::Client>, v4::Error> {
+        self.row_streams
+            .get(stream.rep())
+            .ok_or_else(|| v4::Error::Other("no row stream found".into()))
+    }
+}
+
+impl<CF: ClientFactory> cursor::Host for InstanceState<CF> {
+    #[instrument(name = "spin_outbound_pg.execute_streaming", skip(self, conn, params), err(level = Level::INFO), fields(otel.kind = "client", db.system = "postgresql", otel.name = statement))]
+    async fn execute_streaming(
+        &mut self,
+        conn: Resource<v4::Connection>,
+        statement: String,
+        params: Vec<v4::ParameterValue>,
+    ) -> Result<Resource<cursor::RowStream>, v4::Error> {
+        // The cursor needs a transaction, so gets a session of its own rather
+        // than tying up the guest's connection until the stream is dropped
+        let address = self.get_address(&conn)?.to_owned();
+        let client = self
+            .client_factory
+            .get_client(&address)
+            .await
+            .map_err(|e| v4::Error::ConnectionFailed(format!("{e:?}")))?;
+        let columns = client
+            .declare_cursor(CURSOR_NAME, statement, params)
+            .await?;
+        self.row_streams
+            .push(RowStream {
+                client: Some(client),
+                columns,
+            })
+            .map_err(|_| v4::Error::ConnectionFailed("too many row streams".into()))
+            .map(Resource::new_own)
+    }
+}
+
+impl<CF: ClientFactory> cursor::HostRowStream for InstanceState<CF> {
+    async fn columns(&mut self, stream: Resource<cursor::RowStream>) -> Result<Vec<v4::Column>> {
+        Ok(self
+            .row_streams
+            .get(stream.rep())
+            .context("no row stream found")?
+            .columns
+            .clone())
+    }
+
+    #[instrument(name = "spin_outbound_pg.fetch", skip(self, stream), err(level = Level::INFO), fields(otel.kind = "client", db.system = "postgresql"))]
+    async fn next(
+        &mut self,
+        stream: Resource<cursor::RowStream>,
+        max_rows: u32,
+    ) -> Result<Vec<v4::Row>, v4::Error> {
+        if max_rows == 0 {
+            return Err(v4::Error::BadParameter(
+                "max-rows must be greater than zero".into(),
+            ));
+        }
+        self.get_row_stream(&stream)?.fetch(max_rows).await
+    }
+
+    async fn drop(&mut self, stream: Resource<cursor::RowStream>) -> Result<()> {
+        // Close the cursor before returning, so that the session is released
+        // before the guest continues
+        if let Some(stream) = self.row_streams.remove(stream.rep()) {
+            if let Err(err) = stream.close().await {
+                tracing::warn!("Failed to close Postgres row stream: {err:?}");
+            }
+        }
+        Ok(())
+    }
+}
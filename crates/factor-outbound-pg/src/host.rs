@@ -1,5 +1,6 @@
 use anyhow::Result;
 use spin_core::wasmtime::component::Resource;
+use spin_factor_outbound_networking::reconnect::{sql_sets_session_state, ManagedConnection};
 use spin_world::spin::postgres3_0_0::postgres::{self as v3};
 use spin_world::spin::postgres4_0_0::postgres::{self as v4};
 use spin_world::v1::postgres as v1;
@@ -18,23 +19,60 @@ impl<CF: ClientFactory> InstanceState<CF> {
         &mut self,
         address: &str,
     ) -> Result<Resource<Conn>, v4::Error> {
+        let client = self
+            .client_factory
+            .get_client(address)
+            .await
+            .map_err(|e| v4::Error::ConnectionFailed(format!("{e:?}")))?;
         self.connections
-            .push(
-                self.client_factory
-                    .get_client(address)
-                    .await
-                    .map_err(|e| v4::Error::ConnectionFailed(format!("{e:?}")))?,
-            )
+            .push(ManagedConnection::new(address, client))
             .map_err(|_| v4::Error::ConnectionFailed("too many connections".into()))
             .map(Resource::new_own)
     }
 
+    /// The client of the given connection for running `statement`,
+    /// reconnecting first if the connection has been closed, e.g. because
+    /// the server restarted.
+    ///
+    /// Reconnecting fails the statement if the guest may have left state in
+    /// the lost session, such as an open transaction; see
+    /// [`ManagedConnection::note_session_state`].
     async fn get_client<Conn: 'static>(
-        &self,
+        &mut self,
         connection: Resource<Conn>,
+        statement: &str,
     ) -> Result<&CF::Client, v4::Error> {
+        let managed = self
+            .connections
+            .get_mut(connection.rep())
+            .ok_or_else(|| v4::Error::ConnectionFailed("no connection found".into()))?;
+        // Checking a Postgres connection doesn't need a round trip, so is
+        // done before every use
+        if managed
+            .connection()
+            .is_some_and(|client| client.is_closed())
+        {
+            managed.mark_lost();
+        }
+        let client_factory = &self.client_factory;
+        managed
+            .get_or_reconnect(|address| async move { client_factory.get_client(&address).await })
+            .await
+            .map_err(|e| v4::Error::ConnectionFailed(e.to_string()))?;
+        if sql_sets_session_state(statement) {
+            managed.note_session_state();
+        }
+        Ok(managed.connection().expect("connection was established"))
+    }
+
+    /// The address the given connection was opened to.
+    pub(crate) fn get_address<Conn: 'static>(
+        &self,
+        connection: &Resource<Conn>,
+    ) -> Result<&str, v4::Error> {
         self.connections
             .get(connection.rep())
+            .map(|managed| managed.address())
             .ok_or_else(|| v4::Error::ConnectionFailed("no connection found".into()))
     }
 
@@ -102,7 +140,7 @@ impl<CF: ClientFactory> v3::HostConnection for InstanceState<CF> {
         params: Vec<v3::ParameterValue>,
     ) -> Result<u64, v3::Error> {
         Ok(self
-            .get_client(connection)
+            .get_client(connection, &statement)
             .await?
             .execute(statement, v3_params_to_v4(params))
             .await?)
@@ -116,7 +154,7 @@ impl<CF: ClientFactory> v3::HostConnection for InstanceState<CF> {
         params: Vec<v3::ParameterValue>,
     ) -> Result<v3::RowSet, v3::Error> {
         Ok(self
-            .get_client(connection)
+            .get_client(connection, &statement)
             .await?
             .query(statement, v3_params_to_v4(params))
             .await?
@@ -153,7 +191,7 @@ impl<CF: ClientFactory> v4::HostConnection for InstanceState<CF> {
         statement: String,
         params: Vec<v4::ParameterValue>,
     ) -> Result<u64, v4::Error> {
-        self.get_client(connection)
+        self.get_client(connection, &statement)
             .await?
             .execute(statement, params)
             .await
@@ -166,7 +204,7 @@ impl<CF: ClientFactory> v4::HostConnection for InstanceState<CF> {
         statement: String,
         params: Vec<v4::ParameterValue>,
     ) -> Result<v4::RowSet, v4::Error> {
-        self.get_client(connection)
+        self.get_client(connection, &statement)
             .await?
             .query(statement, params)
             .await
@@ -241,7 +279,7 @@ impl<CF: ClientFactory> v2::HostConnection for InstanceState<CF> {
         params: Vec<v2_types::ParameterValue>,
     ) -> Result<u64, v2::Error> {
         Ok(self
-            .get_client(connection)
+            .get_client(connection, &statement)
             .await?
             .execute(statement, v2_params_to_v3(params)?)
             .await?)
@@ -255,7 +293,7 @@ impl<CF: ClientFactory> v2::HostConnection for InstanceState<CF> {
         params: Vec<v2_types::ParameterValue>,
     ) -> Result<v2_types::RowSet, v2::Error> {
         Ok(self
-            .get_client(connection)
+            .get_client(connection, &statement)
             .await?
             .query(statement, v2_params_to_v3(params)?)
             .await?
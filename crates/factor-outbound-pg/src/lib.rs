@@ -1,18 +1,23 @@
 pub mod client;
+mod cursor;
 mod host;
+mod typed;
 mod types;
 
 use std::sync::Arc;
 
 use client::ClientFactory;
 use spin_factor_outbound_networking::{
-    config::allowed_hosts::OutboundAllowedHosts, OutboundNetworkingFactor,
+    config::allowed_hosts::OutboundAllowedHosts, reconnect::ManagedConnection,
+    OutboundNetworkingFactor,
 };
 use spin_factors::{
     anyhow, ConfigureAppContext, Factor, FactorData, PrepareContext, RuntimeFactors,
     SelfInstanceBuilder,
 };
 
+pub use typed::{QueryParams, QueryRow, TypedQuery};
+
 pub struct OutboundPgFactor<CF = crate::client::PooledTokioClientFactory> {
     _phantom: std::marker::PhantomData<CF>,
 }
@@ -31,6 +36,9 @@ impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
         ctx.link_bindings(
             spin_world::spin::postgres4_0_0::postgres::add_to_linker::<_, FactorData<Self>>,
         )?;
+        ctx.link_bindings(
+            spin_world::spin::postgres4_1_0::cursor::add_to_linker::<_, FactorData<Self>>,
+        )?;
         Ok(())
     }
 
@@ -52,6 +60,7 @@ impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
             allowed_hosts,
             client_factory: ctx.app_state().clone(),
             connections: Default::default(),
+            row_streams: Default::default(),
         })
     }
 }
@@ -73,7 +82,8 @@ impl<C> OutboundPgFactor<C> {
 pub struct InstanceState<CF: ClientFactory> {
     allowed_hosts: OutboundAllowedHosts,
     client_factory: Arc<CF>,
-    connections: spin_resource_table::Table<CF::Client>,
+    connections: spin_resource_table::Table<ManagedConnection<CF::Client>>,
+    row_streams: spin_resource_table::Table<cursor::RowStream<CF::Client>>,
 }
 
 impl<CF: ClientFactory> SelfInstanceBuilder for InstanceState<CF> {}
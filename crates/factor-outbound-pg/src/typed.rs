@@ -0,0 +1,205 @@
+//! Queries whose parameter and row types are checked against the database.
+
+use std::{any::type_name, marker::PhantomData, sync::OnceLock};
+
+use anyhow::{bail, Context, Result};
+use tokio_postgres::{
+    types::{FromSql, ToSql, Type},
+    Client, Statement,
+};
+
+/// A SQL statement whose bind parameters have the types `Params` and whose
+/// result rows have the types `Row`.
+///
+/// The first time the query is run it is prepared against the database,
+/// and the types of its parameters and result columns are checked against
+/// `Params` and `Row`. A mismatch, e.g. because the schema has drifted or the
+/// SQL has a typo, fails that first run rather than some later query with
+/// unlucky data.
+///
+/// ```ignore
+/// static USER_NAME: TypedQuery<(i32,), (String, Option<String>)> =
+///     TypedQuery::new("SELECT name, email FROM users WHERE id = $1");
+///
+/// let rows = USER_NAME.query(&client, &(42,)).await?;
+/// ```
+pub struct TypedQuery<Params, Row> {
+    sql: &'static str,
+    /// Set once the statement has been checked against the database.
+    checked: OnceLock<()>,
+    _marker: PhantomData<fn(Params) -> Row>,
+}
+
+impl<Params: QueryParams, Row: QueryRow> TypedQuery<Params, Row> {
+    /// Returns a query running the given SQL.
+    pub const fn new(sql: &'static str) -> Self {
+        Self {
+            sql,
+            checked: OnceLock::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The SQL the query runs.
+    pub fn sql(&self) -> &'static str {
+        self.sql
+    }
+
+    /// Runs the query, returning its rows.
+    pub async fn query(&self, client: &Client, params: &Params) -> Result<Vec<Row>> {
+        let statement = self.prepare(client).await?;
+        let rows = client
+            .query(&statement, &params.to_sql())
+            .await
+            .with_context(|| format!("running query {:?}", self.sql))?;
+        rows.iter()
+            .map(|row| Row::from_row(row).context("converting query result row"))
+            .collect()
+    }
+
+    /// Runs the statement, returning the number of rows it modified.
+    pub async fn execute(&self, client: &Client, params: &Params) -> Result<u64> {
+        let statement = self.prepare(client).await?;
+        client
+            .execute(&statement, &params.to_sql())
+            .await
+            .with_context(|| format!("running statement {:?}", self.sql))
+    }
+
+    /// Prepares the statement on the client's connection, checking it against
+    /// `Params` and `Row` if it hasn't been checked yet.
+    async fn prepare(&self, client: &Client) -> Result<Statement> {
+        let statement = client
+            .prepare(self.sql)
+            .await
+            .with_context(|| format!("preparing statement {:?}", self.sql))?;
+        if self.checked.get().is_none() {
+            let column_types = statement
+                .columns()
+                .iter()
+                .map(|column| column.type_().clone())
+                .collect::<Vec<_>>();
+            Params::check(statement.params())
+                .and_then(|()| Row::check(&column_types))
+                .with_context(|| format!("statement {:?} doesn't match its types", self.sql))?;
+            _ = self.checked.set(());
+        }
+        Ok(statement)
+    }
+}
+
+/// The bind parameters of a [`TypedQuery`]. Implemented for tuples of up to
+/// eight [`ToSql`] types.
+pub trait QueryParams: Send + Sync {
+    /// Checks that the statement's parameters have the given types.
+    fn check(types: &[Type]) -> Result<()>;
+
+    /// The values of the parameters.
+    fn to_sql(&self) -> Vec<&(dyn ToSql + Sync)>;
+}
+
+/// The result rows of a [`TypedQuery`]. Implemented for tuples of up to eight
+/// [`FromSql`] types.
+pub trait QueryRow: Sized {
+    /// Checks that the statement's result columns have the given types.
+    fn check(types: &[Type]) -> Result<()>;
+
+    /// Converts a result row.
+    fn from_row(row: &tokio_postgres::Row) -> Result<Self>;
+}
+
+fn check_count(kind: &str, types: &[Type], expected: usize) -> Result<()> {
+    if types.len() != expected {
+        bail!("statement has {} {kind}s, expected {expected}", types.len());
+    }
+    Ok(())
+}
+
+fn check_type<T>(kind: &str, index: usize, ty: &Type, accepts: bool) -> Result<()> {
+    if !accepts {
+        bail!(
+            "{kind} {} has type {ty}, which is incompatible with {}",
+            index + 1,
+            type_name::<T>()
+        );
+    }
+    Ok(())
+}
+
+macro_rules! impl_tuples {
+    ($(($($index:tt: $ty:ident),*)),* $(,)?) => {$(
+        impl<$($ty: ToSql + Sync + Send),*> QueryParams for ($($ty,)*) {
+            fn check(types: &[Type]) -> Result<()> {
+                let names: &[&str] = &[$(stringify!($ty)),*];
+                check_count("parameter", types, names.len())?;
+                $(check_type::<$ty>("parameter", $index, &types[$index], <$ty as ToSql>::accepts(&types[$index]))?;)*
+                Ok(())
+            }
+
+            fn to_sql(&self) -> Vec<&(dyn ToSql + Sync)> {
+                vec![$(&self.$index as &(dyn ToSql + Sync)),*]
+            }
+        }
+
+        impl<$($ty: for<'a> FromSql<'a>),*> QueryRow for ($($ty,)*) {
+            fn check(types: &[Type]) -> Result<()> {
+                let names: &[&str] = &[$(stringify!($ty)),*];
+                check_count("column", types, names.len())?;
+                $(check_type::<$ty>("column", $index, &types[$index], <$ty as FromSql>::accepts(&types[$index]))?;)*
+                Ok(())
+            }
+
+            #[allow(unused_variables)]
+            fn from_row(row: &tokio_postgres::Row) -> Result<Self> {
+                Ok(($(row.try_get::<_, $ty>($index)?,)*))
+            }
+        }
+    )*};
+}
+
+impl_tuples!(
+    (),
+    (0: A),
+    (0: A, 1: B),
+    (0: A, 1: B, 2: C),
+    (0: A, 1: B, 2: C, 3: D),
+    (0: A, 1: B, 2: C, 3: D, 4: E),
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F),
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G),
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H),
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_are_checked() {
+        <(i32, String) as QueryParams>::check(&[Type::INT4, Type::TEXT]).unwrap();
+        <(Option<i64>,) as QueryParams>::check(&[Type::INT8]).unwrap();
+        <() as QueryParams>::check(&[]).unwrap();
+        <() as QueryRow>::check(&[]).unwrap();
+
+        let err = <(i32,) as QueryParams>::check(&[Type::INT4, Type::TEXT]).unwrap_err();
+        assert_eq!(err.to_string(), "statement has 2 parameters, expected 1");
+        let err = <(i32, i32) as QueryParams>::check(&[Type::INT4, Type::TEXT]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "parameter 2 has type text, which is incompatible with i32"
+        );
+    }
+
+    #[test]
+    fn rows_are_checked() {
+        <(String, Option<String>) as QueryRow>::check(&[Type::VARCHAR, Type::TEXT]).unwrap();
+        <(bool,) as QueryRow>::check(&[Type::BOOL]).unwrap();
+
+        let err = <(String,) as QueryRow>::check(&[]).unwrap_err();
+        assert_eq!(err.to_string(), "statement has 0 columns, expected 1");
+        let err = <(i64, String) as QueryRow>::check(&[Type::INT4, Type::TEXT]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "column 1 has type int4, which is incompatible with i64"
+        );
+    }
+}
@@ -5,16 +5,20 @@ use tokio_postgres::{types::ToSql, Row};
 mod convert;
 mod decimal;
 mod interval;
+mod json;
+mod numeric;
 mod other;
 mod pg_null;
 
 use convert::{
     date_pg_to_wit, date_wit_to_pg, datetime_pg_to_wit, datetime_wit_to_pg,
     decimal_array_pg_to_wit, decimal_array_wit_to_pg, decimal_range_pg_to_wit,
-    decimal_range_wit_to_pg, decimal_wit_to_pg, jsonb_pg_to_wit, jsonb_wit_to_pg, range_pg_to_wit,
-    range_wit_to_pg, time_pg_to_wit, time_wit_to_pg, timestamp_wit_to_pg, uuid_wit_to_pg,
+    decimal_range_wit_to_pg, range_pg_to_wit, range_wit_to_pg, time_pg_to_wit, time_wit_to_pg,
+    timestamp_wit_to_pg, uuid_wit_to_pg,
 };
 use interval::Interval;
+use json::PgJson;
+use numeric::PgNumeric;
 use other::Other;
 use pg_null::PgNull;
 
@@ -32,7 +36,7 @@ pub fn convert_data_type(pg_type: &Type) -> DbDataType {
         Type::DATE => DbDataType::Date,
         Type::TIME => DbDataType::Time,
         Type::UUID => DbDataType::Uuid,
-        Type::JSONB => DbDataType::Jsonb,
+        Type::JSON | Type::JSONB => DbDataType::Jsonb,
         Type::NUMERIC => DbDataType::Decimal,
         Type::INT4_RANGE => DbDataType::RangeInt32,
         Type::INT8_RANGE => DbDataType::RangeInt64,
@@ -104,10 +108,8 @@ pub fn convert_entry(row: &Row, index: usize) -> anyhow::Result<DbValue> {
         &Type::DATE => try_map_db_value(row, index, DbValue::Date, date_pg_to_wit),
         &Type::TIME => try_map_db_value(row, index, DbValue::Time, time_pg_to_wit),
         &Type::UUID => map_db_value(row, index, DbValue::Uuid, |v: uuid::Uuid| v.to_string()),
-        &Type::JSONB => try_map_db_value(row, index, DbValue::Jsonb, jsonb_pg_to_wit),
-        &Type::NUMERIC => map_db_value(row, index, DbValue::Decimal, |v: rust_decimal::Decimal| {
-            v.to_string()
-        }),
+        &Type::JSON | &Type::JSONB => map_db_value(row, index, DbValue::Jsonb, |v: PgJson| v.0),
+        &Type::NUMERIC => map_db_value(row, index, DbValue::Decimal, |v: PgNumeric| v.0),
         &Type::INT4_RANGE => map_db_value(row, index, DbValue::RangeInt32, range_pg_to_wit),
         &Type::INT8_RANGE => map_db_value(row, index, DbValue::RangeInt64, range_pg_to_wit),
         &Type::NUM_RANGE => {
@@ -149,8 +151,8 @@ pub fn to_sql_parameter(value: &ParameterValue) -> anyhow::Result<Box<dyn ToSql
         ParameterValue::Datetime(v) => Ok(Box::new(datetime_wit_to_pg(v)?)),
         ParameterValue::Timestamp(v) => Ok(Box::new(timestamp_wit_to_pg(*v)?)),
         ParameterValue::Uuid(v) => Ok(Box::new(uuid_wit_to_pg(v)?)),
-        ParameterValue::Jsonb(v) => Ok(Box::new(jsonb_wit_to_pg(v)?)),
-        ParameterValue::Decimal(v) => Ok(Box::new(decimal_wit_to_pg(v)?)),
+        ParameterValue::Jsonb(v) => Ok(Box::new(PgJson(v.clone()))),
+        ParameterValue::Decimal(v) => Ok(Box::new(PgNumeric::parse(v)?)),
         ParameterValue::RangeInt32(v) => Ok(Box::new(range_wit_to_pg(*v))),
         ParameterValue::RangeInt64(v) => Ok(Box::new(range_wit_to_pg(*v))),
         ParameterValue::RangeDecimal(v) => Ok(Box::new(decimal_range_wit_to_pg(v)?)),
@@ -5,40 +5,21 @@ use anyhow::{anyhow, Context};
 use spin_world::spin::postgres4_0_0::postgres::{self as v4};
 
 use super::decimal::RangeableDecimal;
-
-pub fn jsonb_pg_to_wit(value: serde_json::Value) -> anyhow::Result<Vec<u8>> {
-    serde_json::to_vec(&value).context("invalid JSON from database")
-}
-
-pub fn jsonb_wit_to_pg(value: &[u8]) -> anyhow::Result<serde_json::Value> {
-    serde_json::from_slice(value)
-        .with_context(|| format!("invalid JSON {}", String::from_utf8_lossy(value)))
-}
+use super::numeric::PgNumeric;
 
 pub fn uuid_wit_to_pg(value: &str) -> anyhow::Result<uuid::Uuid> {
     uuid::Uuid::parse_str(value).with_context(|| format!("invalid UUID {value}"))
 }
 
-pub fn decimal_wit_to_pg(value: &str) -> anyhow::Result<rust_decimal::Decimal> {
-    rust_decimal::Decimal::from_str_exact(value).with_context(|| format!("invalid decimal {value}"))
+pub fn decimal_array_pg_to_wit(value: Vec<Option<PgNumeric>>) -> Vec<Option<String>> {
+    value.into_iter().map(|opt| opt.map(|d| d.0)).collect()
 }
 
-pub fn decimal_array_pg_to_wit(value: Vec<Option<rust_decimal::Decimal>>) -> Vec<Option<String>> {
-    value.iter().map(|opt| opt.map(|d| d.to_string())).collect()
-}
-
-pub fn decimal_array_wit_to_pg(
-    value: &[Option<String>],
-) -> anyhow::Result<Vec<Option<rust_decimal::Decimal>>> {
+pub fn decimal_array_wit_to_pg(value: &[Option<String>]) -> anyhow::Result<Vec<Option<PgNumeric>>> {
     value
         .iter()
-        .map(|v| match v {
-            None => Ok(None),
-            Some(v) => rust_decimal::Decimal::from_str_exact(v)
-                .with_context(|| format!("invalid decimal {v}"))
-                .map(Some),
-        })
-        .collect::<anyhow::Result<Vec<_>>>()
+        .map(|v| v.as_deref().map(PgNumeric::parse).transpose())
+        .collect()
 }
 
 // Functions to convert between Postgres ranges and the WIT range representations
@@ -284,22 +265,25 @@ mod test {
 
         assert_eq!(arr.len(), pg.len());
         assert_eq!(
-            rust_decimal::Decimal::new(1234, 2),
+            PgNumeric("12.34".to_string()),
             *pg[0].as_ref().expect("some should convert to some")
         );
         assert!(pg[1].is_none(), "none should convert to none");
         assert_eq!(
-            rust_decimal::Decimal::new(123456789987654321, 9),
+            PgNumeric("123456789.987654321".to_string()),
             *pg[2].as_ref().expect("some should convert to some")
         );
+
+        let invalid = vec![Some("12.34".to_string()), Some("twelve".to_string())];
+        assert!(decimal_array_wit_to_pg(&invalid).is_err());
     }
 
     #[test]
     fn can_convert_decimal_array_pg_to_wit() {
         let pg = vec![
-            Some(rust_decimal::Decimal::new(1234, 2)),
+            Some(PgNumeric("12.34".to_string())),
             None,
-            Some(rust_decimal::Decimal::new(123456789987654321, 9)),
+            Some(PgNumeric("123456789.987654321".to_string())),
         ];
 
         let arr = decimal_array_pg_to_wit(pg);
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use bytes::BufMut;
+use tokio_postgres::types::{FromSql, ToSql, Type};
+
+/// The version of Postgres' binary `jsonb` representation.
+const JSONB_VERSION: u8 = 1;
+
+/// A Postgres `json` or `jsonb` value held as JSON text.
+///
+/// The text is passed through as-is rather than being parsed on the host, so
+/// it is validated by the database and values such as large numbers are not
+/// altered in transit.
+#[derive(Debug)]
+pub struct PgJson(pub Vec<u8>);
+
+impl ToSql for PgJson {
+    tokio_postgres::types::to_sql_checked!();
+
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut tokio_postgres::types::private::BytesMut,
+    ) -> Result<tokio_postgres::types::IsNull, Box<dyn std::error::Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        if *ty == Type::JSONB {
+            out.put_u8(JSONB_VERSION);
+        }
+        out.put_slice(&self.0);
+        Ok(tokio_postgres::types::IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool
+    where
+        Self: Sized,
+    {
+        matches!(ty, &Type::JSON | &Type::JSONB)
+    }
+}
+
+impl FromSql<'_> for PgJson {
+    fn from_sql(
+        ty: &Type,
+        raw: &'_ [u8],
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let text = if *ty == Type::JSONB {
+            match raw.split_first() {
+                Some((&JSONB_VERSION, text)) => text,
+                _ => return Err("unsupported jsonb version".into()),
+            }
+        } else {
+            raw
+        };
+        Ok(Self(text.to_vec()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty, &Type::JSON | &Type::JSONB)
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::{anyhow, bail, Result};
+use bytes::{Buf, BufMut};
+use tokio_postgres::types::{FromSql, ToSql, Type};
+
+/// The base of the digits in Postgres' binary `numeric` representation.
+const NBASE: u32 = 10000;
+/// The number of decimal digits in each base-`NBASE` digit.
+const DEC_DIGITS: usize = 4;
+
+const SIGN_POSITIVE: u16 = 0x0000;
+const SIGN_NEGATIVE: u16 = 0x4000;
+const SIGN_NAN: u16 = 0xC000;
+const SIGN_POSITIVE_INFINITY: u16 = 0xD000;
+const SIGN_NEGATIVE_INFINITY: u16 = 0xF000;
+/// The largest display scale Postgres supports.
+const MAX_DSCALE: usize = 0x3FFF;
+
+/// A Postgres `numeric` held as a base-10 string.
+///
+/// Unlike `rust_decimal::Decimal`, this preserves the full precision and
+/// scale of the value (e.g. `1.500` stays `1.500`), and supports `NaN` and
+/// the infinities.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgNumeric(pub String);
+
+impl PgNumeric {
+    /// Validates a base-10 decimal string, e.g. `-123.4500`, `NaN` or
+    /// `Infinity`.
+    pub fn parse(value: &str) -> Result<Self> {
+        Encoded::parse(value)?;
+        Ok(Self(value.to_owned()))
+    }
+}
+
+/// The fields of Postgres' binary `numeric` representation.
+#[derive(Debug, PartialEq)]
+struct Encoded {
+    weight: i16,
+    sign: u16,
+    dscale: u16,
+    digits: Vec<i16>,
+}
+
+impl Encoded {
+    fn parse(value: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid decimal {value}");
+        let trimmed = value.trim();
+        let special = |sign| Self {
+            weight: 0,
+            sign,
+            dscale: 0,
+            digits: vec![],
+        };
+        if trimmed.eq_ignore_ascii_case("nan") {
+            return Ok(special(SIGN_NAN));
+        }
+        let (negative, unsigned) = match trimmed.as_bytes().first() {
+            Some(b'-') => (true, &trimmed[1..]),
+            Some(b'+') => (false, &trimmed[1..]),
+            _ => (false, trimmed),
+        };
+        if unsigned.eq_ignore_ascii_case("infinity") || unsigned.eq_ignore_ascii_case("inf") {
+            return Ok(special(if negative {
+                SIGN_NEGATIVE_INFINITY
+            } else {
+                SIGN_POSITIVE_INFINITY
+            }));
+        }
+
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if (int_part.is_empty() && frac_part.is_empty())
+            || !int_part
+                .bytes()
+                .chain(frac_part.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        if frac_part.len() > MAX_DSCALE {
+            bail!("invalid decimal {value}: scale exceeds {MAX_DSCALE}");
+        }
+        let int_part = int_part.trim_start_matches('0');
+
+        // Align both parts to whole base-NBASE digits around the decimal point
+        let int_pad = (DEC_DIGITS - int_part.len() % DEC_DIGITS) % DEC_DIGITS;
+        let frac_pad = (DEC_DIGITS - frac_part.len() % DEC_DIGITS) % DEC_DIGITS;
+        let decimal_digits: Vec<u8> = std::iter::repeat_n(b'0', int_pad)
+            .chain(int_part.bytes())
+            .chain(frac_part.bytes())
+            .chain(std::iter::repeat_n(b'0', frac_pad))
+            .collect();
+        let mut digits: Vec<i16> = decimal_digits
+            .chunks(DEC_DIGITS)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |acc, d| acc * 10 + i16::from(d - b'0'))
+            })
+            .collect();
+        let int_digits = (int_part.len() + int_pad) / DEC_DIGITS;
+        let mut weight = i64::try_from(int_digits).map_err(|_| invalid())? - 1;
+
+        let leading_zeros = digits.iter().take_while(|d| **d == 0).count();
+        digits.drain(..leading_zeros);
+        weight -= leading_zeros as i64;
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        let dscale = frac_part.len() as u16;
+        if digits.is_empty() {
+            return Ok(Self {
+                weight: 0,
+                sign: SIGN_POSITIVE,
+                dscale,
+                digits,
+            });
+        }
+        let weight = i16::try_from(weight)
+            .map_err(|_| anyhow!("invalid decimal {value}: value out of range"))?;
+        if i16::try_from(digits.len()).is_err() {
+            bail!("invalid decimal {value}: value out of range");
+        }
+        Ok(Self {
+            weight,
+            sign: if negative {
+                SIGN_NEGATIVE
+            } else {
+                SIGN_POSITIVE
+            },
+            dscale,
+            digits,
+        })
+    }
+
+    fn decode(mut raw: &[u8]) -> Result<Self> {
+        if raw.len() < 8 {
+            bail!("invalid numeric: too short");
+        }
+        let ndigits = raw.get_i16();
+        let weight = raw.get_i16();
+        let sign = raw.get_u16();
+        let dscale = raw.get_u16();
+        let ndigits = usize::try_from(ndigits).map_err(|_| anyhow!("invalid numeric"))?;
+        if raw.len() != ndigits * 2 {
+            bail!("invalid numeric: expected {ndigits} digits");
+        }
+        let digits = (0..ndigits).map(|_| raw.get_i16()).collect();
+        Ok(Self {
+            weight,
+            sign,
+            dscale,
+            digits,
+        })
+    }
+
+    fn encode(&self, out: &mut impl BufMut) {
+        out.put_i16(self.digits.len() as i16);
+        out.put_i16(self.weight);
+        out.put_u16(self.sign);
+        out.put_u16(self.dscale);
+        for digit in &self.digits {
+            out.put_i16(*digit);
+        }
+    }
+
+    fn to_decimal_string(&self) -> Result<String> {
+        match self.sign {
+            SIGN_NAN => return Ok("NaN".into()),
+            SIGN_POSITIVE_INFINITY => return Ok("Infinity".into()),
+            SIGN_NEGATIVE_INFINITY => return Ok("-Infinity".into()),
+            SIGN_POSITIVE | SIGN_NEGATIVE => (),
+            sign => bail!("invalid numeric sign {sign:#x}"),
+        }
+        if self.digits.iter().any(|d| !(0..NBASE as i16).contains(d)) {
+            bail!("invalid numeric digit");
+        }
+        let digit = |index: i32| -> i16 {
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| self.digits.get(index))
+                .copied()
+                .unwrap_or(0)
+        };
+        let weight = i32::from(self.weight);
+
+        let mut out = String::new();
+        if self.sign == SIGN_NEGATIVE {
+            out.push('-');
+        }
+        if weight < 0 {
+            out.push('0');
+        } else {
+            out.push_str(&digit(0).to_string());
+            for index in 1..=weight {
+                out.push_str(&format!("{:04}", digit(index)));
+            }
+        }
+        let dscale = usize::from(self.dscale);
+        if dscale > 0 {
+            let mut frac = String::with_capacity(dscale + DEC_DIGITS);
+            let mut index = weight + 1;
+            while frac.len() < dscale {
+                frac.push_str(&format!("{:04}", digit(index)));
+                index += 1;
+            }
+            frac.truncate(dscale);
+            out.push('.');
+            out.push_str(&frac);
+        }
+        Ok(out)
+    }
+}
+
+impl ToSql for PgNumeric {
+    tokio_postgres::types::to_sql_checked!();
+
+    fn to_sql(
+        &self,
+        _ty: &Type,
+        out: &mut tokio_postgres::types::private::BytesMut,
+    ) -> Result<tokio_postgres::types::IsNull, Box<dyn std::error::Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        Encoded::parse(&self.0)?.encode(out);
+        Ok(tokio_postgres::types::IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool
+    where
+        Self: Sized,
+    {
+        matches!(ty, &Type::NUMERIC)
+    }
+}
+
+impl FromSql<'_> for PgNumeric {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'_ [u8],
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(Self(Encoded::decode(raw)?.to_decimal_string()?))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty, &Type::NUMERIC)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(value: &str) -> String {
+        let mut buf = Vec::new();
+        Encoded::parse(value).unwrap().encode(&mut buf);
+        Encoded::decode(&buf).unwrap().to_decimal_string().unwrap()
+    }
+
+    #[test]
+    fn encodes_like_postgres() {
+        // Values as sent by Postgres for `SELECT '<value>'::numeric`
+        let encoded = Encoded::parse("12345.678").unwrap();
+        assert_eq!((encoded.weight, encoded.dscale), (1, 3));
+        assert_eq!(encoded.digits, [1, 2345, 6780]);
+
+        let encoded = Encoded::parse("-0.00012").unwrap();
+        assert_eq!(
+            (encoded.weight, encoded.sign, encoded.dscale),
+            (-1, SIGN_NEGATIVE, 5)
+        );
+        assert_eq!(encoded.digits, [1, 2000]);
+
+        let encoded = Encoded::parse("100000000").unwrap();
+        assert_eq!((encoded.weight, encoded.dscale), (2, 0));
+        assert_eq!(encoded.digits, [1]);
+    }
+
+    #[test]
+    fn round_trips_preserving_precision() {
+        for value in [
+            "0",
+            "0.000",
+            "1.500",
+            "-42",
+            "12345.678",
+            "0.00012",
+            "-0.00012",
+            "100000000",
+            "123456789012345678901234567890.123456789012345678901234567890",
+            "NaN",
+            "Infinity",
+            "-Infinity",
+        ] {
+            assert_eq!(round_trip(value), value);
+        }
+        assert_eq!(round_trip("+007.10"), "7.10");
+        assert_eq!(round_trip(".5"), "0.5");
+        assert_eq!(round_trip("-0"), "0");
+    }
+
+    #[test]
+    fn rejects_invalid_decimals() {
+        for value in ["", "-", ".", "1.2.3", "1e5", "abc", "1,000"] {
+            assert!(PgNumeric::parse(value).is_err(), "{value:?}");
+        }
+    }
+}
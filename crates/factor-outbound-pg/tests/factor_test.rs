@@ -1,4 +1,9 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
 use anyhow::{bail, Result};
+use spin_core::wasmtime::component::Resource;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_outbound_pg::client::Client;
 use spin_factor_outbound_pg::client::ClientFactory;
@@ -10,7 +15,10 @@ use spin_world::async_trait;
 use spin_world::spin::postgres4_0_0::postgres::Error as PgError;
 use spin_world::spin::postgres4_0_0::postgres::HostConnection;
 use spin_world::spin::postgres4_0_0::postgres::{self as v2};
-use spin_world::spin::postgres4_0_0::postgres::{ParameterValue, RowSet};
+use spin_world::spin::postgres4_0_0::postgres::{
+    Column, DbDataType, DbValue, ParameterValue, Row, RowSet,
+};
+use spin_world::spin::postgres4_1_0::cursor::{Host as _, HostRowStream};
 
 #[derive(RuntimeFactors)]
 struct TestFactors {
@@ -104,26 +112,242 @@ async fn exercise_query() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn exercise_execute_streaming() -> anyhow::Result<()> {
+    let address = "postgres://localhost:5432/streaming";
+    let mut state = test_env().build_instance_state().await?;
+
+    let connection = state.pg.open(address.to_string()).await?;
+    let stream = state
+        .pg
+        .execute_streaming(connection, "SELECT * FROM test".to_string(), vec![])
+        .await?;
+    assert_eq!(
+        state
+            .pg
+            .columns(Resource::new_borrow(stream.rep()))
+            .await?
+            .len(),
+        1
+    );
+
+    let mut batches = vec![];
+    loop {
+        let rows = state.pg.next(Resource::new_borrow(stream.rep()), 2).await?;
+        if rows.is_empty() {
+            break;
+        }
+        batches.push(rows.len());
+    }
+    assert_eq!(batches, [2, 2, 1]);
+    assert!(!closed_cursors().contains(&address.to_owned()));
+
+    HostRowStream::drop(&mut state.pg, stream).await?;
+    assert!(closed_cursors().contains(&address.to_owned()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn abandoned_row_stream_is_closed() -> anyhow::Result<()> {
+    let address = "postgres://localhost:5432/abandoned";
+    let mut state = test_env().build_instance_state().await?;
+
+    let connection = state.pg.open(address.to_string()).await?;
+    state
+        .pg
+        .execute_streaming(connection, "SELECT * FROM test".to_string(), vec![])
+        .await?;
+    // As if the instance were torn down without dropping the stream
+    drop(state);
+
+    for _ in 0..100 {
+        if closed_cursors().contains(&address.to_owned()) {
+            return Ok(());
+        }
+        tokio::task::yield_now().await;
+    }
+    bail!("row stream was not closed");
+}
+
+#[tokio::test]
+async fn connection_is_reestablished_after_server_restart() -> anyhow::Result<()> {
+    let address = "postgres://localhost:5432/restarted";
+    let mut state = test_env().build_instance_state().await?;
+
+    let connection = state.pg.open(address.to_string()).await?;
+    restart_server(address);
+    state
+        .pg
+        .query(
+            Resource::new_borrow(connection.rep()),
+            "SELECT * FROM test".to_string(),
+            vec![],
+        )
+        .await?;
+    state
+        .pg
+        .execute(connection, "SELECT * FROM test".to_string(), vec![])
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn losing_an_open_transaction_is_reported_once() -> anyhow::Result<()> {
+    let address = "postgres://localhost:5432/in-transaction";
+    let mut state = test_env().build_instance_state().await?;
+
+    let connection = state.pg.open(address.to_string()).await?;
+    state
+        .pg
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            "BEGIN".to_string(),
+            vec![],
+        )
+        .await?;
+    restart_server(address);
+    let err = state
+        .pg
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            "UPDATE test SET x = 1".to_string(),
+            vec![],
+        )
+        .await
+        .unwrap_err();
+    let PgError::ConnectionFailed(message) = err else {
+        bail!("expected ConnectionFailed, got {err:?}");
+    };
+    assert!(message.contains("open transaction"), "{message}");
+    // The connection was re-established, so the guest can start over
+    state
+        .pg
+        .execute(connection, "BEGIN".to_string(), vec![])
+        .await?;
+    assert_eq!(connection_attempts(address), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_to_down_server_fails_promptly() -> anyhow::Result<()> {
+    let address = "postgres://localhost:5432/down";
+    let mut state = test_env().build_instance_state().await?;
+
+    let connection = state.pg.open(address.to_string()).await?;
+    SERVERS_DOWN.lock().unwrap().push(address.to_owned());
+    restart_server(address);
+    for _ in 0..2 {
+        let err = state
+            .pg
+            .query(
+                Resource::new_borrow(connection.rep()),
+                "SELECT * FROM test".to_string(),
+                vec![],
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PgError::ConnectionFailed(_)), "{err:?}");
+    }
+    // The second call backed off rather than trying again
+    assert_eq!(connection_attempts(address), 2);
+
+    Ok(())
+}
+
+/// Closes the connections of the mock clients to the given address.
+fn restart_server(address: &str) {
+    *RESTARTS
+        .lock()
+        .unwrap()
+        .entry(address.to_owned())
+        .or_default() += 1;
+}
+
+fn restarts(address: &str) -> usize {
+    RESTARTS
+        .lock()
+        .unwrap()
+        .get(address)
+        .copied()
+        .unwrap_or_default()
+}
+
+fn connection_attempts(address: &str) -> usize {
+    CONNECTION_ATTEMPTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|a| *a == address)
+        .count()
+}
+
+/// How many times the mock server at each address has restarted.
+static RESTARTS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+/// The addresses of mock servers which refuse connections.
+static SERVERS_DOWN: Mutex<Vec<String>> = Mutex::new(vec![]);
+/// The address of each attempt to get a mock client.
+static CONNECTION_ATTEMPTS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// The addresses of the mock clients on which a cursor has been closed.
+fn closed_cursors() -> Vec<String> {
+    CLOSED_CURSORS.lock().unwrap().clone()
+}
+
+static CLOSED_CURSORS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// The number of rows in a mock cursor.
+const CURSOR_ROWS: usize = 5;
+
 // TODO: We can expand this mock to track calls and simulate return values
 #[derive(Default)]
 pub struct MockClientFactory {}
-pub struct MockClient {}
+pub struct MockClient {
+    address: String,
+    // How many times the server had restarted when the client connected
+    restarts: usize,
+    fetched: AtomicUsize,
+}
+
+impl MockClient {
+    fn check_open(&self) -> Result<(), v2::Error> {
+        if self.is_closed() {
+            return Err(v2::Error::ConnectionFailed("connection closed".into()));
+        }
+        Ok(())
+    }
+}
 
 #[async_trait]
 impl ClientFactory for MockClientFactory {
     type Client = MockClient;
-    async fn get_client(&self, _address: &str) -> Result<Self::Client> {
-        Ok(MockClient {})
+    async fn get_client(&self, address: &str) -> Result<Self::Client> {
+        CONNECTION_ATTEMPTS.lock().unwrap().push(address.to_owned());
+        if SERVERS_DOWN.lock().unwrap().iter().any(|a| a == address) {
+            bail!("connection refused");
+        }
+        Ok(MockClient {
+            address: address.to_owned(),
+            restarts: restarts(address),
+            fetched: AtomicUsize::new(0),
+        })
     }
 }
 
 #[async_trait]
 impl Client for MockClient {
+    fn is_closed(&self) -> bool {
+        restarts(&self.address) != self.restarts
+    }
+
     async fn execute(
         &self,
         _statement: String,
         _params: Vec<ParameterValue>,
     ) -> Result<u64, v2::Error> {
+        self.check_open()?;
         Ok(0)
     }
 
@@ -132,9 +356,36 @@ impl Client for MockClient {
         _statement: String,
         _params: Vec<ParameterValue>,
     ) -> Result<RowSet, v2::Error> {
+        self.check_open()?;
         Ok(RowSet {
             columns: vec![],
             rows: vec![],
         })
     }
+
+    async fn declare_cursor(
+        &self,
+        _name: &str,
+        _statement: String,
+        _params: Vec<ParameterValue>,
+    ) -> Result<Vec<Column>, v2::Error> {
+        Ok(vec![Column {
+            name: "id".into(),
+            data_type: DbDataType::Int32,
+        }])
+    }
+
+    async fn fetch(&self, _name: &str, max_rows: u32) -> Result<Vec<Row>, v2::Error> {
+        let fetched = self.fetched.load(Ordering::SeqCst);
+        let count = (max_rows as usize).min(CURSOR_ROWS - fetched);
+        self.fetched.store(fetched + count, Ordering::SeqCst);
+        Ok((fetched..fetched + count)
+            .map(|id| vec![DbValue::Int32(id as i32)])
+            .collect())
+    }
+
+    async fn close_cursor(&self, _name: &str) -> Result<(), v2::Error> {
+        CLOSED_CURSORS.lock().unwrap().push(self.address.clone());
+        Ok(())
+    }
 }
@@ -2,6 +2,8 @@ use anyhow::Result;
 use redis::{aio::MultiplexedConnection, AsyncCommands, FromRedisValue, Value};
 use spin_core::wasmtime::component::Resource;
 use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
+use spin_factor_outbound_networking::reconnect::ManagedConnection;
+use spin_world::spin::redis::batch;
 use spin_world::v1::{redis as v1, redis_types};
 use spin_world::v2::redis::{
     self as v2, Connection as RedisConnection, Error, RedisParameter, RedisResult,
@@ -11,7 +13,8 @@ use tracing::{instrument, Level};
 
 pub struct InstanceState {
     pub allowed_hosts: OutboundAllowedHosts,
-    pub connections: spin_resource_table::Table<MultiplexedConnection>,
+    pub connections: spin_resource_table::Table<ManagedConnection<MultiplexedConnection>>,
+    pub(crate) subscriptions: spin_resource_table::Table<crate::pubsub::Subscription>,
 }
 
 impl InstanceState {
@@ -29,20 +32,70 @@ impl InstanceState {
             .await
             .map_err(other_error)?;
         self.connections
-            .push(conn)
+            .push(ManagedConnection::new(address, conn))
             .map(Resource::new_own)
             .map_err(|_| Error::TooManyConnections)
     }
 
+    /// The address the given connection was opened to.
+    pub(crate) fn get_address(
+        &self,
+        connection: &Resource<RedisConnection>,
+    ) -> Result<&str, Error> {
+        self.connections
+            .get(connection.rep())
+            .map(ManagedConnection::address)
+            .ok_or(Error::Other(
+                "could not find connection for resource".into(),
+            ))
+    }
+
+    /// The given connection, reconnecting first if it has been lost, e.g.
+    /// because the server restarted.
     async fn get_conn(
         &mut self,
         connection: Resource<RedisConnection>,
     ) -> Result<&mut MultiplexedConnection, Error> {
-        self.connections
+        self.get_conn_for(connection, std::iter::empty()).await
+    }
+
+    /// The given connection for running the guest's `commands`, reconnecting
+    /// first if it has been lost.
+    ///
+    /// Reconnecting fails the commands if the guest may have left state in
+    /// the lost session, such as a transaction or a selected database; see
+    /// [`ManagedConnection::note_session_state`].
+    async fn get_conn_for<'c>(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        commands: impl IntoIterator<Item = &'c str>,
+    ) -> Result<&mut MultiplexedConnection, Error> {
+        let managed = self
+            .connections
             .get_mut(connection.rep())
             .ok_or(Error::Other(
                 "could not find connection for resource".into(),
-            ))
+            ))?;
+        if managed.is_stale() {
+            if let Some(conn) = managed.connection() {
+                let pong: redis::RedisResult<()> = redis::cmd("PING").query_async(conn).await;
+                if pong.is_err() {
+                    managed.mark_lost();
+                }
+            }
+        }
+        managed
+            .get_or_reconnect(|address| async move {
+                redis::Client::open(address)?
+                    .get_multiplexed_async_connection()
+                    .await
+            })
+            .await
+            .map_err(other_error)?;
+        if commands.into_iter().any(command_sets_session_state) {
+            managed.note_session_state();
+        }
+        Ok(managed.connection().expect("connection was established"))
     }
 }
 
@@ -180,18 +233,9 @@ impl v2::HostConnection for crate::InstanceState {
         command: String,
         arguments: Vec<RedisParameter>,
     ) -> Result<Vec<RedisResult>, Error> {
-        let conn = self.get_conn(connection).await?;
-        let mut cmd = redis::cmd(&command);
-        arguments.iter().for_each(|value| match value {
-            RedisParameter::Int64(v) => {
-                cmd.arg(v);
-            }
-            RedisParameter::Binary(v) => {
-                cmd.arg(v);
-            }
-        });
-
-        cmd.query_async::<RedisResults>(conn)
+        let conn = self.get_conn_for(connection, [command.as_str()]).await?;
+        redis_command(&command, &arguments)
+            .query_async::<RedisResults>(conn)
             .await
             .map(|values| values.0)
             .map_err(other_error)
@@ -203,7 +247,56 @@ impl v2::HostConnection for crate::InstanceState {
     }
 }
 
-fn other_error(e: impl std::fmt::Display) -> Error {
+impl batch::Host for crate::InstanceState {
+    #[instrument(name = "spin_outbound_redis.pipeline", skip(self, connection, commands), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", db.operation.batch.size = commands.len()))]
+    async fn pipeline(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        commands: Vec<(String, Vec<RedisParameter>)>,
+    ) -> Result<Vec<Vec<RedisResult>>, Error> {
+        let conn = self
+            .get_conn_for(
+                connection,
+                commands.iter().map(|(command, _)| command.as_str()),
+            )
+            .await?;
+        let mut pipeline = redis::pipe();
+        for (command, arguments) in &commands {
+            pipeline.add_command(redis_command(command, arguments));
+        }
+        pipeline
+            .query_async::<Vec<RedisResults>>(conn)
+            .await
+            .map(|results| results.into_iter().map(|values| values.0).collect())
+            .map_err(other_error)
+    }
+}
+
+/// Whether a command may leave state in the session which a new session
+/// wouldn't have, e.g. by starting a transaction or selecting a database.
+fn command_sets_session_state(command: &str) -> bool {
+    [
+        "SELECT", "MULTI", "WATCH", "CLIENT", "AUTH", "HELLO", "READONLY",
+    ]
+    .iter()
+    .any(|stateful| command.eq_ignore_ascii_case(stateful))
+}
+
+/// Builds the command for a guest's `execute`, or an entry in its pipeline.
+fn redis_command(command: &str, arguments: &[RedisParameter]) -> redis::Cmd {
+    let mut cmd = redis::cmd(command);
+    arguments.iter().for_each(|value| match value {
+        RedisParameter::Int64(v) => {
+            cmd.arg(v);
+        }
+        RedisParameter::Binary(v) => {
+            cmd.arg(v);
+        }
+    });
+    cmd
+}
+
+pub(crate) fn other_error(e: impl std::fmt::Display) -> Error {
     Error::Other(e.to_string())
 }
 
@@ -1,4 +1,5 @@
 mod host;
+mod pubsub;
 
 use host::InstanceState;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
@@ -27,6 +28,8 @@ impl Factor for OutboundRedisFactor {
     fn init(&mut self, ctx: &mut impl spin_factors::InitContext<Self>) -> anyhow::Result<()> {
         ctx.link_bindings(spin_world::v1::redis::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(spin_world::v2::redis::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(spin_world::spin::redis::batch::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(spin_world::spin::redis::pubsub::add_to_linker::<_, FactorData<Self>>)?;
         Ok(())
     }
 
@@ -47,6 +50,7 @@ impl Factor for OutboundRedisFactor {
         Ok(InstanceState {
             allowed_hosts,
             connections: spin_resource_table::Table::new(1024),
+            subscriptions: spin_resource_table::Table::new(1024),
         })
     }
 }
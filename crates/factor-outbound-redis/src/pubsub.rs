@@ -0,0 +1,63 @@
+use anyhow::Result;
+use futures::stream::{BoxStream, StreamExt};
+use spin_core::wasmtime::component::Resource;
+use spin_world::spin::redis::pubsub;
+use spin_world::v2::redis::{Connection as RedisConnection, Error};
+use tracing::{instrument, Level};
+
+use crate::host::other_error;
+use crate::InstanceState;
+
+/// The state of a `subscription` resource: the messages arriving on a
+/// connection of its own, which is closed when the subscription is dropped.
+pub(crate) struct Subscription {
+    messages: BoxStream<'static, redis::Msg>,
+}
+
+impl pubsub::Host for InstanceState {
+    #[instrument(name = "spin_outbound_redis.subscribe", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis", otel.name = format!("SUBSCRIBE {}", channel)))]
+    async fn subscribe(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        channel: String,
+    ) -> Result<Resource<pubsub::Subscription>, Error> {
+        // A subscribed connection can only be used for pub/sub commands, so
+        // the guest's connection stays free for others
+        let address = self.get_address(&connection)?.to_owned();
+        let mut pubsub = redis::Client::open(address)
+            .map_err(|_| Error::InvalidAddress)?
+            .get_async_pubsub()
+            .await
+            .map_err(other_error)?;
+        pubsub.subscribe(&channel).await.map_err(other_error)?;
+        self.subscriptions
+            .push(Subscription {
+                messages: pubsub.into_on_message().boxed(),
+            })
+            .map(Resource::new_own)
+            .map_err(|_| Error::TooManyConnections)
+    }
+}
+
+impl pubsub::HostSubscription for InstanceState {
+    #[instrument(name = "spin_outbound_redis.next_message", skip(self, subscription), err(level = Level::INFO), fields(otel.kind = "client", db.system = "redis"))]
+    async fn next_message(
+        &mut self,
+        subscription: Resource<pubsub::Subscription>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let subscription = self
+            .subscriptions
+            .get_mut(subscription.rep())
+            .ok_or_else(|| Error::Other("could not find subscription for resource".into()))?;
+        Ok(subscription
+            .messages
+            .next()
+            .await
+            .map(|message| message.get_payload_bytes().to_vec()))
+    }
+
+    async fn drop(&mut self, subscription: Resource<pubsub::Subscription>) -> Result<()> {
+        self.subscriptions.remove(subscription.rep());
+        Ok(())
+    }
+}
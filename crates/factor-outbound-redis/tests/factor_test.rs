@@ -1,10 +1,22 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
 use anyhow::bail;
+use spin_core::wasmtime::component::Resource;
+use spin_factor_outbound_networking::reconnect::STALE_AFTER;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_outbound_redis::OutboundRedisFactor;
 use spin_factor_variables::VariablesFactor;
 use spin_factors::{anyhow, RuntimeFactors};
 use spin_factors_test::{toml, TestEnvironment};
-use spin_world::v2::redis::{Error, HostConnection};
+use spin_world::spin::redis::batch::Host as _;
+use spin_world::spin::redis::pubsub::{Host as _, HostSubscription};
+use spin_world::v2::redis::{Connection, Error, HostConnection, RedisParameter, RedisResult};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::{tcp::OwnedReadHalf, TcpListener, TcpStream};
+use tokio::task::JoinHandle;
 
 #[derive(RuntimeFactors)]
 struct TestFactors {
@@ -41,3 +53,263 @@ async fn no_outbound_hosts_fails() -> anyhow::Result<()> {
     assert!(matches!(err, Error::InvalidAddress));
     Ok(())
 }
+
+fn test_env() -> TestEnvironment<TestFactors> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        redis: OutboundRedisFactor::new(),
+    };
+    TestEnvironment::new(factors).extend_manifest(toml! {
+        spin_manifest_version = 2
+        application.name = "test-app"
+        [[trigger.test]]
+
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["redis://127.0.0.1:*"]
+    })
+}
+
+#[tokio::test]
+async fn idle_connection_is_reestablished_after_server_restart() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(serve(listener));
+
+    let mut state = test_env().build_instance_state().await?;
+    let connection = state.redis.open(format!("redis://{address}")).await?;
+    if let Err(err) = set(&mut state, &connection).await {
+        bail!("expected Ok, got {err:?}");
+    }
+
+    let server = restart(server, address).await?;
+    tokio::time::sleep(STALE_AFTER).await;
+    if let Err(err) = set(&mut state, &connection).await {
+        bail!("expected Ok after reconnecting, got {err:?}");
+    }
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn connection_to_down_server_fails_promptly() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(serve(listener));
+
+    let mut state = test_env().build_instance_state().await?;
+    let connection = state.redis.open(format!("redis://{address}")).await?;
+    server.abort();
+    _ = server.await;
+    tokio::time::sleep(STALE_AFTER).await;
+
+    let Err(Error::Other(err)) = set(&mut state, &connection).await else {
+        bail!("expected reconnecting to fail");
+    };
+    assert!(err.contains("reconnecting failed"), "{err}");
+    // Backs off rather than trying again
+    let Err(Error::Other(err)) = set(&mut state, &connection).await else {
+        bail!("expected reconnecting to fail");
+    };
+    assert!(err.contains("not trying again"), "{err}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn pipeline_sends_commands_together() -> anyhow::Result<()> {
+    const COUNT: usize = 10;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (conn, _) = listener.accept().await?;
+        answer_incrs_together(conn, COUNT).await
+    });
+
+    let mut state = test_env().build_instance_state().await?;
+    let connection = state.redis.open(format!("redis://{address}")).await?;
+    let commands = (0..COUNT)
+        .map(|_| {
+            (
+                "INCR".to_string(),
+                vec![RedisParameter::Binary(b"counter".to_vec())],
+            )
+        })
+        .collect();
+    // Commands sent one at a time would never be answered
+    let pipeline = state.redis.pipeline(connection, commands);
+    let results = match tokio::time::timeout(Duration::from_secs(5), pipeline).await {
+        Ok(results) => results?,
+        Err(_) => bail!("expected the commands to be sent as one pipeline"),
+    };
+
+    assert_eq!(results.len(), COUNT);
+    for (expected, result) in (1..).zip(&results) {
+        assert!(
+            matches!(result.as_slice(), [RedisResult::Int64(n)] if *n == expected),
+            "expected {expected}, got {result:?}"
+        );
+    }
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscription_receives_messages_on_its_own_connection() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let connections = Arc::new(AtomicUsize::new(0));
+    let server = tokio::spawn(serve_subscribers(listener, connections.clone()));
+
+    let mut state = test_env().build_instance_state().await?;
+    let connection = state.redis.open(format!("redis://{address}")).await?;
+    let subscription = state
+        .redis
+        .subscribe(Resource::new_borrow(connection.rep()), "news".into())
+        .await?;
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+    let message = state
+        .redis
+        .next_message(Resource::new_borrow(subscription.rep()))
+        .await?;
+    assert_eq!(message.as_deref(), Some(&b"extra extra"[..]));
+    // The guest's connection is still usable for other commands
+    if let Err(err) = set(&mut state, &connection).await {
+        bail!("expected Ok, got {err:?}");
+    }
+
+    HostSubscription::drop(&mut state.redis, subscription).await?;
+    server.abort();
+    Ok(())
+}
+
+async fn set(
+    state: &mut TestFactorsInstanceState,
+    connection: &Resource<Connection>,
+) -> Result<(), Error> {
+    state
+        .redis
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            "SET".into(),
+            vec![
+                RedisParameter::Binary(b"key".to_vec()),
+                RedisParameter::Binary(b"value".to_vec()),
+            ],
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Stops the mock server, closing its connections, and starts it again on
+/// the same address.
+async fn restart(
+    server: JoinHandle<anyhow::Result<()>>,
+    address: SocketAddr,
+) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+    server.abort();
+    _ = server.await;
+    let listener = TcpListener::bind(address).await?;
+    Ok(tokio::spawn(serve(listener)))
+}
+
+/// A mock Redis server which answers every command with OK.
+async fn serve(listener: TcpListener) -> anyhow::Result<()> {
+    // Aborting the server drops the set, closing its connections
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        let (conn, _) = listener.accept().await?;
+        connections.spawn(answer_commands(conn));
+    }
+}
+
+/// A mock Redis server which answers every command with OK, except
+/// SUBSCRIBE, which it confirms before publishing a message to each channel.
+async fn serve_subscribers(
+    listener: TcpListener,
+    connections: Arc<AtomicUsize>,
+) -> anyhow::Result<()> {
+    let mut tasks = tokio::task::JoinSet::new();
+    loop {
+        let (conn, _) = listener.accept().await?;
+        connections.fetch_add(1, Ordering::SeqCst);
+        tasks.spawn(answer_subscribes(conn));
+    }
+}
+
+async fn answer_subscribes(conn: TcpStream) -> anyhow::Result<()> {
+    let (read, mut write) = conn.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Some(command) = read_command(&mut lines).await? {
+        if !command[0].eq_ignore_ascii_case("SUBSCRIBE") {
+            write.write_all(b"+OK\r\n").await?;
+            continue;
+        }
+        for (n, channel) in (1..).zip(&command[1..]) {
+            let confirmation = format!(
+                "*3\r\n$9\r\nsubscribe\r\n${}\r\n{channel}\r\n:{n}\r\n",
+                channel.len()
+            );
+            write.write_all(confirmation.as_bytes()).await?;
+            let message = format!(
+                "*3\r\n$7\r\nmessage\r\n${}\r\n{channel}\r\n$11\r\nextra extra\r\n",
+                channel.len()
+            );
+            write.write_all(message.as_bytes()).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn answer_commands(conn: TcpStream) -> anyhow::Result<()> {
+    let (read, mut write) = conn.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while read_command(&mut lines).await?.is_some() {
+        write.write_all(b"+OK\r\n").await?;
+    }
+    Ok(())
+}
+
+/// Answers every command with OK, except INCR, which is answered only once
+/// `count` INCRs have arrived, as if each incremented the same counter.
+async fn answer_incrs_together(conn: TcpStream, count: usize) -> anyhow::Result<()> {
+    let (read, mut write) = conn.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut incrs = 0;
+    while let Some(command) = read_command(&mut lines).await? {
+        if !command[0].eq_ignore_ascii_case("INCR") {
+            write.write_all(b"+OK\r\n").await?;
+            continue;
+        }
+        incrs += 1;
+        if incrs == count {
+            for n in 1..=count {
+                write.write_all(format!(":{n}\r\n").as_bytes()).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the next command, as its name followed by its arguments.
+async fn read_command(
+    lines: &mut Lines<BufReader<OwnedReadHalf>>,
+) -> anyhow::Result<Option<Vec<String>>> {
+    while let Some(line) = lines.next_line().await? {
+        // Each command is an array of bulk strings, each taking two lines
+        let Some(len) = line.strip_prefix('*') else {
+            continue;
+        };
+        let mut command = vec![];
+        for _ in 0..len.parse::<usize>()? {
+            lines.next_line().await?;
+            command.push(lines.next_line().await?.unwrap_or_default());
+        }
+        return Ok(Some(command));
+    }
+    Ok(None)
+}
@@ -0,0 +1,109 @@
+//! Formatting of query plans from SQLite's `EXPLAIN QUERY PLAN`.
+//!
+//! Each row of a query plan is a step with an `id`, the `parent` id of the
+//! step it is nested in (zero for top level steps), and a `detail` describing
+//! it. Plans are formatted as trees, in the same way as the `sqlite3` shell.
+
+use spin_world::spin::sqlite3_0_0::sqlite as v3;
+
+/// The query explaining the plan of `statement`.
+pub fn explain_sql(statement: &str) -> Result<String, String> {
+    let statement = statement.trim();
+    if statement.is_empty() {
+        return Err("statement must not be empty".into());
+    }
+    Ok(format!("EXPLAIN QUERY PLAN {statement}"))
+}
+
+/// Formats the result of an `EXPLAIN QUERY PLAN` query as a tree.
+pub fn format_plan(result: &v3::QueryResult) -> Result<String, String> {
+    let steps = result
+        .rows
+        .iter()
+        .map(|row| match row.values.as_slice() {
+            [v3::Value::Integer(id), v3::Value::Integer(parent), _, v3::Value::Text(detail)] => {
+                Ok((*id, *parent, detail.as_str()))
+            }
+            _ => Err(format!("unexpected query plan row: {:?}", row.values)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut plan = String::from("QUERY PLAN\n");
+    write_steps(&mut plan, &steps, 0, "");
+    Ok(plan)
+}
+
+/// Writes the steps nested in `parent`, and recursively their own steps.
+fn write_steps(plan: &mut String, steps: &[(i64, i64, &str)], parent: i64, prefix: &str) {
+    // A step is never nested in itself, which also guards against cycles
+    let children = steps
+        .iter()
+        .filter(|(id, step_parent, _)| *step_parent == parent && *id > parent)
+        .collect::<Vec<_>>();
+    for (i, (id, _, detail)) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        plan.push_str(prefix);
+        plan.push_str(if last { "`--" } else { "|--" });
+        plan.push_str(detail);
+        plan.push('\n');
+        let prefix = format!("{prefix}{}", if last { "   " } else { "|  " });
+        write_steps(plan, steps, *id, &prefix);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(steps: &[(i64, i64, &str)]) -> v3::QueryResult {
+        v3::QueryResult {
+            columns: vec![
+                "id".into(),
+                "parent".into(),
+                "notused".into(),
+                "detail".into(),
+            ],
+            rows: steps
+                .iter()
+                .map(|(id, parent, detail)| v3::RowResult {
+                    values: vec![
+                        v3::Value::Integer(*id),
+                        v3::Value::Integer(*parent),
+                        v3::Value::Integer(0),
+                        v3::Value::Text(detail.to_string()),
+                    ],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn plans_are_formatted_as_trees() {
+        let result = plan(&[
+            (2, 0, "COMPOUND QUERY"),
+            (3, 2, "LEFT-MOST SUBQUERY"),
+            (6, 3, "SCAN a"),
+            (9, 2, "UNION ALL"),
+            (12, 9, "SCAN b"),
+            (20, 0, "USE TEMP B-TREE FOR ORDER BY"),
+        ]);
+        assert_eq!(
+            format_plan(&result).unwrap(),
+            "QUERY PLAN
+|--COMPOUND QUERY
+|  |--LEFT-MOST SUBQUERY
+|  |  `--SCAN a
+|  `--UNION ALL
+|     `--SCAN b
+`--USE TEMP B-TREE FOR ORDER BY
+"
+        );
+    }
+
+    #[test]
+    fn unexpected_rows_are_rejected() {
+        let mut result = plan(&[(2, 0, "SCAN a")]);
+        result.rows[0].values.pop();
+        assert!(format_plan(&result).is_err());
+        assert!(explain_sql("  ").is_err());
+    }
+}
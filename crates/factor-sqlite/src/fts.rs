@@ -0,0 +1,103 @@
+//! SQL for full-text search indexes, built on SQLite's FTS5 extension.
+//!
+//! An index on `table` is an external content FTS5 table named `<table>_fts`,
+//! which stores only the index and reads column values from `table` itself.
+//! Triggers on `table` keep the index in sync with its rows.
+
+/// The statements creating and populating a full-text search index.
+///
+/// All the statements are idempotent, so recreating an existing index just
+/// rebuilds it.
+pub fn create_index_sql(table: &str, columns: &[String]) -> Result<String, String> {
+    if table.is_empty() {
+        return Err("table name must not be empty".into());
+    }
+    if columns.is_empty() {
+        return Err("at least one column must be indexed".into());
+    }
+    if columns.iter().any(|c| c.is_empty()) {
+        return Err("column names must not be empty".into());
+    }
+
+    let fts_table = index_table(table);
+    let fts = quote(&fts_table);
+    let source = quote(table);
+    let trigger = |suffix: &str| quote(&format!("{fts_table}_{suffix}"));
+    let columns: Vec<String> = columns.iter().map(|c| quote(c)).collect();
+    let column_list = columns.join(", ");
+    let values = |row: &str| -> String {
+        columns
+            .iter()
+            .map(|c| format!("{row}.{c}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let (new_values, old_values) = (values("new"), values("old"));
+    let insert =
+        format!("INSERT INTO {fts}(rowid, {column_list}) VALUES (new.rowid, {new_values});");
+    let delete = format!(
+        "INSERT INTO {fts}({fts}, rowid, {column_list}) VALUES ('delete', old.rowid, {old_values});"
+    );
+    let ai = trigger("ai");
+    let ad = trigger("ad");
+    let au = trigger("au");
+
+    Ok(format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {fts} USING fts5({column_list}, content={content});
+CREATE TRIGGER IF NOT EXISTS {ai} AFTER INSERT ON {source} BEGIN {insert} END;
+CREATE TRIGGER IF NOT EXISTS {ad} AFTER DELETE ON {source} BEGIN {delete} END;
+CREATE TRIGGER IF NOT EXISTS {au} AFTER UPDATE ON {source} BEGIN {delete} {insert} END;
+INSERT INTO {fts}({fts}) VALUES ('rebuild');",
+        content = quote_literal(table),
+    ))
+}
+
+/// The query searching a full-text search index, taking the search query and
+/// row limit as parameters `?1` and `?2`.
+///
+/// Rows of the indexed table are returned, best matches first.
+pub fn search_sql(table: &str) -> String {
+    let fts = quote(&index_table(table));
+    let source = quote(table);
+    format!(
+        "SELECT {source}.* FROM {fts} JOIN {source} ON {source}.rowid = {fts}.rowid WHERE {fts} MATCH ?1 ORDER BY {fts}.rank LIMIT ?2"
+    )
+}
+
+/// The name of the FTS5 table indexing `table`.
+fn index_table(table: &str) -> String {
+    format!("{table}_fts")
+}
+
+/// Quotes an SQL identifier.
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Quotes an SQL string literal.
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_are_quoted() {
+        let sql = create_index_sql("my \"notes\"", &["body".into()]).unwrap();
+        assert!(sql.contains("CREATE VIRTUAL TABLE IF NOT EXISTS \"my \"\"notes\"\"_fts\""));
+        assert!(sql.contains("content='my \"notes\"'"));
+        assert!(sql.contains("AFTER INSERT ON \"my \"\"notes\"\"\""));
+
+        let sql = search_sql("it's");
+        assert!(sql.starts_with("SELECT \"it's\".* FROM \"it's_fts\""));
+    }
+
+    #[test]
+    fn invalid_indexes_are_rejected() {
+        assert!(create_index_sql("", &["body".into()]).is_err());
+        assert!(create_index_sql("notes", &[]).is_err());
+        assert!(create_index_sql("notes", &["".into()]).is_err());
+    }
+}
@@ -1,9 +1,12 @@
 use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 
+use spin_factor_audit::{operation, Auditor};
 use spin_factors::wasmtime::component::Resource;
 use spin_factors::{anyhow, SelfInstanceBuilder};
-use spin_world::spin::sqlite::sqlite as v3;
+use spin_world::spin::sqlite3_0_0::sqlite as v3;
+use spin_world::spin::sqlite3_1_0::{attach, backup, explain, fts, vector};
 use spin_world::v1::sqlite as v1;
 use spin_world::v2::sqlite as v2;
 use tracing::field::Empty;
@@ -13,27 +16,68 @@ use crate::{Connection, ConnectionCreator};
 
 pub struct InstanceState {
     allowed_databases: Arc<HashSet<String>>,
-    /// A resource table of connections.
-    connections: spin_resource_table::Table<Box<dyn Connection>>,
+    /// The allowed databases which the component may only read.
+    read_only_databases: Arc<HashSet<String>>,
+    /// Whether the component may back up its allowed databases.
+    backup_allowed: bool,
+    /// Whether the component may create and search vector indexes.
+    vector_search_allowed: bool,
+    /// Whether the component may inspect the query plans of statements.
+    explain_allowed: bool,
+    /// A resource table of connections, with the label of the database each
+    /// connection was opened to.
+    connections: spin_resource_table::Table<(String, Box<dyn Connection>)>,
     /// A map from database label to connection creators.
     connection_creators: HashMap<String, Arc<dyn ConnectionCreator>>,
+    /// Records the statements executed in the audit log.
+    auditor: Auditor,
 }
 
 impl InstanceState {
     /// Create a new `InstanceState`
     ///
-    /// Takes the list of allowed databases, and a function for getting a connection creator given a database label.
+    /// Takes the list of allowed databases, whether those databases may be backed up, and a function for getting a connection creator given a database label.
     pub fn new(
         allowed_databases: Arc<HashSet<String>>,
+        backup_allowed: bool,
         connection_creators: HashMap<String, Arc<dyn ConnectionCreator>>,
     ) -> Self {
         Self {
             allowed_databases,
+            read_only_databases: Default::default(),
+            backup_allowed,
+            vector_search_allowed: false,
+            explain_allowed: false,
             connections: spin_resource_table::Table::new(256),
             connection_creators,
+            auditor: Auditor::disabled(),
         }
     }
 
+    /// Sets the allowed databases which the component may only read.
+    pub fn with_read_only_databases(mut self, read_only_databases: Arc<HashSet<String>>) -> Self {
+        self.read_only_databases = read_only_databases;
+        self
+    }
+
+    /// Sets whether the component may create and search vector indexes.
+    pub fn with_vector_search_allowed(mut self, vector_search_allowed: bool) -> Self {
+        self.vector_search_allowed = vector_search_allowed;
+        self
+    }
+
+    /// Sets whether the component may inspect the query plans of statements.
+    pub fn with_explain_allowed(mut self, explain_allowed: bool) -> Self {
+        self.explain_allowed = explain_allowed;
+        self
+    }
+
+    /// Sets the auditor which records the statements executed.
+    pub fn with_auditor(mut self, auditor: Auditor) -> Self {
+        self.auditor = auditor;
+        self
+    }
+
     /// Get a connection for a given database label.
     fn get_connection<T: 'static>(
         &self,
@@ -41,26 +85,78 @@ impl InstanceState {
     ) -> Result<&dyn Connection, v3::Error> {
         self.connections
             .get(connection.rep())
-            .map(|conn| conn.as_ref())
+            .map(|(_, conn)| conn.as_ref())
             .ok_or(v3::Error::InvalidConnection)
     }
 
-    async fn open_impl<T: 'static>(&mut self, database: String) -> Result<Resource<T>, v3::Error> {
-        if !self.allowed_databases.contains(&database) {
+    /// Get a connection on which the component may use vector search.
+    fn get_vector_connection<T: 'static>(
+        &self,
+        connection: Resource<T>,
+    ) -> Result<&dyn Connection, v3::Error> {
+        if !self.vector_search_allowed {
             return Err(v3::Error::AccessDenied);
         }
-        let conn = self
-            .connection_creators
-            .get(&database)
+        let conn = self.get_connection(connection)?;
+        tracing::Span::current().record(
+            "sqlite.backend",
+            conn.summary().as_deref().unwrap_or("unknown"),
+        );
+        if !conn.supports_vector_search() {
+            return Err(v3::Error::Io(
+                "this database does not support vector search".into(),
+            ));
+        }
+        Ok(conn)
+    }
+
+    /// Create a new connection to an allowed database.
+    async fn create_connection(&self, database: &str) -> Result<Box<dyn Connection>, v3::Error> {
+        if !self.allowed_databases.contains(database) {
+            return Err(v3::Error::AccessDenied);
+        }
+        self.connection_creators
+            .get(database)
             .ok_or(v3::Error::NoSuchDatabase)?
-            .create_connection(&database)
-            .await?;
+            .create_connection(database)
+            .await
+    }
+
+    /// Create a new connection to an allowed database, which rejects writes
+    /// if the component may only read the database.
+    async fn create_main_connection(
+        &self,
+        database: &str,
+    ) -> Result<Box<dyn Connection>, v3::Error> {
+        let conn = self.create_connection(database).await?;
+        if self.is_read_only(database) {
+            conn.set_read_only().await?;
+        }
+        Ok(conn)
+    }
+
+    /// Whether the component may only read the given database.
+    fn is_read_only(&self, database: &str) -> bool {
+        self.read_only_databases.contains(database)
+    }
+
+    async fn open_impl<T: 'static>(&mut self, database: String) -> Result<Resource<T>, v3::Error> {
+        let conn = self.create_main_connection(&database).await?;
         tracing::Span::current().record(
             "sqlite.backend",
             conn.summary().as_deref().unwrap_or("unknown"),
         );
+        self.push_connection(database, conn)
+    }
+
+    /// Add a connection to the given database to the resource table.
+    fn push_connection<T: 'static>(
+        &mut self,
+        database: String,
+        conn: Box<dyn Connection>,
+    ) -> Result<Resource<T>, v3::Error> {
         self.connections
-            .push(conn)
+            .push((database, conn))
             .map_err(|()| v3::Error::Io("too many connections opened".to_string()))
             .map(Resource::new_own)
     }
@@ -71,12 +167,20 @@ impl InstanceState {
         query: String,
         parameters: Vec<v3::Value>,
     ) -> Result<v3::QueryResult, v3::Error> {
-        let conn = self.get_connection(connection)?;
+        let (database, conn) = self
+            .connections
+            .get(connection.rep())
+            .ok_or(v3::Error::InvalidConnection)?;
         tracing::Span::current().record(
             "sqlite.backend",
             conn.summary().as_deref().unwrap_or("unknown"),
         );
-        conn.query(&query, parameters).await
+        // Only the database is audited, as the query and its parameters may
+        // contain sensitive values
+        let result = conn.query(&query, parameters).await;
+        self.auditor
+            .record(operation::SQLITE_EXECUTE, database, &result);
+        result
     }
 
     /// Get the set of allowed databases.
@@ -145,6 +249,331 @@ impl v3::HostConnection for InstanceState {
     }
 }
 
+impl backup::Host for InstanceState {
+    #[instrument(name = "spin_sqlite.backup", skip(self), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn backup(&mut self, database: String, destination: String) -> Result<(), backup::Error> {
+        if !self.backup_allowed || !self.allowed_databases.contains(&database) {
+            return Err(backup::Error::AccessDenied);
+        }
+        let destination = backup_destination(&destination)?;
+        let conn = self
+            .connection_creators
+            .get(&database)
+            .ok_or(backup::Error::NoSuchDatabase)?
+            .create_connection(&database)
+            .await
+            .map_err(to_backup_error)?;
+        tracing::Span::current().record(
+            "sqlite.backend",
+            conn.summary().as_deref().unwrap_or("unknown"),
+        );
+        let db_path = conn.local_path().ok_or(backup::Error::Unsupported)?;
+        let destination = db_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(BACKUP_DIR)
+            .join(destination);
+        if self.is_database_path(&destination).await {
+            return Err(backup::Error::InvalidDestination);
+        }
+        conn.backup(&destination).await
+    }
+
+    fn convert_error(&mut self, error: backup::Error) -> anyhow::Result<backup::Error> {
+        Ok(error)
+    }
+}
+
+impl attach::Host for InstanceState {
+    #[instrument(name = "spin_sqlite.attach", skip(self), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn attach(
+        &mut self,
+        main: String,
+        secondary: String,
+        alias: String,
+    ) -> Result<Resource<v3::Connection>, v3::Error> {
+        if !is_valid_alias(&alias) {
+            return Err(v3::Error::Io(format!(
+                "invalid alias {alias:?}: expected a SQL identifier other than 'main' or 'temp'"
+            )));
+        }
+        let conn = self.create_main_connection(&main).await?;
+        tracing::Span::current().record(
+            "sqlite.backend",
+            conn.summary().as_deref().unwrap_or("unknown"),
+        );
+        let secondary_conn = self.create_connection(&secondary).await?;
+        let path = secondary_conn.local_path().ok_or_else(|| {
+            v3::Error::Io(format!(
+                "database '{secondary}' is not stored in a local file and cannot be attached"
+            ))
+        })?;
+        conn.attach(path, &alias, self.is_read_only(&secondary))
+            .await
+            .map_err(from_attach_error)?;
+        self.push_connection(main, conn)
+    }
+
+    #[instrument(name = "spin_sqlite.open_with_attachments", skip(self), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn open_with_attachments(
+        &mut self,
+        primary: String,
+        attachments: Vec<(String, String)>,
+    ) -> Result<Resource<v3::Connection>, attach::AttachError> {
+        let mut aliases = HashSet::new();
+        for (_, alias) in &attachments {
+            if !is_valid_alias(alias) || !aliases.insert(alias.to_ascii_lowercase()) {
+                return Err(attach::AttachError::InvalidAlias(alias.clone()));
+            }
+        }
+        // Check every grant before opening anything, so that a denied
+        // attachment doesn't reveal whether the others exist
+        for database in std::iter::once(&primary).chain(attachments.iter().map(|(db, _)| db)) {
+            if !self.allowed_databases.contains(database) {
+                return Err(attach::AttachError::AccessDenied(database.clone()));
+            }
+        }
+
+        let conn = self
+            .create_main_connection(&primary)
+            .await
+            .map_err(|e| to_attach_error(e, &primary))?;
+        tracing::Span::current().record(
+            "sqlite.backend",
+            conn.summary().as_deref().unwrap_or("unknown"),
+        );
+        for (database, alias) in &attachments {
+            let attached = self
+                .create_connection(database)
+                .await
+                .map_err(|e| to_attach_error(e, database))?;
+            let path = attached.local_path().ok_or_else(|| {
+                attach::AttachError::Unsupported(format!(
+                    "database '{database}' is not stored in a local file and cannot be attached"
+                ))
+            })?;
+            conn.attach(path, alias, self.is_read_only(database))
+                .await?;
+        }
+        self.push_connection(primary.clone(), conn)
+            .map_err(|e| to_attach_error(e, &primary))
+    }
+
+    fn convert_error(&mut self, error: attach::AttachError) -> anyhow::Result<attach::AttachError> {
+        Ok(error)
+    }
+}
+
+impl fts::Host for InstanceState {
+    #[instrument(name = "spin_sqlite.create_fts_index", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn create_fts_index(
+        &mut self,
+        connection: Resource<v3::Connection>,
+        table: String,
+        columns: Vec<String>,
+    ) -> Result<(), v3::Error> {
+        let statements = crate::fts::create_index_sql(&table, &columns).map_err(v3::Error::Io)?;
+        let conn = self.get_connection(connection)?;
+        tracing::Span::current().record(
+            "sqlite.backend",
+            conn.summary().as_deref().unwrap_or("unknown"),
+        );
+        // A savepoint rather than a transaction, as the guest may already be
+        // in a transaction
+        let result = conn
+            .execute_batch(&format!(
+                "SAVEPOINT spin_fts; {statements} RELEASE spin_fts;"
+            ))
+            .await;
+        if let Err(err) = result {
+            let _ = conn
+                .execute_batch("ROLLBACK TO spin_fts; RELEASE spin_fts;")
+                .await;
+            return Err(v3::Error::Io(format!(
+                "failed to create full-text search index: {err:#}"
+            )));
+        }
+        Ok(())
+    }
+
+    #[instrument(name = "spin_sqlite.fts_search", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn fts_search(
+        &mut self,
+        connection: Resource<v3::Connection>,
+        table: String,
+        query: String,
+        limit: u32,
+    ) -> Result<v3::QueryResult, v3::Error> {
+        let conn = self.get_connection(connection)?;
+        tracing::Span::current().record(
+            "sqlite.backend",
+            conn.summary().as_deref().unwrap_or("unknown"),
+        );
+        conn.query(
+            &crate::fts::search_sql(&table),
+            vec![v3::Value::Text(query), v3::Value::Integer(limit.into())],
+        )
+        .await
+    }
+}
+
+impl vector::Host for InstanceState {
+    #[instrument(name = "spin_sqlite.create_vector_index", skip(self, connection), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn create_vector_index(
+        &mut self,
+        connection: Resource<v3::Connection>,
+        table: String,
+        embedding_column: String,
+        dimensions: u32,
+    ) -> Result<(), v3::Error> {
+        let statements = crate::vector::create_index_sql(&table, &embedding_column, dimensions)
+            .map_err(v3::Error::Io)?;
+        let conn = self.get_vector_connection(connection)?;
+        // A savepoint rather than a transaction, as the guest may already be
+        // in a transaction
+        let result = conn
+            .execute_batch(&format!(
+                "SAVEPOINT spin_vector; {statements} RELEASE spin_vector;"
+            ))
+            .await;
+        if let Err(err) = result {
+            let _ = conn
+                .execute_batch("ROLLBACK TO spin_vector; RELEASE spin_vector;")
+                .await;
+            return Err(v3::Error::Io(format!(
+                "failed to create vector index: {err:#}"
+            )));
+        }
+        Ok(())
+    }
+
+    #[instrument(name = "spin_sqlite.insert_vector", skip(self, connection, embedding), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn insert_vector(
+        &mut self,
+        connection: Resource<v3::Connection>,
+        table: String,
+        embedding_column: String,
+        rowid: i64,
+        embedding: Vec<f32>,
+    ) -> Result<(), v3::Error> {
+        let embedding = crate::vector::encode_embedding(&embedding).map_err(v3::Error::Io)?;
+        let conn = self.get_vector_connection(connection)?;
+        conn.query(
+            &crate::vector::insert_sql(&table, &embedding_column),
+            vec![v3::Value::Blob(embedding), v3::Value::Integer(rowid)],
+        )
+        .await?;
+        if conn.changes().await? == 0 {
+            return Err(v3::Error::Io(format!(
+                "table {table:?} has no row with rowid {rowid}"
+            )));
+        }
+        Ok(())
+    }
+
+    #[instrument(name = "spin_sqlite.search_nearest_neighbors", skip(self, connection, embedding), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
+    async fn search_nearest_neighbors(
+        &mut self,
+        connection: Resource<v3::Connection>,
+        table: String,
+        embedding: Vec<f32>,
+        k: u32,
+    ) -> Result<v3::QueryResult, v3::Error> {
+        let embedding = crate::vector::encode_embedding(&embedding).map_err(v3::Error::Io)?;
+        let conn = self.get_vector_connection(connection)?;
+        conn.query(
+            &crate::vector::search_sql(&table),
+            vec![v3::Value::Blob(embedding), v3::Value::Integer(k.into())],
+        )
+        .await
+    }
+}
+
+impl explain::Host for InstanceState {
+    #[instrument(name = "spin_sqlite.explain", skip(self, connection, parameters), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", otel.name = statement, sqlite.backend = Empty))]
+    async fn explain(
+        &mut self,
+        connection: Resource<v3::Connection>,
+        statement: String,
+        parameters: Vec<v3::Value>,
+    ) -> Result<String, v3::Error> {
+        if !self.explain_allowed {
+            return Err(v3::Error::AccessDenied);
+        }
+        let query = crate::explain::explain_sql(&statement).map_err(v3::Error::Io)?;
+        let conn = self.get_connection(connection)?;
+        tracing::Span::current().record(
+            "sqlite.backend",
+            conn.summary().as_deref().unwrap_or("unknown"),
+        );
+        // The statement itself is only planned, never executed
+        let result = conn.query(&query, parameters).await?;
+        crate::explain::format_plan(&result).map_err(v3::Error::Io)
+    }
+}
+
+/// Checks that an attached database alias is a plain SQL identifier, so that
+/// it can be used unquoted as `alias.table_name`, and doesn't clash with the
+/// schema names SQLite reserves.
+fn is_valid_alias(alias: &str) -> bool {
+    let mut chars = alias.chars();
+    let starts_ok = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    starts_ok
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !alias.eq_ignore_ascii_case("main")
+        && !alias.eq_ignore_ascii_case("temp")
+}
+
+/// The directory, alongside a database, into which its backups are written.
+const BACKUP_DIR: &str = "backups";
+
+impl InstanceState {
+    /// Whether `path` is the file of any configured database, whether or not
+    /// this instance has access to it.
+    async fn is_database_path(&self, path: &Path) -> bool {
+        let path = normalize_path(path);
+        for (label, creator) in &self.connection_creators {
+            // Connections to local databases are opened lazily, so this
+            // doesn't touch the database files
+            let Ok(conn) = creator.create_connection(label).await else {
+                continue;
+            };
+            if conn.local_path().is_some_and(|p| normalize_path(p) == path) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Makes `path` absolute, resolving any symlinks in its parent directory, so
+/// that paths to the same file compare equal.
+fn normalize_path(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    match (
+        path.parent().and_then(|p| p.canonicalize().ok()),
+        path.file_name(),
+    ) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path,
+    }
+}
+
+/// Checks that a backup destination is a relative path which stays within
+/// its base directory.
+fn backup_destination(destination: &str) -> Result<PathBuf, backup::Error> {
+    let path = Path::new(destination);
+    let is_contained = path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !is_contained || path.file_name().is_none() {
+        return Err(backup::Error::InvalidDestination);
+    }
+    Ok(path.to_owned())
+}
+
 impl v2::Host for InstanceState {
     fn convert_error(&mut self, error: v2::Error) -> anyhow::Result<v2::Error> {
         Ok(error)
@@ -222,6 +651,38 @@ fn to_v2_error(error: v3::Error) -> v2::Error {
     }
 }
 
+fn to_backup_error(error: v3::Error) -> backup::Error {
+    match error {
+        v3::Error::NoSuchDatabase => backup::Error::NoSuchDatabase,
+        v3::Error::AccessDenied => backup::Error::AccessDenied,
+        v3::Error::InvalidConnection => backup::Error::Io("invalid connection".into()),
+        v3::Error::DatabaseFull => backup::Error::Io("database full".into()),
+        v3::Error::Io(s) => backup::Error::Io(s),
+    }
+}
+
+/// Converts an error about the named database to an attach error.
+fn to_attach_error(error: v3::Error, database: &str) -> attach::AttachError {
+    match error {
+        v3::Error::NoSuchDatabase => attach::AttachError::NoSuchDatabase(database.to_owned()),
+        v3::Error::AccessDenied => attach::AttachError::AccessDenied(database.to_owned()),
+        v3::Error::InvalidConnection => attach::AttachError::Io("invalid connection".into()),
+        v3::Error::DatabaseFull => attach::AttachError::Io("database full".into()),
+        v3::Error::Io(s) => attach::AttachError::Io(s),
+    }
+}
+
+fn from_attach_error(error: attach::AttachError) -> v3::Error {
+    match error {
+        attach::AttachError::NoSuchDatabase(_) => v3::Error::NoSuchDatabase,
+        attach::AttachError::AccessDenied(_) => v3::Error::AccessDenied,
+        attach::AttachError::InvalidAlias(alias) => {
+            v3::Error::Io(format!("invalid alias {alias:?}"))
+        }
+        attach::AttachError::Unsupported(s) | attach::AttachError::Io(s) => v3::Error::Io(s),
+    }
+}
+
 fn to_legacy_error(error: v3::Error) -> v1::Error {
     match error {
         v3::Error::NoSuchDatabase => v1::Error::NoSuchDatabase,
@@ -1,18 +1,29 @@
+mod explain;
+mod fts;
 mod host;
+mod migrations;
 pub mod runtime_config;
+mod vector;
 
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
 
 use host::InstanceState;
 
 use async_trait::async_trait;
-use spin_factors::{anyhow, Factor, FactorData};
+use spin_factor_audit::Auditor;
+use spin_factors::anyhow::{self, Context as _};
+use spin_factors::{Factor, FactorData, LabelReport};
 use spin_locked_app::MetadataKey;
-use spin_world::spin::sqlite::sqlite as v3;
+use spin_world::spin::sqlite3_0_0::sqlite as v3;
+use spin_world::spin::sqlite3_1_0::{
+    attach, backup, explain as explain_bindings, fts as fts_bindings, vector as vector_bindings,
+};
 use spin_world::v1::sqlite as v1;
 use spin_world::v2::sqlite as v2;
 
+pub use migrations::{Migration, MIGRATIONS_KEY};
 pub use runtime_config::RuntimeConfig;
 
 #[derive(Default)]
@@ -36,6 +47,11 @@ impl Factor for SqliteFactor {
         ctx.link_bindings(v1::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(v2::add_to_linker::<_, FactorData<Self>>)?;
         ctx.link_bindings(v3::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(backup::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(attach::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(fts_bindings::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(vector_bindings::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(explain_bindings::add_to_linker::<_, FactorData<Self>>)?;
         Ok(())
     }
 
@@ -65,43 +81,131 @@ impl Factor for SqliteFactor {
             })
             .collect::<anyhow::Result<HashMap<_, _>>>()?;
 
-        ensure_allowed_databases_are_configured(&allowed_databases, |label| {
-            connection_creators.contains_key(label)
-        })?;
+        let read_only_databases = ctx
+            .app()
+            .components()
+            .map(|component| {
+                let read_only = component
+                    .get_metadata(READ_ONLY_DATABASES_KEY)?
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect::<HashSet<_>>();
+                let allowed = &allowed_databases[component.id()];
+                let mut ungranted = read_only.difference(allowed).collect::<Vec<_>>();
+                if !ungranted.is_empty() {
+                    ungranted.sort();
+                    anyhow::bail!(
+                        "Component {} lists SQLite databases in `sqlite_read_only_databases` which are not in its `sqlite_databases`: {}",
+                        component.id(),
+                        ungranted
+                            .iter()
+                            .map(|label| format!("'{label}'"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                Ok((component.id().to_string(), Arc::new(read_only)))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+        let app_state = AppState::new(allowed_databases, connection_creators);
 
-        Ok(AppState::new(allowed_databases, connection_creators))
+        // Report every problem at once, rather than one per restart
+        let report = app_state.label_report();
+        ensure_allowed_databases_are_configured(&report)?;
+        if !report.unused.is_empty() {
+            tracing::warn!(
+                "The runtime configuration defines SQLite databases which no component uses: {}",
+                report
+                    .unused
+                    .iter()
+                    .map(|label| format!("'{label}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        // Migrations can't be applied here, as connections are async; they are
+        // validated now and applied by `AppState::run_migrations`.
+        let migrations = ctx.app().get_metadata(MIGRATIONS_KEY)?.unwrap_or_default();
+        let mut unconfigured = migrations
+            .keys()
+            .filter(|label| !app_state.connection_creators.contains_key(*label))
+            .collect::<Vec<_>>();
+        if !unconfigured.is_empty() {
+            unconfigured.sort();
+            anyhow::bail!(
+                "The application defines SQLite migrations for databases which are not defined: {}.\nCheck the spelling, or pass a runtime configuration file that defines these databases.",
+                unconfigured
+                    .iter()
+                    .map(|label| format!("'{label}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        Ok(AppState {
+            migrations,
+            read_only_databases,
+            ..app_state
+        })
     }
 
     fn prepare<T: spin_factors::RuntimeFactors>(
         &self,
-        ctx: spin_factors::PrepareContext<T, Self>,
+        mut ctx: spin_factors::PrepareContext<T, Self>,
     ) -> spin_factors::anyhow::Result<Self::InstanceBuilder> {
+        let auditor = Auditor::for_instance(&mut ctx)?;
         let allowed_databases = ctx
             .app_state()
             .allowed_databases
             .get(ctx.app_component().id())
             .cloned()
             .unwrap_or_default();
+        let read_only_databases = ctx
+            .app_state()
+            .read_only_databases
+            .get(ctx.app_component().id())
+            .cloned()
+            .unwrap_or_default();
+        let backup_allowed = ctx
+            .app_component()
+            .get_metadata(BACKUP_ALLOWED_KEY)?
+            .unwrap_or_default();
+        let vector_search_allowed = ctx
+            .app_component()
+            .get_metadata(VECTOR_SEARCH_ALLOWED_KEY)?
+            .unwrap_or_default();
+        let explain_allowed = ctx
+            .app_component()
+            .get_metadata(EXPLAIN_ALLOWED_KEY)?
+            .unwrap_or_default();
         Ok(InstanceState::new(
             allowed_databases,
+            backup_allowed,
             ctx.app_state().connection_creators.clone(),
-        ))
+        )
+        .with_read_only_databases(read_only_databases)
+        .with_vector_search_allowed(vector_search_allowed)
+        .with_explain_allowed(explain_allowed)
+        .with_auditor(auditor))
     }
 }
 
 /// Ensure that all the databases in the allowed databases list for each component are configured
-fn ensure_allowed_databases_are_configured(
-    allowed_databases: &HashMap<String, Arc<HashSet<String>>>,
-    is_configured: impl Fn(&str) -> bool,
-) -> anyhow::Result<()> {
+fn ensure_allowed_databases_are_configured(report: &LabelReport) -> anyhow::Result<()> {
     let mut errors = Vec::new();
-    for (component_id, allowed_dbs) in allowed_databases {
-        for allowed in allowed_dbs.iter() {
-            if !is_configured(allowed) {
-                errors.push(format!(
-                    "- Component {component_id} uses database '{allowed}'"
-                ));
-            }
+    for undefined in &report.undefined {
+        let suggestion = undefined
+            .suggestion
+            .as_ref()
+            .map(|closest| format!(" Did you mean '{closest}'?"))
+            .unwrap_or_default();
+        for component_id in &undefined.components {
+            errors.push(format!(
+                "- Component {component_id} uses database '{}'.{suggestion}",
+                undefined.label
+            ));
         }
     }
 
@@ -125,12 +229,30 @@ fn ensure_allowed_databases_are_configured(
 /// Metadata key for a list of allowed databases for a component.
 pub const ALLOWED_DATABASES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("databases");
 
+/// Metadata key for the allowed databases which a component may only read.
+pub const READ_ONLY_DATABASES_KEY: MetadataKey<Vec<String>> =
+    MetadataKey::new("read_only_databases");
+
+/// Metadata key for whether a component may back up its allowed databases.
+pub const BACKUP_ALLOWED_KEY: MetadataKey<bool> = MetadataKey::new("sqlite_backup");
+
+/// Metadata key for whether a component may create and search vector indexes.
+pub const VECTOR_SEARCH_ALLOWED_KEY: MetadataKey<bool> = MetadataKey::new("sqlite_vector_search");
+
+/// Metadata key for whether a component may inspect the query plans of statements.
+pub const EXPLAIN_ALLOWED_KEY: MetadataKey<bool> = MetadataKey::new("sqlite_explain");
+
 #[derive(Clone)]
 pub struct AppState {
     /// A map from component id to a set of allowed database labels.
     allowed_databases: HashMap<String, Arc<HashSet<String>>>,
+    /// A map from component id to the set of allowed database labels which
+    /// the component may only read.
+    read_only_databases: HashMap<String, Arc<HashSet<String>>>,
     /// A mapping from database label to a connection creator.
     connection_creators: HashMap<String, Arc<dyn ConnectionCreator>>,
+    /// A map from database label to the migrations to apply to it.
+    migrations: HashMap<String, Vec<Migration>>,
 }
 
 impl AppState {
@@ -141,7 +263,9 @@ impl AppState {
     ) -> Self {
         Self {
             allowed_databases,
+            read_only_databases: Default::default(),
             connection_creators,
+            migrations: Default::default(),
         }
     }
 
@@ -160,6 +284,46 @@ impl AppState {
         Some(connection)
     }
 
+    /// Apply the app's SQL migrations which haven't yet been applied to each
+    /// database.
+    ///
+    /// This should be called before any component is instantiated.
+    pub async fn run_migrations(&self) -> anyhow::Result<()> {
+        let mut labels = self.migrations.keys().collect::<Vec<_>>();
+        labels.sort();
+        for label in labels {
+            let conn = self
+                .get_connection(label)
+                .await
+                .with_context(|| format!("no SQLite database '{label}' is defined"))?
+                .map_err(|err| anyhow::anyhow!("{err:?}"))
+                .with_context(|| format!("failed to connect to SQLite database '{label}'"))?;
+            migrations::apply(label, conn.as_ref(), &self.migrations[label]).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the labels of the databases defined by runtime config which no
+    /// component uses, other than the default database, in label order.
+    pub fn unused_databases(&self) -> Vec<String> {
+        self.label_report().unused
+    }
+
+    /// Checks the databases used by components against those defined by
+    /// runtime config.
+    fn label_report(&self) -> LabelReport {
+        LabelReport::new(
+            self.allowed_databases
+                .iter()
+                .flat_map(|(component_id, labels)| {
+                    labels
+                        .iter()
+                        .map(move |label| (component_id.as_str(), label.as_str()))
+                }),
+            self.connection_creators.keys().map(String::as_str),
+        )
+    }
+
     /// Returns true if the given database label is used by any component.
     pub fn database_is_used(&self, label: &str) -> bool {
         self.allowed_databases
@@ -209,6 +373,62 @@ pub trait Connection: Send + Sync {
 
     async fn last_insert_rowid(&self) -> Result<i64, v3::Error>;
 
+    /// Perform an online backup of the database to a file.
+    ///
+    /// `destination` is the path of the file to create, within the `backups`
+    /// directory alongside the database, which has already been checked not
+    /// to be the file of any configured database. Implementations must refuse
+    /// to overwrite an existing file.
+    async fn backup(&self, destination: &Path) -> Result<(), backup::Error> {
+        let _ = destination;
+        Err(backup::Error::Unsupported)
+    }
+
+    /// Attach the database file at `path` to this connection under the schema
+    /// name `alias`, so that its tables can be queried as `alias.table_name`.
+    /// If `read_only` is set, writes through `alias` must fail, whatever SQL
+    /// the component runs on the connection afterwards.
+    ///
+    /// `alias` has already been checked to be a valid SQL identifier.
+    async fn attach(
+        &self,
+        path: &Path,
+        alias: &str,
+        read_only: bool,
+    ) -> Result<(), attach::AttachError> {
+        let _ = (path, alias, read_only);
+        Err(attach::AttachError::Unsupported(
+            "this database does not support attaching other databases".into(),
+        ))
+    }
+
+    /// Make this connection reject writes, e.g. by opening the database
+    /// read-only. This is called before the connection is first used.
+    ///
+    /// Writes must keep failing whatever SQL the component runs, so settings
+    /// which SQL can change, like `PRAGMA query_only`, aren't enough.
+    /// Connections which can't be made read-only must return an error, so
+    /// that components granted read-only access can't write.
+    async fn set_read_only(&self) -> Result<(), v3::Error> {
+        Err(v3::Error::Io(
+            "this database does not support read-only connections".into(),
+        ))
+    }
+
+    /// Whether the connection has the `sqlite-vec` extension loaded, providing
+    /// the `vec0` virtual tables used for vector search.
+    fn supports_vector_search(&self) -> bool {
+        false
+    }
+
+    /// The path of the file the database is stored in, if it is a local file.
+    ///
+    /// Only databases stored in local files can be attached to another
+    /// connection.
+    fn local_path(&self) -> Option<&Path> {
+        None
+    }
+
     /// A human-readable summary of the connection's configuration
     ///
     /// Example: "libSQL at libsql://example.com"
@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use spin_factors::anyhow::{self, Context as _};
+use spin_locked_app::MetadataKey;
+
+use crate::{v3, Connection};
+
+/// Metadata key for the app's SQL migrations, keyed by database label.
+///
+/// The loader embeds the contents of the `[application.sqlite_migrations]`
+/// files in the locked app.
+pub const MIGRATIONS_KEY: MetadataKey<HashMap<String, Vec<Migration>>> =
+    MetadataKey::new("sqlite_migrations");
+
+/// The table in which each database records the migrations applied to it.
+const MIGRATIONS_TABLE: &str = "_spin_migrations";
+
+/// A SQL migration to apply to a database.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Migration {
+    /// The name identifying the migration, i.e. its path in the manifest.
+    pub name: String,
+    /// The SQL statements to execute.
+    pub sql: String,
+}
+
+/// Applies the migrations which haven't already been applied to the database,
+/// in order, returning the names of the migrations applied.
+///
+/// Each migration is applied in a transaction along with its record in the
+/// migrations table, so a failed migration leaves the database unchanged and
+/// is retried next time.
+pub(crate) async fn apply(
+    label: &str,
+    conn: &dyn Connection,
+    migrations: &[Migration],
+) -> anyhow::Result<Vec<String>> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {MIGRATIONS_TABLE} (
+            name TEXT PRIMARY KEY,
+            applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )"
+    ))
+    .await
+    .with_context(|| {
+        format!("failed to create the {MIGRATIONS_TABLE} table in SQLite database '{label}'")
+    })?;
+
+    let applied = conn
+        .query(&format!("SELECT name FROM {MIGRATIONS_TABLE}"), vec![])
+        .await
+        .map_err(|err| anyhow::anyhow!("{err:?}"))
+        .with_context(|| {
+            format!("failed to read applied migrations from SQLite database '{label}'")
+        })?
+        .rows
+        .into_iter()
+        .filter_map(|row| match row.values.into_iter().next() {
+            Some(v3::Value::Text(name)) => Some(name),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    let mut newly_applied = vec![];
+    for migration in migrations {
+        if applied.contains(&migration.name) {
+            continue;
+        }
+        let name = &migration.name;
+        let result = conn
+            .execute_batch(&format!(
+                "BEGIN;\n{sql}\n;\nINSERT INTO {MIGRATIONS_TABLE} (name) VALUES ('{record}');\nCOMMIT;",
+                sql = migration.sql,
+                record = name.replace('\'', "''"),
+            ))
+            .await;
+        if let Err(err) = result {
+            let _ = conn.execute_batch("ROLLBACK").await;
+            return Err(err.context(format!(
+                "failed to apply migration '{name}' to SQLite database '{label}'; the database was left as it was before this migration"
+            )));
+        }
+        tracing::info!("Applied migration '{name}' to SQLite database '{label}'");
+        newly_applied.push(name.clone());
+    }
+    Ok(newly_applied)
+}
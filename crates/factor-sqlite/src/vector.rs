@@ -0,0 +1,132 @@
+//! SQL for vector indexes, built on the `vec0` virtual tables of the
+//! `sqlite-vec` extension.
+//!
+//! An index on `table` is a `vec0` table named `<table>_vec`, whose rows have
+//! the same rowids as the rows of `table` they index. Triggers on `table` keep
+//! the index in sync with the table's embedding column.
+
+/// The name of the `vec0` column holding the indexed embeddings.
+const INDEX_COLUMN: &str = "embedding";
+
+/// The statements creating and populating a vector index.
+///
+/// All the statements are idempotent, so recreating an existing index just
+/// rebuilds it.
+pub fn create_index_sql(
+    table: &str,
+    embedding_column: &str,
+    dimensions: u32,
+) -> Result<String, String> {
+    if table.is_empty() {
+        return Err("table name must not be empty".into());
+    }
+    if embedding_column.is_empty() {
+        return Err("embedding column name must not be empty".into());
+    }
+    if dimensions == 0 {
+        return Err("embeddings must have at least one dimension".into());
+    }
+
+    let vec_table = index_table(table);
+    let vec = quote(&vec_table);
+    let source = quote(table);
+    let column = quote(embedding_column);
+    let index_column = quote(INDEX_COLUMN);
+    let trigger = |suffix: &str| quote(&format!("{vec_table}_{suffix}"));
+    let insert = format!(
+        "INSERT INTO {vec}(rowid, {index_column}) SELECT new.rowid, new.{column} WHERE new.{column} IS NOT NULL;"
+    );
+    let delete = format!("DELETE FROM {vec} WHERE rowid = old.rowid;");
+    let ai = trigger("ai");
+    let ad = trigger("ad");
+    let au = trigger("au");
+
+    Ok(format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {vec} USING vec0({index_column} float[{dimensions}]);
+CREATE TRIGGER IF NOT EXISTS {ai} AFTER INSERT ON {source} BEGIN {insert} END;
+CREATE TRIGGER IF NOT EXISTS {ad} AFTER DELETE ON {source} BEGIN {delete} END;
+CREATE TRIGGER IF NOT EXISTS {au} AFTER UPDATE OF {column} ON {source} BEGIN {delete} {insert} END;
+DELETE FROM {vec};
+INSERT INTO {vec}(rowid, {index_column}) SELECT rowid, {column} FROM {source} WHERE {column} IS NOT NULL;"
+    ))
+}
+
+/// The statement storing an embedding in a row of `table`, taking the
+/// embedding and the rowid as parameters `?1` and `?2`.
+pub fn insert_sql(table: &str, embedding_column: &str) -> String {
+    format!(
+        "UPDATE {} SET {} = ?1 WHERE rowid = ?2",
+        quote(table),
+        quote(embedding_column)
+    )
+}
+
+/// The query searching a vector index, taking the embedding to search for and
+/// the number of rows as parameters `?1` and `?2`.
+///
+/// Rows of the indexed table are returned, nearest first, with their distance
+/// as an additional last column.
+pub fn search_sql(table: &str) -> String {
+    let vec = quote(&index_table(table));
+    let source = quote(table);
+    let index_column = quote(INDEX_COLUMN);
+    format!(
+        "SELECT {source}.*, nearest.distance FROM (SELECT rowid, distance FROM {vec} WHERE {index_column} MATCH ?1 AND k = ?2) AS nearest JOIN {source} ON {source}.rowid = nearest.rowid ORDER BY nearest.distance"
+    )
+}
+
+/// Encodes an embedding in the blob format of `sqlite-vec`: little-endian
+/// 32-bit floats.
+pub fn encode_embedding(embedding: &[f32]) -> Result<Vec<u8>, String> {
+    if embedding.is_empty() {
+        return Err("embeddings must have at least one dimension".into());
+    }
+    Ok(embedding.iter().flat_map(|f| f.to_le_bytes()).collect())
+}
+
+/// The name of the `vec0` table indexing `table`.
+fn index_table(table: &str) -> String {
+    format!("{table}_vec")
+}
+
+/// Quotes an SQL identifier.
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_are_quoted() {
+        let sql = create_index_sql("my \"docs\"", "the embedding", 3).unwrap();
+        assert!(sql.contains(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS \"my \"\"docs\"\"_vec\" USING vec0(\"embedding\" float[3])"
+        ));
+        assert!(sql.contains("new.\"the embedding\" IS NOT NULL"));
+        assert!(sql.contains("AFTER UPDATE OF \"the embedding\" ON \"my \"\"docs\"\"\""));
+
+        assert_eq!(
+            insert_sql("docs", "vec\"tor"),
+            "UPDATE \"docs\" SET \"vec\"\"tor\" = ?1 WHERE rowid = ?2"
+        );
+        assert!(search_sql("it's").contains("FROM \"it's_vec\""));
+    }
+
+    #[test]
+    fn invalid_indexes_are_rejected() {
+        assert!(create_index_sql("", "embedding", 3).is_err());
+        assert!(create_index_sql("docs", "", 3).is_err());
+        assert!(create_index_sql("docs", "embedding", 0).is_err());
+        assert!(encode_embedding(&[]).is_err());
+    }
+
+    #[test]
+    fn embeddings_are_little_endian_floats() {
+        assert_eq!(
+            encode_embedding(&[1.0, -2.5]).unwrap(),
+            [0, 0, 0x80, 0x3f, 0, 0, 0x20, 0xc0]
+        );
+    }
+}
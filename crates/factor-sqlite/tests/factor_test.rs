@@ -3,13 +3,14 @@ use std::{
     sync::Arc,
 };
 
+use spin_factor_audit::{runtime_config::AuditSink, AuditFactor};
 use spin_factor_sqlite::{RuntimeConfig, SqliteFactor};
 use spin_factors::{
     anyhow::{self, bail, Context as _},
     RuntimeFactors,
 };
 use spin_factors_test::{toml, TestEnvironment};
-use spin_world::{async_trait, spin::sqlite::sqlite as v3, v2::sqlite as v2};
+use spin_world::{async_trait, spin::sqlite3_0_0::sqlite as v3, v2::sqlite as v2};
 use v2::HostConnection as _;
 
 #[derive(RuntimeFactors)]
@@ -38,6 +39,63 @@ async fn errors_when_non_configured_database_used() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn undefined_databases_are_reported_together_with_suggestions() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        sqlite: SqliteFactor::new(),
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.orders]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["order"]
+
+            [component.reports]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["order", "reporting"]
+        })
+        .runtime_config(runtime_config_with_databases(&["default", "orders"]))?;
+    let Err(err) = env.build_configured_app().await else {
+        bail!("Expected build_configured_app to error but it did not");
+    };
+
+    let err = err.to_string();
+    assert!(
+        err.contains("- Component orders uses database 'order'. Did you mean 'orders'?"),
+        "{err}"
+    );
+    assert!(
+        err.contains("- Component reports uses database 'order'. Did you mean 'orders'?"),
+        "{err}"
+    );
+    assert!(
+        err.lines()
+            .any(|line| line == "- Component reports uses database 'reporting'."),
+        "{err}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn unused_databases_are_reported() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        sqlite: SqliteFactor::new(),
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["foo"]
+        })
+        .runtime_config(runtime_config_with_databases(&["default", "foo", "unused"]))?;
+    let (_, configured_app) = env.build_configured_app().await?;
+
+    // The default database is always defined, so isn't reported
+    let app_state = configured_app.app_state::<SqliteFactor>()?;
+    assert_eq!(app_state.unused_databases(), ["unused"]);
+    Ok(())
+}
+
 #[tokio::test]
 async fn errors_when_database_not_allowed() -> anyhow::Result<()> {
     let factors = TestFactors {
@@ -95,6 +153,92 @@ async fn it_works_when_database_is_configured() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(RuntimeFactors)]
+struct AuditedFactors {
+    audit: AuditFactor,
+    sqlite: SqliteFactor,
+}
+
+#[tokio::test]
+async fn executed_statements_are_audited_without_parameters() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let log_path = dir.path().join("audit.jsonl");
+    let mut connection_creators = HashMap::new();
+    connection_creators.insert("foo".to_owned(), Arc::new(MockConnectionCreator) as _);
+    let env = TestEnvironment::new(AuditedFactors {
+        audit: AuditFactor::new(),
+        sqlite: SqliteFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["foo"]
+    })
+    .runtime_config(AuditedFactorsRuntimeConfig {
+        audit: Some(spin_factor_audit::RuntimeConfig {
+            sinks: vec![AuditSink::File(log_path.clone())],
+            include: ["sqlite.execute".into()].into(),
+            queue_capacity: 16,
+        }),
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    })?;
+    let (factors, configured_app) = env.build_configured_app().await?;
+    let builders = factors.prepare(&configured_app, "test-component")?;
+    let mut state = factors.build_instance_state(builders)?;
+
+    let conn = state.sqlite.open("foo".into()).await?;
+    // The mock connection fails every query
+    let result = state
+        .sqlite
+        .execute(
+            conn,
+            "SELECT * FROM users WHERE password = ?".into(),
+            vec![v2::Value::Text("hunter2".into())],
+        )
+        .await;
+    assert!(result.is_err());
+
+    configured_app
+        .app_state::<AuditFactor>()?
+        .audit_log()
+        .unwrap()
+        .flush();
+    let log = std::fs::read_to_string(&log_path)?;
+    assert!(
+        !log.contains("hunter2"),
+        "parameters must not be logged: {log}"
+    );
+    assert!(
+        !log.contains("password"),
+        "queries must not be logged: {log}"
+    );
+    let records = log
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["operation"], "sqlite.execute");
+    assert_eq!(records[0]["target"], "foo");
+    assert_eq!(records[0]["outcome"], "failure");
+    assert_eq!(records[0]["component_id"], "test-component");
+    Ok(())
+}
+
+/// Runtime config defining the given databases with mock connections.
+fn runtime_config_with_databases(labels: &[&str]) -> TestFactorsRuntimeConfig {
+    let mut connection_creators = HashMap::new();
+    for label in labels {
+        connection_creators.insert(label.to_string(), Arc::new(MockConnectionCreator) as _);
+    }
+    TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    }
+}
+
 /// A connection creator that returns a mock connection.
 struct MockConnectionCreator;
 
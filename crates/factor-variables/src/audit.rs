@@ -0,0 +1,62 @@
+use spin_expressions::{Key, Provider};
+use spin_factors::anyhow;
+use tracing::{field::Empty, instrument};
+
+/// A [`Provider`] which records each lookup in a tracing span.
+///
+/// The span records which variable was requested, from which provider, and
+/// whether it was found, so that deployments can verify that variables are
+/// only read from approved providers. Values are never recorded.
+#[derive(Debug)]
+pub(crate) struct AuditedProvider {
+    inner: Box<dyn Provider>,
+    /// The position of the provider in the order in which providers are
+    /// consulted.
+    index: usize,
+}
+
+impl AuditedProvider {
+    pub(crate) fn new(inner: Box<dyn Provider>, index: usize) -> Self {
+        Self { inner, index }
+    }
+}
+
+#[spin_world::async_trait]
+impl Provider for AuditedProvider {
+    #[instrument(
+        name = "spin_variables.provider_get",
+        skip_all,
+        fields(
+            variable.key = key.as_str(),
+            variable.provider = self.inner.name(),
+            variable.provider_index = self.index,
+            variable.found = Empty,
+        ),
+    )]
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        let result = self.inner.get(key).await;
+        match &result {
+            Ok(value) => {
+                tracing::Span::current().record("variable.found", value.is_some());
+                tracing::info!(
+                    found = value.is_some(),
+                    "Variable '{}' looked up in provider {}",
+                    key.as_str(),
+                    self.inner.name()
+                );
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Variable '{}' lookup failed in provider {}: {err:#}",
+                    key.as_str(),
+                    self.inner.name()
+                );
+            }
+        }
+        result
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
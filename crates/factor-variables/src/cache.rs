@@ -0,0 +1,147 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use spin_expressions::{Key, Provider};
+use spin_factors::anyhow;
+
+/// A [`Provider`] which caches the values found by another provider.
+///
+/// Once a value is older than the TTL it is stale. A stale value is still
+/// returned, but triggers a refresh from the inner provider in the background,
+/// so that slow providers don't hold up the lookup.
+pub(crate) struct CachedProvider {
+    inner: Arc<dyn Provider>,
+    cache: Arc<TtlCache<String, String>>,
+    /// Variables which are being refreshed in the background.
+    refreshing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl CachedProvider {
+    pub(crate) fn new(inner: Box<dyn Provider>, ttl: Duration) -> Self {
+        Self {
+            inner: inner.into(),
+            cache: Arc::new(TtlCache::new(ttl)),
+            refreshing: Default::default(),
+        }
+    }
+
+    /// Refreshes a stale value in the background, unless it is already being
+    /// refreshed. Returns false if there is no runtime to refresh it on.
+    fn spawn_refresh(&self, name: &str) -> bool {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return false;
+        };
+        if !self.refreshing.lock().unwrap().insert(name.to_owned()) {
+            return true;
+        }
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let refreshing = self.refreshing.clone();
+        let name = name.to_owned();
+        runtime.spawn(async move {
+            let key = Key::new(&name).expect("cached keys are valid");
+            match inner.get(&key).await {
+                Ok(Some(value)) => cache.insert(name.clone(), value),
+                Ok(None) => cache.remove(&name),
+                // Keep serving the stale value; the next lookup retries.
+                Err(err) => tracing::warn!("Failed to refresh variable '{name}': {err:#}"),
+            }
+            refreshing.lock().unwrap().remove(&name);
+        });
+        true
+    }
+}
+
+#[spin_world::async_trait]
+impl Provider for CachedProvider {
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        match self.cache.get(key.as_str()) {
+            Some(Entry::Fresh(value)) => return Ok(Some(value)),
+            Some(Entry::Stale(value)) => {
+                if self.spawn_refresh(key.as_str()) {
+                    tracing::info!(
+                        "Serving stale cached value of variable '{}' while refreshing it",
+                        key.as_str()
+                    );
+                    return Ok(Some(value));
+                }
+            }
+            None => {}
+        }
+        let value = self.inner.get(key).await?;
+        if let Some(value) = &value {
+            self.cache.insert(key.as_str().to_owned(), value.clone());
+        }
+        Ok(value)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+impl std::fmt::Debug for CachedProvider {
+    // The cached values may be secrets, so aren't shown.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedProvider")
+            .field("inner", &self.inner)
+            .field("ttl", &self.cache.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A cache whose entries become stale a fixed time after they are inserted.
+pub(crate) struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (V, Instant)>>,
+}
+
+/// A cached value.
+pub(crate) enum Entry<V> {
+    /// A value inserted within the TTL.
+    Fresh(V),
+    /// A value inserted longer ago than the TTL.
+    Stale(V),
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+
+    pub(crate) fn get<Q>(&self, key: &Q) -> Option<Entry<V>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let entries = self.entries.lock().unwrap();
+        let (value, inserted) = entries.get(key)?;
+        if inserted.elapsed() < self.ttl {
+            Some(Entry::Fresh(value.clone()))
+        } else {
+            Some(Entry::Stale(value.clone()))
+        }
+    }
+
+    pub(crate) fn insert(&self, key: K, value: V) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (value, Instant::now()));
+    }
+
+    pub(crate) fn remove<Q>(&self, key: &Q)
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
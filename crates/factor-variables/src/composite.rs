@@ -0,0 +1,39 @@
+use spin_expressions::{Key, Provider};
+use spin_factors::anyhow;
+
+/// A [`Provider`] which consults a list of providers in order.
+///
+/// The value from the first provider which has one is returned. If a provider
+/// fails, the lookup stops and the error is returned, rather than falling
+/// through to a later provider which may hold a stale or less trusted value.
+#[derive(Debug, Default)]
+pub struct CompositeProvider(Vec<Box<dyn Provider>>);
+
+impl CompositeProvider {
+    /// Creates a `CompositeProvider` which consults `providers` in order.
+    pub fn new(providers: impl IntoIterator<Item = Box<dyn Provider>>) -> Self {
+        Self(providers.into_iter().collect())
+    }
+
+    /// The number of providers consulted.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no providers to consult.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[spin_world::async_trait]
+impl Provider for CompositeProvider {
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        for provider in &self.0 {
+            if let Some(value) = provider.get(key).await? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -77,14 +77,19 @@ impl wasi_config::store::Host for InstanceState {
 fn expressions_to_variables_err(err: spin_expressions::Error) -> variables::Error {
     use spin_expressions::Error;
     let blame = match err {
-        Error::InvalidName(_) | Error::InvalidTemplate(_) | Error::Undefined(_) => Blame::Guest,
+        Error::InvalidName(_)
+        | Error::InvalidTemplate(_)
+        | Error::Undefined(_)
+        | Error::NotAllowed(_) => Blame::Guest,
         Error::Provider(_) => Blame::Host,
     };
     traces::mark_as_error(&err, Some(blame));
     match err {
         Error::InvalidName(msg) => variables::Error::InvalidName(msg),
         Error::Undefined(msg) => variables::Error::Undefined(msg),
-        Error::InvalidTemplate(_) => variables::Error::Other(format!("{err}")),
+        Error::InvalidTemplate(_) | Error::NotAllowed(_) => {
+            variables::Error::Other(format!("{err}"))
+        }
         Error::Provider(err) => variables::Error::Provider(err.to_string()),
     }
 }
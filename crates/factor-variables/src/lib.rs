@@ -1,26 +1,107 @@
+mod audit;
+mod cache;
+mod composite;
 mod host;
 pub mod runtime_config;
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, RwLock, Weak};
 
+use audit::AuditedProvider;
+use cache::CachedProvider;
+pub use composite::CompositeProvider;
 use runtime_config::RuntimeConfig;
-use spin_expressions::{ProviderResolver as ExpressionResolver, Template};
+use spin_expressions::{Key, Provider, ProviderResolver as ExpressionResolver, Template};
 use spin_factors::{
     anyhow, ConfigureAppContext, Factor, FactorData, InitContext, PrepareContext, RuntimeFactors,
     SelfInstanceBuilder,
 };
+use spin_locked_app::MetadataKey;
+
+/// The application variables a component's variables may refer to. If a
+/// component doesn't have this key, they may refer to any.
+const ALLOWED_APPLICATION_VARIABLES_KEY: MetadataKey<Vec<String>> =
+    MetadataKey::new("allowed_application_variables");
 
 /// A factor for providing variables to components.
-#[derive(Default)]
+///
+/// Clones of a `VariablesFactor` share the apps they have configured, so a
+/// clone can be used to [reload](Self::reload_providers) the providers of a
+/// factor which is in use.
+#[derive(Clone, Default)]
 pub struct VariablesFactor {
-    _priv: (),
+    /// The resolvers of the apps configured by this factor.
+    app_resolvers: Arc<Mutex<Vec<Weak<AppResolver>>>>,
 }
 
+/// An app's current resolver, which is replaced when providers are reloaded.
+type AppResolver = RwLock<Arc<ExpressionResolver>>;
+
 impl VariablesFactor {
     /// Creates a new `VariablesFactor`.
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Replaces the variable providers of all the apps configured by this
+    /// factor with those in `new_config`, e.g. when the runtime config file
+    /// has been updated with new credentials.
+    ///
+    /// Instances prepared after this returns use the new providers. Instances
+    /// which were already prepared, such as those handling in-flight requests,
+    /// carry on with the old providers.
+    pub async fn reload_providers(&self, new_config: RuntimeConfig) -> anyhow::Result<()> {
+        let provider_count = new_config.providers.len();
+        let providers: Arc<dyn Provider> = provider(new_config).into();
+
+        let mut app_resolvers = self.app_resolvers.lock().unwrap();
+        app_resolvers.retain(|app_resolver| app_resolver.strong_count() > 0);
+        for app_resolver in app_resolvers.iter().filter_map(Weak::upgrade) {
+            let mut current = app_resolver.write().unwrap();
+            let reloaded = current
+                .with_providers([Box::new(SharedProvider(providers.clone())) as Box<dyn Provider>]);
+            *current = Arc::new(reloaded);
+        }
+        tracing::info!(
+            "Reloaded {provider_count} variable provider(s) for {} app(s)",
+            app_resolvers.len()
+        );
+        Ok(())
+    }
+}
+
+/// The provider for a runtime config, which consults its providers in order,
+/// wrapped for auditing and caching if required.
+fn provider(runtime_config: RuntimeConfig) -> Box<dyn Provider> {
+    let audit = runtime_config.audit;
+    let cache_ttl = runtime_config.cache_ttl;
+    let providers = CompositeProvider::new(runtime_config.into_iter().enumerate().map(
+        move |(index, provider)| {
+            if audit {
+                Box::new(AuditedProvider::new(provider, index)) as Box<dyn Provider>
+            } else {
+                provider
+            }
+        },
+    ));
+    match cache_ttl {
+        Some(ttl) => Box::new(CachedProvider::new(Box::new(providers), ttl)),
+        None => Box::new(providers),
+    }
+}
+
+/// A provider which is shared between the resolvers of several apps.
+#[derive(Debug)]
+struct SharedProvider(Arc<dyn Provider>);
+
+#[spin_world::async_trait]
+impl Provider for SharedProvider {
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        self.0.get(key).await
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
 }
 
 impl Factor for VariablesFactor {
@@ -44,19 +125,25 @@ impl Factor for VariablesFactor {
             ExpressionResolver::new(app.variables().map(|(key, val)| (key.clone(), val.clone())))?;
 
         for component in app.components() {
-            expression_resolver.add_component_variables(
+            let allowed_variables = component.get_metadata(ALLOWED_APPLICATION_VARIABLES_KEY)?;
+            expression_resolver.add_component_variables_with_grants(
                 component.id(),
                 component.config().map(|(k, v)| (k.into(), v.into())),
+                allowed_variables.as_deref(),
             )?;
         }
 
-        let providers = ctx.take_runtime_config().unwrap_or_default();
-        for provider in providers {
-            expression_resolver.add_provider(provider);
-        }
+        let runtime_config = ctx.take_runtime_config().unwrap_or_default();
+        expression_resolver.add_provider(provider(runtime_config));
+
+        let expression_resolver = Arc::new(RwLock::new(Arc::new(expression_resolver)));
+        self.app_resolvers
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&expression_resolver));
 
         Ok(AppState {
-            expression_resolver: Arc::new(expression_resolver),
+            expression_resolver,
         })
     }
 
@@ -65,7 +152,7 @@ impl Factor for VariablesFactor {
         ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<InstanceState> {
         let component_id = ctx.app_component().id().to_string();
-        let expression_resolver = ctx.app_state().expression_resolver.clone();
+        let expression_resolver = ctx.app_state().expression_resolver();
         Ok(InstanceState {
             component_id,
             expression_resolver,
@@ -74,7 +161,7 @@ impl Factor for VariablesFactor {
 }
 
 pub struct AppState {
-    expression_resolver: Arc<ExpressionResolver>,
+    expression_resolver: Arc<AppResolver>,
 }
 
 impl AppState {
@@ -83,7 +170,12 @@ impl AppState {
         expr: impl Into<Box<str>>,
     ) -> spin_expressions::Result<String> {
         let template = Template::new(expr)?;
-        self.expression_resolver.resolve_template(&template).await
+        self.expression_resolver().resolve_template(&template).await
+    }
+
+    /// The app's current resolver.
+    fn expression_resolver(&self) -> Arc<ExpressionResolver> {
+        self.expression_resolver.read().unwrap().clone()
     }
 }
 
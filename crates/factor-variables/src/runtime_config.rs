@@ -1,9 +1,18 @@
+use std::time::Duration;
+
 use spin_expressions::Provider;
 
 /// The runtime configuration for the variables factor.
 #[derive(Default)]
 pub struct RuntimeConfig {
     pub providers: Vec<Box<dyn Provider>>,
+    /// If true, every lookup of a variable from a provider is recorded in a
+    /// tracing span, with the variable name, the provider, and whether the
+    /// variable was found (but never its value).
+    pub audit: bool,
+    /// If set, values found by the providers are cached for this long. A value
+    /// older than this is still used, but is refreshed in the background.
+    pub cache_ttl: Option<Duration>,
 }
 
 impl IntoIterator for RuntimeConfig {
@@ -1,5 +1,11 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
 use spin_expressions::{Key, Provider};
-use spin_factor_variables::{runtime_config::RuntimeConfig, VariablesFactor};
+use spin_factor_variables::{runtime_config::RuntimeConfig, CompositeProvider, VariablesFactor};
 use spin_factors::{anyhow, RuntimeFactors};
 use spin_factors_test::{toml, TestEnvironment};
 use spin_world::v2::variables::Host;
@@ -16,7 +22,11 @@ async fn provider_works() -> anyhow::Result<()> {
     };
     let providers = vec![Box::new(MockProvider) as _];
     let runtime_config = TestFactorsRuntimeConfig {
-        variables: Some(RuntimeConfig { providers }),
+        variables: Some(RuntimeConfig {
+            providers,
+            audit: false,
+            cache_ttl: None,
+        }),
     };
     let env = TestEnvironment::new(factors)
         .extend_manifest(toml! {
@@ -35,6 +45,204 @@ async fn provider_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn audited_provider_works() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+    };
+    let providers = vec![Box::new(MockProvider) as _];
+    let runtime_config = TestFactorsRuntimeConfig {
+        variables: Some(RuntimeConfig {
+            providers,
+            audit: true,
+            cache_ttl: None,
+        }),
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [variables]
+            foo = { required = true }
+            qux = { default = "quux" }
+
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            variables = { baz = "<{{ foo }}>", corge = "{{ qux }}" }
+        })
+        .runtime_config(runtime_config)?;
+
+    let mut state = env.build_instance_state().await?;
+    assert_eq!(state.variables.get("baz".into()).await?, "<bar>");
+    assert_eq!(state.variables.get("corge".into()).await?, "quux");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reloaded_providers_apply_to_new_instances() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+    };
+    let variables = factors.variables.clone();
+    let runtime_config = TestFactorsRuntimeConfig {
+        variables: Some(RuntimeConfig {
+            providers: vec![Box::new(MockProvider) as _],
+            audit: false,
+            cache_ttl: None,
+        }),
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [variables]
+            foo = { required = true }
+
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            variables = { baz = "<{{ foo }}>" }
+        })
+        .runtime_config(runtime_config)?;
+    let (factors, configured_app) = env.build_configured_app().await?;
+    let build_instance_state = || -> anyhow::Result<TestFactorsInstanceState> {
+        let builders = factors.prepare(&configured_app, "test-component")?;
+        Ok(factors.build_instance_state(builders)?)
+    };
+
+    let mut in_flight = build_instance_state()?;
+
+    variables
+        .reload_providers(RuntimeConfig {
+            providers: vec![Box::new(ReloadedProvider) as _],
+            audit: false,
+            cache_ttl: None,
+        })
+        .await?;
+
+    let mut state = build_instance_state()?;
+    assert_eq!(state.variables.get("baz".into()).await?, "<reloaded>");
+    assert_eq!(
+        configured_app
+            .app_state::<VariablesFactor>()?
+            .resolve_expression("{{ foo }}")
+            .await?,
+        "reloaded"
+    );
+    // Instances prepared before the reload carry on with the old providers
+    assert_eq!(in_flight.variables.get("baz".into()).await?, "<bar>");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cached_provider_serves_stale_values_while_refreshing() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+    };
+    let provider = CountingProvider::default();
+    *provider.value.lock().unwrap() = "cached".into();
+    let runtime_config = TestFactorsRuntimeConfig {
+        variables: Some(RuntimeConfig {
+            providers: vec![Box::new(provider.clone()) as _],
+            audit: false,
+            cache_ttl: Some(Duration::from_millis(100)),
+        }),
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [variables]
+            foo = { required = true }
+
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            variables = { baz = "<{{ foo }}>" }
+        })
+        .runtime_config(runtime_config)?;
+    let mut state = env.build_instance_state().await?;
+
+    // Fresh values come from the cache
+    assert_eq!(state.variables.get("baz".into()).await?, "<cached>");
+    assert_eq!(state.variables.get("baz".into()).await?, "<cached>");
+    assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+
+    // A stale value is served while it is refreshed
+    *provider.value.lock().unwrap() = "refreshed".into();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(state.variables.get("baz".into()).await?, "<cached>");
+    let mut value = String::new();
+    for _ in 0..100 {
+        value = state.variables.get("baz".into()).await?;
+        if value != "<cached>" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(value, "<refreshed>");
+    // Only one refresh was made, however often the stale value was served
+    assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default)]
+struct CountingProvider {
+    value: Arc<Mutex<String>>,
+    calls: Arc<AtomicUsize>,
+}
+
+#[spin_world::async_trait]
+impl Provider for CountingProvider {
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        match key.as_str() {
+            "foo" => {
+                let value = self.value.lock().unwrap().clone();
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                Ok(Some(value))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[tokio::test]
+async fn composite_provider_tries_providers_in_order() -> anyhow::Result<()> {
+    let composite = CompositeProvider::new([
+        Box::new(ReloadedProvider) as Box<dyn Provider>,
+        Box::new(MockProvider),
+    ]);
+    // The first provider with a value wins
+    assert_eq!(
+        composite.get(&Key::new("foo")?).await?.as_deref(),
+        Some("reloaded")
+    );
+    assert_eq!(composite.get(&Key::new("baz")?).await?, None);
+
+    // An error stops the lookup
+    let composite = CompositeProvider::new([
+        Box::new(FailingProvider) as Box<dyn Provider>,
+        Box::new(MockProvider),
+    ]);
+    assert!(composite.get(&Key::new("foo")?).await.is_err());
+    Ok(())
+}
+
+#[derive(Debug)]
+struct FailingProvider;
+
+#[spin_world::async_trait]
+impl Provider for FailingProvider {
+    async fn get(&self, _key: &Key) -> anyhow::Result<Option<String>> {
+        anyhow::bail!("provider unavailable")
+    }
+}
+
+#[derive(Debug)]
+struct ReloadedProvider;
+
+#[spin_world::async_trait]
+impl Provider for ReloadedProvider {
+    async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+        match key.as_str() {
+            "foo" => Ok(Some("reloaded".to_string())),
+            _ => Ok(None),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct MockProvider;
 
@@ -47,3 +255,55 @@ impl Provider for MockProvider {
         }
     }
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn granted_application_variables_resolve() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [variables]
+        api_host = { default = "api.example.com" }
+        public_name = { default = "Example" }
+        admin_token = { default = "s3cr3t", secret = true }
+
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_application_variables = ["api_host", "public_*"]
+        variables = { url = "https://{{ api_host }}/{{ public_name }}" }
+    });
+
+    let mut state = env.build_instance_state().await?;
+    let val = state.variables.get("url".into()).await?;
+    assert_eq!(val, "https://api.example.com/Example");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ungranted_application_variables_fail_to_configure() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [variables]
+        api_host = { default = "api.example.com" }
+        admin_token = { default = "s3cr3t", secret = true }
+
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_application_variables = ["api_host", "public_*"]
+        variables = { token = "{{ admin_token }}" }
+    });
+
+    let Err(err) = env.build_instance_state().await else {
+        anyhow::bail!("expected configuring the app to fail");
+    };
+    let message = format!("{err:#}");
+    assert!(
+        message.contains(
+            r#"component "test-component" variable "token" refers to application variable "admin_token", which is not granted by its allowed_application_variables ["api_host", "public_*"]"#
+        ),
+        "{message}"
+    );
+    Ok(())
+}
@@ -0,0 +1,420 @@
+//! Host implementations of `wasi:cli/environment` and `wasi:filesystem/types`
+//! which apply an instance's working directory and the permissions of the
+//! files and directories it creates, delegating everything else to
+//! [`WasiCtxView`].
+
+use spin_factors::anyhow;
+use wasmtime::component::{HasData, Resource};
+use wasmtime_wasi::p2::bindings as latest;
+use wasmtime_wasi::p2::FsResult;
+use wasmtime_wasi::WasiCtxView;
+
+use latest::filesystem::types::{
+    Advice, Descriptor, DescriptorFlags, DescriptorStat, DescriptorType, DirectoryEntry,
+    DirectoryEntryStream, ErrorCode, Filesize, HostDescriptor, HostDirectoryEntryStream,
+    MetadataHashValue, NewTimestamp, OpenFlags, PathFlags,
+};
+use latest::io::streams::{InputStream, OutputStream};
+
+use crate::CreatedModes;
+
+pub(crate) struct WasiFsView<'a> {
+    pub(crate) wasi: WasiCtxView<'a>,
+    pub(crate) working_directory: Option<&'a str>,
+    pub(crate) created_modes: CreatedModes,
+}
+
+pub(crate) struct HasWasiFs;
+
+impl HasData for HasWasiFs {
+    type Data<'a> = WasiFsView<'a>;
+}
+
+impl WasiFsView<'_> {
+    /// Returns the host directory of a directory descriptor.
+    fn dir(&self, fd: &Resource<Descriptor>) -> Option<&cap_std::fs::Dir> {
+        match self.wasi.table.get(fd).ok()? {
+            Descriptor::Dir(dir) => Some(&dir.dir),
+            Descriptor::File(_) => None,
+        }
+    }
+
+    /// Sets the permissions of a newly created file.
+    fn set_file_mode(&self, fd: &Resource<Descriptor>, mode: u32) {
+        if let Ok(Descriptor::File(file)) = self.wasi.table.get(fd) {
+            set_mode(|perms| file.file.set_permissions(perms), mode);
+        }
+    }
+}
+
+/// Calls `set_permissions` with the permissions for a unix mode. This is best
+/// effort: errors are ignored, as the file or directory has been created
+/// either way, and on other platforms it does nothing.
+#[cfg(unix)]
+fn set_mode(
+    set_permissions: impl FnOnce(cap_std::fs::Permissions) -> std::io::Result<()>,
+    mode: u32,
+) {
+    use std::os::unix::fs::PermissionsExt;
+    let perms = cap_std::fs::Permissions::from_std(std::fs::Permissions::from_mode(mode));
+    _ = set_permissions(perms);
+}
+
+#[cfg(not(unix))]
+fn set_mode(
+    _set_permissions: impl FnOnce(cap_std::fs::Permissions) -> std::io::Result<()>,
+    _mode: u32,
+) {
+}
+
+impl latest::cli::environment::Host for WasiFsView<'_> {
+    fn get_environment(&mut self) -> anyhow::Result<Vec<(String, String)>> {
+        latest::cli::environment::Host::get_environment(&mut self.wasi)
+    }
+
+    fn get_arguments(&mut self) -> anyhow::Result<Vec<String>> {
+        latest::cli::environment::Host::get_arguments(&mut self.wasi)
+    }
+
+    fn initial_cwd(&mut self) -> anyhow::Result<Option<String>> {
+        match self.working_directory {
+            Some(cwd) => Ok(Some(cwd.to_owned())),
+            None => latest::cli::environment::Host::initial_cwd(&mut self.wasi),
+        }
+    }
+}
+
+impl latest::filesystem::types::Host for WasiFsView<'_> {
+    fn convert_error_code(&mut self, err: wasmtime_wasi::p2::FsError) -> anyhow::Result<ErrorCode> {
+        latest::filesystem::types::Host::convert_error_code(&mut self.wasi, err)
+    }
+
+    fn filesystem_error_code(
+        &mut self,
+        err: Resource<anyhow::Error>,
+    ) -> anyhow::Result<Option<ErrorCode>> {
+        latest::filesystem::types::Host::filesystem_error_code(&mut self.wasi, err)
+    }
+}
+
+impl HostDescriptor for WasiFsView<'_> {
+    fn read_via_stream(
+        &mut self,
+        fd: Resource<Descriptor>,
+        offset: Filesize,
+    ) -> FsResult<Resource<InputStream>> {
+        HostDescriptor::read_via_stream(&mut self.wasi, fd, offset)
+    }
+
+    fn write_via_stream(
+        &mut self,
+        fd: Resource<Descriptor>,
+        offset: Filesize,
+    ) -> FsResult<Resource<OutputStream>> {
+        HostDescriptor::write_via_stream(&mut self.wasi, fd, offset)
+    }
+
+    fn append_via_stream(&mut self, fd: Resource<Descriptor>) -> FsResult<Resource<OutputStream>> {
+        HostDescriptor::append_via_stream(&mut self.wasi, fd)
+    }
+
+    async fn advise(
+        &mut self,
+        fd: Resource<Descriptor>,
+        offset: Filesize,
+        length: Filesize,
+        advice: Advice,
+    ) -> FsResult<()> {
+        HostDescriptor::advise(&mut self.wasi, fd, offset, length, advice).await
+    }
+
+    async fn sync_data(&mut self, fd: Resource<Descriptor>) -> FsResult<()> {
+        HostDescriptor::sync_data(&mut self.wasi, fd).await
+    }
+
+    async fn get_flags(&mut self, fd: Resource<Descriptor>) -> FsResult<DescriptorFlags> {
+        HostDescriptor::get_flags(&mut self.wasi, fd).await
+    }
+
+    async fn get_type(&mut self, fd: Resource<Descriptor>) -> FsResult<DescriptorType> {
+        HostDescriptor::get_type(&mut self.wasi, fd).await
+    }
+
+    async fn set_size(&mut self, fd: Resource<Descriptor>, size: Filesize) -> FsResult<()> {
+        HostDescriptor::set_size(&mut self.wasi, fd, size).await
+    }
+
+    async fn set_times(
+        &mut self,
+        fd: Resource<Descriptor>,
+        data_access_timestamp: NewTimestamp,
+        data_modification_timestamp: NewTimestamp,
+    ) -> FsResult<()> {
+        HostDescriptor::set_times(
+            &mut self.wasi,
+            fd,
+            data_access_timestamp,
+            data_modification_timestamp,
+        )
+        .await
+    }
+
+    async fn read(
+        &mut self,
+        fd: Resource<Descriptor>,
+        length: Filesize,
+        offset: Filesize,
+    ) -> FsResult<(Vec<u8>, bool)> {
+        HostDescriptor::read(&mut self.wasi, fd, length, offset).await
+    }
+
+    async fn write(
+        &mut self,
+        fd: Resource<Descriptor>,
+        buffer: Vec<u8>,
+        offset: Filesize,
+    ) -> FsResult<Filesize> {
+        HostDescriptor::write(&mut self.wasi, fd, buffer, offset).await
+    }
+
+    async fn read_directory(
+        &mut self,
+        fd: Resource<Descriptor>,
+    ) -> FsResult<Resource<DirectoryEntryStream>> {
+        HostDescriptor::read_directory(&mut self.wasi, fd).await
+    }
+
+    async fn sync(&mut self, fd: Resource<Descriptor>) -> FsResult<()> {
+        HostDescriptor::sync(&mut self.wasi, fd).await
+    }
+
+    async fn create_directory_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        let parent = Resource::new_borrow(fd.rep());
+        HostDescriptor::create_directory_at(&mut self.wasi, fd, path.clone()).await?;
+        if let (Some(mode), Some(dir)) = (self.created_modes.dir, self.dir(&parent)) {
+            set_mode(|perms| dir.set_permissions(&path, perms), mode);
+        }
+        Ok(())
+    }
+
+    async fn stat(&mut self, fd: Resource<Descriptor>) -> FsResult<DescriptorStat> {
+        HostDescriptor::stat(&mut self.wasi, fd).await
+    }
+
+    async fn stat_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+    ) -> FsResult<DescriptorStat> {
+        HostDescriptor::stat_at(&mut self.wasi, fd, path_flags, path).await
+    }
+
+    async fn set_times_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+        data_access_timestamp: NewTimestamp,
+        data_modification_timestamp: NewTimestamp,
+    ) -> FsResult<()> {
+        HostDescriptor::set_times_at(
+            &mut self.wasi,
+            fd,
+            path_flags,
+            path,
+            data_access_timestamp,
+            data_modification_timestamp,
+        )
+        .await
+    }
+
+    async fn link_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        old_path_flags: PathFlags,
+        old_path: String,
+        new_descriptor: Resource<Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::link_at(
+            &mut self.wasi,
+            fd,
+            old_path_flags,
+            old_path,
+            new_descriptor,
+            new_path,
+        )
+        .await
+    }
+
+    async fn open_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+        open_flags: OpenFlags,
+        flags: DescriptorFlags,
+    ) -> FsResult<Resource<Descriptor>> {
+        // Only files which don't exist yet get the configured mode
+        let created_mode = self
+            .created_modes
+            .file
+            .filter(|_| open_flags.contains(OpenFlags::CREATE))
+            .filter(|_| {
+                self.dir(&fd)
+                    .is_some_and(|dir| dir.symlink_metadata(&path).is_err())
+            });
+        let file = HostDescriptor::open_at(&mut self.wasi, fd, path_flags, path, open_flags, flags)
+            .await?;
+        if let Some(mode) = created_mode {
+            self.set_file_mode(&file, mode);
+        }
+        Ok(file)
+    }
+
+    async fn readlink_at(&mut self, fd: Resource<Descriptor>, path: String) -> FsResult<String> {
+        HostDescriptor::readlink_at(&mut self.wasi, fd, path).await
+    }
+
+    async fn remove_directory_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::remove_directory_at(&mut self.wasi, fd, path).await
+    }
+
+    async fn rename_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        old_path: String,
+        new_descriptor: Resource<Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::rename_at(&mut self.wasi, fd, old_path, new_descriptor, new_path).await
+    }
+
+    async fn symlink_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        old_path: String,
+        new_path: String,
+    ) -> FsResult<()> {
+        HostDescriptor::symlink_at(&mut self.wasi, fd, old_path, new_path).await
+    }
+
+    async fn unlink_file_at(&mut self, fd: Resource<Descriptor>, path: String) -> FsResult<()> {
+        HostDescriptor::unlink_file_at(&mut self.wasi, fd, path).await
+    }
+
+    async fn is_same_object(
+        &mut self,
+        fd: Resource<Descriptor>,
+        other: Resource<Descriptor>,
+    ) -> anyhow::Result<bool> {
+        HostDescriptor::is_same_object(&mut self.wasi, fd, other).await
+    }
+
+    async fn metadata_hash(&mut self, fd: Resource<Descriptor>) -> FsResult<MetadataHashValue> {
+        HostDescriptor::metadata_hash(&mut self.wasi, fd).await
+    }
+
+    async fn metadata_hash_at(
+        &mut self,
+        fd: Resource<Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+    ) -> FsResult<MetadataHashValue> {
+        HostDescriptor::metadata_hash_at(&mut self.wasi, fd, path_flags, path).await
+    }
+
+    fn drop(&mut self, fd: Resource<Descriptor>) -> anyhow::Result<()> {
+        HostDescriptor::drop(&mut self.wasi, fd)
+    }
+}
+
+impl HostDirectoryEntryStream for WasiFsView<'_> {
+    async fn read_directory_entry(
+        &mut self,
+        stream: Resource<DirectoryEntryStream>,
+    ) -> FsResult<Option<DirectoryEntry>> {
+        HostDirectoryEntryStream::read_directory_entry(&mut self.wasi, stream).await
+    }
+
+    fn drop(&mut self, stream: Resource<DirectoryEntryStream>) -> anyhow::Result<()> {
+        HostDirectoryEntryStream::drop(&mut self.wasi, stream)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use wasmtime_wasi::{DirPerms, FilePerms, ResourceTable, WasiCtxBuilder};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn created_files_and_directories_get_configured_modes() -> anyhow::Result<()> {
+        let host_dir = tempfile::tempdir()?;
+        std::fs::write(host_dir.path().join("existing.txt"), "")?;
+        std::fs::set_permissions(
+            host_dir.path().join("existing.txt"),
+            std::fs::Permissions::from_mode(0o600),
+        )?;
+
+        let mut ctx = WasiCtxBuilder::new()
+            .preopened_dir(host_dir.path(), "/work", DirPerms::all(), FilePerms::all())?
+            .build();
+        let mut table = ResourceTable::new();
+        let mut view = WasiFsView {
+            wasi: WasiCtxView {
+                ctx: &mut ctx,
+                table: &mut table,
+            },
+            working_directory: Some("/work"),
+            created_modes: CreatedModes {
+                file: Some(0o640),
+                dir: Some(0o750),
+            },
+        };
+        assert_eq!(
+            latest::cli::environment::Host::initial_cwd(&mut view)?.as_deref(),
+            Some("/work")
+        );
+
+        let (preopen, _) = latest::filesystem::preopens::Host::get_directories(&mut view.wasi)?
+            .pop()
+            .unwrap();
+        for file in ["created.txt", "existing.txt"] {
+            view.open_at(
+                Resource::new_borrow(preopen.rep()),
+                PathFlags::empty(),
+                file.into(),
+                OpenFlags::CREATE,
+                DescriptorFlags::READ | DescriptorFlags::WRITE,
+            )
+            .await?;
+        }
+        view.create_directory_at(Resource::new_borrow(preopen.rep()), "dir".into())
+            .await?;
+
+        let mode = |path: &str| {
+            std::fs::metadata(host_dir.path().join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        };
+        assert_eq!(mode("created.txt"), 0o640);
+        assert_eq!(mode("dir"), 0o750);
+        // Files which already existed keep their permissions
+        assert_eq!(mode("existing.txt"), 0o600);
+        Ok(())
+    }
+}
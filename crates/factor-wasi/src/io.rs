@@ -9,6 +9,42 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use wasmtime_wasi::cli::{IsTerminal, StdinStream, StdoutStream};
 use wasmtime_wasi::p2::{InputStream, OutputStream, Pollable, StreamError};
 
+/// The most bytes a [`PipedWriteStream`] accepts in a single write by default.
+pub const DEFAULT_MAX_WRITE_BYTES: usize = 1024 * 1024;
+
+/// Options for piping a guest's stdout or stderr to a [`Write`]r.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PipeOptions {
+    /// The most bytes accepted from the guest in a single write.
+    ///
+    /// Guests must split larger writes, so the host never holds more than
+    /// this much of a guest's output at once.
+    pub max_write_bytes: usize,
+    /// If set, output is passed on a line at a time, with lines longer than
+    /// this truncated and bytes which aren't valid UTF-8 escaped.
+    pub max_line_bytes: Option<usize>,
+}
+
+impl PipeOptions {
+    /// Options which pass output on a line at a time, truncating lines
+    /// longer than `max_line_bytes`.
+    pub fn line_mode(max_line_bytes: usize) -> Self {
+        Self {
+            max_line_bytes: Some(max_line_bytes),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PipeOptions {
+    fn default() -> Self {
+        Self {
+            max_write_bytes: DEFAULT_MAX_WRITE_BYTES,
+            max_line_bytes: None,
+        }
+    }
+}
+
 /// A [`OutputStream`] that writes to a `Write` type.
 ///
 /// `StdinStream::stream` and `StdoutStream::new` can be called more than once in components
@@ -20,23 +56,52 @@ use wasmtime_wasi::p2::{InputStream, OutputStream, Pollable, StreamError};
 /// prefer to avoid, but the properly asynchronous implementations Host{In|Out}putStream based on
 /// `AsyncRead`/`AsyncWrite`` are quite hairy and probably not worth it for "normal" stdio streams in
 /// Spin. If this does prove to be a performance bottleneck, though, we can certainly revisit it.
-pub struct PipedWriteStream<T>(Arc<Mutex<T>>);
+///
+/// Each write is limited to `max_write_bytes`; larger writes are refused
+/// (through the `OutputStream` write permit) or accepted in part (through
+/// `AsyncWrite`), so a guest writing a huge buffer must feed it through in
+/// bounded chunks. The writer is dropped, flushing any output it holds, when
+/// the last clone of the stream is dropped.
+pub struct PipedWriteStream<T> {
+    inner: Arc<Mutex<T>>,
+    max_write_bytes: usize,
+}
 
 impl<T> PipedWriteStream<T> {
     pub fn new(inner: T) -> Self {
-        Self(Arc::new(Mutex::new(inner)))
+        Self::with_max_write_bytes(inner, DEFAULT_MAX_WRITE_BYTES)
+    }
+
+    /// Creates a stream which accepts at most `max_write_bytes` per write.
+    ///
+    /// A `max_write_bytes` of zero is treated as one.
+    pub fn with_max_write_bytes(inner: T, max_write_bytes: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+            max_write_bytes: max_write_bytes.max(1),
+        }
     }
 }
 
 impl<T> Clone for PipedWriteStream<T> {
     fn clone(&self) -> Self {
-        Self(self.0.clone())
+        Self {
+            inner: self.inner.clone(),
+            max_write_bytes: self.max_write_bytes,
+        }
     }
 }
 
 impl<T: Write + Send + Sync + 'static> OutputStream for PipedWriteStream<T> {
     fn write(&mut self, bytes: bytes::Bytes) -> Result<(), StreamError> {
-        self.0
+        if bytes.len() > self.max_write_bytes {
+            return Err(StreamError::LastOperationFailed(anyhow::anyhow!(
+                "write of {} bytes exceeds the permitted {} bytes",
+                bytes.len(),
+                self.max_write_bytes
+            )));
+        }
+        self.inner
             .lock()
             .unwrap()
             .write_all(&bytes)
@@ -44,7 +109,7 @@ impl<T: Write + Send + Sync + 'static> OutputStream for PipedWriteStream<T> {
     }
 
     fn flush(&mut self) -> Result<(), StreamError> {
-        self.0
+        self.inner
             .lock()
             .unwrap()
             .flush()
@@ -52,7 +117,7 @@ impl<T: Write + Send + Sync + 'static> OutputStream for PipedWriteStream<T> {
     }
 
     fn check_write(&mut self) -> Result<usize, StreamError> {
-        Ok(1024 * 1024)
+        Ok(self.max_write_bytes)
     }
 }
 
@@ -62,10 +127,11 @@ impl<T: Write + Send + Sync + 'static> AsyncWrite for PipedWriteStream<T> {
         _cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        Poll::Ready(self.0.lock().unwrap().write(buf))
+        let len = buf.len().min(self.max_write_bytes);
+        Poll::Ready(self.inner.lock().unwrap().write(&buf[..len]))
     }
     fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Poll::Ready(self.0.lock().unwrap().flush())
+        Poll::Ready(self.inner.lock().unwrap().flush())
     }
     fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         Poll::Ready(Ok(()))
@@ -92,6 +158,102 @@ impl<T: Write + Send + Sync + 'static> Pollable for PipedWriteStream<T> {
     async fn ready(&mut self) {}
 }
 
+/// A [`Write`]r which passes output on to `inner` a line at a time.
+///
+/// Lines longer than `max_line_bytes` are truncated, with a marker giving the
+/// number of bytes dropped, so at most `max_line_bytes` are held at once.
+/// Bytes which aren't valid UTF-8 are replaced with `\xNN` escapes. A final
+/// partial line is passed on when the `LineWriter` is dropped.
+pub struct LineWriter<W: Write> {
+    inner: W,
+    max_line_bytes: usize,
+    /// The (possibly truncated) current line
+    line: Vec<u8>,
+    /// The number of bytes dropped from the current line
+    truncated: usize,
+}
+
+impl<W: Write> LineWriter<W> {
+    pub fn new(inner: W, max_line_bytes: usize) -> Self {
+        Self {
+            inner,
+            max_line_bytes,
+            line: Vec::new(),
+            truncated: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        let room = self.max_line_bytes.saturating_sub(self.line.len());
+        let (kept, dropped) = bytes.split_at(bytes.len().min(room));
+        self.line.extend_from_slice(kept);
+        self.truncated += dropped.len();
+    }
+
+    fn write_line(&mut self, newline: bool) -> io::Result<()> {
+        let mut line = escape_invalid_utf8(&self.line);
+        if self.truncated > 0 {
+            line.push_str(&format!("...[truncated {} bytes]", self.truncated));
+        }
+        if newline {
+            line.push('\n');
+        }
+        self.line.clear();
+        self.truncated = 0;
+        self.inner.write_all(line.as_bytes())
+    }
+}
+
+impl<W: Write> Write for LineWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(end) = rest.iter().position(|&b| b == b'\n') {
+            self.push(&rest[..end]);
+            self.write_line(true)?;
+            rest = &rest[end + 1..];
+        }
+        self.push(rest);
+        Ok(buf.len())
+    }
+
+    /// Flushes `inner`. A partial line is held until it is complete.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for LineWriter<W> {
+    fn drop(&mut self) {
+        if !self.line.is_empty() || self.truncated > 0 {
+            let _ = self.write_line(false);
+        }
+        let _ = self.inner.flush();
+    }
+}
+
+/// Converts `bytes` to a string, replacing bytes which aren't valid UTF-8
+/// with `\xNN` escapes.
+fn escape_invalid_utf8(mut bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(valid) => {
+                escaped.push_str(valid);
+                return escaped;
+            }
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                escaped.push_str(std::str::from_utf8(valid).unwrap());
+                let invalid_len = err.error_len().unwrap_or(rest.len());
+                for byte in &rest[..invalid_len] {
+                    escaped.push_str(&format!("\\x{byte:02x}"));
+                }
+                bytes = &rest[invalid_len..];
+            }
+        }
+    }
+}
+
 /// A [`InputStream`] that reads to a `Read` type.
 ///
 /// See [`PipedWriteStream`] for more information on why this is synchronous.
@@ -172,3 +334,89 @@ impl<T: Read + Send + Sync + 'static> StdinStream for PipeReadStream<T> {
         Box::new(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer which records what it is given, and the largest single write.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        written: Arc<Mutex<Vec<u8>>>,
+        max_write: Arc<Mutex<usize>>,
+    }
+
+    impl Recorder {
+        fn written(&self) -> String {
+            String::from_utf8(self.written.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut max_write = self.max_write.lock().unwrap();
+            *max_write = (*max_write).max(buf.len());
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let recorder = Recorder::default();
+        let mut writer = LineWriter::new(recorder.clone(), 8);
+        writer.write_all(b"short\n0123456789").unwrap();
+        writer.write_all(b"abcdef\nnext\n").unwrap();
+        assert_eq!(
+            recorder.written(),
+            "short\n01234567...[truncated 8 bytes]\nnext\n"
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_is_escaped() {
+        let recorder = Recorder::default();
+        let mut writer = LineWriter::new(recorder.clone(), 64);
+        writer.write_all(b"caf\xc3\xa9 \xff\xfe\n").unwrap();
+        // A multi-byte character split across writes is kept whole
+        writer.write_all(b"\xc3").unwrap();
+        writer.write_all(b"\xa9\n").unwrap();
+        assert_eq!(recorder.written(), "caf\u{e9} \\xff\\xfe\n\u{e9}\n");
+    }
+
+    #[test]
+    fn partial_line_is_written_on_drop() {
+        let recorder = Recorder::default();
+        let stream = PipedWriteStream::new(LineWriter::new(recorder.clone(), 64));
+        let mut output: Box<dyn OutputStream> = Box::new(stream.clone());
+        output.write("done\nno newline".into()).unwrap();
+        assert_eq!(recorder.written(), "done\n");
+        drop(output);
+        drop(stream);
+        assert_eq!(recorder.written(), "done\nno newline");
+    }
+
+    #[test]
+    fn oversized_writes_are_refused() {
+        let mut stream = PipedWriteStream::with_max_write_bytes(Recorder::default(), 4);
+        assert_eq!(stream.check_write().unwrap(), 4);
+        stream.write("1234".into()).unwrap();
+        assert!(stream.write("12345".into()).is_err());
+    }
+
+    #[tokio::test]
+    async fn async_writes_are_bounded() {
+        use tokio::io::AsyncWriteExt;
+
+        let recorder = Recorder::default();
+        let mut stream = PipedWriteStream::with_max_write_bytes(recorder.clone(), 1024);
+        let flood = vec![b'x'; 1024 * 1024];
+        stream.write_all(&flood).await.unwrap();
+        assert_eq!(recorder.written.lock().unwrap().len(), flood.len());
+        assert_eq!(*recorder.max_write.lock().unwrap(), 1024);
+    }
+}
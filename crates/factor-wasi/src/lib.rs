@@ -1,4 +1,6 @@
+mod fs;
 mod io;
+pub mod runtime_config;
 pub mod spin;
 mod wasi_2023_10_18;
 mod wasi_2023_11_10;
@@ -10,29 +12,47 @@ use std::{
     path::Path,
 };
 
-use io::{PipeReadStream, PipedWriteStream};
+use fs::{HasWasiFs, WasiFsView};
+use io::{LineWriter, PipeReadStream, PipedWriteStream};
 use spin_factors::{
-    anyhow, AppComponent, Factor, FactorInstanceBuilder, InitContext, PrepareContext,
-    RuntimeFactors, RuntimeFactorsInstanceState,
+    anyhow::{self, Context},
+    AppComponent, Factor, FactorInstanceBuilder, InitContext, PrepareContext, RuntimeFactors,
+    RuntimeFactorsInstanceState,
 };
+use spin_locked_app::MetadataKey;
 use wasmtime::component::HasData;
 use wasmtime_wasi::cli::{StdinStream, StdoutStream};
 use wasmtime_wasi::random::WasiRandomCtx;
 use wasmtime_wasi::{DirPerms, FilePerms, ResourceTable, WasiCtx, WasiCtxBuilder, WasiCtxView};
 
+pub use io::{PipeOptions, DEFAULT_MAX_WRITE_BYTES};
+pub use runtime_config::RuntimeConfig;
 pub use wasmtime_wasi::SocketAddrUse;
 
+/// Metadata key for the directory which relative paths resolve against in a
+/// component.
+pub const WORKING_DIRECTORY_KEY: MetadataKey = MetadataKey::new("working_directory");
+
 pub struct WasiFactor {
     files_mounter: Box<dyn FilesMounter>,
+    created_modes: CreatedModes,
 }
 
 impl WasiFactor {
     pub fn new(files_mounter: impl FilesMounter + 'static) -> Self {
         Self {
             files_mounter: Box::new(files_mounter),
+            created_modes: CreatedModes::default(),
         }
     }
 
+    /// Sets the permissions of the files and directories guests create in
+    /// writable mounts, for modes which the runtime config doesn't set.
+    pub fn created_modes(mut self, created_modes: CreatedModes) -> Self {
+        self.created_modes = created_modes;
+        self
+    }
+
     pub fn get_wasi_impl(
         runtime_instance_state: &mut impl RuntimeFactorsInstanceState,
     ) -> Option<WasiCtxView<'_>> {
@@ -96,6 +116,28 @@ trait InitContextExt: InitContext<WasiFactor> {
         add_to_linker(self.linker(), &O::default(), Self::get_wasi)
     }
 
+    fn get_wasi_fs(data: &mut Self::StoreData) -> WasiFsView<'_> {
+        let (state, table) = Self::get_data_with_table(data);
+        WasiFsView {
+            wasi: WasiCtxView {
+                ctx: &mut state.ctx,
+                table,
+            },
+            working_directory: state.working_directory.as_deref(),
+            created_modes: state.created_modes,
+        }
+    }
+
+    fn link_wasi_fs_bindings(
+        &mut self,
+        add_to_linker: fn(
+            &mut wasmtime::component::Linker<Self::StoreData>,
+            fn(&mut Self::StoreData) -> WasiFsView<'_>,
+        ) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        add_to_linker(self.linker(), Self::get_wasi_fs)
+    }
+
     fn link_random_bindings(
         &mut self,
         add_to_linker: fn(
@@ -131,8 +173,8 @@ impl HasData for HasRandom {
 }
 
 impl Factor for WasiFactor {
-    type RuntimeConfig = ();
-    type AppState = ();
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
     type InstanceBuilder = InstanceBuilder;
 
     fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
@@ -140,7 +182,7 @@ impl Factor for WasiFactor {
 
         ctx.link_wasi_bindings(bindings::clocks::wall_clock::add_to_linker::<_, HasWasi>)?;
         ctx.link_wasi_bindings(bindings::clocks::monotonic_clock::add_to_linker::<_, HasWasi>)?;
-        ctx.link_wasi_bindings(bindings::filesystem::types::add_to_linker::<_, HasWasi>)?;
+        ctx.link_wasi_fs_bindings(bindings::filesystem::types::add_to_linker::<_, HasWasiFs>)?;
         ctx.link_wasi_bindings(bindings::filesystem::preopens::add_to_linker::<_, HasWasi>)?;
         ctx.link_io_bindings(bindings::io::error::add_to_linker::<_, HasIo>)?;
         ctx.link_io_bindings(bindings::io::poll::add_to_linker::<_, HasIo>)?;
@@ -149,7 +191,7 @@ impl Factor for WasiFactor {
         ctx.link_random_bindings(bindings::random::insecure::add_to_linker::<_, HasRandom>)?;
         ctx.link_random_bindings(bindings::random::insecure_seed::add_to_linker::<_, HasRandom>)?;
         ctx.link_wasi_default_bindings(bindings::cli::exit::add_to_linker::<_, HasWasi>)?;
-        ctx.link_wasi_bindings(bindings::cli::environment::add_to_linker::<_, HasWasi>)?;
+        ctx.link_wasi_fs_bindings(bindings::cli::environment::add_to_linker::<_, HasWasiFs>)?;
         ctx.link_wasi_bindings(bindings::cli::stdin::add_to_linker::<_, HasWasi>)?;
         ctx.link_wasi_bindings(bindings::cli::stdout::add_to_linker::<_, HasWasi>)?;
         ctx.link_wasi_bindings(bindings::cli::stderr::add_to_linker::<_, HasWasi>)?;
@@ -173,23 +215,50 @@ impl Factor for WasiFactor {
 
     fn configure_app<T: RuntimeFactors>(
         &self,
-        _ctx: spin_factors::ConfigureAppContext<T, Self>,
+        mut ctx: spin_factors::ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        Ok(())
+        for component in ctx.app().components() {
+            if let Some(working_directory) = component.get_metadata(WORKING_DIRECTORY_KEY)? {
+                let mounts = component
+                    .files()
+                    .map(|mount| mount.path.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>();
+                let id = component.id();
+                validate_working_directory(&working_directory, mounts.iter().map(String::as_str))
+                    .with_context(|| format!("invalid `working_directory` for component {id:?}"))?;
+            }
+        }
+        let configured = ctx.take_runtime_config().unwrap_or_default().created_modes;
+        Ok(AppState {
+            created_modes: CreatedModes {
+                file: configured.file.or(self.created_modes.file),
+                dir: configured.dir.or(self.created_modes.dir),
+            },
+        })
     }
 
     fn prepare<T: RuntimeFactors>(
         &self,
         ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<InstanceBuilder> {
-        let mut wasi_ctx = WasiCtxBuilder::new();
+        let mut builder = InstanceBuilder {
+            ctx: WasiCtxBuilder::new(),
+            mounts: vec![],
+            working_directory: None,
+            created_modes: ctx.app_state().created_modes,
+        };
 
         // Mount files
-        let mount_ctx = MountFilesContext { ctx: &mut wasi_ctx };
+        let mount_ctx = MountFilesContext {
+            ctx: &mut builder.ctx,
+            mounts: &mut builder.mounts,
+        };
         self.files_mounter
             .mount_files(ctx.app_component(), mount_ctx)?;
 
-        let mut builder = InstanceBuilder { ctx: wasi_ctx };
+        if let Some(working_directory) = ctx.app_component().get_metadata(WORKING_DIRECTORY_KEY)? {
+            builder.cwd(working_directory)?;
+        }
 
         // Apply environment variables
         builder.env(ctx.app_component().environment());
@@ -224,6 +293,7 @@ impl FilesMounter for DummyFilesMounter {
 
 pub struct MountFilesContext<'a> {
     ctx: &'a mut WasiCtxBuilder,
+    mounts: &'a mut Vec<String>,
 }
 
 impl MountFilesContext<'_> {
@@ -238,14 +308,38 @@ impl MountFilesContext<'_> {
         } else {
             (DirPerms::READ, FilePerms::READ)
         };
+        let guest_path = guest_path.as_ref();
         self.ctx
             .preopened_dir(host_path, guest_path, dir_perms, file_perms)?;
+        self.mounts.push(guest_path.to_owned());
         Ok(())
     }
 }
 
+/// The permissions of the files and directories which guests create in
+/// writable mounts, as unix modes such as `0o640`.
+///
+/// Created files and directories get their usual permissions where a mode is
+/// `None`. Applying modes is best effort: it is only supported on unix, and
+/// only for guests using WASI 0.2.0 or later.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CreatedModes {
+    /// The mode of created files.
+    pub file: Option<u32>,
+    /// The mode of created directories.
+    pub dir: Option<u32>,
+}
+
+pub struct AppState {
+    created_modes: CreatedModes,
+}
+
 pub struct InstanceBuilder {
     ctx: WasiCtxBuilder,
+    /// The guest paths of the preopened directories.
+    mounts: Vec<String>,
+    working_directory: Option<String>,
+    created_modes: CreatedModes,
 }
 
 impl InstanceBuilder {
@@ -269,6 +363,25 @@ impl InstanceBuilder {
         self.stdout(PipedWriteStream::new(w));
     }
 
+    /// Sets the WASI `stdout` descriptor to the given [`Write`]r, with the
+    /// given [`PipeOptions`].
+    pub fn stdout_pipe_with(
+        &mut self,
+        w: impl Write + Send + Sync + Unpin + 'static,
+        options: PipeOptions,
+    ) {
+        match options.max_line_bytes {
+            Some(max_line_bytes) => self.stdout(PipedWriteStream::with_max_write_bytes(
+                LineWriter::new(w, max_line_bytes),
+                options.max_write_bytes,
+            )),
+            None => self.stdout(PipedWriteStream::with_max_write_bytes(
+                w,
+                options.max_write_bytes,
+            )),
+        }
+    }
+
     /// Sets the WASI `stderr` descriptor to the given [`StdoutStream`].
     pub fn stderr(&mut self, stderr: impl StdoutStream + 'static) {
         self.ctx.stderr(stderr);
@@ -279,6 +392,25 @@ impl InstanceBuilder {
         self.stderr(PipedWriteStream::new(w));
     }
 
+    /// Sets the WASI `stderr` descriptor to the given [`Write`]r, with the
+    /// given [`PipeOptions`].
+    pub fn stderr_pipe_with(
+        &mut self,
+        w: impl Write + Send + Sync + Unpin + 'static,
+        options: PipeOptions,
+    ) {
+        match options.max_line_bytes {
+            Some(max_line_bytes) => self.stderr(PipedWriteStream::with_max_write_bytes(
+                LineWriter::new(w, max_line_bytes),
+                options.max_write_bytes,
+            )),
+            None => self.stderr(PipedWriteStream::with_max_write_bytes(
+                w,
+                options.max_write_bytes,
+            )),
+        }
+    }
+
     /// Appends the given strings to the WASI 'args'.
     pub fn args(&mut self, args: impl IntoIterator<Item = impl AsRef<str>>) {
         for arg in args {
@@ -301,24 +433,72 @@ impl InstanceBuilder {
         guest_path: impl AsRef<str>,
         writable: bool,
     ) -> anyhow::Result<()> {
-        let (dir_perms, file_perms) = if writable {
-            (DirPerms::all(), FilePerms::all())
-        } else {
-            (DirPerms::READ, FilePerms::READ)
-        };
-        self.ctx
-            .preopened_dir(host_path, guest_path, dir_perms, file_perms)?;
+        MountFilesContext {
+            ctx: &mut self.ctx,
+            mounts: &mut self.mounts,
+        }
+        .preopened_dir(host_path, guest_path, writable)
+    }
+
+    /// Sets the directory which the guest resolves relative paths against,
+    /// reporting it as the initial working directory and in the `PWD`
+    /// environment variable.
+    ///
+    /// The directory must be an absolute path within a directory which has
+    /// already been mounted with [`Self::preopened_dir`].
+    pub fn cwd(&mut self, guest_path: impl Into<String>) -> anyhow::Result<()> {
+        let guest_path = guest_path.into();
+        validate_working_directory(&guest_path, self.mounts.iter().map(String::as_str))?;
+        self.ctx.env("PWD", &guest_path);
+        self.working_directory = Some(guest_path);
         Ok(())
     }
+
+    /// Sets the permissions of the files and directories the guest creates in
+    /// writable mounts.
+    pub fn created_modes(&mut self, created_modes: CreatedModes) {
+        self.created_modes = created_modes;
+    }
+}
+
+/// Checks that a working directory is an absolute path within one of the
+/// given mount paths.
+fn validate_working_directory<'a>(
+    working_directory: &str,
+    mounts: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<()> {
+    let path = Path::new(working_directory);
+    anyhow::ensure!(
+        working_directory.starts_with('/'),
+        "working directory {working_directory:?} is not an absolute path"
+    );
+    anyhow::ensure!(
+        !path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir)),
+        "working directory {working_directory:?} must not contain `..`"
+    );
+    anyhow::ensure!(
+        mounts.into_iter().any(|mount| path.starts_with(mount)),
+        "working directory {working_directory:?} is not within any files mount"
+    );
+    Ok(())
 }
 
 impl FactorInstanceBuilder for InstanceBuilder {
     type InstanceState = InstanceState;
 
     fn build(self) -> anyhow::Result<Self::InstanceState> {
-        let InstanceBuilder { ctx: mut wasi_ctx } = self;
+        let InstanceBuilder {
+            ctx: mut wasi_ctx,
+            mounts: _,
+            working_directory,
+            created_modes,
+        } = self;
         Ok(InstanceState {
             ctx: wasi_ctx.build(),
+            working_directory,
+            created_modes,
         })
     }
 }
@@ -348,4 +528,14 @@ impl InstanceBuilder {
 
 pub struct InstanceState {
     ctx: WasiCtx,
+    working_directory: Option<String>,
+    created_modes: CreatedModes,
+}
+
+impl InstanceState {
+    /// The permissions of the files and directories the instance creates in
+    /// writable mounts.
+    pub fn created_modes(&self) -> CreatedModes {
+        self.created_modes
+    }
 }
@@ -0,0 +1,13 @@
+#[cfg(feature = "spin-cli")]
+pub mod spin;
+
+use crate::CreatedModes;
+
+/// Runtime configuration for the WASI factor.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RuntimeConfig {
+    /// The permissions of the files and directories guests create in
+    /// writable mounts. Modes which are unset here fall back to those set
+    /// with [`crate::WasiFactor::created_modes`].
+    pub created_modes: CreatedModes,
+}
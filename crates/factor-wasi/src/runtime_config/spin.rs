@@ -0,0 +1,100 @@
+use serde::Deserialize;
+use spin_factors::anyhow::{self, Context as _};
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+use super::RuntimeConfig;
+use crate::CreatedModes;
+
+/// Get the runtime configuration for the WASI factor from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [wasi]
+/// # Optional; the unix modes of the files and directories which guests
+/// # create in writable mounts
+/// created_file_mode = 0o640
+/// created_dir_mode = 0o750
+/// ```
+///
+/// Returns `None` if there is no `[wasi]` section.
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(wasi) = table.get("wasi") else {
+        return Ok(None);
+    };
+    let wasi = wasi
+        .clone()
+        .try_into::<WasiToml>()
+        .context("invalid `[wasi]` runtime config")?;
+    for (key, mode) in [
+        ("created_file_mode", wasi.created_file_mode),
+        ("created_dir_mode", wasi.created_dir_mode),
+    ] {
+        if let Some(mode) = mode {
+            anyhow::ensure!(
+                mode <= 0o7777,
+                "`wasi.{key}` must be a unix mode such as 0o640, not {mode:#o}"
+            );
+        }
+    }
+    Ok(Some(RuntimeConfig {
+        created_modes: CreatedModes {
+            file: wasi.created_file_mode,
+            dir: wasi.created_dir_mode,
+        },
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WasiToml {
+    created_file_mode: Option<u32>,
+    created_dir_mode: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: toml::Table) -> anyhow::Result<Option<RuntimeConfig>> {
+        config_from_table(&toml)
+    }
+
+    #[test]
+    fn config_is_parsed() {
+        assert!(config(toml::Table::new()).unwrap().is_none());
+
+        let toml = toml::toml! {
+            [wasi]
+            created_file_mode = 0o640
+        };
+        let config = config(toml).unwrap().unwrap();
+        assert_eq!(
+            config.created_modes,
+            CreatedModes {
+                file: Some(0o640),
+                dir: None
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let invalid = [
+            toml::toml! {
+                [wasi]
+                created_file_mode = 0o170000
+            },
+            toml::toml! {
+                [wasi]
+                created_dir_mode = "0750"
+            },
+            toml::toml! {
+                [wasi]
+                created_mode = 0o640
+            },
+        ];
+        for toml in invalid {
+            assert!(config(toml.clone()).is_err(), "{toml}");
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use spin_factor_wasi::{DummyFilesMounter, WasiFactor};
+use spin_factor_wasi::{spin::SpinFilesMounter, DummyFilesMounter, WasiFactor};
 use spin_factors::{anyhow, RuntimeFactors};
 use spin_factors_test::{toml, TestEnvironment};
 use wasmtime_wasi::p2::bindings::cli::environment::Host;
@@ -28,3 +28,48 @@ async fn environment_works() -> anyhow::Result<()> {
     assert_eq!(val.as_deref(), Some("bar"));
     Ok(())
 }
+
+#[tokio::test]
+async fn working_directory_is_set_within_mount() -> anyhow::Result<()> {
+    let host_dir = tempfile::tempdir()?;
+    let factors = TestFactors {
+        wasi: WasiFactor::new(SpinFilesMounter::new(host_dir.path(), true)),
+    };
+    let manifest: toml::Table = toml::from_str(&format!(
+        r#"
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        files = [{{ source = {:?}, destination = "/work" }}]
+        working_directory = "/work/data"
+        "#,
+        host_dir.path().to_str().unwrap()
+    ))?;
+    let env = TestEnvironment::new(factors).extend_manifest(manifest);
+    let mut state = env.build_instance_state().await?;
+    let mut wasi = WasiFactor::get_wasi_impl(&mut state).unwrap();
+
+    let pwd = wasi
+        .get_environment()?
+        .into_iter()
+        .find_map(|(key, val)| (key == "PWD").then_some(val));
+    assert_eq!(pwd.as_deref(), Some("/work/data"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn working_directory_outside_mounts_fails_configuration() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        wasi: WasiFactor::new(DummyFilesMounter),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        working_directory = "/work"
+    });
+    let err = env.build_configured_app().await.err().unwrap();
+    assert!(
+        format!("{err:#}").contains("not within any files mount"),
+        "unexpected error: {err:#}"
+    );
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
-use syn::{parse_macro_input, Data, DeriveInput, Error};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Field, Ident, Type};
 
-#[proc_macro_derive(RuntimeFactors)]
+#[proc_macro_derive(RuntimeFactors, attributes(factors))]
 pub fn derive_factors(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let expanded = expand_factors(&input).unwrap_or_else(|err| err.into_compile_error());
@@ -19,6 +19,43 @@ pub fn derive_factors(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     expanded.into()
 }
 
+/// A field of a `#[derive(RuntimeFactors)]` struct.
+struct FactorsField<'a> {
+    name: &'a Ident,
+    ty: &'a Type,
+    /// Whether the field is a nested `RuntimeFactors` collection, marked with
+    /// `#[factors(flatten)]`, rather than a single factor.
+    flatten: bool,
+}
+
+impl<'a> FactorsField<'a> {
+    fn parse(input: &DeriveInput, field: &'a Field) -> syn::Result<Self> {
+        let name = field
+            .ident
+            .as_ref()
+            .ok_or_else(|| Error::new_spanned(input, "tuple structs are not supported"))?;
+        let mut flatten = false;
+        for attr in &field.attrs {
+            if !attr.path().is_ident("factors") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("flatten") {
+                    flatten = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported factors attribute; expected `flatten`"))
+                }
+            })?;
+        }
+        Ok(Self {
+            name,
+            ty: &field.ty,
+            flatten,
+        })
+    }
+}
+
 #[allow(non_snake_case)]
 fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
     let name = &input.ident;
@@ -46,21 +83,40 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
             ))
         }
     };
-    let mut factor_names = Vec::with_capacity(fields.len());
-    let mut factor_types = Vec::with_capacity(fields.len());
-    for field in fields.iter() {
-        factor_names.push(
-            field
-                .ident
-                .as_ref()
-                .ok_or_else(|| Error::new_spanned(input, "tuple structs are not supported"))?,
-        );
-        factor_types.push(&field.ty);
+    let fields = fields
+        .iter()
+        .map(|field| FactorsField::parse(input, field))
+        .collect::<syn::Result<Vec<_>>>()?;
+    let (nested, factors): (Vec<_>, Vec<_>) = fields.iter().partition(|field| field.flatten);
+    // Duplicates within this struct can be reported here, at the field;
+    // those involving nested collections are caught by the assertion below
+    let mut seen = std::collections::HashSet::new();
+    for field in &factors {
+        let ty = field.ty.to_token_stream().to_string().replace(' ', "");
+        if !seen.insert(ty.clone()) {
+            return Err(Error::new_spanned(
+                field.ty,
+                format!("factor `{ty}` appears more than once in `{name}`"),
+            ));
+        }
     }
+    let factor_names = factors.iter().map(|field| field.name).collect::<Vec<_>>();
+    let factor_types = factors.iter().map(|field| field.ty).collect::<Vec<_>>();
+    let factor_markers = factor_names
+        .iter()
+        .map(|name| format_ident!("__{name}"))
+        .collect::<Vec<_>>();
+    let nested_names = nested.iter().map(|field| field.name).collect::<Vec<_>>();
+    let nested_types = nested.iter().map(|field| field.ty).collect::<Vec<_>>();
+    let nested_markers = nested_names
+        .iter()
+        .map(|name| format_ident!("__{name}"))
+        .collect::<Vec<_>>();
 
     let Any = quote!(::std::any::Any);
     let Send = quote!(::std::marker::Send);
     let TypeId = quote!(::std::any::TypeId);
+    let PhantomData = quote!(::std::marker::PhantomData);
     let factors_crate = format_ident!("spin_factors");
     let factors_path = quote!(::#factors_crate);
     let wasmtime = quote!(#factors_path::wasmtime);
@@ -68,158 +124,389 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
     let Result = quote!(#factors_path::Result);
     let Error = quote!(#factors_path::Error);
     let Factor = quote!(#factors_path::Factor);
+    let RuntimeFactors = quote!(#factors_path::RuntimeFactors);
     let ConfiguredApp = quote!(#factors_path::ConfiguredApp);
     let FactorInstanceBuilder = quote!(#factors_path::FactorInstanceBuilder);
+    let RuntimeConfigFromSource = quote!(#factors_path::runtime_config::RuntimeConfigFromSource);
+    let flatten = quote!(#factors_path::flatten);
+    let tracing = quote!(#factors_path::tracing);
+    let Instant = quote!(::std::time::Instant);
+
+    // Steps which must run in field order, with each nested collection's
+    // factors in the position of its field
+    let mut init_steps = Vec::with_capacity(fields.len());
+    let mut init_nested_steps = Vec::with_capacity(fields.len());
+    let mut configure_steps = Vec::with_capacity(fields.len());
+    let mut prepare_steps = Vec::with_capacity(fields.len());
+    let mut build_fields = Vec::with_capacity(fields.len());
+    let mut runtime_config_fields = Vec::with_capacity(fields.len());
+    for FactorsField {
+        name,
+        ty,
+        flatten: is_nested,
+    } in &fields
+    {
+        let marker = format_ident!("__{name}");
+        if *is_nested {
+            init_steps.push(quote! {
+                <#ty as #RuntimeFactors>::init_nested::<T, #marker>(&mut self.#name, linker)?;
+            });
+            init_nested_steps.push(quote! {
+                <#ty as #RuntimeFactors>::init_nested::<T, #flatten::Join<P, #marker>>(
+                    &mut self.#name,
+                    linker,
+                )?;
+            });
+            configure_steps.push(quote! {
+                <#ty as #RuntimeFactors>::configure_nested::<O, #flatten::Join<P, #marker>>(
+                    &self.#name,
+                    app,
+                    app_state,
+                    runtime_config.#name,
+                    background_tasks,
+                )?;
+            });
+            prepare_steps.push(quote! {
+                <#ty as #RuntimeFactors>::prepare_nested::<O, #flatten::Join<P, #marker>>(
+                    &self.#name,
+                    configured_app,
+                    app_component,
+                    builders,
+                )?;
+            });
+            build_fields.push(quote! {
+                #name: <#ty as #RuntimeFactors>::build_instance_state(&self.#name, builders.#name)?,
+            });
+            runtime_config_fields.push(quote! {
+                #name: <<#ty as #RuntimeFactors>::RuntimeConfig as #RuntimeConfigFromSource<S>>::from_source_unfinalized(source)?,
+            });
+        } else {
+            init_steps.push(quote! {
+                #Factor::init(
+                    &mut self.#name,
+                    &mut #factors_path::FactorInitContext::<'_, T, #marker> {
+                        linker,
+                        _marker: #PhantomData,
+                    },
+                ).map_err(#Error::factor_init_error::<#ty>)?;
+            });
+            init_nested_steps.push(quote! {
+                #Factor::init(
+                    &mut self.#name,
+                    &mut #flatten::NestedFactorInitContext::<'_, T, P, #marker> {
+                        linker,
+                        _marker: #PhantomData,
+                    },
+                ).map_err(#Error::factor_init_error::<#ty>)?;
+            });
+            configure_steps.push(quote! {
+                let state = #Factor::configure_app(
+                    &self.#name,
+                    #factors_path::ConfigureAppContext::<O, #ty>::new(
+                        app,
+                        &*app_state,
+                        runtime_config.#name,
+                        background_tasks,
+                    )?,
+                ).map_err(#Error::factor_configure_app_error::<#ty>)?;
+                P::get(app_state).#name = Some(state);
+            });
+            prepare_steps.push(quote! {
+                let start = timed.then(#Instant::now);
+                let builder = #Factor::prepare::<O>(
+                    &self.#name,
+                    #factors_path::PrepareContext::new(
+                        configured_app.app_state::<#ty>().unwrap(),
+                        app_component,
+                        &mut *builders,
+                    ),
+                ).map_err(#Error::factor_prepare_error::<#ty>)?;
+                P::get(builders).#name = Some(builder);
+                if let Some(start) = start {
+                    span.record(
+                        concat!("spin.factor.", stringify!(#name), ".prepare_ms"),
+                        start.elapsed().as_secs_f64() * 1000.0,
+                    );
+                }
+            });
+            build_fields.push(quote! {
+                #name: #FactorInstanceBuilder::build(
+                    builders.#name.unwrap()
+                ).map_err(#Error::factor_build_error::<#ty>)?,
+            });
+            runtime_config_fields.push(quote! {
+                #name: <S as #factors_path::FactorRuntimeConfigSource<#ty>>::get_runtime_config(source)?,
+            });
+        }
+    }
+
+    // The names of this collection's factors, for reporting duplicates
+    let factor_name_lists = fields.iter().map(|field| {
+        let ty = field.ty;
+        if field.flatten {
+            quote!(<#ty as #RuntimeFactors>::FACTOR_NAMES)
+        } else {
+            quote!(&[stringify!(#ty)])
+        }
+    });
+    let factor_name_counts = fields.iter().map(|field| {
+        let ty = field.ty;
+        if field.flatten {
+            quote!(<#ty as #RuntimeFactors>::FACTOR_NAMES.len())
+        } else {
+            quote!(1)
+        }
+    });
+    let factor_names_list = quote! {
+        const __FACTOR_NAMES: [&str; 0 #( + #factor_name_counts )*] =
+            #flatten::concat_factor_names(&[#( #factor_name_lists ),*]);
+    };
+    // A factor which appears twice, at any level, fails to compile here if
+    // it is spelled the same way both times; otherwise `init` fails
+    let assert_each_factor_once = quote_spanned! {name.span()=>
+        const _: () = #flatten::assert_each_factor_named_once(stringify!(#name), &__FACTOR_NAMES);
+    };
 
     Ok(quote! {
-        impl #factors_path::RuntimeFactors for #name {
-            type AppState = #app_state_name;
-            type InstanceBuilders = #builders_name;
-            type InstanceState = #state_name;
-            type RuntimeConfig = #runtime_config_name;
-
-            fn init<T: #factors_path::AsInstanceState<Self::InstanceState> + Send + 'static>(
-                &mut self,
-                linker: &mut #wasmtime::component::Linker<T>,
-            ) -> #Result<()> {
-                let factor_type_ids = [#(
-                    (stringify!(#factor_types), #TypeId::of::<(<#factor_types as #Factor>::InstanceBuilder, <#factor_types as #Factor>::AppState)>()),
-                )*];
-
-                let mut unique = ::std::collections::HashSet::new();
-                for (name, type_id) in factor_type_ids {
-                    if !unique.insert(type_id) {
-                        return Err(#Error::DuplicateFactorTypes(name.to_owned()));
+        const _: () = {
+            #(
+                #[allow(non_camel_case_types)]
+                struct #factor_markers;
+
+                impl #factors_path::FactorField for #factor_markers {
+                    type State = #state_name;
+                    type Factor = #factor_types;
+
+                    fn get(state: &mut #state_name) -> (
+                        &mut #factors_path::FactorInstanceState<#factor_types>,
+                        &mut #ResourceTable,
+                    ) {
+                        (&mut state.#factor_names, &mut state.__table)
+                    }
+
+                    fn get_field(
+                        state: &mut #state_name,
+                    ) -> &mut #factors_path::FactorInstanceState<#factor_types> {
+                        &mut state.#factor_names
                     }
                 }
+            )*
 
-                #(
-                    #[allow(non_camel_case_types)]
-                    struct #factor_names;
-
-                    impl #factors_path::FactorField for #factor_names {
-                        type State = #state_name;
-                        type Factor = #factor_types;
-
-                        fn get(state: &mut #state_name) -> (
-                            &mut #factors_path::FactorInstanceState<#factor_types>,
-                            &mut #wasmtime::component::ResourceTable,
-                        ) {
-                            (&mut state.#factor_names, &mut state.__table)
+            #(
+                #[allow(non_camel_case_types)]
+                struct #nested_markers;
+
+                impl #flatten::FieldPath<#app_state_name> for #nested_markers {
+                    type Target = <#nested_types as #RuntimeFactors>::AppState;
+
+                    fn get(parent: &mut #app_state_name) -> &mut Self::Target {
+                        &mut parent.#nested_names
+                    }
+                }
+
+                impl #flatten::FieldPath<#builders_name> for #nested_markers {
+                    type Target = <#nested_types as #RuntimeFactors>::InstanceBuilders;
+
+                    fn get(parent: &mut #builders_name) -> &mut Self::Target {
+                        &mut parent.#nested_names
+                    }
+                }
+
+                impl #flatten::FieldPath<#state_name> for #nested_markers {
+                    type Target = <#nested_types as #RuntimeFactors>::InstanceState;
+
+                    fn get(parent: &mut #state_name) -> &mut Self::Target {
+                        &mut parent.#nested_names
+                    }
+                }
+
+                impl<T: #factors_path::AsInstanceState<#state_name>> #flatten::StatePath<T> for #nested_markers {
+                    type State = <#nested_types as #RuntimeFactors>::InstanceState;
+
+                    fn get(store: &mut T) -> (&mut Self::State, &mut #ResourceTable) {
+                        let state = store.as_instance_state();
+                        (&mut state.#nested_names, &mut state.__table)
+                    }
+                }
+            )*
+
+            #factor_names_list
+
+            impl #RuntimeFactors for #name {
+                type AppState = #app_state_name;
+                type InstanceBuilders = #builders_name;
+                type InstanceState = #state_name;
+                type RuntimeConfig = #runtime_config_name;
+                const FACTOR_NAMES: &'static [&'static str] = &__FACTOR_NAMES;
+
+                fn init<T: #factors_path::AsInstanceState<Self::InstanceState> + Send + 'static>(
+                    &mut self,
+                    linker: &mut #wasmtime::component::Linker<T>,
+                ) -> #Result<()> {
+                    let mut unique = ::std::collections::HashSet::new();
+                    for (name, type_id) in <Self as #RuntimeFactors>::factor_type_ids() {
+                        if !unique.insert(type_id) {
+                            return Err(#Error::DuplicateFactorTypes(name.to_owned()));
                         }
                     }
 
-                    #Factor::init(
-                        &mut self.#factor_names,
-                        &mut #factors_path::FactorInitContext::<'_, T, #factor_names> {
-                            linker,
-                            _marker: std::marker::PhantomData,
-                        },
-                    ).map_err(#Error::factor_init_error::<#factor_types>)?;
-                )*
-                Ok(())
-            }
+                    #( #init_steps )*
+                    Ok(())
+                }
 
-            fn configure_app(
-                &self,
-                app: #factors_path::App,
-                runtime_config: Self::RuntimeConfig,
-            ) -> #Result<#ConfiguredApp<Self>> {
-                let mut app_state = #app_state_name {
-                    #( #factor_names: None, )*
-                };
-                #(
-                    app_state.#factor_names = Some(
-                        #Factor::configure_app(
-                            &self.#factor_names,
-                            #factors_path::ConfigureAppContext::<Self, #factor_types>::new(
-                                &app,
-                                &app_state,
-                                runtime_config.#factor_names,
-                            )?,
-                        ).map_err(#Error::factor_configure_app_error::<#factor_types>)?
-                    );
-                )*
-                Ok(#ConfiguredApp::new(app, app_state))
-            }
+                fn factor_type_ids() -> ::std::vec::Vec<(&'static str, #TypeId)> {
+                    #[allow(unused_mut)]
+                    let mut ids = ::std::vec![#(
+                        (stringify!(#factor_types), #TypeId::of::<(<#factor_types as #Factor>::InstanceBuilder, <#factor_types as #Factor>::AppState)>()),
+                    )*];
+                    #(
+                        ids.extend(<#nested_types as #RuntimeFactors>::factor_type_ids());
+                    )*
+                    ids
+                }
 
-            fn prepare(
-                &self, configured_app: &#ConfiguredApp<Self>,
-                component_id: &str,
-            ) -> #Result<Self::InstanceBuilders> {
-                let app_component = configured_app.app().get_component(component_id).ok_or_else(|| {
-                    #factors_path::Error::UnknownComponent(component_id.to_string())
-                })?;
-                let mut builders = #builders_name {
-                    #( #factor_names: None, )*
-                };
-                #(
-                    builders.#factor_names = Some(
-                        #Factor::prepare::<Self>(
-                            &self.#factor_names,
-                            #factors_path::PrepareContext::new(
-                                configured_app.app_state::<#factor_types>().unwrap(),
-                                &app_component,
-                                &mut builders,
-                            ),
-                        ).map_err(#Error::factor_prepare_error::<#factor_types>)?
+                fn init_nested<T: Send + 'static, P: #flatten::StatePath<T, State = Self::InstanceState>>(
+                    &mut self,
+                    linker: &mut #wasmtime::component::Linker<T>,
+                ) -> #Result<()> {
+                    #( #init_nested_steps )*
+                    Ok(())
+                }
+
+                fn configure_app(
+                    &self,
+                    app: #factors_path::App,
+                    runtime_config: Self::RuntimeConfig,
+                ) -> #Result<#ConfiguredApp<Self>> {
+                    let mut app_state = #app_state_name::default();
+                    let background_tasks = #factors_path::BackgroundTasks::default();
+                    <Self as #RuntimeFactors>::configure_nested::<Self, #flatten::SelfPath>(
+                        self,
+                        &app,
+                        &mut app_state,
+                        runtime_config,
+                        &background_tasks,
+                    )?;
+                    Ok(#ConfiguredApp::new(app, app_state, background_tasks))
+                }
+
+                fn configure_nested<O: #RuntimeFactors, P: #flatten::FieldPath<O::AppState, Target = Self::AppState>>(
+                    &self,
+                    app: &#factors_path::App,
+                    app_state: &mut O::AppState,
+                    runtime_config: Self::RuntimeConfig,
+                    background_tasks: &#factors_path::BackgroundTasks,
+                ) -> #Result<()> {
+                    #( #configure_steps )*
+                    Ok(())
+                }
+
+                fn prepare(
+                    &self, configured_app: &#ConfiguredApp<Self>,
+                    component_id: &str,
+                ) -> #Result<Self::InstanceBuilders> {
+                    let app_component = configured_app.app().get_component(component_id).ok_or_else(|| {
+                        #factors_path::Error::UnknownComponent(component_id.to_string())
+                    })?;
+                    let mut builders = #builders_name::default();
+                    <Self as #RuntimeFactors>::prepare_nested::<Self, #flatten::SelfPath>(
+                        self,
+                        configured_app,
+                        &app_component,
+                        &mut builders,
+                    )?;
+                    Ok(builders)
+                }
+
+                // Unused if this collection has no factors of its own
+                #[allow(unused_variables)]
+                fn prepare_nested<O: #RuntimeFactors, P: #flatten::FieldPath<O::InstanceBuilders, Target = Self::InstanceBuilders>>(
+                    &self,
+                    configured_app: &#ConfiguredApp<O>,
+                    app_component: &#factors_path::AppComponent,
+                    builders: &mut O::InstanceBuilders,
+                ) -> #Result<()> {
+                    // Each factor's prepare duration is recorded on this span; skip
+                    // the timing entirely if nothing is listening.
+                    let span = #tracing::info_span!(
+                        "spin_factors.prepare",
+                        #( spin.factor.#factor_names.prepare_ms = #tracing::field::Empty, )*
                     );
-                )*
-                Ok(builders)
-            }
+                    let timed = !span.is_disabled();
+                    let _entered = span.enter();
+                    #( #prepare_steps )*
+                    Ok(())
+                }
 
-            fn build_instance_state(
-                &self,
-                builders: Self::InstanceBuilders,
-            ) -> #Result<Self::InstanceState> {
-                Ok(#state_name {
-                    __table: #ResourceTable::new(),
+                fn build_instance_state(
+                    &self,
+                    builders: Self::InstanceBuilders,
+                ) -> #Result<Self::InstanceState> {
+                    Ok(#state_name {
+                        __table: #ResourceTable::new(),
+                        #( #build_fields )*
+                    })
+                }
+
+                fn app_state<F: #Factor>(app_state: &Self::AppState) -> Option<&F::AppState> {
                     #(
-                        #factor_names: #FactorInstanceBuilder::build(
-                            builders.#factor_names.unwrap()
-                        ).map_err(#Error::factor_build_error::<#factor_types>)?,
+                        if let Some(state) = &app_state.#factor_names {
+                            if let Some(state) = <dyn #Any>::downcast_ref(state) {
+                                return Some(state)
+                            }
+                        }
                     )*
-                })
-            }
-
-            fn app_state<F: #Factor>(app_state: &Self::AppState) -> Option<&F::AppState> {
-                #(
-                    if let Some(state) = &app_state.#factor_names {
-                        if let Some(state) = <dyn #Any>::downcast_ref(state) {
+                    #(
+                        if let Some(state) = <#nested_types as #RuntimeFactors>::app_state::<F>(&app_state.#nested_names) {
                             return Some(state)
                         }
-                    }
-                )*
-                None
-            }
+                    )*
+                    None
+                }
 
-            fn instance_builder_mut<F: #Factor>(
-                builders: &mut Self::InstanceBuilders,
-            ) -> Option<Option<&mut F::InstanceBuilder>> {
-                let type_id = #TypeId::of::<(F::InstanceBuilder, F::AppState)>();
-                #(
-                    if type_id == #TypeId::of::<(<#factor_types as #Factor>::InstanceBuilder, <#factor_types as #Factor>::AppState)>() {
-                        return Some(
-                            builders.#factor_names.as_mut().map(|builder| {
-                                <dyn #Any>::downcast_mut(builder).unwrap()
-                            })
-                        );
-                    }
-                )*
-                None
+                fn instance_builder_mut<F: #Factor>(
+                    builders: &mut Self::InstanceBuilders,
+                ) -> Option<Option<&mut F::InstanceBuilder>> {
+                    let type_id = #TypeId::of::<(F::InstanceBuilder, F::AppState)>();
+                    #(
+                        if type_id == #TypeId::of::<(<#factor_types as #Factor>::InstanceBuilder, <#factor_types as #Factor>::AppState)>() {
+                            return Some(
+                                builders.#factor_names.as_mut().map(|builder| {
+                                    <dyn #Any>::downcast_mut(builder).unwrap()
+                                })
+                            );
+                        }
+                    )*
+                    #(
+                        if let Some(builder) = <#nested_types as #RuntimeFactors>::instance_builder_mut::<F>(&mut builders.#nested_names) {
+                            return Some(builder);
+                        }
+                    )*
+                    None
+                }
             }
-        }
 
+            #assert_each_factor_once
+        };
+
+        #[derive(Default)]
         #vis struct #app_state_name {
             #(
                 pub #factor_names: Option<<#factor_types as #Factor>::AppState>,
             )*
+            #(
+                pub #nested_names: <#nested_types as #RuntimeFactors>::AppState,
+            )*
         }
 
+        #[derive(Default)]
         #vis struct #builders_name {
             #(
                 #factor_names: Option<<#factor_types as #Factor>::InstanceBuilder>,
             )*
+            #(
+                #nested_names: <#nested_types as #RuntimeFactors>::InstanceBuilders,
+            )*
         }
 
         #[allow(dead_code)]
@@ -229,6 +516,11 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
                     self.#factor_names.as_mut().unwrap()
                 }
             )*
+            #(
+                pub fn #nested_names(&mut self) -> &mut <#nested_types as #RuntimeFactors>::InstanceBuilders {
+                    &mut self.#nested_names
+                }
+            )*
         }
 
         impl #factors_path::HasInstanceBuilder for #builders_name {
@@ -244,6 +536,11 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
                         );
                     }
                 )*
+                #(
+                    if let Some(builder) = #factors_path::HasInstanceBuilder::for_factor::<F>(&mut self.#nested_names) {
+                        return Some(builder);
+                    }
+                )*
                 None
             }
         }
@@ -253,6 +550,9 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
             #(
                 pub #factor_names: #factors_path::FactorInstanceState<#factor_types>,
             )*
+            #(
+                pub #nested_names: <#nested_types as #RuntimeFactors>::InstanceState,
+            )*
         }
 
         impl #factors_path::RuntimeFactorsInstanceState for #state_name {
@@ -264,6 +564,13 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
                         return Some((state, &mut self.__table))
                     }
                 )*
+                // A nested collection's resources live in this collection's
+                // table, not its own
+                #(
+                    if let Some((state, _)) = #factors_path::RuntimeFactorsInstanceState::get_with_table::<F>(&mut self.#nested_names) {
+                        return Some((state, &mut self.__table))
+                    }
+                )*
                 None
             }
 
@@ -287,23 +594,34 @@ fn expand_factors(input: &DeriveInput) -> syn::Result<TokenStream> {
             #(
                 pub #factor_names: Option<<#factor_types as #Factor>::RuntimeConfig>,
             )*
+            #(
+                pub #nested_names: <#nested_types as #RuntimeFactors>::RuntimeConfig,
+            )*
+        }
+
+        impl<S> #RuntimeConfigFromSource<S> for #runtime_config_name
+        where
+            #( S: #factors_path::FactorRuntimeConfigSource<#factor_types>, )*
+            #( <#nested_types as #RuntimeFactors>::RuntimeConfig: #RuntimeConfigFromSource<S>, )*
+        {
+            fn from_source_unfinalized(source: &mut S) -> #factors_path::anyhow::Result<Self> {
+                Ok(#runtime_config_name {
+                    #( #runtime_config_fields )*
+                })
+            }
         }
 
         impl #runtime_config_name {
             /// Get the runtime configuration from the given source.
             #[allow(dead_code)]
-            pub fn from_source<T>(mut source: T) -> anyhow::Result<Self>
-                where T: #(#factors_path::FactorRuntimeConfigSource<#factor_types> +)* #factors_path::RuntimeConfigSourceFinalizer
+            pub fn from_source<T>(mut source: T) -> #factors_path::anyhow::Result<Self>
+                where
+                    Self: #RuntimeConfigFromSource<T>,
+                    T: #factors_path::RuntimeConfigSourceFinalizer,
             {
-                #(
-                    let #factor_names = <T as #factors_path::FactorRuntimeConfigSource<#factor_types>>::get_runtime_config(&mut source)?;
-                )*
+                let runtime_config = <Self as #RuntimeConfigFromSource<T>>::from_source_unfinalized(&mut source)?;
                 source.finalize()?;
-                Ok(#runtime_config_name {
-                    #(
-                        #factor_names,
-                    )*
-                })
+                Ok(runtime_config)
             }
         }
     })
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+/// Which components of other apps each app loaded by a
+/// [`FactorsExecutor`](crate::FactorsExecutor) may chain to.
+///
+/// Chaining across apps is denied unless allowed here. Chaining to the
+/// components of the same app is not affected by the policy.
+#[derive(Clone, Debug, Default)]
+pub struct ChainingPolicy {
+    // Maps source app names -> targets reachable from the app
+    allowed: HashMap<String, Vec<ChainingTarget>>,
+}
+
+#[derive(Clone, Debug)]
+struct ChainingTarget {
+    app: String,
+    // `None` if every component of the app is reachable
+    component: Option<String>,
+}
+
+impl ChainingPolicy {
+    /// Allows the components of `source_app` to chain to any component of
+    /// `target_app`.
+    pub fn allow_app(
+        mut self,
+        source_app: impl Into<String>,
+        target_app: impl Into<String>,
+    ) -> Self {
+        self.allowed
+            .entry(source_app.into())
+            .or_default()
+            .push(ChainingTarget {
+                app: target_app.into(),
+                component: None,
+            });
+        self
+    }
+
+    /// Allows the components of `source_app` to chain to the given component
+    /// of `target_app`.
+    pub fn allow_component(
+        mut self,
+        source_app: impl Into<String>,
+        target_app: impl Into<String>,
+        component_id: impl Into<String>,
+    ) -> Self {
+        self.allowed
+            .entry(source_app.into())
+            .or_default()
+            .push(ChainingTarget {
+                app: target_app.into(),
+                component: Some(component_id.into()),
+            });
+        self
+    }
+
+    /// Returns whether the components of `source_app` may chain to the given
+    /// component of `target_app`.
+    pub fn allows(&self, source_app: &str, target_app: &str, component_id: &str) -> bool {
+        source_app == target_app
+            || self.allowed.get(source_app).is_some_and(|targets| {
+                targets.iter().any(|target| {
+                    target.app == target_app
+                        && target
+                            .component
+                            .as_ref()
+                            .is_none_or(|component| component == component_id)
+                })
+            })
+    }
+}
+
+/// Why a request could not be chained to a component of another app.
+#[derive(Debug)]
+pub enum ChainingError {
+    /// The [`ChainingPolicy`] doesn't allow the source app to reach the
+    /// component.
+    Denied {
+        source_app: String,
+        target_app: String,
+        component_id: String,
+    },
+    /// No app with the name is loaded.
+    NoSuchApp(String),
+    /// The app has no component with the ID.
+    NoSuchComponent {
+        target_app: String,
+        component_id: String,
+    },
+    /// The app was unloaded before the chained request completed.
+    Unloaded(String),
+}
+
+impl std::fmt::Display for ChainingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Denied {
+                source_app,
+                target_app,
+                component_id,
+            } => write!(
+                f,
+                "app {source_app:?} is not allowed to chain to component {component_id:?} of app {target_app:?}"
+            ),
+            Self::NoSuchApp(app) => write!(f, "no app {app:?} is loaded"),
+            Self::NoSuchComponent {
+                target_app,
+                component_id,
+            } => write!(f, "app {target_app:?} has no component {component_id:?}"),
+            Self::Unloaded(app) => {
+                write!(f, "app {app:?} was unloaded before the chained request completed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChainingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_denies_unless_allowed() {
+        let policy = ChainingPolicy::default()
+            .allow_app("frontend", "billing")
+            .allow_component("frontend", "users", "lookup");
+
+        assert!(policy.allows("frontend", "billing", "charge"));
+        assert!(policy.allows("frontend", "users", "lookup"));
+        assert!(!policy.allows("frontend", "users", "admin"));
+        assert!(!policy.allows("frontend", "audit", "log"));
+        // Allowed in one direction only
+        assert!(!policy.allows("billing", "frontend", "home"));
+        // Within an app
+        assert!(policy.allows("audit", "audit", "log"));
+    }
+}
@@ -1,14 +1,46 @@
-use std::{collections::HashMap, sync::Arc};
+mod chaining;
+mod tasks;
+mod timing;
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, OnceLock, Weak},
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
-use spin_app::{App, AppComponent};
+use sha2::{Digest, Sha256};
+use spin_app::{
+    limits::{EffectiveLimits, LimitsResolver},
+    locked::{BuildMetadata, ComponentSourceKind},
+    App, AppComponent, APP_NAME_KEY,
+};
 use spin_core::{async_trait, Component};
 use spin_factors::{
     AsInstanceState, ConfiguredApp, Factor, HasInstanceBuilder, RuntimeFactors,
     RuntimeFactorsInstanceState,
 };
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tracing::{field::Empty, Instrument};
+
+use crate::{
+    tasks::Supervisor,
+    timing::{as_millis_f64, Timings},
+};
+
+pub use crate::{
+    chaining::{ChainingError, ChainingPolicy},
+    tasks::{BackgroundTaskState, BackgroundTaskStatus},
+    timing::{ComponentTimingStats, PhaseTimingStats},
+};
 
-/// A FactorsExecutor manages execution of a Spin app.
+/// A FactorsExecutor manages execution of Spin apps.
+///
+/// Several apps may be loaded with one executor, sharing its engine. Each is
+/// registered under its name while it is loaded, so that requests can be
+/// chained from the components of one app to those of another, as allowed by
+/// the executor's [`ChainingPolicy`].
 ///
 /// It is generic over the executor's [`RuntimeFactors`]. Additionally, it
 /// holds any other per-instance state needed by the caller.
@@ -16,6 +48,12 @@ pub struct FactorsExecutor<T: RuntimeFactors, U: 'static = ()> {
     core_engine: spin_core::Engine<InstanceState<T::InstanceState, U>>,
     factors: T,
     hooks: Vec<Box<dyn ExecutorHooks<T, U>>>,
+    /// Limits the number of concurrent instantiations, if set.
+    instantiation_permits: Option<Semaphore>,
+    limits: LimitsResolver,
+    chaining_policy: ChainingPolicy,
+    // Maps app names -> loaded apps
+    apps: Mutex<HashMap<String, Weak<LoadedApp<T, U>>>>,
 }
 
 impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
@@ -33,9 +71,36 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
             factors,
             core_engine: core_engine_builder.build(),
             hooks: Default::default(),
+            instantiation_permits: None,
+            limits: Default::default(),
+            chaining_policy: Default::default(),
+            apps: Default::default(),
         })
     }
 
+    /// Limits the number of components which may be instantiated at once to
+    /// `n`, e.g. so that bursts of new instances don't tie up every core.
+    ///
+    /// Instantiations beyond the limit wait for an earlier one to finish
+    /// rather than failing.
+    pub fn with_max_parallelism(mut self, n: usize) -> Self {
+        self.instantiation_permits = Some(Semaphore::new(n.max(1)));
+        self
+    }
+
+    /// Sets the resolver for the limits of each loaded component, which
+    /// applies the operator's limits and the host's caps to the limits in the
+    /// app's manifest.
+    pub fn set_limits(&mut self, limits: LimitsResolver) {
+        self.limits = limits;
+    }
+
+    /// Sets the policy for which components of other apps each loaded app's
+    /// components may chain to. By default, no app may chain to another.
+    pub fn set_chaining_policy(&mut self, policy: ChainingPolicy) {
+        self.chaining_policy = policy;
+    }
+
     pub fn core_engine(&self) -> &spin_core::Engine<InstanceState<T::InstanceState, U>> {
         &self.core_engine
     }
@@ -47,13 +112,41 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
         self.hooks.push(Box::new(hooks));
     }
 
+    /// Inserts the given [`ExecutorHooks`] at `position` in this executor's
+    /// hooks, shifting any later hooks back, e.g. so that authorization hooks
+    /// can run before hooks which were added earlier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `position` is greater than the number of hooks added so far.
+    pub fn add_hooks_ordered(
+        &mut self,
+        hooks: impl ExecutorHooks<T, U> + 'static,
+        position: usize,
+    ) {
+        assert!(
+            position <= self.hooks.len(),
+            "hooks position {position} is out of bounds (there are {} hooks)",
+            self.hooks.len()
+        );
+        self.hooks.insert(position, Box::new(hooks));
+    }
+
     /// Loads a [`App`] with this executor.
+    ///
+    /// The app is registered under its name, or its ID if it has no name,
+    /// until the returned [`FactorsExecutorApp`] is dropped or the app is
+    /// unloaded with [`Self::unload_app`]. Fails if an app with the same name
+    /// is already loaded.
     pub async fn load_app(
         self: Arc<Self>,
         app: App,
         runtime_config: T::RuntimeConfig,
         component_loader: &impl ComponentLoader<T, U>,
     ) -> anyhow::Result<FactorsExecutorApp<T, U>> {
+        let name = app
+            .get_metadata(APP_NAME_KEY)?
+            .unwrap_or_else(|| app.id().to_owned());
         let configured_app = self
             .factors
             .configure_app(app, runtime_config)
@@ -65,20 +158,166 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
 
         let components = configured_app.app().components();
         let mut component_instance_pres = HashMap::with_capacity(components.len());
+        let mut component_hashes = HashMap::with_capacity(components.len());
+        let mut component_limits = HashMap::with_capacity(components.len());
 
         for component in components {
+            let limits = self
+                .limits
+                .resolve(&component)
+                .with_context(|| format!("invalid limits for component {:?}", component.id()))?;
+            if !limits.clamped.is_empty() {
+                tracing::warn!(
+                    "component {:?} limits {} exceed the host's caps; lowered to the caps",
+                    component.id(),
+                    limits.clamped.join(", "),
+                );
+            }
+            anyhow::ensure!(
+                limits.max_fuel.is_none() || self.core_engine.consumes_fuel(),
+                "component {:?} has a max_fuel limit, but the engine doesn't meter fuel",
+                component.id(),
+            );
+            component_limits.insert(component.id().to_string(), ComponentLimits::new(limits));
+
             let instance_pre = component_loader
                 .load_instance_pre(&self.core_engine, &component)
                 .await?;
+            component_hashes.insert(component.id().to_string(), OnceLock::new());
             component_instance_pres.insert(component.id().to_string(), instance_pre);
         }
 
-        Ok(FactorsExecutorApp {
+        let timings = Timings::new(component_instance_pres.keys().map(String::as_str));
+        let background_tasks = Supervisor::start(configured_app.take_background_tasks());
+
+        let loaded = Arc::new(LoadedApp {
+            name: name.clone(),
             executor: self.clone(),
             configured_app,
             component_instance_pres,
+            component_hashes,
+            component_limits,
+            timings,
+            background_tasks,
+            unloaded: watch::channel(false).0,
+        });
+        {
+            let mut apps = self.apps.lock().unwrap();
+            if apps.get(&name).is_some_and(|app| app.strong_count() > 0) {
+                anyhow::bail!("an app named {name:?} is already loaded");
+            }
+            apps.insert(name, Arc::downgrade(&loaded));
+        }
+
+        Ok(FactorsExecutorApp {
+            loaded,
+            owner: true,
+        })
+    }
+
+    /// Returns the loaded app with the given name, if there is one.
+    pub fn app(&self, name: &str) -> Option<FactorsExecutorApp<T, U>> {
+        let loaded = self.apps.lock().unwrap().get(name)?.upgrade()?;
+        Some(FactorsExecutorApp {
+            loaded,
+            owner: false,
         })
     }
+
+    /// Returns the names of the loaded apps, sorted.
+    pub fn list_apps(&self) -> Vec<String> {
+        let mut names = self
+            .apps
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, app)| app.strong_count() > 0)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Unloads the app with the given name, returning whether it was loaded.
+    ///
+    /// Chained requests to the app which are still in flight fail with
+    /// [`ChainingError::Unloaded`], and no more are dispatched to it.
+    pub fn unload_app(&self, name: &str) -> bool {
+        let Some(app) = self.apps.lock().unwrap().remove(name) else {
+            return false;
+        };
+        match app.upgrade() {
+            Some(loaded) => {
+                loaded.unloaded.send_replace(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resolves the target of a request chained from a component of
+    /// `source_app` to the given component of `target_app`, e.g. from the
+    /// host `component.app.spin.internal`, checking it against the executor's
+    /// [`ChainingPolicy`].
+    pub fn resolve_chaining_target(
+        &self,
+        source_app: &str,
+        target_app: &str,
+        component_id: &str,
+    ) -> Result<FactorsExecutorApp<T, U>, ChainingError> {
+        if !self
+            .chaining_policy
+            .allows(source_app, target_app, component_id)
+        {
+            return Err(ChainingError::Denied {
+                source_app: source_app.to_owned(),
+                target_app: target_app.to_owned(),
+                component_id: component_id.to_owned(),
+            });
+        }
+        let app = self
+            .app(target_app)
+            .ok_or_else(|| ChainingError::NoSuchApp(target_app.to_owned()))?;
+        if !app
+            .loaded
+            .component_instance_pres
+            .contains_key(component_id)
+        {
+            return Err(ChainingError::NoSuchComponent {
+                target_app: target_app.to_owned(),
+                component_id: component_id.to_owned(),
+            });
+        }
+        Ok(app)
+    }
+
+    /// Dispatches a request chained from a component of `source_app` to the
+    /// given component of `target_app`, by calling `dispatch` with the target
+    /// app once it has been resolved with [`Self::resolve_chaining_target`].
+    ///
+    /// If the target app is unloaded before `dispatch` completes, it is
+    /// cancelled and [`ChainingError::Unloaded`] is returned.
+    pub async fn dispatch_chained<F, Fut>(
+        &self,
+        source_app: &str,
+        target_app: &str,
+        component_id: &str,
+        dispatch: F,
+    ) -> Result<Fut::Output, ChainingError>
+    where
+        F: FnOnce(FactorsExecutorApp<T, U>) -> Fut,
+        Fut: Future,
+    {
+        let app = self.resolve_chaining_target(source_app, target_app, component_id)?;
+        let mut unloaded = app.loaded.unloaded.subscribe();
+        tokio::select! {
+            output = dispatch(app) => Ok(output),
+            // An error means the app was dropped, which also unloads it
+            _ = unloaded.wait_for(|unloaded| *unloaded) => {
+                Err(ChainingError::Unloaded(target_app.to_owned()))
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -97,6 +336,21 @@ where
         let _ = builder;
         Ok(())
     }
+
+    /// Post instantiate hooks run after [`FactorsInstanceBuilder::instantiate`]
+    /// succeeds or fails, e.g. to clean up or record the outcome.
+    ///
+    /// An error fails the instantiation if it had succeeded. If it had already
+    /// failed, the instantiation error is returned and hook errors are only
+    /// logged.
+    async fn post_instantiate(
+        &self,
+        component_id: &str,
+        result: &anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let _ = (component_id, result);
+        Ok(())
+    }
 }
 
 /// A ComponentLoader is responsible for loading Wasmtime [`Component`]s.
@@ -123,28 +377,98 @@ pub trait ComponentLoader<T: RuntimeFactors, U>: Sync {
 type InstancePre<T, U> =
     spin_core::InstancePre<InstanceState<<T as RuntimeFactors>::InstanceState, U>>;
 
+/// Parses a digest of the form `sha256:<hex>`, as in locked apps.
+fn parse_sha256_digest(digest: &str) -> anyhow::Result<[u8; 32]> {
+    let hex = digest
+        .strip_prefix("sha256:")
+        .context("digest is not of the form `sha256:<hex>`")?;
+    anyhow::ensure!(
+        hex.len() == 64 && hex.is_ascii(),
+        "digest is not 64 hex digits"
+    );
+    let mut bytes = [0; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).expect("digest is ASCII");
+        *byte = u8::from_str_radix(pair, 16).context("digest is not 64 hex digits")?;
+    }
+    Ok(bytes)
+}
+
 /// A FactorsExecutorApp represents a loaded Spin app, ready for instantiation.
 ///
 /// It is generic over the executor's [`RuntimeFactors`] and any ad-hoc additional
 /// per-instance state needed by the caller.
+///
+/// The app runs any background tasks registered by factors for as long as it
+/// is loaded. Dropping the app returned by [`FactorsExecutor::load_app`]
+/// unloads it, and cancels them once any chained requests to it have failed.
 pub struct FactorsExecutorApp<T: RuntimeFactors, U: 'static> {
+    loaded: Arc<LoadedApp<T, U>>,
+    // Whether this was returned by `load_app`, rather than looked up
+    owner: bool,
+}
+
+impl<T: RuntimeFactors, U: 'static> Drop for FactorsExecutorApp<T, U> {
+    fn drop(&mut self) {
+        if !self.owner {
+            return;
+        }
+        let mut apps = self.loaded.executor.apps.lock().unwrap();
+        let name = &self.loaded.name;
+        if apps
+            .get(name)
+            .is_some_and(|app| app.ptr_eq(&Arc::downgrade(&self.loaded)))
+        {
+            apps.remove(name);
+        }
+        self.loaded.unloaded.send_replace(true);
+    }
+}
+
+/// The state of a loaded app, shared with any requests chained to it.
+struct LoadedApp<T: RuntimeFactors, U: 'static> {
+    name: String,
     executor: Arc<FactorsExecutor<T, U>>,
     configured_app: ConfiguredApp<T>,
     // Maps component IDs -> InstancePres
     component_instance_pres: HashMap<String, InstancePre<T, U>>,
+    // Maps component IDs -> SHA-256 of the components, computed on first use
+    component_hashes: HashMap<String, OnceLock<[u8; 32]>>,
+    // Maps component IDs -> resolved limits
+    component_limits: HashMap<String, ComponentLimits>,
+    timings: Timings,
+    background_tasks: Supervisor,
+    /// Set to true when the app is unloaded.
+    unloaded: watch::Sender<bool>,
 }
 
 impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
     pub fn engine(&self) -> &spin_core::Engine<InstanceState<T::InstanceState, U>> {
-        &self.executor.core_engine
+        &self.loaded.executor.core_engine
+    }
+
+    /// Returns the name the app is registered under while it is loaded.
+    pub fn name(&self) -> &str {
+        &self.loaded.name
+    }
+
+    /// Returns true if `other` is this same loaded app, rather than another
+    /// app loaded under the same name.
+    pub fn is_same_app(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.loaded, &other.loaded)
+    }
+
+    /// Returns true once the app has been unloaded.
+    pub fn is_unloaded(&self) -> bool {
+        *self.loaded.unloaded.borrow()
     }
 
     pub fn configured_app(&self) -> &ConfiguredApp<T> {
-        &self.configured_app
+        &self.loaded.configured_app
     }
 
     pub fn app(&self) -> &App {
-        self.configured_app.app()
+        self.loaded.configured_app.app()
     }
 
     pub fn get_component(&self, component_id: &str) -> anyhow::Result<&Component> {
@@ -152,40 +476,202 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
     }
 
     pub fn get_instance_pre(&self, component_id: &str) -> anyhow::Result<&InstancePre<T, U>> {
-        self.component_instance_pres
+        self.loaded
+            .component_instance_pres
             .get(component_id)
             .with_context(|| format!("no such component {component_id:?}"))
     }
 
+    /// Returns the IDs of the app's components, sorted.
+    pub fn list_components(&self) -> Vec<&str> {
+        let mut component_ids = self
+            .loaded
+            .component_instance_pres
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        component_ids.sort_unstable();
+        component_ids
+    }
+
+    /// Returns the SHA-256 digest of the given component's binary, e.g. for
+    /// audit logs or as a cache key.
+    ///
+    /// This is the digest of the component's Wasm source recorded in the
+    /// locked app, or of its inline source. Failing both, it is the digest
+    /// of the compiled component, which may differ for the same source under
+    /// a different Spin version or engine configuration. It is computed on
+    /// first use.
+    pub fn get_component_hash(&self, component_id: &str) -> anyhow::Result<[u8; 32]> {
+        let hash = self
+            .loaded
+            .component_hashes
+            .get(component_id)
+            .with_context(|| format!("no such component {component_id:?}"))?;
+        if let Some(hash) = hash.get() {
+            return Ok(*hash);
+        }
+        let computed = self
+            .compute_component_hash(component_id)
+            .with_context(|| format!("failed to hash component {component_id:?}"))?;
+        Ok(*hash.get_or_init(|| computed))
+    }
+
+    fn compute_component_hash(&self, component_id: &str) -> anyhow::Result<[u8; 32]> {
+        let component = self
+            .app()
+            .get_component(component_id)
+            .with_context(|| format!("no such component {component_id:?}"))?;
+        if let Some(digest) = component.source_digest() {
+            return parse_sha256_digest(digest)
+                .with_context(|| format!("invalid source digest {digest:?}"));
+        }
+        if let Some(inline) = &component.source().content.inline {
+            return Ok(Sha256::digest(inline).into());
+        }
+        let bytes = self.get_component(component_id)?.serialize()?;
+        Ok(Sha256::digest(bytes).into())
+    }
+
+    /// Returns information about each of the app's components, sorted by ID,
+    /// e.g. for admin endpoints to show what is running.
+    ///
+    /// An item is an error only if the component's metadata is malformed.
+    pub fn components_info(&self) -> impl Iterator<Item = anyhow::Result<ComponentInfo>> + '_ {
+        self.list_components()
+            .into_iter()
+            .map(|component_id| self.component_info(component_id))
+    }
+
+    fn component_info(&self, component_id: &str) -> anyhow::Result<ComponentInfo> {
+        let app_component = self
+            .app()
+            .get_component(component_id)
+            .with_context(|| format!("no such component {component_id:?}"))?;
+        let exports = self
+            .get_component(component_id)?
+            .component_type()
+            .exports(self.engine().as_ref())
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        Ok(ComponentInfo {
+            id: component_id.to_owned(),
+            description: app_component.description()?,
+            source_digest: app_component.source_digest().map(Into::into),
+            source_kind: app_component.source_kind()?,
+            build_metadata: app_component.build_metadata()?,
+            exports,
+        })
+    }
+
+    /// Returns the limits which apply to instances of the given component.
+    pub fn limits(&self, component_id: &str) -> anyhow::Result<&EffectiveLimits> {
+        self.loaded
+            .component_limits
+            .get(component_id)
+            .map(|limits| &limits.effective)
+            .with_context(|| format!("no such component {component_id:?}"))
+    }
+
+    /// Returns rolling statistics of recent [`Self::prepare`] and
+    /// [`FactorsInstanceBuilder::instantiate`] durations for the given
+    /// component ID.
+    pub fn timing_stats(&self, component_id: &str) -> Option<ComponentTimingStats> {
+        self.loaded.timings.stats(component_id)
+    }
+
+    /// Returns the status of each background task registered by factors.
+    pub fn background_task_statuses(&self) -> Vec<BackgroundTaskStatus> {
+        self.loaded.background_tasks.statuses()
+    }
+
+    /// Returns a future which resolves to an error if a background task fails
+    /// under [`spin_factors::TaskPolicy::FailApp`], and otherwise never
+    /// resolves.
+    pub fn background_task_failure(&self) -> impl Future<Output = anyhow::Error> + Send + 'static {
+        self.loaded.background_tasks.failure()
+    }
+
+    /// Asks the app's background tasks to shut down and waits for them to
+    /// return, cancelling any which are still running after `timeout`.
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.loaded.background_tasks.shutdown(timeout).await
+    }
+
+    /// Dispatches a request chained from one of the app's components to the
+    /// given component of `target_app`, as with
+    /// [`FactorsExecutor::dispatch_chained`].
+    pub async fn dispatch_chained<F, Fut>(
+        &self,
+        target_app: &str,
+        component_id: &str,
+        dispatch: F,
+    ) -> Result<Fut::Output, ChainingError>
+    where
+        F: FnOnce(FactorsExecutorApp<T, U>) -> Fut,
+        Fut: Future,
+    {
+        self.loaded
+            .executor
+            .dispatch_chained(&self.loaded.name, target_app, component_id, dispatch)
+            .await
+    }
+
     /// Returns an instance builder for the given component ID.
     pub fn prepare(&self, component_id: &str) -> anyhow::Result<FactorsInstanceBuilder<'_, T, U>> {
+        let span = tracing::info_span!(
+            "spin_factors_executor.prepare",
+            spin.component_id = component_id,
+            spin.prepare_duration_ms = Empty,
+        );
+        let _entered = span.enter();
+        let start = Instant::now();
+
         let app_component = self
+            .loaded
             .configured_app
             .app()
             .get_component(component_id)
             .with_context(|| format!("no such component {component_id:?}"))?;
 
-        let instance_pre = self.component_instance_pres.get(component_id).unwrap();
+        let instance_pre = self
+            .loaded
+            .component_instance_pres
+            .get(component_id)
+            .unwrap();
+        let limits = self.loaded.component_limits.get(component_id).unwrap();
 
         let factor_builders = self
+            .loaded
             .executor
             .factors
-            .prepare(&self.configured_app, component_id)?;
+            .prepare(&self.loaded.configured_app, component_id)?;
 
-        let store_builder = self.executor.core_engine.store_builder();
+        let mut store_builder = self.loaded.executor.core_engine.store_builder();
+        store_builder.component_id(component_id);
+        if let Some(max_memory_bytes) = limits.effective.max_memory_bytes {
+            store_builder.max_memory_size(max_memory_bytes.try_into().unwrap_or(usize::MAX));
+        }
 
         let mut builder = FactorsInstanceBuilder {
             store_builder,
             factor_builders,
             instance_pre,
             app_component,
-            factors: &self.executor.factors,
+            factors: &self.loaded.executor.factors,
+            timings: &self.loaded.timings,
+            instantiation_permits: self.loaded.executor.instantiation_permits.as_ref(),
+            limits,
+            hooks: &self.loaded.executor.hooks,
         };
 
-        for hooks in &self.executor.hooks {
+        for hooks in &self.loaded.executor.hooks {
             hooks.prepare_instance(&mut builder)?;
         }
 
+        let elapsed = start.elapsed();
+        span.record("spin.prepare_duration_ms", as_millis_f64(elapsed));
+        self.loaded.timings.record_prepare(component_id, elapsed);
         Ok(builder)
     }
 }
@@ -200,6 +686,50 @@ pub struct FactorsInstanceBuilder<'a, F: RuntimeFactors, U: 'static> {
     factor_builders: F::InstanceBuilders,
     instance_pre: &'a InstancePre<F, U>,
     factors: &'a F,
+    timings: &'a Timings,
+    instantiation_permits: Option<&'a Semaphore>,
+    limits: &'a ComponentLimits,
+    hooks: &'a [Box<dyn ExecutorHooks<F, U>>],
+}
+
+/// Information about a component of a loaded app, as returned by
+/// [`FactorsExecutorApp::components_info`].
+#[derive(Clone, Debug)]
+pub struct ComponentInfo {
+    /// The component's ID.
+    pub id: String,
+    /// The component's description, if it has one.
+    pub description: Option<String>,
+    /// The SHA-256 digest of the component's Wasm source, if known; see
+    /// [`AppComponent::source_digest`].
+    pub source_digest: Option<String>,
+    /// Where the component's Wasm source came from, if recorded.
+    pub source_kind: Option<ComponentSourceKind>,
+    /// How the component's Wasm source was built, if by `spin build`.
+    pub build_metadata: Option<BuildMetadata>,
+    /// The names of the compiled component's exports, e.g.
+    /// `wasi:http/incoming-handler@0.2.0`.
+    pub exports: Vec<String>,
+}
+
+/// The resolved limits of a component, with the permits which enforce its
+/// `max_concurrency`.
+struct ComponentLimits {
+    effective: EffectiveLimits,
+    concurrency_permits: Option<Arc<Semaphore>>,
+}
+
+impl ComponentLimits {
+    fn new(effective: EffectiveLimits) -> Self {
+        let concurrency_permits = effective.max_concurrency.map(|n| {
+            let n = usize::try_from(n).unwrap_or(Semaphore::MAX_PERMITS);
+            Arc::new(Semaphore::new(n.clamp(1, Semaphore::MAX_PERMITS)))
+        });
+        Self {
+            effective,
+            concurrency_permits,
+        }
+    }
 }
 
 impl<T: RuntimeFactors, U: 'static> FactorsInstanceBuilder<'_, T, U> {
@@ -208,6 +738,11 @@ impl<T: RuntimeFactors, U: 'static> FactorsInstanceBuilder<'_, T, U> {
         &self.app_component
     }
 
+    /// Returns the limits which apply to the instance.
+    pub fn limits(&self) -> &EffectiveLimits {
+        &self.limits.effective
+    }
+
     /// Returns the store builder for the instance.
     pub fn store_builder(&mut self) -> &mut spin_core::StoreBuilder {
         &mut self.store_builder
@@ -243,14 +778,105 @@ impl<T: RuntimeFactors, U: Send> FactorsInstanceBuilder<'_, T, U> {
         spin_core::Instance,
         spin_core::Store<InstanceState<T::InstanceState, U>>,
     )> {
-        let instance_state = InstanceState {
-            core: Default::default(),
-            factors: self.factors.build_instance_state(self.factor_builders)?,
-            executor: executor_instance_state,
+        let Self {
+            app_component,
+            store_builder,
+            factor_builders,
+            instance_pre,
+            factors,
+            timings,
+            instantiation_permits,
+            limits,
+            hooks: executor_hooks,
+        } = self;
+        let component_id = app_component.id();
+        let span = tracing::info_span!(
+            "spin_factors_executor.instantiate",
+            spin.component_id = component_id,
+            spin.store_build_ms = Empty,
+            spin.instantiate_queue_ms = Empty,
+            spin.instantiate_ms = Empty,
+            spin.concurrency_queue_ms = Empty,
+        );
+        let start = Instant::now();
+
+        let instantiated = async {
+            // The permit is held by the store, so that at most `max_concurrency`
+            // instances of the component are alive at once. Like the
+            // instantiation permits, excess instances wait rather than fail.
+            let concurrency_permit = match &limits.concurrency_permits {
+                Some(permits) => {
+                    let permit = permits
+                        .clone()
+                        .acquire_owned()
+                        .instrument(span.clone())
+                        .await?;
+                    span.record("spin.concurrency_queue_ms", as_millis_f64(start.elapsed()));
+                    Some(permit)
+                }
+                None => None,
+            };
+            let store_start = Instant::now();
+
+            let mut store = span.in_scope(|| {
+                let instance_state = InstanceState {
+                    core: Default::default(),
+                    factors: factors.build_instance_state(factor_builders)?,
+                    executor: executor_instance_state,
+                    _concurrency_permit: concurrency_permit,
+                };
+                store_builder.build(instance_state)
+            })?;
+            if let Some(max_execution_time) = limits.effective.max_execution_time {
+                store.set_deadline(Instant::now() + max_execution_time);
+            }
+            if let Some(max_fuel) = limits.effective.max_fuel {
+                // The engine meters fuel, as checked when the app was loaded
+                store.as_mut().set_fuel(max_fuel)?;
+            }
+            let built = Instant::now();
+            span.record("spin.store_build_ms", as_millis_f64(built - store_start));
+
+            // The semaphore is never closed, so acquiring only waits
+            let _permit = match instantiation_permits {
+                Some(permits) => Some(permits.acquire().instrument(span.clone()).await?),
+                None => None,
+            };
+            let acquired = Instant::now();
+            span.record("spin.instantiate_queue_ms", as_millis_f64(acquired - built));
+
+            let instance = instance_pre
+                .instantiate_async(&mut store)
+                .instrument(span.clone())
+                .await?;
+            span.record("spin.instantiate_ms", as_millis_f64(acquired.elapsed()));
+
+            timings.record_instantiate(component_id, start.elapsed());
+            anyhow::Ok((instance, store))
+        }
+        .await;
+
+        // Hooks observe the outcome without taking ownership of the instance
+        let (result, instantiated) = match instantiated {
+            Ok(instantiated) => (Ok(()), Some(instantiated)),
+            Err(err) => (Err(err), None),
         };
-        let mut store = self.store_builder.build(instance_state)?;
-        let instance = self.instance_pre.instantiate_async(&mut store).await?;
-        Ok((instance, store))
+        for hooks in executor_hooks {
+            let hook_result = hooks
+                .post_instantiate(component_id, &result)
+                .instrument(span.clone())
+                .await;
+            match (&result, hook_result) {
+                (_, Ok(())) => {}
+                (Ok(()), Err(err)) => return Err(err),
+                (Err(_), Err(err)) => {
+                    tracing::warn!(
+                        "post-instantiate hook failed after failed instantiation of {component_id:?}: {err:#}"
+                    );
+                }
+            }
+        }
+        result.map(|()| instantiated.expect("instantiation succeeded"))
     }
 }
 
@@ -262,6 +888,9 @@ pub struct InstanceState<T, U> {
     core: spin_core::State,
     factors: T,
     executor: U,
+    /// Held for the life of the instance if its component has a
+    /// `max_concurrency` limit.
+    _concurrency_permit: Option<OwnedSemaphorePermit>,
 }
 
 impl<T, U> InstanceState<T, U> {
@@ -310,9 +939,22 @@ impl<T: RuntimeFactorsInstanceState, U> AsInstanceState<T> for InstanceState<T,
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    };
+
     use spin_factor_wasi::{DummyFilesMounter, WasiFactor};
-    use spin_factors::RuntimeFactors;
-    use spin_factors_test::TestEnvironment;
+    use spin_factors::{
+        BackgroundTasks, ConfigureAppContext, PrepareContext, RuntimeFactors, TaskPolicy,
+    };
+    use spin_factors_test::{toml, TestEnvironment};
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, Layer};
 
     use super::*;
 
@@ -321,6 +963,126 @@ mod tests {
         wasi: WasiFactor,
     }
 
+    #[derive(RuntimeFactors)]
+    struct SleepyFactors {
+        wasi: WasiFactor,
+        sleepy: SleepyFactor,
+    }
+
+    /// A factor which sleeps in prepare.
+    struct SleepyFactor(Duration);
+
+    impl Factor for SleepyFactor {
+        type RuntimeConfig = ();
+        type AppState = ();
+        type InstanceBuilder = ();
+
+        fn configure_app<T: RuntimeFactors>(
+            &self,
+            _ctx: ConfigureAppContext<T, Self>,
+        ) -> anyhow::Result<Self::AppState> {
+            Ok(())
+        }
+
+        fn prepare<T: RuntimeFactors>(
+            &self,
+            _ctx: PrepareContext<T, Self>,
+        ) -> anyhow::Result<Self::InstanceBuilder> {
+            std::thread::sleep(self.0);
+            Ok(())
+        }
+    }
+
+    #[derive(RuntimeFactors)]
+    struct TaskFactors {
+        wasi: WasiFactor,
+        tasks: TasksFactor,
+    }
+
+    /// A factor which registers background tasks in configure_app.
+    struct TasksFactor(Box<dyn Fn(&BackgroundTasks) + Send + Sync>);
+
+    impl Factor for TasksFactor {
+        type RuntimeConfig = ();
+        type AppState = ();
+        type InstanceBuilder = ();
+
+        fn configure_app<T: RuntimeFactors>(
+            &self,
+            ctx: ConfigureAppContext<T, Self>,
+        ) -> anyhow::Result<Self::AppState> {
+            (self.0)(ctx.background_tasks());
+            Ok(())
+        }
+
+        fn prepare<T: RuntimeFactors>(
+            &self,
+            _ctx: PrepareContext<T, Self>,
+        ) -> anyhow::Result<Self::InstanceBuilder> {
+            Ok(())
+        }
+    }
+
+    async fn load_app_with_tasks(
+        register: impl Fn(&BackgroundTasks) + Send + Sync + 'static,
+    ) -> anyhow::Result<FactorsExecutorApp<TaskFactors, ()>> {
+        let factors = TaskFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+            tasks: TasksFactor(Box::new(register)),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+        executor
+            .load_app(app, Default::default(), &DummyComponentLoader)
+            .await
+    }
+
+    /// Polls `f` until it returns true, failing after a few seconds.
+    async fn eventually(mut f: impl FnMut() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !f() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("condition not met in time")
+    }
+
+    /// A tracing layer which captures `f64` span fields.
+    #[derive(Clone, Default)]
+    struct CapturedFields(Arc<Mutex<HashMap<String, f64>>>);
+
+    impl CapturedFields {
+        fn get(&self, name: &str) -> Option<f64> {
+            self.0.lock().unwrap().get(name).copied()
+        }
+    }
+
+    impl Visit for CapturedFields {
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_string(), value);
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: Subscriber> Layer<S> for CapturedFields {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            attrs.record(&mut self.clone());
+        }
+
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
     #[tokio::test]
     async fn instance_builder_works() -> anyhow::Result<()> {
         let factors = TestFactors {
@@ -352,10 +1114,687 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn prepare_and_instantiate_are_timed() -> anyhow::Result<()> {
+        let captured = CapturedFields::default();
+        let _guard = tracing_subscriber::registry()
+            .with(captured.clone())
+            .set_default();
+
+        let sleep = Duration::from_millis(20);
+        let factors = SleepyFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+            sleepy: SleepyFactor(sleep),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+        let factors_app = executor
+            .load_app(app, Default::default(), &DummyComponentLoader)
+            .await?;
+
+        let start = Instant::now();
+        let (_instance, _store) = factors_app.prepare("empty")?.instantiate(()).await?;
+        let total_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        let sleepy_ms = captured.get("spin.factor.sleepy.prepare_ms").unwrap();
+        let wasi_ms = captured.get("spin.factor.wasi.prepare_ms").unwrap();
+        let prepare_ms = captured.get("spin.prepare_duration_ms").unwrap();
+        assert!(sleepy_ms >= sleep.as_secs_f64() * 1000.0, "{sleepy_ms}");
+        assert!(wasi_ms < sleepy_ms, "{wasi_ms} >= {sleepy_ms}");
+        assert!(prepare_ms >= sleepy_ms, "{prepare_ms} < {sleepy_ms}");
+        assert!(prepare_ms <= total_ms, "{prepare_ms} > {total_ms}");
+        assert!(captured.get("spin.store_build_ms").is_some());
+        assert!(captured.get("spin.instantiate_ms").is_some());
+
+        let stats = factors_app.timing_stats("empty").unwrap();
+        assert_eq!(stats.prepare.samples, 1);
+        assert_eq!(stats.instantiate.samples, 1);
+        assert!(stats.prepare.p50 >= sleep);
+        assert!(factors_app.timing_stats("unknown").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn instantiations_beyond_max_parallelism_wait() -> anyhow::Result<()> {
+        let captured = CapturedFields::default();
+        let _guard = tracing_subscriber::registry()
+            .with(captured.clone())
+            .set_default();
+
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor =
+            Arc::new(FactorsExecutor::new(engine_builder, env.factors)?.with_max_parallelism(1));
+        let factors_app = executor
+            .load_app(app, Default::default(), &DummyComponentLoader)
+            .await?;
+
+        let (first, second) = tokio::join!(
+            factors_app.prepare("empty")?.instantiate(()),
+            factors_app.prepare("empty")?.instantiate(()),
+        );
+        first?;
+        second?;
+        assert!(captured.get("spin.instantiate_queue_ms").is_some());
+        assert_eq!(
+            factors_app
+                .timing_stats("empty")
+                .unwrap()
+                .instantiate
+                .samples,
+            2
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resolved_limits_are_applied() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors).extend_manifest(toml! {
+            [application]
+            name = "test-app"
+            limits = { max_memory_bytes = 1048576, max_execution_time_ms = 5000 }
+
+            [component.empty]
+            source = "does-not-exist.wasm"
+            limits = { max_memory_bytes = 8388608, max_concurrency = 1 }
+        });
+        let locked = env.build_locked_app().await?;
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let mut executor = FactorsExecutor::new(engine_builder, env.factors)?;
+        executor.set_limits(LimitsResolver::new(
+            Default::default(),
+            spin_app::limits::Limits {
+                max_memory_bytes: Some(4194304),
+                ..Default::default()
+            },
+        ));
+        let factors_app = Arc::new(executor)
+            .load_app(
+                App::new("test-app", locked),
+                Default::default(),
+                &DummyComponentLoader,
+            )
+            .await?;
+
+        let limits = factors_app.limits("empty")?;
+        assert_eq!(limits.max_memory_bytes, Some(4194304));
+        assert_eq!(limits.max_execution_time, Some(Duration::from_secs(5)));
+        assert_eq!(limits.max_concurrency, Some(1));
+        assert_eq!(limits.clamped, ["max_memory_bytes"]);
+        assert_eq!(
+            factors_app.prepare("empty")?.limits().max_memory_bytes,
+            Some(4194304)
+        );
+
+        // A second instance waits until the first is dropped
+        let (_, first) = factors_app.prepare("empty")?.instantiate(()).await?;
+        let second = factors_app.prepare("empty")?.instantiate(());
+        tokio::pin!(second);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut second)
+                .await
+                .is_err(),
+            "second instance should wait for the first"
+        );
+        drop(first);
+        second.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fuel_limits_require_fuel_metering() -> anyhow::Result<()> {
+        let load = |config: spin_core::Config| async move {
+            let factors = TestFactors {
+                wasi: WasiFactor::new(DummyFilesMounter),
+            };
+            let env = TestEnvironment::new(factors).extend_manifest(toml! {
+                [component.empty]
+                source = "does-not-exist.wasm"
+                limits = { max_fuel = 1000000 }
+            });
+            let locked = env.build_locked_app().await?;
+            let engine_builder = spin_core::Engine::builder(&config)?;
+            Arc::new(FactorsExecutor::new(engine_builder, env.factors)?)
+                .load_app(
+                    App::new("test-app", locked),
+                    Default::default(),
+                    &DummyComponentLoader,
+                )
+                .await
+        };
+
+        let Err(err) = load(Default::default()).await else {
+            panic!("a fuel limit was accepted without fuel metering");
+        };
+        assert!(err.to_string().contains("max_fuel"), "{err}");
+
+        let mut config = spin_core::Config::default();
+        config.consume_fuel(true);
+        let factors_app = load(config).await?;
+        let (_, store) = factors_app.prepare("empty")?.instantiate(()).await?;
+        let fuel = store.as_ref().get_fuel()?;
+        assert!(fuel > 0 && fuel <= 1000000, "{fuel}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ticking_task_stops_when_app_is_dropped() -> anyhow::Result<()> {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let task_ticks = ticks.clone();
+        let factors_app = load_app_with_tasks(move |tasks| {
+            let ticks = task_ticks.clone();
+            tasks.register("ticker", TaskPolicy::FailApp, move |_| {
+                let ticks = ticks.clone();
+                async move {
+                    loop {
+                        ticks.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                }
+            });
+        })
+        .await?;
+
+        eventually(|| ticks.load(Ordering::SeqCst) > 1).await;
+        let statuses = factors_app.background_task_statuses();
+        assert_eq!(statuses[0].name, "ticker");
+        assert_eq!(statuses[0].state, BackgroundTaskState::Running);
+
+        drop(factors_app);
+        // Give the runtime a chance to process the cancellation
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let stopped_at = ticks.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), stopped_at);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn panicking_tasks_respect_policy() -> anyhow::Result<()> {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task_runs = runs.clone();
+        let factors_app = load_app_with_tasks(move |tasks| {
+            let runs = task_runs.clone();
+            tasks.register(
+                "flaky",
+                TaskPolicy::RestartWithBackoff,
+                move |mut shutdown| {
+                    let run = runs.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        if run < 2 {
+                            panic!("flaky run {run}");
+                        }
+                        shutdown.requested().await;
+                        Ok(())
+                    }
+                },
+            );
+            tasks.register("fatal", TaskPolicy::FailApp, |_| async { panic!("boom") });
+        })
+        .await?;
+
+        let failure = tokio::time::timeout(
+            Duration::from_secs(5),
+            factors_app.background_task_failure(),
+        )
+        .await?;
+        assert!(failure.to_string().contains("\"fatal\""), "{failure}");
+        assert!(failure.to_string().contains("panicked: boom"), "{failure}");
+
+        eventually(|| {
+            let flaky = &factors_app.background_task_statuses()[0];
+            flaky.restarts == 2 && flaky.state == BackgroundTaskState::Running
+        })
+        .await;
+        let statuses = factors_app.background_task_statuses();
+        assert_eq!(
+            statuses[0].last_error.as_deref(),
+            Some("panicked: flaky run 1")
+        );
+        assert_eq!(statuses[1].state, BackgroundTaskState::Failed);
+        assert_eq!(statuses[1].restarts, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shutdown_awaits_task_completion() -> anyhow::Result<()> {
+        let cleaned_up = Arc::new(AtomicBool::new(false));
+        let task_cleaned_up = cleaned_up.clone();
+        let factors_app = load_app_with_tasks(move |tasks| {
+            let cleaned_up = task_cleaned_up.clone();
+            tasks.register("graceful", TaskPolicy::FailApp, move |mut shutdown| {
+                let cleaned_up = cleaned_up.clone();
+                async move {
+                    shutdown.requested().await;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    cleaned_up.store(true, Ordering::SeqCst);
+                    Ok(())
+                }
+            });
+        })
+        .await?;
+
+        factors_app.shutdown(Duration::from_secs(5)).await;
+        assert!(cleaned_up.load(Ordering::SeqCst));
+        assert_eq!(
+            factors_app.background_task_statuses()[0].state,
+            BackgroundTaskState::Stopped
+        );
+        Ok(())
+    }
+
+    /// Hooks which record their name when preparing an instance.
+    struct NamedHooks(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+    impl<T: RuntimeFactors> ExecutorHooks<T, ()> for NamedHooks {
+        fn prepare_instance(
+            &self,
+            _builder: &mut FactorsInstanceBuilder<T, ()>,
+        ) -> anyhow::Result<()> {
+            self.1.lock().unwrap().push(self.0);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn ordered_hooks_are_inserted_at_position() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let mut executor = FactorsExecutor::new(engine_builder, env.factors)?;
+        let order = Arc::new(Mutex::new(vec![]));
+        executor.add_hooks(NamedHooks("tracing", order.clone()));
+        executor.add_hooks_ordered(NamedHooks("auth", order.clone()), 0);
+        executor.add_hooks_ordered(NamedHooks("last", order.clone()), 2);
+
+        let factors_app = Arc::new(executor)
+            .load_app(app, Default::default(), &DummyComponentLoader)
+            .await?;
+        factors_app.prepare("empty")?;
+        assert_eq!(*order.lock().unwrap(), ["auth", "tracing", "last"]);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic = "out of bounds"]
+    fn ordered_hooks_position_must_be_in_bounds() {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let engine_builder = spin_core::Engine::builder(&Default::default()).unwrap();
+        let mut executor = FactorsExecutor::new(engine_builder, factors).unwrap();
+        executor.add_hooks_ordered(NamedHooks("auth", Default::default()), 1);
+    }
+
+    /// Hooks which record the outcome of each instantiation.
+    struct OutcomeHooks {
+        outcomes: Arc<Mutex<Vec<(String, bool)>>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl<T: RuntimeFactors> ExecutorHooks<T, ()> for OutcomeHooks {
+        async fn post_instantiate(
+            &self,
+            component_id: &str,
+            result: &anyhow::Result<()>,
+        ) -> anyhow::Result<()> {
+            self.outcomes
+                .lock()
+                .unwrap()
+                .push((component_id.to_owned(), result.is_ok()));
+            anyhow::ensure!(!self.fail, "hook failed");
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn post_instantiate_hooks_observe_outcome() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let mut executor = FactorsExecutor::new(engine_builder, env.factors)?;
+        let outcomes = Arc::new(Mutex::new(vec![]));
+        executor.add_hooks(OutcomeHooks {
+            outcomes: outcomes.clone(),
+            fail: false,
+        });
+        let executor = Arc::new(executor);
+
+        let factors_app = executor
+            .clone()
+            .load_app(
+                App::new("test-app", locked.clone()),
+                Default::default(),
+                &DummyComponentLoader,
+            )
+            .await?;
+        factors_app.prepare("empty")?.instantiate(()).await?;
+
+        let factors_app = executor
+            .load_app(
+                App::new("test-app", locked),
+                Default::default(),
+                &TrappingComponentLoader,
+            )
+            .await?;
+        let err = factors_app
+            .prepare("empty")?
+            .instantiate(())
+            .await
+            .map(|_| ())
+            .expect_err("trapping component should fail to instantiate");
+        assert!(!err.to_string().contains("hook failed"), "{err:#}");
+
+        assert_eq!(
+            *outcomes.lock().unwrap(),
+            [("empty".to_owned(), true), ("empty".to_owned(), false)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn post_instantiate_hook_error_fails_only_successful_instantiation() -> anyhow::Result<()>
+    {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let mut executor = FactorsExecutor::new(engine_builder, env.factors)?;
+        executor.add_hooks(OutcomeHooks {
+            outcomes: Default::default(),
+            fail: true,
+        });
+        let executor = Arc::new(executor);
+
+        let factors_app = executor
+            .clone()
+            .load_app(
+                App::new("test-app", locked.clone()),
+                Default::default(),
+                &DummyComponentLoader,
+            )
+            .await?;
+        let err = factors_app
+            .prepare("empty")?
+            .instantiate(())
+            .await
+            .map(|_| ())
+            .expect_err("hook error should fail instantiation");
+        assert_eq!(err.to_string(), "hook failed");
+
+        // The original failure is reported rather than the hook's
+        let factors_app = executor
+            .load_app(
+                App::new("test-app", locked),
+                Default::default(),
+                &TrappingComponentLoader,
+            )
+            .await?;
+        let err = factors_app
+            .prepare("empty")?
+            .instantiate(())
+            .await
+            .map(|_| ())
+            .expect_err("trapping component should fail to instantiate");
+        assert_ne!(err.to_string(), "hook failed");
+        Ok(())
+    }
+
+    async fn load_test_app(
+        loader: &impl ComponentLoader<TestFactors, ()>,
+    ) -> anyhow::Result<FactorsExecutorApp<TestFactors, ()>> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        load_env_app(TestEnvironment::new(factors), loader).await
+    }
+
+    async fn load_env_app(
+        env: TestEnvironment<TestFactors>,
+        loader: &impl ComponentLoader<TestFactors, ()>,
+    ) -> anyhow::Result<FactorsExecutorApp<TestFactors, ()>> {
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+        executor.load_app(app, Default::default(), loader).await
+    }
+
+    #[tokio::test]
+    async fn component_hashes_identify_binaries() -> anyhow::Result<()> {
+        let empty = load_test_app(&DummyComponentLoader).await?;
+        let trapping = load_test_app(&TrappingComponentLoader).await?;
+        assert_ne!(
+            empty.get_component_hash("empty")?,
+            trapping.get_component_hash("empty")?
+        );
+
+        let empty_again = load_test_app(&DummyComponentLoader).await?;
+        assert_eq!(
+            empty.get_component_hash("empty")?,
+            empty_again.get_component_hash("empty")?
+        );
+        assert!(empty.get_component_hash("missing").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn component_hashes_are_source_digests_when_known() -> anyhow::Result<()> {
+        let digest = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+        let env = TestEnvironment::new(TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        });
+        let mut locked = env.build_locked_app().await?;
+        locked.components[0].source.content.digest = Some(format!("sha256:{digest}"));
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+        let app = executor
+            .load_app(
+                App::new("test-app", locked),
+                Default::default(),
+                &DummyComponentLoader,
+            )
+            .await?;
+
+        let hash = app.get_component_hash("empty")?;
+        let hex = hash.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(hex, digest);
+        assert!(parse_sha256_digest("sha256:abc").is_err());
+        assert!(parse_sha256_digest(&format!("md5:{digest}")).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_components_is_sorted() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors).extend_manifest(toml! {
+            [component.zebra]
+            source = "does-not-exist.wasm"
+
+            [component.empty]
+            source = "does-not-exist.wasm"
+
+            [component.alpha]
+            source = "does-not-exist.wasm"
+        });
+        let app = load_env_app(env, &DummyComponentLoader).await?;
+        assert_eq!(app.list_components(), ["alpha", "empty", "zebra"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn components_info_describes_components() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors).extend_manifest(toml! {
+            [component.empty]
+            source = "does-not-exist.wasm"
+            description = "Does nothing"
+        });
+        let app = load_env_app(env, &ExportingComponentLoader).await?;
+
+        let infos = app.components_info().collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(infos.len(), 1);
+        let info = &infos[0];
+        assert_eq!(info.id, "empty");
+        assert_eq!(info.description.as_deref(), Some("Does nothing"));
+        assert_eq!(
+            info.source_kind,
+            Some(ComponentSourceKind::Local {
+                path: "does-not-exist.wasm".into()
+            })
+        );
+        assert_eq!(info.source_digest, None);
+        assert_eq!(info.build_metadata, None);
+        assert_eq!(info.exports, ["run"]);
+        Ok(())
+    }
+
+    fn chaining_executor(
+        policy: ChainingPolicy,
+    ) -> anyhow::Result<Arc<FactorsExecutor<TestFactors, ()>>> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let mut executor = FactorsExecutor::new(engine_builder, factors)?;
+        executor.set_chaining_policy(policy);
+        Ok(Arc::new(executor))
+    }
+
+    /// Loads an app with the given name and an `empty` component.
+    async fn load_named_app(
+        executor: &Arc<FactorsExecutor<TestFactors, ()>>,
+        name: &str,
+    ) -> anyhow::Result<FactorsExecutorApp<TestFactors, ()>> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let mut locked = TestEnvironment::new(factors).build_locked_app().await?;
+        locked.metadata.insert("name".into(), name.into());
+        let app = App::new(name, locked);
+        executor
+            .clone()
+            .load_app(app, Default::default(), &DummyComponentLoader)
+            .await
+    }
+
+    #[tokio::test]
+    async fn cross_app_chaining_follows_policy() -> anyhow::Result<()> {
+        let executor =
+            chaining_executor(ChainingPolicy::default().allow_app("frontend", "backend"))?;
+        let _frontend = load_named_app(&executor, "frontend").await?;
+        let _backend = load_named_app(&executor, "backend").await?;
+        assert_eq!(executor.list_apps(), ["backend", "frontend"]);
+        assert!(load_named_app(&executor, "backend").await.is_err());
+
+        let instantiated = executor
+            .dispatch_chained("frontend", "backend", "empty", |app| async move {
+                app.prepare("empty")?.instantiate(()).await?;
+                anyhow::Ok(app.name().to_owned())
+            })
+            .await?;
+        assert_eq!(instantiated?, "backend");
+
+        let err = executor
+            .dispatch_chained("backend", "frontend", "empty", |_| async {})
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                ChainingError::Denied { source_app, target_app, component_id }
+                    if source_app == "backend" && target_app == "frontend" && component_id == "empty"
+            ),
+            "{err}"
+        );
+        let err = executor
+            .dispatch_chained("frontend", "backend", "missing", |_| async {})
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ChainingError::NoSuchComponent { .. }),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unloading_target_app_fails_chained_requests() -> anyhow::Result<()> {
+        let executor =
+            chaining_executor(ChainingPolicy::default().allow_app("frontend", "backend"))?;
+        let _frontend = load_named_app(&executor, "frontend").await?;
+        let backend = load_named_app(&executor, "backend").await?;
+
+        // A chained request which is still in flight when the app is unloaded
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let dispatch = executor.dispatch_chained("frontend", "backend", "empty", |_| async {
+            started_tx.send(()).unwrap();
+            std::future::pending::<()>().await
+        });
+        let unload = async {
+            started_rx.await.unwrap();
+            assert!(executor.unload_app("backend"));
+        };
+        let (result, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(dispatch, unload)
+        })
+        .await?;
+        let err = result.unwrap_err();
+        assert!(
+            matches!(&err, ChainingError::Unloaded(app) if app == "backend"),
+            "{err}"
+        );
+
+        let err = executor
+            .dispatch_chained("frontend", "backend", "empty", |_| async {})
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ChainingError::NoSuchApp(_)), "{err}");
+
+        // The name can be reused, and dropping the unloaded app doesn't
+        // unregister the new one
+        let _reloaded = load_named_app(&executor, "backend").await?;
+        drop(backend);
+        assert!(executor.app("backend").is_some());
+        Ok(())
+    }
+
     struct DummyComponentLoader;
 
     #[async_trait]
-    impl ComponentLoader<TestFactors, ()> for DummyComponentLoader {
+    impl<T: RuntimeFactors> ComponentLoader<T, ()> for DummyComponentLoader {
         async fn load_component(
             &self,
             engine: &spin_core::wasmtime::Engine,
@@ -364,4 +1803,45 @@ mod tests {
             Component::new(engine, "(component)")
         }
     }
+
+    /// Loads a component whose start function traps, so instantiation fails.
+    struct TrappingComponentLoader;
+
+    #[async_trait]
+    impl<T: RuntimeFactors> ComponentLoader<T, ()> for TrappingComponentLoader {
+        async fn load_component(
+            &self,
+            engine: &spin_core::wasmtime::Engine,
+            _component: &AppComponent,
+        ) -> anyhow::Result<Component> {
+            Component::new(
+                engine,
+                r#"(component
+                    (core module $m (func $start unreachable) (start $start))
+                    (core instance (instantiate $m))
+                )"#,
+            )
+        }
+    }
+
+    /// Loads a component which exports a function named `run`.
+    struct ExportingComponentLoader;
+
+    #[async_trait]
+    impl<T: RuntimeFactors> ComponentLoader<T, ()> for ExportingComponentLoader {
+        async fn load_component(
+            &self,
+            engine: &spin_core::wasmtime::Engine,
+            _component: &AppComponent,
+        ) -> anyhow::Result<Component> {
+            Component::new(
+                engine,
+                r#"(component
+                    (core module $m (func (export "run")))
+                    (core instance $i (instantiate $m))
+                    (func (export "run") (canon lift (core func $i "run")))
+                )"#,
+            )
+        }
+    }
 }
@@ -0,0 +1,261 @@
+use std::{
+    any::Any,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use spin_factors::{BackgroundTask, ShutdownSignal, TaskPolicy};
+use tokio::{
+    sync::watch,
+    task::{JoinError, JoinHandle, JoinSet},
+};
+
+/// The delay before the first restart of a failed task.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// The longest delay between restarts of a repeatedly failing task. A task
+/// which runs for at least this long before failing is restarted after
+/// [`INITIAL_BACKOFF`] again.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The state of a background task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BackgroundTaskState {
+    /// The task is running.
+    Running,
+    /// The task failed and is waiting to be restarted.
+    Restarting,
+    /// The task returned successfully before shutdown was requested.
+    Completed,
+    /// The task failed under [`TaskPolicy::FailApp`].
+    Failed,
+    /// The task was stopped by shutdown.
+    Stopped,
+}
+
+/// The status of a background task.
+#[derive(Clone, Debug)]
+pub struct BackgroundTaskStatus {
+    /// The name the task was registered with.
+    pub name: String,
+    /// What the task is currently doing.
+    pub state: BackgroundTaskState,
+    /// The number of times the task has been restarted.
+    pub restarts: u32,
+    /// The most recent error or panic message from the task, if any.
+    pub last_error: Option<String>,
+}
+
+/// Runs an app's background tasks, applying their [`TaskPolicy`] when they
+/// fail.
+///
+/// Dropping the supervisor cancels all of its tasks.
+pub(crate) struct Supervisor {
+    statuses: Arc<Mutex<Vec<BackgroundTaskStatus>>>,
+    shutdown: watch::Sender<bool>,
+    failure: Arc<watch::Sender<Option<String>>>,
+    // Dropping the `JoinSet` aborts the supervising tasks, which in turn abort
+    // the tasks they are running.
+    supervising: Mutex<Option<JoinSet<()>>>,
+}
+
+impl Supervisor {
+    /// Starts running the given tasks. Must be called within a Tokio runtime.
+    pub fn start(tasks: Vec<BackgroundTask>) -> Self {
+        let statuses = Arc::new(Mutex::new(
+            tasks
+                .iter()
+                .map(|task| BackgroundTaskStatus {
+                    name: task.name.clone(),
+                    state: BackgroundTaskState::Running,
+                    restarts: 0,
+                    last_error: None,
+                })
+                .collect(),
+        ));
+        let (shutdown, _) = watch::channel(false);
+        let failure = Arc::new(watch::channel(None).0);
+        let mut supervising = JoinSet::new();
+        for (index, task) in tasks.into_iter().enumerate() {
+            supervising.spawn(supervise(
+                task,
+                TaskStatus {
+                    statuses: statuses.clone(),
+                    index,
+                },
+                shutdown.subscribe(),
+                failure.clone(),
+            ));
+        }
+        Self {
+            statuses,
+            shutdown,
+            failure,
+            supervising: Mutex::new(Some(supervising)),
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<BackgroundTaskStatus> {
+        self.statuses.lock().unwrap().clone()
+    }
+
+    /// Returns a future which resolves if a task fails under
+    /// [`TaskPolicy::FailApp`], and otherwise never resolves.
+    pub fn failure(&self) -> impl Future<Output = anyhow::Error> + Send + 'static {
+        let mut failure = self.failure.subscribe();
+        async move {
+            let message = failure
+                .wait_for(Option::is_some)
+                .await
+                .map(|message| message.clone().unwrap_or_default());
+            match message {
+                Ok(message) => anyhow::anyhow!(message),
+                // The supervisor was dropped without any task failing
+                Err(_) => std::future::pending().await,
+            }
+        }
+    }
+
+    /// Requests shutdown and waits for the tasks to return, cancelling any
+    /// which are still running after `timeout`.
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.shutdown.send_replace(true);
+        let Some(mut supervising) = self.supervising.lock().unwrap().take() else {
+            return;
+        };
+        let drained = tokio::time::timeout(timeout, async {
+            while supervising.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            tracing::warn!(
+                "Background tasks did not stop within {timeout:?} of shutdown; cancelling them"
+            );
+            supervising.shutdown().await;
+        }
+        for status in self.statuses.lock().unwrap().iter_mut() {
+            if matches!(
+                status.state,
+                BackgroundTaskState::Running | BackgroundTaskState::Restarting
+            ) {
+                status.state = BackgroundTaskState::Stopped;
+            }
+        }
+    }
+}
+
+/// A supervised task's entry in the shared status list.
+struct TaskStatus {
+    statuses: Arc<Mutex<Vec<BackgroundTaskStatus>>>,
+    index: usize,
+}
+
+impl TaskStatus {
+    fn update(&self, f: impl FnOnce(&mut BackgroundTaskStatus)) {
+        f(&mut self.statuses.lock().unwrap()[self.index])
+    }
+}
+
+async fn supervise(
+    task: BackgroundTask,
+    status: TaskStatus,
+    shutdown: watch::Receiver<bool>,
+    failure: Arc<watch::Sender<Option<String>>>,
+) {
+    let name = &task.name;
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        status.update(|status| status.state = BackgroundTaskState::Running);
+        let started = Instant::now();
+        // Runs on its own task so that panics are caught
+        let result = AbortOnDrop(tokio::spawn(
+            task.run(ShutdownSignal::new(shutdown.clone())),
+        ))
+        .await;
+
+        let shutting_down = ShutdownSignal::new(shutdown.clone()).is_requested();
+        let error = match result {
+            Ok(Ok(())) => {
+                tracing::debug!("Background task {name:?} finished");
+                status.update(|status| {
+                    status.state = if shutting_down {
+                        BackgroundTaskState::Stopped
+                    } else {
+                        BackgroundTaskState::Completed
+                    }
+                });
+                return;
+            }
+            Ok(Err(err)) => format!("{err:#}"),
+            Err(err) => panic_message(err),
+        };
+        status.update(|status| status.last_error = Some(error.clone()));
+
+        if shutting_down {
+            tracing::warn!("Background task {name:?} failed during shutdown: {error}");
+            status.update(|status| status.state = BackgroundTaskState::Stopped);
+            return;
+        }
+
+        match task.policy {
+            TaskPolicy::FailApp => {
+                tracing::error!("Background task {name:?} failed: {error}");
+                status.update(|status| status.state = BackgroundTaskState::Failed);
+                failure.send_replace(Some(format!("background task {name:?} failed: {error}")));
+                return;
+            }
+            TaskPolicy::RestartWithBackoff => {
+                if started.elapsed() >= MAX_BACKOFF {
+                    backoff = INITIAL_BACKOFF;
+                }
+                tracing::warn!(
+                    "Background task {name:?} failed: {error}; restarting in {backoff:?}"
+                );
+                status.update(|status| status.state = BackgroundTaskState::Restarting);
+                let mut signal = ShutdownSignal::new(shutdown.clone());
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = signal.requested() => {
+                        status.update(|status| status.state = BackgroundTaskState::Stopped);
+                        return;
+                    }
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                status.update(|status| status.restarts += 1);
+            }
+        }
+    }
+}
+
+/// Describes why a task's Tokio task didn't complete.
+fn panic_message(err: JoinError) -> String {
+    if !err.is_panic() {
+        return "task was cancelled".into();
+    }
+    let payload: Box<dyn Any + Send> = err.into_panic();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic payload");
+    format!("panicked: {message}")
+}
+
+/// A [`JoinHandle`] which aborts its task when dropped.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// The number of most recent samples kept per component and phase.
+const WINDOW_SIZE: usize = 256;
+
+/// Rolling prepare and instantiate timings for each component of an app.
+///
+/// Samples are recorded with atomics rather than under a lock, as they are
+/// recorded for every instance.
+pub(crate) struct Timings {
+    components: HashMap<String, ComponentSamples>,
+}
+
+#[derive(Default)]
+struct ComponentSamples {
+    prepare: Samples,
+    instantiate: Samples,
+}
+
+/// A ring buffer of the most recent [`WINDOW_SIZE`] durations, in nanoseconds.
+struct Samples {
+    nanos: [AtomicU64; WINDOW_SIZE],
+    /// The number of samples ever recorded.
+    count: AtomicUsize,
+}
+
+impl Default for Samples {
+    fn default() -> Self {
+        Self {
+            nanos: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Timings {
+    pub fn new<'a>(component_ids: impl IntoIterator<Item = &'a str>) -> Self {
+        let components = component_ids
+            .into_iter()
+            .map(|id| (id.to_string(), Default::default()))
+            .collect();
+        Self { components }
+    }
+
+    pub fn record_prepare(&self, component_id: &str, duration: Duration) {
+        if let Some(samples) = self.components.get(component_id) {
+            samples.prepare.push(duration);
+        }
+    }
+
+    pub fn record_instantiate(&self, component_id: &str, duration: Duration) {
+        if let Some(samples) = self.components.get(component_id) {
+            samples.instantiate.push(duration);
+        }
+    }
+
+    pub fn stats(&self, component_id: &str) -> Option<ComponentTimingStats> {
+        let samples = self.components.get(component_id)?;
+        Some(ComponentTimingStats {
+            prepare: samples.prepare.stats(),
+            instantiate: samples.instantiate.stats(),
+        })
+    }
+}
+
+impl Samples {
+    fn push(&self, duration: Duration) {
+        let index = self.count.fetch_add(1, Ordering::Relaxed) % WINDOW_SIZE;
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.nanos[index].store(nanos, Ordering::Relaxed);
+    }
+
+    /// Computes statistics from the samples in the window. Samples recorded
+    /// concurrently may or may not be included.
+    fn stats(&self) -> PhaseTimingStats {
+        let len = self.count.load(Ordering::Relaxed).min(WINDOW_SIZE);
+        let mut sorted: Vec<_> = self.nanos[..len]
+            .iter()
+            .map(|nanos| Duration::from_nanos(nanos.load(Ordering::Relaxed)))
+            .collect();
+        sorted.sort_unstable();
+        PhaseTimingStats {
+            samples: sorted.len(),
+            p50: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+        }
+    }
+}
+
+/// Returns the nearest-rank percentile of the given sorted samples.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Rolling timing statistics for a component, as returned by
+/// [`FactorsExecutorApp::timing_stats`](crate::FactorsExecutorApp::timing_stats).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ComponentTimingStats {
+    /// Timings of [`FactorsExecutorApp::prepare`](crate::FactorsExecutorApp::prepare).
+    pub prepare: PhaseTimingStats,
+    /// Timings of [`FactorsInstanceBuilder::instantiate`](crate::FactorsInstanceBuilder::instantiate).
+    pub instantiate: PhaseTimingStats,
+}
+
+/// Rolling timing statistics for one phase of instantiation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhaseTimingStats {
+    /// The number of samples the statistics are computed from.
+    pub samples: usize,
+    /// The median duration.
+    pub p50: Duration,
+    /// The 95th percentile duration.
+    pub p95: Duration,
+}
+
+/// Converts a duration to fractional milliseconds for recording on a span.
+pub(crate) fn as_millis_f64(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let ms = |n| Duration::from_millis(n);
+        let sorted: Vec<_> = (1..=20).map(ms).collect();
+        assert_eq!(percentile(&sorted, 50), ms(10));
+        assert_eq!(percentile(&sorted, 95), ms(19));
+        assert_eq!(percentile(&sorted[..1], 95), ms(1));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn samples_are_windowed() {
+        let timings = Timings::new(["a"]);
+        for n in 0..(WINDOW_SIZE as u64 + 10) {
+            timings.record_prepare("a", Duration::from_millis(n));
+        }
+        timings.record_prepare("unknown", Duration::ZERO);
+        let stats = timings.stats("a").unwrap();
+        assert_eq!(stats.prepare.samples, WINDOW_SIZE);
+        // The oldest samples have been overwritten
+        assert_eq!(stats.prepare.p50, Duration::from_millis(10 + 127));
+        assert_eq!(stats.instantiate, PhaseTimingStats::default());
+        assert!(timings.stats("unknown").is_none());
+    }
+}
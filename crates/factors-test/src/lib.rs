@@ -2,7 +2,7 @@ use spin_app::locked::LockedApp;
 use spin_factors::{
     anyhow::{self, Context},
     wasmtime::{component::Linker, Config, Engine},
-    App, RuntimeFactors,
+    App, ConfiguredApp, RuntimeFactors,
 };
 use spin_loader::FilesMountStrategy;
 
@@ -76,20 +76,27 @@ impl<T: RuntimeFactors> TestEnvironment<T> {
     /// [`RuntimeFactors::InstanceState`] for the last component defined in the
     /// manifest.
     pub async fn build_instance_state(self) -> anyhow::Result<T::InstanceState> {
-        let locked_app = self
-            .build_locked_app()
-            .await
-            .context("failed to build locked app")?;
-        let app = App::new("test-app", locked_app);
-        let configured_app = self.factors.configure_app(app, self.runtime_config)?;
+        let (factors, configured_app) = self.build_configured_app().await?;
 
         let component =
             configured_app.app().components().last().context(
                 "expected configured app to have at least one component, but it did not",
             )?;
-        let builders = self.factors.prepare(&configured_app, component.id())?;
+        let builders = factors.prepare(&configured_app, component.id())?;
+
+        Ok(factors.build_instance_state(builders)?)
+    }
 
-        Ok(self.factors.build_instance_state(builders)?)
+    /// Run through the [`Factor`]s' `configure_app` lifecycle step, returning
+    /// the factors along with the [`ConfiguredApp`].
+    pub async fn build_configured_app(self) -> anyhow::Result<(T, ConfiguredApp<T>)> {
+        let locked_app = self
+            .build_locked_app()
+            .await
+            .context("failed to build locked app")?;
+        let app = App::new("test-app", locked_app);
+        let configured_app = self.factors.configure_app(app, self.runtime_config)?;
+        Ok((self.factors, configured_app))
     }
 
     pub async fn build_locked_app(&self) -> anyhow::Result<LockedApp> {
@@ -4,7 +4,8 @@ use std::marker::PhantomData;
 use wasmtime::component::{HasData, Linker, ResourceTable};
 
 use crate::{
-    prepare::FactorInstanceBuilder, App, AsInstanceState, Error, PrepareContext, RuntimeFactors,
+    prepare::FactorInstanceBuilder, App, AsInstanceState, BackgroundTask, BackgroundTasks, Error,
+    PrepareContext, RuntimeFactors,
 };
 
 /// A contained (i.e., "factored") piece of runtime functionality.
@@ -40,6 +41,7 @@ pub trait Factor: Any + Sized {
     /// - The `spin_app::App`
     /// - This factors's `RuntimeConfig`
     /// - The `AppState` for any factors configured before this one
+    /// - The registry of [`BackgroundTasks`] for the app
     ///
     /// A runtime may - but is not required to - reuse the returned config
     /// across multiple instances. Because this method may be called
@@ -125,6 +127,8 @@ pub trait FactorField {
 
     fn get(field: &mut Self::State)
         -> (&mut FactorInstanceState<Self::Factor>, &mut ResourceTable);
+
+    fn get_field(field: &mut Self::State) -> &mut FactorInstanceState<Self::Factor>;
 }
 
 impl<T, G> InitContext<G::Factor> for FactorInitContext<'_, T, G>
@@ -149,6 +153,7 @@ pub struct ConfigureAppContext<'a, T: RuntimeFactors, F: Factor> {
     app: &'a App,
     app_state: &'a T::AppState,
     runtime_config: Option<F::RuntimeConfig>,
+    background_tasks: &'a BackgroundTasks,
 }
 
 impl<'a, T: RuntimeFactors, F: Factor> ConfigureAppContext<'a, T, F> {
@@ -157,11 +162,13 @@ impl<'a, T: RuntimeFactors, F: Factor> ConfigureAppContext<'a, T, F> {
         app: &'a App,
         app_state: &'a T::AppState,
         runtime_config: Option<F::RuntimeConfig>,
+        background_tasks: &'a BackgroundTasks,
     ) -> crate::Result<Self> {
         Ok(Self {
             app,
             app_state,
             runtime_config,
+            background_tasks,
         })
     }
 
@@ -184,18 +191,29 @@ impl<'a, T: RuntimeFactors, F: Factor> ConfigureAppContext<'a, T, F> {
     pub fn take_runtime_config(&mut self) -> Option<F::RuntimeConfig> {
         self.runtime_config.take()
     }
+
+    /// Get the registry of background tasks to run for as long as the app is
+    /// loaded.
+    pub fn background_tasks(&self) -> &'a BackgroundTasks {
+        self.background_tasks
+    }
 }
 
 #[doc(hidden)]
 pub struct ConfiguredApp<T: RuntimeFactors> {
     app: App,
     app_state: T::AppState,
+    background_tasks: BackgroundTasks,
 }
 
 impl<T: RuntimeFactors> ConfiguredApp<T> {
     #[doc(hidden)]
-    pub fn new(app: App, app_state: T::AppState) -> Self {
-        Self { app, app_state }
+    pub fn new(app: App, app_state: T::AppState, background_tasks: BackgroundTasks) -> Self {
+        Self {
+            app,
+            app_state,
+            background_tasks,
+        }
     }
 
     /// Get the configured [`App`].
@@ -207,4 +225,10 @@ impl<T: RuntimeFactors> ConfiguredApp<T> {
     pub fn app_state<U: Factor>(&self) -> crate::Result<&U::AppState> {
         T::app_state::<U>(&self.app_state).ok_or(Error::no_such_factor::<U>())
     }
+
+    /// Take the background tasks registered while configuring the app, e.g.
+    /// to run them.
+    pub fn take_background_tasks(&self) -> Vec<BackgroundTask> {
+        self.background_tasks.take()
+    }
 }
@@ -0,0 +1,168 @@
+//! Support for `#[factors(flatten)]` fields, which nest one
+//! [`RuntimeFactors`](crate::RuntimeFactors) collection inside another.
+//!
+//! The nested collection's factors are initialized, configured and prepared
+//! as part of the outer collection, so they see the outer collection's app
+//! state and instance builders. Everything here is used by
+//! `#[derive(RuntimeFactors)]` and should not be used directly.
+
+use std::marker::PhantomData;
+
+use wasmtime::component::{Linker, ResourceTable};
+
+use crate::{factor::FactorField, FactorInstanceState, InitContext};
+
+/// A path from the data of a `Store<T>` to the instance state of a nested
+/// collection, alongside the resource table shared by the whole store.
+pub trait StatePath<T>: 'static {
+    type State;
+
+    fn get(store: &mut T) -> (&mut Self::State, &mut ResourceTable);
+}
+
+/// A path from one of the structs generated for a collection (its app state,
+/// instance builders or instance state) to that of a nested collection.
+pub trait FieldPath<S>: 'static {
+    type Target;
+
+    fn get(parent: &mut S) -> &mut Self::Target;
+}
+
+/// The empty [`FieldPath`].
+pub struct SelfPath;
+
+impl<S> FieldPath<S> for SelfPath {
+    type Target = S;
+
+    fn get(parent: &mut S) -> &mut S {
+        parent
+    }
+}
+
+/// The path `P` followed by the [`FieldPath`] `Q`.
+pub struct Join<P, Q>(PhantomData<(P, Q)>);
+
+impl<S, P: FieldPath<S>, Q: FieldPath<P::Target>> FieldPath<S> for Join<P, Q> {
+    type Target = Q::Target;
+
+    fn get(parent: &mut S) -> &mut Self::Target {
+        Q::get(P::get(parent))
+    }
+}
+
+impl<T, P: StatePath<T>, Q: FieldPath<P::State>> StatePath<T> for Join<P, Q> {
+    type State = Q::Target;
+
+    fn get(store: &mut T) -> (&mut Self::State, &mut ResourceTable) {
+        let (state, table) = P::get(store);
+        (Q::get(state), table)
+    }
+}
+
+/// The [`InitContext`] of a factor in a nested collection, whose instance
+/// state is found by following the path `P`.
+pub struct NestedFactorInitContext<'a, T: 'static, P, G> {
+    pub linker: &'a mut Linker<T>,
+    pub _marker: PhantomData<(P, G)>,
+}
+
+impl<T, P, G> InitContext<G::Factor> for NestedFactorInitContext<'_, T, P, G>
+where
+    T: Send + 'static,
+    P: StatePath<T, State = G::State>,
+    G: FactorField,
+{
+    type StoreData = T;
+
+    fn linker(&mut self) -> &mut Linker<Self::StoreData> {
+        self.linker
+    }
+
+    fn get_data_with_table(
+        store: &mut Self::StoreData,
+    ) -> (&mut FactorInstanceState<G::Factor>, &mut ResourceTable) {
+        let (state, table) = P::get(store);
+        (G::get_field(state), table)
+    }
+}
+
+/// Concatenates lists of factor type names into one of length `N`, for
+/// [`RuntimeFactors::FACTOR_NAMES`](crate::RuntimeFactors::FACTOR_NAMES).
+pub const fn concat_factor_names<const N: usize>(lists: &[&[&'static str]]) -> [&'static str; N] {
+    let mut names = [""; N];
+    let mut n = 0;
+    let mut i = 0;
+    while i < lists.len() {
+        let mut j = 0;
+        while j < lists[i].len() {
+            names[n] = lists[i][j];
+            n += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+    assert!(n == N, "wrong number of factor names");
+    names
+}
+
+/// Fails to compile, when evaluated in a constant, if any name appears in
+/// `names` more than once, with a message naming the factor and the
+/// `collection` it is duplicated in.
+pub const fn assert_each_factor_named_once(collection: &str, names: &[&str]) {
+    let mut i = 0;
+    while i < names.len() {
+        let mut j = i + 1;
+        while j < names.len() {
+            if str_eq(names[i], names[j]) {
+                duplicate_factor(collection, names[i]);
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const MESSAGE_CAPACITY: usize = 512;
+
+// Const panics can only format a `&str` argument, so the message is built
+// up in a buffer first
+const fn duplicate_factor(collection: &str, factor: &str) -> ! {
+    let mut message = [0u8; MESSAGE_CAPACITY];
+    let mut len = 0;
+    len = push_str(&mut message, len, "factor `");
+    len = push_str(&mut message, len, factor);
+    len = push_str(&mut message, len, "` appears more than once in `");
+    len = push_str(&mut message, len, collection);
+    len = push_str(&mut message, len, "`, including its flattened collections");
+    match std::str::from_utf8(message.split_at(len).0) {
+        Ok(message) => panic!("{}", message),
+        // Truncated in the middle of a character
+        Err(_) => panic!("a factor appears more than once in a collection"),
+    }
+}
+
+const fn push_str(message: &mut [u8; MESSAGE_CAPACITY], mut len: usize, s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && len < MESSAGE_CAPACITY {
+        message[len] = bytes[i];
+        len += 1;
+        i += 1;
+    }
+    len
+}
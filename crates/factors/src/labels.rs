@@ -0,0 +1,74 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The label which runtime config defines for every app, and so is never
+/// reported as unused.
+pub const DEFAULT_LABEL: &str = "default";
+
+/// A label used by components but not defined by runtime config, as found by
+/// [`LabelReport::new`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndefinedLabel {
+    /// The undefined label.
+    pub label: String,
+    /// The components which use the label, in ID order.
+    pub components: Vec<String>,
+    /// The defined label most similar to `label`, if any is similar enough to
+    /// be a likely typo.
+    pub suggestion: Option<String>,
+}
+
+/// A cross-check of the labels of resources (such as key-value stores or
+/// SQLite databases) used by an app's components against the labels defined
+/// by runtime config.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelReport {
+    /// The labels used by components but not defined, in label order.
+    pub undefined: Vec<UndefinedLabel>,
+    /// The labels defined but used by no component, in label order.
+    /// [`DEFAULT_LABEL`] is never included.
+    pub unused: Vec<String>,
+}
+
+impl LabelReport {
+    /// Checks the `(component ID, label)` pairs of `uses` against the
+    /// `defined` labels.
+    pub fn new<'a>(
+        uses: impl IntoIterator<Item = (&'a str, &'a str)>,
+        defined: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let defined: BTreeSet<_> = defined.into_iter().collect();
+        let mut used = BTreeSet::new();
+        let mut undefined = BTreeMap::<_, BTreeSet<_>>::new();
+        for (component_id, label) in uses {
+            used.insert(label);
+            if !defined.contains(label) {
+                undefined.entry(label).or_default().insert(component_id);
+            }
+        }
+        let undefined = undefined
+            .into_iter()
+            .map(|(label, components)| UndefinedLabel {
+                label: label.to_owned(),
+                components: components.into_iter().map(ToOwned::to_owned).collect(),
+                suggestion: closest_label(label, &defined).map(ToOwned::to_owned),
+            })
+            .collect();
+        let unused = defined
+            .into_iter()
+            .filter(|label| *label != DEFAULT_LABEL && !used.contains(label))
+            .map(ToOwned::to_owned)
+            .collect();
+        Self { undefined, unused }
+    }
+}
+
+/// The defined label most similar to `label`, if any is similar enough to be
+/// a likely typo.
+fn closest_label<'a>(label: &str, defined: &BTreeSet<&'a str>) -> Option<&'a str> {
+    defined
+        .iter()
+        .map(|candidate| (levenshtein::levenshtein(label, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
@@ -1,10 +1,15 @@
 mod factor;
+#[doc(hidden)]
+pub mod flatten;
+mod labels;
 mod prepare;
 pub mod runtime_config;
 mod runtime_factors;
+mod tasks;
 
 pub use anyhow;
 pub use serde;
+pub use tracing;
 pub use wasmtime;
 
 pub use spin_app::{App, AppComponent};
@@ -15,11 +20,13 @@ pub use crate::{
         ConfigureAppContext, ConfiguredApp, Factor, FactorData, FactorField, FactorInitContext,
         FactorInstanceState, InitContext,
     },
+    labels::{LabelReport, UndefinedLabel, DEFAULT_LABEL},
     prepare::{FactorInstanceBuilder, PrepareContext, SelfInstanceBuilder},
     runtime_config::{FactorRuntimeConfigSource, RuntimeConfigSourceFinalizer},
     runtime_factors::{
         AsInstanceState, HasInstanceBuilder, RuntimeFactors, RuntimeFactorsInstanceState,
     },
+    tasks::{BackgroundTask, BackgroundTasks, ShutdownSignal, TaskPolicy},
 };
 
 /// Result wrapper type defaulting to use [`Error`].
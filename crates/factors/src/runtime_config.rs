@@ -14,6 +14,16 @@ impl<F: Factor> FactorRuntimeConfigSource<F> for () {
     }
 }
 
+/// Gets the runtime configuration of every factor in a
+/// [`RuntimeFactors`](crate::RuntimeFactors) collection, including those of
+/// flattened collections, without finalizing the source.
+///
+/// Implemented by `#[derive(RuntimeFactors)]` for the generated runtime config.
+#[doc(hidden)]
+pub trait RuntimeConfigFromSource<S>: Sized {
+    fn from_source_unfinalized(source: &mut S) -> anyhow::Result<Self>;
+}
+
 /// Run some finalization logic on a [`FactorRuntimeConfigSource`].
 pub trait RuntimeConfigSourceFinalizer {
     /// Finalize the runtime config source.
@@ -1,6 +1,11 @@
+use spin_app::AppComponent;
 use wasmtime::component::{Linker, ResourceTable};
 
-use crate::{factor::FactorInstanceState, App, ConfiguredApp, Factor};
+use crate::{
+    factor::FactorInstanceState,
+    flatten::{FieldPath, StatePath},
+    App, BackgroundTasks, ConfiguredApp, Factor,
+};
 
 /// A collection of `Factor`s that are initialized and configured together.
 ///
@@ -30,15 +35,42 @@ use crate::{factor::FactorInstanceState, App, ConfiguredApp, Factor};
 /// // Instantiate the component
 /// let instance = linker.instantiate_async(&mut store, &component).await?;
 /// ```
+///
+/// # Nesting
+///
+/// A field marked `#[factors(flatten)]` holds another `RuntimeFactors`
+/// collection, whose factors become part of this one. This lets an embedder
+/// add factors to an existing collection without redeclaring its fields:
+///
+/// ```ignore
+/// #[derive(RuntimeFactors)]
+/// struct MyFactors {
+///     #[factors(flatten)]
+///     trigger: TriggerFactors,
+///     custom: MyCustomFactor,
+/// }
+/// ```
+///
+/// Factors are initialized, configured and prepared in field order, with the
+/// nested collection's factors in the position of its field, and can depend
+/// on one another across levels. The generated runtime config, app state,
+/// instance builders and instance state each hold the nested collection's
+/// equivalent in a field of the same name. A factor type which appears more
+/// than once, at any level, is a compile error naming the factor, or an error
+/// from [`RuntimeFactors::init`] if it is spelled differently each time.
 pub trait RuntimeFactors: Send + Sync + Sized + 'static {
     /// The per application state of all the factors.
-    type AppState: Sync + Send;
+    type AppState: Sync + Send + Default;
     /// The per instance state of the factors.
     type InstanceState: RuntimeFactorsInstanceState;
     /// The collection of all the `InstanceBuilder`s of the factors.
-    type InstanceBuilders: Send + HasInstanceBuilder;
+    type InstanceBuilders: Send + HasInstanceBuilder + Default;
     /// The runtime configuration of all the factors.
     type RuntimeConfig: Default;
+    /// The names of the types of all the factors, as written, including
+    /// those of flattened collections.
+    #[doc(hidden)]
+    const FACTOR_NAMES: &'static [&'static str];
 
     /// Initialize the factors with the given linker.
     ///
@@ -49,6 +81,19 @@ pub trait RuntimeFactors: Send + Sync + Sized + 'static {
         linker: &mut Linker<T>,
     ) -> crate::Result<()>;
 
+    /// The names and type ids of all the factors, including those of
+    /// flattened collections, which `init` checks are distinct.
+    #[doc(hidden)]
+    fn factor_type_ids() -> Vec<(&'static str, std::any::TypeId)>;
+
+    /// Initialize the factors of a collection flattened into another, whose
+    /// instance state is found by following the path `P`.
+    #[doc(hidden)]
+    fn init_nested<T: Send + 'static, P: StatePath<T, State = Self::InstanceState>>(
+        &mut self,
+        linker: &mut Linker<T>,
+    ) -> crate::Result<()>;
+
     /// Configure the factors with the given app and runtime config.
     fn configure_app(
         &self,
@@ -56,6 +101,17 @@ pub trait RuntimeFactors: Send + Sync + Sized + 'static {
         runtime_config: Self::RuntimeConfig,
     ) -> crate::Result<ConfiguredApp<Self>>;
 
+    /// Configure the factors of a collection flattened into the collection
+    /// `O`, storing their app state at the path `P`.
+    #[doc(hidden)]
+    fn configure_nested<O: RuntimeFactors, P: FieldPath<O::AppState, Target = Self::AppState>>(
+        &self,
+        app: &App,
+        app_state: &mut O::AppState,
+        runtime_config: Self::RuntimeConfig,
+        background_tasks: &BackgroundTasks,
+    ) -> crate::Result<()>;
+
     /// Prepare the factors' instance state builders.
     fn prepare(
         &self,
@@ -63,6 +119,19 @@ pub trait RuntimeFactors: Send + Sync + Sized + 'static {
         component_id: &str,
     ) -> crate::Result<Self::InstanceBuilders>;
 
+    /// Prepare the factors of a collection flattened into the collection `O`,
+    /// storing their instance builders at the path `P`.
+    #[doc(hidden)]
+    fn prepare_nested<
+        O: RuntimeFactors,
+        P: FieldPath<O::InstanceBuilders, Target = Self::InstanceBuilders>,
+    >(
+        &self,
+        configured_app: &ConfiguredApp<O>,
+        app_component: &AppComponent,
+        builders: &mut O::InstanceBuilders,
+    ) -> crate::Result<()>;
+
     /// Build the instance state for the factors.
     fn build_instance_state(
         &self,
@@ -0,0 +1,106 @@
+use std::{future::Future, pin::Pin, sync::Mutex};
+
+use tokio::sync::watch;
+
+/// What the executor does when a background task fails, i.e. returns an
+/// error or panics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskPolicy {
+    /// Restart the task after a delay, which doubles with each consecutive
+    /// failure.
+    RestartWithBackoff,
+    /// Stop the task and fail the app.
+    FailApp,
+}
+
+type RunTask = Box<
+    dyn Fn(ShutdownSignal) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// A background task registered by a factor with [`BackgroundTasks::register`].
+pub struct BackgroundTask {
+    /// The name of the task, for logs and status reports.
+    pub name: String,
+    /// What to do when the task fails.
+    pub policy: TaskPolicy,
+    run: RunTask,
+}
+
+impl BackgroundTask {
+    /// Starts a run of the task, which should return once `shutdown` is
+    /// requested.
+    pub fn run(
+        &self,
+        shutdown: ShutdownSignal,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> {
+        (self.run)(shutdown)
+    }
+}
+
+impl std::fmt::Debug for BackgroundTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackgroundTask")
+            .field("name", &self.name)
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The background tasks registered by factors while configuring an app.
+///
+/// The tasks are run by the executor for as long as the app is loaded.
+#[derive(Debug, Default)]
+pub struct BackgroundTasks {
+    tasks: Mutex<Vec<BackgroundTask>>,
+}
+
+impl BackgroundTasks {
+    /// Registers a background task with the given name.
+    ///
+    /// `run` is called to start the task, and called again to restart it if
+    /// it fails under [`TaskPolicy::RestartWithBackoff`]. The task is
+    /// cancelled when the app is unloaded; tasks which need to clean up
+    /// should instead return promptly once the given [`ShutdownSignal`] is
+    /// requested.
+    pub fn register<F, Fut>(&self, name: impl Into<String>, policy: TaskPolicy, run: F)
+    where
+        F: Fn(ShutdownSignal) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.tasks.lock().unwrap().push(BackgroundTask {
+            name: name.into(),
+            policy,
+            run: Box::new(move |shutdown| Box::pin(run(shutdown))),
+        });
+    }
+
+    /// Takes the registered tasks, e.g. to run them.
+    pub fn take(&self) -> Vec<BackgroundTask> {
+        std::mem::take(&mut self.tasks.lock().unwrap())
+    }
+}
+
+/// Tells a background task when the app is shutting down.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Returns a signal which is requested once the sending half of
+    /// `receiver` sends `true` or is dropped.
+    pub fn new(receiver: watch::Receiver<bool>) -> Self {
+        Self(receiver)
+    }
+
+    /// Returns whether shutdown has been requested.
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow() || self.0.has_changed().is_err()
+    }
+
+    /// Waits until shutdown is requested.
+    pub async fn requested(&mut self) {
+        // An error means the sender was dropped, which also means shutdown
+        _ = self.0.wait_for(|requested| *requested).await;
+    }
+}
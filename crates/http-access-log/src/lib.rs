@@ -0,0 +1,344 @@
+//! Access logging for the HTTP trigger.
+//!
+//! An [`AccessLogWriter`] writes one line per request, in Combined Log Format
+//! or as JSON, to stdout, a file or syslog. It is configured by the
+//! manifest's `[application.trigger.http.access_log]` section:
+//!
+//! ```toml
+//! [application.trigger.http.access_log]
+//! format = "json"          # or "combined" (the default)
+//! destination = "file"     # or "stdout" (the default) or "syslog"
+//! path = "logs/access.log" # required for, and only allowed with, "file"
+//! ```
+
+#![deny(missing_docs)]
+
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    net::IpAddr,
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The `[application.trigger.http.access_log]` manifest section.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessLogConfig {
+    /// How each entry is formatted.
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    /// Where entries are written.
+    #[serde(default)]
+    pub destination: AccessLogDestination,
+    /// The file entries are appended to, for the `file` destination. A
+    /// relative path is relative to the directory Spin is run from.
+    pub path: Option<PathBuf>,
+}
+
+/// The format of access log entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// The Combined Log Format used by Apache and nginx.
+    #[default]
+    Combined,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Where access log entries are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogDestination {
+    /// The standard output of the Spin process.
+    #[default]
+    Stdout,
+    /// The file at the configured `path`.
+    File,
+    /// The local syslog daemon, with the `local0` facility. Only supported
+    /// on Unix.
+    Syslog,
+}
+
+/// A request to be recorded in the access log.
+#[derive(Clone, Debug, Serialize)]
+pub struct AccessLogEntry<'a> {
+    /// The address of the client which sent the request.
+    pub client_addr: IpAddr,
+    /// When the request was received.
+    pub time: DateTime<Utc>,
+    /// The request method.
+    pub method: &'a str,
+    /// The request target: the path and any query string.
+    pub target: &'a str,
+    /// The request's protocol version, e.g. `HTTP/1.1`.
+    pub protocol: &'a str,
+    /// The status code of the response.
+    pub status: u16,
+    /// The number of bytes in the response body.
+    pub bytes: u64,
+    /// The request's `Referer` header, if it has one.
+    pub referer: Option<&'a str>,
+    /// The request's `User-Agent` header, if it has one.
+    pub user_agent: Option<&'a str>,
+    /// How long the request took, from receiving it to sending the end of the
+    /// response body.
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+}
+
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+impl AccessLogEntry<'_> {
+    /// Formats the entry in Combined Log Format, e.g.
+    ///
+    /// ```text
+    /// 127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326 "-" "curl/8.4.0"
+    /// ```
+    pub fn to_combined(&self) -> String {
+        let mut line = format!(
+            "{} - - [{}] \"{} {} {}\" {} ",
+            self.client_addr,
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            escape(self.method),
+            escape(self.target),
+            escape(self.protocol),
+            self.status,
+        );
+        // An empty body is logged as `-`
+        match self.bytes {
+            0 => line.push('-'),
+            bytes => write!(line, "{bytes}").unwrap(),
+        }
+        for header in [self.referer, self.user_agent] {
+            write!(
+                line,
+                " \"{}\"",
+                header.map(escape).as_deref().unwrap_or("-")
+            )
+            .unwrap();
+        }
+        line
+    }
+
+    /// Formats the entry as a single-line JSON object.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("access log entries are always serializable")
+    }
+}
+
+/// Escapes a request value for a quoted field of a Combined Log Format entry,
+/// so that a client can't forge entries or break the format. As in Apache,
+/// quotes and backslashes are escaped with a backslash and other control and
+/// non-ASCII characters are written as `\xhh` escapes of their UTF-8 bytes.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            _ => {
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    write!(escaped, "\\x{byte:02x}").unwrap();
+                }
+            }
+        }
+    }
+    escaped
+}
+
+/// Writes [`AccessLogEntry`]s to a configured destination.
+///
+/// Writes are synchronous; a single writer may be shared between threads.
+pub struct AccessLogWriter {
+    format: AccessLogFormat,
+    sink: Sink,
+}
+
+enum Sink {
+    Stdout,
+    File(Mutex<File>),
+    #[cfg(unix)]
+    Syslog(std::os::unix::net::UnixDatagram),
+}
+
+impl AccessLogWriter {
+    /// Returns a writer for the given configuration, opening any log file or
+    /// syslog connection.
+    pub fn new(config: &AccessLogConfig) -> anyhow::Result<Self> {
+        let sink = match (config.destination, &config.path) {
+            (AccessLogDestination::File, Some(path)) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| {
+                        format!("failed to open access log file {}", path.display())
+                    })?;
+                Sink::File(Mutex::new(file))
+            }
+            (AccessLogDestination::File, None) => {
+                anyhow::bail!("access log destination \"file\" requires a `path`")
+            }
+            (_, Some(_)) => {
+                anyhow::bail!("access log `path` is only allowed with destination \"file\"")
+            }
+            (AccessLogDestination::Stdout, None) => Sink::Stdout,
+            (AccessLogDestination::Syslog, None) => Self::syslog_sink()?,
+        };
+        Ok(Self {
+            format: config.format,
+            sink,
+        })
+    }
+
+    #[cfg(unix)]
+    fn syslog_sink() -> anyhow::Result<Sink> {
+        // Linux and most BSDs use `/dev/log`; macOS uses `/var/run/syslog`
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        ["/dev/log", "/var/run/syslog"]
+            .into_iter()
+            .find(|path| socket.connect(path).is_ok())
+            .context("failed to connect to the syslog daemon")?;
+        Ok(Sink::Syslog(socket))
+    }
+
+    #[cfg(not(unix))]
+    fn syslog_sink() -> anyhow::Result<Sink> {
+        anyhow::bail!("access log destination \"syslog\" is only supported on Unix")
+    }
+
+    /// Formats an entry in the configured format.
+    pub fn format(&self, entry: &AccessLogEntry) -> String {
+        match self.format {
+            AccessLogFormat::Combined => entry.to_combined(),
+            AccessLogFormat::Json => entry.to_json(),
+        }
+    }
+
+    /// Writes an entry to the configured destination.
+    pub fn write(&self, entry: &AccessLogEntry) -> io::Result<()> {
+        let line = self.format(entry);
+        match &self.sink {
+            Sink::Stdout => writeln!(io::stdout().lock(), "{line}"),
+            Sink::File(file) => {
+                // Write each entry in one call so that entries don't interleave
+                let mut file = file.lock().unwrap_or_else(|err| err.into_inner());
+                file.write_all(format!("{line}\n").as_bytes())
+            }
+            #[cfg(unix)]
+            Sink::Syslog(socket) => {
+                // `<134>` is the priority of the `local0` facility's `info`
+                // level, as in RFC 3164
+                let message = format!("<134>spin[{}]: {line}", std::process::id());
+                socket.send(message.as_bytes()).map(|_| ())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> AccessLogEntry<'static> {
+        AccessLogEntry {
+            client_addr: "127.0.0.1".parse().unwrap(),
+            time: DateTime::parse_from_rfc3339("2000-10-10T13:55:36Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            method: "GET",
+            target: "/index.html?page=2",
+            protocol: "HTTP/1.1",
+            status: 200,
+            bytes: 2326,
+            referer: None,
+            user_agent: Some("curl/8.4.0"),
+            duration: Duration::from_micros(1500),
+        }
+    }
+
+    #[test]
+    fn combined_format() {
+        assert_eq!(
+            entry().to_combined(),
+            r#"127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html?page=2 HTTP/1.1" 200 2326 "-" "curl/8.4.0""#
+        );
+
+        let entry = AccessLogEntry {
+            bytes: 0,
+            referer: Some("https://example.com/\"quoted\""),
+            user_agent: Some("evil\nagent\u{e9}"),
+            ..entry()
+        };
+        assert_eq!(
+            entry.to_combined(),
+            r#"127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html?page=2 HTTP/1.1" 200 - "https://example.com/\"quoted\"" "evil\x0aagent\xc3\xa9""#
+        );
+    }
+
+    #[test]
+    fn json_format() {
+        let json: serde_json::Value = serde_json::from_str(&entry().to_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "client_addr": "127.0.0.1",
+                "time": "2000-10-10T13:55:36Z",
+                "method": "GET",
+                "target": "/index.html?page=2",
+                "protocol": "HTTP/1.1",
+                "status": 200,
+                "bytes": 2326,
+                "referer": null,
+                "user_agent": "curl/8.4.0",
+                "duration_ms": 1.5,
+            })
+        );
+    }
+
+    #[test]
+    fn file_destination_appends_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let config: AccessLogConfig = toml::from_str(&format!(
+            "format = \"json\"\ndestination = \"file\"\npath = {:?}",
+            path.to_str().unwrap()
+        ))
+        .unwrap();
+        for _ in 0..2 {
+            let writer = AccessLogWriter::new(&config).unwrap();
+            writer.write(&entry()).unwrap();
+        }
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines, [entry().to_json(), entry().to_json()]);
+    }
+
+    #[test]
+    fn path_must_match_destination() {
+        let config: AccessLogConfig = toml::from_str("destination = \"file\"").unwrap();
+        assert!(AccessLogWriter::new(&config).is_err());
+        let config: AccessLogConfig = toml::from_str("path = \"access.log\"").unwrap();
+        assert!(AccessLogWriter::new(&config).is_err());
+        let config: AccessLogConfig = toml::from_str("").unwrap();
+        assert_eq!(config.format, AccessLogFormat::Combined);
+        assert_eq!(config.destination, AccessLogDestination::Stdout);
+    }
+}
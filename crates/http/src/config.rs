@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use spin_http_routes::HttpTriggerRouteConfig;
+use spin_http_routes::{HttpTriggerHostConfig, HttpTriggerRouteConfig};
 
 /// Configuration for the HTTP trigger
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -9,9 +9,169 @@ pub struct HttpTriggerConfig {
     pub component: String,
     /// HTTP route the component will be invoked for
     pub route: HttpTriggerRouteConfig,
+    /// Host or hosts the route is restricted to; if omitted, the route
+    /// matches requests for any host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<HttpTriggerHostConfig>,
     /// The HTTP executor the component requires
     #[serde(default)]
     pub executor: Option<HttpExecutorType>,
+    /// Authentication required before the component is invoked. `OPTIONS`
+    /// requests which the trigger answers itself, such as CORS preflights,
+    /// don't require it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<HttpRouteAuthConfig>,
+    /// For server-sent event responses, the number of seconds the component
+    /// may go without writing before the stream is ended; if omitted, a silent
+    /// stream is kept open until the client disconnects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_idle_timeout_secs: Option<u64>,
+    /// The HTTP methods the component handles; if omitted, the component is
+    /// assumed to handle every method. Declaring methods lets the trigger
+    /// answer `OPTIONS` requests itself unless `OPTIONS` is declared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<String>>,
+    /// Whether `HEAD` requests are passed to the component as `GET` requests,
+    /// with the body of its response discarded, unless it declares `HEAD` in
+    /// its `methods`. The response has no `Content-Length` unless the
+    /// component sets one or its body has a known length
+    #[serde(default = "default_auto_head", alias = "handle_head_as_get")]
+    pub auto_head: bool,
+    /// Whether each request is given an `X-Request-ID` header, keeping the
+    /// client's if it sent one, which is also passed on to chained components
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inject_request_id: bool,
+    /// Whether request bodies with a `Content-Encoding` of `gzip`, `br` or
+    /// `zstd` are decompressed before being passed to the component. Bodies
+    /// are held to the component's `max_request_body_bytes`, both compressed
+    /// and decompressed, or to 64 MiB if it has no limit.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub decompress_request_body: bool,
+    /// Whether responses are compressed with `gzip` for clients which accept
+    /// it, unless their type is usually compressed already
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub compress_response: bool,
+    /// With `compress_response`, the size in bytes below which responses are
+    /// not compressed
+    #[serde(default = "default_min_compress_size_bytes")]
+    pub min_compress_size_bytes: usize,
+    /// Whether the request body is streamed to the component as it arrives,
+    /// or read in full before the component is invoked
+    #[serde(default, skip_serializing_if = "BodyHandling::is_stream")]
+    pub body_handling: BodyHandling,
+    /// With `body_handling = "buffer"`, the number of bytes of the body kept
+    /// in memory; the rest is written to a temporary file
+    #[serde(default = "default_max_memory_bytes")]
+    pub max_memory_bytes: u64,
+    /// With `body_handling = "buffer"`, the size in bytes above which request
+    /// bodies are rejected; if omitted, the component's
+    /// `max_request_body_bytes` limit applies, or 256 MiB if it has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_bytes: Option<u64>,
+    /// A component which is sent a copy of a sample of the route's requests,
+    /// with its responses discarded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<HttpShadowConfig>,
+}
+
+fn default_auto_head() -> bool {
+    true
+}
+
+fn default_min_compress_size_bytes() -> usize {
+    1024
+}
+
+fn default_max_memory_bytes() -> u64 {
+    1024 * 1024
+}
+
+/// How an HTTP route passes request bodies to its component.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyHandling {
+    /// The body is passed on as it arrives.
+    #[default]
+    Stream,
+    /// The whole body is read before the component is invoked, for
+    /// components which need all of it anyway, e.g. to verify a signature.
+    Buffer,
+}
+
+impl BodyHandling {
+    fn is_stream(&self) -> bool {
+        *self == Self::Stream
+    }
+}
+
+/// Shadowing of a sample of an HTTP route's requests to a second component,
+/// e.g. to compare a new version of a component against the one serving the
+/// route before switching over to it.
+///
+/// The route's component serves every request as usual. Sampled requests are
+/// also copied to the shadow component once the route's component has read
+/// the request body, and the shadow's response is discarded; only its status
+/// and duration are recorded. The shadow component runs with its own
+/// permissions, so any outbound requests or writes it makes really happen:
+/// components used as shadows should not have side effects which conflict
+/// with those of the route's component.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpShadowConfig {
+    /// The component the copies are sent to, which must have an HTTP trigger
+    /// of its own (e.g. with `route = { private = true }`)
+    pub component: String,
+    /// The percentage of requests copied, from 0 to 100
+    pub percent: f64,
+    /// The number of milliseconds the shadow component may take to respond in
+    /// full before it is abandoned
+    #[serde(default = "default_shadow_timeout_ms")]
+    pub timeout_ms: u64,
+    /// The size in bytes above which request bodies are not copied; sampled
+    /// requests with larger bodies are not shadowed
+    #[serde(default = "default_shadow_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+fn default_shadow_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_shadow_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+/// Host-enforced authentication for an HTTP route.
+///
+/// Credentials are never written into the manifest directly; instead each
+/// field names an application variable which is resolved (through any
+/// configured variables providers) when a request is matched.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "lowercase", tag = "type")]
+pub enum HttpRouteAuthConfig {
+    /// The request must carry an `Authorization: Bearer <token>` header.
+    Bearer {
+        /// The variable holding the expected token.
+        token_variable: String,
+    },
+    /// The request must carry an `Authorization: Basic <credentials>` header.
+    Basic {
+        /// The variable holding the expected user name.
+        username_variable: String,
+        /// The variable holding the expected password.
+        password_variable: String,
+    },
+}
+
+impl HttpRouteAuthConfig {
+    /// The authentication scheme, as used in the `Authorization` and
+    /// `WWW-Authenticate` headers.
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Self::Bearer { .. } => "Bearer",
+            Self::Basic { .. } => "Basic",
+        }
+    }
 }
 
 /// The executor for the HTTP component.
@@ -78,4 +238,181 @@ mod tests {
         assert_eq!(config.entrypoint, "_start");
         assert_eq!(config.argv, "${SCRIPT_NAME} ${ARGS}");
     }
+
+    #[test]
+    fn auth_config_smoke_test() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "admin"
+            route = "/admin/..."
+            auth = { type = "bearer", token_variable = "admin_token" }
+        }
+        .try_into()
+        .unwrap();
+        let Some(HttpRouteAuthConfig::Bearer { token_variable }) = config.auth else {
+            panic!("wrong auth type");
+        };
+        assert_eq!(token_variable, "admin_token");
+
+        let auth: HttpRouteAuthConfig = toml::toml! {
+            type = "basic"
+            username_variable = "admin_user"
+            password_variable = "admin_password"
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(auth.scheme(), "Basic");
+
+        toml::toml! {
+            type = "bearer"
+            token = "hunter2"
+        }
+        .try_into::<HttpRouteAuthConfig>()
+        .expect_err("literal tokens should be rejected");
+    }
+
+    #[test]
+    fn auto_head_defaults_to_true() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "page"
+            route = "/..."
+        }
+        .try_into()
+        .unwrap();
+        assert!(config.auto_head);
+        assert_eq!(config.methods, None);
+
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "page"
+            route = "/..."
+            auto_head = false
+            methods = ["GET", "POST"]
+        }
+        .try_into()
+        .unwrap();
+        assert!(!config.auto_head);
+        assert_eq!(config.methods.unwrap(), ["GET", "POST"]);
+
+        // The option's former name is still accepted
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "page"
+            route = "/..."
+            handle_head_as_get = false
+        }
+        .try_into()
+        .unwrap();
+        assert!(!config.auto_head);
+    }
+
+    #[test]
+    fn inject_request_id_defaults_to_false() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "api"
+            route = "/..."
+        }
+        .try_into()
+        .unwrap();
+        assert!(!config.inject_request_id);
+
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "api"
+            route = "/..."
+            inject_request_id = true
+        }
+        .try_into()
+        .unwrap();
+        assert!(config.inject_request_id);
+    }
+
+    #[test]
+    fn decompress_request_body_defaults_to_false() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "upload"
+            route = "/..."
+        }
+        .try_into()
+        .unwrap();
+        assert!(!config.decompress_request_body);
+
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "upload"
+            route = "/..."
+            decompress_request_body = true
+        }
+        .try_into()
+        .unwrap();
+        assert!(config.decompress_request_body);
+    }
+
+    #[test]
+    fn compress_response_defaults() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "site"
+            route = "/..."
+        }
+        .try_into()
+        .unwrap();
+        assert!(!config.compress_response);
+        assert_eq!(config.min_compress_size_bytes, 1024);
+
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "site"
+            route = "/..."
+            compress_response = true
+            min_compress_size_bytes = 256
+        }
+        .try_into()
+        .unwrap();
+        assert!(config.compress_response);
+        assert_eq!(config.min_compress_size_bytes, 256);
+    }
+
+    #[test]
+    fn body_handling_defaults_to_stream() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "upload"
+            route = "/..."
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(config.body_handling, BodyHandling::Stream);
+        assert_eq!(config.max_memory_bytes, 1024 * 1024);
+        assert_eq!(config.max_total_bytes, None);
+
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "upload"
+            route = "/..."
+            body_handling = "buffer"
+            max_memory_bytes = 65536
+            max_total_bytes = 10485760
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(config.body_handling, BodyHandling::Buffer);
+        assert_eq!(config.max_memory_bytes, 65536);
+        assert_eq!(config.max_total_bytes, Some(10485760));
+
+        toml::toml! {
+            component = "upload"
+            route = "/..."
+            body_handling = "spool"
+        }
+        .try_into::<HttpTriggerConfig>()
+        .expect_err("unknown body handling should be rejected");
+    }
+
+    #[test]
+    fn shadow_config_smoke_test() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "checkout-v1"
+            route = "/checkout/..."
+            shadow = { component = "checkout-v2", percent = 5 }
+        }
+        .try_into()
+        .unwrap();
+        let shadow = config.shadow.expect("should have a shadow");
+        assert_eq!(shadow.component, "checkout-v2");
+        assert_eq!(shadow.percent, 5.0);
+        assert_eq!(shadow.timeout_ms, 5000);
+        assert_eq!(shadow.max_body_bytes, 1024 * 1024);
+    }
 }
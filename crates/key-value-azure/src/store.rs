@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use azure_core::prelude::Continuation;
 use azure_data_cosmos::{
     prelude::{
         AuthorizationToken, CollectionClient, CosmosClient, CosmosClientBuilder, Operation, Query,
@@ -8,7 +9,9 @@ use azure_data_cosmos::{
 };
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use spin_factor_key_value::{log_cas_error, log_error, Cas, Error, Store, StoreManager, SwapError};
+use spin_factor_key_value::{
+    log_cas_error, log_error, Cas, Error, KeysPage, Store, StoreManager, StoreStats, SwapError,
+};
 use std::sync::{Arc, Mutex};
 
 pub struct KeyValueAzureCosmos {
@@ -95,6 +98,38 @@ impl KeyValueAzureCosmos {
 
         Ok(Self { client, app_id })
     }
+
+    /// Counts the documents in a store's partition, and sums the lengths of
+    /// their ids and values.
+    ///
+    /// This is only done when an app id is set: otherwise every document in
+    /// the container is its own partition, and the cross-partition query would
+    /// be too expensive to run routinely.
+    async fn store_stats(&self, store_name: &str) -> Result<Option<StoreStats>, Error> {
+        let Some(app_id) = &self.app_id else {
+            return Ok(None);
+        };
+        let store_id = format!("{app_id}/{store_name}");
+        let query = format!(
+            "SELECT COUNT(1) AS key_count, SUM(LENGTH(c.id) + ARRAY_LENGTH(c[\"value\"])) AS approx_bytes \
+             FROM c WHERE c.store_id='{store_id}'"
+        );
+        let mut stream = self
+            .client
+            .query_documents(Query::new(query))
+            .partition_key(&store_id)
+            .map_err(log_error)?
+            .into_stream::<Stats>();
+
+        let mut stats = StoreStats::default();
+        while let Some(resp) = stream.next().await {
+            for (partial, _) in resp.map_err(log_error)?.results {
+                stats.key_count += partial.key_count;
+                stats.approx_bytes += partial.approx_bytes.unwrap_or_default();
+            }
+        }
+        Ok(Some(stats))
+    }
 }
 
 fn cosmos_client(account: impl Into<String>, token: AuthorizationToken) -> Result<CosmosClient> {
@@ -131,6 +166,10 @@ impl StoreManager for KeyValueAzureCosmos {
             "Azure CosmosDB database: {database}, collection: {collection}"
         ))
     }
+
+    async fn stats(&self, store_name: &str) -> Option<StoreStats> {
+        self.store_stats(store_name).await.ok().flatten()
+    }
 }
 
 #[derive(Clone)]
@@ -208,6 +247,10 @@ impl Store for AzureCosmosStore {
         self.get_keys().await
     }
 
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        self.get_keys_page(cursor, limit).await
+    }
+
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
         let stmt = Query::new(self.get_in_query(keys));
         let query = self
@@ -310,6 +353,14 @@ impl Store for AzureCosmosStore {
             store_id: self.store_id.clone(),
         }))
     }
+
+    /// Cosmos DB can only apply a transactional batch within one logical
+    /// partition, which without an app ID holds just one key, and the SDK
+    /// version in use has no batch API, so multi-key transactions are not
+    /// supported.
+    fn supports_transactions(&self) -> bool {
+        false
+    }
 }
 
 struct CompareAndSwap {
@@ -451,6 +502,29 @@ impl AzureCosmosStore {
         Ok(res)
     }
 
+    /// Lists one page of keys, using Cosmos DB's continuation token as the
+    /// cursor.
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        let mut query = self
+            .client
+            .query_documents(Query::new(self.get_keys_query()))
+            .query_cross_partition(true)
+            .max_item_count(i32::try_from(limit.max(1)).unwrap_or(i32::MAX));
+        if let Some(cursor) = cursor {
+            query = query.continuation(Continuation::from(cursor));
+        }
+
+        let mut stream = query.into_stream::<Key>();
+        let Some(resp) = stream.next().await else {
+            return Ok(KeysPage::default());
+        };
+        let resp = resp.map_err(log_error)?;
+        Ok(KeysPage {
+            keys: resp.results.into_iter().map(|(key, _)| key.id).collect(),
+            cursor: resp.continuation_token.map(|token| token.as_string()),
+        })
+    }
+
     fn get_query(&self, key: &str) -> String {
         let mut query = format!("SELECT * FROM c WHERE c.id='{key}'");
         self.append_store_id(&mut query, true);
@@ -538,6 +612,14 @@ impl CosmosEntity for Counter {
     }
 }
 
+// Aggregate structure for store statistics queries
+#[derive(Deserialize, Debug)]
+struct Stats {
+    key_count: u64,
+    // Missing when the partition has no documents
+    approx_bytes: Option<u64>,
+}
+
 // Key structure for operations with generic value types
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Key {
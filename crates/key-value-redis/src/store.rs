@@ -1,7 +1,10 @@
 use anyhow::{Context, Result};
 use redis::{aio::ConnectionManager, parse_redis_url, AsyncCommands, Client, RedisError};
 use spin_core::async_trait;
-use spin_factor_key_value::{log_error, Cas, Error, Store, StoreManager, SwapError};
+use spin_factor_key_value::{
+    log_error, Cas, Error, KeysPage, Store, StoreManager, StoreStats, SwapError, TxError, TxOp,
+    TxWrite,
+};
 use std::sync::Arc;
 use tokio::sync::OnceCell;
 use url::Url;
@@ -11,6 +14,13 @@ pub struct KeyValueRedis {
     connection: OnceCell<ConnectionManager>,
 }
 
+/// The number of keys sampled to estimate the memory used by a store.
+const MEMORY_USAGE_SAMPLES: usize = 16;
+
+/// The number of times a transaction is retried when a watched key changes
+/// but every condition still holds, e.g. because it was set to the same value.
+const TRANSACTION_ATTEMPTS: usize = 8;
+
 impl KeyValueRedis {
     pub fn new(address: String) -> Result<Self> {
         let database_url = parse_redis_url(&address).context("Invalid Redis URL")?;
@@ -20,21 +30,67 @@ impl KeyValueRedis {
             connection: OnceCell::new(),
         })
     }
-}
 
-#[async_trait]
-impl StoreManager for KeyValueRedis {
-    async fn get(&self, _name: &str) -> Result<Arc<dyn Store>, Error> {
-        let connection = self
-            .connection
+    async fn connection(&self) -> Result<&ConnectionManager, Error> {
+        self.connection
             .get_or_try_init(|| async {
                 Client::open(self.database_url.clone())?
                     .get_connection_manager()
                     .await
             })
             .await
+            .map_err(log_error)
+    }
+
+    /// Counts the keys in the database with `DBSIZE`, and estimates the memory
+    /// they use from the `MEMORY USAGE` of a few random keys, multiplied up.
+    ///
+    /// The estimate includes Redis's per-key overhead, and may be well off if
+    /// value sizes vary widely.
+    async fn store_stats(&self) -> Result<StoreStats, Error> {
+        let mut connection = self.connection().await?.clone();
+        let key_count: u64 = redis::cmd("DBSIZE")
+            .query_async(&mut connection)
+            .await
             .map_err(log_error)?;
 
+        let mut sampled_keys = 0u64;
+        let mut sampled_bytes = 0u64;
+        for _ in 0..MEMORY_USAGE_SAMPLES.min(key_count as usize) {
+            let Some(key): Option<String> = redis::cmd("RANDOMKEY")
+                .query_async(&mut connection)
+                .await
+                .map_err(log_error)?
+            else {
+                break;
+            };
+            let usage: Option<u64> = redis::cmd("MEMORY")
+                .arg("USAGE")
+                .arg(&key)
+                .query_async(&mut connection)
+                .await
+                .map_err(log_error)?;
+            if let Some(usage) = usage {
+                sampled_keys += 1;
+                sampled_bytes += usage;
+            }
+        }
+        let approx_bytes = match sampled_keys {
+            0 => 0,
+            _ => sampled_bytes / sampled_keys * key_count,
+        };
+        Ok(StoreStats {
+            key_count,
+            approx_bytes,
+        })
+    }
+}
+
+#[async_trait]
+impl StoreManager for KeyValueRedis {
+    async fn get(&self, _name: &str) -> Result<Arc<dyn Store>, Error> {
+        let connection = self.connection().await?;
+
         Ok(Arc::new(RedisStore {
             connection: connection.clone(),
             database_url: self.database_url.clone(),
@@ -49,6 +105,10 @@ impl StoreManager for KeyValueRedis {
         let redis::ConnectionInfo { addr, .. } = self.database_url.as_str().parse().ok()?;
         Some(format!("Redis at {addr}"))
     }
+
+    async fn stats(&self, _store_name: &str) -> Option<StoreStats> {
+        self.store_stats().await.ok()
+    }
 }
 
 struct RedisStore {
@@ -96,6 +156,28 @@ impl Store for RedisStore {
         self.connection.clone().keys("*").await.map_err(log_error)
     }
 
+    /// Pages through the keys with `SCAN`, whose cursor is passed through as
+    /// is. `limit` is only a hint to Redis, and keys written during the scan
+    /// may be returned more than once.
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        let cursor: u64 = match cursor {
+            Some(cursor) => cursor
+                .parse()
+                .map_err(|_| Error::Other(format!("invalid key cursor {cursor:?}")))?,
+            None => 0,
+        };
+        let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("COUNT")
+            .arg(limit.max(1))
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(log_error)?;
+        // Redis returns a zero cursor once the scan is complete
+        let cursor = (next != 0).then(|| next.to_string());
+        Ok(KeysPage { keys, cursor })
+    }
+
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
         self.connection.clone().keys(keys).await.map_err(log_error)
     }
@@ -140,6 +222,65 @@ impl Store for RedisStore {
             bucket_rep,
         }))
     }
+
+    fn supports_transactions(&self) -> bool {
+        true
+    }
+
+    /// `transact` WATCHes the keys, checks their conditions, and then applies the writes with
+    /// MULTI/EXEC, which Redis aborts if any watched key changed in the meantime. Like CAS, it
+    /// uses its own connection, as WATCH is scoped to a connection.
+    async fn transact(&self, ops: Vec<TxOp>) -> Result<(), TxError> {
+        let mut connection = Client::open(self.database_url.clone())
+            .map_err(log_error)?
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(log_error)?;
+        let keys = ops.iter().map(|op| op.key.as_str()).collect::<Vec<_>>();
+
+        for _ in 0..TRANSACTION_ATTEMPTS {
+            redis::cmd("WATCH")
+                .arg(&keys)
+                .exec_async(&mut connection)
+                .await
+                .map_err(log_error)?;
+            let current: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+                .arg(&keys)
+                .query_async(&mut connection)
+                .await
+                .map_err(log_error)?;
+            if let Some(op) = ops
+                .iter()
+                .zip(&current)
+                .find_map(|(op, current)| (!op.condition.holds(current.as_deref())).then_some(op))
+            {
+                // The watch is discarded with the connection
+                return Err(TxError::ConditionFailed(op.key.clone()));
+            }
+
+            let mut transaction = redis::pipe();
+            transaction.atomic();
+            for op in &ops {
+                match &op.write {
+                    TxWrite::Set(value) => transaction.set(&op.key, value).ignore(),
+                    TxWrite::Delete => transaction.del(&op.key).ignore(),
+                };
+            }
+            // EXEC returns nil if it was aborted by a change to a watched key
+            let applied: Option<()> = transaction
+                .query_async(&mut connection)
+                .await
+                .map_err(log_error)?;
+            if applied.is_some() {
+                return Ok(());
+            }
+            // Re-check the conditions against the conflicting write
+        }
+
+        Err(TxError::Store(Error::Other(format!(
+            "transaction aborted by concurrent writes {TRANSACTION_ATTEMPTS} times"
+        ))))
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,110 @@
+//! Tests against a real Redis server.
+//!
+//! These are ignored by default. To run them, start a server, e.g. with
+//! `docker run -d -p 6379:6379 redis:7`, then run
+//! `cargo test -p spin-key-value-redis -- --ignored`. Set `REDIS_URL` if the
+//! server isn't at `redis://localhost:6379`.
+
+use std::sync::Arc;
+
+use spin_factor_key_value::{Store, StoreManager, TxCondition, TxError, TxOp, TxWrite};
+use spin_key_value_redis::KeyValueRedis;
+
+async fn store() -> anyhow::Result<Arc<dyn Store>> {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+    Ok(KeyValueRedis::new(url)?.get("default").await?)
+}
+
+/// Keys unique to this run, so that earlier runs don't interfere.
+fn test_keys(test: &str) -> anyhow::Result<(String, String)> {
+    let run = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_nanos();
+    Ok((format!("{test}-{run}-a"), format!("{test}-{run}-b")))
+}
+
+fn transfer(from: &str, to: &str, values: (i64, i64)) -> Vec<TxOp> {
+    vec![
+        TxOp {
+            key: from.to_owned(),
+            condition: TxCondition::Equals(values.0.to_string().into_bytes()),
+            write: TxWrite::Set((values.0 - 1).to_string().into_bytes()),
+        },
+        TxOp {
+            key: to.to_owned(),
+            condition: TxCondition::Equals(values.1.to_string().into_bytes()),
+            write: TxWrite::Set((values.1 + 1).to_string().into_bytes()),
+        },
+    ]
+}
+
+async fn get_number(store: &dyn Store, key: &str) -> anyhow::Result<i64> {
+    let value = store.get(key).await?.expect("key should exist");
+    Ok(String::from_utf8(value)?.parse()?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a local Redis server"]
+async fn failed_transaction_applies_nothing() -> anyhow::Result<()> {
+    let store = store().await?;
+    assert!(store.supports_transactions());
+    let (a, b) = test_keys("failed")?;
+    store.set(&a, b"10").await?;
+    store.set(&b, b"0").await?;
+
+    // A conflicting write lands between reading and transacting
+    store.set(&b, b"5").await?;
+    let err = store.transact(transfer(&a, &b, (10, 0))).await.unwrap_err();
+    assert!(matches!(err, TxError::ConditionFailed(key) if key == b));
+    assert_eq!(get_number(store.as_ref(), &a).await?, 10);
+
+    store
+        .transact(vec![TxOp {
+            key: a.clone(),
+            condition: TxCondition::Unconditional,
+            write: TxWrite::Delete,
+        }])
+        .await?;
+    assert_eq!(store.get(&a).await?, None);
+    store.delete(&b).await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a local Redis server"]
+async fn concurrent_transactions_are_atomic() -> anyhow::Result<()> {
+    let store = store().await?;
+    let (a, b) = test_keys("concurrent")?;
+    store.set(&a, b"100").await?;
+    store.set(&b, b"100").await?;
+
+    let tasks = [(a.clone(), b.clone()), (b.clone(), a.clone())].map(|(from, to)| {
+        let store = store.clone();
+        tokio::spawn(async move {
+            for _ in 0..50 {
+                loop {
+                    let values = (
+                        get_number(store.as_ref(), &from).await?,
+                        get_number(store.as_ref(), &to).await?,
+                    );
+                    match store.transact(transfer(&from, &to, values)).await {
+                        Ok(()) => break,
+                        // Another transfer got in first: retry with fresh values
+                        Err(TxError::ConditionFailed(_)) => continue,
+                        Err(err) => anyhow::bail!("unexpected error: {err}"),
+                    }
+                }
+            }
+            anyhow::Ok(())
+        })
+    });
+    for task in tasks {
+        task.await??;
+    }
+
+    // Had any transfer been lost or half-applied, the balances would differ
+    assert_eq!(get_number(store.as_ref(), &a).await?, 100);
+    assert_eq!(get_number(store.as_ref(), &b).await?, 100);
+    store.delete_many(vec![a, b]).await?;
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use anyhow::Result;
 use rusqlite::{named_params, Connection};
 use spin_core::async_trait;
-use spin_factor_key_value::{log_cas_error, log_error, Cas, Error, Store, StoreManager, SwapError};
+use spin_factor_key_value::{
+    log_cas_error, log_error, Cas, Error, KeysPage, Store, StoreManager, StoreStats, SwapError,
+    TxError, TxOp, TxWrite,
+};
 use std::rc::Rc;
 use std::{
     path::PathBuf,
@@ -59,20 +62,42 @@ impl KeyValueSqlite {
 
         Ok(Arc::new(Mutex::new(connection)))
     }
+
+    fn connection(&self) -> Result<&Arc<Mutex<Connection>>, Error> {
+        if let Some(c) = self.connection.get() {
+            return Ok(c);
+        }
+        // Only create the connection if we failed to get it.
+        // We might do duplicate work here if there's a race, but that's fine.
+        let new = self.create_connection()?;
+        Ok(self.connection.get_or_init(|| new))
+    }
+
+    /// Counts the store's keys, and the bytes in its keys and values, which is
+    /// cheap as the table is indexed by store.
+    fn store_stats(&self, store_name: &str) -> Result<StoreStats, Error> {
+        let (key_count, approx_bytes): (i64, i64) = self
+            .connection()?
+            .lock()
+            .unwrap()
+            .prepare_cached(
+                "SELECT COUNT(*), COALESCE(SUM(LENGTH(CAST(key AS BLOB)) + LENGTH(value)), 0)
+                 FROM spin_key_value WHERE store=$1",
+            )
+            .map_err(log_error)?
+            .query_row([store_name], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(log_error)?;
+        Ok(StoreStats {
+            key_count: key_count.try_into().unwrap_or_default(),
+            approx_bytes: approx_bytes.try_into().unwrap_or_default(),
+        })
+    }
 }
 
 #[async_trait]
 impl StoreManager for KeyValueSqlite {
     async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
-        let connection = task::block_in_place(|| {
-            if let Some(c) = self.connection.get() {
-                return Ok(c);
-            }
-            // Only create the connection if we failed to get it.
-            // We might do duplicate work here if there's a race, but that's fine.
-            let new = self.create_connection()?;
-            Ok(self.connection.get_or_init(|| new))
-        })?;
+        let connection = task::block_in_place(|| self.connection())?;
 
         Ok(Arc::new(SqliteStore {
             name: name.to_owned(),
@@ -90,6 +115,10 @@ impl StoreManager for KeyValueSqlite {
             DatabaseLocation::Path(path) => format!("\"{}\"", path.display()),
         })
     }
+
+    async fn stats(&self, store_name: &str) -> Option<StoreStats> {
+        task::block_in_place(|| self.store_stats(store_name)).ok()
+    }
 }
 
 struct SqliteStore {
@@ -161,6 +190,40 @@ impl Store for SqliteStore {
         })
     }
 
+    async fn get_keys_page(&self, cursor: Option<String>, limit: usize) -> Result<KeysPage, Error> {
+        let limit = limit.max(1);
+        let mut keys: Vec<String> = task::block_in_place(|| {
+            self.connection
+                .lock()
+                .unwrap()
+                .prepare_cached(
+                    "SELECT key FROM spin_key_value
+                     WHERE store=:name AND (:cursor IS NULL OR key > :cursor)
+                     ORDER BY key LIMIT :limit",
+                )
+                .map_err(log_error)?
+                // One more key than requested tells whether there's another page
+                .query_map(
+                    named_params! {
+                        ":name": &self.name,
+                        ":cursor": cursor,
+                        ":limit": i64::try_from(limit).unwrap_or(i64::MAX).saturating_add(1),
+                    },
+                    |row| row.get(0),
+                )
+                .map_err(log_error)?
+                .map(|r| r.map_err(log_error))
+                .collect::<Result<_, _>>()
+        })?;
+        let cursor = if keys.len() > limit {
+            keys.truncate(limit);
+            keys.last().cloned()
+        } else {
+            None
+        };
+        Ok(KeysPage { keys, cursor })
+    }
+
     async fn get_many(&self, keys: Vec<String>) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
         task::block_in_place(|| {
             let sql_value_keys: Vec<rusqlite::types::Value> =
@@ -273,6 +336,57 @@ impl Store for SqliteStore {
             bucket_rep,
         }))
     }
+
+    fn supports_transactions(&self) -> bool {
+        true
+    }
+
+    async fn transact(&self, ops: Vec<TxOp>) -> Result<(), TxError> {
+        task::block_in_place(|| {
+            let mut binding = self.connection.lock().unwrap();
+            // An immediate transaction takes the write lock up front, so other
+            // connections to the database file can't write between our reads
+            // and writes.
+            let tx = binding
+                .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+                .map_err(log_error)?;
+
+            for op in &ops {
+                let current: Option<Vec<u8>> = tx
+                    .prepare_cached("SELECT value FROM spin_key_value WHERE store=$1 AND key=$2")
+                    .map_err(log_error)?
+                    .query_map([&self.name, &op.key], |row| row.get(0))
+                    .map_err(log_error)?
+                    .next()
+                    .transpose()
+                    .map_err(log_error)?;
+                if !op.condition.holds(current.as_deref()) {
+                    // Dropping the transaction rolls it back
+                    return Err(TxError::ConditionFailed(op.key.clone()));
+                }
+            }
+
+            for op in ops {
+                match op.write {
+                    TxWrite::Set(value) => tx
+                        .prepare_cached(
+                            "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
+                             ON CONFLICT(store, key) DO UPDATE SET value=$3",
+                        )
+                        .map_err(log_error)?
+                        .execute(rusqlite::params![&self.name, op.key, value]),
+                    TxWrite::Delete => tx
+                        .prepare_cached("DELETE FROM spin_key_value WHERE store=$1 AND key=$2")
+                        .map_err(log_error)?
+                        .execute([&self.name, &op.key]),
+                }
+                .map_err(log_error)?;
+            }
+
+            tx.commit().map_err(log_error)?;
+            Ok(())
+        })
+    }
 }
 
 struct CompareAndSwap {
@@ -359,7 +473,7 @@ impl Cas for CompareAndSwap {
 mod test {
     use super::*;
     use spin_core::wasmtime::component::Resource;
-    use spin_factor_key_value::{DelegatingStoreManager, KeyValueDispatch};
+    use spin_factor_key_value::{DelegatingStoreManager, KeyValueDispatch, TxCondition};
     use spin_world::v2::key_value::HostStore;
     use spin_world::wasi::keyvalue::atomics::HostCas as wasi_cas_host;
     use spin_world::wasi::keyvalue::atomics::{CasError, Host};
@@ -555,6 +669,181 @@ mod test {
         }
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn cas_is_native_in_memory_and_on_disk() -> Result<()> {
+        use spin_world::spin::key_value::capabilities::{CasSupport, Host as _};
+
+        let dir = tempfile::tempdir()?;
+        for location in [
+            DatabaseLocation::InMemory,
+            DatabaseLocation::Path(dir.path().join("kv.db")),
+        ] {
+            let mut kv = KeyValueDispatch::new(
+                ["default".to_owned()].into_iter().collect(),
+                Arc::new(DelegatingStoreManager::new([(
+                    "default".to_owned(),
+                    Arc::new(KeyValueSqlite::new(location)) as _,
+                )])),
+            );
+            let rep = kv.open("default".to_owned()).await??.rep();
+
+            let capabilities = kv.capabilities(Resource::new_own(rep)).await?;
+            assert!(matches!(capabilities.cas, CasSupport::Native));
+            assert!(capabilities.atomic_increment);
+
+            cas_failed(&mut kv, rep).await?;
+            cas_succeeds(&mut kv, rep).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn stats_are_per_store() -> Result<()> {
+        let manager = KeyValueSqlite::new(DatabaseLocation::InMemory);
+        let default = manager.get("default").await?;
+        default.set("a", b"12").await?;
+        default.set("bc", b"3456").await?;
+        default.set("a", b"1").await?;
+        manager.get("other").await?.set("z", b"9").await?;
+
+        assert_eq!(
+            manager.stats("default").await,
+            Some(StoreStats {
+                key_count: 2,
+                approx_bytes: 8,
+            })
+        );
+        assert_eq!(
+            manager.stats("empty").await,
+            Some(StoreStats {
+                key_count: 0,
+                approx_bytes: 0,
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn keys_are_paged_in_order() -> Result<()> {
+        let manager = KeyValueSqlite::new(DatabaseLocation::InMemory);
+        let store = manager.get("default").await?;
+        for key in ["c", "a", "e", "b", "d"] {
+            store.set(key, b"").await?;
+        }
+        manager.get("other").await?.set("aa", b"").await?;
+
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = store.get_keys_page(cursor, 2).await?;
+            pages.push(page.keys);
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(pages, [vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+
+        // A page which ends exactly at the last key has no cursor
+        let page = store.get_keys_page(Some("b".into()), 3).await?;
+        assert_eq!(page.keys, ["c", "d", "e"]);
+        assert_eq!(page.cursor, None);
+        Ok(())
+    }
+
+    fn transfer(from: &str, to: &str, values: (i64, i64)) -> Vec<TxOp> {
+        vec![
+            TxOp {
+                key: from.to_owned(),
+                condition: TxCondition::Equals(values.0.to_string().into_bytes()),
+                write: TxWrite::Set((values.0 - 1).to_string().into_bytes()),
+            },
+            TxOp {
+                key: to.to_owned(),
+                condition: TxCondition::Equals(values.1.to_string().into_bytes()),
+                write: TxWrite::Set((values.1 + 1).to_string().into_bytes()),
+            },
+        ]
+    }
+
+    async fn get_number(store: &dyn Store, key: &str) -> Result<i64> {
+        let value = store.get(key).await?.expect("key should exist");
+        Ok(String::from_utf8(value)?.parse()?)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn failed_transaction_applies_nothing() -> Result<()> {
+        let manager = KeyValueSqlite::new(DatabaseLocation::InMemory);
+        let store = manager.get("default").await?;
+        assert!(store.supports_transactions());
+        store.set("a", b"10").await?;
+        store.set("b", b"0").await?;
+
+        // A conflicting write lands between reading and transacting
+        store.set("b", b"5").await?;
+        let err = store
+            .transact(transfer("a", "b", (10, 0)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TxError::ConditionFailed(key) if key == "b"));
+        assert_eq!(get_number(store.as_ref(), "a").await?, 10);
+
+        store
+            .transact(vec![
+                TxOp {
+                    key: "a".to_owned(),
+                    condition: TxCondition::Unconditional,
+                    write: TxWrite::Delete,
+                },
+                TxOp {
+                    key: "c".to_owned(),
+                    condition: TxCondition::Absent,
+                    write: TxWrite::Set(b"1".to_vec()),
+                },
+            ])
+            .await?;
+        assert_eq!(store.get("a").await?, None);
+        assert_eq!(store.get("c").await?.as_deref(), Some(b"1" as &[_]));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_transactions_are_atomic() -> Result<()> {
+        let manager = KeyValueSqlite::new(DatabaseLocation::InMemory);
+        let store = manager.get("default").await?;
+        store.set("a", b"100").await?;
+        store.set("b", b"100").await?;
+
+        let tasks = [("a", "b"), ("b", "a")].map(|(from, to)| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                for _ in 0..50 {
+                    loop {
+                        let values = (
+                            get_number(store.as_ref(), from).await?,
+                            get_number(store.as_ref(), to).await?,
+                        );
+                        match store.transact(transfer(from, to, values)).await {
+                            Ok(()) => break,
+                            // Another transfer got in first: retry with fresh values
+                            Err(TxError::ConditionFailed(_)) => continue,
+                            Err(err) => anyhow::bail!("unexpected error: {err}"),
+                        }
+                    }
+                }
+                anyhow::Ok(())
+            })
+        });
+        for task in tasks {
+            task.await??;
+        }
+
+        // Had any transfer been lost or half-applied, the balances would differ
+        assert_eq!(get_number(store.as_ref(), "a").await?, 100);
+        assert_eq!(get_number(store.as_ref(), "b").await?, 100);
+        Ok(())
+    }
+
     async fn kv_incr(kv: &mut KeyValueDispatch, rep: u32, delta: i64) -> i64 {
         let res = kv
             .increment(Resource::new_own(rep), "counter".to_owned(), delta)
@@ -1,5 +1,7 @@
 mod bert;
 mod llama;
+mod llava;
+mod tools;
 
 use anyhow::Context;
 use bert::{BertModel, Config};
@@ -7,6 +9,7 @@ use candle::{safetensors::load_buffer, DType};
 use candle_nn::VarBuilder;
 use spin_common::ui::quoted_path;
 use spin_core::async_trait;
+use spin_world::spin::llm::llm as llm3;
 use spin_world::v2::llm::{self as wasi_llm};
 use std::{
     collections::{hash_map::Entry, HashMap},
@@ -29,6 +32,7 @@ pub struct LocalLlmEngine {
 #[derive(Debug)]
 enum InferencingModelArch {
     Llama,
+    Llava,
 }
 
 impl FromStr for InferencingModelArch {
@@ -37,6 +41,7 @@ impl FromStr for InferencingModelArch {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "llama" => Ok(InferencingModelArch::Llama),
+            "llava" => Ok(InferencingModelArch::Llava),
             _ => Err(()),
         }
     }
@@ -45,13 +50,31 @@ impl FromStr for InferencingModelArch {
 /// A model that is prepared and cached after loading.
 ///
 /// This trait does not specify anything about if the results are cached.
+/// Implementations which support prompt caching reuse model state between
+/// calls only when `params.cache_prompt_prefix` is set.
 #[async_trait]
 trait InferencingModel: Send + Sync {
     async fn infer(
         &self,
         prompt: String,
-        params: wasi_llm::InferencingParams,
-    ) -> anyhow::Result<wasi_llm::InferencingResult>;
+        params: llm3::InferencingParams,
+    ) -> anyhow::Result<llm3::InferencingResult>;
+
+    /// Whether the model accepts images with its prompt.
+    fn supports_images(&self) -> bool {
+        false
+    }
+
+    /// Performs inferencing with the images placed in the prompt.
+    async fn infer_with_images(
+        &self,
+        prompt: String,
+        images: Vec<image::DynamicImage>,
+        params: llm3::InferencingParams,
+    ) -> anyhow::Result<llm3::InferencingResult> {
+        let _ = (prompt, images, params);
+        anyhow::bail!("the model does not accept images")
+    }
 }
 
 impl LocalLlmEngine {
@@ -64,11 +87,80 @@ impl LocalLlmEngine {
         let model = self.inferencing_model(model).await?;
 
         model
-            .infer(prompt, params)
+            .infer(prompt, params.into())
+            .await
+            .map(Into::into)
+            .map_err(|e| wasi_llm::Error::RuntimeError(e.to_string()))
+    }
+
+    /// Performs inferencing, reusing the model state from the previous prompt
+    /// if it is a prefix of this one.
+    pub async fn infer_with_prompt_cache(
+        &mut self,
+        model: wasi_llm::InferencingModel,
+        prompt: String,
+        params: wasi_llm::InferencingParams,
+    ) -> Result<llm3::InferencingResult, wasi_llm::Error> {
+        let model = self.inferencing_model(model).await?;
+
+        model
+            .infer(
+                prompt,
+                llm3::InferencingParams {
+                    cache_prompt_prefix: true,
+                    ..params.into()
+                },
+            )
             .await
             .map_err(|e| wasi_llm::Error::RuntimeError(e.to_string()))
     }
 
+    /// Returns whether the given inferencing model accepts images, loading it
+    /// if necessary.
+    pub async fn supports_images(&mut self, model: &str) -> Result<bool, wasi_llm::Error> {
+        let model = self.inferencing_model(model.to_owned()).await?;
+        Ok(model.supports_images())
+    }
+
+    /// Performs inferencing with the images placed in the prompt.
+    pub async fn infer_with_image(
+        &mut self,
+        model: wasi_llm::InferencingModel,
+        prompt: String,
+        images: Vec<llm3::ImageData>,
+        params: wasi_llm::InferencingParams,
+    ) -> Result<llm3::InferencingResult, wasi_llm::Error> {
+        let model = self.inferencing_model(model).await?;
+        if !model.supports_images() {
+            return Err(wasi_llm::Error::ModelNotSupported);
+        }
+        let images = llava::decode_images(&prompt, &images)?;
+
+        model
+            .infer_with_images(prompt, images, params.into())
+            .await
+            .map_err(|e| wasi_llm::Error::RuntimeError(e.to_string()))
+    }
+
+    /// Performs inferencing over a conversation, describing the tools to the
+    /// model in its prompt and recognizing calls to them in its answer.
+    pub async fn infer_with_tools(
+        &mut self,
+        model: wasi_llm::InferencingModel,
+        messages: Vec<llm3::Message>,
+        tools: Vec<llm3::ToolDef>,
+        params: wasi_llm::InferencingParams,
+    ) -> Result<llm3::ToolCallResult, wasi_llm::Error> {
+        let prompt = tools::render_prompt(&messages, &tools)?;
+        let model = self.inferencing_model(model).await?;
+
+        let result = model
+            .infer(prompt, params.into())
+            .await
+            .map_err(|e| wasi_llm::Error::RuntimeError(e.to_string()))?;
+        Ok(tools::parse_result(result, &tools))
+    }
+
     pub async fn generate_embeddings(
         &mut self,
         model: wasi_llm::EmbeddingModel,
@@ -79,6 +171,37 @@ impl LocalLlmEngine {
             wasi_llm::Error::RuntimeError(format!("Error occurred generating embeddings: {e}"))
         })
     }
+
+    /// Counts the tokens the given embeddings model's tokenizer splits the
+    /// text into, including special tokens but without truncation or padding.
+    pub async fn tokenize(
+        &mut self,
+        model: wasi_llm::EmbeddingModel,
+        text: String,
+    ) -> Result<u32, wasi_llm::Error> {
+        let model = self.embeddings_model(model).await?;
+        let mut tokenizer = model.0.clone();
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| {
+                wasi_llm::Error::RuntimeError(format!("Error configuring tokenizer: {e}"))
+            })?
+            .with_padding(None);
+        let encoding = tokenizer.encode(text, true).map_err(|e| {
+            wasi_llm::Error::RuntimeError(format!("Error occurred tokenizing text: {e}"))
+        })?;
+        Ok(encoding.len() as u32)
+    }
+
+    /// Loads the given model into the model cache without running inference.
+    pub async fn warm_up(&mut self, model: &str) -> Result<(), wasi_llm::Error> {
+        if model == MODEL_ALL_MINILM_L6_V2 {
+            self.embeddings_model(model.to_owned()).await?;
+        } else {
+            self.inferencing_model(model.to_owned()).await?;
+        }
+        Ok(())
+    }
 }
 
 impl LocalLlmEngine {
@@ -153,6 +276,12 @@ impl LocalLlmEngine {
                         llama::LlamaModels::new(&model_dir)
                             .await
                             .map_err(|e| wasi_llm::Error::RuntimeError(e.to_string()))?,
+                    )
+                        as Arc<dyn InferencingModel>,
+                    InferencingModelArch::Llava => Arc::new(
+                        llava::LlavaModel::new(&model_dir)
+                            .await
+                            .map_err(|e| wasi_llm::Error::RuntimeError(e.to_string()))?,
                     ),
                 };
 
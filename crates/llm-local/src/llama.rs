@@ -8,8 +8,13 @@ use candle_transformers::{
 };
 use rand::{RngCore, SeedableRng};
 use spin_core::async_trait;
-use spin_world::v2::llm::{self as wasi_llm, InferencingUsage};
-use std::{collections::HashMap, fs, path::Path, sync::Arc};
+use spin_world::spin::llm::llm::{self as llm3, InferencingUsage};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
 use tokenizers::Tokenizer;
 
 const TOKENIZER_FILENAME: &str = "tokenizer.json";
@@ -34,6 +39,15 @@ pub(crate) struct LlamaModels {
     cache: Cache,
     tokenizer: Tokenizer,
     device: Device,
+    /// The model state after the most recent prompt run with prompt caching.
+    prompt_cache: Arc<Mutex<Option<PromptCache>>>,
+}
+
+/// The model state (KV cache) after processing a prompt prefix.
+struct PromptCache {
+    /// The tokens the state was computed from.
+    tokens: Vec<u32>,
+    cache: Cache,
 }
 
 impl LlamaModels {
@@ -71,8 +85,52 @@ impl LlamaModels {
             cache,
             tokenizer,
             device,
+            prompt_cache: Default::default(),
         })
     }
+
+    /// Runs the model over all but the last of the prompt tokens, so that
+    /// generation can start from the last one.
+    ///
+    /// If the state cached from a previous prompt covers a prefix of these
+    /// tokens it is reused and only the remaining tokens are processed. The
+    /// resulting state is then cached for later prompts.
+    ///
+    /// Returns the model state, the position of the next token to process and
+    /// the number of tokens whose state was reused.
+    fn prefill(&self, tokens: &[u32]) -> Result<(Cache, usize, usize)> {
+        let prefix = &tokens[..tokens.len().saturating_sub(1)];
+        let cached = self
+            .prompt_cache
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|cached| prefix.starts_with(&cached.tokens))
+            .map(|cached| (cached.cache.clone(), cached.tokens.len()));
+        let (mut cache, reused) = cached.unwrap_or_else(|| (self.cache.clone(), 0));
+
+        if reused == 0 {
+            if !prefix.is_empty() {
+                let input = Tensor::new(prefix, &self.device)?.unsqueeze(0)?;
+                self.model.forward(&input, 0, &mut cache)?;
+            }
+        } else {
+            // The model only masks multi-token inputs correctly when there is
+            // no prior state, so process the rest of the prefix token by token.
+            for (index_pos, token) in prefix.iter().enumerate().skip(reused) {
+                let input = Tensor::new(&[*token], &self.device)?.unsqueeze(0)?;
+                self.model.forward(&input, index_pos, &mut cache)?;
+            }
+        }
+
+        if !prefix.is_empty() {
+            *self.prompt_cache.lock().unwrap() = Some(PromptCache {
+                tokens: prefix.to_vec(),
+                cache: cache.clone(),
+            });
+        }
+        Ok((cache, prefix.len(), reused))
+    }
 }
 
 #[async_trait]
@@ -80,12 +138,11 @@ impl InferencingModel for LlamaModels {
     async fn infer(
         &self,
         prompt: String,
-        params: wasi_llm::InferencingParams,
-    ) -> anyhow::Result<wasi_llm::InferencingResult> {
+        params: llm3::InferencingParams,
+    ) -> anyhow::Result<llm3::InferencingResult> {
         let model = Arc::clone(&self.model);
         let config = &self.config;
         let tokenizer = self.tokenizer.clone();
-        let mut cache = self.cache.clone();
         // Try to retrieve the End of Sentence (EOS) token ID from config or
         // default to a single EOS token. EOS token is used to determine when to stop.
         let eos_token_id = config.clone().eos_token_id.or_else(|| {
@@ -99,6 +156,13 @@ impl InferencingModel for LlamaModels {
             .map_err(|e| anyhow!(e.to_string()))?
             .get_ids()
             .to_vec();
+        let prompt_token_count = tokens.len();
+        let (mut cache, mut index_pos, cache_hit_tokens) =
+            if params.cache_prompt_prefix && self.cache.use_kv_cache {
+                self.prefill(&tokens)?
+            } else {
+                (self.cache.clone(), 0, 0)
+            };
         let mut rng = rand::rngs::StdRng::from_os_rng();
 
         let mut logits_processor = {
@@ -115,15 +179,15 @@ impl InferencingModel for LlamaModels {
             LogitsProcessor::from_sampling(rng.next_u64(), sampling)
         };
 
-        let mut index_pos = 0;
         let mut tokens_generated = 0;
 
         for index in 0..params.max_tokens {
-            let (context_size, context_index) = if self.cache.use_kv_cache && index > 0 {
-                (1, index_pos)
-            } else {
-                (tokens.len(), 0)
-            };
+            let (context_size, context_index) =
+                if self.cache.use_kv_cache && (index > 0 || index_pos > 0) {
+                    (1, index_pos)
+                } else {
+                    (tokens.len(), 0)
+                };
             let ctxt = &tokens[tokens.len().saturating_sub(context_size)..];
             let input = Tensor::new(ctxt, &self.device)?.unsqueeze(0)?;
             let logits = model.forward(&input, context_index, &mut cache)?;
@@ -164,11 +228,13 @@ impl InferencingModel for LlamaModels {
             .decode(&tokens, true)
             .map_err(|e| anyhow!(e.to_string()))?;
 
-        Ok(wasi_llm::InferencingResult {
+        Ok(llm3::InferencingResult {
             text: output_text,
             usage: InferencingUsage {
                 prompt_token_count: tokens.len() as u32,
                 generated_token_count: tokens_generated,
+                cache_hit_tokens: cache_hit_tokens as u32,
+                cache_miss_tokens: (prompt_token_count - cache_hit_tokens) as u32,
             },
         })
     }
@@ -176,7 +242,10 @@ impl InferencingModel for LlamaModels {
 
 ///  Loads a list of SafeTensors file paths from a given model directory and
 ///  path to the model index JSON file relative to the model folder.
-fn load_safetensors(model_dir: &Path, json_file: &str) -> Result<Vec<std::path::PathBuf>> {
+pub(crate) fn load_safetensors(
+    model_dir: &Path,
+    json_file: &str,
+) -> Result<Vec<std::path::PathBuf>> {
     let json_file = model_dir.join(json_file);
     let json_file = std::fs::File::open(&json_file)
         .with_context(|| format!("Could not read model index file: {json_file:?}"))?;
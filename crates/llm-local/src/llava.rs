@@ -0,0 +1,277 @@
+use crate::llama::{auto_device, load_safetensors};
+use crate::InferencingModel;
+use anyhow::{anyhow, bail, Result};
+use candle::{safetensors::load_buffer, DType, Device, IndexOp, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::{
+    generation::{LogitsProcessor, Sampling},
+    models::{
+        llama::Cache,
+        llava::{config::LLaVAConfig, LLaVA},
+    },
+};
+use image::{imageops::FilterType, DynamicImage, Rgb, RgbImage};
+use rand::{RngCore, SeedableRng};
+use spin_core::async_trait;
+use spin_world::spin::llm::llm::{self as llm3, InferencingUsage};
+use spin_world::v2::llm::{self as wasi_llm};
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+use tokenizers::Tokenizer;
+
+const TOKENIZER_FILENAME: &str = "tokenizer.json";
+const CONFIG_FILENAME: &str = "config.json";
+const MODEL_SAFETENSORS_INDEX_FILE: &str = "model.safetensors.index.json";
+
+/// Marks where an image goes in a prompt.
+const IMAGE_PLACEHOLDER: &str = "<image>";
+
+/// The input size and normalization of the CLIP ViT-L/14-336 vision tower
+/// used by LLaVA 1.5 models.
+const IMAGE_SIZE: u32 = 336;
+const IMAGE_MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+const IMAGE_STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+
+/// Decodes images passed to `infer-with-image`, checking that the prompt has
+/// a placeholder for each of them, or none at all.
+pub(crate) fn decode_images(
+    prompt: &str,
+    images: &[llm3::ImageData],
+) -> Result<Vec<DynamicImage>, wasi_llm::Error> {
+    let placeholders = prompt.matches(IMAGE_PLACEHOLDER).count();
+    if placeholders != 0 && placeholders != images.len() {
+        return Err(wasi_llm::Error::InvalidInput(format!(
+            "the prompt has {placeholders} {IMAGE_PLACEHOLDER} placeholders but {} images were given",
+            images.len()
+        )));
+    }
+    images
+        .iter()
+        .enumerate()
+        .map(|(index, image)| {
+            let format = match image.format {
+                llm3::ImageFormat::Png => image::ImageFormat::Png,
+                llm3::ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+                llm3::ImageFormat::Webp => image::ImageFormat::WebP,
+            };
+            image::load_from_memory_with_format(&image.bytes, format).map_err(|e| {
+                wasi_llm::Error::InvalidInput(format!("could not decode image {index}: {e}"))
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub(crate) struct LlavaModel {
+    model: Arc<LLaVA>,
+    config: LLaVAConfig,
+    cache: Cache,
+    tokenizer: Tokenizer,
+    device: Device,
+    dtype: DType,
+}
+
+impl LlavaModel {
+    pub async fn new(model_dir: &Path) -> Result<Self> {
+        let tokenizer_path = model_dir.join(TOKENIZER_FILENAME);
+        let config_path = model_dir.join(CONFIG_FILENAME);
+
+        let dtype = DType::F16;
+        let device = auto_device()?;
+
+        let tokenizer = Tokenizer::from_file(tokenizer_path).map_err(|e| anyhow!(e.to_string()))?;
+        let config: LLaVAConfig = serde_json::from_slice(&fs::read(config_path)?)?;
+        if config.image_aspect_ratio != "pad" {
+            bail!(
+                "LLaVA models with image aspect ratio {:?} are not supported; only \"pad\" is",
+                config.image_aspect_ratio
+            );
+        }
+        let cache = Cache::new(true, dtype, &config.to_llama_config(), &device)?;
+
+        let safetensor_files = load_safetensors(model_dir, MODEL_SAFETENSORS_INDEX_FILE)?;
+
+        let mut tensor_map: HashMap<String, Tensor> = HashMap::new();
+
+        for file in safetensor_files {
+            let data = fs::read(file)?;
+            let tensors = load_buffer(&data, &device)?;
+            for (k, v) in tensors {
+                tensor_map.insert(k, v);
+            }
+        }
+        let vb = VarBuilder::from_tensors(tensor_map, dtype, &device);
+        let model = LLaVA::load(vb, &config, None)?;
+
+        Ok(Self {
+            model: Arc::new(model),
+            config,
+            cache,
+            tokenizer,
+            device,
+            dtype,
+        })
+    }
+
+    /// Pads the image to a square with the mean color, as LLaVA was trained
+    /// on, and converts it to a normalized `(1, 3, IMAGE_SIZE, IMAGE_SIZE)`
+    /// tensor.
+    fn image_tensor(&self, image: &DynamicImage) -> Result<Tensor> {
+        let image = image.to_rgb8();
+        let (width, height) = image.dimensions();
+        let side = width.max(height);
+        let mean = Rgb(IMAGE_MEAN.map(|c| (c * 255.) as u8));
+        let mut square = RgbImage::from_pixel(side, side, mean);
+        image::imageops::overlay(
+            &mut square,
+            &image,
+            ((side - width) / 2).into(),
+            ((side - height) / 2).into(),
+        );
+        let resized =
+            image::imageops::resize(&square, IMAGE_SIZE, IMAGE_SIZE, FilterType::CatmullRom);
+
+        let size = IMAGE_SIZE as usize;
+        let pixels = Tensor::from_vec(resized.into_raw(), (size, size, 3), &Device::Cpu)?
+            .permute((2, 0, 1))?
+            .to_dtype(DType::F32)?;
+        let mean = Tensor::new(&IMAGE_MEAN, &Device::Cpu)?.reshape((3, 1, 1))?;
+        let std = Tensor::new(&IMAGE_STD, &Device::Cpu)?.reshape((3, 1, 1))?;
+        let pixels = (pixels / 255.)?.broadcast_sub(&mean)?.broadcast_div(&std)?;
+        Ok(pixels
+            .unsqueeze(0)?
+            .to_dtype(self.dtype)?
+            .to_device(&self.device)?)
+    }
+
+    /// Tokenizes the prompt, with the model's image token in place of each
+    /// image placeholder.
+    ///
+    /// Without placeholders, one image token per image is put before the
+    /// prompt.
+    fn tokenize(&self, prompt: &str, image_count: usize) -> Result<Vec<i64>> {
+        let prompt = if image_count > 0 && !prompt.contains(IMAGE_PLACEHOLDER) {
+            format!("{}\n{prompt}", IMAGE_PLACEHOLDER.repeat(image_count))
+        } else {
+            prompt.to_owned()
+        };
+        let mut tokens = vec![];
+        for (index, chunk) in prompt.split(IMAGE_PLACEHOLDER).enumerate() {
+            if index > 0 {
+                tokens.push(self.config.image_token_index as i64);
+            }
+            // Only the start of the prompt gets special tokens
+            let encoding = self
+                .tokenizer
+                .encode(chunk, index == 0)
+                .map_err(|e| anyhow!(e.to_string()))?;
+            tokens.extend(encoding.get_ids().iter().map(|&id| id as i64));
+        }
+        Ok(tokens)
+    }
+}
+
+#[async_trait]
+impl InferencingModel for LlavaModel {
+    async fn infer(
+        &self,
+        prompt: String,
+        params: llm3::InferencingParams,
+    ) -> anyhow::Result<llm3::InferencingResult> {
+        self.infer_with_images(prompt, vec![], params).await
+    }
+
+    fn supports_images(&self) -> bool {
+        true
+    }
+
+    async fn infer_with_images(
+        &self,
+        prompt: String,
+        images: Vec<DynamicImage>,
+        params: llm3::InferencingParams,
+    ) -> anyhow::Result<llm3::InferencingResult> {
+        let model = Arc::clone(&self.model);
+        let tokens = self.tokenize(&prompt, images.len())?;
+        let prompt_token_count = tokens.len();
+        let image_sizes = images
+            .iter()
+            .map(|image| (image.width(), image.height()))
+            .collect::<Vec<_>>();
+        let images = images
+            .iter()
+            .map(|image| self.image_tensor(image))
+            .collect::<Result<Vec<_>>>()?;
+
+        let input_ids = Tensor::new(tokens, &self.device)?.unsqueeze(0)?;
+        let mut input_embeds =
+            model.prepare_inputs_labels_for_multimodal(&input_ids, &images, &image_sizes)?;
+        let mut cache = self.cache.clone();
+        let mut rng = rand::rngs::StdRng::from_os_rng();
+
+        let mut logits_processor = {
+            let temperature = params.temperature;
+            let sampling = if temperature <= 0. {
+                Sampling::ArgMax
+            } else {
+                Sampling::TopKThenTopP {
+                    k: params.top_k as usize,
+                    p: params.top_p as f64,
+                    temperature: params.temperature as f64,
+                }
+            };
+            LogitsProcessor::from_sampling(rng.next_u64(), sampling)
+        };
+
+        let mut generated = vec![];
+        let mut index_pos = 0;
+        for index in 0..params.max_tokens {
+            // After the prompt, only the embedding of the latest token is new
+            let (_, embeds_len, _) = input_embeds.dims3()?;
+            let (context_size, context_index) = if index > 0 {
+                (1, index_pos)
+            } else {
+                (embeds_len, 0)
+            };
+            let input = input_embeds.i((.., embeds_len.saturating_sub(context_size).., ..))?;
+            let logits = model.forward(&input, context_index, &mut cache)?;
+            let logits = logits.squeeze(0)?;
+            let logits = if params.repeat_penalty == 1. {
+                logits
+            } else {
+                let start_at = generated
+                    .len()
+                    .saturating_sub(params.repeat_penalty_last_n_token_count as usize);
+                candle_transformers::utils::apply_repeat_penalty(
+                    &logits,
+                    params.repeat_penalty,
+                    &generated[start_at..],
+                )?
+            };
+            index_pos += context_size;
+
+            let next_token = logits_processor.sample(&logits)?;
+            if next_token as usize == self.config.eos_token_id {
+                break;
+            }
+            generated.push(next_token);
+            let next_token = Tensor::new(&[next_token], &self.device)?;
+            let next_embeds = model.llama.embed(&next_token)?.unsqueeze(0)?;
+            input_embeds = Tensor::cat(&[input_embeds, next_embeds], 1)?;
+        }
+
+        let output_text = self
+            .tokenizer
+            .decode(&generated, true)
+            .map_err(|e| anyhow!(e.to_string()))?;
+
+        Ok(llm3::InferencingResult {
+            text: output_text,
+            usage: InferencingUsage {
+                prompt_token_count: prompt_token_count as u32,
+                generated_token_count: generated.len() as u32,
+                cache_hit_tokens: 0,
+                cache_miss_tokens: prompt_token_count as u32,
+            },
+        })
+    }
+}
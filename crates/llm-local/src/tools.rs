@@ -0,0 +1,236 @@
+//! Tool use for models without native support for it: the tools are described
+//! in the prompt, and the model is asked to answer with a JSON tool call when
+//! it wants to use one.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use serde::Deserialize;
+use spin_world::spin::llm::llm as llm3;
+use spin_world::v2::llm::{self as wasi_llm};
+
+const TOOL_INSTRUCTIONS: &str = "You can call the following tools. To call one, answer with \
+only a JSON object of the form {\"name\": <tool name>, \"arguments\": <arguments object>}, \
+where the arguments match the tool's JSON Schema. Otherwise, answer in plain text.";
+
+/// Renders a conversation and the tools available to the model into a Llama 2
+/// chat prompt.
+///
+/// The tools are described in the system prompt, after any system messages
+/// from the conversation.
+pub(crate) fn render_prompt(
+    messages: &[llm3::Message],
+    tools: &[llm3::ToolDef],
+) -> Result<String, wasi_llm::Error> {
+    let mut system = messages
+        .iter()
+        .filter(|m| m.role == llm3::MessageRole::System)
+        .map(|m| m.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !tools.is_empty() {
+        if !system.is_empty() {
+            system.push_str("\n\n");
+        }
+        system.push_str(TOOL_INSTRUCTIONS);
+        let mut names = HashSet::new();
+        for tool in tools {
+            if !names.insert(tool.name.as_str()) {
+                return Err(wasi_llm::Error::InvalidInput(format!(
+                    "tool {:?} is defined more than once",
+                    tool.name
+                )));
+            }
+            let parameters: serde_json::Value =
+                serde_json::from_str(&tool.parameters).map_err(|e| {
+                    wasi_llm::Error::InvalidInput(format!(
+                        "parameters of tool {:?} are not a JSON Schema: {e}",
+                        tool.name
+                    ))
+                })?;
+            let tool = serde_json::json!({
+                "name": tool.name,
+                "description": tool.description,
+                "parameters": parameters,
+            });
+            write!(system, "\n{tool}").unwrap();
+        }
+    }
+
+    let mut prompt = String::new();
+    let mut system = (!system.is_empty()).then_some(system);
+    let mut in_turn = false;
+    for message in messages {
+        match message.role {
+            llm3::MessageRole::System => {}
+            llm3::MessageRole::User | llm3::MessageRole::Tool => {
+                if !in_turn {
+                    if !prompt.is_empty() {
+                        prompt.push_str("<s>");
+                    }
+                    prompt.push_str("[INST] ");
+                    if let Some(system) = system.take() {
+                        write!(prompt, "<<SYS>>\n{system}\n<</SYS>>\n\n").unwrap();
+                    }
+                    in_turn = true;
+                } else {
+                    prompt.push('\n');
+                }
+                if message.role == llm3::MessageRole::Tool {
+                    prompt.push_str("Tool result: ");
+                }
+                prompt.push_str(message.content.trim());
+            }
+            llm3::MessageRole::Assistant => {
+                if in_turn {
+                    prompt.push_str(" [/INST]");
+                    in_turn = false;
+                }
+                write!(prompt, " {} </s>", message.content.trim()).unwrap();
+            }
+        }
+    }
+    if !in_turn {
+        return Err(wasi_llm::Error::InvalidInput(
+            "the conversation must end with a user or tool message".into(),
+        ));
+    }
+    prompt.push_str(" [/INST]");
+    Ok(prompt)
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    arguments: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Interprets the text generated by the model as a call to one of the tools,
+/// if it is one, or as a text completion otherwise.
+pub(crate) fn parse_result(
+    result: llm3::InferencingResult,
+    tools: &[llm3::ToolDef],
+) -> llm3::ToolCallResult {
+    let text = result.text.trim();
+    // Models often wrap JSON in a Markdown code block
+    let json = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|t| t.strip_suffix("```"))
+        .unwrap_or(text)
+        .trim();
+    match serde_json::from_str::<ToolCall>(json) {
+        Ok(call) if tools.iter().any(|t| t.name == call.name) => {
+            llm3::ToolCallResult::ToolCall(llm3::ToolCall {
+                name: call.name,
+                arguments: serde_json::Value::Object(call.arguments).to_string(),
+                usage: result.usage,
+            })
+        }
+        _ => llm3::ToolCallResult::Text(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: llm3::MessageRole, content: &str) -> llm3::Message {
+        llm3::Message {
+            role,
+            content: content.into(),
+        }
+    }
+
+    fn weather_tool() -> llm3::ToolDef {
+        llm3::ToolDef {
+            name: "get_weather".into(),
+            description: "Gets the current weather in a city".into(),
+            parameters: r#"{"type": "object", "properties": {"city": {"type": "string"}}}"#.into(),
+        }
+    }
+
+    fn result(text: &str) -> llm3::InferencingResult {
+        llm3::InferencingResult {
+            text: text.into(),
+            usage: llm3::InferencingUsage {
+                prompt_token_count: 10,
+                generated_token_count: 5,
+                cache_hit_tokens: 0,
+                cache_miss_tokens: 10,
+            },
+        }
+    }
+
+    #[test]
+    fn prompt_describes_tools_and_conversation() {
+        let messages = [
+            message(llm3::MessageRole::System, "Be brief."),
+            message(llm3::MessageRole::User, "What's the weather in Paris?"),
+            message(
+                llm3::MessageRole::Assistant,
+                r#"{"name": "get_weather", "arguments": {"city": "Paris"}}"#,
+            ),
+            message(llm3::MessageRole::Tool, "Sunny, 20C"),
+        ];
+        let prompt = render_prompt(&messages, &[weather_tool()]).unwrap();
+
+        let tool = serde_json::json!({
+            "name": "get_weather",
+            "description": "Gets the current weather in a city",
+            "parameters": {"type": "object", "properties": {"city": {"type": "string"}}},
+        });
+        let expected = format!(
+            "[INST] <<SYS>>\nBe brief.\n\n{TOOL_INSTRUCTIONS}\n{tool}\n<</SYS>>\n\nWhat's the weather in Paris? [/INST] \
+            {{\"name\": \"get_weather\", \"arguments\": {{\"city\": \"Paris\"}}}} </s>\
+            <s>[INST] Tool result: Sunny, 20C [/INST]"
+        );
+        assert_eq!(prompt, expected);
+    }
+
+    #[test]
+    fn invalid_tools_are_rejected() {
+        let messages = [message(llm3::MessageRole::User, "Hi")];
+        let mut tool = weather_tool();
+        tool.parameters = "not json".into();
+        assert!(matches!(
+            render_prompt(&messages, &[tool]),
+            Err(wasi_llm::Error::InvalidInput(msg)) if msg.contains("not a JSON Schema")
+        ));
+        assert!(matches!(
+            render_prompt(&messages, &[weather_tool(), weather_tool()]),
+            Err(wasi_llm::Error::InvalidInput(msg)) if msg.contains("more than once")
+        ));
+        assert!(matches!(
+            render_prompt(&[], &[weather_tool()]),
+            Err(wasi_llm::Error::InvalidInput(msg)) if msg.contains("must end with")
+        ));
+    }
+
+    #[test]
+    fn tool_calls_are_parsed() {
+        let tools = [weather_tool()];
+        let llm3::ToolCallResult::ToolCall(call) = parse_result(
+            result(
+                "```json\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}\n```",
+            ),
+            &tools,
+        ) else {
+            panic!("expected a tool call");
+        };
+        assert_eq!(call.name, "get_weather");
+        assert_eq!(call.arguments, r#"{"city":"Paris"}"#);
+        assert_eq!(call.usage.generated_token_count, 5);
+
+        // Text, and calls to tools which weren't offered, are text completions
+        for text in [
+            "It's sunny in Paris.",
+            r#"{"name": "get_time", "arguments": {}}"#,
+        ] {
+            assert!(matches!(
+                parse_result(result(text), &tools),
+                llm3::ToolCallResult::Text(r) if r.text == text
+            ));
+        }
+    }
+}
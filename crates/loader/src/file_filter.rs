@@ -0,0 +1,149 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// Decides which files are excluded from a component's `files` by its
+/// `exclude_files` patterns.
+///
+/// Patterns are matched against paths relative to the application directory
+/// and are evaluated in order, gitignore-style: the last pattern matching a
+/// path decides whether it is excluded. A pattern prefixed with `!` re-includes
+/// paths excluded by earlier patterns. A pattern matching a directory applies
+/// to everything beneath it. Paths not matched by any pattern are included.
+///
+/// Because the `files` entries are applied first, this gives the combined
+/// lists the ordering "include `files`, then apply each `exclude_files` entry
+/// in turn", e.g. `files = ["assets/**/*"]` with
+/// `exclude_files = ["assets/tmp", "!assets/tmp/keep.txt"]`.
+#[derive(Debug, Default)]
+pub(crate) struct ExcludeFilter {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    pattern: glob::Pattern,
+    negated: bool,
+}
+
+impl ExcludeFilter {
+    /// Parses the given `exclude_files` patterns.
+    pub fn new(exclude_files: &[String]) -> Result<Self> {
+        let rules = exclude_files
+            .iter()
+            .map(|pattern| Rule::parse(pattern))
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns true if there are no `exclude_files` patterns.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns true if the given path (relative to the application directory)
+    /// is excluded.
+    pub fn is_excluded(&self, app_root_path: &Path) -> bool {
+        let app_root_path: PathBuf = app_root_path
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&app_root_path))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+impl Rule {
+    fn parse(exclude: &str) -> Result<Self> {
+        let (pattern, negated) = match exclude.strip_prefix('!') {
+            Some(pattern) => (pattern, true),
+            None => (exclude, false),
+        };
+        if pattern.is_empty() {
+            bail!("Invalid exclude_files pattern {exclude:?}: pattern is empty");
+        }
+        let path = Path::new(pattern);
+        if path.has_root()
+            || path
+                .components()
+                .any(|c| matches!(c, Component::ParentDir | Component::Prefix(_)))
+        {
+            bail!("Invalid exclude_files pattern {exclude:?}: patterns must be relative to the application directory and cannot refer to files outside it");
+        }
+        let pattern = glob::Pattern::new(pattern.trim_start_matches("./"))
+            .with_context(|| format!("Invalid exclude_files glob pattern {exclude:?}"))?;
+        Ok(Self { pattern, negated })
+    }
+
+    /// Returns true if the pattern matches the path or any of its ancestors.
+    fn matches(&self, path: &Path) -> bool {
+        path.ancestors()
+            .take_while(|p| !p.as_os_str().is_empty())
+            .any(|p| self.pattern.matches_path(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(patterns: &[&str]) -> ExcludeFilter {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        ExcludeFilter::new(&patterns).unwrap()
+    }
+
+    #[test]
+    fn simple_patterns_exclude() {
+        let f = filter(&["secrets/*", "*.bak"]);
+        assert!(f.is_excluded(Path::new("secrets/key.pem")));
+        assert!(f.is_excluded(Path::new("assets/old.bak")));
+        assert!(f.is_excluded(Path::new("./secrets/key.pem")));
+        assert!(!f.is_excluded(Path::new("assets/logo.png")));
+        assert!(!filter(&[]).is_excluded(Path::new("anything")));
+    }
+
+    #[test]
+    fn directory_patterns_exclude_contents() {
+        let f = filter(&["assets/tmp"]);
+        assert!(f.is_excluded(Path::new("assets/tmp/a.txt")));
+        assert!(f.is_excluded(Path::new("assets/tmp/nested/b.txt")));
+        assert!(!f.is_excluded(Path::new("assets/tmpfile.txt")));
+    }
+
+    #[test]
+    fn later_patterns_take_precedence() {
+        let f = filter(&["assets/tmp/**", "!assets/tmp/keep.txt"]);
+        assert!(f.is_excluded(Path::new("assets/tmp/scratch.txt")));
+        assert!(!f.is_excluded(Path::new("assets/tmp/keep.txt")));
+
+        // Re-excluding after a re-include
+        let f = filter(&["assets/tmp/**", "!assets/tmp/*.txt", "assets/tmp/draft*"]);
+        assert!(!f.is_excluded(Path::new("assets/tmp/keep.txt")));
+        assert!(f.is_excluded(Path::new("assets/tmp/draft.txt")));
+        assert!(f.is_excluded(Path::new("assets/tmp/image.png")));
+
+        // A re-include before the exclude has no effect
+        let f = filter(&["!assets/tmp/keep.txt", "assets/tmp/**"]);
+        assert!(f.is_excluded(Path::new("assets/tmp/keep.txt")));
+    }
+
+    #[test]
+    fn patterns_escaping_app_dir_are_rejected() {
+        for pattern in [
+            "/etc/passwd",
+            "../sibling/*",
+            "assets/../../x",
+            "!../x",
+            "!",
+        ] {
+            let err = ExcludeFilter::new(&[pattern.to_string()]).unwrap_err();
+            assert!(
+                err.to_string().contains(&format!("{pattern:?}")),
+                "{pattern:?}: {err}"
+            );
+        }
+    }
+}
@@ -18,6 +18,7 @@ use spin_common::paths::parent_dir;
 use spin_locked_app::locked::LockedApp;
 
 pub mod cache;
+mod file_filter;
 mod fs;
 #[cfg(feature = "async-io")]
 mod http;
@@ -43,6 +44,52 @@ pub async fn from_file(
     loader.load_file(path).await
 }
 
+/// Like [`from_file`], but fails rather than warning if the manifest defines
+/// components which no trigger uses.
+pub async fn from_file_strict(
+    manifest_path: impl AsRef<Path>,
+    files_mount_strategy: FilesMountStrategy,
+    cache_root: Option<PathBuf>,
+) -> Result<LockedApp> {
+    let options = ManifestLoadOptions {
+        strict_components: true,
+        ..Default::default()
+    };
+    from_file_with_options(manifest_path, files_mount_strategy, cache_root, options).await
+}
+
+/// Like [`from_file`], with additional options controlling how the manifest
+/// is interpreted.
+pub async fn from_file_with_options(
+    manifest_path: impl AsRef<Path>,
+    files_mount_strategy: FilesMountStrategy,
+    cache_root: Option<PathBuf>,
+    options: ManifestLoadOptions,
+) -> Result<LockedApp> {
+    let path = manifest_path.as_ref();
+    let app_root = parent_dir(path).context("manifest path has no parent directory")?;
+    let mut loader = LocalLoader::new(&app_root, files_mount_strategy, cache_root).await?;
+    loader.set_strict_components(options.strict_components);
+    loader.set_profile(options.profile);
+    loader.set_direct_mount_staging_root(options.direct_mount_staging_root);
+    loader.load_file(path).await
+}
+
+/// Options for [`from_file_with_options`].
+#[derive(Debug, Default)]
+pub struct ManifestLoadOptions {
+    /// Fail rather than warn if the manifest defines components which no
+    /// trigger uses.
+    pub strict_components: bool,
+    /// The manifest `[profile.<name>]` to apply, if any. Without a profile,
+    /// the manifest is loaded as written.
+    pub profile: Option<String>,
+    /// With [`FilesMountStrategy::Direct`], the directory in which to stage
+    /// copies of mounted directories containing files excluded by
+    /// `exclude_files`. Without it, such directories can't be mounted.
+    pub direct_mount_staging_root: Option<PathBuf>,
+}
+
 /// Load a Spin locked app from a standalone Wasm file.
 pub async fn from_wasm_file(wasm_path: impl AsRef<Path>) -> Result<LockedApp> {
     let app_root = std::env::current_dir()?;
@@ -57,8 +104,11 @@ pub enum FilesMountStrategy {
     /// Copy files into the given mount root directory.
     Copy(PathBuf),
     /// Mount files directly from their source director(ies). This only
-    /// supports mounting full directories; mounting single files, glob
-    /// patterns, and `exclude_files` are not supported.
+    /// supports mounting full directories; mounting single files and glob
+    /// patterns are not supported. Directories containing files excluded by
+    /// `exclude_files` are mounted as filtered copies instead (see
+    /// [`ManifestLoadOptions::direct_mount_staging_root`]), so changes to
+    /// them are not seen by the guest.
     Direct,
 }
 
@@ -3,7 +3,11 @@ use std::path::{Path, PathBuf};
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use futures::{future::try_join_all, StreamExt};
 use reqwest::Url;
-use spin_common::{paths::parent_dir, sloth, ui::quoted_path};
+use spin_common::{
+    paths::{component_build_metadata_path, parent_dir},
+    sha256, sloth,
+    ui::quoted_path,
+};
 use spin_expressions::Resolver;
 use spin_locked_app::{
     locked::{
@@ -18,7 +22,7 @@ use spin_serde::DependencyName;
 use std::collections::BTreeMap;
 use tokio::{io::AsyncWriteExt, sync::Semaphore};
 
-use crate::{cache::Cache, FilesMountStrategy};
+use crate::{cache::Cache, file_filter::ExcludeFilter, FilesMountStrategy};
 
 #[derive(Debug)]
 pub struct LocalLoader {
@@ -26,6 +30,13 @@ pub struct LocalLoader {
     files_mount_strategy: FilesMountStrategy,
     file_loading_permits: std::sync::Arc<Semaphore>,
     wasm_loader: WasmLoader,
+    /// If true, components which no trigger uses are an error rather than a warning.
+    strict_components: bool,
+    /// The manifest profile to apply, if any.
+    profile: Option<String>,
+    /// Where filtered copies of direct-mounted directories containing
+    /// excluded files are staged, if anywhere.
+    direct_mount_staging_root: Option<PathBuf>,
 }
 
 impl LocalLoader {
@@ -44,9 +55,28 @@ impl LocalLoader {
             // Limit concurrency to avoid hitting system resource limits
             file_loading_permits: file_loading_permits.clone(),
             wasm_loader: WasmLoader::new(app_root, cache_root, Some(file_loading_permits)).await?,
+            strict_components: false,
+            profile: None,
+            direct_mount_staging_root: None,
         })
     }
 
+    /// Makes components which no trigger uses an error rather than a warning.
+    pub fn set_strict_components(&mut self, strict: bool) {
+        self.strict_components = strict;
+    }
+
+    /// Applies the named manifest `[profile.<name>]` before loading the app.
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    /// Sets the directory in which filtered copies of direct-mounted
+    /// directories containing files excluded by `exclude_files` are staged.
+    pub fn set_direct_mount_staging_root(&mut self, staging_root: Option<PathBuf>) {
+        self.direct_mount_staging_root = staging_root;
+    }
+
     // Load the manifest file (spin.toml) at the given path into a LockedApp,
     // preparing all its content for execution.
     pub async fn load_file(&self, path: impl AsRef<Path>) -> Result<LockedApp> {
@@ -75,7 +105,25 @@ impl LocalLoader {
     pub(crate) async fn load_manifest(&self, mut manifest: AppManifest) -> Result<LockedApp> {
         spin_manifest::normalize::normalize_manifest(&mut manifest);
 
+        if let Some(profile) = &self.profile {
+            let dropped = spin_manifest::profile::apply_profile(&mut manifest, profile)?;
+            for trigger in dropped {
+                terminal::einfo!(
+                    "Profile:",
+                    "{profile:?} disables component `{}`; skipping `{}` trigger {:?}",
+                    trigger.component,
+                    trigger.trigger_type,
+                    trigger.trigger_id,
+                );
+            }
+        }
+
         manifest.validate_dependencies()?;
+        manifest.validate_component_references()?;
+        if let Some(message) = unused_components_message(&manifest) {
+            ensure!(!self.strict_components, "{message}");
+            terminal::warn!("{message}");
+        }
 
         let AppManifest {
             spin_manifest_version: _,
@@ -83,9 +131,12 @@ impl LocalLoader {
             variables,
             triggers,
             components,
+            profiles: _,
         } = manifest;
 
-        let metadata = locked_metadata(application, triggers.keys().cloned())?;
+        let sqlite_migrations =
+            self.load_sqlite_migrations(application.sqlite_migrations.clone())?;
+        let metadata = locked_metadata(application, triggers.keys().cloned(), sqlite_migrations)?;
 
         let variables = variables
             .into_iter()
@@ -140,6 +191,39 @@ impl LocalLoader {
         })
     }
 
+    // Reads each database's SQL migration files, so that the migrations are
+    // carried in the locked app rather than referring to the app directory.
+    fn load_sqlite_migrations(
+        &self,
+        migrations: impl IntoIterator<Item = (String, Vec<String>)>,
+    ) -> Result<BTreeMap<String, Vec<SqliteMigration>>> {
+        migrations
+            .into_iter()
+            .map(|(label, paths)| {
+                let migrations = paths
+                    .into_iter()
+                    .map(|path| {
+                        let abs_path = safe_canonicalize(&self.app_root.join(&path))?;
+                        if abs_path.strip_prefix(&self.app_root).is_err() {
+                            bail!(
+                                "SQLite migration {} for database '{label}' is outside the application directory. Migrations must be within the application directory.",
+                                quoted_path(&path)
+                            );
+                        }
+                        let sql = std::fs::read_to_string(&abs_path).with_context(|| {
+                            format!(
+                                "Failed to read SQLite migration {} for database '{label}'",
+                                quoted_path(&path)
+                            )
+                        })?;
+                        Ok(SqliteMigration { name: path, sql })
+                    })
+                    .collect::<Result<_>>()?;
+                Ok((label, migrations))
+            })
+            .collect()
+    }
+
     // Load the given component into a LockedComponent, ready for execution.
     async fn load_component(
         &self,
@@ -150,25 +234,54 @@ impl LocalLoader {
         let allowed_outbound_hosts = component
             .normalized_allowed_outbound_hosts()
             .context("`allowed_http_hosts` is malformed")?;
-        AllowedHostsConfig::validate(&allowed_outbound_hosts, resolver)
-            .context("`allowed_outbound_hosts` is malformed")?;
+        AllowedHostsConfig::validate(&allowed_outbound_hosts, resolver).with_context(|| {
+            format!("`allowed_outbound_hosts` of component `{id}` is malformed")
+        })?;
 
         let component_requires_service_chaining = requires_service_chaining(&component);
+        let has_build = component.build.is_some();
 
-        let metadata = ValuesMapBuilder::new()
+        let mut metadata = ValuesMapBuilder::new()
             .string("description", component.description)
+            .serializable(
+                "allowed_application_variables",
+                component.allowed_application_variables,
+            )?
             .string_array("allowed_outbound_hosts", allowed_outbound_hosts)
             .string_array("key_value_stores", component.key_value_stores)
             .string_array("databases", component.sqlite_databases)
+            .string_array("read_only_databases", component.sqlite_read_only_databases)
+            .serializable("sqlite_backup", component.sqlite_backup.then_some(true))?
+            .serializable(
+                "sqlite_vector_search",
+                component.sqlite_vector_search.then_some(true),
+            )?
+            .serializable("sqlite_explain", component.sqlite_explain.then_some(true))?
             .string_array("ai_models", component.ai_models)
+            .serializable("working_directory", component.working_directory)?
+            .serializable(
+                "limits",
+                (!component.limits.is_empty()).then_some(component.limits),
+            )?
             .serializable("build", component.build)?
+            .serializable(locked::SOURCE_KIND_KEY, source_kind(&component.source))?
             .take();
 
-        let source = self
+        let mut source = self
             .load_component_source(id, component.source.clone())
             .await
             .with_context(|| format!("Failed to load Wasm source {}", component.source))?;
 
+        if let (true, v2::ComponentSource::Local(path)) = (has_build, &component.source) {
+            if let Some(build_metadata) = self.current_build_metadata(id, path)? {
+                source.content.digest = Some(build_metadata.digest.clone());
+                metadata.insert(
+                    locked::BUILD_METADATA_KEY.into(),
+                    serde_json::to_value(build_metadata)?,
+                );
+            }
+        }
+
         let dependencies = self
             .load_component_dependencies(
                 id,
@@ -179,6 +292,8 @@ impl LocalLoader {
 
         let env = component.environment.into_iter().collect();
 
+        let exclude = ExcludeFilter::new(&component.exclude_files)?;
+
         let files = if component.files.is_empty() {
             vec![]
         } else {
@@ -186,9 +301,12 @@ impl LocalLoader {
                 FilesMountStrategy::Copy(files_mount_root) => {
                     let component_mount_root = files_mount_root.join(id.as_ref());
                     // Copy mounted files into component mount root, concurrently
-                    try_join_all(component.files.iter().map(|f| {
-                        self.copy_file_mounts(f, &component_mount_root, &component.exclude_files)
-                    }))
+                    try_join_all(
+                        component
+                            .files
+                            .iter()
+                            .map(|f| self.copy_file_mounts(f, &component_mount_root, &exclude)),
+                    )
                     .await?;
 
                     // All component files (copies) are in `component_mount_root` now
@@ -198,14 +316,13 @@ impl LocalLoader {
                     }]
                 }
                 FilesMountStrategy::Direct => {
-                    ensure!(
-                        component.exclude_files.is_empty(),
-                        "Cannot load a component with `exclude_files` using --direct-mounts"
-                    );
                     let mut files = vec![];
-                    for mount in &component.files {
+                    for (index, mount) in component.files.iter().enumerate() {
                         // Validate (and canonicalize) direct mount directory
-                        files.push(self.resolve_direct_mount(mount).await?);
+                        files.push(
+                            self.resolve_direct_mount(id, index, mount, &exclude)
+                                .await?,
+                        );
                     }
                     files
                 }
@@ -300,27 +417,57 @@ impl LocalLoader {
         component_id: &KebabId,
         source: v2::ComponentSource,
     ) -> Result<LockedComponentSource> {
-        let path = self
+        let (path, digest) = self
             .wasm_loader
-            .load_component_source(component_id.as_ref(), &source)
+            .load_component_source_and_digest(component_id.as_ref(), &source)
             .await?;
         Ok(LockedComponentSource {
             content_type: "application/wasm".into(),
-            content: file_content_ref(path)?,
+            content: ContentRef {
+                digest,
+                ..file_content_ref(path)?
+            },
         })
     }
 
+    // Returns the metadata recorded by `spin build` for the component, if
+    // it is of the Wasm file now at `path`. A file since produced by some
+    // other means would otherwise be misattributed to the build.
+    fn current_build_metadata(
+        &self,
+        component_id: &KebabId,
+        path: &str,
+    ) -> Result<Option<locked::BuildMetadata>> {
+        let metadata_path = component_build_metadata_path(&self.app_root, component_id.as_ref());
+        let Ok(bytes) = std::fs::read(&metadata_path) else {
+            return Ok(None);
+        };
+        let metadata: locked::BuildMetadata = match serde_json::from_slice(&bytes) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                tracing::warn!(
+                    "Ignoring unreadable build metadata {}: {e}",
+                    quoted_path(&metadata_path)
+                );
+                return Ok(None);
+            }
+        };
+        let wasm_path = self.app_root.join(path);
+        let digest = sha256::hex_digest_from_file(&wasm_path)
+            .with_context(|| format!("Cannot read {}", quoted_path(&wasm_path)))?;
+        Ok((metadata.digest == format!("sha256:{digest}")).then_some(metadata))
+    }
+
     // Copy content(s) from the given `mount`
     async fn copy_file_mounts(
         &self,
         mount: &WasiFilesMount,
         dest_root: &Path,
-        exclude_files: &[String],
+        exclude: &ExcludeFilter,
     ) -> Result<()> {
         match mount {
             WasiFilesMount::Pattern(pattern) => {
-                self.copy_glob_or_path(pattern, dest_root, exclude_files)
-                    .await
+                self.copy_glob_or_path(pattern, dest_root, exclude).await
             }
             WasiFilesMount::Placement {
                 source,
@@ -328,7 +475,7 @@ impl LocalLoader {
             } => {
                 let src = Path::new(source);
                 let dest = dest_root.join(destination.trim_start_matches('/'));
-                self.copy_file_or_directory(src, &dest, destination, exclude_files)
+                self.copy_file_or_directory(src, &dest, destination, exclude)
                     .await
             }
         }
@@ -339,7 +486,7 @@ impl LocalLoader {
         &self,
         glob_or_path: &str,
         dest_root: &Path,
-        exclude_files: &[String],
+        exclude: &ExcludeFilter,
     ) -> Result<()> {
         if glob_or_path == ".." || glob_or_path.ends_with("/..") {
             bail!("A file pattern can't end in a parent directory path (..)\nIf you want to include a directory, use source-destination form, or a glob pattern ending in **/*.\nLearn more: https://spinframework.dev/writing-apps#including-files-with-components");
@@ -361,15 +508,17 @@ impl LocalLoader {
             if path.is_dir() {
                 // "single/dir"
                 let pattern = path.join("**/*");
-                self.copy_glob(&pattern, &self.app_root, &dest, exclude_files)
+                self.copy_glob(&pattern, &self.app_root, &dest, exclude)
                     .await?;
+            } else if exclude.is_excluded(Path::new(glob_or_path)) {
+                tracing::debug!("File {glob_or_path:?} excluded by exclude_files");
             } else {
                 // "single/file.txt"
                 self.copy_single_file(&path, &dest, glob_or_path).await?;
             }
         } else if looks_like_glob_pattern(glob_or_path) {
             // "glob/pattern/*"
-            self.copy_glob(&path, &self.app_root, dest_root, exclude_files)
+            self.copy_glob(&path, &self.app_root, dest_root, exclude)
                 .await?;
         } else {
             bail!("{glob_or_path:?} does not exist and doesn't appear to be a glob pattern");
@@ -383,7 +532,7 @@ impl LocalLoader {
         src: &Path,
         dest: &Path,
         guest_dest: &str,
-        exclude_files: &[String],
+        exclude: &ExcludeFilter,
     ) -> Result<()> {
         let src_path = self.app_root.join(src);
         let meta = crate::fs::metadata(&src_path)
@@ -392,8 +541,9 @@ impl LocalLoader {
         if meta.is_dir() {
             // { source = "host/dir", destination = "guest/dir" }
             let pattern = src_path.join("**/*");
-            self.copy_glob(&pattern, &src_path, dest, exclude_files)
-                .await?;
+            self.copy_glob(&pattern, &src_path, dest, exclude).await?;
+        } else if exclude.is_excluded(src) {
+            tracing::debug!("File {src:?} excluded by exclude_files");
         } else {
             // { source = "host/file.txt", destination = "guest/file.txt" }
             self.copy_single_file(&src_path, dest, guest_dest).await?;
@@ -407,7 +557,7 @@ impl LocalLoader {
         pattern: &Path,
         src_prefix: &Path,
         dest_root: &Path,
-        exclude_files: &[String],
+        exclude: &ExcludeFilter,
     ) -> Result<()> {
         let pattern = pattern
             .to_str()
@@ -416,14 +566,6 @@ impl LocalLoader {
         let paths = glob::glob(pattern)
             .with_context(|| format!("Failed to resolve glob pattern {pattern:?}"))?;
 
-        let exclude_patterns = exclude_files
-            .iter()
-            .map(|pattern| {
-                glob::Pattern::new(pattern)
-                    .with_context(|| format!("Invalid exclude_files glob pattern {pattern:?}"))
-            })
-            .collect::<Result<Vec<_>>>()?;
-
         crate::fs::create_dir_all(dest_root)
             .await
             .with_context(|| {
@@ -443,13 +585,8 @@ impl LocalLoader {
                 bail!("{pattern} cannot be mapped because it is outside the application directory. Files must be within the application directory.");
             };
 
-            if exclude_patterns
-                .iter()
-                .any(|pattern| pattern.matches_path(app_root_path))
-            {
-                tracing::debug!(
-                    "File {app_root_path:?} excluded by exclude_files {exclude_files:?}"
-                );
+            if exclude.is_excluded(app_root_path) {
+                tracing::debug!("File {app_root_path:?} excluded by exclude_files");
                 continue;
             }
 
@@ -465,12 +602,16 @@ impl LocalLoader {
     async fn copy_single_file(&self, src: &Path, dest: &Path, guest_dest: &str) -> Result<()> {
         // Sanity checks: src is in app_root...
         src.strip_prefix(&self.app_root)?;
-        // ...and dest is in the Copy root.
-        if let FilesMountStrategy::Copy(files_mount_root) = &self.files_mount_strategy {
-            dest.strip_prefix(files_mount_root)?;
-        } else {
+        // ...and dest is in the Copy root (or the staging root, for filtered
+        // direct mounts).
+        let copy_root = match &self.files_mount_strategy {
+            FilesMountStrategy::Copy(files_mount_root) => Some(files_mount_root),
+            FilesMountStrategy::Direct => self.direct_mount_staging_root.as_ref(),
+        };
+        let Some(copy_root) = copy_root else {
             unreachable!();
-        }
+        };
+        dest.strip_prefix(copy_root)?;
 
         let _loading_permit = self.file_loading_permits.acquire().await?;
         let dest_parent = parent_dir(dest)?;
@@ -521,7 +662,17 @@ impl LocalLoader {
 
     // Resolve the given direct mount directory, checking that it is valid for
     // direct mounting and returning its canonicalized source path.
-    async fn resolve_direct_mount(&self, mount: &WasiFilesMount) -> Result<ContentPath> {
+    //
+    // A directory containing files excluded by `exclude_files` can't be
+    // mounted as it is without exposing them, so a copy without them is
+    // staged and mounted instead.
+    async fn resolve_direct_mount(
+        &self,
+        id: &KebabId,
+        index: usize,
+        mount: &WasiFilesMount,
+        exclude: &ExcludeFilter,
+    ) -> Result<ContentPath> {
         let (src, dest) = match mount {
             WasiFilesMount::Pattern(pattern) => (pattern, pattern),
             WasiFilesMount::Placement {
@@ -533,11 +684,51 @@ impl LocalLoader {
         if !path.is_dir() {
             bail!("Only directory mounts are supported with `--direct-mounts`; {src:?} is not a directory.");
         }
+        let Some(excluded) = self.first_excluded_file(&path, exclude)? else {
+            return Ok(ContentPath {
+                content: file_content_ref(&path)?,
+                path: dest.into(),
+            });
+        };
+
+        let Some(staging_root) = &self.direct_mount_staging_root else {
+            bail!("Cannot direct-mount {src:?} because it contains {excluded:?}, which is excluded by `exclude_files`, and there is nowhere to stage a copy without the excluded files.");
+        };
+        tracing::debug!(
+            "Direct mount {src:?} contains excluded file {excluded:?}: mounting a filtered copy"
+        );
+        let staged = staging_root.join(id.as_ref()).join(index.to_string());
+        let pattern = path.join("**/*");
+        self.copy_glob(&pattern, &path, &staged, exclude).await?;
         Ok(ContentPath {
-            content: file_content_ref(src)?,
+            content: file_content_ref(&staged)?,
             path: dest.into(),
         })
     }
+
+    // Returns the first file beneath `dir` (relative to the app root) which
+    // is excluded by `exclude`, if any.
+    fn first_excluded_file(&self, dir: &Path, exclude: &ExcludeFilter) -> Result<Option<PathBuf>> {
+        if exclude.is_empty() {
+            return Ok(None);
+        }
+        let pattern = dir.join("**/*");
+        let pattern = pattern
+            .to_str()
+            .with_context(|| format!("invalid (non-utf8) file pattern {pattern:?}"))?;
+        for path in glob::glob(pattern)
+            .with_context(|| format!("Failed to resolve glob pattern {pattern:?}"))?
+        {
+            let path = path?;
+            let Ok(app_root_path) = path.strip_prefix(&self.app_root) else {
+                bail!("{pattern} cannot be mapped because it is outside the application directory. Files must be within the application directory.");
+            };
+            if path.is_file() && exclude.is_excluded(app_root_path) {
+                return Ok(Some(app_root_path.to_owned()));
+            }
+        }
+        Ok(None)
+    }
 }
 
 fn explain_file_mount_source_error(e: anyhow::Error, src: &Path) -> anyhow::Error {
@@ -576,9 +767,20 @@ fn safe_canonicalize(path: &Path) -> std::io::Result<PathBuf> {
     Ok(path.absolutize()?.into_owned())
 }
 
+/// A SQL migration, as recorded in the locked app's `sqlite_migrations`
+/// metadata.
+#[derive(serde::Serialize)]
+struct SqliteMigration {
+    /// The path of the migration file, as given in the manifest.
+    name: String,
+    /// The contents of the migration file.
+    sql: String,
+}
+
 fn locked_metadata(
     details: v2::AppDetails,
     trigger_types: impl Iterator<Item = String>,
+    sqlite_migrations: BTreeMap<String, Vec<SqliteMigration>>,
 ) -> Result<ValuesMap> {
     let mut builder = ValuesMapBuilder::new();
     builder
@@ -586,7 +788,15 @@ fn locked_metadata(
         .string("version", details.version)
         .string("description", details.description)
         .string_array("authors", details.authors)
-        .serializable("triggers", &details.trigger_global_configs)?;
+        .serializable("triggers", &details.trigger_global_configs)?
+        .serializable(
+            "sqlite_migrations",
+            (!sqlite_migrations.is_empty()).then_some(sqlite_migrations),
+        )?
+        .serializable(
+            "limits",
+            (!details.limits.is_empty()).then_some(&details.limits),
+        )?;
 
     // Duplicate single-trigger global options into "trigger" with "type"
     // key to maintain backward compatibility for a while.
@@ -605,6 +815,23 @@ fn locked_metadata(
     Ok(builder.build())
 }
 
+/// A message listing the components which no trigger uses, if there are any.
+fn unused_components_message(manifest: &AppManifest) -> Option<String> {
+    let unused = manifest.unused_components();
+    if unused.is_empty() {
+        return None;
+    }
+    let ids = unused
+        .iter()
+        .map(|id| format!("`{id}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "No trigger uses the component(s) {ids}, so they will never run. \
+        Add a trigger for each component, or remove it from the manifest."
+    ))
+}
+
 fn locked_variable(variable: v2::Variable) -> Result<locked::Variable> {
     ensure!(
         variable.required ^ variable.default.is_some(),
@@ -692,11 +919,26 @@ impl WasmLoader {
         component_id: &str,
         source: &v2::ComponentSource,
     ) -> Result<PathBuf> {
+        let (path, _) = self
+            .load_component_source_and_digest(component_id, source)
+            .await?;
+        Ok(path)
+    }
+
+    /// Like [`Self::load_component_source`], but also returns the SHA-256
+    /// digest of the content if it is known without reading it, i.e. for
+    /// remote and registry sources.
+    pub(crate) async fn load_component_source_and_digest(
+        &self,
+        component_id: &str,
+        source: &v2::ComponentSource,
+    ) -> Result<(PathBuf, Option<String>)> {
         let content = match source {
-            v2::ComponentSource::Local(path) => self.app_root.join(path),
-            v2::ComponentSource::Remote { url, digest } => {
-                self.load_http_source(url, digest).await?
-            }
+            v2::ComponentSource::Local(path) => (self.app_root.join(path), None),
+            v2::ComponentSource::Remote { url, digest } => (
+                self.load_http_source(url, digest).await?,
+                Some(digest.clone()),
+            ),
             v2::ComponentSource::Registry {
                 registry,
                 package,
@@ -705,8 +947,10 @@ impl WasmLoader {
                 let version = semver::Version::parse(version).with_context(|| format!("Component {component_id} specifies an invalid semantic version ({version:?}) for its package version"))?;
                 let version_req = format!("={version}").parse().expect("version");
 
-                self.load_registry_source(registry.as_ref(), package, &version_req)
-                    .await?
+                let (path, digest) = self
+                    .load_registry_source(registry.as_ref(), package, &version_req)
+                    .await?;
+                (path, Some(digest))
             }
         };
         Ok(content)
@@ -739,12 +983,14 @@ impl WasmLoader {
         Ok(path)
     }
 
+    // Load the latest matching release of a package from a registry and
+    // return the path to the local copy, and the digest of the release.
     async fn load_registry_source(
         &self,
         registry: Option<&wasm_pkg_client::Registry>,
         package: &wasm_pkg_client::PackageRef,
         version: &semver::VersionReq,
-    ) -> Result<PathBuf> {
+    ) -> Result<(PathBuf, String)> {
         let mut client_config = wasm_pkg_client::Config::global_defaults().await?;
 
         if let Some(registry) = registry.cloned() {
@@ -796,7 +1042,7 @@ impl WasmLoader {
             dest
         };
 
-        Ok(path)
+        Ok((path, digest))
     }
 
     /// Loads a dependency
@@ -814,7 +1060,7 @@ impl WasmLoader {
                 // dependency name is not a kebab id.
                 let package = dependency_name.package().unwrap();
 
-                let content = self.load_registry_source(None, package, &version).await?;
+                let (content, _) = self.load_registry_source(None, package, &version).await?;
                 Ok((content, None))
             }
             v2::ComponentDependency::Package {
@@ -850,7 +1096,7 @@ impl WasmLoader {
                     None => None,
                 };
 
-                let content = self
+                let (content, _) = self
                     .load_registry_source(registry.as_ref(), &package, &version)
                     .await?;
                 Ok((content, export))
@@ -890,6 +1136,27 @@ fn file_url(path: impl AsRef<Path>) -> Result<String> {
     Ok(Url::from_file_path(abs_path).unwrap().to_string())
 }
 
+/// Where the manifest says a component's Wasm source comes from.
+fn source_kind(source: &v2::ComponentSource) -> locked::ComponentSourceKind {
+    match source {
+        v2::ComponentSource::Local(path) => {
+            locked::ComponentSourceKind::Local { path: path.clone() }
+        }
+        v2::ComponentSource::Remote { url, .. } => {
+            locked::ComponentSourceKind::Url { url: url.clone() }
+        }
+        v2::ComponentSource::Registry {
+            registry,
+            package,
+            version,
+        } => locked::ComponentSourceKind::Registry {
+            registry: registry.as_ref().map(|r| r.to_string()),
+            package: package.to_string(),
+            version: version.clone(),
+        },
+    }
+}
+
 /// Determines if a component requires the host to support local
 /// service chaining.
 pub fn requires_service_chaining(component: &spin_manifest::schema::v2::Component) -> bool {
@@ -913,6 +1180,8 @@ fn warn_if_component_load_slothful() -> sloth::SlothGuard {
 
 #[cfg(test)]
 mod test {
+    use spin_common::url::parse_file_url;
+
     use super::*;
 
     #[tokio::test]
@@ -938,4 +1207,395 @@ mod test {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn exclude_files_are_applied_in_order() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("exclude-files");
+        let wd = tempfile::tempdir()?;
+        let loader = LocalLoader::new(
+            &app_root,
+            FilesMountStrategy::Copy(wd.path().to_owned()),
+            None,
+        )
+        .await?;
+        loader.load_file(app_root.join("spin.toml")).await?;
+
+        let copied = |component: &str| list_files(&wd.path().join(component));
+
+        // Re-inclusion after a directory exclude, and exclusion within a
+        // directory mount
+        assert_eq!(
+            copied("ordered")?,
+            ["assets/a.txt", "assets/tmp/keep.txt", "static/index.html"]
+        );
+        // Simple manifests behave as before
+        assert_eq!(copied("simple")?, ["assets/a.txt"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn exclude_files_are_applied_to_direct_mounts() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("exclude-files");
+        let staging = tempfile::tempdir()?;
+        let mut loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+        loader.set_direct_mount_staging_root(Some(staging.path().to_owned()));
+        let locked = loader.load_file(app_root.join("direct.toml")).await?;
+
+        let mounts = |component: &str| -> anyhow::Result<Vec<(String, PathBuf)>> {
+            let component = locked
+                .components
+                .iter()
+                .find(|c| c.id == component)
+                .unwrap();
+            component
+                .files
+                .iter()
+                .map(|mount| {
+                    let source = parse_file_url(mount.content.source.as_deref().unwrap())?;
+                    Ok((mount.path.to_string_lossy().into_owned(), source))
+                })
+                .collect()
+        };
+
+        // Directories containing excluded files are staged without them
+        let filtered = mounts("filtered")?;
+        assert_eq!(filtered[0].0, "/static");
+        assert!(filtered[0].1.starts_with(staging.path()));
+        assert_eq!(list_files(&filtered[0].1)?, ["index.html"]);
+        assert_eq!(filtered[1].0, "assets");
+        assert!(filtered[1].1.starts_with(staging.path()));
+        assert_eq!(list_files(&filtered[1].1)?, ["a.txt", "tmp/keep.txt"]);
+
+        // Directories without excluded files are still mounted directly
+        let unfiltered = mounts("unfiltered")?;
+        assert_eq!(unfiltered.len(), 1);
+        assert_eq!(
+            unfiltered[0].1,
+            safe_canonicalize(&app_root.join("assets"))?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn direct_mounts_with_excluded_files_need_a_staging_root() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("exclude-files");
+        let loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+        let err = loader
+            .load_file(app_root.join("direct.toml"))
+            .await
+            .expect_err("loader should not have succeeded");
+        let err_ctx = format!("{err:#}");
+        assert!(
+            err_ctx.contains("excluded by `exclude_files`"),
+            "expected error about the excluded file but got {err_ctx}",
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dependencies_inherit_filtered_files() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("exclude-files");
+        let staging = tempfile::tempdir()?;
+        let mut loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+        loader.set_direct_mount_staging_root(Some(staging.path().to_owned()));
+        let locked = loader.load_file(app_root.join("direct.toml")).await?;
+
+        // Dependencies see the files of the component which they inherit
+        // configuration from, which must not include the excluded files
+        let component = locked
+            .components
+            .iter()
+            .find(|c| c.id == "with-dependency")
+            .unwrap();
+        let (_, dependency) = component.dependencies.iter().next().unwrap();
+        assert!(matches!(
+            dependency.inherit,
+            locked::InheritConfiguration::All
+        ));
+        assert_eq!(component.files.len(), 1);
+        let source = parse_file_url(component.files[0].content.source.as_deref().unwrap())?;
+        assert_eq!(list_files(&source)?, ["index.html"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sqlite_migrations_are_embedded_in_order() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("sqlite-migrations");
+        let wd = tempfile::tempdir()?;
+        let loader = LocalLoader::new(
+            &app_root,
+            FilesMountStrategy::Copy(wd.path().to_owned()),
+            None,
+        )
+        .await?;
+        let locked = loader.load_file(app_root.join("spin.toml")).await?;
+
+        let migrations = &locked.metadata["sqlite_migrations"]["default"];
+        assert_eq!(
+            migrations,
+            &serde_json::json!([
+                {
+                    "name": "migrations/002_add_owners.sql",
+                    "sql": "CREATE TABLE owners (name TEXT NOT NULL);\n",
+                },
+                {
+                    "name": "migrations/001_create_pets.sql",
+                    "sql": "CREATE TABLE pets (name TEXT NOT NULL);\n",
+                },
+            ])
+        );
+
+        let mut manifest = spin_manifest::manifest_from_file(app_root.join("spin.toml"))?;
+        manifest.application.sqlite_migrations["default"].push("migrations/missing.sql".into());
+        let err = loader
+            .load_manifest(manifest)
+            .await
+            .expect_err("loader should not have succeeded");
+        let err_ctx = format!("{err:#}");
+        assert!(
+            err_ctx.contains("migrations/missing.sql"),
+            "expected error to name the missing migration but got {err_ctx}",
+        );
+
+        for outside in ["../exclude-files/spin.toml", "/etc/hostname"] {
+            let mut manifest = spin_manifest::manifest_from_file(app_root.join("spin.toml"))?;
+            manifest.application.sqlite_migrations["default"].push(outside.into());
+            let err = loader
+                .load_manifest(manifest)
+                .await
+                .expect_err("loader should not have succeeded");
+            let err_ctx = format!("{err:#}");
+            assert!(
+                err_ctx.contains("outside the application directory"),
+                "expected error for {outside} to reject it but got {err_ctx}",
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn exclude_files_outside_app_dir_are_rejected() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("exclude-files");
+        let wd = tempfile::tempdir()?;
+        let loader = LocalLoader::new(
+            &app_root,
+            FilesMountStrategy::Copy(wd.path().to_owned()),
+            None,
+        )
+        .await?;
+        let mut manifest = spin_manifest::manifest_from_file(app_root.join("spin.toml"))?;
+        let component = manifest.components.values_mut().next().unwrap();
+        component.exclude_files = vec!["../file-errors/*".into()];
+        let err = loader
+            .load_manifest(manifest)
+            .await
+            .expect_err("loader should not have succeeded");
+        let err_ctx = format!("{err:#}");
+        assert!(
+            err_ctx.contains("cannot refer to files outside it"),
+            "expected error about escaping the app dir but got {err_ctx}",
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unused_components_are_an_error_only_when_strict() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("unused-components");
+        let manifest = spin_manifest::manifest_from_file(app_root.join("spin.toml"))?;
+        let mut loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+
+        // The inline component is not reported
+        let mut normalized = manifest.clone();
+        spin_manifest::normalize::normalize_manifest(&mut normalized);
+        let message = unused_components_message(&normalized).expect("expected a warning");
+        assert!(message.contains("`orphan`"), "{message}");
+        assert!(!message.contains("inline"), "{message}");
+
+        let locked = loader.load_manifest(manifest.clone()).await?;
+        assert_eq!(locked.components.len(), 3);
+
+        loader.set_strict_components(true);
+        let err = loader
+            .load_manifest(manifest)
+            .await
+            .expect_err("strict loader should not have succeeded");
+        assert!(err.to_string().contains("`orphan`"), "{err}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn undefined_component_references_are_rejected() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("unused-components");
+        let mut manifest = spin_manifest::manifest_from_file(app_root.join("spin.toml"))?;
+        manifest.triggers["http"][0].component = Some(v2::ComponentSpec::Reference(
+            KebabId::try_from("usde".to_owned()).unwrap(),
+        ));
+        let loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+        let err = loader
+            .load_manifest(manifest)
+            .await
+            .expect_err("loader should not have succeeded");
+        assert!(
+            err.to_string().contains(r#"Did you mean "used"?"#),
+            "expected a suggestion but got {err}",
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn profile_disables_component_and_its_trigger() -> anyhow::Result<()> {
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("profiles");
+        let manifest = spin_manifest::manifest_from_file(app_root.join("spin.toml"))?;
+        let mut loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+
+        // Without a profile, the manifest is loaded as written
+        let locked = loader.load_manifest(manifest.clone()).await?;
+        assert_eq!(locked.components.len(), 2);
+        assert_eq!(locked.triggers.len(), 2);
+
+        loader.set_profile(Some("prod".into()));
+        let locked = loader.load_manifest(manifest.clone()).await?;
+        let component_ids: Vec<_> = locked.components.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(component_ids, ["web"]);
+        assert_eq!(locked.triggers.len(), 1);
+        assert_eq!(locked.triggers[0].trigger_config["component"], "web");
+
+        loader.set_profile(Some("staging".into()));
+        let err = loader
+            .load_manifest(manifest)
+            .await
+            .expect_err("undefined profile should be rejected");
+        assert!(err.to_string().contains("staging"), "{err}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn runtime_variable_values_override_profile_defaults() -> anyhow::Result<()> {
+        use spin_expressions::{async_trait::async_trait, Key, Provider, ProviderResolver};
+
+        #[derive(Debug)]
+        struct CliVariables;
+
+        #[async_trait]
+        impl Provider for CliVariables {
+            async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
+                Ok((key.as_str() == "api_url").then(|| "https://cli.example.com".to_owned()))
+            }
+        }
+
+        let app_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("profiles");
+        let manifest = spin_manifest::manifest_from_file(app_root.join("spin.toml"))?;
+        let mut loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+        loader.set_profile(Some("dev".into()));
+        let locked = loader.load_manifest(manifest).await?;
+
+        let web = locked.components.iter().find(|c| c.id == "web").unwrap();
+        assert_eq!(web.env["LOG_LEVEL"], "debug");
+
+        let resolver_for = |providers: Vec<Box<dyn Provider>>| -> anyhow::Result<_> {
+            let mut resolver = ProviderResolver::new(locked.variables.clone())?;
+            resolver.add_component_variables("web", web.config.clone())?;
+            for provider in providers {
+                resolver.add_provider(provider);
+            }
+            Ok(resolver)
+        };
+
+        let resolver = resolver_for(vec![])?;
+        let value = resolver.resolve("web", Key::new("api_url")?).await?;
+        assert_eq!(value, "http://localhost:3000");
+
+        let resolver = resolver_for(vec![Box::new(CliVariables)])?;
+        let value = resolver.resolve("web", Key::new("api_url")?).await?;
+        assert_eq!(value, "https://cli.example.com");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn build_metadata_round_trips_through_locking_if_current() -> anyhow::Result<()> {
+        let app_dir = tempfile::tempdir()?;
+        let app_root = app_dir.path().canonicalize()?;
+        std::fs::write(
+            app_root.join("spin.toml"),
+            r#"
+            spin_manifest_version = 2
+            [application]
+            name = "built"
+            [[trigger.http]]
+            route = "/..."
+            component = "api"
+            [component.api]
+            source = "api.wasm"
+            build.command = "cargo build"
+            "#,
+        )?;
+        std::fs::write(app_root.join("api.wasm"), "spin")?;
+        let build_metadata = locked::BuildMetadata {
+            built_at: "2024-06-01T12:00:00Z".into(),
+            spin_version: "3.4.0".into(),
+            commands: vec!["cargo build".into()],
+            digest: "sha256:a5a2729ffa0eeacc15323a9168807c72d18d1cb375dbde899c44d6803dad2b19"
+                .into(),
+        };
+        let metadata_path = component_build_metadata_path(&app_root, "api");
+        std::fs::create_dir_all(metadata_path.parent().unwrap())?;
+        std::fs::write(&metadata_path, serde_json::to_vec(&build_metadata)?)?;
+
+        let load = || async {
+            let loader = LocalLoader::new(&app_root, FilesMountStrategy::Direct, None).await?;
+            let locked = loader.load_file(app_root.join("spin.toml")).await?;
+            let locked = LockedApp::from_json(&locked.to_json()?)?;
+            anyhow::Ok(locked.components.into_iter().next().unwrap())
+        };
+
+        let component = load().await?;
+        assert_eq!(
+            component.metadata[locked::SOURCE_KIND_KEY],
+            serde_json::json!({ "type": "local", "path": "api.wasm" })
+        );
+        let locked_build_metadata: locked::BuildMetadata =
+            serde_json::from_value(component.metadata[locked::BUILD_METADATA_KEY].clone())?;
+        assert_eq!(locked_build_metadata, build_metadata);
+        assert_eq!(component.source.content.digest, Some(build_metadata.digest));
+
+        // Rebuilt by other means since
+        std::fs::write(app_root.join("api.wasm"), "spun")?;
+        let component = load().await?;
+        assert!(!component.metadata.contains_key(locked::BUILD_METADATA_KEY));
+        assert_eq!(component.source.content.digest, None);
+        Ok(())
+    }
+
+    /// Lists the files beneath `root`, relative to it.
+    fn list_files(root: &Path) -> anyhow::Result<Vec<String>> {
+        let mut files = glob::glob(&format!("{}/**/*", root.display()))?
+            .filter_map(|path| path.ok().filter(|p| p.is_file()))
+            .map(|path| {
+                let relative = path.strip_prefix(root).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        Ok(files)
+    }
 }
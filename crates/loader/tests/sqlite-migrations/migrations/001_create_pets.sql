@@ -0,0 +1 @@
+CREATE TABLE pets (name TEXT NOT NULL);
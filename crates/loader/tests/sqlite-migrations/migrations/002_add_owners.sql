@@ -0,0 +1 @@
+CREATE TABLE owners (name TEXT NOT NULL);
@@ -293,6 +293,56 @@ pub struct LockedComponentSource {
     pub content: ContentRef,
 }
 
+/// The component metadata key under which the loader records where a
+/// component's Wasm source came from, as a [`ComponentSourceKind`].
+pub const SOURCE_KIND_KEY: &str = "source_kind";
+
+/// The component metadata key under which the loader records how a locally
+/// built component was built, as [`BuildMetadata`].
+pub const BUILD_METADATA_KEY: &str = "build_metadata";
+
+/// Where a component's Wasm source came from, as specified in the manifest.
+///
+/// The locked source itself always refers to a local copy of the content.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ComponentSourceKind {
+    /// A file in the application directory.
+    Local {
+        /// The path of the file, relative to the application directory.
+        path: String,
+    },
+    /// A package from a component registry.
+    Registry {
+        /// The registry, if not the default for the package.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        registry: Option<String>,
+        /// The package name, e.g. `example:component`.
+        package: String,
+        /// The package version.
+        version: String,
+    },
+    /// A file downloaded from a URL.
+    Url {
+        /// The URL of the file.
+        url: String,
+    },
+}
+
+/// A record of the build which produced a component's Wasm source, made by
+/// `spin build`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildMetadata {
+    /// When the build finished, as an RFC 3339 timestamp.
+    pub built_at: String,
+    /// The version of Spin which ran the build.
+    pub spin_version: String,
+    /// The build commands, in the order they were run.
+    pub commands: Vec<String>,
+    /// The SHA-256 digest of the Wasm file produced, e.g. `sha256:abc...`.
+    pub digest: String,
+}
+
 /// A ContentPath specifies content mapped to a WASI path.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ContentPath {
@@ -22,6 +22,8 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
         authors: manifest.authors,
         targets: Default::default(),
         trigger_global_configs,
+        sqlite_migrations: Default::default(),
+        limits: Default::default(),
         tool: Default::default(),
     };
 
@@ -61,12 +63,19 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
                 source: component.source,
                 description: component.description,
                 variables,
+                allowed_application_variables: None,
                 environment: component.environment,
                 files: component.files,
                 exclude_files: component.exclude_files,
+                working_directory: None,
                 key_value_stores: component.key_value_stores,
                 sqlite_databases: component.sqlite_databases,
+                sqlite_read_only_databases: vec![],
+                sqlite_backup: false,
+                sqlite_vector_search: false,
+                sqlite_explain: false,
                 ai_models: component.ai_models,
+                limits: Default::default(),
                 build: component.build,
                 tool: Default::default(),
                 allowed_outbound_hosts,
@@ -91,6 +100,7 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
         variables: app_variables,
         triggers,
         components,
+        profiles: Default::default(),
     })
 }
 
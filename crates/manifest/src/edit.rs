@@ -0,0 +1,431 @@
+//! Editing of Spin manifests which preserves formatting and comments.
+//!
+//! Deserializing a manifest into the [`schema`](crate::schema) types and
+//! serializing it again loses the author's comments and layout. A
+//! [`ManifestEditor`] instead edits the TOML document in place, so anything an
+//! edit doesn't touch is left byte-for-byte as it was.
+//!
+//! Each edit is checked against the schema types before it is applied, and an
+//! edit which would make the manifest invalid is rejected, leaving the
+//! manifest unchanged.
+
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+
+use crate::{
+    schema::v2::{AppManifest, Component},
+    Error, ManifestVersion,
+};
+
+/// An editable Spin manifest.
+///
+/// Only version 2 manifests can be edited.
+#[derive(Clone, Debug)]
+pub struct ManifestEditor {
+    doc: DocumentMut,
+}
+
+impl ManifestEditor {
+    /// Reads a manifest file for editing.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Validates the edited manifest and writes it to the given file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.validate()?;
+        std::fs::write(path, self.doc.to_string())?;
+        Ok(())
+    }
+
+    /// Parses and validates the edited manifest.
+    pub fn validate(&self) -> Result<AppManifest, Error> {
+        let manifest = crate::manifest_from_str(&self.doc.to_string())?;
+        manifest
+            .validate_dependencies()
+            .map_err(Error::ValidationError)?;
+        Ok(manifest)
+    }
+
+    /// Adds a component with the given ID.
+    pub fn add_component(&mut self, id: &str, component: &Component) -> Result<(), Error> {
+        let edit = format!("add component `{id}`");
+        self.edit(&edit, |doc| {
+            let components = implicit_table(doc.as_table_mut(), "component")
+                .ok_or_else(|| reason("`component` is not a table"))?;
+            if components.contains_key(id) {
+                return Err(reason("a component with that ID already exists"));
+            }
+            let mut table = toml_edit::ser::to_document(component)
+                .map_err(|err| reason(format!("cannot serialize the component: {err}")))?
+                .as_table()
+                .clone();
+            if let Some(build) = table.get_mut("build") {
+                if let Some(build_table) = build.as_inline_table().cloned() {
+                    *build = Item::Table(build_table.into_table());
+                }
+            }
+            table.set_implicit(false);
+            table.set_position(next_position(doc.as_table()));
+            let components = implicit_table(doc.as_table_mut(), "component").unwrap();
+            components.insert(id, Item::Table(table));
+            Ok(())
+        })
+    }
+
+    /// Replaces the `allowed_outbound_hosts` of a component.
+    pub fn set_allowed_outbound_hosts(
+        &mut self,
+        component_id: &str,
+        hosts: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<(), Error> {
+        let edit = format!("set allowed outbound hosts of component `{component_id}`");
+        let hosts: Array = hosts.into_iter().map(Into::into).collect::<Array>();
+        self.edit(&edit, |doc| {
+            let component = component_table(doc, component_id)?;
+            set_value(component, "allowed_outbound_hosts", hosts.into());
+            Ok(())
+        })
+    }
+
+    /// Adds a trigger of the given type, e.g. an `[[trigger.http]]` table.
+    pub fn add_trigger(&mut self, trigger_type: &str, trigger: Table) -> Result<(), Error> {
+        let edit = format!("add `{trigger_type}` trigger");
+        self.edit(&edit, |doc| {
+            let mut trigger = trigger;
+            trigger.set_implicit(false);
+            trigger.set_position(next_position(doc.as_table()));
+            let triggers = implicit_table(doc.as_table_mut(), "trigger")
+                .ok_or_else(|| reason("`trigger` is not a table"))?;
+            let typed_triggers = triggers
+                .entry(trigger_type)
+                .or_insert_with(|| Item::ArrayOfTables(Default::default()))
+                .as_array_of_tables_mut()
+                .ok_or_else(|| {
+                    reason(format!(
+                        "`trigger.{trigger_type}` is not an array of tables"
+                    ))
+                })?;
+            typed_triggers.push(trigger);
+            Ok(())
+        })
+    }
+
+    /// Sets the version requirement of a registry dependency in every
+    /// component which depends on it, returning the number of components
+    /// updated.
+    pub fn bump_dependency_version(&mut self, name: &str, version: &str) -> Result<usize, Error> {
+        let edit = format!("set version of dependency `{name}` to `{version}`");
+        semver::VersionReq::parse(version)
+            .map_err(|err| edit_error(&edit, format!("invalid version requirement: {err}")))?;
+        let mut updated = 0;
+        self.edit(&edit, |doc| {
+            let Some(components) = doc.get_mut("component").and_then(Item::as_table_like_mut)
+            else {
+                return Err(reason("the manifest has no components"));
+            };
+            for (component_id, component) in components.iter_mut() {
+                let component_id = component_id.get().to_owned();
+                let Some(dependency) = component
+                    .get_mut("dependencies")
+                    .and_then(|deps| deps.get_mut(name))
+                else {
+                    continue;
+                };
+                let Some(dependency) = dependency.as_value_mut() else {
+                    return Err(reason(format!(
+                        "dependency in component `{component_id}` is not a value"
+                    )));
+                };
+                let current = if dependency.is_str() {
+                    dependency
+                } else {
+                    dependency
+                        .as_inline_table_mut()
+                        .and_then(|table| table.get_mut("version"))
+                        .ok_or_else(|| {
+                            reason(format!(
+                                "dependency in component `{component_id}` is not from a registry"
+                            ))
+                        })?
+                };
+                replace_value(current, version.into());
+                updated += 1;
+            }
+            if updated == 0 {
+                return Err(reason("no component has that dependency"));
+            }
+            Ok(())
+        })?;
+        Ok(updated)
+    }
+
+    /// Applies an edit to a copy of the document, and keeps the copy only if
+    /// the edit succeeds and the edited manifest is valid.
+    fn edit(
+        &mut self,
+        edit: &str,
+        f: impl FnOnce(&mut DocumentMut) -> Result<(), String>,
+    ) -> Result<(), Error> {
+        let mut edited = Self {
+            doc: self.doc.clone(),
+        };
+        f(&mut edited.doc).map_err(|reason| edit_error(edit, reason))?;
+        edited
+            .validate()
+            .map_err(|err| edit_error(edit, format!("the edited manifest is invalid: {err}")))?;
+        *self = edited;
+        Ok(())
+    }
+}
+
+impl FromStr for ManifestEditor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if ManifestVersion::detect(s)? != ManifestVersion::V2 {
+            return Err(Error::InvalidVersion(
+                "only version 2 manifests can be edited".into(),
+            ));
+        }
+        let doc = s.parse().map_err(|err| Error::InvalidEdit {
+            edit: "parse manifest".into(),
+            reason: format!("{err}"),
+        })?;
+        Ok(Self { doc })
+    }
+}
+
+impl Display for ManifestEditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+fn edit_error(edit: &str, reason: impl Into<String>) -> Error {
+    Error::InvalidEdit {
+        edit: edit.to_owned(),
+        reason: reason.into(),
+    }
+}
+
+fn reason(reason: impl Into<String>) -> String {
+    reason.into()
+}
+
+/// Returns the table at `key`, creating it as an implicit table (one without
+/// its own header) if it doesn't exist.
+fn implicit_table<'a>(parent: &'a mut Table, key: &str) -> Option<&'a mut Table> {
+    parent
+        .entry(key)
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+}
+
+fn component_table<'a>(doc: &'a mut DocumentMut, id: &str) -> Result<&'a mut Table, String> {
+    doc.get_mut("component")
+        .and_then(|components| components.get_mut(id))
+        .and_then(Item::as_table_mut)
+        .ok_or_else(|| reason("no component has that ID"))
+}
+
+/// Sets a value in a table, keeping the formatting around an existing value,
+/// such as a trailing comment.
+fn set_value(table: &mut Table, key: &str, value: Value) {
+    match table.get_mut(key).and_then(Item::as_value_mut) {
+        Some(existing) => replace_value(existing, value),
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+}
+
+fn replace_value(existing: &mut Value, mut value: Value) {
+    *value.decor_mut() = existing.decor().clone();
+    *existing = value;
+}
+
+/// The position after every table in the document, so that a new table is
+/// written at the end rather than next to its parent.
+fn next_position(table: &Table) -> usize {
+    fn max_position(table: &Table) -> usize {
+        let mut max = table.position().unwrap_or(0);
+        for (_, item) in table.iter() {
+            match item {
+                Item::Table(table) => max = max.max(max_position(table)),
+                Item::ArrayOfTables(tables) => {
+                    for table in tables.iter() {
+                        max = max.max(max_position(table));
+                    }
+                }
+                _ => {}
+            }
+        }
+        max
+    }
+    max_position(table) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::v2::KebabId;
+
+    const MANIFEST: &str = r#"# The shop app
+spin_manifest_version = 2
+
+[application]
+name = "shop" # keep this name
+version = "1.0.0"
+
+# Routes
+[[trigger.http]]
+route = "/cart/..."
+component = "cart"
+
+[component.cart]
+source = "cart.wasm"
+# Only the payments API
+allowed_outbound_hosts = ["https://payments.example.com"] # reviewed
+key_value_stores = ["default"]
+
+[component.cart.dependencies]
+"shop:tax/calculator" = "0.1.0" # pinned
+"shop:audit/log" = { version = "^1.2", registry = "registry.example.com" }
+"#;
+
+    fn editor() -> ManifestEditor {
+        MANIFEST.parse().unwrap()
+    }
+
+    #[test]
+    fn untouched_manifest_round_trips() {
+        assert_eq!(editor().to_string(), MANIFEST);
+    }
+
+    #[test]
+    fn add_component_appends_table() {
+        let mut editor = editor();
+        let component: Component = toml::from_str(
+            r#"
+            source = "checkout.wasm"
+            allowed_outbound_hosts = ["https://payments.example.com"]
+            build = { command = "cargo build" }
+            "#,
+        )
+        .unwrap();
+        editor.add_component("checkout", &component).unwrap();
+
+        let edited = editor.to_string();
+        assert!(edited.starts_with(MANIFEST), "{edited}");
+        assert!(edited.contains("[component.checkout]"), "{edited}");
+        assert!(edited.contains("[component.checkout.build]"), "{edited}");
+        let manifest = editor.validate().unwrap();
+        let id: KebabId = "checkout".to_owned().try_into().unwrap();
+        assert_eq!(
+            manifest.components[&id].allowed_outbound_hosts,
+            ["https://payments.example.com"]
+        );
+
+        let err = editor.add_component("checkout", &component).unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{err}");
+    }
+
+    #[test]
+    fn set_allowed_outbound_hosts_keeps_comments() {
+        let mut editor = editor();
+        editor
+            .set_allowed_outbound_hosts(
+                "cart",
+                ["https://payments.example.com", "redis://cache:6379"],
+            )
+            .unwrap();
+
+        let edited = editor.to_string();
+        assert_eq!(
+            edited,
+            MANIFEST.replace(
+                r#"allowed_outbound_hosts = ["https://payments.example.com"] # reviewed"#,
+                r#"allowed_outbound_hosts = ["https://payments.example.com", "redis://cache:6379"] # reviewed"#
+            )
+        );
+
+        let err = editor
+            .set_allowed_outbound_hosts("basket", ["https://example.com"])
+            .unwrap_err();
+        assert!(err.to_string().contains("no component"), "{err}");
+    }
+
+    #[test]
+    fn add_trigger_appends_table() {
+        let mut editor = editor();
+        let mut trigger = Table::new();
+        trigger.insert("route", toml_edit::value("/checkout"));
+        trigger.insert("component", toml_edit::value("cart"));
+        editor.add_trigger("http", trigger).unwrap();
+
+        let edited = editor.to_string();
+        assert!(edited.starts_with(MANIFEST), "{edited}");
+        let manifest = editor.validate().unwrap();
+        let routes: Vec<_> = manifest.triggers["http"]
+            .iter()
+            .map(|trigger| trigger.config["route"].as_str().unwrap())
+            .collect();
+        assert_eq!(routes, ["/cart/...", "/checkout"]);
+    }
+
+    #[test]
+    fn bump_dependency_version_keeps_formatting() {
+        let mut editor = editor();
+        assert_eq!(
+            editor
+                .bump_dependency_version("shop:tax/calculator", "0.2.0")
+                .unwrap(),
+            1
+        );
+        editor
+            .bump_dependency_version("shop:audit/log", "^1.3")
+            .unwrap();
+
+        let edited = editor.to_string();
+        let expected = MANIFEST
+            .replace(
+                r#""shop:tax/calculator" = "0.1.0" # pinned"#,
+                r#""shop:tax/calculator" = "0.2.0" # pinned"#,
+            )
+            .replace(r#"version = "^1.2""#, r#"version = "^1.3""#);
+        assert_eq!(edited, expected);
+
+        assert!(editor
+            .bump_dependency_version("shop:missing/dep", "1.0.0")
+            .is_err());
+        assert!(editor
+            .bump_dependency_version("shop:tax/calculator", "not a version")
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_edits_are_rejected() {
+        let mut editor = editor();
+        let component: Component = toml::from_str(r#"source = "checkout.wasm""#).unwrap();
+        let err = editor
+            .add_component("Not_Kebab_Case", &component)
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidEdit { .. }), "{err}");
+        assert!(err.to_string().contains("invalid"), "{err}");
+
+        let mut trigger = Table::new();
+        trigger.insert("route", toml_edit::value("/checkout"));
+        trigger.insert("component", toml_edit::value(42));
+        assert!(editor.add_trigger("http", trigger).is_err());
+
+        // Rejected edits leave the manifest unchanged
+        assert_eq!(editor.to_string(), MANIFEST);
+        editor.validate().unwrap();
+    }
+}
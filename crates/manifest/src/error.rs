@@ -34,6 +34,15 @@ pub enum Error {
         reason: String,
     },
 
+    /// An edit which cannot be made, or would make the manifest invalid
+    #[error("cannot {edit}: {reason}")]
+    InvalidEdit {
+        /// A description of the edit
+        edit: String,
+        /// The reason why the edit cannot be made
+        reason: String,
+    },
+
     /// Invalid manifest version
     #[error("invalid manifest version: {0}")]
     InvalidVersion(String),
@@ -3,8 +3,10 @@
 #![deny(missing_docs)]
 
 pub mod compat;
+pub mod edit;
 pub mod error;
 pub mod normalize;
+pub mod profile;
 pub mod schema;
 
 use std::path::Path;
@@ -0,0 +1,237 @@
+//! Applying manifest profiles.
+
+use anyhow::Context;
+
+use crate::schema::v2::{AppManifest, KebabId};
+
+/// A trigger removed by [`apply_profile`] because it used a component which
+/// the profile disables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DroppedTrigger {
+    /// The type of the trigger, e.g. `http`.
+    pub trigger_type: String,
+    /// The ID of the trigger.
+    pub trigger_id: String,
+    /// The disabled component which the trigger used.
+    pub component: KebabId,
+}
+
+/// Applies the named `[profile.<name>]` to the manifest:
+/// - Overrides the defaults of application variables. A variable given a
+///   value by the profile is no longer required.
+/// - Extends or replaces component `environment` entries, and replaces
+///   component `allowed_outbound_hosts`.
+/// - Removes disabled components, along with any triggers which use them.
+///
+/// The manifest should already be normalized, so that all triggers have IDs
+/// and refer to their components by ID. Returns the triggers which were
+/// removed.
+pub fn apply_profile(
+    manifest: &mut AppManifest,
+    name: &str,
+) -> anyhow::Result<Vec<DroppedTrigger>> {
+    let profile = KebabId::try_from(name.to_owned())
+        .ok()
+        .and_then(|id| manifest.profiles.get(&id))
+        .cloned()
+        .with_context(|| {
+            let defined = manifest
+                .profiles
+                .keys()
+                .map(|id| format!("\"{id}\""))
+                .collect::<Vec<_>>();
+            if defined.is_empty() {
+                format!("Profile {name:?} is not defined: the manifest has no profiles")
+            } else {
+                format!(
+                    "Profile {name:?} is not defined. Defined profiles are: {}",
+                    defined.join(", ")
+                )
+            }
+        })?;
+
+    for (variable_name, value) in profile.variables {
+        let variable = manifest.variables.get_mut(&variable_name).with_context(|| {
+            format!(
+                "Profile {name:?} sets variable \"{variable_name}\", which is not defined in the `[variables]` table"
+            )
+        })?;
+        variable.default = Some(value);
+        variable.required = false;
+    }
+
+    let mut disabled = vec![];
+    for (component_id, overrides) in profile.components {
+        let Some(component) = manifest.components.get_mut(&component_id) else {
+            let suggestion = manifest
+                .closest_component_id(&component_id)
+                .map(|closest| format!(" Did you mean \"{closest}\"?"))
+                .unwrap_or_default();
+            anyhow::bail!(
+                "Profile {name:?} configures component \"{component_id}\", which is not defined in the manifest.{suggestion}"
+            );
+        };
+        component.environment.extend(overrides.environment);
+        if let Some(allowed_outbound_hosts) = overrides.allowed_outbound_hosts {
+            component.allowed_outbound_hosts = allowed_outbound_hosts;
+        }
+        if overrides.enabled == Some(false) {
+            disabled.push(component_id);
+        }
+    }
+
+    let mut dropped = vec![];
+    for (trigger_type, triggers) in &mut manifest.triggers {
+        triggers.retain(|trigger| {
+            let Some(component) = trigger
+                .component_references()
+                .find(|id| disabled.contains(id))
+            else {
+                return true;
+            };
+            dropped.push(DroppedTrigger {
+                trigger_type: trigger_type.clone(),
+                trigger_id: trigger.id.clone(),
+                component: component.clone(),
+            });
+            false
+        });
+    }
+    manifest.triggers.retain(|_, triggers| !triggers.is_empty());
+    for id in &disabled {
+        manifest.components.shift_remove(id);
+    }
+
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_serde::LowerSnakeId;
+
+    use super::*;
+
+    fn manifest() -> AppManifest {
+        let mut manifest: AppManifest = toml::from_str(
+            r#"
+            spin_manifest_version = 2
+            [application]
+            name = "profiles"
+
+            [variables]
+            api_url = { default = "https://api.example.com" }
+            token = { required = true }
+
+            [[trigger.http]]
+            route = "/..."
+            component = "web"
+
+            [[trigger.http]]
+            route = "/mock/..."
+            component = "mock-api"
+
+            [[trigger.redis]]
+            channel = "events"
+            component = "mock-api"
+
+            [component.web]
+            source = "web.wasm"
+            environment = { LOG_LEVEL = "info", REGION = "eu" }
+            allowed_outbound_hosts = ["https://api.example.com"]
+
+            [component.mock-api]
+            source = "mock.wasm"
+
+            [profile.dev]
+            variables = { api_url = "http://localhost:3000", token = "dev-token" }
+
+            [profile.dev.component.web]
+            environment = { LOG_LEVEL = "debug" }
+            allowed_outbound_hosts = ["http://localhost:3000"]
+
+            [profile.prod.component.mock-api]
+            enabled = false
+            "#,
+        )
+        .unwrap();
+        crate::normalize::normalize_manifest(&mut manifest);
+        manifest
+    }
+
+    fn id(id: &str) -> KebabId {
+        KebabId::try_from(id.to_owned()).unwrap()
+    }
+
+    fn var(name: &str) -> LowerSnakeId {
+        LowerSnakeId::try_from(name.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn disabling_component_drops_its_triggers() {
+        let mut manifest = manifest();
+        let dropped = apply_profile(&mut manifest, "prod").unwrap();
+
+        assert!(!manifest.components.contains_key(&id("mock-api")));
+        assert!(manifest.components.contains_key(&id("web")));
+        assert_eq!(manifest.triggers["http"].len(), 1);
+        assert!(!manifest.triggers.contains_key("redis"));
+        assert_eq!(
+            dropped
+                .iter()
+                .map(|d| (d.trigger_type.as_str(), d.component.as_ref()))
+                .collect::<Vec<_>>(),
+            [("http", "mock-api"), ("redis", "mock-api")]
+        );
+        manifest.validate_component_references().unwrap();
+    }
+
+    #[test]
+    fn overrides_variables_and_component_settings() {
+        let mut manifest = manifest();
+        let dropped = apply_profile(&mut manifest, "dev").unwrap();
+        assert!(dropped.is_empty());
+
+        let api_url = &manifest.variables[&var("api_url")];
+        assert_eq!(api_url.default.as_deref(), Some("http://localhost:3000"));
+        let token = &manifest.variables[&var("token")];
+        assert_eq!(token.default.as_deref(), Some("dev-token"));
+        assert!(!token.required);
+
+        let web = &manifest.components[&id("web")];
+        assert_eq!(web.environment["LOG_LEVEL"], "debug");
+        assert_eq!(web.environment["REGION"], "eu");
+        assert_eq!(web.allowed_outbound_hosts, ["http://localhost:3000"]);
+        assert_eq!(manifest.components.len(), 2);
+    }
+
+    #[test]
+    fn unknown_profile_lists_defined_profiles() {
+        let err = apply_profile(&mut manifest(), "staging").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("\"dev\""), "unexpected error: {message}");
+        assert!(message.contains("\"prod\""), "unexpected error: {message}");
+    }
+
+    #[test]
+    fn overrides_must_refer_to_defined_items() {
+        let mut manifest = manifest();
+        manifest.profiles[&id("dev")]
+            .variables
+            .insert(var("api_ulr"), "x".into());
+        let err = apply_profile(&mut manifest, "dev").unwrap_err();
+        assert!(
+            err.to_string().contains("api_ulr"),
+            "unexpected error: {err}"
+        );
+
+        let mut manifest = self::manifest();
+        manifest.profiles[&id("prod")]
+            .components
+            .insert(id("wbe"), Default::default());
+        let err = apply_profile(&mut manifest, "prod").unwrap_err();
+        assert!(
+            err.to_string().contains("Did you mean \"web\"?"),
+            "unexpected error: {err}"
+        );
+    }
+}
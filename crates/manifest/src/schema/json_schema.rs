@@ -13,6 +13,15 @@ pub struct TriggerSchema {
     /// Redis triggers
     #[schemars(default)]
     redis: Vec<RedisTriggerSchema>,
+    /// Kafka triggers
+    #[schemars(default)]
+    kafka: Vec<KafkaTriggerSchema>,
+    /// WebSocket triggers
+    #[schemars(default)]
+    websocket: Vec<WebSocketTriggerSchema>,
+    /// gRPC triggers
+    #[schemars(default)]
+    grpc: Vec<GrpcTriggerSchema>,
 }
 
 #[allow(dead_code)]
@@ -30,9 +39,77 @@ pub struct HttpTriggerSchema {
     pub components: Map<String, OneOrManyComponentSpecs>,
     /// `route = "/user/:name/..."`
     route: HttpRouteSchema,
+    /// `host = "api.example.com"` or `host = ["example.com", "*.example.com"]`
+    #[schemars(default)]
+    host: Option<HttpHostSchema>,
     /// `executor = { type = "wagi" }
     #[schemars(default, schema_with = "toml_table")]
     executor: Option<toml::Table>,
+    /// `auth = { type = "bearer", token_variable = "admin_token" }`
+    #[schemars(default, schema_with = "toml_table")]
+    auth: Option<toml::Table>,
+    /// `write_idle_timeout_secs = 300`
+    #[schemars(default)]
+    write_idle_timeout_secs: Option<u64>,
+    /// `methods = ["GET", "POST"]`
+    #[schemars(default)]
+    methods: Option<Vec<String>>,
+    /// `auto_head = false`
+    #[schemars(default)]
+    auto_head: Option<bool>,
+    /// `inject_request_id = true`
+    #[schemars(default)]
+    inject_request_id: bool,
+    /// `decompress_request_body = true`
+    #[schemars(default)]
+    decompress_request_body: bool,
+    /// `compress_response = true`
+    #[schemars(default)]
+    compress_response: bool,
+    /// `min_compress_size_bytes = 1024`
+    #[schemars(default)]
+    min_compress_size_bytes: Option<usize>,
+    /// `body_handling = "buffer"`
+    #[schemars(default)]
+    body_handling: Option<String>,
+    /// `max_memory_bytes = 1048576`
+    #[schemars(default)]
+    max_memory_bytes: Option<u64>,
+    /// `max_total_bytes = 104857600`
+    #[schemars(default)]
+    max_total_bytes: Option<u64>,
+    /// `shadow = { component = "checkout-v2", percent = 5, timeout_ms = 2000 }`
+    #[schemars(default)]
+    shadow: Option<HttpShadowSchema>,
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct HttpShadowSchema {
+    /// The component which is sent a copy of sampled requests, with its responses
+    /// discarded. It must have an HTTP trigger of its own, e.g. with
+    /// `route = { private = true }`. Its outbound requests and other side effects
+    /// are not suppressed.
+    ///
+    /// Example: `component = "checkout-v2"`
+    component: String,
+    /// The percentage of requests to copy, from 0 to 100.
+    ///
+    /// Example: `percent = 5`
+    percent: f64,
+    /// The time in milliseconds the shadow component may take before it is abandoned.
+    /// Defaults to 5000.
+    ///
+    /// Example: `timeout_ms = 2000`
+    #[schemars(default)]
+    timeout_ms: Option<u64>,
+    /// The size in bytes above which request bodies are not copied, and the request
+    /// is not shadowed. Defaults to 1 MiB.
+    ///
+    /// Example: `max_body_bytes = 65536`
+    #[schemars(default)]
+    max_body_bytes: Option<usize>,
 }
 
 #[allow(dead_code)]
@@ -61,6 +138,21 @@ pub enum HttpRouteSchema {
     Private(HttpPrivateEndpoint),
 }
 
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(untagged)]
+pub enum HttpHostSchema {
+    /// The host that the trigger accepts requests for. If omitted, the trigger
+    /// accepts requests for any host. A leading `*.` matches any subdomain.
+    ///
+    /// Example: `host = "api.example.com"`
+    Host(String),
+    /// The hosts that the trigger accepts requests for.
+    ///
+    /// Example: `host = ["example.com", "*.example.com"]`
+    Hosts(Vec<String>),
+}
+
 #[allow(dead_code)]
 #[derive(JsonSchema)]
 #[schemars(deny_unknown_fields)]
@@ -89,6 +181,121 @@ pub struct RedisTriggerSchema {
     address: Option<String>,
 }
 
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct KafkaTriggerSchema {
+    /// `id = "trigger-id"`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// `component = ...`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<ComponentSpec>,
+    /// `components = { ... }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub components: Map<String, OneOrManyComponentSpecs>,
+    /// `topics = ["orders", "refunds"]`
+    topics: Vec<String>,
+    /// `bootstrap_servers = "broker-1:9092,broker-2:9092"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bootstrap_servers: Option<String>,
+    /// `group_id = "order-processors"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group_id: Option<String>,
+    /// The protocol used to communicate with brokers: "plaintext" (the default), "ssl",
+    /// "sasl_plaintext" or "sasl_ssl".
+    ///
+    /// Example: `security_protocol = "sasl_ssl"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    security_protocol: Option<String>,
+    /// `sasl = { mechanism = "SCRAM-SHA-512", username = "spin", password = "{{ kafka_password }}" }`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sasl: Option<KafkaSaslSchema>,
+    /// A PEM file of CA certificates for verifying the brokers' certificates.
+    ///
+    /// Example: `ca_cert_path = "/etc/kafka/ca.pem"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ca_cert_path: Option<String>,
+    /// The number of times a message is delivered to the component before it is
+    /// dead-lettered (or skipped if there is no dead letter topic). Defaults to 3.
+    ///
+    /// Example: `max_attempts = 5`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_attempts: Option<u32>,
+    /// `dead_letter_topic = "orders-failed"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dead_letter_topic: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct KafkaSaslSchema {
+    /// The SASL mechanism: "PLAIN", "SCRAM-SHA-256" or "SCRAM-SHA-512".
+    ///
+    /// Example: `mechanism = "SCRAM-SHA-512"`
+    mechanism: String,
+    /// The username, which may reference application variables.
+    ///
+    /// Example: `username = "{{ kafka_username }}"`
+    username: String,
+    /// The password, which may reference application variables.
+    ///
+    /// Example: `password = "{{ kafka_password }}"`
+    password: String,
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct WebSocketTriggerSchema {
+    /// `id = "trigger-id"`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// `component = ...`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<ComponentSpec>,
+    /// `components = { ... }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub components: Map<String, OneOrManyComponentSpecs>,
+    /// `route = "/chat/..."`
+    route: String,
+    /// The label of the group that connections belong to. Connections opened on
+    /// different routes share a group if their labels match. Defaults to the
+    /// request path, so each path under a wildcard route is its own group.
+    ///
+    /// Example: `group = "lobby"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    /// Whether the messages the component returns are sent to every connection
+    /// in the group, rather than only to the connection that sent the message.
+    ///
+    /// Example: `broadcast = true`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    broadcast: bool,
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct GrpcTriggerSchema {
+    /// `id = "trigger-id"`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// `component = ...`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<ComponentSpec>,
+    /// `components = { ... }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub components: Map<String, OneOrManyComponentSpecs>,
+    /// The fully qualified name of the proto service method that the component
+    /// handles, in the form `package.Service/Method`. The component is passed
+    /// the encoded request message and returns the encoded response message.
+    ///
+    /// Example: `method = "helloworld.Greeter/SayHello"`
+    method: String,
+}
+
 /// The SQLite databases which the component is allowed to access. Databases are identified
 /// by label e.g. "default" or "analytics". Databases other than "default" must be mapped
 /// to a backing store in the runtime config. Use "spin up --sqlite" to run database setup scripts.
@@ -1,9 +1,10 @@
 use std::ops::Range;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 use anyhow::{bail, ensure, Context as _};
 use futures_util::future::{BoxFuture, Shared};
-use spin_expressions::Resolver;
+use spin_expressions::{ProviderResolver, Resolver};
 use url::Host;
 
 /// The domain used for service chaining.
@@ -17,10 +18,21 @@ pub type SharedFutureResult<T> = Shared<BoxFuture<'static, Result<Arc<T>, Arc<an
 /// A check for whether a URL is allowed by the outbound networking configuration.
 #[derive(Clone)]
 pub struct OutboundAllowedHosts {
-    allowed_hosts_future: SharedFutureResult<AllowedHostsConfig>,
+    source: AllowedHostsSource,
     disallowed_host_handler: Option<Arc<dyn DisallowedHostHandler>>,
 }
 
+#[derive(Clone)]
+enum AllowedHostsSource {
+    /// Resolved once, on first use
+    Future(SharedFutureResult<AllowedHostsConfig>),
+    /// Re-resolved whenever the shared config is due a refresh
+    Refreshing {
+        allowed_hosts: Arc<RefreshingAllowedHosts>,
+        resolver: Arc<ProviderResolver>,
+    },
+}
+
 impl OutboundAllowedHosts {
     /// Creates a new `OutboundAllowedHosts` instance.
     pub fn new(
@@ -28,7 +40,24 @@ impl OutboundAllowedHosts {
         disallowed_host_handler: Option<Arc<dyn DisallowedHostHandler>>,
     ) -> Self {
         Self {
-            allowed_hosts_future,
+            source: AllowedHostsSource::Future(allowed_hosts_future),
+            disallowed_host_handler,
+        }
+    }
+
+    /// Creates a new `OutboundAllowedHosts` instance which checks against the
+    /// given shared [`RefreshingAllowedHosts`], using `resolver` to resolve
+    /// any templates when a refresh is due.
+    pub fn refreshing(
+        allowed_hosts: Arc<RefreshingAllowedHosts>,
+        resolver: Arc<ProviderResolver>,
+        disallowed_host_handler: Option<Arc<dyn DisallowedHostHandler>>,
+    ) -> Self {
+        Self {
+            source: AllowedHostsSource::Refreshing {
+                allowed_hosts,
+                resolver,
+            },
             disallowed_host_handler,
         }
     }
@@ -58,6 +87,13 @@ impl OutboundAllowedHosts {
         Ok(is_allowed)
     }
 
+    /// Returns the highest MQTT quality of service the allowed hosts permit
+    /// for `url`, or `None` if they don't limit it.
+    pub async fn max_qos(&self, url: &str) -> anyhow::Result<Option<QosLevel>> {
+        let url = OutboundUrl::parse(url, "mqtt")?;
+        Ok(self.resolve().await?.max_qos(&url))
+    }
+
     /// Checks if allowed hosts permit relative requests
     ///
     /// Calls the [`DisallowedHostHandler`] if set and relative requests are
@@ -77,10 +113,13 @@ impl OutboundAllowedHosts {
     }
 
     async fn resolve(&self) -> anyhow::Result<Arc<AllowedHostsConfig>> {
-        self.allowed_hosts_future
-            .clone()
-            .await
-            .map_err(anyhow::Error::msg)
+        match &self.source {
+            AllowedHostsSource::Future(future) => future.clone().await.map_err(anyhow::Error::msg),
+            AllowedHostsSource::Refreshing {
+                allowed_hosts,
+                resolver,
+            } => allowed_hosts.get(resolver).await,
+        }
     }
 
     fn report_disallowed_host(&self, scheme: &str, authority: &str) {
@@ -90,6 +129,87 @@ impl OutboundAllowedHosts {
     }
 }
 
+/// An allowed_outbound_hosts config which is shared between instances and
+/// periodically re-resolved, so that templated entries follow changes to the
+/// variables they use.
+///
+/// The config is re-resolved on the first check after `refresh_interval` has
+/// elapsed. A new config replaces the old one as a whole, so a check sees
+/// either the old or the new config. If re-resolution fails, the previous
+/// config is kept (and retried after another interval).
+pub struct RefreshingAllowedHosts {
+    hosts: Arc<[String]>,
+    refresh_interval: Duration,
+    /// The current config and when it was resolved
+    current: RwLock<Option<(Instant, Arc<AllowedHostsConfig>)>>,
+    /// Held while re-resolving, so that concurrent checks share one refresh
+    refreshing: futures_util::lock::Mutex<()>,
+}
+
+impl RefreshingAllowedHosts {
+    /// Creates a new `RefreshingAllowedHosts` for the given raw
+    /// allowed_outbound_hosts values.
+    pub fn new(hosts: Arc<[String]>, refresh_interval: Duration) -> Self {
+        Self {
+            hosts,
+            refresh_interval,
+            current: Default::default(),
+            refreshing: Default::default(),
+        }
+    }
+
+    /// Returns the current config, first re-resolving it with `resolver` if
+    /// it is due a refresh.
+    pub async fn get(
+        &self,
+        resolver: &ProviderResolver,
+    ) -> anyhow::Result<Arc<AllowedHostsConfig>> {
+        if let Some(config) = self.fresh() {
+            return Ok(config);
+        }
+        let _refreshing = self.refreshing.lock().await;
+        // Another check may have refreshed the config while we waited
+        if let Some(config) = self.fresh() {
+            return Ok(config);
+        }
+
+        let resolved = match resolver.prepare().await {
+            Ok(prepared) => AllowedHostsConfig::parse(&self.hosts, &prepared).map_err(Into::into),
+            Err(err) => Err(err.into()),
+        };
+        let mut current = self.current.write().unwrap();
+        let config = match (resolved, current.take()) {
+            (Ok(config), _) => Arc::new(config),
+            (Err(err), Some((_, previous))) => {
+                tracing::warn!(
+                    %err, "error.type" = "allowed_hosts_refresh_failed",
+                    "Error refreshing allowed outbound hosts; keeping the previous config",
+                );
+                previous
+            }
+            (Err(err), None) => {
+                tracing::error!(
+                    %err, "error.type" = "invalid_allowed_hosts",
+                    "Error resolving allowed outbound hosts",
+                );
+                return Err(err);
+            }
+        };
+        *current = Some((Instant::now(), config.clone()));
+        Ok(config)
+    }
+
+    /// Returns the current config if it isn't due a refresh.
+    fn fresh(&self) -> Option<Arc<AllowedHostsConfig>> {
+        match &*self.current.read().unwrap() {
+            Some((resolved_at, config)) if resolved_at.elapsed() < self.refresh_interval => {
+                Some(config.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
 /// A trait for handling disallowed hosts
 pub trait DisallowedHostHandler: Send + Sync {
     /// Called when a host is disallowed
@@ -109,41 +229,73 @@ pub struct AllowedHostConfig {
     scheme: SchemeConfig,
     host: HostConfig,
     port: PortConfig,
+    max_qos: Option<QosLevel>,
 }
 
 impl AllowedHostConfig {
     /// Parses the given string as an `allowed_hosts_config` item.
     pub fn parse(url: impl Into<String>) -> anyhow::Result<Self> {
         let original = url.into();
-        let url = original.trim();
+        Self::parse_entry(original.clone()).map_err(|reason| {
+            let help = if reason.needs_format_help() {
+                format!("\n{FORMAT_HELP}")
+            } else {
+                String::new()
+            };
+            anyhow::anyhow!("invalid allowed outbound host {original:?}: {reason}{help}")
+        })
+    }
+
+    /// Parses the given string as an `allowed_hosts_config` item, returning
+    /// what is wrong with it if it is invalid.
+    fn parse_entry(original: String) -> Result<Self, AllowedHostErrorReason> {
+        let (url, options) = original
+            .trim()
+            .split_once('?')
+            .unwrap_or((original.trim(), ""));
         let Some((scheme, rest)) = url.split_once("://") else {
-            match url {
-                "*" | ":" | "" | "?" => bail!("{url:?} is not an allowed outbound host format.\nHosts must be in the form <scheme>://<host>[:<port>], with '*' wildcards allowed for each.\nIf you intended to allow all outbound networking, you can use '*://*:*' - this will obviate all network sandboxing.\nLearn more: https://spinframework.dev/v3/http-outbound#granting-http-permissions-to-components"),
-                _ => bail!("{url:?} does not contain a scheme (e.g., 'http://' or '*://')\nLearn more: https://spinframework.dev/v3/http-outbound#granting-http-permissions-to-components"),
-            }
+            return match url {
+                "*" | ":" | "" | "?" => Err(AllowedHostErrorReason::NotAHostPattern),
+                _ => Err(AllowedHostErrorReason::MissingScheme),
+            };
         };
         let (host, rest) = rest.rsplit_once(':').unwrap_or((rest, ""));
         let port = match rest.split_once('/') {
             Some((port, path)) => {
                 if !path.is_empty() {
-                    bail!("{url:?} has a path but is not allowed to");
+                    return Err(AllowedHostErrorReason::PathNotAllowed);
                 }
                 port
             }
             None => rest,
         };
 
-        let port = PortConfig::parse(port, scheme)
-            .with_context(|| format!("Invalid allowed host port {port:?}"))?;
-        let scheme = SchemeConfig::parse(scheme)
-            .with_context(|| format!("Invalid allowed host scheme {scheme:?}"))?;
-        let host =
-            HostConfig::parse(host).with_context(|| format!("Invalid allowed host {host:?}"))?;
+        let port =
+            PortConfig::parse(port, scheme).map_err(|err| AllowedHostErrorReason::InvalidPort {
+                port: port.to_owned(),
+                reason: format!("{err:#}"),
+            })?;
+        let scheme =
+            SchemeConfig::parse(scheme).map_err(|err| AllowedHostErrorReason::InvalidScheme {
+                scheme: scheme.to_owned(),
+                reason: format!("{err:#}"),
+            })?;
+        let host = HostConfig::parse(host).map_err(|err| AllowedHostErrorReason::InvalidHost {
+            host: host.to_owned(),
+            reason: format!("{err:#}"),
+        })?;
+        let max_qos = parse_max_qos(options, &scheme).map_err(|err| {
+            AllowedHostErrorReason::InvalidOptions {
+                options: options.to_owned(),
+                reason: format!("{err:#}"),
+            }
+        })?;
 
         Ok(Self {
             scheme,
             host,
             port,
+            max_qos,
             original,
         })
     }
@@ -160,6 +312,11 @@ impl AllowedHostConfig {
         &self.port
     }
 
+    /// The highest MQTT quality of service allowed, if limited.
+    pub fn max_qos(&self) -> Option<QosLevel> {
+        self.max_qos
+    }
+
     /// Returns true if this config is for service chaining requests.
     pub fn is_for_service_chaining(&self) -> bool {
         self.host.is_for_service_chaining()
@@ -181,7 +338,10 @@ impl AllowedHostConfig {
 
 impl PartialEq for AllowedHostConfig {
     fn eq(&self, other: &Self) -> bool {
-        self.scheme == other.scheme && self.host == other.host && self.port == other.port
+        self.scheme == other.scheme
+            && self.host == other.host
+            && self.port == other.port
+            && self.max_qos == other.max_qos
     }
 }
 
@@ -191,6 +351,62 @@ impl std::fmt::Display for AllowedHostConfig {
     }
 }
 
+/// Parses the `?name=value&...` options of an allowed_outbound_hosts item.
+/// The only option is `max_qos`, for MQTT hosts.
+fn parse_max_qos(options: &str, scheme: &SchemeConfig) -> anyhow::Result<Option<QosLevel>> {
+    let mut max_qos = None;
+    for option in options.split('&').filter(|o| !o.is_empty()) {
+        match option.split_once('=') {
+            Some(("max_qos", value)) => {
+                ensure!(
+                    scheme.allows("mqtt"),
+                    "the max_qos option only applies to mqtt hosts"
+                );
+                max_qos = Some(value.parse()?);
+            }
+            _ => bail!("unknown option {option:?}; the only option is max_qos, for mqtt hosts"),
+        }
+    }
+    Ok(max_qos)
+}
+
+/// An MQTT quality of service, as set with the `max_qos` option of an
+/// allowed_outbound_hosts item, e.g. `mqtt://broker.example.com:1883?max_qos=1`,
+/// to limit the quality of service components may use with the broker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QosLevel {
+    /// QoS 0
+    AtMostOnce,
+    /// QoS 1
+    AtLeastOnce,
+    /// QoS 2
+    ExactlyOnce,
+}
+
+impl std::str::FromStr for QosLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Self::AtMostOnce),
+            "1" => Ok(Self::AtLeastOnce),
+            "2" => Ok(Self::ExactlyOnce),
+            _ => bail!("invalid QoS {s:?}; must be 0, 1 or 2"),
+        }
+    }
+}
+
+impl std::fmt::Display for QosLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self {
+            Self::AtMostOnce => 0,
+            Self::AtLeastOnce => 1,
+            Self::ExactlyOnce => 2,
+        };
+        write!(f, "{level}")
+    }
+}
+
 /// Represents the scheme part of an allowed_outbound_hosts item.
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum SchemeConfig {
@@ -410,6 +626,7 @@ fn well_known_port(scheme: &str) -> Option<u16> {
         "mysql" => Some(3306),
         "redis" => Some(6379),
         "mqtt" => Some(1883),
+        "kafka" => Some(9092),
         "http" => Some(80),
         "https" => Some(443),
         _ => None,
@@ -428,30 +645,188 @@ impl PartialAllowedHostConfig {
     fn resolve(
         self,
         resolver: &spin_expressions::PreparedResolver,
-    ) -> anyhow::Result<AllowedHostConfig> {
+    ) -> Result<AllowedHostConfig, AllowedHostErrorReason> {
         match self {
             Self::Exact(h) => Ok(h),
-            Self::Unresolved(t) => AllowedHostConfig::parse(resolver.resolve_template(&t)?),
+            Self::Unresolved(t) => {
+                let resolved = resolver.resolve_template(&t).map_err(|err| {
+                    AllowedHostErrorReason::UnresolvedTemplate(format!("{err:#}"))
+                })?;
+                AllowedHostConfig::parse_entry(resolved.clone()).map_err(|reason| {
+                    AllowedHostErrorReason::InvalidResolvedTemplate {
+                        resolved,
+                        reason: Box::new(reason),
+                    }
+                })
+            }
         }
     }
 
     /// Validates this config. Only templates that can be resolved with default
     /// values from the given resolver will be fully validated.
-    fn validate(&self, resolver: &Resolver) -> anyhow::Result<()> {
+    fn validate(&self, resolver: &Resolver) -> Result<(), AllowedHostErrorReason> {
         if let Self::Unresolved(template) = self {
             let Ok(resolved) = resolver.resolve_template(template) else {
                 // We're missing a default value so we can't validate further
                 return Ok(());
             };
-            AllowedHostConfig::parse(&resolved).with_context(|| {
-                let template_str = template.to_string();
-                format!("using default variable value(s) with template {template_str:?} results in invalid config {resolved:?}")
+            AllowedHostConfig::parse_entry(resolved.clone()).map_err(|reason| {
+                AllowedHostErrorReason::InvalidResolvedTemplate {
+                    resolved,
+                    reason: Box::new(reason),
+                }
             })?;
         }
         Ok(())
     }
 }
 
+/// How to write an allowed_outbound_hosts item, for errors about its format.
+const FORMAT_HELP: &str = "Hosts must be in the form <scheme>://<host>[:<port>], with '*' wildcards allowed for each.\nIf you intended to allow all outbound networking, you can use '*://*:*' - this will obviate all network sandboxing.\nLearn more: https://spinframework.dev/v3/http-outbound#granting-http-permissions-to-components";
+
+/// The invalid items of an allowed_outbound_hosts config.
+#[derive(Debug, Default)]
+pub struct AllowedHostsError {
+    entries: Vec<InvalidAllowedHost>,
+}
+
+impl AllowedHostsError {
+    /// The invalid items, in the order they appear in the config.
+    pub fn entries(&self) -> &[InvalidAllowedHost] {
+        &self.entries
+    }
+
+    fn push(&mut self, index: usize, original: &str, reason: AllowedHostErrorReason) {
+        self.entries.push(InvalidAllowedHost {
+            index,
+            original: original.to_owned(),
+            reason,
+        });
+    }
+
+    fn into_result(self) -> Result<(), Self> {
+        if self.entries.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl std::fmt::Display for AllowedHostsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.entries.len() {
+            1 => write!(f, "1 allowed outbound host is invalid:")?,
+            n => write!(f, "{n} allowed outbound hosts are invalid:")?,
+        }
+        for (n, entry) in (1..).zip(&self.entries) {
+            write!(f, "\n{n}. {entry}")?;
+        }
+        if self
+            .entries
+            .iter()
+            .any(|entry| entry.reason.needs_format_help())
+        {
+            write!(f, "\n{FORMAT_HELP}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AllowedHostsError {}
+
+/// An invalid allowed_outbound_hosts item.
+#[derive(Debug)]
+pub struct InvalidAllowedHost {
+    /// The position of the item in the config, counting from zero.
+    pub index: usize,
+    /// The item as written.
+    pub original: String,
+    /// What is wrong with the item.
+    pub reason: AllowedHostErrorReason,
+}
+
+impl std::fmt::Display for InvalidAllowedHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {:?}: {}", self.index, self.original, self.reason)
+    }
+}
+
+/// What is wrong with an allowed_outbound_hosts item.
+#[derive(Debug)]
+pub enum AllowedHostErrorReason {
+    /// The item is `insecure:allow-all`, which is not allowed.
+    InsecureAllowAll,
+    /// The item is not in the form of a host at all, e.g. `*`.
+    NotAHostPattern,
+    /// The item has no scheme, e.g. `example.com`.
+    MissingScheme,
+    /// The item has a path, e.g. `https://example.com/api`.
+    PathNotAllowed,
+    /// The item's port is missing with no default, or is not a valid port or
+    /// range of ports.
+    InvalidPort { port: String, reason: String },
+    /// The item's scheme is not valid.
+    InvalidScheme { scheme: String, reason: String },
+    /// The item's host is not valid.
+    InvalidHost { host: String, reason: String },
+    /// The item's options, after `?`, are not valid.
+    InvalidOptions { options: String, reason: String },
+    /// The item is a malformed template.
+    InvalidTemplate(String),
+    /// The item is a template whose variables could not be resolved.
+    UnresolvedTemplate(String),
+    /// The item is a template which resolves to an invalid item, with the
+    /// values of its variables or, when validating a manifest, their defaults.
+    InvalidResolvedTemplate {
+        resolved: String,
+        reason: Box<AllowedHostErrorReason>,
+    },
+}
+
+impl AllowedHostErrorReason {
+    /// Whether the item is so far from a valid one that the error should
+    /// explain the format.
+    fn needs_format_help(&self) -> bool {
+        match self {
+            Self::NotAHostPattern | Self::MissingScheme => true,
+            Self::InvalidResolvedTemplate { reason, .. } => reason.needs_format_help(),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for AllowedHostErrorReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InsecureAllowAll => f.write_str(
+                "'insecure:allow-all' is not allowed - use '*://*:*' instead if you really want to allow all outbound traffic",
+            ),
+            Self::NotAHostPattern => f.write_str("is not an allowed outbound host format"),
+            Self::MissingScheme => {
+                f.write_str("does not contain a scheme (e.g., 'http://' or '*://')")
+            }
+            Self::PathNotAllowed => f.write_str("has a path but is not allowed to"),
+            Self::InvalidPort { port, reason } => write!(f, "invalid port {port:?}: {reason}"),
+            Self::InvalidScheme { scheme, reason } => {
+                write!(f, "invalid scheme {scheme:?}: {reason}")
+            }
+            Self::InvalidHost { host, reason } => write!(f, "invalid host {host:?}: {reason}"),
+            Self::InvalidOptions { options, reason } => {
+                write!(f, "invalid options {options:?}: {reason}")
+            }
+            Self::InvalidTemplate(reason) => write!(f, "invalid template: {reason}"),
+            Self::UnresolvedTemplate(reason) => {
+                write!(f, "could not resolve template: {reason}")
+            }
+            Self::InvalidResolvedTemplate { resolved, reason } => write!(
+                f,
+                "template resolves to invalid config {resolved:?}: {reason}"
+            ),
+        }
+    }
+}
+
 /// Represents an allowed_outbound_hosts config.
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub enum AllowedHostsConfig {
@@ -462,44 +837,75 @@ pub enum AllowedHostsConfig {
 impl AllowedHostsConfig {
     /// Parses the given allowed_outbound_hosts values, resolving any templates
     /// with the given resolver.
+    ///
+    /// If any values are invalid, the error describes all of them.
     pub fn parse<S: AsRef<str>>(
         hosts: &[S],
         resolver: &spin_expressions::PreparedResolver,
-    ) -> anyhow::Result<AllowedHostsConfig> {
-        let partial = Self::parse_partial(hosts)?;
-        let allowed = partial
+    ) -> Result<AllowedHostsConfig, AllowedHostsError> {
+        let mut errors = AllowedHostsError::default();
+        let allowed = Self::parse_partial(hosts, &mut errors)
             .into_iter()
-            .map(|p| p.resolve(resolver))
-            .collect::<anyhow::Result<Vec<_>>>()?;
+            .filter_map(|(index, partial)| match partial.resolve(resolver) {
+                Ok(allowed) => Some(allowed),
+                Err(reason) => {
+                    errors.push(index, hosts[index].as_ref(), reason);
+                    None
+                }
+            })
+            .collect();
+        errors.into_result()?;
         Ok(Self::SpecificHosts(allowed))
     }
 
     /// Validates the given allowed_outbound_hosts values with the given resolver.
-    pub fn validate<S: AsRef<str>>(hosts: &[S], resolver: &Resolver) -> anyhow::Result<()> {
-        for partial in Self::parse_partial(hosts)? {
-            partial.validate(resolver)?;
+    ///
+    /// If any values are invalid, the error describes all of them.
+    pub fn validate<S: AsRef<str>>(
+        hosts: &[S],
+        resolver: &Resolver,
+    ) -> Result<(), AllowedHostsError> {
+        let mut errors = AllowedHostsError::default();
+        for (index, partial) in Self::parse_partial(hosts, &mut errors) {
+            if let Err(reason) = partial.validate(resolver) {
+                errors.push(index, hosts[index].as_ref(), reason);
+            }
         }
-        Ok(())
+        errors.into_result()
     }
 
     /// Parse the given allowed_outbound_hosts values with deferred parsing of
-    /// templated values.
-    fn parse_partial<S: AsRef<str>>(hosts: &[S]) -> anyhow::Result<Vec<PartialAllowedHostConfig>> {
+    /// templated values, returning each valid value with its index and adding
+    /// the invalid ones to `errors`.
+    fn parse_partial<S: AsRef<str>>(
+        hosts: &[S],
+        errors: &mut AllowedHostsError,
+    ) -> Vec<(usize, PartialAllowedHostConfig)> {
         if hosts.len() == 1 && hosts[0].as_ref() == "insecure:allow-all" {
-            bail!("'insecure:allow-all' is not allowed - use '*://*:*' instead if you really want to allow all outbound traffic'")
+            errors.push(
+                0,
+                hosts[0].as_ref(),
+                AllowedHostErrorReason::InsecureAllowAll,
+            );
+            return vec![];
         }
         let mut allowed = Vec::with_capacity(hosts.len());
-        for host in hosts {
-            let template = spin_expressions::Template::new(host.as_ref())?;
-            if template.is_literal() {
-                allowed.push(PartialAllowedHostConfig::Exact(AllowedHostConfig::parse(
-                    host.as_ref(),
-                )?));
-            } else {
-                allowed.push(PartialAllowedHostConfig::Unresolved(template));
+        for (index, host) in hosts.iter().enumerate() {
+            let host = host.as_ref();
+            let partial = match spin_expressions::Template::new(host) {
+                Ok(template) if template.is_literal() => {
+                    AllowedHostConfig::parse_entry(host.to_owned())
+                        .map(PartialAllowedHostConfig::Exact)
+                }
+                Ok(template) => Ok(PartialAllowedHostConfig::Unresolved(template)),
+                Err(err) => Err(AllowedHostErrorReason::InvalidTemplate(err.to_string())),
+            };
+            match partial {
+                Ok(partial) => allowed.push((index, partial)),
+                Err(reason) => errors.push(index, host, reason),
             }
         }
-        Ok(allowed)
+        allowed
     }
 
     /// Returns true if the given url is allowed.
@@ -510,6 +916,20 @@ impl AllowedHostsConfig {
         }
     }
 
+    /// Returns the highest MQTT quality of service allowed for the given url,
+    /// or `None` if it isn't limited, i.e. if any item which allows the url
+    /// doesn't set `max_qos`.
+    pub fn max_qos(&self, url: &OutboundUrl) -> Option<QosLevel> {
+        let AllowedHostsConfig::SpecificHosts(hosts) = self else {
+            return None;
+        };
+        let mut max = None;
+        for host in hosts.iter().filter(|h| h.allows(url)) {
+            max = max.max(Some(host.max_qos?));
+        }
+        max
+    }
+
     /// Returns true if relative ("self") requests to any of the given schemes
     /// are allowed.
     pub fn allows_relative_url(&self, schemes: &[&str]) -> bool {
@@ -620,6 +1040,22 @@ pub fn parse_service_chaining_target(url: &http::Uri) -> Option<String> {
     parse_service_chaining_host(host)
 }
 
+/// Parses a service chaining target in another app from a URL, as
+/// `(app_name, component_id)`, e.g. from `http://component.app.spin.internal`.
+pub fn parse_cross_app_chaining_target(url: &http::Uri) -> Option<(String, String)> {
+    let host = url.authority().map(|a| a.host().trim())?;
+    let (host, _) = host.rsplit_once(':').unwrap_or((host, ""));
+
+    let (component_id, rest) = host.split_once('.')?;
+    let (app_name, rest) = rest.split_once('.')?;
+
+    if rest == SERVICE_CHAINING_DOMAIN {
+        Some((app_name.to_owned(), component_id.to_owned()))
+    } else {
+        None
+    }
+}
+
 fn parse_service_chaining_host(host: &str) -> Option<String> {
     let (host, _) = host.rsplit_once(':').unwrap_or((host, ""));
 
@@ -640,6 +1076,7 @@ mod test {
                 scheme,
                 host,
                 port,
+                max_qos: None,
                 original: String::new(),
             }
         }
@@ -676,6 +1113,104 @@ mod test {
     use super::*;
     use std::net::{Ipv4Addr, Ipv6Addr};
 
+    /// A provider whose values can be changed, e.g. to rotate a hostname.
+    #[derive(Clone, Debug, Default)]
+    struct MutableProvider(Arc<std::sync::Mutex<Option<Result<String, String>>>>);
+
+    impl MutableProvider {
+        fn set(&self, value: Result<&str, &str>) {
+            *self.0.lock().unwrap() = Some(value.map(Into::into).map_err(Into::into));
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl spin_expressions::Provider for MutableProvider {
+        async fn get(&self, key: &spin_expressions::Key) -> anyhow::Result<Option<String>> {
+            assert_eq!(key.as_ref(), "api_host");
+            match &*self.0.lock().unwrap() {
+                Some(Ok(value)) => Ok(Some(value.clone())),
+                Some(Err(err)) => bail!("{err}"),
+                None => Ok(None),
+            }
+        }
+    }
+
+    fn refreshing_allowed_hosts(
+        provider: &MutableProvider,
+        refresh_interval: Duration,
+    ) -> OutboundAllowedHosts {
+        let mut resolver = ProviderResolver::new([(
+            "api_host".into(),
+            spin_locked_app::Variable {
+                description: None,
+                default: None,
+                secret: false,
+            },
+        )])
+        .unwrap();
+        resolver.add_provider(Box::new(provider.clone()));
+        let hosts: Arc<[String]> = [
+            "https://{{ api_host }}".to_string(),
+            "https://static.example.com".to_string(),
+        ]
+        .into();
+        OutboundAllowedHosts::refreshing(
+            Arc::new(RefreshingAllowedHosts::new(hosts, refresh_interval)),
+            Arc::new(resolver),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn refreshing_allowed_hosts_follow_rotated_variables() {
+        let provider = MutableProvider::default();
+        provider.set(Ok("old.example.com"));
+        let allowed_hosts = refreshing_allowed_hosts(&provider, Duration::from_millis(100));
+
+        let check = |url: &'static str| {
+            let allowed_hosts = allowed_hosts.clone();
+            async move { allowed_hosts.check_url(url, "https").await.unwrap() }
+        };
+
+        assert!(check("https://old.example.com").await);
+        provider.set(Ok("new.example.com"));
+        // Not yet due a refresh
+        assert!(!check("https://new.example.com").await);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(check("https://new.example.com").await);
+        assert!(!check("https://old.example.com").await);
+        assert!(check("https://static.example.com").await);
+    }
+
+    #[tokio::test]
+    async fn refreshing_allowed_hosts_keep_previous_config_on_error() {
+        let provider = MutableProvider::default();
+        provider.set(Ok("api.example.com"));
+        let allowed_hosts = refreshing_allowed_hosts(&provider, Duration::ZERO);
+        assert!(allowed_hosts
+            .check_url("https://api.example.com", "https")
+            .await
+            .unwrap());
+
+        provider.set(Err("vault unavailable"));
+        assert!(allowed_hosts
+            .check_url("https://api.example.com", "https")
+            .await
+            .unwrap());
+        assert!(allowed_hosts
+            .check_url("https://static.example.com", "https")
+            .await
+            .unwrap());
+
+        // With no previous config, the error is returned
+        let fresh = refreshing_allowed_hosts(&provider, Duration::ZERO);
+        assert!(fresh
+            .check_url("https://api.example.com", "https")
+            .await
+            .is_err());
+    }
+
     #[test]
     fn outbound_url_handles_at_in_paths() {
         let url = "https://example.com/file@0.1.0.json";
@@ -766,6 +1301,48 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_allowed_hosts_accepts_mqtt_max_qos() {
+        let config = AllowedHostConfig::parse("mqtt://broker.example.com:1883?max_qos=1").unwrap();
+        assert_eq!(config.max_qos(), Some(QosLevel::AtLeastOnce));
+        assert_eq!(config.port(), &PortConfig::new(1883));
+        assert_eq!(
+            AllowedHostConfig::parse("*://broker.example.com:1883?max_qos=0")
+                .unwrap()
+                .max_qos(),
+            Some(QosLevel::AtMostOnce)
+        );
+
+        assert!(AllowedHostConfig::parse("mqtt://broker.example.com:1883?max_qos=3").is_err());
+        assert!(AllowedHostConfig::parse("https://example.com?max_qos=1").is_err());
+        assert!(AllowedHostConfig::parse("mqtt://broker.example.com:1883?qos=1").is_err());
+    }
+
+    #[test]
+    fn test_max_qos_is_highest_of_matching_hosts() {
+        let max_qos = |hosts: &[&str]| {
+            let config = AllowedHostsConfig::parse(hosts, &dummy_resolver()).unwrap();
+            config.max_qos(&OutboundUrl::parse("mqtt://broker.example.com:1883", "mqtt").unwrap())
+        };
+        assert_eq!(
+            max_qos(&["mqtt://broker.example.com:1883?max_qos=0"]),
+            Some(QosLevel::AtMostOnce)
+        );
+        assert_eq!(
+            max_qos(&[
+                "mqtt://broker.example.com:1883?max_qos=0",
+                "mqtt://*.example.com:1883?max_qos=1",
+                "mqtt://other.example.com:1883?max_qos=2",
+            ]),
+            Some(QosLevel::AtLeastOnce)
+        );
+        // An item without a limit allows any QoS
+        assert_eq!(
+            max_qos(&["mqtt://broker.example.com:1883?max_qos=0", "mqtt://*:1883",]),
+            None
+        );
+    }
+
     #[test]
     fn test_allowed_hosts_does_not_accept_plain_host_without_port() {
         assert!(AllowedHostConfig::parse("spin.fermyon.dev").is_err());
@@ -991,4 +1568,94 @@ mod test {
             AllowedHostsConfig::parse(&["*://127.0.0.1/24:63551"], &dummy_resolver()).unwrap();
         assert!(allowed.allows(&OutboundUrl::parse("tcp://127.0.0.1:63551", "tcp").unwrap()));
     }
+
+    #[test]
+    fn test_cross_app_chaining_target() {
+        let target = |url: &str| parse_cross_app_chaining_target(&url.parse().unwrap());
+        assert_eq!(
+            target("http://backend.billing.spin.internal/charge"),
+            Some(("billing".into(), "backend".into()))
+        );
+        assert_eq!(
+            target("http://backend.billing.spin.internal:8080"),
+            Some(("billing".into(), "backend".into()))
+        );
+        // Chaining within the app
+        assert_eq!(target("http://backend.spin.internal"), None);
+        assert_eq!(target("http://backend.billing.example.com"), None);
+    }
+
+    #[test]
+    fn test_all_invalid_entries_are_reported() {
+        let resolver = Resolver::new([(
+            "bad_host".into(),
+            spin_locked_app::Variable {
+                description: None,
+                default: Some("invalid host".into()),
+                secret: false,
+            },
+        )])
+        .unwrap();
+        let hosts = [
+            "https://example.com",
+            "example.com",
+            "https://example.com:80..abc",
+            "https://{{ bad_host }}",
+        ];
+        let err = AllowedHostsConfig::validate(&hosts, &resolver).unwrap_err();
+        assert!(
+            matches!(
+                err.entries(),
+                [
+                    InvalidAllowedHost {
+                        index: 1,
+                        reason: AllowedHostErrorReason::MissingScheme,
+                        ..
+                    },
+                    InvalidAllowedHost {
+                        index: 2,
+                        reason: AllowedHostErrorReason::InvalidPort { .. },
+                        ..
+                    },
+                    InvalidAllowedHost {
+                        index: 3,
+                        reason: AllowedHostErrorReason::InvalidResolvedTemplate { .. },
+                        ..
+                    },
+                ]
+            ),
+            "{err}"
+        );
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "3 allowed outbound hosts are invalid:\n1. [1] \"example.com\": does not contain a scheme"
+            ),
+            "{message}"
+        );
+        assert!(
+            message.contains("\n2. [2] \"https://example.com:80..abc\": invalid port \"80..abc\""),
+            "{message}"
+        );
+        assert!(
+            message.contains("\n3. [3] \"https://{{ bad_host }}\": template resolves to invalid config \"https://invalid host\""),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_valid_entries_parse_as_before() {
+        let hosts = [
+            "https://example.com",
+            "*://*.example.org:8000..9000",
+            "mqtt://broker.example.com?max_qos=1",
+        ];
+        let config = AllowedHostsConfig::parse(&hosts, &dummy_resolver()).unwrap();
+        let expected = hosts
+            .iter()
+            .map(|host| AllowedHostConfig::parse(*host).unwrap())
+            .collect();
+        assert_eq!(config, AllowedHostsConfig::SpecificHosts(expected));
+        assert!(AllowedHostsConfig::validate(&hosts, &Resolver::new([]).unwrap()).is_ok());
+    }
 }
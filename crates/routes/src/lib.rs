@@ -16,6 +16,9 @@ pub struct Router {
     /// Resolves paths to routing information - specifically component IDs
     /// but also recording about the original route.
     router: std::sync::Arc<routefinder::Router<RouteHandler>>,
+    /// Routers for routes restricted to particular hosts, most specific host
+    /// first.
+    host_routers: std::sync::Arc<Vec<(RouteHost, routefinder::Router<RouteHandler>)>>,
 }
 
 /// What a route maps to
@@ -23,6 +26,8 @@ pub struct Router {
 struct RouteHandler {
     /// The component ID that the route maps to.
     component_id: String,
+    /// The host the route is restricted to, if any.
+    host: Option<RouteHost>,
     /// The route, including any application base.
     based_route: Cow<'static, str>,
     /// The route, not including any application base.
@@ -37,10 +42,16 @@ struct RouteHandler {
 pub struct DuplicateRoute {
     /// The duplicated route pattern.
     route: String,
+    /// The host the duplicated route is restricted to, if any.
+    host: Option<RouteHost>,
     /// The raw route that was duplicated.
     pub replaced_id: String,
     /// The component ID corresponding to the duplicated route.
     pub effective_id: String,
+    /// The component whose route was declared first, before any of its
+    /// duplicates. With more than two duplicates, this may differ from
+    /// `replaced_id`.
+    pub first_registered_id: String,
 }
 
 impl Router {
@@ -51,6 +62,29 @@ impl Router {
     pub fn build<'a>(
         base: &str,
         component_routes: impl IntoIterator<Item = (&'a str, &'a HttpTriggerRouteConfig)>,
+        duplicate_routes: Option<&mut Vec<DuplicateRoute>>,
+    ) -> Result<Self> {
+        let component_routes = component_routes
+            .into_iter()
+            .map(|(component_id, route)| (component_id, route, None));
+        Self::build_with_hosts(base, component_routes, duplicate_routes)
+    }
+
+    /// Builds a router based on application configuration, where routes may
+    /// be restricted to particular hosts.
+    ///
+    /// Routes are duplicates if they have the same path and host.
+    /// `duplicate_routes` is an optional mutable reference to a vector of `DuplicateRoute`
+    /// that will be populated with any duplicate routes found during the build process.
+    pub fn build_with_hosts<'a>(
+        base: &str,
+        component_routes: impl IntoIterator<
+            Item = (
+                &'a str,
+                &'a HttpTriggerRouteConfig,
+                Option<&'a HttpTriggerHostConfig>,
+            ),
+        >,
         mut duplicate_routes: Option<&mut Vec<DuplicateRoute>>,
     ) -> Result<Self> {
         // Some information we need to carry between stages of the builder.
@@ -58,49 +92,77 @@ impl Router {
             based_route: String,
             raw_route: &'a str,
             component_id: &'a str,
+            host: Option<RouteHost>,
         }
 
         let mut routes = IndexMap::new();
 
-        // Filter out private endpoints and capture the routes.
-        let routes_iter = component_routes
-            .into_iter()
-            .filter_map(|(component_id, route)| {
-                match route {
-                    HttpTriggerRouteConfig::Route(raw_route) => {
-                        let based_route = sanitize_with_base(base, raw_route);
-                        Some(Ok(RoutingEntry { based_route, raw_route, component_id }))
-                    }
-                    HttpTriggerRouteConfig::Private(endpoint) => if endpoint.private {
-                        None
+        // Filter out private endpoints and capture the routes, one for each
+        // host they are restricted to.
+        let mut routing_entries = vec![];
+        for (component_id, route, hosts) in component_routes {
+            let raw_route = match route {
+                HttpTriggerRouteConfig::Route(raw_route) => raw_route,
+                HttpTriggerRouteConfig::Private(endpoint) => {
+                    if endpoint.private {
+                        continue;
                     } else {
-                        Some(Err(anyhow!("route must be a string pattern or '{{ private = true }}': component '{component_id}' has {{ private = false }}")))
+                        return Err(anyhow!("route must be a string pattern or '{{ private = true }}': component '{component_id}' has {{ private = false }}"));
                     }
                 }
-            });
+            };
+            let based_route = sanitize_with_base(base, raw_route);
+            let hosts = match hosts {
+                None => vec![None],
+                Some(hosts) => hosts
+                    .hosts()
+                    .iter()
+                    .map(|host| {
+                        RouteHost::parse(host).map(Some).map_err(|e| {
+                            anyhow!("Invalid host {host:?} for component {component_id}: {e}")
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            };
+            for host in hosts {
+                routing_entries.push(RoutingEntry {
+                    based_route: based_route.clone(),
+                    raw_route,
+                    component_id,
+                    host,
+                });
+            }
+        }
 
         // Remove duplicates.
-        for re in routes_iter {
-            let re = re?;
-            if let Some(replaced) = routes.insert(re.raw_route, re) {
+        let mut first_registered_ids = HashMap::new();
+        for re in routing_entries {
+            let key = (re.host.clone(), re.raw_route);
+            let first_registered_id = *first_registered_ids
+                .entry(key.clone())
+                .or_insert(re.component_id);
+            if let Some(replaced) = routes.insert(key.clone(), re) {
                 if let Some(duplicate_routes) = &mut duplicate_routes {
                     let effective_id = routes
-                        .get(replaced.raw_route)
+                        .get(&key)
                         .unwrap() // Safe because we just inserted it
                         .component_id
                         .to_owned();
                     duplicate_routes.push(DuplicateRoute {
                         route: replaced.based_route,
+                        host: replaced.host,
                         replaced_id: replaced.component_id.to_owned(),
                         effective_id,
+                        first_registered_id: first_registered_id.to_owned(),
                     });
                 }
             }
         }
 
-        // Build a `routefinder` from the remaining routes.
+        // Build a `routefinder` for each host from the remaining routes.
 
         let mut rf = routefinder::Router::new();
+        let mut host_routers = IndexMap::new();
 
         for re in routes.into_values() {
             let (rfroute, parsed) = Self::parse_route(&re.based_route).map_err(|e| {
@@ -113,16 +175,27 @@ impl Router {
 
             let handler = RouteHandler {
                 component_id: re.component_id.to_string(),
+                host: re.host.clone(),
                 based_route: re.based_route.into(),
                 raw_route: re.raw_route.to_string().into(),
                 parsed_based_route: parsed,
             };
 
-            rf.add(rfroute, handler).map_err(|e| anyhow!("{e}"))?;
+            let router = match re.host {
+                None => &mut rf,
+                Some(host) => host_routers
+                    .entry(host)
+                    .or_insert_with(routefinder::Router::new),
+            };
+            router.add(rfroute, handler).map_err(|e| anyhow!("{e}"))?;
         }
 
+        let mut host_routers = Vec::from_iter(host_routers);
+        host_routers.sort_by_key(|(host, _)| host.specificity());
+
         let router = Self {
             router: std::sync::Arc::new(rf),
+            host_routers: std::sync::Arc::new(host_routers),
         };
 
         Ok(router)
@@ -145,23 +218,56 @@ impl Router {
     }
 
     /// Returns the constructed routes.
+    ///
+    /// Routes which are restricted to a host are displayed with the host.
     pub fn routes(&self) -> impl Iterator<Item = (&(impl fmt::Display + fmt::Debug), &String)> {
+        self.handlers()
+            .map(|handler| (handler, &handler.component_id))
+    }
+
+    /// Returns the number of routes, including those restricted to a host.
+    pub fn len(&self) -> usize {
+        self.handlers().count()
+    }
+
+    /// true if there are no routes; otherwise false.
+    pub fn is_empty(&self) -> bool {
+        self.handlers().next().is_none()
+    }
+
+    /// Returns the based route of the first route which maps to the given
+    /// component, if any. Host-agnostic routes are considered first.
+    pub fn route_for_component(&self, component_id: &str) -> Option<&str> {
+        self.handlers()
+            .find(|handler| handler.component_id == component_id)
+            .map(|handler| handler.based_route.as_ref())
+    }
+
+    /// Returns all the route handlers, host-agnostic ones first.
+    fn handlers(&self) -> impl Iterator<Item = &RouteHandler> {
         self.router
             .iter()
-            .map(|(_spec, handler)| (&handler.parsed_based_route, &handler.component_id))
+            .chain(
+                self.host_routers
+                    .iter()
+                    .flat_map(|(_host, router)| router.iter()),
+            )
+            .map(|(_spec, handler)| handler)
     }
 
     /// true if one or more routes is under the reserved `/.well-known/spin/*`
     /// prefix; otherwise false.
     pub fn contains_reserved_route(&self) -> bool {
-        self.router
-            .iter()
-            .any(|(_spec, handler)| handler.based_route.starts_with(crate::WELL_KNOWN_PREFIX))
+        self.handlers()
+            .any(|handler| handler.based_route.starts_with(crate::WELL_KNOWN_PREFIX))
     }
 
     /// This returns the component ID that should handle the given path, or an error
     /// if no component matches.
     ///
+    /// Only routes which are not restricted to a host are considered; see
+    /// [`Router::route_with_host`].
+    ///
     /// If multiple components could potentially handle the same request based on their
     /// defined routes, components with matching exact routes take precedence followed
     /// by matching wildcard patterns with the longest matching prefix.
@@ -169,9 +275,35 @@ impl Router {
         &'router self,
         path: &'path str,
     ) -> Result<RouteMatch<'router, 'path>> {
-        let best_match = self
-            .router
-            .best_match(path)
+        self.route_with_host(None, path)
+    }
+
+    /// This returns the component ID that should handle the given host and
+    /// path, or an error if no component matches.
+    ///
+    /// Routes restricted to a host matching `host` are considered first, most
+    /// specific host first (exact hosts, then wildcards with the longest
+    /// suffix). If none of them match the path, routes which are not
+    /// restricted to a host are considered. Within each set of routes, paths
+    /// are matched as in [`Router::route`].
+    pub fn route_with_host<'path, 'router: 'path>(
+        &'router self,
+        host: Option<&str>,
+        path: &'path str,
+    ) -> Result<RouteMatch<'router, 'path>> {
+        let host_matches = host
+            .map(normalize_host)
+            .into_iter()
+            .flat_map(|host| {
+                self.host_routers
+                    .iter()
+                    .filter(move |(route_host, _)| route_host.matches(&host))
+            })
+            .filter_map(|(_host, router)| router.best_match(path));
+
+        let best_match = host_matches
+            .chain(self.router.best_match(path))
+            .next()
             .ok_or_else(|| anyhow!("Cannot match route for path {path}"))?;
 
         let route_handler = best_match.handler();
@@ -196,6 +328,90 @@ impl DuplicateRoute {
             &self.route
         }
     }
+
+    /// The host the duplicated route is restricted to, if any.
+    pub fn host(&self) -> Option<String> {
+        self.host.as_ref().map(ToString::to_string)
+    }
+}
+
+impl fmt::Display for RouteHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.parsed_based_route, &self.host) {
+            (route, None) => write!(f, "{route}"),
+            (ParsedRoute::Exact(path), Some(host)) => write!(f, "{path} (host {host})"),
+            (ParsedRoute::TrailingWildcard(pattern), Some(host)) => {
+                write!(f, "{pattern} (wildcard, host {host})")
+            }
+        }
+    }
+}
+
+/// A host that a route is restricted to.
+///
+/// Wildcards follow the same rules as `allowed_outbound_hosts`: `*.example.com`
+/// matches any subdomain of `example.com`, at any depth, but not `example.com`
+/// itself.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum RouteHost {
+    /// An exact host name.
+    Exact(String),
+    /// Any subdomain of a domain. This holds the suffix including the leading
+    /// dot, e.g. `.example.com`.
+    AnySubdomain(String),
+}
+
+impl RouteHost {
+    fn parse(host: &str) -> Result<Self> {
+        let host = normalize_host(host.trim());
+        if host.is_empty() {
+            return Err(anyhow!("host must not be empty"));
+        }
+        if host.contains(['/', ':']) {
+            return Err(anyhow!("host must not include a scheme, port or path"));
+        }
+        if let Some(domain) = host.strip_prefix("*.") {
+            if domain.is_empty() || domain.contains('*') {
+                return Err(anyhow!("wildcards are allowed only as prefixes"));
+            }
+            return Ok(Self::AnySubdomain(format!(".{domain}")));
+        }
+        if host.contains('*') {
+            return Err(anyhow!("wildcards are allowed only as subdomains"));
+        }
+        Ok(Self::Exact(host))
+    }
+
+    /// Returns true if the given (normalized) request host matches.
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            Self::Exact(exact) => host == exact,
+            Self::AnySubdomain(suffix) => host.ends_with(suffix.as_str()),
+        }
+    }
+
+    /// A sort key ordering more specific hosts first.
+    fn specificity(&self) -> (bool, std::cmp::Reverse<usize>) {
+        match self {
+            Self::Exact(_) => (false, std::cmp::Reverse(0)),
+            Self::AnySubdomain(suffix) => (true, std::cmp::Reverse(suffix.len())),
+        }
+    }
+}
+
+impl fmt::Display for RouteHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(host) => write!(f, "{host}"),
+            Self::AnySubdomain(suffix) => write!(f, "*{suffix}"),
+        }
+    }
+}
+
+/// Normalizes a host name for matching: host names are case-insensitive and
+/// may have a trailing dot.
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
 }
 
 #[derive(Clone, Debug)]
@@ -236,6 +452,7 @@ impl RouteMatch<'_, '_> {
             inner: RouteMatchKind::Synthetic {
                 route_handler: RouteHandler {
                     component_id,
+                    host: None,
                     based_route: "/...".into(),
                     raw_route: "/...".into(),
                     parsed_based_route: ParsedRoute::TrailingWildcard(String::new()),
@@ -387,7 +604,7 @@ fn sanitize<S: Into<String>>(s: S) -> String {
 
 /// An HTTP trigger route
 #[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(untagged)]
+#[serde(untagged, expecting = "expected string or { private = true }")]
 pub enum HttpTriggerRouteConfig {
     /// A route that is routable.
     Route(String),
@@ -395,6 +612,27 @@ pub enum HttpTriggerRouteConfig {
     Private(HttpPrivateEndpoint),
 }
 
+/// The host or hosts an HTTP trigger route is restricted to, e.g.
+/// `host = "api.example.com"` or `host = ["example.com", "*.example.com"]`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged, expecting = "expected string or list of strings")]
+pub enum HttpTriggerHostConfig {
+    /// A single host.
+    Host(String),
+    /// A list of hosts.
+    Hosts(Vec<String>),
+}
+
+impl HttpTriggerHostConfig {
+    /// The configured hosts.
+    pub fn hosts(&self) -> &[String] {
+        match self {
+            Self::Host(host) => std::slice::from_ref(host),
+            Self::Hosts(hosts) => hosts,
+        }
+    }
+}
+
 /// Indicates that a trigger is a private endpoint (not routable).
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -419,6 +657,39 @@ impl<T: Into<String>> From<T> for HttpTriggerRouteConfig {
 mod route_tests {
     use super::*;
 
+    #[test]
+    fn route_for_component_finds_based_route() -> Result<()> {
+        let r = Router::build(
+            "/base",
+            [("foo", &"/foo".into()), ("bar", &"/bar/...".into())],
+            None,
+        )?;
+        assert_eq!(r.route_for_component("foo"), Some("/base/foo"));
+        assert_eq!(r.route_for_component("bar"), Some("/base/bar/..."));
+        assert_eq!(r.route_for_component("baz"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn len_counts_routes() -> Result<()> {
+        let r = Router::build("/", [], None)?;
+        assert!(r.is_empty());
+        assert_eq!(r.len(), 0);
+
+        let r = Router::build(
+            "/",
+            [
+                ("foo", &"/foo".into()),
+                ("bar", &"/bar/...".into()),
+                ("all", &"/...".into()),
+            ],
+            None,
+        )?;
+        assert!(!r.is_empty());
+        assert_eq!(r.len(), 3);
+        Ok(())
+    }
+
     #[test]
     fn test_router_exact() -> Result<()> {
         let r = Router::build(
@@ -595,6 +866,29 @@ mod route_tests {
         assert_eq!("comp-second /foo", duplicates[0].effective_id);
     }
 
+    #[test]
+    fn duplicate_routes_record_first_registered() {
+        let mut duplicates = Vec::new();
+        let routes = Router::build(
+            "/",
+            vec![
+                ("first", &"/foo".into()),
+                ("second", &"/foo".into()),
+                ("third", &"/foo".into()),
+            ],
+            Some(&mut duplicates),
+        )
+        .unwrap();
+
+        assert_eq!("third", routes.routes().next().unwrap().1);
+        assert_eq!(2, duplicates.len());
+        assert_eq!("second", duplicates[1].replaced_id);
+        assert_eq!("third", duplicates[1].effective_id);
+        for duplicate in &duplicates {
+            assert_eq!("first", duplicate.first_registered_id);
+        }
+    }
+
     #[test]
     fn duplicate_routes_reporting_is_faithful() {
         let mut duplicates = Vec::new();
@@ -738,4 +1032,166 @@ mod route_tests {
         let routes = Router::build("/", vec![("comp", &"/.well-known/spin".into())], None).unwrap();
         assert!(!routes.contains_reserved_route());
     }
+
+    fn host(host: &str) -> HttpTriggerHostConfig {
+        HttpTriggerHostConfig::Host(host.into())
+    }
+
+    #[test]
+    fn same_path_routes_by_host() {
+        let api = host("api.example.com");
+        let www = host("www.example.com");
+        let routes = Router::build_with_hosts(
+            "/",
+            [
+                ("api", &"/...".into(), Some(&api)),
+                ("www", &"/...".into(), Some(&www)),
+            ],
+            None,
+        )
+        .unwrap();
+
+        let m = routes
+            .route_with_host(Some("api.example.com"), "/foo")
+            .unwrap();
+        assert_eq!("api", m.component_id());
+        let m = routes
+            .route_with_host(Some("WWW.Example.com."), "/foo")
+            .unwrap();
+        assert_eq!("www", m.component_id());
+
+        routes
+            .route_with_host(Some("example.com"), "/foo")
+            .expect_err("no route for example.com");
+        routes.route("/foo").expect_err("all routes have hosts");
+    }
+
+    #[test]
+    fn wildcard_hosts_match_subdomains() {
+        let any = host("*.example.com");
+        let admin = host("admin.example.com");
+        let routes = Router::build_with_hosts(
+            "/",
+            [
+                ("any", &"/...".into(), Some(&any)),
+                ("admin", &"/...".into(), Some(&admin)),
+            ],
+            None,
+        )
+        .unwrap();
+
+        let m = routes
+            .route_with_host(Some("a.b.example.com"), "/foo")
+            .unwrap();
+        assert_eq!("any", m.component_id());
+        // Exact hosts take precedence over wildcards
+        let m = routes
+            .route_with_host(Some("admin.example.com"), "/foo")
+            .unwrap();
+        assert_eq!("admin", m.component_id());
+
+        routes
+            .route_with_host(Some("example.com"), "/foo")
+            .expect_err("wildcard should not match the bare domain");
+        routes
+            .route_with_host(Some("notexample.com"), "/foo")
+            .expect_err("wildcard should match only subdomains");
+    }
+
+    #[test]
+    fn host_routes_fall_back_to_hostless_routes() {
+        let api = HttpTriggerHostConfig::Hosts(vec!["api.example.com".into()]);
+        let routes = Router::build_with_hosts(
+            "/",
+            [
+                ("api", &"/api/...".into(), Some(&api)),
+                ("fallback", &"/...".into(), None),
+            ],
+            None,
+        )
+        .unwrap();
+
+        let m = routes
+            .route_with_host(Some("api.example.com"), "/api/foo")
+            .unwrap();
+        assert_eq!("api", m.component_id());
+        let m = routes
+            .route_with_host(Some("api.example.com"), "/other")
+            .unwrap();
+        assert_eq!("fallback", m.component_id());
+        let m = routes
+            .route_with_host(Some("www.example.com"), "/api/foo")
+            .unwrap();
+        assert_eq!("fallback", m.component_id());
+        let m = routes.route_with_host(None, "/api/foo").unwrap();
+        assert_eq!("fallback", m.component_id());
+    }
+
+    #[test]
+    fn duplicate_routes_are_per_host() {
+        let api = host("api.example.com");
+        let both =
+            HttpTriggerHostConfig::Hosts(vec!["api.example.com".into(), "*.example.org".into()]);
+        let mut duplicates = Vec::new();
+        let routes = Router::build_with_hosts(
+            "/",
+            [
+                ("hostless", &"/foo".into(), None),
+                ("first", &"/foo".into(), Some(&api)),
+                ("second", &"/foo".into(), Some(&both)),
+            ],
+            Some(&mut duplicates),
+        )
+        .unwrap();
+
+        assert_eq!(3, routes.routes().count());
+        assert_eq!(1, duplicates.len());
+        assert_eq!("first", duplicates[0].replaced_id);
+        assert_eq!("second", duplicates[0].effective_id);
+        assert_eq!(Some("api.example.com".into()), duplicates[0].host());
+    }
+
+    #[test]
+    fn host_routes_display_host() {
+        let api = host("API.example.com");
+        let any = host("*.example.com");
+        let routes = Router::build_with_hosts(
+            "/",
+            [
+                ("exact", &"/foo".into(), Some(&api)),
+                ("wild", &"/bar/...".into(), Some(&any)),
+            ],
+            None,
+        )
+        .unwrap();
+
+        let displayed = routes
+            .routes()
+            .map(|(route, component_id)| format!("{component_id}: {route}"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            displayed,
+            [
+                "exact: /foo (host api.example.com)",
+                "wild: /bar (wildcard, host *.example.com)",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_hosts_are_rejected() {
+        for invalid in [
+            "",
+            "*",
+            "*.",
+            "api.*.com",
+            "api*.example.com",
+            "example.com:3000",
+            "http://example.com",
+        ] {
+            let host = host(invalid);
+            Router::build_with_hosts("/", [("comp", &"/".into(), Some(&host))], None)
+                .expect_err(invalid);
+        }
+    }
 }
@@ -0,0 +1,364 @@
+//! Merging runtime config files with the files they include.
+//!
+//! A runtime config file may list other runtime config files to build on in
+//! a top-level `include` array, e.g. `include = ["base.toml", "prod.toml"]`.
+//! Relative include paths are resolved against the directory of the file
+//! which includes them. The included files are merged in order, and then the
+//! including file is merged over them, so that:
+//!
+//! * later files override earlier files key by key
+//! * tables are merged recursively
+//! * any other value, including an array, replaces the earlier value entirely
+//!
+//! Other relative paths, e.g. of key-value store databases, are still resolved
+//! as if they appeared in the top-level runtime config file.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use spin_common::ui::quoted_path;
+use toml::Value;
+
+/// The top-level key listing the files a runtime config file includes.
+const INCLUDE_KEY: &str = "include";
+
+/// A runtime config file merged with the files it includes.
+#[derive(Debug, Default)]
+pub struct MergedRuntimeConfig {
+    /// The effective runtime config, without any `include` keys.
+    pub toml: toml::Table,
+    /// The file each leaf value came from, keyed by dotted key path.
+    provenance: BTreeMap<String, PathBuf>,
+    /// Whether any file was included.
+    has_includes: bool,
+}
+
+impl MergedRuntimeConfig {
+    /// Loads the runtime config file at `path`, merged with the files it
+    /// includes.
+    ///
+    /// Errors if a file can't be read or parsed, or if files include each
+    /// other in a cycle.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut merged = Self::default();
+        merged.merge_file(path, &mut vec![])?;
+        Ok(merged)
+    }
+
+    /// Returns whether the runtime config file included any other files.
+    pub fn has_includes(&self) -> bool {
+        self.has_includes
+    }
+
+    /// Returns the file the value at the given dotted key path came from.
+    pub fn source_of(&self, key: &str) -> Option<&Path> {
+        self.provenance.get(key).map(PathBuf::as_path)
+    }
+
+    /// Returns the effective runtime config as one `key = value` line per
+    /// leaf value, each annotated with the file it came from.
+    ///
+    /// The values may include secrets such as tokens.
+    pub fn describe_provenance(&self) -> String {
+        let mut lines = vec![];
+        self.describe_table(&self.toml, "", &mut lines);
+        lines.join("\n")
+    }
+
+    fn describe_table(&self, table: &toml::Table, prefix: &str, lines: &mut Vec<String>) {
+        for (key, value) in table {
+            let key = key_path(prefix, key);
+            match value {
+                Value::Table(table) if !table.is_empty() => self.describe_table(table, &key, lines),
+                value => {
+                    let source = self
+                        .source_of(&key)
+                        .map(|path| format!("  # from {}", quoted_path(path)))
+                        .unwrap_or_default();
+                    lines.push(format!("{key} = {value}{source}"));
+                }
+            }
+        }
+    }
+
+    /// Merges the file at `path`, after the files it includes. `including`
+    /// holds the chain of files which included this one.
+    fn merge_file(&mut self, path: &Path, including: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read runtime config file {}", quoted_path(path)))?;
+        let mut table: toml::Table = toml::from_str(&text).with_context(|| {
+            format!(
+                "failed to parse runtime config file {} as toml",
+                quoted_path(path)
+            )
+        })?;
+
+        let includes = match table.remove(INCLUDE_KEY) {
+            None => vec![],
+            Some(Value::Array(includes)) => includes
+                .into_iter()
+                .map(|include| match include {
+                    Value::String(include) => Ok(include),
+                    _ => anyhow::bail!(
+                        "`{INCLUDE_KEY}` in {} must be an array of file paths",
+                        quoted_path(path)
+                    ),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            Some(_) => anyhow::bail!(
+                "`{INCLUDE_KEY}` in {} must be an array of file paths",
+                quoted_path(path)
+            ),
+        };
+
+        if !includes.is_empty() {
+            self.has_includes = true;
+            let canonical = path
+                .canonicalize()
+                .with_context(|| format!("failed to resolve {}", quoted_path(path)))?;
+            if including.contains(&canonical) {
+                let cycle = including
+                    .iter()
+                    .chain([&canonical])
+                    .map(|path| quoted_path(path).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                anyhow::bail!("runtime config files include each other in a cycle: {cycle}");
+            }
+            let dir = canonical.parent().unwrap_or(Path::new("."));
+            including.push(canonical.clone());
+            for include in includes {
+                self.merge_file(&dir.join(include), including)?;
+            }
+            including.pop();
+        }
+
+        merge_table(&mut self.toml, table, "", path, &mut self.provenance);
+        Ok(())
+    }
+}
+
+/// Merges `overlay` over `base`, recording `source` as the provenance of each
+/// leaf value taken from `overlay`.
+fn merge_table(
+    base: &mut toml::Table,
+    overlay: toml::Table,
+    prefix: &str,
+    source: &Path,
+    provenance: &mut BTreeMap<String, PathBuf>,
+) {
+    for (key, value) in overlay {
+        let path = key_path(prefix, &key);
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => {
+                merge_table(base, overlay, &path, source, provenance)
+            }
+            (_, value) => {
+                // Nothing beneath a replaced value survives
+                let nested_prefix = format!("{path}.");
+                provenance.retain(|key, _| *key != path && !key.starts_with(&nested_prefix));
+                record_provenance(&value, &path, source, provenance);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_provenance(
+    value: &Value,
+    path: &str,
+    source: &Path,
+    provenance: &mut BTreeMap<String, PathBuf>,
+) {
+    match value {
+        Value::Table(table) if !table.is_empty() => {
+            for (key, value) in table {
+                record_provenance(value, &key_path(path, key), source, provenance);
+            }
+        }
+        _ => {
+            provenance.insert(path.to_owned(), source.to_owned());
+        }
+    }
+}
+
+fn key_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn tables_are_deep_merged() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        write(
+            dir.path(),
+            "base/base.toml",
+            r#"
+            state_dir = "base-state"
+
+            [key_value_store.default]
+            type = "redis"
+            url = "redis://base"
+
+            [key_value_store.cache]
+            type = "spin"
+            "#,
+        );
+        let path = write(
+            dir.path(),
+            "runtime-config.toml",
+            r#"
+            include = ["base/base.toml"]
+
+            [key_value_store.default]
+            url = "redis://prod"
+            "#,
+        );
+
+        let merged = MergedRuntimeConfig::load(&path)?;
+        assert!(merged.has_includes());
+        let expected: toml::Table = toml::from_str(
+            r#"
+            state_dir = "base-state"
+
+            [key_value_store.default]
+            type = "redis"
+            url = "redis://prod"
+
+            [key_value_store.cache]
+            type = "spin"
+            "#,
+        )?;
+        assert_eq!(merged.toml, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn later_files_replace_arrays() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        write(
+            dir.path(),
+            "base.toml",
+            r#"
+            [[variables_provider]]
+            type = "env"
+            prefix = "BASE"
+
+            [[variables_provider]]
+            type = "env"
+            prefix = "OTHER"
+            "#,
+        );
+        write(
+            dir.path(),
+            "staging.toml",
+            r#"
+            [[variables_provider]]
+            type = "env"
+            prefix = "STAGING"
+            "#,
+        );
+        let path = write(
+            dir.path(),
+            "runtime-config.toml",
+            r#"include = ["base.toml", "staging.toml"]"#,
+        );
+
+        let merged = MergedRuntimeConfig::load(&path)?;
+        let providers = merged.toml["variables_provider"].as_array().unwrap();
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0]["prefix"].as_str(), Some("STAGING"));
+        Ok(())
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "a.toml", r#"include = ["nested/b.toml"]"#);
+        write(dir.path(), "nested/b.toml", r#"include = ["../a.toml"]"#);
+
+        let err = MergedRuntimeConfig::load(&dir.path().join("a.toml")).unwrap_err();
+        assert!(
+            err.to_string().contains("cycle"),
+            "unexpected error: {err:#}"
+        );
+    }
+
+    #[test]
+    fn include_must_be_array_of_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "runtime-config.toml",
+            r#"include = "base.toml""#,
+        );
+        assert!(MergedRuntimeConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn provenance_names_source_files() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let base = write(
+            dir.path(),
+            "base.toml",
+            r#"
+            log_dir = "logs"
+
+            [key_value_store.default]
+            type = "spin"
+            path = "base.db"
+            "#,
+        );
+        let path = write(
+            dir.path(),
+            "runtime-config.toml",
+            r#"
+            include = ["base.toml"]
+
+            [key_value_store.default]
+            type = "redis"
+            "#,
+        );
+
+        let merged = MergedRuntimeConfig::load(&path)?;
+        // Included files are resolved relative to the canonical including file
+        let base = base.canonicalize()?;
+        assert_eq!(merged.source_of("log_dir"), Some(base.as_path()));
+        assert_eq!(
+            merged.source_of("key_value_store.default.type"),
+            Some(path.as_path())
+        );
+        assert_eq!(
+            merged.source_of("key_value_store.default.path"),
+            Some(base.as_path())
+        );
+
+        let description = merged.describe_provenance();
+        assert!(description.contains(&format!(
+            "key_value_store.default.type = \"redis\"  # from {}",
+            quoted_path(&path)
+        )));
+        assert!(description.contains(&format!(
+            "log_dir = \"logs\"  # from {}",
+            quoted_path(&base)
+        )));
+        Ok(())
+    }
+}
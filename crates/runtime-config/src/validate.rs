@@ -0,0 +1,390 @@
+//! Pre-flight checks of a runtime config.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use spin_common::ui::quoted_path;
+use toml::Value;
+
+use crate::TomlRuntimeConfigSource;
+
+/// A problem found by [`TomlRuntimeConfigSource::validate`] which may stop
+/// the runtime config from working as intended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigValidationWarning {
+    /// The dotted path of the TOML key the problem was found in, e.g.
+    /// `key_value_store.my-store.path`.
+    pub key: String,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl ConfigValidationWarning {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigValidationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl TomlRuntimeConfigSource<'_, '_> {
+    /// Checks the runtime config for problems without applying it.
+    ///
+    /// Unlike resolving the runtime config, this has no side effects: it
+    /// creates no files or directories and makes no network connections. It
+    /// checks that:
+    ///
+    /// * key-value store and SQLite database labels can be referenced by
+    ///   components
+    /// * local database and `.env` files exist
+    /// * client TLS and outbound TLS certificate and key files are readable
+    /// * variables provider URLs are well-formed
+    ///
+    /// Errors if a section of the runtime config has the wrong shape.
+    pub fn validate(&self) -> anyhow::Result<Vec<ConfigValidationWarning>> {
+        let table = self.toml.table.as_ref();
+        let runtime_config_dir = self
+            .outbound_networking
+            .map(|config| config.runtime_config_dir());
+        let mut warnings = vec![];
+
+        if let Some(stores) = labeled_tables(table, "key_value_store")? {
+            for (label, store) in stores {
+                let key = format!("key_value_store.{label}");
+                check_label(&key, label, &mut warnings);
+                // Relative paths are resolved against the runtime config file's directory
+                if let Some(path) = local_database_path(&key, store, runtime_config_dir)? {
+                    check_database_path(&key, &path, &mut warnings);
+                }
+            }
+        }
+
+        if let Some(databases) = labeled_tables(table, "sqlite_database")? {
+            let cwd = std::env::current_dir().context("failed to get current working directory")?;
+            for (label, database) in databases {
+                let key = format!("sqlite_database.{label}");
+                check_label(&key, label, &mut warnings);
+                // Relative paths are resolved against the working directory
+                if let Some(path) = local_database_path(&key, database, Some(cwd.as_path()))? {
+                    check_database_path(&key, &path, &mut warnings);
+                }
+            }
+        }
+
+        // Client TLS configs are only loaded from a runtime config file
+        if let (Some(dir), Some(tls_configs)) = (runtime_config_dir, table.get("client_tls")) {
+            let tls_configs = tls_configs
+                .as_array()
+                .context("`client_tls` must be an array of tables")?;
+            for (index, tls_config) in tls_configs.iter().enumerate() {
+                for field in [
+                    "ca_roots_file",
+                    "client_cert_file",
+                    "client_private_key_file",
+                ] {
+                    let key = format!("client_tls[{index}].{field}");
+                    if let Some(path) = optional_str(&key, tls_config.get(field))? {
+                        check_readable(key, &dir.join(path), &mut warnings);
+                    }
+                }
+            }
+        }
+
+        if let (Some(dir), Some(outbound_tls)) = (runtime_config_dir, table.get("outbound_tls")) {
+            if let Some(files) = outbound_tls.get("ca_bundle_files") {
+                let files = files
+                    .as_array()
+                    .context("`outbound_tls.ca_bundle_files` must be an array of strings")?;
+                for (index, path) in files.iter().enumerate() {
+                    let key = format!("outbound_tls.ca_bundle_files[{index}]");
+                    if let Some(path) = optional_str(&key, Some(path))? {
+                        check_readable(key, &dir.join(path), &mut warnings);
+                    }
+                }
+            }
+            if let Some(hosts) = outbound_tls.get("hosts") {
+                let hosts = hosts
+                    .as_array()
+                    .context("`outbound_tls.hosts` must be an array of tables")?;
+                for (index, host_config) in hosts.iter().enumerate() {
+                    let key = format!("outbound_tls.hosts[{index}].ca_file");
+                    if let Some(path) = optional_str(&key, host_config.get("ca_file"))? {
+                        check_readable(key, &dir.join(path), &mut warnings);
+                    }
+                }
+            }
+        }
+
+        let providers_key = if table.contains_key("variables_provider") {
+            "variables_provider"
+        } else {
+            "config_provider"
+        };
+        if let Some(providers) = table.get(providers_key) {
+            let providers = providers
+                .as_array()
+                .with_context(|| format!("`{providers_key}` must be an array of tables"))?;
+            for (index, provider) in providers.iter().enumerate() {
+                let key = format!("{providers_key}[{index}]");
+                let provider_type = optional_str(&format!("{key}.type"), provider.get("type"))?;
+                let url_field = match provider_type {
+                    Some("vault") => "url",
+                    Some("azure_key_vault") => "vault_url",
+                    Some("env") => {
+                        let key = format!("{key}.dotenv_path");
+                        if let Some(path) = optional_str(&key, provider.get("dotenv_path"))? {
+                            if !Path::new(path).is_file() {
+                                warnings.push(ConfigValidationWarning::new(
+                                    key,
+                                    format!("{} does not exist", quoted_path(path)),
+                                ));
+                            }
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let key = format!("{key}.{url_field}");
+                if let Some(url) = optional_str(&key, provider.get(url_field))? {
+                    check_url(&key, url, &mut warnings);
+                }
+            }
+        }
+
+        Ok(warnings)
+    }
+}
+
+/// Returns the `[<section>.<label>]` tables, if the section is present.
+fn labeled_tables<'a>(
+    table: &'a toml::Table,
+    section: &str,
+) -> anyhow::Result<Option<Vec<(&'a String, &'a Value)>>> {
+    let Some(value) = table.get(section) else {
+        return Ok(None);
+    };
+    let tables = value
+        .as_table()
+        .with_context(|| format!("`{section}` must be a table of labeled tables"))?;
+    Ok(Some(tables.iter().collect()))
+}
+
+fn optional_str<'a>(key: &str, value: Option<&'a Value>) -> anyhow::Result<Option<&'a str>> {
+    value
+        .map(|value| {
+            value
+                .as_str()
+                .with_context(|| format!("`{key}` must be a string"))
+        })
+        .transpose()
+}
+
+/// Returns the resolved path of a local (`type = "spin"`) database, if it has one.
+fn local_database_path(
+    key: &str,
+    config: &Value,
+    base_dir: Option<&Path>,
+) -> anyhow::Result<Option<PathBuf>> {
+    if config.get("type").and_then(Value::as_str) != Some("spin") {
+        return Ok(None);
+    }
+    let Some(path) = optional_str(&format!("{key}.path"), config.get("path"))? else {
+        return Ok(None);
+    };
+    Ok(Some(match base_dir {
+        Some(base_dir) => base_dir.join(path),
+        None => PathBuf::from(path),
+    }))
+}
+
+/// Components can only reference stores by kebab-case or snake_case labels.
+fn check_label(key: &str, label: &str, warnings: &mut Vec<ConfigValidationWarning>) {
+    let is_valid = spin_serde::KebabId::try_from(label.to_owned()).is_ok()
+        || spin_serde::SnakeId::try_from(label.to_owned()).is_ok();
+    if !is_valid {
+        warnings.push(ConfigValidationWarning::new(
+            key,
+            format!("label {label:?} is not kebab-case or snake_case, so no component can use it"),
+        ));
+    }
+}
+
+fn check_database_path(key: &str, path: &Path, warnings: &mut Vec<ConfigValidationWarning>) {
+    let key = format!("{key}.path");
+    if path.is_dir() {
+        warnings.push(ConfigValidationWarning::new(
+            key,
+            format!("{} is a directory, not a database file", quoted_path(path)),
+        ));
+    } else if !path.exists() {
+        warnings.push(ConfigValidationWarning::new(
+            key,
+            format!(
+                "{} does not exist; a new empty database will be created",
+                quoted_path(path)
+            ),
+        ));
+    }
+}
+
+fn check_readable(key: String, path: &Path, warnings: &mut Vec<ConfigValidationWarning>) {
+    if let Err(err) = std::fs::File::open(path) {
+        warnings.push(ConfigValidationWarning::new(
+            key,
+            format!("cannot read {}: {err}", quoted_path(path)),
+        ));
+    }
+}
+
+fn check_url(key: &str, url: &str, warnings: &mut Vec<ConfigValidationWarning>) {
+    match url::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+        Ok(_) => warnings.push(ConfigValidationWarning::new(
+            key,
+            format!("{url:?} is not an HTTP or HTTPS URL"),
+        )),
+        Err(err) => warnings.push(ConfigValidationWarning::new(
+            key,
+            format!("{url:?} is not a valid URL: {err}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_factor_outbound_networking::runtime_config::spin::SpinRuntimeConfig as OutboundNetworkingSpinRuntimeConfig;
+    use spin_trigger::cli::UserProvidedPath;
+
+    use super::*;
+    use crate::{key_value_config_resolver, sqlite_config_resolver, TomlResolver};
+
+    fn validate(toml: &toml::Table, dir: &Path) -> anyhow::Result<Vec<ConfigValidationWarning>> {
+        let key_value = key_value_config_resolver(Some(dir.to_owned()), None);
+        let sqlite = sqlite_config_resolver(None)?;
+        let outbound_networking = OutboundNetworkingSpinRuntimeConfig::new(dir);
+        let source = TomlRuntimeConfigSource::new(
+            TomlResolver::new(
+                toml,
+                None,
+                UserProvidedPath::Default,
+                UserProvidedPath::Default,
+            ),
+            &key_value,
+            Some(&outbound_networking),
+            &sqlite,
+        );
+        source.validate()
+    }
+
+    fn warned_keys(warnings: &[ConfigValidationWarning]) -> Vec<&str> {
+        warnings.iter().map(|w| w.key.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_warnings() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("store.db"), "")?;
+        std::fs::write(dir.path().join("ca.pem"), "")?;
+        let toml = toml::from_str(
+            r#"
+            [key_value_store.my-store]
+            type = "spin"
+            path = "store.db"
+
+            [[client_tls]]
+            component_ids = ["hello"]
+            hosts = ["example.com"]
+            ca_roots_file = "ca.pem"
+
+            [outbound_tls]
+            ca_bundle_files = ["ca.pem"]
+
+            [[outbound_tls.hosts]]
+            hosts = ["*.corp.internal"]
+            ca_file = "ca.pem"
+
+            [[variables_provider]]
+            type = "vault"
+            url = "https://vault.example.com:8200"
+            token = "token"
+            mount = "secret"
+            "#,
+        )?;
+        assert_eq!(validate(&toml, dir.path())?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn warns_about_problems() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let toml = toml::from_str(
+            r#"
+            [key_value_store."My Store"]
+            type = "redis"
+            url = "redis://localhost"
+
+            [key_value_store.missing]
+            type = "spin"
+            path = "missing.db"
+
+            [[client_tls]]
+            component_ids = ["hello"]
+            hosts = ["example.com"]
+            client_cert_file = "missing.crt"
+            client_private_key_file = "missing.key"
+
+            [outbound_tls]
+            ca_bundle_files = ["missing-bundle.pem"]
+
+            [[outbound_tls.hosts]]
+            hosts = ["db.corp.internal"]
+            ca_file = "missing-ca.pem"
+
+            [[variables_provider]]
+            type = "azure_key_vault"
+            vault_url = "not a url"
+
+            [[variables_provider]]
+            type = "vault"
+            url = "file:///etc/vault"
+            token = "token"
+            mount = "secret"
+            "#,
+        )?;
+        let warnings = validate(&toml, dir.path())?;
+        let mut keys = warned_keys(&warnings);
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "client_tls[0].client_cert_file",
+                "client_tls[0].client_private_key_file",
+                "key_value_store.My Store",
+                "key_value_store.missing.path",
+                "outbound_tls.ca_bundle_files[0]",
+                "outbound_tls.hosts[0].ca_file",
+                "variables_provider[0].vault_url",
+                "variables_provider[1].url",
+            ]
+        );
+        // Validation must not create the missing database
+        assert!(!dir.path().join("missing.db").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn errors_on_malformed_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let toml = toml::toml! {
+            key_value_store = "default"
+        };
+        assert!(validate(&toml, dir.path()).is_err());
+    }
+}
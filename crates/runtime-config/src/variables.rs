@@ -1,3 +1,6 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Context as _;
 use serde::Deserialize;
 use spin_expressions::Provider;
 use spin_factor_variables::runtime_config::RuntimeConfig;
@@ -7,16 +10,49 @@ use spin_variables_env::{EnvVariablesConfig, EnvVariablesProvider};
 use spin_variables_static::StaticVariablesProvider;
 use spin_variables_vault::VaultVariablesProvider;
 
+use crate::MergedRuntimeConfig;
+
+/// Resolves a runtime configuration for the variables factor from a runtime
+/// config file, e.g. to reload the variables providers after the file changes.
+///
+/// If there is no runtime config file, only the default providers are used.
+pub fn runtime_config_from_file(
+    runtime_config_path: Option<&Path>,
+) -> anyhow::Result<RuntimeConfig> {
+    let table = match runtime_config_path {
+        Some(runtime_config_path) => MergedRuntimeConfig::load(runtime_config_path)?.toml,
+        None => Default::default(),
+    };
+    runtime_config_from_toml(&table)
+}
+
 /// Resolves a runtime configuration for the variables factor from a TOML table.
 pub fn runtime_config_from_toml(table: &impl GetTomlValue) -> anyhow::Result<RuntimeConfig> {
     // Always include the environment variable provider.
     let var_provider = vec![Box::<EnvVariablesProvider>::default() as _];
+    let audit = match table.get("variables_audit") {
+        Some(value) => value
+            .as_bool()
+            .context("'variables_audit' must be a boolean")?,
+        None => false,
+    };
+    let cache_ttl = match table.get("variables_cache_ttl_secs") {
+        Some(value) => Some(Duration::from_secs(
+            value
+                .as_integer()
+                .and_then(|secs| u64::try_from(secs).ok())
+                .context("'variables_cache_ttl_secs' must be a non-negative integer")?,
+        )),
+        None => None,
+    };
     let value = table
         .get("variables_provider")
         .or_else(|| table.get("config_provider"));
     let Some(array) = value else {
         return Ok(RuntimeConfig {
             providers: var_provider,
+            audit,
+            cache_ttl,
         });
     };
 
@@ -26,7 +62,11 @@ pub fn runtime_config_from_toml(table: &impl GetTomlValue) -> anyhow::Result<Run
         .map(VariableProviderConfiguration::into_provider)
         .collect::<anyhow::Result<Vec<_>>>()?;
     providers.extend(var_provider);
-    Ok(RuntimeConfig { providers })
+    Ok(RuntimeConfig {
+        providers,
+        audit,
+        cache_ttl,
+    })
 }
 
 /// A runtime configuration used in the Spin CLI for one type of variable provider.
@@ -54,9 +94,9 @@ impl VariableProviderConfiguration {
                 config.dotenv_path,
             )),
             VariableProviderConfiguration::Vault(provider) => Box::new(provider),
-            VariableProviderConfiguration::AzureKeyVault(config) => Box::new(
-                AzureKeyVaultProvider::create(config.vault_url.clone(), config.try_into()?)?,
-            ),
+            VariableProviderConfiguration::AzureKeyVault(config) => {
+                Box::new(AzureKeyVaultProvider::from_config(config)?)
+            }
         };
         Ok(provider)
     }
@@ -0,0 +1,160 @@
+//! Watching the runtime config file for changes.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context as _;
+use notify::{RecursiveMode, Watcher as _};
+use spin_common::ui::quoted_path;
+use tokio::sync::broadcast;
+
+use crate::MergedRuntimeConfig;
+
+/// The number of changes a slow subscriber can fall behind by before it
+/// misses some.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Sent when the runtime config file has changed.
+#[derive(Clone, Debug)]
+pub struct RuntimeConfigChanged {
+    /// The path of the runtime config file.
+    pub path: PathBuf,
+    /// The new contents of the file, merged with the files it includes.
+    pub toml: Arc<toml::Table>,
+}
+
+/// Watches a runtime config file, sending a [`RuntimeConfigChanged`] to each
+/// subscriber whenever the file changes.
+///
+/// Changes which leave the file unreadable or not valid TOML are logged and
+/// otherwise ignored, as are changes which don't alter the parsed contents
+/// (editors often write a file several times when saving it). Only the file
+/// itself is watched, not the files it includes. Watching stops when the
+/// `RuntimeConfigWatcher` is dropped.
+pub struct RuntimeConfigWatcher {
+    sender: broadcast::Sender<RuntimeConfigChanged>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl RuntimeConfigWatcher {
+    /// Starts watching the runtime config file at `path`.
+    pub fn new(path: &Path) -> anyhow::Result<Self> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("{} is not a file", quoted_path(path)))?;
+        // Watch the directory rather than the file, as editors often save by
+        // replacing the file.
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("failed to find directory {}", quoted_path(dir)))?;
+        let path = dir.join(file_name);
+
+        let current = MergedRuntimeConfig::load(&path)
+            .ok()
+            .map(|merged| merged.toml);
+        let current = Mutex::new(current);
+
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let event_sender = sender.clone();
+        let watched_path = path.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !event.kind.is_access() && event.paths.contains(&watched_path) => {
+                    let Some(toml) = read_changed(&watched_path, &mut current.lock().unwrap())
+                    else {
+                        return;
+                    };
+                    tracing::info!("Runtime config file {} changed", quoted_path(&watched_path));
+                    // No subscribers is not an error
+                    _ = event_sender.send(RuntimeConfigChanged {
+                        path: watched_path.clone(),
+                        toml: Arc::new(toml),
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Error watching runtime config file: {e}"),
+            })
+            .context("failed to create runtime config file watcher")?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("failed to watch {}", quoted_path(&dir)))?;
+
+        Ok(Self {
+            sender,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns a receiver for subsequent changes to the runtime config file.
+    pub fn subscribe(&self) -> broadcast::Receiver<RuntimeConfigChanged> {
+        self.sender.subscribe()
+    }
+}
+
+/// Reads and parses the file at `path`, merged with the files it includes,
+/// returning the new contents if they differ from `current` (which is then
+/// updated).
+fn read_changed(path: &Path, current: &mut Option<toml::Table>) -> Option<toml::Table> {
+    let toml = match MergedRuntimeConfig::load(path) {
+        Ok(merged) => merged.toml,
+        Err(e) => {
+            tracing::warn!(
+                "Ignoring change to runtime config file {}: {e:#}",
+                quoted_path(path)
+            );
+            return None;
+        }
+    };
+    if current.as_ref() == Some(&toml) {
+        return None;
+    }
+    *current = Some(toml.clone());
+    Some(toml)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    async fn next_change(
+        changes: &mut broadcast::Receiver<RuntimeConfigChanged>,
+    ) -> RuntimeConfigChanged {
+        tokio::time::timeout(Duration::from_secs(10), changes.recv())
+            .await
+            .expect("timed out waiting for change")
+            .expect("watcher stopped")
+    }
+
+    #[tokio::test]
+    async fn sends_parsed_changes() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("runtime-config.toml");
+        std::fs::write(&path, "variables_audit = false")?;
+
+        let watcher = RuntimeConfigWatcher::new(&path)?;
+        let mut changes = watcher.subscribe();
+
+        // Invalid TOML doesn't produce a change...
+        std::fs::write(&path, "variables_audit = ")?;
+        // ...but subsequent valid TOML does
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        std::fs::write(&path, "variables_audit = true")?;
+
+        let change = next_change(&mut changes).await;
+        assert_eq!(change.path.file_name(), path.file_name());
+        assert_eq!(change.toml["variables_audit"].as_bool(), Some(true));
+
+        // Changes to other files in the directory are ignored
+        std::fs::write(dir.path().join("other.toml"), "variables_audit = false")?;
+        std::fs::write(&path, "variables_audit = false")?;
+        let change = next_change(&mut changes).await;
+        assert_eq!(change.toml["variables_audit"].as_bool(), Some(false));
+        Ok(())
+    }
+}
@@ -1,16 +1,19 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use super::{TriggerAppArgs, TriggerFactors, TriggerFactorsRuntimeConfig};
 
 use anyhow::Context as _;
+use spin_app::limits::{Limits, LimitsResolver};
+use spin_factor_variables::VariablesFactor;
 use spin_factors_executor::FactorsExecutor;
-use spin_runtime_config::ResolvedRuntimeConfig;
+use spin_runtime_config::{ResolvedRuntimeConfig, RuntimeConfigWatcher};
 use spin_trigger::cli::{
-    FactorsConfig, InitialKvSetterHook, KeyValueDefaultStoreSummaryHook, MaxInstanceMemoryHook,
-    RuntimeFactorsBuilder, SqlStatementExecutorHook, SqliteDefaultStoreSummaryHook,
-    StdioLoggingExecutorHooks,
+    FactorsConfig, InitialKvSetterHook, KeyValueCopyHook, KeyValueDefaultStoreSummaryHook,
+    MemoryWarningHook, RuntimeFactorsBuilder, SqlStatementExecutorHook,
+    SqliteDefaultStoreSummaryHook, SqliteMigrationsHook, StdioLoggingExecutorHooks,
 };
 use spin_variables_static::StaticVariablesProvider;
+use tokio::sync::broadcast;
 
 /// A [`RuntimeFactorsBuilder`] for [`TriggerFactors`].
 pub struct FactorsBuilder;
@@ -29,10 +32,12 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             config.local_app_dir.clone().map(PathBuf::from),
             config.state_dir.clone(),
             config.log_dir.clone(),
+            config.watch_runtime_config,
         )?;
 
         let cli_static_variables = args.get_variables()?.clone();
-        let cli_static_variables_provider = StaticVariablesProvider::new(cli_static_variables);
+        let cli_static_variables_provider =
+            StaticVariablesProvider::new(cli_static_variables.clone());
 
         // Insert the parsed static variables provided via cli arguments
         // into the set of variable providers with highest precedence.
@@ -45,6 +50,7 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             .insert(0, Box::new(cli_static_variables_provider));
 
         runtime_config.summarize(config.runtime_config_file.as_deref());
+        tracing::debug!("Resolved runtime config:\n{}", runtime_config.describe());
 
         let factors = TriggerFactors::new(
             runtime_config.state_dir(),
@@ -52,9 +58,45 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             args.allow_transient_write,
         )
         .context("failed to create factors")?;
+
+        #[cfg(unix)]
+        reload_variables_on_sighup(
+            &factors.variables,
+            config.runtime_config_file.clone(),
+            cli_static_variables.clone(),
+        )?;
+
+        if let Some(watcher) = runtime_config.watcher.take() {
+            reload_variables_on_change(&factors.variables, watcher, cli_static_variables);
+        }
+
         Ok((factors, runtime_config))
     }
 
+    fn trigger_runtime_config<'a>(
+        runtime_config: &'a Self::RuntimeConfig,
+        trigger_type: &str,
+    ) -> Option<&'a toml::Table> {
+        runtime_config.trigger_config(trigger_type)
+    }
+
+    fn state_dir(runtime_config: &Self::RuntimeConfig) -> Option<PathBuf> {
+        runtime_config.state_dir()
+    }
+
+    fn limits(runtime_config: &Self::RuntimeConfig, args: &Self::CliArgs) -> LimitsResolver {
+        // A max instance memory from the flag or runtime config overrides the
+        // limits in the manifest, but not the runtime config's `[limits]` caps
+        let max_instance_memory = args
+            .max_instance_memory
+            .or(runtime_config.max_instance_memory());
+        let operator_limits = Limits {
+            max_memory_bytes: max_instance_memory.map(|bytes| bytes as u64),
+            ..Default::default()
+        };
+        LimitsResolver::new(operator_limits, runtime_config.host_limits().clone())
+    }
+
     fn configure_app<U: Send + 'static>(
         executor: &mut FactorsExecutor<Self::Factors, U>,
         runtime_config: &Self::RuntimeConfig,
@@ -66,22 +108,106 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             runtime_config.log_dir(),
             config.truncate_logs,
         ));
+        // Migrations are applied before any `--sqlite` statements, which may
+        // rely on the tables they create
+        executor.add_hooks(SqliteMigrationsHook);
         executor.add_hooks(SqlStatementExecutorHook::new(
             args.sqlite_statements.clone(),
         ));
+        // Stores are copied before `--key-value` pairs are set, so that the
+        // pairs aren't overwritten
+        executor.add_hooks(KeyValueCopyHook::new(args.key_value_copies.clone()));
         executor.add_hooks(InitialKvSetterHook::new(args.key_values.clone()));
         executor.add_hooks(SqliteDefaultStoreSummaryHook);
         executor.add_hooks(KeyValueDefaultStoreSummaryHook);
 
-        let max_instance_memory = args
-            .max_instance_memory
-            .or(runtime_config.max_instance_memory());
-
-        // Only add the hook if a max instance memory size is specified via flag or runtime config.
-        if let Some(max_instance_memory) = max_instance_memory {
-            executor.add_hooks(MaxInstanceMemoryHook::new(max_instance_memory));
+        let warning_percent = args
+            .max_instance_memory_warning_percent
+            .or(runtime_config.max_instance_memory_warning_percent());
+        if let Some(warning_percent) = warning_percent {
+            executor.add_hooks(MemoryWarningHook::new(warning_percent));
         }
 
         Ok(())
     }
 }
+
+/// Reloads the variables providers from the runtime config file when the
+/// process receives SIGHUP, e.g. after a Vault token in the file is renewed.
+#[cfg(unix)]
+fn reload_variables_on_sighup(
+    variables: &VariablesFactor,
+    runtime_config_file: Option<PathBuf>,
+    cli_static_variables: HashMap<String, String>,
+) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    if tokio::runtime::Handle::try_current().is_err() {
+        // Nothing is running that could use reloaded providers.
+        return Ok(());
+    }
+    let mut hangups = signal(SignalKind::hangup()).context("failed to listen for SIGHUP")?;
+    let variables = variables.clone();
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            tracing::info!("Received SIGHUP: reloading variables providers");
+            let new_config = spin_runtime_config::variables::runtime_config_from_file(
+                runtime_config_file.as_deref(),
+            );
+            reload_variables(&variables, new_config, &cli_static_variables).await;
+        }
+    });
+    Ok(())
+}
+
+/// Reloads the variables providers whenever the watched runtime config file
+/// changes.
+fn reload_variables_on_change(
+    variables: &VariablesFactor,
+    watcher: RuntimeConfigWatcher,
+    cli_static_variables: HashMap<String, String>,
+) {
+    if tokio::runtime::Handle::try_current().is_err() {
+        // Nothing is running that could use reloaded providers.
+        return;
+    }
+    let mut changes = watcher.subscribe();
+    let variables = variables.clone();
+    tokio::spawn(async move {
+        // The watcher stops watching when dropped, so keep it alive as long
+        // as this task.
+        let _watcher = watcher;
+        loop {
+            let change = match changes.recv().await {
+                Ok(change) => change,
+                // Only the latest contents matter, and a newer change follows.
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            tracing::info!("Runtime config file changed: reloading variables providers");
+            let new_config =
+                spin_runtime_config::variables::runtime_config_from_toml(change.toml.as_ref());
+            reload_variables(&variables, new_config, &cli_static_variables).await;
+        }
+    });
+}
+
+async fn reload_variables(
+    variables: &VariablesFactor,
+    new_config: anyhow::Result<spin_factor_variables::runtime_config::RuntimeConfig>,
+    cli_static_variables: &HashMap<String, String>,
+) {
+    let reload = async {
+        let mut new_config = new_config?;
+        // As at startup, variables from the command line take precedence.
+        new_config.providers.insert(
+            0,
+            Box::new(StaticVariablesProvider::new(cli_static_variables.clone())),
+        );
+        variables.reload_providers(new_config).await
+    };
+    if let Err(e) = reload.await {
+        terminal::error!("Failed to reload variables providers: {e:#}");
+        terminal::warn!("Continuing with the previous variables providers.");
+    }
+}
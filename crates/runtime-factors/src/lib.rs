@@ -8,9 +8,11 @@ use std::path::PathBuf;
 
 use anyhow::Context as _;
 use spin_common::arg_parser::parse_kv;
+use spin_factor_audit::AuditFactor;
 use spin_factor_key_value::KeyValueFactor;
 use spin_factor_llm::LlmFactor;
 use spin_factor_outbound_http::OutboundHttpFactor;
+use spin_factor_outbound_kafka::{KafkaFactor, NetworkedKafkaProducer};
 use spin_factor_outbound_mqtt::{NetworkedMqttClient, OutboundMqttFactor};
 use spin_factor_outbound_mysql::OutboundMysqlFactor;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
@@ -27,12 +29,15 @@ use spin_variables_static::VariableSource;
 pub struct TriggerFactors {
     pub wasi: WasiFactor,
     pub variables: VariablesFactor,
+    // Must come before the factors whose operations it audits
+    pub audit: AuditFactor,
     pub key_value: KeyValueFactor,
     pub outbound_networking: OutboundNetworkingFactor,
     pub outbound_http: OutboundHttpFactor,
     pub sqlite: SqliteFactor,
     pub redis: OutboundRedisFactor,
     pub mqtt: OutboundMqttFactor,
+    pub kafka: KafkaFactor,
     pub pg: OutboundPgFactor,
     pub mysql: OutboundMysqlFactor,
     pub llm: LlmFactor,
@@ -47,12 +52,14 @@ impl TriggerFactors {
         Ok(Self {
             wasi: wasi_factor(working_dir, allow_transient_writes),
             variables: VariablesFactor::default(),
+            audit: AuditFactor::new(),
             key_value: KeyValueFactor::new(),
             outbound_networking: outbound_networking_factor(),
             outbound_http: OutboundHttpFactor::default(),
             sqlite: SqliteFactor::new(),
             redis: OutboundRedisFactor::new(),
             mqtt: OutboundMqttFactor::new(NetworkedMqttClient::creator()),
+            kafka: KafkaFactor::new(NetworkedKafkaProducer::creator()),
             pg: OutboundPgFactor::new(),
             mysql: OutboundMysqlFactor::new(),
             llm: LlmFactor::new(
@@ -99,15 +106,28 @@ pub struct TriggerAppArgs {
     #[clap(long = "key-value", value_parser = parse_kv)]
     pub key_values: Vec<(String, String)>,
 
+    /// Copy the keys of one key-value store into another (from=to) before the
+    /// application starts, e.g. to migrate data to a new store. Keys already
+    /// in the destination store are left as they are. Both stores must be used
+    /// by the application. Can be used multiple times.
+    #[clap(long = "key-value-copy", value_parser = parse_kv, value_name = "FROM=TO")]
+    pub key_value_copies: Vec<(String, String)>,
+
     /// Run a SQLite statement such as a migration against the default database.
     /// To run from a file, prefix the filename with @ e.g. spin up --sqlite @migration.sql
     #[clap(long = "sqlite")]
     pub sqlite_statements: Vec<String>,
 
-    /// Sets the maxmimum memory allocation limit for an instance in bytes.
+    /// Sets the maxmimum memory allocation limit for an instance in bytes,
+    /// overriding any `max_memory_bytes` limit in the application manifest.
     #[clap(long, env = "SPIN_MAX_INSTANCE_MEMORY")]
     pub max_instance_memory: Option<usize>,
 
+    /// Logs a warning the first time an instance's memory use reaches this
+    /// percentage of the maximum memory allocation limit.
+    #[clap(long, env = "SPIN_MAX_INSTANCE_MEMORY_WARNING_PERCENT", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub max_instance_memory_warning_percent: Option<u8>,
+
     /// Variable(s) to be passed to the app
     ///
     /// A single key-value pair can be passed as `key=value`. Alternatively, the
@@ -0,0 +1,4 @@
+#[test]
+fn duplicate_factors_fail_to_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,106 @@
+use spin_factor_outbound_networking::{
+    config::allowed_hosts::OutboundAllowedHosts, OutboundNetworkingFactor,
+};
+use spin_factor_variables::VariablesFactor;
+use spin_factors::{
+    anyhow,
+    wasmtime::{component::Linker, Engine},
+    ConfigureAppContext, Factor, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
+};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_runtime_factors::TriggerFactors;
+
+#[derive(RuntimeFactors)]
+struct ExtendedFactors {
+    #[factors(flatten)]
+    trigger: TriggerFactors,
+    allowed_hosts: AllowedHostsFactor,
+}
+
+/// A factor which depends on a factor of the flattened `TriggerFactors`.
+struct AllowedHostsFactor;
+
+struct AllowedHostsInstance {
+    allowed_hosts: OutboundAllowedHosts,
+}
+
+impl SelfInstanceBuilder for AllowedHostsInstance {}
+
+impl Factor for AllowedHostsFactor {
+    type RuntimeConfig = ();
+    type AppState = ();
+    type InstanceBuilder = AllowedHostsInstance;
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        _ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        Ok(())
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<Self::InstanceBuilder> {
+        let allowed_hosts = ctx
+            .instance_builder::<OutboundNetworkingFactor>()?
+            .allowed_hosts();
+        Ok(AllowedHostsInstance { allowed_hosts })
+    }
+}
+
+#[tokio::test]
+async fn flattened_factors_build_instance_state() -> anyhow::Result<()> {
+    let factors = ExtendedFactors {
+        trigger: TriggerFactors::new(None, std::env::temp_dir(), false)?,
+        allowed_hosts: AllowedHostsFactor,
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["https://allowed.example.com"]
+    });
+    let mut state = env.build_instance_state().await?;
+
+    let allowed_hosts = &state.allowed_hosts.allowed_hosts;
+    assert!(
+        allowed_hosts
+            .check_url("https://allowed.example.com", "https")
+            .await?
+    );
+    assert!(
+        !allowed_hosts
+            .check_url("https://denied.example.com", "https")
+            .await?
+    );
+
+    // Nested factors' state is found through the outer instance state
+    assert!(spin_factor_wasi::WasiFactor::get_wasi_impl(&mut state).is_some());
+    Ok(())
+}
+
+/// Names `VariablesFactor` differently from `TriggerFactors`, so the
+/// duplicate can't be caught at compile time.
+type AliasedVariablesFactor = VariablesFactor;
+
+#[derive(RuntimeFactors)]
+struct AliasedDuplicateFactors {
+    #[factors(flatten)]
+    trigger: TriggerFactors,
+    variables: AliasedVariablesFactor,
+}
+
+#[test]
+fn flattened_duplicate_factor_fails_init() -> anyhow::Result<()> {
+    let mut factors = AliasedDuplicateFactors {
+        trigger: TriggerFactors::new(None, std::env::temp_dir(), false)?,
+        variables: VariablesFactor::new(),
+    };
+    let mut linker = Linker::<AliasedDuplicateFactorsInstanceState>::new(&Engine::default());
+    let err = factors.init(&mut linker).unwrap_err();
+    assert!(
+        matches!(&err, spin_factors::Error::DuplicateFactorTypes(name) if name == "VariablesFactor"),
+        "unexpected error: {err}"
+    );
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use spin_factor_wasi::CreatedModes;
+use spin_factors::anyhow;
+use spin_factors_test::{toml, TestEnvironment};
+use spin_runtime_config::ResolvedRuntimeConfig;
+use spin_runtime_factors::{TriggerFactors, TriggerFactorsRuntimeConfig};
+use spin_trigger::cli::UserProvidedPath;
+
+#[tokio::test]
+async fn wasi_created_modes_come_from_runtime_config() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let runtime_config_path = dir.path().join("runtime-config.toml");
+    std::fs::write(
+        &runtime_config_path,
+        "[wasi]\ncreated_file_mode = 0o640\ncreated_dir_mode = 0o750\n",
+    )?;
+    let runtime_config = ResolvedRuntimeConfig::<TriggerFactorsRuntimeConfig>::from_file(
+        Some(&runtime_config_path),
+        None,
+        UserProvidedPath::Unset,
+        UserProvidedPath::Unset,
+        false,
+    )?;
+
+    let factors = TriggerFactors::new(None, dir.path(), false)?;
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+        })
+        .runtime_config(runtime_config)?;
+    let state = env.build_instance_state().await?;
+
+    assert_eq!(
+        state.wasi.created_modes(),
+        CreatedModes {
+            file: Some(0o640),
+            dir: Some(0o750),
+        }
+    );
+    Ok(())
+}
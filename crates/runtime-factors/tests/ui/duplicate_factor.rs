@@ -0,0 +1,10 @@
+use spin_factor_variables::VariablesFactor;
+use spin_factors::RuntimeFactors;
+
+#[derive(RuntimeFactors)]
+struct DuplicateFactors {
+    variables: VariablesFactor,
+    more_variables: VariablesFactor,
+}
+
+fn main() {}
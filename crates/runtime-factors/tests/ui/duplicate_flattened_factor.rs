@@ -0,0 +1,12 @@
+use spin_factor_variables::VariablesFactor;
+use spin_factors::RuntimeFactors;
+use spin_runtime_factors::TriggerFactors;
+
+#[derive(RuntimeFactors)]
+struct ExtendedFactors {
+    #[factors(flatten)]
+    trigger: TriggerFactors,
+    variables: VariablesFactor,
+}
+
+fn main() {}
@@ -1,13 +1,28 @@
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::OnceLock,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
 use anyhow::Context as _;
 use async_trait::async_trait;
+use rusqlite::{
+    hooks::{AuthAction, AuthContext, Authorization},
+    OpenFlags,
+};
 use spin_factor_sqlite::Connection;
-use spin_world::spin::sqlite::sqlite;
+use spin_world::spin::sqlite3_0_0::sqlite;
+use spin_world::spin::sqlite3_1_0::{attach, backup};
+
+/// The number of database pages copied in each step of a backup.
+const BACKUP_PAGES_PER_STEP: std::ffi::c_int = 256;
+/// The pause between steps of a backup, giving other connections a chance to
+/// use the database.
+const BACKUP_STEP_PAUSE: Duration = Duration::from_millis(5);
 
 /// The location of an in-process sqlite database.
 #[derive(Debug, Clone)]
@@ -46,6 +61,8 @@ impl InProcDatabaseLocation {
 pub struct InProcConnection {
     location: InProcDatabaseLocation,
     connection: OnceLock<Arc<Mutex<rusqlite::Connection>>>,
+    /// Whether the database is opened read-only.
+    read_only: AtomicBool,
 }
 
 impl InProcConnection {
@@ -54,6 +71,7 @@ impl InProcConnection {
         Ok(Self {
             location,
             connection,
+            read_only: AtomicBool::new(false),
         })
     }
 
@@ -68,11 +86,25 @@ impl InProcConnection {
     }
 
     fn create_connection(&self) -> Result<Arc<Mutex<rusqlite::Connection>>, sqlite::Error> {
+        #[cfg(feature = "vector-search")]
+        register_vector_extension();
+        let flags = if self.read_only.load(Ordering::Acquire) {
+            OpenFlags::default()
+                .difference(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)
+                .union(OpenFlags::SQLITE_OPEN_READ_ONLY)
+        } else {
+            OpenFlags::default()
+        };
         let connection = match &self.location {
-            InProcDatabaseLocation::InMemory => rusqlite::Connection::open_in_memory(),
-            InProcDatabaseLocation::Path(path) => rusqlite::Connection::open(path),
+            InProcDatabaseLocation::InMemory => {
+                rusqlite::Connection::open_in_memory_with_flags(flags)
+            }
+            InProcDatabaseLocation::Path(path) => {
+                rusqlite::Connection::open_with_flags(path, flags)
+            }
         }
         .map_err(|e| sqlite::Error::Io(e.to_string()))?;
+        guard_attachments(&connection);
         Ok(Arc::new(Mutex::new(connection)))
     }
 }
@@ -118,6 +150,77 @@ impl Connection for InProcConnection {
         Ok(conn.last_insert_rowid())
     }
 
+    async fn backup(&self, destination: &Path) -> Result<(), backup::Error> {
+        let InProcDatabaseLocation::Path(path) = &self.location else {
+            return Err(backup::Error::Unsupported);
+        };
+        if destination == path {
+            return Err(backup::Error::InvalidDestination);
+        }
+        let source = path.clone();
+        let destination = destination.to_owned();
+        tokio::task::spawn_blocking(move || backup_database(&source, &destination))
+            .await
+            .context("internal runtime error")
+            .map_err(|e| backup::Error::Io(e.to_string()))?
+    }
+
+    async fn attach(
+        &self,
+        path: &Path,
+        alias: &str,
+        read_only: bool,
+    ) -> Result<(), attach::AttachError> {
+        let connection = self
+            .db_connection()
+            .map_err(|e| attach::AttachError::Io(format!("{e:?}")))?;
+        let path = path.to_str().ok_or_else(|| {
+            attach::AttachError::Unsupported(format!("database path {path:?} is not valid UTF-8"))
+        })?;
+        // Connections are opened with URI filenames enabled, so a read-only
+        // database can be attached with `mode=ro`
+        let path = if read_only {
+            read_only_uri(path)
+        } else {
+            path.to_owned()
+        };
+        let alias = alias.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let conn = connection.lock().unwrap();
+            conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+            let result = conn.execute("ATTACH DATABASE ?1 AS ?2", [path, alias]);
+            guard_attachments(&conn);
+            result
+        })
+        .await
+        .context("internal runtime error")
+        .map_err(|e| attach::AttachError::Io(e.to_string()))?
+        .map_err(|e| attach::AttachError::Io(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn set_read_only(&self) -> Result<(), sqlite::Error> {
+        self.read_only.store(true, Ordering::Release);
+        // The database is opened read-only when the connection is first used
+        if self.connection.get().is_some() {
+            return Err(sqlite::Error::Io(
+                "cannot make an open connection read-only".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn supports_vector_search(&self) -> bool {
+        cfg!(feature = "vector-search")
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        match &self.location {
+            InProcDatabaseLocation::InMemory => None,
+            InProcDatabaseLocation::Path(path) => Some(path),
+        }
+    }
+
     fn summary(&self) -> Option<String> {
         Some(match &self.location {
             InProcDatabaseLocation::InMemory => "a temporary in-memory database".to_string(),
@@ -127,6 +230,23 @@ impl Connection for InProcConnection {
 }
 
 // This function lives outside the query function to make it more readable.
+/// Registers the sqlite-vec extension to be loaded into every connection
+/// opened afterwards.
+#[cfg(feature = "vector-search")]
+fn register_vector_extension() {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        // SAFETY: `sqlite3_vec_init` is an SQLite extension entry point, which
+        // is what `sqlite3_auto_extension` expects, despite its signature.
+        #[allow(clippy::missing_transmute_annotations)]
+        unsafe {
+            rusqlite::ffi::sqlite3_auto_extension(Some(std::mem::transmute(
+                sqlite_vec::sqlite3_vec_init as *const (),
+            )));
+        }
+    });
+}
+
 fn execute_query(
     connection: &Mutex<rusqlite::Connection>,
     query: &str,
@@ -165,6 +285,86 @@ fn execute_query(
     Ok(sqlite::QueryResult { columns, rows })
 }
 
+/// Keeps SQL run on the connection from attaching or detaching databases,
+/// which would let it reach arbitrary files, or detach a database attached
+/// read-only and attach it again writably. The host attaches databases with
+/// the authorizer removed.
+fn guard_attachments(connection: &rusqlite::Connection) {
+    connection.authorizer(Some(|ctx: AuthContext<'_>| match ctx.action {
+        AuthAction::Attach { .. } | AuthAction::Detach { .. } => Authorization::Deny,
+        _ => Authorization::Allow,
+    }));
+}
+
+/// Returns the URI which opens the database file at `path` read-only.
+///
+/// Characters which SQLite treats specially in URI filenames are escaped.
+fn read_only_uri(path: &str) -> String {
+    let mut uri = String::from("file:");
+    if cfg!(windows) && Path::new(path).is_absolute() {
+        // SQLite expects an absolute Windows path as `file:/C:/dir/db.sqlite`
+        uri.push('/');
+    }
+    for c in path.chars() {
+        match c {
+            '%' => uri.push_str("%25"),
+            '?' => uri.push_str("%3f"),
+            '#' => uri.push_str("%23"),
+            '\\' if cfg!(windows) => uri.push('/'),
+            c => uri.push(c),
+        }
+    }
+    uri.push_str("?mode=ro");
+    uri
+}
+
+/// Copies the database at `source` to `destination` using SQLite's online
+/// backup API.
+///
+/// The backup reads through its own connection, so it doesn't hold the lock on
+/// the connection used for queries, and it copies a few pages at a time so
+/// that writers aren't blocked for the duration of the backup.
+///
+/// The destination file is created exclusively, so an existing file is never
+/// overwritten, and it is removed again if the backup fails.
+fn backup_database(source: &Path, destination: &Path) -> Result<(), backup::Error> {
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            backup::Error::Io(format!(
+                "failed to create backup directory '{}': {e}",
+                parent.display()
+            ))
+        })?;
+    }
+    match std::fs::File::create_new(destination) {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(backup::Error::InvalidDestination)
+        }
+        Err(e) => {
+            return Err(backup::Error::Io(format!(
+                "failed to create backup '{}': {e}",
+                destination.display()
+            )))
+        }
+    }
+    let result = copy_database(source, destination);
+    if result.is_err() {
+        _ = std::fs::remove_file(destination);
+    }
+    result.map_err(|e| backup::Error::Io(format!("{e:#}")))
+}
+
+fn copy_database(source: &Path, destination: &Path) -> anyhow::Result<()> {
+    let source = rusqlite::Connection::open(source).context("failed to open database")?;
+    let mut destination = rusqlite::Connection::open(destination)
+        .with_context(|| format!("failed to open backup '{}'", destination.display()))?;
+    rusqlite::backup::Backup::new(&source, &mut destination)
+        .context("failed to start backup")?
+        .run_to_completion(BACKUP_PAGES_PER_STEP, BACKUP_STEP_PAUSE, None)
+        .context("backup failed")
+}
+
 fn convert_data(
     arguments: impl Iterator<Item = sqlite::Value>,
 ) -> impl Iterator<Item = rusqlite::types::Value> {
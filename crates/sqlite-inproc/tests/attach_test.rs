@@ -0,0 +1,468 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use spin_factor_sqlite::{ConnectionCreator, RuntimeConfig, SqliteFactor};
+use spin_factors::{
+    anyhow::{self, Context as _},
+    wasmtime::component::Resource,
+    RuntimeFactors,
+};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+use spin_world::spin::{sqlite3_0_0::sqlite as v3, sqlite3_1_0::attach};
+use v3::HostConnection as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    sqlite: SqliteFactor,
+}
+
+/// Builds a test environment with the "default" and "archive" databases in
+/// `dir`, and an in-memory "scratch" database.
+fn test_env(dir: &Path) -> anyhow::Result<TestEnvironment<TestFactors>> {
+    let creator = |location: InProcDatabaseLocation| -> Arc<dyn ConnectionCreator> {
+        Arc::new(
+            move || -> anyhow::Result<Box<dyn spin_factor_sqlite::Connection>> {
+                let connection = InProcConnection::new(location.clone())
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                Ok(Box::new(connection))
+            },
+        )
+    };
+    let connection_creators = HashMap::from([
+        (
+            "default".to_owned(),
+            creator(InProcDatabaseLocation::Path(dir.join("default.db"))),
+        ),
+        (
+            "archive".to_owned(),
+            creator(InProcDatabaseLocation::Path(dir.join("archive.db"))),
+        ),
+        (
+            "scratch".to_owned(),
+            creator(InProcDatabaseLocation::InMemory),
+        ),
+    ]);
+    TestEnvironment::new(TestFactors {
+        sqlite: SqliteFactor::new(),
+    })
+    .runtime_config(TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    })
+}
+
+#[tokio::test]
+async fn attached_database_can_be_queried() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = test_env(dir.path())?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default", "archive"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    let archive = state.sqlite.open("archive".into()).await?;
+    for statement in [
+        "CREATE TABLE pets (name TEXT NOT NULL)",
+        "INSERT INTO pets (name) VALUES ('Splodge')",
+    ] {
+        state
+            .sqlite
+            .execute(
+                Resource::new_borrow(archive.rep()),
+                statement.into(),
+                vec![],
+            )
+            .await?;
+    }
+
+    let connection = attach::Host::attach(
+        &mut state.sqlite,
+        "default".into(),
+        "archive".into(),
+        "old".into(),
+    )
+    .await?;
+    for statement in [
+        "CREATE TABLE pets (name TEXT NOT NULL)",
+        "INSERT INTO pets SELECT name FROM old.pets",
+    ] {
+        state
+            .sqlite
+            .execute(
+                Resource::new_borrow(connection.rep()),
+                statement.into(),
+                vec![],
+            )
+            .await?;
+    }
+
+    let default = state.sqlite.open("default".into()).await?;
+    let result = state
+        .sqlite
+        .execute(
+            Resource::new_borrow(default.rep()),
+            "SELECT name FROM pets".into(),
+            vec![],
+        )
+        .await?;
+    assert_eq!(result.rows.len(), 1);
+    assert!(matches!(
+        result.rows[0].values.as_slice(),
+        [v3::Value::Text(name)] if name == "Splodge"
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn attach_requires_access_to_both_databases() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = test_env(dir.path())?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    for (main, secondary) in [("default", "archive"), ("archive", "default")] {
+        let result = attach::Host::attach(
+            &mut state.sqlite,
+            main.into(),
+            secondary.into(),
+            "db".into(),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(v3::Error::AccessDenied)),
+            "{main} + {secondary}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn attach_rejects_invalid_aliases_and_in_memory_databases() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = test_env(dir.path())?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default", "archive", "scratch"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    for alias in [
+        "",
+        "main",
+        "TEMP",
+        "1st",
+        "old db",
+        "old\"; DROP TABLE pets; --",
+    ] {
+        let result = attach::Host::attach(
+            &mut state.sqlite,
+            "default".into(),
+            "archive".into(),
+            alias.into(),
+        )
+        .await;
+        assert!(matches!(result, Err(v3::Error::Io(_))), "{alias:?}");
+    }
+
+    let result = attach::Host::attach(
+        &mut state.sqlite,
+        "default".into(),
+        "scratch".into(),
+        "scratch".into(),
+    )
+    .await;
+    assert!(matches!(result, Err(v3::Error::Io(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn open_with_attachments_joins_across_databases() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = test_env(dir.path())?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default", "archive"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    for (database, statements) in [
+        (
+            "default",
+            [
+                "CREATE TABLE owners (id INTEGER PRIMARY KEY, name TEXT NOT NULL)",
+                "INSERT INTO owners (id, name) VALUES (1, 'Lou')",
+            ],
+        ),
+        (
+            "archive",
+            [
+                "CREATE TABLE pets (owner INTEGER NOT NULL, name TEXT NOT NULL)",
+                "INSERT INTO pets (owner, name) VALUES (1, 'Splodge')",
+            ],
+        ),
+    ] {
+        let connection = state.sqlite.open(database.into()).await?;
+        for statement in statements {
+            state
+                .sqlite
+                .execute(
+                    Resource::new_borrow(connection.rep()),
+                    statement.into(),
+                    vec![],
+                )
+                .await?;
+        }
+    }
+
+    let connection = attach::Host::open_with_attachments(
+        &mut state.sqlite,
+        "default".into(),
+        vec![("archive".into(), "arch".into())],
+    )
+    .await?;
+    let result = state
+        .sqlite
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            "SELECT owners.name, pets.name FROM owners JOIN arch.pets ON pets.owner = owners.id"
+                .into(),
+            vec![],
+        )
+        .await?;
+    assert_eq!(result.rows.len(), 1);
+    assert!(matches!(
+        result.rows[0].values.as_slice(),
+        [v3::Value::Text(owner), v3::Value::Text(pet)] if owner == "Lou" && pet == "Splodge"
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn open_with_attachments_refuses_ungranted_databases() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = test_env(dir.path())?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    let result = attach::Host::open_with_attachments(
+        &mut state.sqlite,
+        "default".into(),
+        vec![("archive".into(), "arch".into())],
+    )
+    .await;
+    assert!(
+        matches!(&result, Err(attach::AttachError::AccessDenied(label)) if label == "archive"),
+        "{result:?}"
+    );
+
+    let result = attach::Host::open_with_attachments(
+        &mut state.sqlite,
+        "default".into(),
+        vec![
+            ("default".into(), "arch".into()),
+            ("default".into(), "ARCH".into()),
+        ],
+    )
+    .await;
+    assert!(
+        matches!(&result, Err(attach::AttachError::InvalidAlias(alias)) if alias == "ARCH"),
+        "{result:?}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_only_databases_reject_writes() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    // Create the table while the archive is still writable
+    let mut state = test_env(dir.path())?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["archive"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+    let archive = state.sqlite.open("archive".into()).await?;
+    state
+        .sqlite
+        .execute(
+            Resource::new_borrow(archive.rep()),
+            "CREATE TABLE pets (name TEXT NOT NULL)".into(),
+            vec![],
+        )
+        .await?;
+
+    let mut state = test_env(dir.path())?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default", "archive"]
+            sqlite_read_only_databases = ["archive"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    let connection = attach::Host::open_with_attachments(
+        &mut state.sqlite,
+        "default".into(),
+        vec![("archive".into(), "arch".into())],
+    )
+    .await?;
+    state
+        .sqlite
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            "SELECT name FROM arch.pets".into(),
+            vec![],
+        )
+        .await?;
+    let result = state
+        .sqlite
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            "INSERT INTO arch.pets (name) VALUES ('Splodge')".into(),
+            vec![],
+        )
+        .await;
+    assert!(matches!(result, Err(v3::Error::Io(_))), "{result:?}");
+
+    let archive = state.sqlite.open("archive".into()).await?;
+    let result = state
+        .sqlite
+        .execute(
+            Resource::new_borrow(archive.rep()),
+            "INSERT INTO pets (name) VALUES ('Splodge')".into(),
+            vec![],
+        )
+        .await;
+    assert!(matches!(result, Err(v3::Error::Io(_))), "{result:?}");
+    Ok(())
+}
+
+/// Builds the state for a component which may only read "archive", after
+/// creating a table in it.
+async fn read_only_archive_state(dir: &Path) -> anyhow::Result<TestFactorsInstanceState> {
+    let archive = rusqlite::Connection::open(dir.join("archive.db"))?;
+    archive.execute("CREATE TABLE pets (name TEXT NOT NULL)", [])?;
+    drop(archive);
+    test_env(dir)?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default", "archive"]
+            sqlite_read_only_databases = ["archive"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")
+}
+
+fn archived_pets(dir: &Path) -> anyhow::Result<i64> {
+    let archive = rusqlite::Connection::open(dir.join("archive.db"))?;
+    Ok(archive.query_row("SELECT COUNT(*) FROM pets", [], |row| row.get(0))?)
+}
+
+#[tokio::test]
+async fn read_only_databases_cannot_be_made_writable_with_pragmas() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = read_only_archive_state(dir.path()).await?;
+
+    let archive = state.sqlite.open("archive".into()).await?;
+    // Whether or not the pragma is accepted, it mustn't allow writes
+    let _ = state
+        .sqlite
+        .execute(
+            Resource::new_borrow(archive.rep()),
+            "PRAGMA query_only = OFF".into(),
+            vec![],
+        )
+        .await;
+    let result = state
+        .sqlite
+        .execute(
+            Resource::new_borrow(archive.rep()),
+            "INSERT INTO pets (name) VALUES ('Splodge')".into(),
+            vec![],
+        )
+        .await;
+    assert!(matches!(result, Err(v3::Error::Io(_))), "{result:?}");
+    assert_eq!(archived_pets(dir.path())?, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_only_attachments_cannot_be_reattached_writably() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = read_only_archive_state(dir.path()).await?;
+
+    let connection = attach::Host::open_with_attachments(
+        &mut state.sqlite,
+        "default".into(),
+        vec![("archive".into(), "arch".into())],
+    )
+    .await?;
+    let archive_path = dir.path().join("archive.db");
+    let archive_path = archive_path.to_str().unwrap();
+    for statement in [
+        "DETACH DATABASE arch".to_owned(),
+        format!("ATTACH DATABASE '{archive_path}' AS rw"),
+    ] {
+        let result = state
+            .sqlite
+            .execute(
+                Resource::new_borrow(connection.rep()),
+                statement.clone(),
+                vec![],
+            )
+            .await;
+        assert!(
+            matches!(result, Err(v3::Error::Io(_))),
+            "{statement}: {result:?}"
+        );
+    }
+    for statement in [
+        "INSERT INTO arch.pets (name) VALUES ('Splodge')",
+        "INSERT INTO rw.pets (name) VALUES ('Splodge')",
+    ] {
+        let result = state
+            .sqlite
+            .execute(
+                Resource::new_borrow(connection.rep()),
+                statement.into(),
+                vec![],
+            )
+            .await;
+        assert!(
+            matches!(result, Err(v3::Error::Io(_))),
+            "{statement}: {result:?}"
+        );
+    }
+    assert_eq!(archived_pets(dir.path())?, 0);
+    Ok(())
+}
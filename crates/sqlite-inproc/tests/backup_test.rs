@@ -0,0 +1,252 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use spin_factor_key_value::{runtime_config::spin::MakeKeyValueStore as _, StoreManager as _};
+use spin_factor_sqlite::{ConnectionCreator, RuntimeConfig, SqliteFactor};
+use spin_factors::{
+    anyhow::{self, Context as _},
+    wasmtime::component::Resource,
+    RuntimeFactors,
+};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_key_value_spin::{SpinKeyValueRuntimeConfig, SpinKeyValueStore};
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+use spin_world::spin::{sqlite3_0_0::sqlite as v3, sqlite3_1_0::backup};
+use v3::HostConnection as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    sqlite: SqliteFactor,
+}
+
+/// Builds a test environment with the "default" database at `db_path`.
+fn test_env(db_path: PathBuf) -> anyhow::Result<TestEnvironment<TestFactors>> {
+    test_env_with_databases([("default", db_path)])
+}
+
+/// Builds a test environment with a database at each of the given paths.
+fn test_env_with_databases<const N: usize>(
+    databases: [(&str, PathBuf); N],
+) -> anyhow::Result<TestEnvironment<TestFactors>> {
+    let connection_creators: HashMap<String, Arc<dyn ConnectionCreator>> = databases
+        .into_iter()
+        .map(|(label, db_path)| {
+            let creator = move || -> anyhow::Result<Box<dyn spin_factor_sqlite::Connection>> {
+                let location = InProcDatabaseLocation::Path(db_path.clone());
+                let connection =
+                    InProcConnection::new(location).map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                Ok(Box::new(connection))
+            };
+            (label.to_owned(), Arc::new(creator) as _)
+        })
+        .collect();
+    TestEnvironment::new(TestFactors {
+        sqlite: SqliteFactor::new(),
+    })
+    .runtime_config(TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    })
+}
+
+/// Creates a table in the database and inserts a row into it.
+async fn insert_pet(state: &mut TestFactorsInstanceState, database: &str) -> anyhow::Result<()> {
+    let connection = state.sqlite.open(database.into()).await?;
+    for statement in [
+        "CREATE TABLE pets (name TEXT NOT NULL)",
+        "INSERT INTO pets (name) VALUES ('Splodge')",
+    ] {
+        state
+            .sqlite
+            .execute(
+                Resource::new_borrow(connection.rep()),
+                statement.into(),
+                vec![],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_is_a_valid_database() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("sqlite_db.db");
+    let mut state = test_env(db_path)?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default"]
+            sqlite_backup = true
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    insert_pet(&mut state, "default").await?;
+
+    backup::Host::backup(
+        &mut state.sqlite,
+        "default".into(),
+        "nightly/snapshot.db".into(),
+    )
+    .await?;
+
+    let backup_path = dir.path().join("backups/nightly/snapshot.db");
+    let contents = std::fs::read(&backup_path)?;
+    assert!(contents.starts_with(b"SQLite format 3\0"));
+
+    let backup = rusqlite::Connection::open(&backup_path)?;
+    let name: String = backup.query_row("SELECT name FROM pets", [], |row| row.get(0))?;
+    assert_eq!(name, "Splodge");
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_requires_permission() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = test_env(dir.path().join("sqlite_db.db"))?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default"]
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    let result =
+        backup::Host::backup(&mut state.sqlite, "default".into(), "snapshot.db".into()).await;
+    assert!(matches!(result, Err(backup::Error::AccessDenied)));
+    assert!(!dir.path().join("snapshot.db").exists());
+    assert!(!dir.path().join("backups/snapshot.db").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_rejects_destinations_outside_database_directory() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let mut state = test_env(dir.path().join("db/sqlite_db.db"))?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default"]
+            sqlite_backup = true
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+
+    let absolute = dir.path().join("snapshot.db").display().to_string();
+    for destination in ["../snapshot.db", "", ".", "a/../../b.db", absolute.as_str()] {
+        let result =
+            backup::Host::backup(&mut state.sqlite, "default".into(), destination.into()).await;
+        assert!(
+            matches!(result, Err(backup::Error::InvalidDestination)),
+            "{destination:?}: {result:?}"
+        );
+    }
+    assert!(!dir.path().join("snapshot.db").exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_cannot_overwrite_another_database() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    // A second label's database which happens to live in the default
+    // database's backups directory
+    let other_path = dir.path().join("backups/other.db");
+    let mut state = test_env_with_databases([
+        ("default", dir.path().join("sqlite_db.db")),
+        ("other", other_path.clone()),
+    ])?
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+        sqlite_backup = true
+    })
+    .build_instance_state()
+    .await
+    .context("build_instance_state failed")?;
+    insert_pet(&mut state, "default").await?;
+
+    for destination in ["../sqlite_db.db", "other.db", "./other.db"] {
+        let result =
+            backup::Host::backup(&mut state.sqlite, "default".into(), destination.into()).await;
+        assert!(
+            matches!(result, Err(backup::Error::InvalidDestination)),
+            "{destination:?}: {result:?}"
+        );
+    }
+    // The other database hasn't been created yet, so refusing it can't rely
+    // on the file existing
+    assert!(!other_path.exists());
+
+    // Once it exists, it is still refused
+    let other = rusqlite::Connection::open(&other_path)?;
+    other.execute_batch(
+        "CREATE TABLE secrets (value TEXT); INSERT INTO secrets VALUES ('s3cret')",
+    )?;
+    drop(other);
+    let result = backup::Host::backup(&mut state.sqlite, "default".into(), "other.db".into()).await;
+    assert!(matches!(result, Err(backup::Error::InvalidDestination)));
+    let other = rusqlite::Connection::open(&other_path)?;
+    let value: String = other.query_row("SELECT value FROM secrets", [], |row| row.get(0))?;
+    assert_eq!(value, "s3cret");
+    Ok(())
+}
+
+#[tokio::test]
+async fn backup_cannot_overwrite_key_value_store() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    // The default key-value store lives alongside the default database, and
+    // another store may have been configured in its backups directory
+    let default_kv = dir.path().join("sqlite_key_value.db");
+    let backups_kv = dir.path().join("backups/kv.db");
+    for path in [&default_kv, &backups_kv] {
+        let store = SpinKeyValueStore::new(Some(dir.path().to_owned()))
+            .make_store(SpinKeyValueRuntimeConfig::new(Some(path.clone())))?
+            .get("default")
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        store
+            .set("key", b"value")
+            .await
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+    }
+
+    let mut state = test_env(dir.path().join("sqlite_db.db"))?
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            sqlite_databases = ["default"]
+            sqlite_backup = true
+        })
+        .build_instance_state()
+        .await
+        .context("build_instance_state failed")?;
+    insert_pet(&mut state, "default").await?;
+
+    for destination in ["../sqlite_key_value.db", "kv.db"] {
+        let result =
+            backup::Host::backup(&mut state.sqlite, "default".into(), destination.into()).await;
+        assert!(
+            matches!(result, Err(backup::Error::InvalidDestination)),
+            "{destination:?}: {result:?}"
+        );
+    }
+    for path in [&default_kv, &backups_kv] {
+        let kv = rusqlite::Connection::open(path)?;
+        let value: Vec<u8> =
+            kv.query_row("SELECT value FROM spin_key_value", [], |row| row.get(0))?;
+        assert_eq!(value, b"value");
+        let has_pets: bool = kv.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'pets')",
+            [],
+            |row| row.get(0),
+        )?;
+        assert!(!has_pets, "{} was overwritten", path.display());
+    }
+    Ok(())
+}
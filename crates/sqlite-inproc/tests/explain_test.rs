@@ -0,0 +1,132 @@
+use std::{collections::HashMap, sync::Arc};
+
+use spin_factor_sqlite::{ConnectionCreator, RuntimeConfig, SqliteFactor};
+use spin_factors::{
+    anyhow::{self, Context as _},
+    wasmtime::component::Resource,
+    RuntimeFactors,
+};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+use spin_world::spin::{sqlite3_0_0::sqlite as v3, sqlite3_1_0::explain};
+use v3::HostConnection as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    sqlite: SqliteFactor,
+}
+
+async fn instance_state(manifest: toml::Table) -> anyhow::Result<TestFactorsInstanceState> {
+    let creator = || -> anyhow::Result<Box<dyn spin_factor_sqlite::Connection>> {
+        let connection = InProcConnection::new(InProcDatabaseLocation::InMemory)
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        Ok(Box::new(connection))
+    };
+    let connection_creators: HashMap<String, Arc<dyn ConnectionCreator>> =
+        HashMap::from([("default".to_owned(), Arc::new(creator) as _)]);
+    TestEnvironment::new(TestFactors {
+        sqlite: SqliteFactor::new(),
+    })
+    .extend_manifest(manifest)
+    .runtime_config(TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    })?
+    .build_instance_state()
+    .await
+    .context("build_instance_state failed")
+}
+
+async fn explain(
+    state: &mut TestFactorsInstanceState,
+    connection: &Resource<v3::Connection>,
+    statement: &str,
+    parameters: Vec<v3::Value>,
+) -> Result<String, v3::Error> {
+    explain::Host::explain(
+        &mut state.sqlite,
+        Resource::new_borrow(connection.rep()),
+        statement.into(),
+        parameters,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn explain_requires_capability() -> anyhow::Result<()> {
+    let mut state = instance_state(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+    })
+    .await?;
+    let connection = state.sqlite.open("default".into()).await?;
+    assert!(matches!(
+        explain(&mut state, &connection, "SELECT 1", vec![]).await,
+        Err(v3::Error::AccessDenied)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn explain_returns_plan_without_executing() -> anyhow::Result<()> {
+    let mut state = instance_state(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+        sqlite_explain = true
+    })
+    .await?;
+    let connection = state.sqlite.open("default".into()).await?;
+    for statement in [
+        "CREATE TABLE orders (id INTEGER PRIMARY KEY, customer TEXT, total REAL)",
+        "CREATE INDEX orders_by_customer ON orders (customer)",
+    ] {
+        state
+            .sqlite
+            .execute(
+                Resource::new_borrow(connection.rep()),
+                statement.into(),
+                vec![],
+            )
+            .await?;
+    }
+
+    let plan = explain(
+        &mut state,
+        &connection,
+        "SELECT * FROM orders WHERE customer = ? ORDER BY total",
+        vec![v3::Value::Text("alice".into())],
+    )
+    .await?;
+    assert!(plan.starts_with("QUERY PLAN\n"), "{plan}");
+    assert!(
+        plan.contains("SEARCH orders USING INDEX orders_by_customer"),
+        "{plan}"
+    );
+    assert!(plan.contains("`--USE TEMP B-TREE FOR ORDER BY"), "{plan}");
+
+    explain(
+        &mut state,
+        &connection,
+        "INSERT INTO orders (customer, total) VALUES ('bob', 1.0)",
+        vec![],
+    )
+    .await?;
+    let result = state
+        .sqlite
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            "SELECT COUNT(*) FROM orders".into(),
+            vec![],
+        )
+        .await?;
+    assert_eq!(result.rows[0].values, [v3::Value::Integer(0)]);
+
+    assert!(matches!(
+        explain(&mut state, &connection, "SELECT * FROM missing", vec![]).await,
+        Err(v3::Error::Io(_))
+    ));
+    Ok(())
+}
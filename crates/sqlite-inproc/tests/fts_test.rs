@@ -0,0 +1,182 @@
+use std::{collections::HashMap, sync::Arc};
+
+use spin_factor_sqlite::{ConnectionCreator, RuntimeConfig, SqliteFactor};
+use spin_factors::{
+    anyhow::{self, Context as _},
+    wasmtime::component::Resource,
+    RuntimeFactors,
+};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+use spin_world::spin::{sqlite3_0_0::sqlite as v3, sqlite3_1_0::fts};
+use v3::HostConnection as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    sqlite: SqliteFactor,
+}
+
+async fn instance_state() -> anyhow::Result<TestFactorsInstanceState> {
+    let creator = || -> anyhow::Result<Box<dyn spin_factor_sqlite::Connection>> {
+        let connection = InProcConnection::new(InProcDatabaseLocation::InMemory)
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        Ok(Box::new(connection))
+    };
+    let connection_creators: HashMap<String, Arc<dyn ConnectionCreator>> =
+        HashMap::from([("default".to_owned(), Arc::new(creator) as _)]);
+    TestEnvironment::new(TestFactors {
+        sqlite: SqliteFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+    })
+    .runtime_config(TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    })?
+    .build_instance_state()
+    .await
+    .context("build_instance_state failed")
+}
+
+async fn execute(
+    state: &mut TestFactorsInstanceState,
+    connection: &Resource<v3::Connection>,
+    statement: &str,
+) -> anyhow::Result<v3::QueryResult> {
+    Ok(state
+        .sqlite
+        .execute(
+            Resource::new_borrow(connection.rep()),
+            statement.into(),
+            vec![],
+        )
+        .await?)
+}
+
+async fn search(
+    state: &mut TestFactorsInstanceState,
+    connection: &Resource<v3::Connection>,
+    query: &str,
+) -> anyhow::Result<Vec<String>> {
+    let result = fts::Host::fts_search(
+        &mut state.sqlite,
+        Resource::new_borrow(connection.rep()),
+        "notes".into(),
+        query.into(),
+        10,
+    )
+    .await?;
+    assert_eq!(result.columns, ["title", "body"]);
+    Ok(result
+        .rows
+        .into_iter()
+        .map(|row| match &row.values[0] {
+            v3::Value::Text(title) => title.clone(),
+            other => panic!("unexpected title {other:?}"),
+        })
+        .collect())
+}
+
+#[tokio::test]
+async fn index_tracks_table_changes() -> anyhow::Result<()> {
+    let mut state = instance_state().await?;
+    let connection = state.sqlite.open("default".into()).await?;
+    execute(
+        &mut state,
+        &connection,
+        "CREATE TABLE notes (title TEXT, body TEXT)",
+    )
+    .await?;
+    execute(
+        &mut state,
+        &connection,
+        "INSERT INTO notes VALUES ('existing', 'indexed when the index is created')",
+    )
+    .await?;
+
+    fts::Host::create_fts_index(
+        &mut state.sqlite,
+        Resource::new_borrow(connection.rep()),
+        "notes".into(),
+        vec!["title".into(), "body".into()],
+    )
+    .await?;
+    assert_eq!(
+        search(&mut state, &connection, "indexed").await?,
+        ["existing"]
+    );
+
+    execute(
+        &mut state,
+        &connection,
+        "INSERT INTO notes VALUES ('shopping', 'buy apples and pears')",
+    )
+    .await?;
+    assert_eq!(
+        search(&mut state, &connection, "apples").await?,
+        ["shopping"]
+    );
+
+    execute(
+        &mut state,
+        &connection,
+        "UPDATE notes SET body = 'buy oranges' WHERE title = 'shopping'",
+    )
+    .await?;
+    assert!(search(&mut state, &connection, "apples").await?.is_empty());
+    assert_eq!(
+        search(&mut state, &connection, "oranges").await?,
+        ["shopping"]
+    );
+
+    execute(
+        &mut state,
+        &connection,
+        "DELETE FROM notes WHERE title = 'shopping'",
+    )
+    .await?;
+    assert!(search(&mut state, &connection, "oranges").await?.is_empty());
+
+    // Creating the index again is harmless
+    fts::Host::create_fts_index(
+        &mut state.sqlite,
+        Resource::new_borrow(connection.rep()),
+        "notes".into(),
+        vec!["title".into(), "body".into()],
+    )
+    .await?;
+    assert_eq!(
+        search(&mut state, &connection, "indexed").await?,
+        ["existing"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_index_creation_leaves_no_trace() -> anyhow::Result<()> {
+    let mut state = instance_state().await?;
+    let connection = state.sqlite.open("default".into()).await?;
+    execute(&mut state, &connection, "CREATE TABLE notes (title TEXT)").await?;
+
+    let result = fts::Host::create_fts_index(
+        &mut state.sqlite,
+        Resource::new_borrow(connection.rep()),
+        "notes".into(),
+        vec!["title".into(), "no_such_column".into()],
+    )
+    .await;
+    assert!(matches!(result, Err(v3::Error::Io(_))));
+
+    let tables = execute(
+        &mut state,
+        &connection,
+        "SELECT name FROM sqlite_schema WHERE name LIKE 'notes_fts%'",
+    )
+    .await?;
+    assert!(tables.rows.is_empty());
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use spin_factor_sqlite::{Connection as _, ConnectionCreator, RuntimeConfig, SqliteFactor};
+use spin_factors::{anyhow, App, RuntimeFactors};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    sqlite: SqliteFactor,
+}
+
+/// Configures an app with the "default" database at `db_path` and the given
+/// `sqlite_migrations` metadata, returning the SQLite factor's app state.
+async fn configure_app(
+    db_path: PathBuf,
+    migrations: serde_json::Value,
+) -> anyhow::Result<spin_factor_sqlite::AppState> {
+    let creator = move || -> anyhow::Result<Box<dyn spin_factor_sqlite::Connection>> {
+        let location = InProcDatabaseLocation::Path(db_path.clone());
+        let connection = InProcConnection::new(location).map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        Ok(Box::new(connection))
+    };
+    let connection_creators: HashMap<String, Arc<dyn ConnectionCreator>> =
+        HashMap::from([("default".to_owned(), Arc::new(creator) as _)]);
+    let env = TestEnvironment::new(TestFactors {
+        sqlite: SqliteFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+    })
+    .runtime_config(TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    })?;
+
+    let mut locked_app = env.build_locked_app().await?;
+    locked_app
+        .metadata
+        .insert("sqlite_migrations".into(), migrations);
+    let configured_app = env
+        .factors
+        .configure_app(App::new("test-app", locked_app), env.runtime_config)?;
+    Ok(configured_app.app_state::<SqliteFactor>()?.clone())
+}
+
+fn migration(name: &str, sql: &str) -> serde_json::Value {
+    serde_json::json!({ "name": name, "sql": sql })
+}
+
+fn tables(db_path: &Path) -> anyhow::Result<Vec<String>> {
+    let conn = rusqlite::Connection::open(db_path)?;
+    let mut statement =
+        conn.prepare("SELECT name FROM sqlite_schema WHERE type = 'table' ORDER BY name")?;
+    let names = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(names)
+}
+
+#[tokio::test]
+async fn migrations_are_applied_once_in_order() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("sqlite_db.db");
+    let migrations = serde_json::json!({
+        "default": [
+            migration("001_pets.sql", "CREATE TABLE pets (name TEXT NOT NULL);"),
+            migration("002_splodge.sql", "INSERT INTO pets (name) VALUES ('Splodge')"),
+        ]
+    });
+
+    let sqlite = configure_app(db_path.clone(), migrations.clone()).await?;
+    sqlite.run_migrations().await?;
+    assert_eq!(tables(&db_path)?, ["_spin_migrations", "pets"]);
+
+    // Running again, e.g. on the next `spin up`, applies nothing new
+    let sqlite = configure_app(db_path.clone(), migrations).await?;
+    sqlite.run_migrations().await?;
+    let conn = rusqlite::Connection::open(&db_path)?;
+    let pets: i64 = conn.query_row("SELECT COUNT(*) FROM pets", [], |row| row.get(0))?;
+    assert_eq!(pets, 1);
+
+    // A new migration is applied on its own
+    let migrations = serde_json::json!({
+        "default": [
+            migration("001_pets.sql", "CREATE TABLE pets (name TEXT NOT NULL);"),
+            migration("002_splodge.sql", "INSERT INTO pets (name) VALUES ('Splodge')"),
+            migration("003_owners.sql", "CREATE TABLE owners (name TEXT NOT NULL);"),
+        ]
+    });
+    let sqlite = configure_app(db_path.clone(), migrations).await?;
+    sqlite.run_migrations().await?;
+    assert_eq!(tables(&db_path)?, ["_spin_migrations", "owners", "pets"]);
+    let pets: i64 = conn.query_row("SELECT COUNT(*) FROM pets", [], |row| row.get(0))?;
+    assert_eq!(pets, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_migration_is_rolled_back_and_reported() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("sqlite_db.db");
+    let migrations = serde_json::json!({
+        "default": [
+            migration("001_pets.sql", "CREATE TABLE pets (name TEXT NOT NULL);"),
+            migration("002_broken.sql", "CREATE TABLE owners (name TEXT); INSERT INTO nowhere VALUES (1);"),
+            migration("003_never.sql", "CREATE TABLE never (name TEXT);"),
+        ]
+    });
+
+    let sqlite = configure_app(db_path.clone(), migrations).await?;
+    let err = sqlite
+        .run_migrations()
+        .await
+        .expect_err("migration should have failed");
+    let err = format!("{err:#}");
+    assert!(err.contains("002_broken.sql"), "{err}");
+    assert!(err.contains("'default'"), "{err}");
+
+    // The first migration was applied; the broken one left no trace
+    assert_eq!(tables(&db_path)?, ["_spin_migrations", "pets"]);
+
+    // The database is still usable through Spin
+    let connection = InProcConnection::new(InProcDatabaseLocation::Path(db_path))
+        .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+    let applied = connection
+        .query("SELECT name FROM _spin_migrations", vec![])
+        .await
+        .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+    assert_eq!(applied.rows.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn migrations_for_undefined_databases_are_rejected() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let migrations = serde_json::json!({
+        "nonexistent": [migration("001_pets.sql", "CREATE TABLE pets (name TEXT);")]
+    });
+    let Err(err) = configure_app(dir.path().join("sqlite_db.db"), migrations).await else {
+        anyhow::bail!("expected configure_app to fail");
+    };
+    assert!(format!("{err:#}").contains("'nonexistent'"), "{err:#}");
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::{collections::HashMap, sync::Arc};
+
+use spin_factor_sqlite::{ConnectionCreator, RuntimeConfig, SqliteFactor};
+use spin_factors::{
+    anyhow::{self, Context as _},
+    wasmtime::component::Resource,
+    RuntimeFactors,
+};
+use spin_factors_test::{toml, TestEnvironment};
+use spin_sqlite_inproc::{InProcConnection, InProcDatabaseLocation};
+use spin_world::spin::{sqlite3_0_0::sqlite as v3, sqlite3_1_0::vector};
+use v3::HostConnection as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    sqlite: SqliteFactor,
+}
+
+async fn instance_state(manifest: toml::Table) -> anyhow::Result<TestFactorsInstanceState> {
+    let creator = || -> anyhow::Result<Box<dyn spin_factor_sqlite::Connection>> {
+        let connection = InProcConnection::new(InProcDatabaseLocation::InMemory)
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+        Ok(Box::new(connection))
+    };
+    let connection_creators: HashMap<String, Arc<dyn ConnectionCreator>> =
+        HashMap::from([("default".to_owned(), Arc::new(creator) as _)]);
+    TestEnvironment::new(TestFactors {
+        sqlite: SqliteFactor::new(),
+    })
+    .extend_manifest(manifest)
+    .runtime_config(TestFactorsRuntimeConfig {
+        sqlite: Some(RuntimeConfig {
+            connection_creators,
+        }),
+    })?
+    .build_instance_state()
+    .await
+    .context("build_instance_state failed")
+}
+
+async fn create_index(
+    state: &mut TestFactorsInstanceState,
+    connection: &Resource<v3::Connection>,
+) -> Result<(), v3::Error> {
+    vector::Host::create_vector_index(
+        &mut state.sqlite,
+        Resource::new_borrow(connection.rep()),
+        "docs".into(),
+        "embedding".into(),
+        2,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn vector_search_requires_capability() -> anyhow::Result<()> {
+    let mut state = instance_state(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+    })
+    .await?;
+    let connection = state.sqlite.open("default".into()).await?;
+    assert!(matches!(
+        create_index(&mut state, &connection).await,
+        Err(v3::Error::AccessDenied)
+    ));
+    Ok(())
+}
+
+#[cfg(not(feature = "vector-search"))]
+#[tokio::test]
+async fn vector_search_requires_extension() -> anyhow::Result<()> {
+    let mut state = instance_state(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+        sqlite_vector_search = true
+    })
+    .await?;
+    let connection = state.sqlite.open("default".into()).await?;
+    assert!(matches!(
+        create_index(&mut state, &connection).await,
+        Err(v3::Error::Io(msg)) if msg.contains("does not support vector search")
+    ));
+    Ok(())
+}
+
+#[cfg(feature = "vector-search")]
+#[tokio::test]
+async fn nearest_neighbors_track_table_changes() -> anyhow::Result<()> {
+    let mut state = instance_state(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        sqlite_databases = ["default"]
+        sqlite_vector_search = true
+    })
+    .await?;
+    let connection = state.sqlite.open("default".into()).await?;
+
+    async fn execute(
+        state: &mut TestFactorsInstanceState,
+        connection: &Resource<v3::Connection>,
+        statement: &str,
+    ) -> anyhow::Result<v3::QueryResult> {
+        Ok(state
+            .sqlite
+            .execute(
+                Resource::new_borrow(connection.rep()),
+                statement.into(),
+                vec![],
+            )
+            .await?)
+    }
+
+    async fn search(
+        state: &mut TestFactorsInstanceState,
+        connection: &Resource<v3::Connection>,
+        embedding: Vec<f32>,
+    ) -> anyhow::Result<Vec<String>> {
+        let result = vector::Host::search_nearest_neighbors(
+            &mut state.sqlite,
+            Resource::new_borrow(connection.rep()),
+            "docs".into(),
+            embedding,
+            2,
+        )
+        .await?;
+        assert_eq!(result.columns, ["title", "embedding", "distance"]);
+        Ok(result
+            .rows
+            .into_iter()
+            .map(|row| match &row.values[0] {
+                v3::Value::Text(title) => title.clone(),
+                other => panic!("unexpected title {other:?}"),
+            })
+            .collect())
+    }
+
+    execute(
+        &mut state,
+        &connection,
+        "CREATE TABLE docs (title TEXT, embedding BLOB)",
+    )
+    .await?;
+    execute(
+        &mut state,
+        &connection,
+        "INSERT INTO docs VALUES ('east', '[1, 0]'), ('north', '[0, 1]'), ('unembedded', NULL)",
+    )
+    .await?;
+    create_index(&mut state, &connection).await?;
+    assert_eq!(
+        search(&mut state, &connection, vec![0.9, 0.1]).await?,
+        ["east", "north"]
+    );
+
+    // Embeddings stored with insert-vector are indexed
+    execute(
+        &mut state,
+        &connection,
+        "INSERT INTO docs VALUES ('west', NULL)",
+    )
+    .await?;
+    let rowid = state
+        .sqlite
+        .last_insert_rowid(Resource::new_borrow(connection.rep()))
+        .await?;
+    vector::Host::insert_vector(
+        &mut state.sqlite,
+        Resource::new_borrow(connection.rep()),
+        "docs".into(),
+        "embedding".into(),
+        rowid,
+        vec![-1.0, 0.0],
+    )
+    .await?;
+    assert_eq!(
+        search(&mut state, &connection, vec![-0.9, 0.1]).await?,
+        ["west", "north"]
+    );
+
+    // Deleted rows are no longer found
+    execute(
+        &mut state,
+        &connection,
+        "DELETE FROM docs WHERE title = 'west'",
+    )
+    .await?;
+    assert_eq!(
+        search(&mut state, &connection, vec![-0.9, 0.1]).await?,
+        ["north", "east"]
+    );
+
+    // Storing an embedding needs an existing row
+    assert!(matches!(
+        vector::Host::insert_vector(
+            &mut state.sqlite,
+            Resource::new_borrow(connection.rep()),
+            "docs".into(),
+            "embedding".into(),
+            rowid,
+            vec![-1.0, 0.0],
+        )
+        .await,
+        Err(v3::Error::Io(msg)) if msg.contains("no row")
+    ));
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use anyhow::Context;
 use async_trait::async_trait;
 use spin_factor_sqlite::Connection;
-use spin_world::spin::sqlite::sqlite as v3;
-use spin_world::spin::sqlite::sqlite::{self, RowResult};
+use spin_world::spin::sqlite3_0_0::sqlite as v3;
+use spin_world::spin::sqlite3_0_0::sqlite::{self, RowResult};
 use tokio::sync::OnceCell;
 
 /// A lazy wrapper around a [`LibSqlConnection`] that implements the [`Connection`] trait.
@@ -0,0 +1,443 @@
+//! Implementation for the Spin gRPC trigger.
+
+mod codec;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use bytes::Bytes;
+use clap::Args;
+use futures::future::BoxFuture;
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use serde::Deserialize;
+use spin_factors::RuntimeFactors;
+use spin_trigger::{App, Trigger};
+use spin_trigger_http::TlsConfig;
+use spin_world::exports::spin::grpc::inbound_grpc::{self, RequestContext};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tonic::body::BoxBody;
+use tonic::metadata::{KeyAndValueRef, MetadataMap};
+use tonic::server::{Grpc, UnaryService};
+use tonic::{Code, Status};
+use tracing::{instrument, Level};
+
+use codec::RawCodec;
+
+/// A [`spin_trigger::TriggerApp`] for the gRPC trigger.
+type TriggerApp<F> = spin_trigger::TriggerApp<GrpcTrigger, F>;
+
+/// The address the server listens on if none is given on the command line.
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:3002";
+
+/// Metadata which describes the call itself rather than being set by the
+/// caller, and so is not passed to components.
+const RESERVED_METADATA: &[&str] = &["content-type", "te", "user-agent"];
+
+pub struct GrpcTrigger {
+    listen_addr: SocketAddr,
+    tls_config: Option<TlsConfig>,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// IP address and port to accept gRPC calls on
+    #[clap(
+        long = "grpc-listen",
+        env = "SPIN_GRPC_LISTEN_ADDR",
+        default_value = DEFAULT_LISTEN_ADDR
+    )]
+    pub address: SocketAddr,
+
+    /// The path to the certificate to use for TLS, as for the HTTP trigger. If this is not set, calls are served over cleartext HTTP/2 (h2c). The cert should be in PEM format
+    #[clap(long, env = "SPIN_TLS_CERT", requires = "tls-key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// The path to the certificate key to use for TLS, as for the HTTP trigger. The key should be in PKCS#8 format
+    #[clap(long, env = "SPIN_TLS_KEY", requires = "tls-cert")]
+    pub tls_key: Option<PathBuf>,
+}
+
+impl CliArgs {
+    fn into_tls_config(self) -> Option<TlsConfig> {
+        match (self.tls_cert, self.tls_key) {
+            (Some(cert_path), Some(key_path)) => Some(TlsConfig {
+                cert_path,
+                key_path,
+            }),
+            (None, None) => None,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// gRPC trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Proto service method, as `package.Service/Method`
+    method: String,
+}
+
+impl<F: RuntimeFactors> Trigger<F> for GrpcTrigger {
+    const TYPE: &'static str = "grpc";
+
+    type CliArgs = CliArgs;
+
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        Ok(Self {
+            listen_addr: cli_args.address,
+            tls_config: cli_args.into_tls_config(),
+        })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<F>) -> anyhow::Result<()> {
+        let trigger_type = <Self as Trigger<F>>::TYPE;
+        let methods = method_components(
+            trigger_app
+                .app()
+                .trigger_configs::<TriggerConfig>(trigger_type)?
+                .into_iter()
+                .map(|(_, config)| config),
+        )?;
+
+        let tls_acceptor = self.tls_config.as_ref().map(tls_acceptor).transpose()?;
+
+        let listener = TcpListener::bind(self.listen_addr)
+            .await
+            .with_context(|| format!("gRPC trigger failed to listen on {}", self.listen_addr))?;
+
+        let scheme = if tls_acceptor.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        println!("Serving gRPC calls on {scheme}://{}", self.listen_addr);
+        println!("Available Methods:");
+        let mut available: Vec<_> = methods.iter().collect();
+        available.sort();
+        for (path, component) in available {
+            println!("  {component}: {}", &path[1..]);
+        }
+
+        let state = Arc::new(AppState {
+            trigger_app,
+            methods,
+        });
+
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::warn!("Failed to accept gRPC connection: {err}");
+                    continue;
+                }
+            };
+            tokio::spawn(
+                state
+                    .clone()
+                    .serve_connection(stream, peer, tls_acceptor.clone()),
+            );
+        }
+    }
+}
+
+/// Maps the request path of each configured method to the component which
+/// handles it.
+fn method_components(
+    configs: impl IntoIterator<Item = TriggerConfig>,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut methods = HashMap::new();
+    for config in configs {
+        let path = method_path(&config.method)?;
+        if let Some(existing) = methods.insert(path, config.component.clone()) {
+            bail!(
+                "gRPC method '{}' is handled by both component '{existing}' and component '{}'",
+                config.method,
+                config.component
+            );
+        }
+    }
+    Ok(methods)
+}
+
+/// The request path of a call to a method given as `package.Service/Method`.
+fn method_path(method: &str) -> anyhow::Result<String> {
+    let method = method.strip_prefix('/').unwrap_or(method);
+    match method.split_once('/') {
+        Some((service, name)) if !service.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Ok(format!("/{service}/{name}"))
+        }
+        _ => bail!(
+            "invalid gRPC method '{method}': expected the fully qualified service name and the method name, \
+             e.g. 'helloworld.Greeter/SayHello'"
+        ),
+    }
+}
+
+/// Creates a TLS acceptor which negotiates HTTP/2, as gRPC clients require.
+fn tls_acceptor(tls_config: &TlsConfig) -> anyhow::Result<TlsAcceptor> {
+    let mut server_config = tls_config.rustls_server_config()?;
+    server_config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(Arc::new(server_config).into())
+}
+
+/// State shared by all the trigger's connections.
+struct AppState<F: RuntimeFactors> {
+    trigger_app: TriggerApp<F>,
+    /// Maps request paths to component IDs.
+    methods: HashMap<String, String>,
+}
+
+impl<F: RuntimeFactors> AppState<F> {
+    /// Serves the calls on a single HTTP/2 connection.
+    async fn serve_connection(
+        self: Arc<Self>,
+        stream: TcpStream,
+        peer: SocketAddr,
+        tls_acceptor: Option<TlsAcceptor>,
+    ) {
+        let service = service_fn(move |req| {
+            let state = self.clone();
+            async move { Ok::<_, Infallible>(state.handle_call(req).await) }
+        });
+        let builder = hyper::server::conn::http2::Builder::new(TokioExecutor::new());
+        let result = match tls_acceptor {
+            Some(acceptor) => match acceptor.accept(stream).await {
+                Ok(stream) => {
+                    builder
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                }
+                Err(err) => {
+                    tracing::debug!("TLS handshake with {peer} failed: {err}");
+                    return;
+                }
+            },
+            None => {
+                builder
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+            }
+        };
+        if let Err(err) = result {
+            tracing::debug!("gRPC connection from {peer} failed: {err}");
+        }
+    }
+
+    /// Routes a call to the component handling its method.
+    async fn handle_call(
+        self: &Arc<Self>,
+        req: http::Request<Incoming>,
+    ) -> http::Response<BoxBody> {
+        let path = req.uri().path();
+        let Some(component_id) = self.methods.get(path).cloned() else {
+            return Status::unimplemented(format!("method {path} is not implemented")).into_http();
+        };
+        let handler = Handler {
+            state: self.clone(),
+            component_id,
+            method: path[1..].to_owned(),
+        };
+        Grpc::new(RawCodec).unary(handler, req).await
+    }
+
+    #[instrument(name = "spin_trigger_grpc.handle_request", skip_all, err(level = Level::INFO), fields(
+        otel.name = method,
+        otel.kind = "server",
+        rpc.system = "grpc",
+        rpc.method = method,
+    ))]
+    async fn dispatch(
+        &self,
+        component_id: &str,
+        method: &str,
+        request: tonic::Request<Bytes>,
+    ) -> Result<tonic::Response<Bytes>, Status> {
+        tracing::trace!("Executing gRPC component {component_id}");
+
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "grpc",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let ctx = RequestContext {
+            method: method.to_owned(),
+            metadata: caller_metadata(request.metadata()),
+        };
+        match self
+            .call_handler(component_id, &ctx, &request.into_inner())
+            .await
+        {
+            Ok(Ok(response)) => Ok(tonic::Response::new(response.into())),
+            Ok(Err(status)) => Err(guest_status(status)),
+            Err(err) => {
+                tracing::error!(
+                    "Component {component_id} failed to handle gRPC call to {method}: {err:?}"
+                );
+                Err(Status::internal(
+                    "the application failed to handle the call",
+                ))
+            }
+        }
+    }
+
+    async fn call_handler(
+        &self,
+        component_id: &str,
+        ctx: &RequestContext,
+        request: &[u8],
+    ) -> anyhow::Result<Result<Vec<u8>, inbound_grpc::Status>> {
+        let (instance, mut store) = self
+            .trigger_app
+            .prepare(component_id)?
+            .instantiate(())
+            .await?;
+
+        let pre = instance.instance_pre(&store);
+        let guest_indices = inbound_grpc::GuestIndices::new(&pre)?;
+        let guest = guest_indices.load(&mut store, &instance)?;
+
+        guest.call_handle_request(&mut store, ctx, request).await
+    }
+}
+
+/// Handles calls to a single method, for [`Grpc::unary`].
+struct Handler<F: RuntimeFactors> {
+    state: Arc<AppState<F>>,
+    component_id: String,
+    method: String,
+}
+
+impl<F: RuntimeFactors> UnaryService<Bytes> for Handler<F> {
+    type Response = Bytes;
+    type Future = BoxFuture<'static, Result<tonic::Response<Bytes>, Status>>;
+
+    fn call(&mut self, request: tonic::Request<Bytes>) -> Self::Future {
+        let state = self.state.clone();
+        let component_id = self.component_id.clone();
+        let method = self.method.clone();
+        Box::pin(async move { state.dispatch(&component_id, &method, request).await })
+    }
+}
+
+/// The ASCII metadata set by the caller, as passed to components.
+fn caller_metadata(metadata: &MetadataMap) -> Vec<(String, String)> {
+    metadata
+        .iter()
+        .filter_map(|entry| match entry {
+            KeyAndValueRef::Ascii(key, value) => Some((key.as_str(), value.to_str().ok()?)),
+            KeyAndValueRef::Binary(..) => None,
+        })
+        .filter(|(key, _)| !key.starts_with("grpc-") && !RESERVED_METADATA.contains(key))
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .collect()
+}
+
+/// The status a call fails with when the component returns an error. Codes
+/// which don't name an error are sent as `UNKNOWN`.
+fn guest_status(status: inbound_grpc::Status) -> Status {
+    let code = match i32::try_from(status.code).map(Code::from_i32) {
+        Ok(Code::Ok) | Err(_) => Code::Unknown,
+        Ok(code) => code,
+    };
+    Status::new(code, status.message)
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::metadata::{BinaryMetadataValue, MetadataValue};
+
+    use super::*;
+
+    #[test]
+    fn method_paths_are_normalized() {
+        assert_eq!(
+            "/helloworld.Greeter/SayHello",
+            method_path("helloworld.Greeter/SayHello").unwrap()
+        );
+        assert_eq!(
+            "/helloworld.Greeter/SayHello",
+            method_path("/helloworld.Greeter/SayHello").unwrap()
+        );
+        for invalid in ["SayHello", "helloworld.Greeter/", "/SayHello", "a/b/c"] {
+            assert!(method_path(invalid).is_err(), "{invalid:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn duplicate_methods_are_rejected() {
+        let config = |component: &str, method: &str| TriggerConfig {
+            component: component.into(),
+            method: method.into(),
+        };
+        let methods = method_components([
+            config("greeter", "helloworld.Greeter/SayHello"),
+            config("farewell", "helloworld.Greeter/SayGoodbye"),
+        ])
+        .unwrap();
+        assert_eq!("greeter", methods["/helloworld.Greeter/SayHello"]);
+        assert_eq!("farewell", methods["/helloworld.Greeter/SayGoodbye"]);
+
+        let err = method_components([
+            config("greeter", "helloworld.Greeter/SayHello"),
+            config("other", "/helloworld.Greeter/SayHello"),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("both component"), "{err}");
+    }
+
+    #[test]
+    fn trigger_config_parses() {
+        let config: TriggerConfig = toml::toml! {
+            component = "greeter"
+            method = "helloworld.Greeter/SayHello"
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!("greeter", config.component);
+        assert_eq!("helloworld.Greeter/SayHello", config.method);
+    }
+
+    #[test]
+    fn only_caller_metadata_is_passed_on() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("x-tenant", MetadataValue::from_static("acme"));
+        metadata.insert(
+            "content-type",
+            MetadataValue::from_static("application/grpc"),
+        );
+        metadata.insert("grpc-timeout", MetadataValue::from_static("5S"));
+        metadata.insert_bin("trace-bin", BinaryMetadataValue::from_bytes(b"\x00\x01"));
+        assert_eq!(
+            vec![("x-tenant".to_owned(), "acme".to_owned())],
+            caller_metadata(&metadata)
+        );
+    }
+
+    #[test]
+    fn guest_status_codes_are_mapped() {
+        let status = |code| inbound_grpc::Status {
+            code,
+            message: "nope".into(),
+        };
+        let not_found = guest_status(status(5));
+        assert_eq!(Code::NotFound, not_found.code());
+        assert_eq!("nope", not_found.message());
+        assert_eq!(Code::Unknown, guest_status(status(0)).code());
+        assert_eq!(Code::Unknown, guest_status(status(99)).code());
+        assert_eq!(Code::Unknown, guest_status(status(u32::MAX)).code());
+    }
+}
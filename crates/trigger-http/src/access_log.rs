@@ -0,0 +1,193 @@
+//! Recording requests in the access log configured by the manifest's
+//! `[application.trigger.http.access_log]` section.
+
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use http::{header, Request, Response, StatusCode};
+use http_body_util::BodyExt;
+use hyper::body::{Body as _, Bytes, Frame, SizeHint};
+use spin_http_access_log::{AccessLogEntry, AccessLogWriter};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// The parts of a request which are recorded in the access log, captured
+/// when the request is received.
+pub(crate) struct PendingAccessLogEntry {
+    writer: Arc<AccessLogWriter>,
+    client_addr: SocketAddr,
+    time: chrono::DateTime<chrono::Utc>,
+    start: Instant,
+    method: String,
+    target: String,
+    protocol: String,
+    referer: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl PendingAccessLogEntry {
+    pub(crate) fn new<B>(
+        writer: Arc<AccessLogWriter>,
+        req: &Request<B>,
+        client_addr: SocketAddr,
+    ) -> Self {
+        let header_value = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+        let target = req
+            .uri()
+            .path_and_query()
+            .map_or_else(|| req.uri().path().to_owned(), |pq| pq.as_str().to_owned());
+        Self {
+            writer,
+            client_addr,
+            time: chrono::Utc::now(),
+            start: Instant::now(),
+            method: req.method().to_string(),
+            target,
+            protocol: format!("{:?}", req.version()),
+            referer: header_value(header::REFERER),
+            user_agent: header_value(header::USER_AGENT),
+        }
+    }
+
+    /// Records the outcome of handling the request. A response is recorded
+    /// once its body has been sent, or abandoned by the client; a failure to
+    /// respond is recorded immediately as a 500.
+    pub(crate) fn finish(
+        self,
+        result: anyhow::Result<Response<Body>>,
+    ) -> anyhow::Result<Response<Body>> {
+        match result {
+            Ok(res) => {
+                let status = res.status();
+                Ok(res.map(|inner| {
+                    LoggedBody {
+                        inner,
+                        bytes: 0,
+                        pending: Some((self, status)),
+                    }
+                    .boxed()
+                }))
+            }
+            Err(err) => {
+                self.write(StatusCode::INTERNAL_SERVER_ERROR, 0);
+                Err(err)
+            }
+        }
+    }
+
+    fn write(self, status: StatusCode, bytes: u64) {
+        let entry = AccessLogEntry {
+            client_addr: self.client_addr.ip(),
+            time: self.time,
+            method: &self.method,
+            target: &self.target,
+            protocol: &self.protocol,
+            status: status.as_u16(),
+            bytes,
+            referer: self.referer.as_deref(),
+            user_agent: self.user_agent.as_deref(),
+            duration: self.start.elapsed(),
+        };
+        if let Err(err) = self.writer.write(&entry) {
+            tracing::warn!("Failed to write access log entry: {err}");
+        }
+    }
+}
+
+/// A response body which counts the bytes sent, and writes the access log
+/// entry for its request when it is dropped.
+struct LoggedBody {
+    inner: Body,
+    bytes: u64,
+    pending: Option<(PendingAccessLogEntry, StatusCode)>,
+}
+
+impl hyper::body::Body for LoggedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                this.bytes += data.len() as u64;
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        if let Some((pending, status)) = self.pending.take() {
+            pending.write(status, self.bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_http::body;
+    use spin_http_access_log::AccessLogConfig;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn entry_is_written_when_body_is_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let writer = AccessLogWriter::new(&AccessLogConfig {
+            destination: spin_http_access_log::AccessLogDestination::File,
+            path: Some(path.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let req = Request::get("/orders?page=2")
+            .header(header::USER_AGENT, "curl/8.4.0")
+            .body(())
+            .unwrap();
+        let pending =
+            PendingAccessLogEntry::new(Arc::new(writer), &req, "127.0.0.1:4000".parse().unwrap());
+        let res = Response::builder()
+            .status(StatusCode::CREATED)
+            .body(body::full(Bytes::from_static(b"created")))
+            .unwrap();
+        let res = pending.finish(Ok(res)).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().is_empty());
+
+        res.into_body().collect().await.unwrap();
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            log.starts_with("127.0.0.1 - - ["),
+            "unexpected log entry: {log}"
+        );
+        assert!(
+            log.ends_with("] \"GET /orders?page=2 HTTP/1.1\" 201 7 \"-\" \"curl/8.4.0\"\n"),
+            "unexpected log entry: {log}"
+        );
+    }
+}
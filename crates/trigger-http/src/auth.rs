@@ -0,0 +1,224 @@
+//! Host-enforced authentication for HTTP trigger routes.
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{header, HeaderMap, Response, StatusCode};
+use spin_app::App;
+use spin_factor_variables::VariablesFactor;
+use spin_factors::{ConfiguredApp, RuntimeFactors};
+use spin_http::{body, config::HttpRouteAuthConfig};
+
+use crate::{instrument::MatchedRoute, Body};
+
+/// The realm advertised in `WWW-Authenticate` challenges.
+const REALM: &str = "spin";
+
+/// The credentials a request must present, resolved from application variables.
+pub(crate) enum Credentials {
+    Bearer { token: String },
+    Basic { username: String, password: String },
+}
+
+impl Credentials {
+    /// Resolves the expected credentials for the given auth config.
+    pub async fn resolve<F: RuntimeFactors>(
+        config: &HttpRouteAuthConfig,
+        configured_app: &ConfiguredApp<F>,
+    ) -> anyhow::Result<Self> {
+        let variables = configured_app
+            .app_state::<VariablesFactor>()
+            .context("route authentication requires the variables factor")?;
+        Ok(match config {
+            HttpRouteAuthConfig::Bearer { token_variable } => Self::Bearer {
+                token: resolve_variable(variables, token_variable).await?,
+            },
+            HttpRouteAuthConfig::Basic {
+                username_variable,
+                password_variable,
+            } => Self::Basic {
+                username: resolve_variable(variables, username_variable).await?,
+                password: resolve_variable(variables, password_variable).await?,
+            },
+        })
+    }
+
+    /// Returns whether the given request headers carry matching credentials.
+    pub fn authorize(&self, headers: &HeaderMap) -> bool {
+        let Some(authorization) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+        let Some((scheme, presented)) = authorization.trim().split_once(' ') else {
+            return false;
+        };
+        let presented = presented.trim();
+        match self {
+            Self::Bearer { token } => {
+                scheme.eq_ignore_ascii_case("bearer")
+                    && constant_time_eq(presented.as_bytes(), token.as_bytes())
+            }
+            Self::Basic { username, password } => {
+                if !scheme.eq_ignore_ascii_case("basic") {
+                    return false;
+                }
+                let Ok(decoded) = STANDARD.decode(presented) else {
+                    return false;
+                };
+                let Some((presented_username, presented_password)) = std::str::from_utf8(&decoded)
+                    .ok()
+                    .and_then(|creds| creds.split_once(':'))
+                else {
+                    return false;
+                };
+                // Evaluate both comparisons so timing doesn't reveal which one failed.
+                let username_ok =
+                    constant_time_eq(presented_username.as_bytes(), username.as_bytes());
+                let password_ok =
+                    constant_time_eq(presented_password.as_bytes(), password.as_bytes());
+                username_ok & password_ok
+            }
+        }
+    }
+}
+
+async fn resolve_variable(
+    variables: &spin_factor_variables::AppState,
+    name: &str,
+) -> anyhow::Result<String> {
+    variables
+        .resolve_expression(format!("{{{{ {name} }}}}"))
+        .await
+        .with_context(|| format!("failed to resolve auth variable {name:?}"))
+}
+
+/// Ensures every variable referenced by an auth config is declared by the app.
+pub(crate) fn validate_auth_config(
+    app: &App,
+    component_id: &str,
+    config: &HttpRouteAuthConfig,
+) -> anyhow::Result<()> {
+    let referenced = match config {
+        HttpRouteAuthConfig::Bearer { token_variable } => vec![token_variable],
+        HttpRouteAuthConfig::Basic {
+            username_variable,
+            password_variable,
+        } => vec![username_variable, password_variable],
+    };
+    for name in referenced {
+        anyhow::ensure!(
+            app.variables().any(|(key, _)| key == name),
+            "HTTP trigger for component '{component_id}' uses auth variable '{name}', which is not defined in the application's variables"
+        );
+    }
+    Ok(())
+}
+
+/// Creates an HTTP 401 response with a challenge for the given auth config.
+pub(crate) fn unauthorized(
+    config: &HttpRouteAuthConfig,
+    route: impl Into<String>,
+) -> anyhow::Result<Response<Body>> {
+    let challenge = match config {
+        HttpRouteAuthConfig::Bearer { .. } => format!("Bearer realm=\"{REALM}\""),
+        HttpRouteAuthConfig::Basic { .. } => {
+            format!("Basic realm=\"{REALM}\", charset=\"UTF-8\"")
+        }
+    };
+    Ok(MatchedRoute::with_response_extension(
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(header::WWW_AUTHENTICATE, challenge)
+            .body(body::empty())?,
+        route,
+    ))
+}
+
+/// Compares two byte strings without short-circuiting on the first mismatch.
+///
+/// The length of the expected value is not considered secret.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn headers_with_authorization(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn bearer() -> Credentials {
+        Credentials::Bearer {
+            token: "s3cret".into(),
+        }
+    }
+
+    fn basic() -> Credentials {
+        Credentials::Basic {
+            username: "admin".into(),
+            password: "pa:ss".into(),
+        }
+    }
+
+    #[test]
+    fn bearer_accepts_matching_token() {
+        assert!(bearer().authorize(&headers_with_authorization("Bearer s3cret")));
+        assert!(bearer().authorize(&headers_with_authorization("bearer s3cret")));
+    }
+
+    #[test]
+    fn bearer_rejects_wrong_token() {
+        assert!(!bearer().authorize(&headers_with_authorization("Bearer s3cre")));
+        assert!(!bearer().authorize(&headers_with_authorization("Bearer s3cret2")));
+        assert!(!bearer().authorize(&headers_with_authorization("Basic s3cret")));
+    }
+
+    #[test]
+    fn missing_header_is_rejected() {
+        assert!(!bearer().authorize(&HeaderMap::new()));
+        assert!(!basic().authorize(&HeaderMap::new()));
+        assert!(!bearer().authorize(&headers_with_authorization("Bearer")));
+    }
+
+    #[test]
+    fn basic_accepts_matching_credentials() {
+        let encoded = STANDARD.encode("admin:pa:ss");
+        assert!(basic().authorize(&headers_with_authorization(&format!("Basic {encoded}"))));
+    }
+
+    #[test]
+    fn basic_rejects_wrong_credentials() {
+        for creds in ["admin:wrong", "other:pa:ss", "admin", ""] {
+            let encoded = STANDARD.encode(creds);
+            assert!(
+                !basic().authorize(&headers_with_authorization(&format!("Basic {encoded}"))),
+                "{creds:?} should be rejected"
+            );
+        }
+        assert!(!basic().authorize(&headers_with_authorization("Basic not-base64!")));
+    }
+
+    #[test]
+    fn unauthorized_sets_challenge() {
+        let config = HttpRouteAuthConfig::Bearer {
+            token_variable: "token".into(),
+        };
+        let resp = unauthorized(&config, "/admin/...").unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            resp.headers()[header::WWW_AUTHENTICATE],
+            "Bearer realm=\"spin\""
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Reading request bodies in full for routes with `body_handling = "buffer"`.
+
+use std::{
+    path::Path,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use anyhow::Context as _;
+use http::{header, HeaderMap, Request, StatusCode};
+use http_body_util::BodyExt;
+use hyper::body::{Body as _, Bytes, Frame, SizeHint};
+use tokio::io::{AsyncRead, AsyncSeekExt, AsyncWriteExt, ReadBuf};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// The limit on the size of a buffered request body, for routes without a
+/// `max_total_bytes` and components without a `max_request_body_bytes` limit.
+pub(crate) const DEFAULT_MAX_TOTAL_BYTES: u64 = 256 * 1024 * 1024;
+
+/// The size of the chunks in which a spilled body is read back.
+const SPILL_READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Why a request body could not be buffered.
+#[derive(Debug)]
+pub(crate) enum BufferError {
+    /// The body could not be read from the client.
+    Read(anyhow::Error),
+    /// The body is larger than the route's `max_total_bytes`, or
+    /// [`DEFAULT_MAX_TOTAL_BYTES`].
+    TooLarge(u64),
+    /// The part of the body over `max_memory_bytes` could not be written to a
+    /// temporary file.
+    Spill(anyhow::Error),
+}
+
+impl BufferError {
+    /// The status of the response to the request.
+    pub(crate) fn status(&self) -> StatusCode {
+        match self {
+            Self::Read(_) => StatusCode::BAD_REQUEST,
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Spill(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Display for BufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(err) => write!(f, "failed to read request body: {err:#}"),
+            Self::TooLarge(max) => write!(f, "request body exceeds the limit of {max} bytes"),
+            Self::Spill(err) => write!(f, "failed to buffer request body: {err:#}"),
+        }
+    }
+}
+
+/// Reads the whole body of a request before it is passed to the component,
+/// setting its `Content-Length`.
+///
+/// Up to `max_memory_bytes` of the body are kept in memory. The rest is
+/// written to a temporary file in `spill_dir`, which the component then reads
+/// the body from. The file is deleted when the body is dropped, or when
+/// buffering fails.
+///
+/// Requests whose bodies are larger than `max_total_bytes`, or
+/// [`DEFAULT_MAX_TOTAL_BYTES`] if there is no limit, are rejected with
+/// [`BufferError::TooLarge`], so that a client can't fill the disk. The body
+/// isn't read if the request's `Content-Length` says it is too large.
+pub(crate) async fn buffer_request_body(
+    req: Request<Body>,
+    max_memory_bytes: u64,
+    max_total_bytes: Option<u64>,
+    spill_dir: &Path,
+) -> Result<Request<Body>, BufferError> {
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+    let max_total_bytes = max_total_bytes.unwrap_or(DEFAULT_MAX_TOTAL_BYTES);
+    if content_length.is_some_and(|length| length > max_total_bytes) {
+        return Err(BufferError::TooLarge(max_total_bytes));
+    }
+
+    let (mut parts, mut body) = req.into_parts();
+    let mut memory = Vec::new();
+    let mut spill: Option<(tokio::fs::File, tempfile::TempPath)> = None;
+    let mut total = 0u64;
+    let mut trailers = None;
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|err| BufferError::Read(err.into()))?;
+        let mut data = match frame.into_data() {
+            Ok(data) => data,
+            Err(frame) => {
+                trailers = frame.into_trailers().ok();
+                continue;
+            }
+        };
+        total += data.len() as u64;
+        if total > max_total_bytes {
+            return Err(BufferError::TooLarge(max_total_bytes));
+        }
+
+        let memory_space = (max_memory_bytes as usize).saturating_sub(memory.len());
+        if spill.is_none() {
+            let in_memory = data.split_to(data.len().min(memory_space));
+            memory.extend_from_slice(&in_memory);
+            if data.is_empty() {
+                continue;
+            }
+            spill = Some(create_spill_file(spill_dir).map_err(BufferError::Spill)?);
+        }
+        let (file, _) = spill.as_mut().unwrap();
+        file.write_all(&data)
+            .await
+            .map_err(|err| BufferError::Spill(err.into()))?;
+    }
+
+    let spill = match spill {
+        Some((mut file, path)) => {
+            let rewind = async {
+                file.flush().await?;
+                file.rewind().await
+            };
+            rewind.await.map_err(|err| BufferError::Spill(err.into()))?;
+            Some(SpillFile {
+                file,
+                buf: vec![0; SPILL_READ_CHUNK_SIZE],
+                _path: path,
+            })
+        }
+        None => None,
+    };
+    parts.headers.insert(header::CONTENT_LENGTH, total.into());
+    let body = BufferedBody {
+        memory: Some(memory.into()),
+        spill,
+        trailers,
+        remaining: total,
+    };
+    Ok(Request::from_parts(parts, body.boxed()))
+}
+
+/// Creates a temporary file in `dir`, which is deleted when its path is
+/// dropped.
+fn create_spill_file(dir: &Path) -> anyhow::Result<(tokio::fs::File, tempfile::TempPath)> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create directory {}", dir.display()))?;
+    let (file, path) = tempfile::Builder::new()
+        .prefix("request-body-")
+        .tempfile_in(dir)
+        .with_context(|| format!("failed to create temporary file in {}", dir.display()))?
+        .into_parts();
+    Ok((tokio::fs::File::from_std(file), path))
+}
+
+/// The part of a buffered body which didn't fit in memory.
+struct SpillFile {
+    file: tokio::fs::File,
+    buf: Vec<u8>,
+    // Deletes the file when the body is dropped
+    _path: tempfile::TempPath,
+}
+
+/// A request body which has been read in full, from memory and then from any
+/// spill file.
+struct BufferedBody {
+    memory: Option<Bytes>,
+    spill: Option<SpillFile>,
+    trailers: Option<HeaderMap>,
+    /// The number of bytes of data not yet returned
+    remaining: u64,
+}
+
+impl hyper::body::Body for BufferedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(memory) = this.memory.take().filter(|m| !m.is_empty()) {
+            this.remaining -= memory.len() as u64;
+            return Poll::Ready(Some(Ok(Frame::data(memory))));
+        }
+        if let Some(spill) = &mut this.spill {
+            let mut buf = ReadBuf::new(&mut spill.buf);
+            ready!(Pin::new(&mut spill.file).poll_read(cx, &mut buf)).map_err(|err| {
+                ErrorCode::InternalError(Some(format!("failed to read buffered body: {err}")))
+            })?;
+            let len = buf.filled().len();
+            if len > 0 {
+                this.remaining = this.remaining.saturating_sub(len as u64);
+                return Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(
+                    &spill.buf[..len],
+                )))));
+            }
+            // Delete the file as soon as it has been read
+            this.spill = None;
+        }
+        Poll::Ready(this.trailers.take().map(|t| Ok(Frame::trailers(t))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0 && self.trailers.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use http_body_util::StreamBody;
+    use spin_http::body;
+
+    use super::*;
+
+    fn request(body: Body) -> Request<Body> {
+        Request::post("/upload").body(body).unwrap()
+    }
+
+    fn spill_files(dir: &Path) -> usize {
+        std::fs::read_dir(dir).map_or(0, |entries| entries.count())
+    }
+
+    #[tokio::test]
+    async fn small_bodies_stay_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let payload = Bytes::from_static(b"hello");
+        let req = buffer_request_body(request(body::full(payload.clone())), 16, None, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(spill_files(dir.path()), 0);
+        assert_eq!(req.headers()[header::CONTENT_LENGTH], "5");
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, payload);
+    }
+
+    #[tokio::test]
+    async fn large_bodies_spill_to_a_file_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let payload = Bytes::from((0..200_000).map(|i| i as u8).collect::<Vec<_>>());
+        // Arrives in several chunks, as from a client
+        let chunks = payload
+            .chunks(30_000)
+            .map(|chunk| Ok::<_, ErrorCode>(Frame::data(Bytes::copy_from_slice(chunk))))
+            .collect::<Vec<_>>();
+        let body = StreamBody::new(stream::iter(chunks)).boxed();
+
+        let req = buffer_request_body(request(body), 1000, None, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(spill_files(dir.path()), 1);
+        assert_eq!(req.headers()[header::CONTENT_LENGTH], "200000");
+        assert_eq!(req.body().size_hint().exact(), Some(200_000));
+
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, payload);
+        assert_eq!(spill_files(dir.path()), 0);
+
+        // Even if the body isn't read
+        let req = buffer_request_body(request(body::full(payload)), 1000, None, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(spill_files(dir.path()), 1);
+        drop(req);
+        assert_eq!(spill_files(dir.path()), 0);
+    }
+
+    #[tokio::test]
+    async fn spill_file_is_deleted_when_buffering_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let body = StreamBody::new(stream::iter([
+            Ok(Frame::data(Bytes::from(vec![0; 2000]))),
+            Err(ErrorCode::HttpRequestDenied),
+        ]))
+        .boxed();
+        let err = buffer_request_body(request(body), 1000, None, dir.path())
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(spill_files(dir.path()), 0);
+
+        let body = StreamBody::new(stream::iter([
+            Ok::<_, ErrorCode>(Frame::data(Bytes::from(vec![0; 2000]))),
+            Ok(Frame::data(Bytes::from(vec![0; 2000]))),
+        ]))
+        .boxed();
+        let err = buffer_request_body(request(body), 1000, Some(3000), dir.path())
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(spill_files(dir.path()), 0);
+    }
+
+    #[tokio::test]
+    async fn oversized_content_length_is_rejected_unread() {
+        let dir = tempfile::tempdir().unwrap();
+        let req = Request::post("/upload")
+            .header(header::CONTENT_LENGTH, 5000)
+            .body(body::empty())
+            .unwrap();
+        let err = buffer_request_body(req, 1000, Some(3000), dir.path())
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn bodies_are_capped_without_a_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let req = Request::post("/upload")
+            .header(header::CONTENT_LENGTH, DEFAULT_MAX_TOTAL_BYTES + 1)
+            .body(body::empty())
+            .unwrap();
+        let err = buffer_request_body(req, 1000, None, dir.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BufferError::TooLarge(DEFAULT_MAX_TOTAL_BYTES)
+        ));
+    }
+}
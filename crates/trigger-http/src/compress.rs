@@ -0,0 +1,271 @@
+//! Compressing response bodies for routes with `compress_response`.
+
+use std::{
+    io::Write,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use http::{header, HeaderMap, HeaderValue, Response, StatusCode};
+use http_body_util::BodyExt;
+use hyper::body::{Body as _, Bytes, Frame, SizeHint};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// Returns whether the client accepts `gzip` responses, according to the
+/// request's `Accept-Encoding` header.
+pub(crate) fn accepts_gzip(headers: &HeaderMap) -> bool {
+    let mut gzip = None;
+    let mut wildcard = None;
+    for value in headers.get_all(header::ACCEPT_ENCODING) {
+        let Ok(value) = value.to_str() else { continue };
+        for item in value.split(',') {
+            let mut params = item.split(';');
+            let coding = params.next().unwrap_or_default().trim();
+            // A coding is acceptable unless its quality is zero
+            let accepted = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .all(|q| q.trim().parse::<f32>().map_or(true, |q| q > 0.0));
+            if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+                gzip = Some(accepted);
+            } else if coding == "*" {
+                wildcard = Some(accepted);
+            }
+        }
+    }
+    gzip.or(wildcard).unwrap_or(false)
+}
+
+/// Returns whether a response of the given `Content-Type` is worth
+/// compressing. Images and video are usually compressed already, and event
+/// streams must reach the client as soon as each event is written.
+fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if essence == "image/svg+xml" {
+        return true;
+    }
+    !(essence.starts_with("image/")
+        || essence.starts_with("video/")
+        || essence == "text/event-stream")
+}
+
+/// Compresses the body of the response with `gzip` if `accepts_gzip` is set
+/// and the response is eligible: its type is compressible, it isn't already
+/// encoded, and it is not known to be smaller than `min_size` bytes.
+///
+/// Compressed responses get `Content-Encoding: gzip` and lose their
+/// `Content-Length`. Responses of compressible types are marked as varying by
+/// `Accept-Encoding` whether or not they are compressed, so that caches don't
+/// serve a compressed response to a client which can't decode it.
+pub(crate) fn compress_response(
+    mut response: Response<Body>,
+    accepts_gzip: bool,
+    min_size: usize,
+) -> Response<Body> {
+    let headers = response.headers();
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let has_body = !matches!(
+        response.status(),
+        StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED
+    ) && !response.status().is_informational();
+    if !has_body || !is_compressible(content_type) || headers.contains_key(header::CONTENT_ENCODING)
+    {
+        return response;
+    }
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+    // A response without a `Content-Length` is streamed, and so not known to
+    // be small
+    let length = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .or_else(|| response.body().size_hint().exact());
+    if !accepts_gzip || length.is_some_and(|length| length < min_size as u64) {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_LENGTH);
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    response.map(|body| GzipBody::new(body).boxed())
+}
+
+/// A response body which compresses the frames of the inner body with `gzip`
+/// as they are written, passing on any trailers.
+struct GzipBody {
+    inner: Body,
+    /// The encoder, until the inner body ends.
+    encoder: Option<GzEncoder<Vec<u8>>>,
+    /// Trailers received from the inner body, sent after the compressed data.
+    trailers: Option<HeaderMap>,
+}
+
+impl GzipBody {
+    fn new(inner: Body) -> Self {
+        Self {
+            inner,
+            encoder: Some(GzEncoder::new(vec![], Compression::default())),
+            trailers: None,
+        }
+    }
+}
+
+fn internal_error(err: std::io::Error) -> ErrorCode {
+    ErrorCode::InternalError(Some(format!("failed to compress response body: {err}")))
+}
+
+impl hyper::body::Body for GzipBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            let Some(encoder) = &mut this.encoder else {
+                return Poll::Ready(this.trailers.take().map(|t| Ok(Frame::trailers(t))));
+            };
+            let frame = match Pin::new(&mut this.inner).poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => frame,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => {
+                    let encoder = this.encoder.take().unwrap();
+                    let compressed = encoder.finish().map_err(internal_error)?;
+                    return Poll::Ready(Some(Ok(Frame::data(compressed.into()))));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+            match frame.into_data() {
+                Ok(data) => {
+                    // Flush each write so that streamed responses aren't held
+                    // back waiting for more data
+                    encoder
+                        .write_all(&data)
+                        .and_then(|()| encoder.flush())
+                        .map_err(internal_error)?;
+                    let compressed = std::mem::take(encoder.get_mut());
+                    if !compressed.is_empty() {
+                        return Poll::Ready(Some(Ok(Frame::data(compressed.into()))));
+                    }
+                }
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        this.trailers = Some(trailers);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.encoder.is_none() && self.trailers.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use spin_http::body;
+
+    use super::*;
+
+    const PAYLOAD: &str = "the quick brown fox jumps over the lazy dog. ";
+
+    fn accept(value: &str) -> HeaderMap {
+        HeaderMap::from_iter([(header::ACCEPT_ENCODING, value.parse().unwrap())])
+    }
+
+    fn response(content_type: &str, body: String) -> Response<Body> {
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(body::full(body.into()))
+            .unwrap()
+    }
+
+    #[test]
+    fn accept_encoding_is_parsed() {
+        assert!(accepts_gzip(&accept("gzip")));
+        assert!(accepts_gzip(&accept("br, GZIP;q=0.5")));
+        assert!(accepts_gzip(&accept("*")));
+        assert!(!accepts_gzip(&accept("br, zstd")));
+        assert!(!accepts_gzip(&accept("gzip;q=0")));
+        assert!(!accepts_gzip(&accept("gzip;q=0.0, *")));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn eligible_responses_are_compressed() {
+        let payload = PAYLOAD.repeat(100);
+        let res = compress_response(response("text/html", payload.clone()), true, 1024);
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(res.headers()[header::VARY], "accept-encoding");
+        assert!(!res.headers().contains_key(header::CONTENT_LENGTH));
+
+        let compressed = res.into_body().collect().await.unwrap().to_bytes();
+        assert!(compressed.len() < payload.len());
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed.as_ref())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, payload);
+    }
+
+    #[tokio::test]
+    async fn ineligible_responses_are_unchanged() {
+        let payload = PAYLOAD.repeat(100);
+        let cases = [
+            ("image/png", true, 0),
+            ("video/mp4", true, 0),
+            ("text/event-stream", true, 0),
+            ("text/html", false, 0),
+            ("text/html", true, payload.len() + 1),
+        ];
+        for (content_type, accepts_gzip, min_size) in cases {
+            let res = compress_response(
+                response(content_type, payload.clone()),
+                accepts_gzip,
+                min_size,
+            );
+            let case = (content_type, accepts_gzip, min_size);
+            assert!(
+                !res.headers().contains_key(header::CONTENT_ENCODING),
+                "{case:?}"
+            );
+            assert_eq!(
+                res.headers()[header::CONTENT_LENGTH],
+                payload.len().to_string(),
+                "{case:?}"
+            );
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, payload, "{case:?}");
+        }
+
+        let mut res = response("text/html", payload.clone());
+        res.headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static("br"));
+        let res = compress_response(res, true, 0);
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "br");
+    }
+}
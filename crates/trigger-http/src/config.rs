@@ -0,0 +1,332 @@
+//! Deserialization of the HTTP trigger's manifest config, with errors which
+//! say which trigger and key are at fault.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use serde::de::DeserializeOwned;
+use spin_app::App;
+use spin_http::config::HttpTriggerConfig;
+
+/// Deserializes the config of each of the app's `[[trigger.http]]` entries,
+/// in manifest order.
+///
+/// A component may have several triggers, but they must share every option
+/// except `route` and `host`, which the router applies to each trigger
+/// separately: the server looks up the rest by component, including for
+/// requests which don't come through a route at all, such as chained
+/// requests.
+pub(crate) fn trigger_configs(app: &App) -> anyhow::Result<Vec<HttpTriggerConfig>> {
+    let configs = app
+        .triggers_with_type("http")
+        .enumerate()
+        .map(|(index, trigger)| {
+            let value = trigger.typed_config::<serde_json::Value>()?;
+            deserialize(&value).map_err(|err| {
+                let component = value.get("component").and_then(|c| c.as_str());
+                let mut described = vec![];
+                if !is_generated_id(trigger.id(), component) {
+                    described.push(format!("id '{}'", trigger.id()));
+                }
+                if let Some(component) = component {
+                    described.push(format!("component '{component}'"));
+                }
+                let described = if described.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", described.join(", "))
+                };
+                anyhow::anyhow!("trigger.http[{index}]{described}: {err}")
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    ensure_shared_options(&configs)?;
+    Ok(configs)
+}
+
+/// The options which may differ between the triggers of one component.
+const PER_TRIGGER_OPTIONS: &[&str] = &["component", "route", "host"];
+
+/// Ensures that each component's triggers agree on all the options not in
+/// [`PER_TRIGGER_OPTIONS`].
+fn ensure_shared_options(configs: &[HttpTriggerConfig]) -> anyhow::Result<()> {
+    let mut firsts = HashMap::new();
+    for (index, config) in configs.iter().enumerate() {
+        let serde_json::Value::Object(mut options) = serde_json::to_value(config)? else {
+            unreachable!("trigger configs serialize to objects");
+        };
+        for option in PER_TRIGGER_OPTIONS {
+            options.remove(*option);
+        }
+        let (first_index, first_options) = match firsts.entry(config.component.as_str()) {
+            Entry::Vacant(entry) => {
+                entry.insert((index, options));
+                continue;
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        };
+        let mut differing = first_options
+            .keys()
+            .chain(options.keys())
+            .filter(|option| first_options.get(*option) != options.get(*option))
+            .map(|option| format!("`{option}`"))
+            .collect::<Vec<_>>();
+        differing.sort();
+        differing.dedup();
+        let verb = if differing.len() == 1 {
+            "differs"
+        } else {
+            "differ"
+        };
+        anyhow::ensure!(
+            differing.is_empty(),
+            "trigger.http[{index}] (component '{}'): {} {verb} from trigger.http[{first_index}] for the same component. \
+             A component's HTTP triggers may differ only in `route` and `host`; use a separate component for routes which need different options.",
+            config.component,
+            differing.join(", "),
+        );
+    }
+    Ok(())
+}
+
+/// Deserializes the app's `[application.trigger.http]` table, if it has one.
+pub(crate) fn trigger_metadata<T: DeserializeOwned>(app: &App) -> anyhow::Result<Option<T>> {
+    let Some(value) = app.get_trigger_metadata::<serde_json::Value>("http")? else {
+        return Ok(None);
+    };
+    deserialize(&value)
+        .map(Some)
+        .map_err(|err| anyhow::anyhow!("application.trigger.http: {err}"))
+}
+
+/// Deserializes a table, describing any error with the path to the key at
+/// fault, e.g. "invalid value for `executor.type`: unknown variant ...".
+fn deserialize<T: DeserializeOwned>(value: &serde_json::Value) -> Result<T, String> {
+    serde_path_to_error::deserialize(value).map_err(|err| {
+        let path = err.path().to_string();
+        let inner = err.into_inner();
+        if path == "." {
+            // e.g. a missing or unknown field, which the message names
+            inner.to_string()
+        } else {
+            format!("invalid value for `{path}`: {inner}")
+        }
+    })
+}
+
+/// Whether a trigger ID was assigned by Spin rather than set in the manifest;
+/// see `spin_manifest::normalize`.
+fn is_generated_id(id: &str, component: Option<&str>) -> bool {
+    component.is_some_and(|component| id == format!("{component}-http-trigger"))
+        || id
+            .strip_prefix("http-trigger")
+            .is_some_and(|n| n.parse::<usize>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn app(manifest: toml::Table) -> App {
+        let locked = spin_factors_test::build_locked_app(&manifest)
+            .await
+            .unwrap();
+        App::new("test-app", locked)
+    }
+
+    async fn trigger_configs_error(triggers: toml::Value) -> String {
+        let mut manifest = toml::toml! {
+            spin_manifest_version = 2
+
+            [application]
+            name = "test-app"
+
+            [component.api]
+            source = "does-not-exist.wasm"
+
+            [component.admin]
+            source = "does-not-exist.wasm"
+        };
+        manifest.insert(
+            "trigger".into(),
+            toml::Value::Table([("http".to_owned(), triggers)].into_iter().collect()),
+        );
+        let err = trigger_configs(&app(manifest).await).unwrap_err();
+        err.to_string()
+    }
+
+    #[tokio::test]
+    async fn invalid_trigger_configs_are_located() {
+        let triggers = toml::Value::Array(vec![
+            toml::toml! {
+                component = "admin"
+                route = "/admin"
+            }
+            .into(),
+            toml::toml! {
+                component = "api"
+                route = 42
+            }
+            .into(),
+        ]);
+        assert_eq!(
+            trigger_configs_error(triggers).await,
+            "trigger.http[1] (component 'api'): invalid value for `route`: expected string or { private = true }"
+        );
+
+        let triggers = toml::Value::Array(vec![toml::toml! {
+            id = "api-trigger"
+            component = "api"
+            route = "/..."
+            executor = { type = "spiny" }
+        }
+        .into()]);
+        let err = trigger_configs_error(triggers).await;
+        assert!(
+            err.starts_with(
+                "trigger.http[0] (id 'api-trigger', component 'api'): invalid value for `executor"
+            ),
+            "{err}"
+        );
+        assert!(err.contains("spiny"), "{err}");
+
+        let triggers = toml::Value::Array(vec![toml::toml! {
+            component = "api"
+            route = "/..."
+            write_idle_timeout_secs = "soon"
+        }
+        .into()]);
+        let err = trigger_configs_error(triggers).await;
+        assert!(
+            err.starts_with(
+                "trigger.http[0] (component 'api'): invalid value for `write_idle_timeout_secs`"
+            ),
+            "{err}"
+        );
+
+        let triggers = toml::Value::Array(vec![toml::toml! {
+            component = "api"
+            route = "/..."
+            rout = "/other"
+        }
+        .into()]);
+        let err = trigger_configs_error(triggers).await;
+        assert!(
+            err.starts_with("trigger.http[0] (component 'api'): "),
+            "{err}"
+        );
+        assert!(err.contains("unknown field `rout`"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn triggers_for_one_component_must_share_options() {
+        let triggers = toml::Value::Array(vec![
+            toml::toml! {
+                component = "admin"
+                route = "/public"
+            }
+            .into(),
+            toml::toml! {
+                component = "api"
+                route = "/api/..."
+            }
+            .into(),
+            toml::toml! {
+                component = "admin"
+                route = "/admin"
+                methods = ["GET"]
+                auth = { type = "bearer", token_variable = "admin_token" }
+            }
+            .into(),
+        ]);
+        assert_eq!(
+            trigger_configs_error(triggers).await,
+            "trigger.http[2] (component 'admin'): `auth`, `methods` differ from trigger.http[0] for the same component. \
+             A component's HTTP triggers may differ only in `route` and `host`; use a separate component for routes which need different options."
+        );
+    }
+
+    #[tokio::test]
+    async fn triggers_for_one_component_may_differ_in_route_and_host() {
+        let manifest = toml::toml! {
+            spin_manifest_version = 2
+
+            [application]
+            name = "test-app"
+
+            [[trigger.http]]
+            component = "api"
+            route = "/api/..."
+            inject_request_id = true
+
+            [[trigger.http]]
+            component = "api"
+            route = "/..."
+            host = "api.example.com"
+            inject_request_id = true
+
+            [component.api]
+            source = "does-not-exist.wasm"
+        };
+        let configs = trigger_configs(&app(manifest).await).unwrap();
+        assert_eq!(configs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn valid_trigger_configs_are_unaffected() {
+        let manifest = toml::toml! {
+            spin_manifest_version = 2
+
+            [application]
+            name = "test-app"
+
+            [application.trigger.http]
+            listen = ["127.0.0.1:3001"]
+
+            [[trigger.http]]
+            component = "api"
+            route = "/api/..."
+            executor = { type = "wagi" }
+
+            [[trigger.http]]
+            component = "internal"
+            route = { private = true }
+
+            [component.api]
+            source = "does-not-exist.wasm"
+
+            [component.internal]
+            source = "does-not-exist.wasm"
+        };
+        let app = app(manifest).await;
+        let configs = trigger_configs(&app).unwrap();
+        assert_eq!(configs.len(), 2);
+        assert_eq!(configs[0].component, "api");
+        assert_eq!(configs[1].component, "internal");
+
+        let metadata = trigger_metadata::<crate::TriggerMetadata>(&app)
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.listen, ["127.0.0.1:3001"]);
+    }
+
+    #[tokio::test]
+    async fn invalid_trigger_metadata_is_located() {
+        let manifest = toml::toml! {
+            spin_manifest_version = 2
+
+            [application]
+            name = "test-app"
+
+            [application.trigger.http]
+            listen = "127.0.0.1:3001"
+        };
+        let err = trigger_metadata::<crate::TriggerMetadata>(&app(manifest).await)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .starts_with("application.trigger.http: invalid value for `listen`"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Decompressing request bodies for routes with `decompress_request_body`.
+
+use std::io::Read;
+
+use http::{header, HeaderValue, Request, StatusCode};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::body::Bytes;
+use spin_http::body;
+
+use crate::Body;
+
+/// The limit on the size of a decompressed request body, for components
+/// without a `max_request_body_bytes` limit.
+pub(crate) const DEFAULT_MAX_DECOMPRESSED_BYTES: u64 = 64 * 1024 * 1024;
+
+/// A `Content-Encoding` which the server can decompress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl Encoding {
+    /// Parses a single `Content-Encoding`. Lists of encodings, `identity` and
+    /// unknown encodings are not decompressed.
+    fn parse(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(Self::Gzip)
+        } else if value.eq_ignore_ascii_case("br") {
+            Some(Self::Brotli)
+        } else if value.eq_ignore_ascii_case("zstd") {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    fn decoder<'a>(self, compressed: &'a [u8]) -> std::io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Self::Gzip => Box::new(flate2::read::MultiGzDecoder::new(compressed)),
+            Self::Brotli => Box::new(brotli::Decompressor::new(compressed, 4096)),
+            Self::Zstd => Box::new(zstd::stream::read::Decoder::new(compressed)?),
+        })
+    }
+}
+
+/// Why a request body could not be decompressed.
+#[derive(Debug)]
+pub(crate) enum DecompressError {
+    /// The body could not be read or was not validly encoded.
+    Malformed(anyhow::Error),
+    /// The compressed or decompressed body is larger than the component's
+    /// `max_request_body_bytes` limit, or [`DEFAULT_MAX_DECOMPRESSED_BYTES`].
+    TooLarge(u64),
+}
+
+impl DecompressError {
+    /// The status of the response to the request.
+    pub(crate) fn status(&self) -> StatusCode {
+        match self {
+            Self::Malformed(_) => StatusCode::BAD_REQUEST,
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+}
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(err) => write!(f, "malformed compressed request body: {err:#}"),
+            Self::TooLarge(max) => {
+                write!(
+                    f,
+                    "request body exceeds the decompression limit of {max} bytes"
+                )
+            }
+        }
+    }
+}
+
+/// Decompresses the body of a request whose `Content-Encoding` is `gzip`,
+/// `br` or `zstd`, removing the `Content-Encoding` header and setting the
+/// `Content-Length` to that of the decompressed body. Other requests are
+/// returned unchanged.
+///
+/// Decompression stops with [`DecompressError::TooLarge`] once the body
+/// exceeds `max_bytes`, or [`DEFAULT_MAX_DECOMPRESSED_BYTES`] if there is no
+/// limit, so that a small request can't expand without bound. The compressed
+/// body is read in full first, so it is held to the same limit.
+pub(crate) async fn decompress_request_body(
+    req: Request<Body>,
+    max_bytes: Option<u64>,
+) -> Result<Request<Body>, DecompressError> {
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_MAX_DECOMPRESSED_BYTES);
+    let Some(encoding) = req
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(Encoding::parse)
+    else {
+        return Ok(req);
+    };
+
+    let (mut parts, body) = req.into_parts();
+    let compressed = Limited::new(body, usize::try_from(max_bytes).unwrap_or(usize::MAX))
+        .collect()
+        .await
+        .map_err(|err| {
+            if err.is::<LengthLimitError>() {
+                DecompressError::TooLarge(max_bytes)
+            } else {
+                DecompressError::Malformed(anyhow::anyhow!(err))
+            }
+        })?
+        .to_bytes();
+    // Decompression is CPU-bound, so keep it off the async runtime's threads
+    let decompressed =
+        tokio::task::spawn_blocking(move || decompress(encoding, &compressed, max_bytes))
+            .await
+            .map_err(|err| DecompressError::Malformed(err.into()))??;
+
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, decompressed.len().into());
+    Ok(Request::from_parts(parts, body::full(decompressed)))
+}
+
+fn decompress(
+    encoding: Encoding,
+    compressed: &[u8],
+    max_bytes: u64,
+) -> Result<Bytes, DecompressError> {
+    let decoder = encoding
+        .decoder(compressed)
+        .map_err(|err| DecompressError::Malformed(err.into()))?;
+    // Read one byte past the limit to tell a body at the limit from one over it
+    let mut decompressed = vec![];
+    decoder
+        .take(max_bytes.saturating_add(1))
+        .read_to_end(&mut decompressed)
+        .map_err(|err| DecompressError::Malformed(err.into()))?;
+    if decompressed.len() as u64 > max_bytes {
+        return Err(DecompressError::TooLarge(max_bytes));
+    }
+    Ok(decompressed.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+    fn request(encoding: &str, body: Vec<u8>) -> Request<Body> {
+        Request::post("/upload")
+            .header(header::CONTENT_ENCODING, encoding)
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(body::full(body.into()))
+            .unwrap()
+    }
+
+    async fn assert_decompressed(req: Request<Body>) {
+        let req = decompress_request_body(req, None).await.unwrap();
+        assert!(req.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(
+            req.headers()[header::CONTENT_LENGTH],
+            PAYLOAD.len().to_string()
+        );
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, PAYLOAD);
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn gzip_bodies_are_decompressed() {
+        assert_decompressed(request("gzip", gzip(PAYLOAD))).await;
+        assert_decompressed(request("x-gzip", gzip(PAYLOAD))).await;
+    }
+
+    #[tokio::test]
+    async fn brotli_bodies_are_decompressed() {
+        let mut compressed = vec![];
+        {
+            let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+            encoder.write_all(PAYLOAD).unwrap();
+        }
+        assert_decompressed(request("br", compressed)).await;
+    }
+
+    #[tokio::test]
+    async fn zstd_bodies_are_decompressed() {
+        let compressed = zstd::encode_all(PAYLOAD, 0).unwrap();
+        assert_decompressed(request("zstd", compressed)).await;
+    }
+
+    #[tokio::test]
+    async fn unknown_encodings_pass_through_unchanged() {
+        for encoding in ["deflate", "identity", "gzip, br"] {
+            let req = decompress_request_body(request(encoding, PAYLOAD.to_vec()), None)
+                .await
+                .unwrap();
+            assert_eq!(req.headers()[header::CONTENT_ENCODING], encoding);
+            assert_eq!(
+                req.headers()[header::CONTENT_LENGTH],
+                PAYLOAD.len().to_string()
+            );
+            let body = req.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, PAYLOAD);
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_and_oversized_bodies_are_rejected() {
+        let err = decompress_request_body(request("gzip", PAYLOAD.to_vec()), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+
+        // Compressible enough that the compressed body is within the limit
+        let payload = PAYLOAD.repeat(16);
+        let max = payload.len() as u64;
+        decompress_request_body(request("gzip", gzip(&payload)), Some(max))
+            .await
+            .unwrap();
+        let err = decompress_request_body(request("gzip", gzip(&payload)), Some(max - 1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn oversized_compressed_bodies_are_rejected_unread() {
+        // Incompressible data grows when compressed, so the compressed body
+        // is over a limit which the decompressed body is within
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let payload = (0..4096)
+            .map(|_| {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+        let compressed = zstd::encode_all(payload.as_slice(), 0).unwrap();
+        assert!(compressed.len() > payload.len());
+
+        let max = payload.len() as u64;
+        let err = decompress_request_body(request("zstd", compressed), Some(max))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, DecompressError::TooLarge(m) if m == max),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn bodies_are_capped_without_a_component_limit() {
+        let payload = vec![0; DEFAULT_MAX_DECOMPRESSED_BYTES as usize + 1];
+        let compressed = zstd::encode_all(payload.as_slice(), 1).unwrap();
+        drop(payload);
+
+        let err = decompress_request_body(request("zstd", compressed), None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                DecompressError::TooLarge(DEFAULT_MAX_DECOMPRESSED_BYTES)
+            ),
+            "{err}"
+        );
+    }
+}
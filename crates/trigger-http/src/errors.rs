@@ -0,0 +1,377 @@
+//! Mapping guest failures to HTTP error responses.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use http::{HeaderValue, Request, Response, StatusCode};
+use hyper::body::Bytes;
+use serde::Deserialize;
+use spin_core::{ResourceLimitExceeded, Trap};
+use spin_http::body;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// The header a request ID is taken from, if the client sent one, and which
+/// holds the ID of requests to routes which inject request IDs.
+pub(crate) const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The placeholder in an error response body template that is replaced by the
+/// request ID.
+const REQUEST_ID_PLACEHOLDER: &str = "{request_id}";
+
+/// The kind of failure that prevented a component from handling a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCategory {
+    /// The instance exceeded a resource limit, e.g. its maximum memory size.
+    ResourceLimit,
+    /// The instance ran past its execution deadline.
+    Timeout,
+    /// The guest trapped, e.g. because it panicked.
+    Trap,
+    /// The host failed, e.g. in a host function or while instantiating.
+    HostError,
+    /// The handler returned an error rather than a response.
+    HandlerError,
+}
+
+impl ErrorCategory {
+    /// Classifies an error returned by an HTTP executor.
+    pub fn classify(err: &anyhow::Error) -> Self {
+        if err.downcast_ref::<ResourceLimitExceeded>().is_some() {
+            return Self::ResourceLimit;
+        }
+        if let Some(trap) = err.downcast_ref::<Trap>() {
+            return match trap {
+                Trap::Interrupt => Self::Timeout,
+                Trap::OutOfFuel => Self::ResourceLimit,
+                _ => Self::Trap,
+            };
+        }
+        if err.downcast_ref::<HandlerError>().is_some() || err.downcast_ref::<ErrorCode>().is_some()
+        {
+            return Self::HandlerError;
+        }
+        Self::HostError
+    }
+
+    fn default_response(self) -> ErrorResponseConfig {
+        match self {
+            Self::Timeout => ErrorResponseConfig {
+                status: StatusCode::GATEWAY_TIMEOUT.as_u16(),
+                body: None,
+            },
+            Self::ResourceLimit => ErrorResponseConfig {
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                body: Some("Component exceeded a resource limit".into()),
+            },
+            Self::Trap | Self::HostError | Self::HandlerError => ErrorResponseConfig {
+                status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                body: None,
+            },
+        }
+    }
+}
+
+/// An error indicating that a handler failed without a host or guest fault,
+/// e.g. by returning without producing a response.
+#[derive(Debug)]
+pub(crate) struct HandlerError(pub &'static str);
+
+impl std::fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for HandlerError {}
+
+/// Marks an error from executing an instance as caused by a resource limit if
+/// the instance was denied a resource, describing the denied request.
+pub(crate) fn resource_limit_context(
+    err: anyhow::Error,
+    state: &spin_core::State,
+) -> anyhow::Error {
+    if !state.memory_limit_exceeded() {
+        return err;
+    }
+    let err = err.context(ResourceLimitExceeded);
+    match state.memory_limit_message() {
+        Some(message) => err.context(message),
+        None => err,
+    }
+}
+
+/// The response sent for one [`ErrorCategory`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ErrorResponseConfig {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response body, if any. Any `{request_id}` is replaced by the ID of
+    /// the failed request.
+    pub body: Option<String>,
+}
+
+/// How the server responds when a component fails to handle a request,
+/// from the `[application.trigger.http]` manifest section.
+#[derive(Clone, Debug, Default)]
+pub struct ErrorResponses {
+    responses: HashMap<ErrorCategory, ErrorResponseConfig>,
+    debug_errors: bool,
+}
+
+impl ErrorResponses {
+    /// Creates error responses from the configured overrides. If
+    /// `debug_errors` is set, the error message is appended to each body.
+    pub fn new(
+        responses: HashMap<ErrorCategory, ErrorResponseConfig>,
+        debug_errors: bool,
+    ) -> anyhow::Result<Self> {
+        for (category, response) in &responses {
+            let status = StatusCode::from_u16(response.status)
+                .with_context(|| format!("invalid status for {category:?} error responses"))?;
+            anyhow::ensure!(
+                status.is_client_error() || status.is_server_error(),
+                "status for {category:?} error responses must be 4xx or 5xx, not {status}"
+            );
+        }
+        Ok(Self {
+            responses,
+            debug_errors,
+        })
+    }
+
+    /// Builds the response to a request which failed with the given error.
+    ///
+    /// The error message is included only if debug errors are enabled, as it
+    /// may reveal details of the app or host.
+    pub fn response(
+        &self,
+        err: &anyhow::Error,
+        request_id: &str,
+    ) -> anyhow::Result<Response<Body>> {
+        let category = ErrorCategory::classify(err);
+        let config = self
+            .responses
+            .get(&category)
+            .cloned()
+            .unwrap_or_else(|| category.default_response());
+
+        let mut text = config
+            .body
+            .map(|body| body.replace(REQUEST_ID_PLACEHOLDER, request_id))
+            .unwrap_or_default();
+        if self.debug_errors {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&format!("{err:?}"));
+        }
+        let body = if text.is_empty() {
+            body::empty()
+        } else {
+            body::full(Bytes::from(text))
+        };
+        Ok(Response::builder().status(config.status).body(body)?)
+    }
+}
+
+/// The header telling an error handler component the status of the error
+/// response it is handling.
+pub(crate) const ERROR_CODE_HEADER: &str = "x-spin-error-code";
+
+/// The components which handle requests the server would otherwise answer
+/// with a built-in error response, from the `[application.trigger.http]`
+/// `error_handlers` field.
+#[derive(Clone, Debug, Default)]
+pub struct ErrorHandlers {
+    /// The component for requests which match no route.
+    pub not_found: Option<String>,
+    /// The component for requests whose component failed with a 500 response.
+    pub internal_error: Option<String>,
+}
+
+impl ErrorHandlers {
+    /// Creates error handlers from a map of status code to component ID.
+    pub fn new(handlers: HashMap<String, String>) -> anyhow::Result<Self> {
+        let mut error_handlers = Self::default();
+        for (status, component_id) in handlers {
+            match status.as_str() {
+                "404" => error_handlers.not_found = Some(component_id),
+                "500" => error_handlers.internal_error = Some(component_id),
+                _ => anyhow::bail!(
+                    "cannot handle status {status:?}: error handlers may only be set for \"404\" and \"500\""
+                ),
+            }
+        }
+        Ok(error_handlers)
+    }
+
+    /// The handler components, along with the status each handles.
+    pub(crate) fn components(&self) -> impl Iterator<Item = (StatusCode, &str)> {
+        [
+            (StatusCode::NOT_FOUND, &self.not_found),
+            (StatusCode::INTERNAL_SERVER_ERROR, &self.internal_error),
+        ]
+        .into_iter()
+        .filter_map(|(status, component_id)| Some((status, component_id.as_deref()?)))
+    }
+}
+
+/// The ID of a request, for correlating error responses with logs: the
+/// client's `x-request-id` if it sent one, or else a new random ID.
+pub(crate) fn request_id<B>(req: &Request<B>) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| HeaderValue::to_str(id).ok())
+        .filter(|id| !id.is_empty())
+        .map(str::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use http_body_util::BodyExt;
+
+    async fn body_text(response: Response<Body>) -> String {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn error_handlers_accept_only_404_and_500() {
+        let handlers = ErrorHandlers::new(
+            [
+                ("404".to_owned(), "not-found".to_owned()),
+                ("500".to_owned(), "oops".to_owned()),
+            ]
+            .into(),
+        )
+        .unwrap();
+        assert_eq!(handlers.not_found.as_deref(), Some("not-found"));
+        assert_eq!(handlers.internal_error.as_deref(), Some("oops"));
+        assert_eq!(
+            handlers.components().collect::<Vec<_>>(),
+            [
+                (StatusCode::NOT_FOUND, "not-found"),
+                (StatusCode::INTERNAL_SERVER_ERROR, "oops")
+            ]
+        );
+
+        let err = ErrorHandlers::new([("503".to_owned(), "busy".to_owned())].into()).unwrap_err();
+        assert!(err.to_string().contains(r#""503""#), "{err}");
+    }
+
+    #[test]
+    fn classifies_errors() {
+        let timeout = anyhow::Error::from(Trap::Interrupt).context("guest invocation failed");
+        assert_eq!(ErrorCategory::classify(&timeout), ErrorCategory::Timeout);
+
+        let trap = anyhow::Error::from(Trap::UnreachableCodeReached);
+        assert_eq!(ErrorCategory::classify(&trap), ErrorCategory::Trap);
+
+        let limit =
+            anyhow::Error::from(Trap::UnreachableCodeReached).context(ResourceLimitExceeded);
+        assert_eq!(
+            ErrorCategory::classify(&limit),
+            ErrorCategory::ResourceLimit
+        );
+
+        let handler = anyhow::Error::from(ErrorCode::InternalError(None))
+            .context("guest failed to produce a response");
+        assert_eq!(
+            ErrorCategory::classify(&handler),
+            ErrorCategory::HandlerError
+        );
+
+        let host = anyhow::anyhow!("connection refused");
+        assert_eq!(ErrorCategory::classify(&host), ErrorCategory::HostError);
+    }
+
+    #[tokio::test]
+    async fn default_responses() {
+        let responses = ErrorResponses::default();
+
+        let timeout = anyhow::Error::from(Trap::Interrupt);
+        let response = responses.response(&timeout, "id").unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+        let trap = anyhow::Error::from(Trap::UnreachableCodeReached).context("secret detail");
+        let response = responses.response(&trap, "id").unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body_text(response).await, "");
+
+        let limit =
+            anyhow::Error::from(Trap::UnreachableCodeReached).context(ResourceLimitExceeded);
+        let response = responses.response(&limit, "id").unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body_text(response).await,
+            "Component exceeded a resource limit"
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_responses() {
+        let responses = ErrorResponses::new(
+            [(
+                ErrorCategory::Trap,
+                ErrorResponseConfig {
+                    status: 503,
+                    body: Some("Sorry! (request {request_id})".into()),
+                },
+            )]
+            .into(),
+            false,
+        )
+        .unwrap();
+
+        let trap = anyhow::Error::from(Trap::UnreachableCodeReached).context("secret detail");
+        let response = responses.response(&trap, "abc123").unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_text(response).await, "Sorry! (request abc123)");
+
+        // Unconfigured categories keep their defaults
+        let timeout = anyhow::Error::from(Trap::Interrupt);
+        let response = responses.response(&timeout, "abc123").unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn debug_errors_include_message() {
+        let responses = ErrorResponses::new(Default::default(), true).unwrap();
+        let trap = anyhow::Error::from(Trap::UnreachableCodeReached).context("secret detail");
+        let response = responses.response(&trap, "id").unwrap();
+        assert!(body_text(response).await.contains("secret detail"));
+    }
+
+    #[test]
+    fn rejects_non_error_statuses() {
+        let config = |status| {
+            [(
+                ErrorCategory::Timeout,
+                ErrorResponseConfig { status, body: None },
+            )]
+            .into()
+        };
+        assert!(ErrorResponses::new(config(200), false).is_err());
+        assert!(ErrorResponses::new(config(1000), false).is_err());
+        assert!(ErrorResponses::new(config(408), false).is_ok());
+    }
+
+    #[test]
+    fn request_id_prefers_header() {
+        let req = Request::get("/")
+            .header("x-request-id", "abc")
+            .body(())
+            .unwrap();
+        assert_eq!(request_id(&req), "abc");
+
+        let req = Request::get("/").body(()).unwrap();
+        assert!(!request_id(&req).is_empty());
+    }
+}
@@ -23,12 +23,21 @@ macro_rules! http_span {
             "http.response.status_code" = ::tracing::field::Empty,
             "http.route" = ::tracing::field::Empty,
             "otel.name" = ::tracing::field::Empty,
+            "spin.request_id" = ::tracing::field::Empty,
         )
     };
 }
 
 pub(crate) use http_span;
 
+/// Records an instance's memory use on the current `execute_wasm` span.
+pub(crate) fn record_memory_usage(state: &spin_core::State) {
+    let usage = state.memory_usage();
+    let span = tracing::Span::current();
+    span.record("spin.memory_peak_bytes", usage.peak);
+    span.record("spin.memory_grow_failures", usage.grow_failures);
+}
+
 /// Finish setting attributes on the HTTP span.
 pub(crate) fn finalize_http_span(
     response: Result<Response<Body>>,
@@ -37,10 +46,16 @@ pub(crate) fn finalize_http_span(
     let span = tracing::Span::current();
     match response {
         Ok(response) => {
-            tracing::info!(
-                "Request finished, sending response with status code {}",
-                response.status()
-            );
+            match response.extensions().get::<RequestId>() {
+                Some(RequestId(id)) => tracing::info!(
+                    "Request {id} finished, sending response with status code {}",
+                    response.status()
+                ),
+                None => tracing::info!(
+                    "Request finished, sending response with status code {}",
+                    response.status()
+                ),
+            }
 
             let matched_route = response.extensions().get::<MatchedRoute>();
             // Set otel.name and http.route
@@ -94,3 +109,8 @@ impl MatchedRoute {
         resp
     }
 }
+
+/// RequestId is used as a response extension to include the ID injected into a request in the
+/// log line for its response.
+#[derive(Clone)]
+pub(crate) struct RequestId(pub String);
@@ -1,18 +1,31 @@
 //! Implementation for the Spin HTTP engine.
 
+mod access_log;
+mod auth;
+mod buffer;
+mod compress;
+mod config;
+mod decompress;
+mod errors;
 mod headers;
 mod instrument;
+mod limits;
+mod listener;
+mod methods;
 mod outbound_http;
 mod server;
+mod shadow;
 mod spin;
+mod sse;
 mod tls;
 mod wagi;
 mod wasi;
 
 use std::{
+    collections::HashMap,
     error::Error,
     net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -21,10 +34,14 @@ use clap::Args;
 use serde::Deserialize;
 use spin_app::App;
 use spin_factors::RuntimeFactors;
+use spin_http_access_log::{AccessLogConfig, AccessLogWriter};
 use spin_trigger::Trigger;
 use wasmtime_wasi_http::bindings::http::types::ErrorCode;
 
+pub use errors::{ErrorCategory, ErrorHandlers, ErrorResponseConfig, ErrorResponses};
+pub use listener::ListenerOptions;
 pub use server::HttpServer;
+pub use sse::SseConfig;
 
 pub use tls::TlsConfig;
 
@@ -37,11 +54,20 @@ pub(crate) type TriggerApp<F> = spin_trigger::TriggerApp<HttpTrigger, F>;
 pub(crate) type TriggerInstanceBuilder<'a, F> =
     spin_trigger::TriggerInstanceBuilder<'a, HttpTrigger, F>;
 
+/// The address the server listens on if none is given on the command line or
+/// in the manifest.
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:3000";
+
+/// The directory, under the state directory (or the system temporary directory
+/// if there is none), that buffered request bodies spill to.
+const SPILL_DIR_NAME: &str = "http-request-bodies";
+
 #[derive(Args)]
 pub struct CliArgs {
-    /// IP address and port to listen on
-    #[clap(long = "listen", env = "SPIN_HTTP_LISTEN_ADDR", default_value = "127.0.0.1:3000", value_parser = parse_listen_addr)]
-    pub address: SocketAddr,
+    /// IP address and port to listen on. May be repeated to listen on several
+    /// addresses [default: 127.0.0.1:3000]
+    #[clap(long = "listen", env = "SPIN_HTTP_LISTEN_ADDR", value_delimiter = ',', value_parser = parse_listen_addr)]
+    pub addresses: Vec<SocketAddr>,
 
     /// The path to the certificate to use for https, if this is not set, normal http will be used. The cert should be in PEM format
     #[clap(long, env = "SPIN_TLS_CERT", requires = "tls-key")]
@@ -70,13 +96,21 @@ impl CliArgs {
 
 /// The Spin HTTP trigger.
 pub struct HttpTrigger {
-    /// The address the server should listen on.
+    /// The addresses the server should listen on.
     ///
-    /// Note that this might not be the actual socket address that ends up being bound to.
-    /// If the port is set to 0, the actual address will be determined by the OS.
-    listen_addr: SocketAddr,
+    /// Note that these might not be the actual socket addresses that end up being bound to.
+    /// If a port is set to 0, the actual address will be determined by the OS.
+    listen_addrs: Vec<SocketAddr>,
     tls_config: Option<TlsConfig>,
     find_free_port: bool,
+    listener_options: ListenerOptions,
+    error_responses: ErrorResponses,
+    error_handlers: ErrorHandlers,
+    sse_config: SseConfig,
+    access_log: Option<AccessLogWriter>,
+    /// Where the bodies of buffered requests spill to when they don't fit in
+    /// memory.
+    spill_dir: PathBuf,
 }
 
 impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
@@ -87,15 +121,32 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
 
     fn new(cli_args: Self::CliArgs, app: &spin_app::App) -> anyhow::Result<Self> {
         let find_free_port = cli_args.find_free_port;
+        // Addresses given on the command line replace those in the manifest
+        let listen_addrs = if cli_args.addresses.is_empty() {
+            Self::manifest_listen_addrs(app)?
+        } else {
+            cli_args.addresses.clone()
+        };
 
         Self::new(
             app,
-            cli_args.address,
+            listen_addrs,
             cli_args.into_tls_config(),
             find_free_port,
         )
     }
 
+    fn update_runtime_config(&mut self, config: &toml::Table) -> anyhow::Result<()> {
+        self.listener_options = ListenerOptions::from_runtime_config(config)?;
+        Ok(())
+    }
+
+    fn set_state_dir(&mut self, state_dir: Option<&Path>) {
+        if let Some(state_dir) = state_dir {
+            self.spill_dir = state_dir.join(SPILL_DIR_NAME);
+        }
+    }
+
     async fn run(self, trigger_app: TriggerApp<F>) -> anyhow::Result<()> {
         let server = self.into_server(trigger_app)?;
 
@@ -113,16 +164,30 @@ impl HttpTrigger {
     /// Create a new `HttpTrigger`.
     pub fn new(
         app: &spin_app::App,
-        listen_addr: SocketAddr,
+        listen_addrs: Vec<SocketAddr>,
         tls_config: Option<TlsConfig>,
         find_free_port: bool,
     ) -> anyhow::Result<Self> {
         Self::validate_app(app)?;
+        anyhow::ensure!(
+            !listen_addrs.is_empty(),
+            "the HTTP trigger requires at least one address to listen on"
+        );
+        let error_responses = Self::manifest_error_responses(app)?;
+        let error_handlers = Self::manifest_error_handlers(app)?;
+        let sse_config = Self::manifest_sse_config(app)?;
+        let access_log = Self::manifest_access_log(app)?;
 
         Ok(Self {
-            listen_addr,
+            listen_addrs,
             tls_config,
             find_free_port,
+            listener_options: Default::default(),
+            error_responses,
+            error_handlers,
+            sse_config,
+            access_log,
+            spill_dir: std::env::temp_dir().join(SPILL_DIR_NAME),
         })
     }
 
@@ -132,26 +197,94 @@ impl HttpTrigger {
         trigger_app: TriggerApp<F>,
     ) -> anyhow::Result<Arc<HttpServer<F>>> {
         let Self {
-            listen_addr,
+            listen_addrs,
             tls_config,
             find_free_port,
+            listener_options,
+            error_responses,
+            error_handlers,
+            sse_config,
+            access_log,
+            spill_dir,
         } = self;
         let server = Arc::new(HttpServer::new(
-            listen_addr,
+            listen_addrs,
             tls_config,
             find_free_port,
+            listener_options,
+            error_responses,
+            error_handlers,
+            sse_config,
+            access_log,
+            spill_dir,
             trigger_app,
         )?);
         Ok(server)
     }
 
-    fn validate_app(app: &App) -> anyhow::Result<()> {
-        #[derive(Deserialize)]
-        #[serde(deny_unknown_fields)]
-        struct TriggerMetadata {
-            base: Option<String>,
+    /// Returns the addresses from the manifest's `[application.trigger.http]`
+    /// `listen` field, or the default address if there are none.
+    fn manifest_listen_addrs(app: &App) -> anyhow::Result<Vec<SocketAddr>> {
+        let listen = config::trigger_metadata::<TriggerMetadata>(app)?
+            .map(|metadata| metadata.listen)
+            .unwrap_or_default();
+        if listen.is_empty() {
+            return Ok(vec![parse_listen_addr(DEFAULT_LISTEN_ADDR)?]);
         }
-        if let Some(TriggerMetadata { base: Some(base) }) = app.get_trigger_metadata("http")? {
+        listen
+            .iter()
+            .map(|addr| {
+                parse_listen_addr(addr)
+                    .with_context(|| format!("invalid HTTP trigger listen address {addr:?}"))
+            })
+            .collect()
+    }
+
+    /// Returns the error responses configured by the manifest's
+    /// `[application.trigger.http]` `error_responses` and `debug_errors`
+    /// fields.
+    fn manifest_error_responses(app: &App) -> anyhow::Result<ErrorResponses> {
+        let Some(metadata) = config::trigger_metadata::<TriggerMetadata>(app)? else {
+            return Ok(ErrorResponses::default());
+        };
+        ErrorResponses::new(metadata.error_responses, metadata.debug_errors)
+            .context("invalid HTTP trigger error_responses")
+    }
+
+    /// Returns the error handler components from the manifest's
+    /// `[application.trigger.http]` `error_handlers` field.
+    fn manifest_error_handlers(app: &App) -> anyhow::Result<ErrorHandlers> {
+        let error_handlers = config::trigger_metadata::<TriggerMetadata>(app)?
+            .map(|metadata| metadata.error_handlers)
+            .unwrap_or_default();
+        ErrorHandlers::new(error_handlers).context("invalid HTTP trigger error_handlers")
+    }
+
+    /// Returns the server-sent event options from the manifest's
+    /// `[application.trigger.http]` `sse` field.
+    fn manifest_sse_config(app: &App) -> anyhow::Result<SseConfig> {
+        let sse = config::trigger_metadata::<TriggerMetadata>(app)?
+            .map(|metadata| metadata.sse)
+            .unwrap_or_default();
+        sse.validate()
+            .context("invalid HTTP trigger sse configuration")?;
+        Ok(sse)
+    }
+
+    /// Returns the access log writer configured by the manifest's
+    /// `[application.trigger.http.access_log]` section, if there is one.
+    fn manifest_access_log(app: &App) -> anyhow::Result<Option<AccessLogWriter>> {
+        config::trigger_metadata::<TriggerMetadata>(app)?
+            .and_then(|metadata| metadata.access_log)
+            .map(|config| AccessLogWriter::new(&config).context("invalid HTTP trigger access_log"))
+            .transpose()
+    }
+
+    fn validate_app(app: &App) -> anyhow::Result<()> {
+        if let Some(TriggerMetadata {
+            base: Some(base), ..
+        }) = config::trigger_metadata(app)?
+        {
             if base == "/" {
                 tracing::warn!("This application has the deprecated trigger 'base' set to the default value '/'. This may be an error in the future!");
             } else {
@@ -162,6 +295,24 @@ impl HttpTrigger {
     }
 }
 
+/// The HTTP trigger's `[application.trigger.http]` manifest section.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerMetadata {
+    base: Option<String>,
+    #[serde(default)]
+    listen: Vec<String>,
+    #[serde(default)]
+    error_responses: HashMap<ErrorCategory, ErrorResponseConfig>,
+    #[serde(default)]
+    debug_errors: bool,
+    #[serde(default)]
+    error_handlers: HashMap<String, String>,
+    #[serde(default)]
+    sse: SseConfig,
+    access_log: Option<AccessLogConfig>,
+}
+
 fn parse_listen_addr(addr: &str) -> anyhow::Result<SocketAddr> {
     let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
     // Prefer 127.0.0.1 over e.g. [::1] because CHANGE IS HARD
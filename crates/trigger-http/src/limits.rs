@@ -0,0 +1,141 @@
+//! Enforcing a component's `max_response_body_bytes` limit.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use http::{header, Response};
+use http_body_util::BodyExt;
+use hyper::body::{Body as _, Bytes, Frame, SizeHint};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// Limits the body of a component's response to `max_bytes`.
+///
+/// A response whose `Content-Length`, or otherwise known length, is over the
+/// limit is an error, so the client gets an error response instead. A
+/// streamed body which grows over the limit ends with an error as soon as it
+/// does, which aborts the response, as its head has already been sent.
+pub(crate) fn limit_response_body(
+    response: Response<Body>,
+    max_bytes: u64,
+) -> anyhow::Result<Response<Body>> {
+    let length = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .or_else(|| response.body().size_hint().exact());
+    if let Some(length) = length {
+        anyhow::ensure!(
+            length <= max_bytes,
+            "response body of {length} bytes exceeds the component's max_response_body_bytes limit of {max_bytes}"
+        );
+    }
+    Ok(response.map(|body| LimitedBody::new(body, max_bytes).boxed()))
+}
+
+/// A response body which fails once more than `max_bytes` have been read.
+struct LimitedBody {
+    inner: Body,
+    max_bytes: u64,
+    remaining: u64,
+}
+
+impl LimitedBody {
+    fn new(inner: Body, max_bytes: u64) -> Self {
+        Self {
+            inner,
+            max_bytes,
+            remaining: max_bytes,
+        }
+    }
+}
+
+impl hyper::body::Body for LimitedBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            other => return other,
+        };
+        if let Some(data) = frame.data_ref() {
+            let len = data.len() as u64;
+            if len > this.remaining {
+                tracing::error!(
+                    "Response body exceeded the component's max_response_body_bytes limit of {}",
+                    this.max_bytes
+                );
+                return Poll::Ready(Some(Err(ErrorCode::HttpResponseBodySize(Some(
+                    this.max_bytes,
+                )))));
+            }
+            this.remaining -= len;
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use http_body_util::StreamBody;
+    use spin_http::body;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn bodies_within_the_limit_are_unchanged() {
+        let res = Response::new(body::full(Bytes::from_static(b"hello")));
+        let res = limit_response_body(res, 5).unwrap();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+    }
+
+    #[test]
+    fn bodies_known_to_be_too_large_are_rejected() {
+        let res = Response::new(body::full(Bytes::from_static(b"hello")));
+        let err = limit_response_body(res, 4).unwrap_err();
+        assert!(err.to_string().contains("max_response_body_bytes"), "{err}");
+
+        let res = Response::builder()
+            .header(header::CONTENT_LENGTH, "1000")
+            .body(body::empty())
+            .unwrap();
+        assert!(limit_response_body(res, 999).is_err());
+    }
+
+    #[tokio::test]
+    async fn streamed_bodies_fail_once_over_the_limit() {
+        let chunks = ["abc", "def", "ghi"]
+            .map(|chunk| Ok::<_, ErrorCode>(Frame::data(Bytes::from_static(chunk.as_bytes()))));
+        let res = Response::new(StreamBody::new(stream::iter(chunks)).boxed());
+        let mut body = limit_response_body(res, 7).unwrap().into_body();
+
+        let mut read = vec![];
+        let err = loop {
+            match body.frame().await.unwrap() {
+                Ok(frame) => read.extend_from_slice(frame.data_ref().unwrap()),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(read, b"abcdef");
+        assert!(matches!(err, ErrorCode::HttpResponseBodySize(Some(7))));
+    }
+}
@@ -0,0 +1,154 @@
+use std::{net::SocketAddr, time::Duration};
+
+use anyhow::{ensure, Context};
+use serde::Deserialize;
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+use tokio::net::TcpListener;
+
+/// The listen backlog used if none is configured, matching tokio's
+/// `TcpListener::bind`.
+const DEFAULT_BACKLOG: u32 = 1024;
+
+/// Socket options for the HTTP trigger's listeners.
+///
+/// These are set in the `[trigger.http]` section of the runtime config, e.g.
+///
+/// ```toml
+/// [trigger.http]
+/// backlog = 4096
+/// tcp_keepalive_secs = 60
+/// tcp_nodelay = true
+/// reuse_port = true
+/// ```
+///
+/// Options which aren't set keep their defaults.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenerOptions {
+    /// The maximum number of connections waiting to be accepted.
+    pub backlog: Option<u32>,
+    /// Enables TCP keepalive, probing connections which have been idle for
+    /// this many seconds.
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Sets `TCP_NODELAY`, disabling Nagle's algorithm.
+    pub tcp_nodelay: Option<bool>,
+    /// Sets `SO_REUSEPORT`, allowing several processes to listen on the same
+    /// address. Only supported on Unix.
+    pub reuse_port: Option<bool>,
+}
+
+impl ListenerOptions {
+    /// Parses options from the `[trigger.http]` section of the runtime config.
+    pub fn from_runtime_config(config: &toml::Table) -> anyhow::Result<Self> {
+        let options: Self = toml::Value::Table(config.clone())
+            .try_into()
+            .context("invalid HTTP listener options")?;
+        ensure!(
+            options.tcp_keepalive_secs != Some(0),
+            "`tcp_keepalive_secs` must be at least 1"
+        );
+        ensure!(
+            options.backlog.is_none_or(|backlog| backlog > 0),
+            "`backlog` must be at least 1"
+        );
+        #[cfg(not(unix))]
+        ensure!(
+            options.reuse_port != Some(true),
+            "`reuse_port` is not supported on this platform"
+        );
+        Ok(options)
+    }
+
+    /// Binds a listener to the given address, with these options applied to
+    /// the socket before it starts listening.
+    pub(crate) fn bind(&self, addr: SocketAddr) -> std::io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+        // Like tokio's `TcpListener::bind`, allow rebinding an address with
+        // connections lingering in TIME_WAIT
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        if addr.is_ipv6() {
+            // Allow listening on both e.g. `[::]:3000` and `0.0.0.0:3000`
+            socket.set_only_v6(true)?;
+        }
+        #[cfg(unix)]
+        if let Some(reuse_port) = self.reuse_port {
+            socket.set_reuse_port(reuse_port)?;
+        }
+        if let Some(nodelay) = self.tcp_nodelay {
+            socket.set_nodelay(nodelay)?;
+        }
+        if let Some(secs) = self.tcp_keepalive_secs {
+            let keepalive = TcpKeepalive::new().with_time(Duration::from_secs(secs));
+            socket.set_tcp_keepalive(&keepalive)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+        let backlog = self.backlog.unwrap_or(DEFAULT_BACKLOG);
+        socket.listen(i32::try_from(backlog).unwrap_or(i32::MAX))?;
+        TcpListener::from_std(socket.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use socket2::SockRef;
+
+    use super::*;
+
+    fn localhost() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn socket_options_are_applied() {
+        let options = ListenerOptions {
+            backlog: Some(16),
+            tcp_keepalive_secs: Some(42),
+            tcp_nodelay: Some(true),
+            reuse_port: cfg!(unix).then_some(true),
+        };
+        let listener = options.bind(localhost()).unwrap();
+        let socket = SockRef::from(&listener);
+        assert!(socket.keepalive().unwrap());
+        assert!(socket.nodelay().unwrap());
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        assert_eq!(socket.keepalive_time().unwrap(), Duration::from_secs(42));
+        #[cfg(unix)]
+        assert!(socket.reuse_port().unwrap());
+    }
+
+    #[tokio::test]
+    async fn default_options_accept_connections() {
+        let listener = ListenerOptions::default().bind(localhost()).unwrap();
+        let socket = SockRef::from(&listener);
+        assert!(!socket.keepalive().unwrap());
+
+        let addr = listener.local_addr().unwrap();
+        let (client, accepted) =
+            tokio::join!(tokio::net::TcpStream::connect(addr), listener.accept());
+        assert_eq!(client.unwrap().local_addr().unwrap(), accepted.unwrap().1);
+    }
+
+    #[test]
+    fn options_are_parsed_from_runtime_config() {
+        let options = ListenerOptions::from_runtime_config(&toml::toml! {
+            backlog = 4096
+            tcp_keepalive_secs = 60
+            tcp_nodelay = true
+        })
+        .unwrap();
+        assert_eq!(options.backlog, Some(4096));
+        assert_eq!(options.tcp_keepalive_secs, Some(60));
+        assert_eq!(options.tcp_nodelay, Some(true));
+        assert_eq!(options.reuse_port, None);
+
+        for invalid in [
+            toml::toml! { tcp_keepalive_secs = 0 },
+            toml::toml! { backlog = -1 },
+            toml::toml! { listen = ["127.0.0.1:3000"] },
+        ] {
+            assert!(ListenerOptions::from_runtime_config(&invalid).is_err());
+        }
+    }
+}
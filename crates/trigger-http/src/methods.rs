@@ -0,0 +1,177 @@
+//! Handling `HEAD` and `OPTIONS` requests for components which don't handle
+//! them themselves, according to a route's `auto_head` and `methods`.
+
+use http::{header, HeaderValue, Method, Response, StatusCode};
+use hyper::body::Body as _;
+use spin_http::{body, config::HttpTriggerConfig};
+
+use crate::Body;
+
+/// How the trigger handles a request, according to its method.
+#[derive(Debug, PartialEq)]
+pub(crate) enum MethodHandling {
+    /// The request is passed to the component unchanged.
+    Component,
+    /// The request is passed to the component as a `GET` request, and the
+    /// body of its response is discarded.
+    HeadAsGet,
+    /// The trigger answers the request itself, with this `Allow` header.
+    Options(HeaderValue),
+}
+
+impl MethodHandling {
+    /// Returns how a request with the given method to a route with the given
+    /// config is handled. A method the component declares is always passed to
+    /// it, so that its own response wins.
+    pub(crate) fn new(config: &HttpTriggerConfig, method: &Method) -> Self {
+        let declares = |method: &Method| {
+            config.methods.as_ref().map(|methods| {
+                methods
+                    .iter()
+                    .any(|declared| declared.eq_ignore_ascii_case(method.as_str()))
+            })
+        };
+        // A component which declares methods but not `GET` can't answer a
+        // `GET` in place of the `HEAD`
+        if *method == Method::HEAD
+            && config.auto_head
+            && declares(&Method::HEAD) != Some(true)
+            && declares(&Method::GET) != Some(false)
+        {
+            return Self::HeadAsGet;
+        }
+        // Only the component knows which methods it handles if it doesn't
+        // declare them
+        if *method == Method::OPTIONS && declares(&Method::OPTIONS) == Some(false) {
+            return Self::Options(allow_header(config));
+        }
+        Self::Component
+    }
+}
+
+/// The `Allow` header for a route which declares its methods: the declared
+/// methods, with `HEAD` if the trigger answers it and `OPTIONS`.
+fn allow_header(config: &HttpTriggerConfig) -> HeaderValue {
+    let mut allow: Vec<String> = vec![];
+    for method in config.methods.iter().flatten() {
+        let method = method.to_ascii_uppercase();
+        if !allow.contains(&method) {
+            allow.push(method);
+        }
+    }
+    let get = allow.iter().any(|m| m == "GET");
+    let mut add = |method: &str, condition: bool| {
+        if condition && !allow.iter().any(|m| m == method) {
+            allow.push(method.to_owned());
+        }
+    };
+    add("HEAD", config.auto_head && get);
+    add("OPTIONS", true);
+    // Methods are validated as tokens when the server is built, so are valid
+    // header values
+    HeaderValue::from_str(&allow.join(", ")).expect("HTTP methods are valid header values")
+}
+
+/// The trigger's response to an `OPTIONS` request.
+pub(crate) fn options_response(allow: HeaderValue) -> anyhow::Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(header::ALLOW, allow)
+        .body(body::empty())?)
+}
+
+/// Turns the component's response to a `GET` request into the response to a
+/// `HEAD` request, discarding the body but keeping the headers. The
+/// `Content-Length` the `GET` response would have had is kept, or set if the
+/// body's length is known; otherwise it is omitted, as the body is never read
+/// to count it, since a streamed body (such as an event stream) may not end.
+pub(crate) fn head_response(res: Response<Body>) -> Response<Body> {
+    let (mut parts, body) = res.into_parts();
+    if !parts.headers.contains_key(header::CONTENT_LENGTH) {
+        if let Some(length) = body.size_hint().exact() {
+            parts.headers.insert(header::CONTENT_LENGTH, length.into());
+        }
+    }
+    Response::from_parts(parts, body::empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+    use hyper::body::Bytes;
+
+    use super::*;
+
+    fn config(methods: Option<&[&str]>, auto_head: bool) -> HttpTriggerConfig {
+        HttpTriggerConfig {
+            methods: methods.map(|methods| methods.iter().map(|m| m.to_string()).collect()),
+            auto_head,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn head_is_answered_with_get_unless_declared() {
+        let cases = [
+            (None, true, MethodHandling::HeadAsGet),
+            (None, false, MethodHandling::Component),
+            (Some(&["GET"][..]), true, MethodHandling::HeadAsGet),
+            (Some(&["GET", "HEAD"][..]), true, MethodHandling::Component),
+            (Some(&["POST"][..]), true, MethodHandling::Component),
+        ];
+        for (methods, auto_head, expected) in cases {
+            let handling = MethodHandling::new(&config(methods, auto_head), &Method::HEAD);
+            assert_eq!(handling, expected, "{methods:?}, auto_head = {auto_head}");
+        }
+        assert_eq!(
+            MethodHandling::new(&config(None, true), &Method::GET),
+            MethodHandling::Component
+        );
+    }
+
+    #[test]
+    fn options_is_answered_when_methods_are_declared() {
+        let handling = MethodHandling::new(&config(Some(&["get", "POST"]), true), &Method::OPTIONS);
+        assert_eq!(
+            handling,
+            MethodHandling::Options(HeaderValue::from_static("GET, POST, HEAD, OPTIONS"))
+        );
+        let handling = MethodHandling::new(&config(Some(&["POST"]), true), &Method::OPTIONS);
+        assert_eq!(
+            handling,
+            MethodHandling::Options(HeaderValue::from_static("POST, OPTIONS"))
+        );
+
+        let res = options_response(HeaderValue::from_static("GET, OPTIONS")).unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()[header::ALLOW], "GET, OPTIONS");
+    }
+
+    #[test]
+    fn options_passes_through_when_declared_or_unknown() {
+        for methods in [None, Some(&["GET", "OPTIONS"][..])] {
+            let handling = MethodHandling::new(&config(methods, true), &Method::OPTIONS);
+            assert_eq!(handling, MethodHandling::Component, "{methods:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn head_response_has_empty_body_and_get_length() {
+        let res = Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(body::full(Bytes::from_static(b"hello, world")))
+            .unwrap();
+        let res = head_response(res);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(res.headers()[header::CONTENT_LENGTH], "12");
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+
+        // A length set by the component is kept
+        let res = Response::builder()
+            .header(header::CONTENT_LENGTH, "42")
+            .body(body::empty())
+            .unwrap();
+        assert_eq!(head_response(res).headers()[header::CONTENT_LENGTH], "42");
+    }
+}
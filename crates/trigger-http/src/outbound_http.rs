@@ -3,35 +3,66 @@ use std::{
     sync::Arc,
 };
 
-use http::uri::Scheme;
+use http::{uri::Scheme, HeaderValue, Request};
 use spin_core::async_trait;
 use spin_factor_outbound_http::intercept::{self, InterceptOutcome, InterceptRequest};
-use spin_factor_outbound_networking::config::allowed_hosts::parse_service_chaining_target;
+use spin_factor_outbound_networking::config::allowed_hosts::{
+    parse_cross_app_chaining_target, parse_service_chaining_target,
+};
 use spin_factors::RuntimeFactors;
+use spin_factors_executor::ChainingError;
 use spin_http::routes::RouteMatch;
-use wasmtime_wasi_http::{HttpError, HttpResult};
+use wasmtime_wasi_http::{bindings::http::types::ErrorCode, HttpError, HttpResult};
 
-use crate::HttpServer;
+use crate::{errors::REQUEST_ID_HEADER, Body, HttpServer};
 
-/// An outbound HTTP interceptor that handles service chaining requests.
+/// An outbound HTTP interceptor that handles service chaining requests, both
+/// within the app and to the components of other apps loaded with the same
+/// executor.
 pub struct OutboundHttpInterceptor<F: RuntimeFactors> {
     server: Arc<HttpServer<F>>,
+    /// The ID of the request being handled, passed on to chained components.
+    request_id: Option<HeaderValue>,
 }
 
 impl<F: RuntimeFactors> OutboundHttpInterceptor<F> {
     pub fn new(server: Arc<HttpServer<F>>) -> Self {
-        Self { server }
+        Self {
+            server,
+            request_id: None,
+        }
+    }
+
+    /// Passes the given request ID on to chained components, unless the
+    /// chained request sets its own.
+    pub fn with_request_id(mut self, request_id: HeaderValue) -> Self {
+        self.request_id = Some(request_id);
+        self
     }
 }
 
 const CHAINED_CLIENT_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0);
 
+impl<F: RuntimeFactors> OutboundHttpInterceptor<F> {
+    /// Converts an intercepted request into a request to a chained
+    /// component, passing on the ID of the request being handled.
+    fn chained_request(&self, request: InterceptRequest) -> Request<Body> {
+        let mut req = request.into_hyper_request();
+        if let Some(request_id) = &self.request_id {
+            req.headers_mut()
+                .entry(REQUEST_ID_HEADER)
+                .or_insert_with(|| request_id.clone());
+        }
+        req
+    }
+}
+
 #[async_trait]
 impl<F: RuntimeFactors> intercept::OutboundHttpInterceptor for OutboundHttpInterceptor<F> {
     async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
         // Handle service chaining requests
         if let Some(component_id) = parse_service_chaining_target(request.uri()) {
-            let req = request.into_hyper_request();
+            let req = self.chained_request(request);
             let path = req.uri().path().to_owned();
             let route_match = RouteMatch::synthetic(component_id, path);
             let resp = self
@@ -40,8 +71,34 @@ impl<F: RuntimeFactors> intercept::OutboundHttpInterceptor for OutboundHttpInter
                 .await
                 .map_err(HttpError::trap)?;
             Ok(InterceptOutcome::Complete(resp))
+        } else if let Some((target_app, component_id)) =
+            parse_cross_app_chaining_target(request.uri())
+        {
+            let req = self.chained_request(request);
+            let resp = self
+                .server
+                .handle_cross_app_chained(&target_app, &component_id, req, CHAINED_CLIENT_ADDR)
+                .await
+                .map_err(|err| {
+                    tracing::info!("Refusing chained request: {err}");
+                    HttpError::from(chaining_error_code(&err))
+                })?
+                .map_err(HttpError::trap)?;
+            Ok(InterceptOutcome::Complete(resp))
         } else {
             Ok(InterceptOutcome::Continue(request))
         }
     }
 }
+
+/// The error returned to a component whose request couldn't be chained to
+/// another app.
+fn chaining_error_code(err: &ChainingError) -> ErrorCode {
+    match err {
+        ChainingError::Denied { .. } => ErrorCode::HttpRequestDenied,
+        ChainingError::NoSuchApp(_) | ChainingError::NoSuchComponent { .. } => {
+            ErrorCode::DestinationNotFound
+        }
+        ChainingError::Unloaded(_) => ErrorCode::DestinationUnavailable,
+    }
+}
@@ -2,14 +2,17 @@ use std::{
     collections::HashMap,
     future::Future,
     io::{ErrorKind, IsTerminal},
-    net::SocketAddr,
-    sync::Arc,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
 };
 
 use anyhow::{bail, Context};
+use futures::future::try_join_all;
 use http::{
     uri::{Authority, Scheme},
-    Request, Response, StatusCode, Uri,
+    HeaderValue, Method, Request, Response, StatusCode, Uri,
 };
 use http_body_util::BodyExt;
 use hyper::{
@@ -21,44 +24,77 @@ use hyper_util::{
     server::conn::auto::Builder,
 };
 use spin_app::{APP_DESCRIPTION_KEY, APP_NAME_KEY};
+use spin_factor_audit::AuditFactor;
 use spin_factor_outbound_http::{OutboundHttpFactor, SelfRequestOrigin};
 use spin_factors::RuntimeFactors;
+use spin_factors_executor::{BackgroundTaskState, ChainingError};
 use spin_http::{
     app_info::AppInfo,
     body,
-    config::{HttpExecutorType, HttpTriggerConfig},
+    config::{BodyHandling, HttpExecutorType, HttpTriggerConfig},
     routes::{RouteMatch, Router},
     trigger::HandlerType,
 };
+use spin_http_access_log::AccessLogWriter;
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     net::TcpListener,
     task,
 };
+use tokio_rustls::TlsAcceptor;
 use tracing::Instrument;
 use wasmtime_wasi::p2::bindings::CommandIndices;
 use wasmtime_wasi_http::body::HyperOutgoingBody;
 
 use crate::{
+    access_log::PendingAccessLogEntry,
+    auth::{self, Credentials},
+    buffer::{buffer_request_body, BufferError},
+    compress::{accepts_gzip, compress_response},
+    decompress::decompress_request_body,
+    errors::{
+        request_id, ErrorCategory, ErrorHandlers, ErrorResponses, ERROR_CODE_HEADER,
+        REQUEST_ID_HEADER,
+    },
     headers::strip_forbidden_headers,
-    instrument::{finalize_http_span, http_span, instrument_error, MatchedRoute},
+    instrument::{finalize_http_span, http_span, instrument_error, MatchedRoute, RequestId},
+    limits::limit_response_body,
+    methods::{head_response, options_response, MethodHandling},
     outbound_http::OutboundHttpInterceptor,
+    shadow::{run_shadow, sample, tee_request},
     spin::SpinHttpExecutor,
+    sse::{event_stream_response, SseConfig},
     wagi::WagiHttpExecutor,
     wasi::WasiHttpExecutor,
-    Body, NotFoundRouteKind, TlsConfig, TriggerApp, TriggerInstanceBuilder,
+    Body, HttpTrigger, ListenerOptions, NotFoundRouteKind, TlsConfig, TriggerApp,
+    TriggerInstanceBuilder,
 };
 
 pub const MAX_RETRIES: u16 = 10;
 
 /// An HTTP server which runs Spin apps.
 pub struct HttpServer<F: RuntimeFactors> {
-    /// The address the server is listening on.
-    listen_addr: SocketAddr,
+    /// The addresses the server is listening on.
+    listen_addrs: Vec<SocketAddr>,
+    /// The addresses actually bound, once the server is serving.
+    bound_addrs: OnceLock<Vec<SocketAddr>>,
     /// The TLS configuration for the server.
     tls_config: Option<TlsConfig>,
     /// Whether to find a free port if the specified port is already in use.
     find_free_port: bool,
+    /// Socket options for the listeners.
+    listener_options: ListenerOptions,
+    /// How to respond when a component fails.
+    error_responses: ErrorResponses,
+    /// The components which handle requests instead of built-in error
+    /// responses.
+    error_handlers: ErrorHandlers,
+    /// Options for server-sent event responses.
+    sse_config: SseConfig,
+    /// The writer of the access log, if one is configured.
+    access_log: Option<Arc<AccessLogWriter>>,
+    /// Where the bodies of buffered requests spill to.
+    spill_dir: PathBuf,
     /// Request router.
     router: Router,
     /// The app being triggered.
@@ -67,38 +103,51 @@ pub struct HttpServer<F: RuntimeFactors> {
     component_trigger_configs: HashMap<String, HttpTriggerConfig>,
     // Component ID -> handler type
     component_handler_types: HashMap<String, HandlerType>,
+    // App name -> server for requests chained to that app, built on first use
+    chained_servers: Mutex<HashMap<String, Arc<Self>>>,
 }
 
 impl<F: RuntimeFactors> HttpServer<F> {
     /// Create a new [`HttpServer`].
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        listen_addr: SocketAddr,
+        listen_addrs: Vec<SocketAddr>,
         tls_config: Option<TlsConfig>,
         find_free_port: bool,
+        listener_options: ListenerOptions,
+        error_responses: ErrorResponses,
+        error_handlers: ErrorHandlers,
+        sse_config: SseConfig,
+        access_log: Option<AccessLogWriter>,
+        spill_dir: PathBuf,
         trigger_app: TriggerApp<F>,
     ) -> anyhow::Result<Self> {
         // This needs to be a vec before building the router to handle duplicate routes
         let component_trigger_configs = Vec::from_iter(
-            trigger_app
-                .app()
-                .trigger_configs::<HttpTriggerConfig>("http")?
+            crate::config::trigger_configs(trigger_app.app())?
                 .into_iter()
-                .map(|(_, config)| (config.component.clone(), config)),
+                .map(|config| (config.component.clone(), config)),
         );
 
         // Build router
         let component_routes = component_trigger_configs
             .iter()
-            .map(|(component_id, config)| (component_id.as_str(), &config.route));
+            .map(|(component_id, config)| {
+                (component_id.as_str(), &config.route, config.host.as_ref())
+            });
         let mut duplicate_routes = Vec::new();
-        let router = Router::build("/", component_routes, Some(&mut duplicate_routes))?;
+        let router = Router::build_with_hosts("/", component_routes, Some(&mut duplicate_routes))?;
         if !duplicate_routes.is_empty() {
             tracing::error!(
                 "The following component routes are duplicates and will never be used:"
             );
             for dup in &duplicate_routes {
+                let host = dup
+                    .host()
+                    .map(|host| format!(" (host {host})"))
+                    .unwrap_or_default();
                 tracing::error!(
-                    "  {}: {} (duplicate of {})",
+                    "  {}: {}{host} (duplicate of {})",
                     dup.replaced_id,
                     dup.route(),
                     dup.effective_id,
@@ -116,9 +165,58 @@ impl<F: RuntimeFactors> HttpServer<F> {
             router.routes().collect::<Vec<_>>()
         );
 
-        // Now that router is built we can merge duplicate routes by component
+        // Now that router is built we can merge duplicate routes by component:
+        // a component's triggers differ only in their routes and hosts
         let component_trigger_configs = HashMap::from_iter(component_trigger_configs);
 
+        for (component_id, trigger_config) in &component_trigger_configs {
+            if let Some(auth) = &trigger_config.auth {
+                auth::validate_auth_config(trigger_app.app(), component_id, auth)?;
+            }
+            anyhow::ensure!(
+                trigger_config.write_idle_timeout_secs != Some(0),
+                "`write_idle_timeout_secs` for component '{component_id}' must be at least 1"
+            );
+            for method in trigger_config.methods.iter().flatten() {
+                Method::from_bytes(method.as_bytes()).with_context(|| {
+                    format!("invalid method {method:?} in `methods` for component '{component_id}'")
+                })?;
+            }
+        }
+
+        for (status, component_id) in error_handlers.components() {
+            anyhow::ensure!(
+                component_trigger_configs.contains_key(component_id),
+                "error handler for {} responses uses component '{component_id}', which has no HTTP trigger. \
+                 Add a trigger for it with `route = {{ private = true }}` to keep it from serving requests of its own.",
+                status.as_u16()
+            );
+        }
+
+        for (component_id, trigger_config) in &component_trigger_configs {
+            let Some(shadow) = &trigger_config.shadow else {
+                continue;
+            };
+            anyhow::ensure!(
+                component_trigger_configs.contains_key(&shadow.component),
+                "component '{component_id}' shadows requests to component '{}', which has no HTTP trigger. \
+                 Add a trigger for it with `route = {{ private = true }}` to keep it from serving requests of its own.",
+                shadow.component
+            );
+            anyhow::ensure!(
+                &shadow.component != component_id,
+                "component '{component_id}' cannot shadow requests to itself"
+            );
+            anyhow::ensure!(
+                (0.0..=100.0).contains(&shadow.percent),
+                "shadow `percent` for component '{component_id}' must be between 0 and 100"
+            );
+            anyhow::ensure!(
+                shadow.timeout_ms > 0,
+                "shadow `timeout_ms` for component '{component_id}' must be at least 1"
+            );
+        }
+
         let component_handler_types = component_trigger_configs
             .iter()
             .map(|(component_id, trigger_config)| {
@@ -140,41 +238,69 @@ impl<F: RuntimeFactors> HttpServer<F> {
             })
             .collect::<anyhow::Result<_>>()?;
         Ok(Self {
-            listen_addr,
+            listen_addrs,
+            bound_addrs: OnceLock::new(),
             tls_config,
             find_free_port,
+            listener_options,
+            error_responses,
+            error_handlers,
+            sse_config,
+            access_log: access_log.map(Arc::new),
+            spill_dir,
             router,
             trigger_app,
             component_trigger_configs,
             component_handler_types,
+            chained_servers: Default::default(),
         })
     }
 
-    /// Serve incoming requests over the provided [`TcpListener`].
+    /// Serve incoming requests on each of the server's listen addresses.
+    ///
+    /// All listeners are served by the returned future, so they stop accepting
+    /// connections together when it is dropped (e.g. on Ctrl+C), or when any
+    /// one of them fails.
     pub async fn serve(self: Arc<Self>) -> anyhow::Result<()> {
-        let listener: TcpListener = if self.find_free_port {
-            self.search_for_free_port().await?
-        } else {
-            TcpListener::bind(self.listen_addr).await.map_err(|err| {
-                if err.kind() == ErrorKind::AddrInUse {
-                    anyhow::anyhow!("{} is already in use. To have Spin search for a free port, use the --find-free-port option.", self.listen_addr)
-                } else {
-                    anyhow::anyhow!("Unable to listen on {}: {err:?}", self.listen_addr)
-                }
-            })?
+        let mut listeners = Vec::with_capacity(self.listen_addrs.len());
+        for &addr in &self.listen_addrs {
+            let listener = if self.find_free_port {
+                self.search_for_free_port(addr)?
+            } else {
+                self.listener_options.bind(addr).map_err(|err| {
+                    if err.kind() == ErrorKind::AddrInUse {
+                        anyhow::anyhow!("{addr} is already in use. To have Spin search for a free port, use the --find-free-port option.")
+                    } else {
+                        anyhow::anyhow!("Unable to listen on {addr}: {err:?}")
+                    }
+                })?
+            };
+            listeners.push(listener);
+        }
+        let bound_addrs = listeners
+            .iter()
+            .map(TcpListener::local_addr)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (scheme, acceptor) = match &self.tls_config {
+            Some(tls_config) => (Scheme::HTTPS, Some(tls_config.server_config()?)),
+            None => (Scheme::HTTP, None),
         };
+        self.print_startup_msgs(&scheme, &bound_addrs)?;
+        let _ = self.bound_addrs.set(bound_addrs);
 
-        if let Some(tls_config) = self.tls_config.clone() {
-            self.serve_https(listener, tls_config).await?;
-        } else {
-            self.serve_http(listener).await?;
-        }
+        try_join_all(
+            listeners
+                .into_iter()
+                .map(|listener| self.clone().accept(listener, acceptor.clone())),
+        )
+        .await?;
         Ok(())
     }
 
-    async fn search_for_free_port(&self) -> anyhow::Result<TcpListener> {
+    fn search_for_free_port(&self, base_addr: SocketAddr) -> anyhow::Result<TcpListener> {
         let mut found_listener = None;
-        let mut addr = self.listen_addr;
+        let mut addr = base_addr;
 
         for _ in 1..=MAX_RETRIES {
             if addr.port() == u16::MAX {
@@ -183,7 +309,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
                 );
             }
 
-            match TcpListener::bind(addr).await {
+            match self.listener_options.bind(addr) {
                 Ok(listener) => {
                     found_listener = Some(listener);
                     break;
@@ -198,38 +324,46 @@ impl<F: RuntimeFactors> HttpServer<F> {
 
         found_listener.ok_or_else(|| anyhow::anyhow!(
             "Couldn't find a free port in the range {}-{}. Consider retrying with a different base port.",
-            self.listen_addr.port(),
-            self.listen_addr.port() + MAX_RETRIES
+            base_addr.port(),
+            base_addr.port().saturating_add(MAX_RETRIES)
         ))
     }
 
-    async fn serve_http(self: Arc<Self>, listener: TcpListener) -> anyhow::Result<()> {
-        self.print_startup_msgs("http", &listener)?;
-        loop {
-            let (stream, client_addr) = listener.accept().await?;
-            self.clone()
-                .serve_connection(stream, Scheme::HTTP, client_addr);
-        }
-    }
-
-    async fn serve_https(
+    /// Accepts connections on the given listener, using TLS if an acceptor is
+    /// given.
+    async fn accept(
         self: Arc<Self>,
         listener: TcpListener,
-        tls_config: TlsConfig,
+        acceptor: Option<TlsAcceptor>,
     ) -> anyhow::Result<()> {
-        self.print_startup_msgs("https", &listener)?;
-        let acceptor = tls_config.server_config()?;
         loop {
             let (stream, client_addr) = listener.accept().await?;
-            match acceptor.accept(stream).await {
-                Ok(stream) => self
+            match &acceptor {
+                None => self
                     .clone()
-                    .serve_connection(stream, Scheme::HTTPS, client_addr),
-                Err(err) => tracing::error!(?err, "Failed to start TLS session"),
+                    .serve_connection(stream, Scheme::HTTP, client_addr),
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => self
+                        .clone()
+                        .serve_connection(stream, Scheme::HTTPS, client_addr),
+                    Err(err) => tracing::error!(?err, "Failed to start TLS session"),
+                },
             }
         }
     }
 
+    /// The address outbound requests to the app itself ("self requests") are
+    /// sent to.
+    ///
+    /// This is the first bound IPv4 address if there is one, or the first
+    /// bound address otherwise, with unspecified addresses (e.g. `0.0.0.0`)
+    /// replaced by loopback. Before the server is serving, the configured
+    /// listen addresses are used instead.
+    fn self_request_addr(&self) -> SocketAddr {
+        let addrs = self.bound_addrs.get().unwrap_or(&self.listen_addrs);
+        primary_addr(addrs).unwrap_or_else(|| SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+    }
+
     /// Handles incoming requests using an HTTP executor.
     ///
     /// This method handles well known paths and routes requests to the handler when the router
@@ -251,21 +385,32 @@ impl<F: RuntimeFactors> HttpServer<F> {
         // Handle well-known spin paths
         if let Some(well_known) = path.strip_prefix(spin_http::WELL_KNOWN_PREFIX) {
             return match well_known {
-                "health" => Ok(MatchedRoute::with_response_extension(
-                    Response::new(body::full(Bytes::from_static(b"OK"))),
-                    path,
-                )),
+                "health" => self.health(path),
                 "info" => self.app_info(path),
                 _ => Self::not_found(NotFoundRouteKind::WellKnown),
             };
         }
 
-        match self.router.route(&path) {
+        match self
+            .router
+            .route_with_host(request_host(&req).as_deref(), &path)
+        {
             Ok(route_match) => {
                 self.handle_trigger_route(req, route_match, server_scheme, client_addr)
                     .await
             }
-            Err(_) => Self::not_found(NotFoundRouteKind::Normal(path.to_string())),
+            Err(_) => {
+                if let Some(handler) = &self.error_handlers.not_found {
+                    set_req_uri(&mut req, server_scheme)?;
+                    if let Some(res) = self
+                        .handle_error(handler, req, StatusCode::NOT_FOUND, client_addr)
+                        .await
+                    {
+                        return Ok(res);
+                    }
+                }
+                Self::not_found(NotFoundRouteKind::Normal(path.to_string()))
+            }
         }
     }
 
@@ -286,6 +431,38 @@ impl<F: RuntimeFactors> HttpServer<F> {
 
         let component_id = route_match.component_id();
 
+        let trigger_config = self
+            .component_trigger_configs
+            .get(component_id)
+            .with_context(|| format!("unknown component ID {component_id:?}"))?;
+
+        // Answered without authentication, so that CORS preflight requests,
+        // which never carry credentials, succeed
+        let method_handling = MethodHandling::new(trigger_config, req.method());
+        if let MethodHandling::Options(allow) = method_handling {
+            return Ok(MatchedRoute::with_response_extension(
+                options_response(allow)?,
+                route_match.raw_route(),
+            ));
+        }
+
+        // Enforce route authentication before the component is instantiated
+        if let Some(auth) = &trigger_config.auth {
+            let credentials =
+                match Credentials::resolve(auth, self.trigger_app.configured_app()).await {
+                    Ok(credentials) => credentials,
+                    Err(err) => {
+                        tracing::error!("Error resolving route credentials: {err:?}");
+                        instrument_error(&err);
+                        return Self::internal_error(None, route_match.raw_route());
+                    }
+                };
+            if !credentials.authorize(req.headers()) {
+                tracing::info!("Rejecting unauthorized request for component {component_id}");
+                return auth::unauthorized(auth, route_match.raw_route());
+            }
+        }
+
         spin_telemetry::metrics::monotonic_counter!(
             spin.request_count = 1,
             trigger_type = "http",
@@ -293,6 +470,185 @@ impl<F: RuntimeFactors> HttpServer<F> {
             component_id = component_id
         );
 
+        let injected_id = if trigger_config.inject_request_id {
+            Some(inject_request_id(&mut req)?)
+        } else {
+            None
+        };
+        let request_id = request_id(&req);
+
+        if trigger_config.decompress_request_body {
+            let max_bytes = self
+                .trigger_app
+                .limits(component_id)?
+                .max_request_body_bytes;
+            req = match decompress_request_body(req, max_bytes).await {
+                Ok(req) => req,
+                Err(err) => {
+                    tracing::info!("Rejecting request {request_id}: {err}");
+                    return Ok(MatchedRoute::with_response_extension(
+                        Response::builder()
+                            .status(err.status())
+                            .body(body::empty())?,
+                        route_match.raw_route(),
+                    ));
+                }
+            };
+        }
+
+        // A buffered body is kept by the instance's store, so any part of it
+        // spilled to disk is deleted when the instance is dropped
+        if trigger_config.body_handling == BodyHandling::Buffer {
+            let max_total_bytes = trigger_config.max_total_bytes.or(self
+                .trigger_app
+                .limits(component_id)?
+                .max_request_body_bytes);
+            req = match buffer_request_body(
+                req,
+                trigger_config.max_memory_bytes,
+                max_total_bytes,
+                &self.spill_dir,
+            )
+            .await
+            {
+                Ok(req) => req,
+                Err(err) => {
+                    if matches!(err, BufferError::Spill(_)) {
+                        tracing::error!("Failed to buffer request {request_id}: {err}");
+                    } else {
+                        tracing::info!("Rejecting request {request_id}: {err}");
+                    }
+                    return Ok(MatchedRoute::with_response_extension(
+                        Response::builder()
+                            .status(err.status())
+                            .body(body::empty())?,
+                        route_match.raw_route(),
+                    ));
+                }
+            };
+        }
+
+        let mut instance_builder =
+            self.prepare_instance(component_id, server_scheme.clone(), injected_id.clone())?;
+        if let Some(auditor) = instance_builder.factor_builder::<AuditFactor>() {
+            auditor.set_request_id(request_id.clone());
+        }
+        // The body is consumed by the component, so an error handler gets
+        // only the head of the original request
+        let error_request = self
+            .error_handlers
+            .internal_error
+            .is_some()
+            .then(|| request_head(&req));
+
+        // The shadow component gets a copy of the request as the route's
+        // component sees it, once the route's component has read the body
+        if let Some(shadow) = &trigger_config.shadow {
+            if sample(shadow.percent, &mut rand::rng()) {
+                let (primary, shadow_req) = tee_request(req, shadow.max_body_bytes);
+                req = primary;
+                let server = self.clone();
+                let shadow_component = shadow.component.clone();
+                let timeout = Duration::from_millis(shadow.timeout_ms);
+                let span = tracing::info_span!(
+                    "spin_http.shadow",
+                    shadow.component = %shadow_component,
+                    shadow.outcome = tracing::field::Empty,
+                    shadow.duration_ms = tracing::field::Empty,
+                    http.response.status_code = tracing::field::Empty,
+                );
+                task::spawn(
+                    async move {
+                        run_shadow(shadow_req, &shadow_component, timeout, |req| {
+                            server.execute_shadow(&shadow_component, req, client_addr)
+                        })
+                        .await;
+                    }
+                    .instrument(span),
+                );
+            }
+        }
+
+        // Components which only handle GET can still answer HEAD requests:
+        // they see a GET, and the body of their response is discarded
+        let head_as_get = method_handling == MethodHandling::HeadAsGet;
+        // The body of a response to a HEAD request is discarded, so is never
+        // compressed
+        let gzip_accepted = trigger_config.compress_response
+            && req.method() != Method::HEAD
+            && accepts_gzip(req.headers());
+        if head_as_get {
+            *req.method_mut() = Method::GET;
+        }
+
+        let res = self
+            .execute(
+                instance_builder,
+                trigger_config,
+                &route_match,
+                req,
+                client_addr,
+            )
+            .await;
+        let res = match self
+            .trigger_app
+            .limits(component_id)?
+            .max_response_body_bytes
+        {
+            Some(max_bytes) => res.and_then(|res| limit_response_body(res, max_bytes)),
+            None => res,
+        };
+        let mut res = match res {
+            Ok(res) if head_as_get => {
+                MatchedRoute::with_response_extension(head_response(res), route_match.raw_route())
+            }
+            Ok(res) => {
+                let res = event_stream_response(
+                    res,
+                    self.sse_config.keepalive(),
+                    trigger_config
+                        .write_idle_timeout_secs
+                        .map(Duration::from_secs),
+                );
+                let res = if trigger_config.compress_response {
+                    compress_response(res, gzip_accepted, trigger_config.min_compress_size_bytes)
+                } else {
+                    res
+                };
+                MatchedRoute::with_response_extension(res, route_match.raw_route())
+            }
+            Err(err) => {
+                let category = ErrorCategory::classify(&err);
+                tracing::error!("Error processing request {request_id} ({category:?}): {err:?}");
+                instrument_error(&err);
+                let mut res = self.error_responses.response(&err, &request_id)?;
+                if let (Some(handler), Some(req)) =
+                    (&self.error_handlers.internal_error, error_request)
+                {
+                    if res.status() == StatusCode::INTERNAL_SERVER_ERROR {
+                        res = self
+                            .handle_error(handler, req, res.status(), client_addr)
+                            .await
+                            .unwrap_or(res);
+                    }
+                }
+                MatchedRoute::with_response_extension(res, route_match.raw_route())
+            }
+        };
+        if injected_id.is_some() {
+            res.extensions_mut().insert(RequestId(request_id));
+        }
+        Ok(res)
+    }
+
+    /// Prepares an instance of the component, setting up outbound HTTP
+    /// requests from it. Any `request_id` is passed on to chained components.
+    fn prepare_instance(
+        self: &Arc<Self>,
+        component_id: &str,
+        server_scheme: Scheme,
+        request_id: Option<HeaderValue>,
+    ) -> anyhow::Result<TriggerInstanceBuilder<'_, F>> {
         let mut instance_builder = self.trigger_app.prepare(component_id)?;
 
         // Set up outbound HTTP request origin and service chaining
@@ -304,33 +660,46 @@ impl<F: RuntimeFactors> HttpServer<F> {
             .context(
             "The wasi HTTP trigger was configured without the required wasi outbound http support",
         )?;
-        let origin = SelfRequestOrigin::create(server_scheme, &self.listen_addr.to_string())?;
+        let origin =
+            SelfRequestOrigin::create(server_scheme, &self.self_request_addr().to_string())?;
         outbound_http.set_self_request_origin(origin);
-        outbound_http.set_request_interceptor(OutboundHttpInterceptor::new(self.clone()))?;
+        let mut interceptor = OutboundHttpInterceptor::new(self.clone());
+        if let Some(request_id) = request_id {
+            interceptor = interceptor.with_request_id(request_id);
+        }
+        outbound_http.set_request_interceptor(interceptor)?;
+        Ok(instance_builder)
+    }
 
-        // Prepare HTTP executor
-        let trigger_config = self
-            .component_trigger_configs
-            .get(component_id)
-            .with_context(|| format!("unknown component ID {component_id:?}"))?;
+    /// Runs the request through the component with the executor its trigger
+    /// config requires.
+    async fn execute(
+        &self,
+        instance_builder: TriggerInstanceBuilder<'_, F>,
+        trigger_config: &HttpTriggerConfig,
+        route_match: &RouteMatch<'_, '_>,
+        req: Request<Body>,
+        client_addr: SocketAddr,
+    ) -> anyhow::Result<Response<Body>> {
+        let component_id = route_match.component_id();
         let handler_type = self.component_handler_types.get(component_id).unwrap();
         let executor = trigger_config
             .executor
             .as_ref()
             .unwrap_or(&HttpExecutorType::Http);
 
-        let res = match executor {
+        match executor {
             HttpExecutorType::Http => match handler_type {
                 HandlerType::Spin => {
                     SpinHttpExecutor
-                        .execute(instance_builder, &route_match, req, client_addr)
+                        .execute(instance_builder, route_match, req, client_addr)
                         .await
                 }
                 HandlerType::Wasi0_2(_)
                 | HandlerType::Wasi2023_11_10(_)
                 | HandlerType::Wasi2023_10_18(_) => {
                     WasiHttpExecutor { handler_type }
-                        .execute(instance_builder, &route_match, req, client_addr)
+                        .execute(instance_builder, route_match, req, client_addr)
                         .await
                 }
                 HandlerType::Wagi(_) => unreachable!(),
@@ -345,23 +714,187 @@ impl<F: RuntimeFactors> HttpServer<F> {
                     indices,
                 };
                 executor
-                    .execute(instance_builder, &route_match, req, client_addr)
+                    .execute(instance_builder, route_match, req, client_addr)
                     .await
             }
-        };
-        match res {
-            Ok(res) => Ok(MatchedRoute::with_response_extension(
-                res,
-                route_match.raw_route(),
-            )),
+        }
+    }
+
+    /// Passes a request which would get a built-in `status` response to the
+    /// app's error handler component, telling it the status in the
+    /// `x-spin-error-code` header.
+    ///
+    /// Returns `None` if the handler fails, so that the built-in response can
+    /// be sent instead. Handlers which respond with a success status have it
+    /// replaced with `status`.
+    async fn handle_error(
+        self: &Arc<Self>,
+        component_id: &str,
+        mut req: Request<Body>,
+        status: StatusCode,
+        client_addr: SocketAddr,
+    ) -> Option<Response<Body>> {
+        req.headers_mut()
+            .insert(ERROR_CODE_HEADER, HeaderValue::from(status.as_u16()));
+        let server_scheme = req.uri().scheme().cloned().unwrap_or(Scheme::HTTP);
+        let route_match = RouteMatch::synthetic(component_id.to_owned(), req.uri().path().into());
+        let result = async {
+            let trigger_config = self
+                .component_trigger_configs
+                .get(component_id)
+                .with_context(|| format!("unknown component ID {component_id:?}"))?;
+            let request_id = if trigger_config.inject_request_id {
+                Some(inject_request_id(&mut req)?)
+            } else {
+                None
+            };
+            let instance_builder =
+                self.prepare_instance(component_id, server_scheme, request_id)?;
+            self.execute(
+                instance_builder,
+                trigger_config,
+                &route_match,
+                req,
+                client_addr,
+            )
+            .await
+        }
+        .await;
+        match result {
+            Ok(mut res) => {
+                if res.status().is_success() {
+                    *res.status_mut() = status;
+                }
+                Some(res)
+            }
             Err(err) => {
-                tracing::error!("Error processing request: {err:?}");
+                tracing::error!(
+                    "Error handler component {component_id:?} failed to handle a {status} response: {err:?}"
+                );
                 instrument_error(&err);
-                Self::internal_error(None, route_match.raw_route())
+                None
             }
         }
     }
 
+    /// Passes a copy of a request to a route's shadow component. The response
+    /// is for the shadow's own bookkeeping only, and never reaches the client.
+    async fn execute_shadow(
+        self: &Arc<Self>,
+        component_id: &str,
+        mut req: Request<Body>,
+        client_addr: SocketAddr,
+    ) -> anyhow::Result<Response<Body>> {
+        let server_scheme = req.uri().scheme().cloned().unwrap_or(Scheme::HTTP);
+        let route_match = RouteMatch::synthetic(component_id.to_owned(), req.uri().path().into());
+        let trigger_config = self
+            .component_trigger_configs
+            .get(component_id)
+            .with_context(|| format!("unknown component ID {component_id:?}"))?;
+        let request_id = if trigger_config.inject_request_id {
+            Some(inject_request_id(&mut req)?)
+        } else {
+            None
+        };
+        let instance_builder = self.prepare_instance(component_id, server_scheme, request_id)?;
+        self.execute(
+            instance_builder,
+            trigger_config,
+            &route_match,
+            req,
+            client_addr,
+        )
+        .await
+    }
+
+    /// Handles a request chained from one of the app's components to a
+    /// component of another app loaded with the same executor, as the
+    /// executor's chaining policy allows.
+    pub(crate) async fn handle_cross_app_chained(
+        &self,
+        target_app: &str,
+        component_id: &str,
+        req: Request<Body>,
+        client_addr: SocketAddr,
+    ) -> Result<anyhow::Result<Response<Body>>, ChainingError> {
+        self.trigger_app
+            .dispatch_chained(target_app, component_id, |app| async move {
+                let server = self.chained_server(app)?;
+                let path = req.uri().path().to_owned();
+                let route_match = RouteMatch::synthetic(component_id.to_owned(), path);
+                server
+                    .handle_trigger_route(req, route_match, Scheme::HTTP, client_addr)
+                    .await
+            })
+            .await
+    }
+
+    /// Returns the server for requests chained to `trigger_app`, creating it
+    /// with [`Self::for_chained_app`] the first time the app is chained to.
+    ///
+    /// Servers are rebuilt when their app is reloaded under the same name,
+    /// and dropped once it is unloaded.
+    fn chained_server(&self, trigger_app: TriggerApp<F>) -> anyhow::Result<Arc<Self>> {
+        let mut servers = self.chained_servers.lock().unwrap();
+        servers.retain(|_, server| !server.trigger_app.is_unloaded());
+        if let Some(server) = servers.get(trigger_app.name()) {
+            if server.trigger_app.is_same_app(&trigger_app) {
+                return Ok(server.clone());
+            }
+        }
+        let name = trigger_app.name().to_owned();
+        let server = Arc::new(Self::for_chained_app(trigger_app, self.spill_dir.clone())?);
+        servers.insert(name, server.clone());
+        Ok(server)
+    }
+
+    /// Creates a server for requests chained to `trigger_app` from another
+    /// app, configured by the app's own manifest.
+    ///
+    /// The server doesn't listen for requests itself, but requests its
+    /// components make to their own app go to the addresses in the manifest.
+    fn for_chained_app(trigger_app: TriggerApp<F>, spill_dir: PathBuf) -> anyhow::Result<Self> {
+        let app = trigger_app.app();
+        let listen_addrs = HttpTrigger::manifest_listen_addrs(app)?;
+        let error_responses = HttpTrigger::manifest_error_responses(app)?;
+        let error_handlers = HttpTrigger::manifest_error_handlers(app)?;
+        let sse_config = HttpTrigger::manifest_sse_config(app)?;
+        Self::new(
+            listen_addrs,
+            None,
+            false,
+            Default::default(),
+            error_responses,
+            error_handlers,
+            sse_config,
+            None,
+            spill_dir,
+            trigger_app,
+        )
+    }
+
+    /// Reports the app healthy unless a background task has failed.
+    fn health(&self, route: String) -> anyhow::Result<Response<Body>> {
+        let failed = self
+            .trigger_app
+            .background_task_statuses()
+            .into_iter()
+            .filter(|status| status.state == BackgroundTaskState::Failed)
+            .map(|status| {
+                let error = status.last_error.unwrap_or_default();
+                format!("background task {:?} failed: {error}", status.name)
+            })
+            .collect::<Vec<_>>();
+        let response = if failed.is_empty() {
+            Response::new(body::full(Bytes::from_static(b"OK")))
+        } else {
+            Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(body::full(failed.join("\n").into()))?
+        };
+        Ok(MatchedRoute::with_response_extension(response, route))
+    }
+
     /// Returns spin status information.
     fn app_info(&self, route: String) -> anyhow::Result<Response<Body>> {
         let info = AppInfo::new(self.trigger_app.app());
@@ -441,6 +974,10 @@ impl<F: RuntimeFactors> HttpServer<F> {
     ) -> anyhow::Result<Response<HyperOutgoingBody>> {
         let span = http_span!(request, client_addr);
         let method = request.method().to_string();
+        let access_log_entry = self
+            .access_log
+            .clone()
+            .map(|writer| PendingAccessLogEntry::new(writer, &request, client_addr));
         async {
             let result = self
                 .handle(
@@ -452,17 +989,32 @@ impl<F: RuntimeFactors> HttpServer<F> {
                     client_addr,
                 )
                 .await;
+            let result = match access_log_entry {
+                Some(entry) => entry.finish(result),
+                None => result,
+            };
             finalize_http_span(result, method)
         }
         .instrument(span)
         .await
     }
 
-    fn print_startup_msgs(&self, scheme: &str, listener: &TcpListener) -> anyhow::Result<()> {
-        let local_addr = listener.local_addr()?;
-        let base_url = format!("{scheme}://{local_addr:?}");
-        terminal::step!("\nServing", "{base_url}");
-        tracing::info!("Serving {base_url}");
+    fn print_startup_msgs(
+        &self,
+        scheme: &Scheme,
+        local_addrs: &[SocketAddr],
+    ) -> anyhow::Result<()> {
+        let base_urls: Vec<String> = local_addrs
+            .iter()
+            .map(|local_addr| format!("{scheme}://{local_addr:?}"))
+            .collect();
+        println!();
+        for base_url in &base_urls {
+            terminal::step!("Serving", "{base_url}");
+            tracing::info!("Serving {base_url}");
+        }
+        let base_url = base_urls.first().context("no addresses to serve on")?;
+        tracing::info!("Serving {} routes", self.router.len());
 
         println!("Available Routes:");
         for (route, component_id) in self.router.routes() {
@@ -477,6 +1029,42 @@ impl<F: RuntimeFactors> HttpServer<F> {
     }
 }
 
+/// Picks the primary address of a server listening on the given addresses,
+/// preferring IPv4 (see `parse_listen_addr`), with unspecified addresses
+/// replaced by loopback so that they can be connected to.
+fn primary_addr(addrs: &[SocketAddr]) -> Option<SocketAddr> {
+    let mut addr = *addrs
+        .iter()
+        .find(|addr| addr.is_ipv4())
+        .or_else(|| addrs.first())?;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr.ip() {
+            IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    Some(addr)
+}
+
+/// The host name the incoming request is addressed to, without any port,
+/// taken from the request URI or else the `Host` header.
+///
+/// This is used only for routing; an ambiguous or invalid authority is
+/// rejected later by `set_req_uri`.
+fn request_host<B>(req: &Request<B>) -> Option<String> {
+    if let Some(host) = req.uri().host() {
+        return Some(host.to_owned());
+    }
+    let authority: Authority = req
+        .headers()
+        .get(http::header::HOST)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(authority.host().to_owned())
+}
+
 /// The incoming request's scheme and authority
 ///
 /// The incoming request's URI is relative to the server, so we need to set the scheme and authority.
@@ -519,6 +1107,27 @@ fn set_req_uri(req: &mut Request<Body>, scheme: Scheme) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Copies the method, URI, version and headers of a request, with an empty
+/// body.
+fn request_head<B>(req: &Request<B>) -> Request<Body> {
+    let mut head = Request::new(body::empty());
+    *head.method_mut() = req.method().clone();
+    *head.uri_mut() = req.uri().clone();
+    *head.version_mut() = req.version();
+    *head.headers_mut() = req.headers().clone();
+    head
+}
+
+/// Sets the request's `x-request-id` header to its ID, keeping the client's
+/// ID if it sent one, and records the ID on the current span.
+fn inject_request_id<B>(req: &mut Request<B>) -> anyhow::Result<HeaderValue> {
+    let request_id = request_id(req);
+    tracing::Span::current().record("spin.request_id", request_id.as_str());
+    let value = HeaderValue::from_str(&request_id)?;
+    req.headers_mut().insert(REQUEST_ID_HEADER, value.clone());
+    Ok(value)
+}
+
 /// An HTTP executor.
 pub(crate) trait HttpExecutor {
     fn execute<F: RuntimeFactors>(
@@ -529,3 +1138,448 @@ pub(crate) trait HttpExecutor {
         client_addr: SocketAddr,
     ) -> impl Future<Output = anyhow::Result<Response<Body>>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use spin_app::{App, AppComponent};
+    use spin_core::{async_trait, Component};
+    use spin_factor_outbound_http::intercept::{InterceptOutcome, OutboundHttpInterceptor as _};
+    use spin_factor_outbound_networking::OutboundNetworkingFactor;
+    use spin_factor_variables::VariablesFactor;
+    use spin_factors_executor::{ChainingPolicy, ComponentLoader, ExecutorHooks, FactorsExecutor};
+    use wasmtime_wasi_http::{bindings::http::types::ErrorCode, HttpResult};
+
+    use super::*;
+
+    #[derive(RuntimeFactors)]
+    struct TestFactors {
+        variables: VariablesFactor,
+        networking: OutboundNetworkingFactor,
+        http: OutboundHttpFactor,
+    }
+
+    /// Loads components which export `fermyon:spin/inbound-http`, and respond
+    /// to `GET` requests with `200 OK` and their component ID as the body, and
+    /// to any other method with `405 Method Not Allowed`.
+    struct ResponderLoader;
+
+    #[async_trait]
+    impl ComponentLoader<TestFactors, ()> for ResponderLoader {
+        async fn load_component(
+            &self,
+            engine: &spin_core::wasmtime::Engine,
+            component: &AppComponent,
+        ) -> anyhow::Result<Component> {
+            Component::new(engine, responder_wat(component.id()))
+        }
+    }
+
+    fn responder_wat(body: &str) -> String {
+        let len = (body.len() as u32)
+            .to_le_bytes()
+            .map(|b| format!("\\{b:02x}"))
+            .concat();
+        format!(
+            r#"(component
+                (core module $m
+                    (memory (export "memory") 1)
+                    (global $heap (mut i32) (i32.const 1024))
+                    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                        (local $ptr i32)
+                        (local.set $ptr
+                            (i32.and (i32.add (global.get $heap) (i32.const 7)) (i32.const -8)))
+                        (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+                        (local.get $ptr))
+                    (func (export "handle-request")
+                        (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32) (result i32)
+                        ;; the first parameter is the method, where 0 is GET
+                        (select (i32.const 16) (i32.const 128) (i32.eqz (local.get 0))))
+                    (data (i32.const 16)
+                        ;; status: 200
+                        "\c8\00\00\00"
+                        ;; headers: none
+                        "\00\00\00\00\00\00\00\00\00\00\00\00"
+                        ;; body: some, at 64
+                        "\01\00\00\00\40\00\00\00" "{len}")
+                    (data (i32.const 64) "{body}")
+                    (data (i32.const 128)
+                        ;; status: 405
+                        "\95\01\00\00"
+                        ;; headers: none
+                        "\00\00\00\00\00\00\00\00\00\00\00\00"
+                        ;; body: none
+                        "\00\00\00\00\00\00\00\00\00\00\00\00"))
+                (core instance $i (instantiate $m))
+                (type $method' (enum "get" "post" "put" "delete" "patch" "head" "options"))
+                (export $method "method" (type $method'))
+                (type $fields (list (tuple string string)))
+                (type $request' (record
+                    (field "method" $method)
+                    (field "uri" string)
+                    (field "headers" $fields)
+                    (field "params" $fields)
+                    (field "body" (option (list u8)))))
+                (export $request "request" (type $request'))
+                (type $response' (record
+                    (field "status" u16)
+                    (field "headers" (option $fields))
+                    (field "body" (option (list u8)))))
+                (export $response "response" (type $response'))
+                (func $handle (param "req" $request) (result $response)
+                    (canon lift (core func $i "handle-request")
+                        (memory $i "memory") (realloc (func $i "realloc"))))
+                (instance $inbound
+                    (export "method" (type $method))
+                    (export "request" (type $request))
+                    (export "response" (type $response))
+                    (export "handle-request" (func $handle)))
+                (export "fermyon:spin/inbound-http" (instance $inbound))
+            )"#
+        )
+    }
+
+    /// Counts the instances created by an executor.
+    struct InstantiationCounter(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl ExecutorHooks<TestFactors, ()> for InstantiationCounter {
+        async fn post_instantiate(
+            &self,
+            component_id: &str,
+            result: &anyhow::Result<()>,
+        ) -> anyhow::Result<()> {
+            let _ = (component_id, result);
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn new_executor() -> anyhow::Result<FactorsExecutor<TestFactors, ()>> {
+        let factors = TestFactors {
+            variables: VariablesFactor::default(),
+            networking: OutboundNetworkingFactor::new(),
+            http: OutboundHttpFactor::default(),
+        };
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        FactorsExecutor::new(engine_builder, factors)
+    }
+
+    fn test_executor(
+        policy: ChainingPolicy,
+    ) -> anyhow::Result<Arc<FactorsExecutor<TestFactors, ()>>> {
+        let mut executor = new_executor()?;
+        executor.set_chaining_policy(policy);
+        Ok(Arc::new(executor))
+    }
+
+    /// Returns an executor along with the count of the instances it creates.
+    fn counting_executor(
+    ) -> anyhow::Result<(Arc<FactorsExecutor<TestFactors, ()>>, Arc<AtomicUsize>)> {
+        let instantiations = Arc::new(AtomicUsize::new(0));
+        let mut executor = new_executor()?;
+        executor.add_hooks(InstantiationCounter(instantiations.clone()));
+        Ok((Arc::new(executor), instantiations))
+    }
+
+    /// Returns the manifest of an app with the given name, whose components
+    /// each have an HTTP trigger on `/<component ID>`, followed by `extra`.
+    fn app_manifest(name: &str, component_ids: &[&str], extra: &str) -> String {
+        let mut manifest = format!("spin_manifest_version = 2\n[application]\nname = {name:?}\n");
+        for id in component_ids {
+            manifest.push_str(&format!(
+                "[[trigger.http]]\nroute = \"/{id}\"\ncomponent = {id:?}\n{extra}\n\
+                 [component.{id}]\nsource = \"does-not-exist.wasm\"\n"
+            ));
+        }
+        manifest
+    }
+
+    /// Loads the app with the given manifest with `executor`, and creates a
+    /// server for it.
+    async fn test_server(
+        executor: &Arc<FactorsExecutor<TestFactors, ()>>,
+        manifest: &str,
+    ) -> anyhow::Result<Arc<HttpServer<TestFactors>>> {
+        let locked =
+            spin_factors_test::build_locked_app(&toml::from_str::<toml::Table>(manifest)?).await?;
+        let app = App::new("test-app", locked);
+        let trigger_app = executor
+            .clone()
+            .load_app(app, Default::default(), &ResponderLoader)
+            .await?;
+        Ok(Arc::new(HttpServer::new(
+            vec![],
+            None,
+            false,
+            Default::default(),
+            ErrorResponses::default(),
+            ErrorHandlers::default(),
+            SseConfig::default(),
+            None,
+            std::env::temp_dir(),
+            trigger_app,
+        )?))
+    }
+
+    /// Sends a request to `url` from a component of the server's app.
+    async fn outbound_request(
+        server: &Arc<HttpServer<TestFactors>>,
+        url: &str,
+    ) -> HttpResult<Response<Body>> {
+        let req = Request::get(url).body(Vec::new()).unwrap();
+        match OutboundHttpInterceptor::new(server.clone())
+            .intercept(req.into())
+            .await?
+        {
+            InterceptOutcome::Complete(res) => Ok(res),
+            InterceptOutcome::Continue(_) => panic!("request to {url} was not chained"),
+        }
+    }
+
+    /// Sends a request to the server as if from a client.
+    async fn inbound_request(
+        server: &Arc<HttpServer<TestFactors>>,
+        req: Request<Body>,
+    ) -> anyhow::Result<Response<Body>> {
+        let client_addr = "127.0.0.1:54321".parse().unwrap();
+        server.handle(req, Scheme::HTTP, client_addr).await
+    }
+
+    async fn body_text(res: Response<Body>) -> String {
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn unauthorized_requests_are_rejected_before_instantiation() -> anyhow::Result<()> {
+        let (executor, instantiations) = counting_executor()?;
+        let mut manifest = app_manifest(
+            "app",
+            &["admin"],
+            r#"auth = { type = "bearer", token_variable = "admin_token" }"#,
+        );
+        manifest.push_str("[variables]\nadmin_token = { default = \"s3cret\" }\n");
+        let server = test_server(&executor, &manifest).await?;
+
+        for authorization in [None, Some("Bearer wrong"), Some("Basic czNjcmV0")] {
+            let mut req = Request::get("http://localhost:3000/admin");
+            if let Some(authorization) = authorization {
+                req = req.header(http::header::AUTHORIZATION, authorization);
+            }
+            let res = inbound_request(&server, req.body(body::empty())?).await?;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{authorization:?}");
+            assert!(res.headers().contains_key(http::header::WWW_AUTHENTICATE));
+        }
+        assert_eq!(instantiations.load(Ordering::SeqCst), 0);
+
+        // Authorized requests are instantiated, and counted
+        let req = Request::get("http://localhost:3000/admin")
+            .header(http::header::AUTHORIZATION, "Bearer s3cret")
+            .body(body::empty())?;
+        let res = inbound_request(&server, req).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body_text(res).await, "admin");
+        assert_eq!(instantiations.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn options_requests_answered_by_the_trigger_skip_auth() -> anyhow::Result<()> {
+        let (executor, instantiations) = counting_executor()?;
+        let auth = r#"auth = { type = "bearer", token_variable = "admin_token" }"#;
+        let variables = "[variables]\nadmin_token = { default = \"s3cret\" }\n";
+        let extra = format!("methods = [\"GET\"]\n{auth}");
+        let mut manifest = app_manifest("app", &["admin"], &extra);
+        manifest.push_str(variables);
+        let server = test_server(&executor, &manifest).await?;
+
+        // A CORS preflight request carries no credentials
+        let req = Request::options("http://localhost:3000/admin")
+            .header(http::header::ORIGIN, "https://example.com")
+            .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(body::empty())?;
+        let res = inbound_request(&server, req).await?;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()[http::header::ALLOW], "GET, HEAD, OPTIONS");
+
+        let req = Request::get("http://localhost:3000/admin").body(body::empty())?;
+        let res = inbound_request(&server, req).await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        // The component answers `OPTIONS` itself if it doesn't declare its
+        // methods, so the request must be authorized
+        let mut manifest = app_manifest("app", &["admin"], auth);
+        manifest.push_str(variables);
+        let server = test_server(&executor, &manifest).await?;
+        let req = Request::options("http://localhost:3000/admin").body(body::empty())?;
+        let res = inbound_request(&server, req).await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        assert_eq!(instantiations.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn head_requests_are_answered_by_get_only_components() -> anyhow::Result<()> {
+        let executor = Arc::new(new_executor()?);
+        let manifest = app_manifest("app", &["page"], r#"methods = ["GET"]"#);
+        let server = test_server(&executor, &manifest).await?;
+
+        let req = Request::head("http://localhost:3000/page").body(body::empty())?;
+        let res = inbound_request(&server, req).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        // The length of the body of the component's response to the `GET`
+        assert_eq!(res.headers()[http::header::CONTENT_LENGTH], "4");
+        assert_eq!(body_text(res).await, "");
+
+        // Without `auto_head`, the component is sent the `HEAD`
+        let manifest = app_manifest("app", &["page"], "methods = [\"GET\"]\nauto_head = false");
+        let server = test_server(&executor, &manifest).await?;
+        let req = Request::head("http://localhost:3000/page").body(body::empty())?;
+        let res = inbound_request(&server, req).await?;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cross_app_chaining_follows_policy() -> anyhow::Result<()> {
+        let policy = ChainingPolicy::default()
+            .allow_component("frontend", "backend", "api")
+            .allow_app("frontend", "billing");
+        let executor = test_executor(policy)?;
+        let frontend = test_server(&executor, &app_manifest("frontend", &["home"], "")).await?;
+        let backend =
+            test_server(&executor, &app_manifest("backend", &["api", "admin"], "")).await?;
+
+        let res = outbound_request(&frontend, "http://api.backend.spin.internal/orders")
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body_text(res).await, "api");
+
+        // Within an app, chaining is unaffected by the policy
+        let res = outbound_request(&backend, "http://admin.spin.internal/")
+            .await
+            .unwrap();
+        assert_eq!(body_text(res).await, "admin");
+
+        for (server, url) in [
+            (&frontend, "http://admin.backend.spin.internal/"),
+            (&backend, "http://home.frontend.spin.internal/"),
+        ] {
+            let Err(err) = outbound_request(server, url).await else {
+                panic!("request to {url} was not denied");
+            };
+            assert!(
+                matches!(err.downcast()?, ErrorCode::HttpRequestDenied),
+                "request to {url} failed with the wrong error"
+            );
+        }
+
+        // Allowed, but not loaded
+        let Err(err) = outbound_request(&frontend, "http://api.billing.spin.internal/").await
+        else {
+            panic!("request to an app which isn't loaded succeeded");
+        };
+        assert!(matches!(err.downcast()?, ErrorCode::DestinationNotFound));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cross_app_chained_servers_are_reused_until_reload() -> anyhow::Result<()> {
+        let policy = ChainingPolicy::default().allow_app("frontend", "backend");
+        let executor = test_executor(policy)?;
+        let frontend = test_server(&executor, &app_manifest("frontend", &["home"], "")).await?;
+        let backend = test_server(&executor, &app_manifest("backend", &["api"], "")).await?;
+        let chained_server = || frontend.chained_servers.lock().unwrap()["backend"].clone();
+
+        outbound_request(&frontend, "http://api.backend.spin.internal/")
+            .await
+            .unwrap();
+        let first = chained_server();
+        outbound_request(&frontend, "http://api.backend.spin.internal/")
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &chained_server()));
+
+        // A reloaded app gets a new server
+        drop(backend);
+        let _backend = test_server(&executor, &app_manifest("backend", &["api"], "")).await?;
+        outbound_request(&frontend, "http://api.backend.spin.internal/")
+            .await
+            .unwrap();
+        assert!(!Arc::ptr_eq(&first, &chained_server()));
+        assert_eq!(frontend.chained_servers.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    fn addrs(addrs: &[&str]) -> Vec<SocketAddr> {
+        addrs.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn request_host_ignores_port() {
+        let req = Request::get("/foo")
+            .header(http::header::HOST, "API.example.com:3000")
+            .body(())
+            .unwrap();
+        assert_eq!(request_host(&req).as_deref(), Some("API.example.com"));
+
+        let req = Request::get("http://example.com/foo").body(()).unwrap();
+        assert_eq!(request_host(&req).as_deref(), Some("example.com"));
+
+        let req = Request::get("/foo").body(()).unwrap();
+        assert_eq!(request_host(&req), None);
+    }
+
+    #[tokio::test]
+    async fn request_head_copies_all_but_body() {
+        let req = Request::post("http://example.com/orders?page=2")
+            .header("x-custom", "value")
+            .body(body::full(Bytes::from_static(b"payload")))
+            .unwrap();
+        let head = request_head(&req);
+        assert_eq!(head.method(), Method::POST);
+        assert_eq!(head.uri(), req.uri());
+        assert_eq!(head.headers()["x-custom"], "value");
+        let body = head.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn inject_request_id_keeps_client_id() {
+        let mut req = Request::get("/")
+            .header("x-request-id", "abc")
+            .body(())
+            .unwrap();
+        assert_eq!(inject_request_id(&mut req).unwrap(), "abc");
+        assert_eq!(req.headers()["x-request-id"], "abc");
+
+        let mut req = Request::get("/").body(()).unwrap();
+        let id = inject_request_id(&mut req).unwrap();
+        assert!(uuid::Uuid::parse_str(id.to_str().unwrap()).is_ok());
+        assert_eq!(req.headers()["x-request-id"], id);
+    }
+
+    #[test]
+    fn primary_addr_prefers_ipv4() {
+        let primary = primary_addr(&addrs(&["[::1]:3001", "127.0.0.1:3000"]));
+        assert_eq!(primary, Some("127.0.0.1:3000".parse().unwrap()));
+
+        let primary = primary_addr(&addrs(&["[::1]:3001", "[::2]:3000"]));
+        assert_eq!(primary, Some("[::1]:3001".parse().unwrap()));
+
+        assert_eq!(primary_addr(&[]), None);
+    }
+
+    #[test]
+    fn primary_addr_replaces_unspecified_with_loopback() {
+        let primary = primary_addr(&addrs(&["0.0.0.0:3000"]));
+        assert_eq!(primary, Some("127.0.0.1:3000".parse().unwrap()));
+
+        let primary = primary_addr(&addrs(&["[::]:3000"]));
+        assert_eq!(primary, Some("[::1]:3000".parse().unwrap()));
+    }
+}
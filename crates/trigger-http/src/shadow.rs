@@ -0,0 +1,356 @@
+//! Shadowing of a sample of a route's requests to a second component, for
+//! routes with a `shadow` config.
+//!
+//! The route's component is passed the request as usual, with its body
+//! copied as the component reads it. Once the whole body has been read, the
+//! copy is sent to the shadow component in the background, and its response
+//! is discarded, so the shadow never affects the response to the client.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+
+use http::{Request, Response, StatusCode};
+use http_body_util::BodyExt;
+use hyper::body::{Body as _, Bytes, Frame, SizeHint};
+use rand::Rng;
+use spin_http::body;
+use tokio::sync::oneshot;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// Whether a request is one of the `percent` of requests which are shadowed.
+pub(crate) fn sample(percent: f64, rng: &mut impl Rng) -> bool {
+    rng.random::<f64>() * 100.0 < percent
+}
+
+/// Splits a request into the request to pass to the route's component, whose
+/// body is copied as it is read, and the copy for the shadow component.
+///
+/// Bodies of more than `max_body_bytes` are not copied, and neither are
+/// bodies which the route's component doesn't read in full.
+pub(crate) fn tee_request(
+    req: Request<Body>,
+    max_body_bytes: usize,
+) -> (Request<Body>, ShadowRequest) {
+    let (parts, body) = req.into_parts();
+    let mut head = Request::new(());
+    *head.method_mut() = parts.method.clone();
+    *head.uri_mut() = parts.uri.clone();
+    *head.version_mut() = parts.version;
+    *head.headers_mut() = parts.headers.clone();
+
+    let (done, body_copy) = oneshot::channel();
+    let mut tee = TeeBody {
+        inner: body,
+        copy: Vec::new(),
+        max_bytes: max_body_bytes,
+        done: Some(done),
+    };
+    // Components need not read a body which is already known to be empty
+    if tee.inner.is_end_stream() {
+        tee.finish();
+    }
+    (
+        Request::from_parts(parts, tee.boxed()),
+        ShadowRequest { head, body_copy },
+    )
+}
+
+/// A copy of a request, to send to the shadow component.
+pub(crate) struct ShadowRequest {
+    head: Request<()>,
+    body_copy: oneshot::Receiver<Bytes>,
+}
+
+impl ShadowRequest {
+    /// Waits for the route's component to read the body, returning `None` if
+    /// the body was not copied.
+    async fn into_request(self) -> Option<Request<Body>> {
+        let body = self.body_copy.await.ok()?;
+        let (parts, ()) = self.head.into_parts();
+        Some(Request::from_parts(parts, body::full(body)))
+    }
+}
+
+/// What became of a shadowed request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ShadowOutcome {
+    /// The body was not copied, so the request was not sent.
+    Skipped,
+    /// The shadow component responded with the given status.
+    Responded(StatusCode),
+    /// The shadow component failed.
+    Failed,
+    /// The shadow component didn't respond in full within the timeout.
+    TimedOut,
+}
+
+impl ShadowOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Skipped => "skipped",
+            Self::Responded(_) => "responded",
+            Self::Failed => "failed",
+            Self::TimedOut => "timed_out",
+        }
+    }
+}
+
+/// Sends a copy of a request to the shadow component using `dispatch`, once
+/// the route's component has read the body, and discards the response.
+///
+/// The outcome is recorded on the current span, which should have
+/// `http.response.status_code`, `shadow.outcome` and `shadow.duration_ms`
+/// fields, and in metrics.
+pub(crate) async fn run_shadow<Fut>(
+    shadow: ShadowRequest,
+    component_id: &str,
+    timeout: Duration,
+    dispatch: impl FnOnce(Request<Body>) -> Fut,
+) -> ShadowOutcome
+where
+    Fut: Future<Output = anyhow::Result<Response<Body>>>,
+{
+    let span = tracing::Span::current();
+    let Some(req) = shadow.into_request().await else {
+        tracing::debug!("Not shadowing request to {component_id}: the body was not copied");
+        record_outcome(&span, component_id, ShadowOutcome::Skipped);
+        return ShadowOutcome::Skipped;
+    };
+
+    let started = Instant::now();
+    // The response is read in full, so that the duration covers all the
+    // shadow component's work
+    let response = async {
+        let res = dispatch(req).await?;
+        let status = res.status();
+        res.into_body().collect().await?;
+        anyhow::Ok(status)
+    };
+    let outcome = match tokio::time::timeout(timeout, response).await {
+        Ok(Ok(status)) => ShadowOutcome::Responded(status),
+        Ok(Err(err)) => {
+            tracing::info!("Shadow component {component_id} failed: {err:?}");
+            ShadowOutcome::Failed
+        }
+        Err(_) => {
+            tracing::info!("Shadow component {component_id} timed out after {timeout:?}");
+            ShadowOutcome::TimedOut
+        }
+    };
+    let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+    span.record("shadow.duration_ms", duration_ms);
+    spin_telemetry::metrics::histogram!(
+        spin.http_shadow_duration_ms = duration_ms,
+        component_id = component_id,
+        outcome = outcome.as_str()
+    );
+    record_outcome(&span, component_id, outcome);
+    outcome
+}
+
+fn record_outcome(span: &tracing::Span, component_id: &str, outcome: ShadowOutcome) {
+    span.record("shadow.outcome", outcome.as_str());
+    if let ShadowOutcome::Responded(status) = outcome {
+        span.record("http.response.status_code", status.as_u16());
+    }
+    spin_telemetry::metrics::monotonic_counter!(
+        spin.http_shadow_request_count = 1,
+        component_id = component_id,
+        outcome = outcome.as_str()
+    );
+}
+
+/// A request body which copies the data read from it, up to a limit, and
+/// sends the copy once the whole body has been read.
+struct TeeBody {
+    inner: Body,
+    copy: Vec<u8>,
+    max_bytes: usize,
+    /// Receives the copy, unless copying has been abandoned
+    done: Option<oneshot::Sender<Bytes>>,
+}
+
+impl TeeBody {
+    fn finish(&mut self) {
+        if let Some(done) = self.done.take() {
+            _ = done.send(std::mem::take(&mut self.copy).into());
+        }
+    }
+
+    fn abandon(&mut self) {
+        self.done = None;
+        self.copy = Vec::new();
+    }
+}
+
+impl hyper::body::Body for TeeBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref().filter(|_| this.done.is_some()) {
+                    if this.copy.len() + data.len() > this.max_bytes {
+                        this.abandon();
+                    } else {
+                        this.copy.extend_from_slice(data);
+                    }
+                }
+                // Readers may stop at the last data frame, without waiting
+                // for the end of the stream
+                if this.inner.is_end_stream() {
+                    this.finish();
+                }
+            }
+            Some(Err(_)) => this.abandon(),
+            None => this.finish(),
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::stream;
+    use http_body_util::StreamBody;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn streamed_body(chunks: &[&'static [u8]]) -> Body {
+        let frames = chunks
+            .iter()
+            .map(|chunk| Ok::<_, ErrorCode>(Frame::data(Bytes::from_static(chunk))))
+            .collect::<Vec<_>>();
+        StreamBody::new(stream::iter(frames)).boxed()
+    }
+
+    fn request(chunks: &[&'static [u8]]) -> Request<Body> {
+        Request::post("http://example.com/checkout?cart=1")
+            .header("x-custom", "value")
+            .body(streamed_body(chunks))
+            .unwrap()
+    }
+
+    /// A stand-in for the route's component, which echoes the request.
+    async fn echo(req: Request<Body>) -> Response<Bytes> {
+        let (parts, body) = req.into_parts();
+        let body = body.collect().await.unwrap().to_bytes();
+        let mut echoed = format!("{} {}\n", parts.method, parts.uri).into_bytes();
+        for (name, value) in &parts.headers {
+            echoed.extend_from_slice(format!("{name}: {value:?}\n").as_bytes());
+        }
+        echoed.extend_from_slice(&body);
+        Response::new(echoed.into())
+    }
+
+    #[test]
+    fn sampling_respects_the_percentage() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2460);
+        for percent in [5.0, 50.0] {
+            let sampled = (0..100_000).filter(|_| sample(percent, &mut rng)).count();
+            let expected = 1000.0 * percent;
+            // Well over four standard deviations
+            assert!(
+                (sampled as f64 - expected).abs() < 1000.0,
+                "sampled {sampled} of 100000 at {percent}%"
+            );
+        }
+        assert!(!(0..1000).any(|_| sample(0.0, &mut rng)));
+        assert!((0..1000).all(|_| sample(100.0, &mut rng)));
+    }
+
+    #[tokio::test]
+    async fn primary_response_is_unaffected_by_shadowing() {
+        let chunks: &[&[u8]] = &[b"first ", b"second ", b"third"];
+        let unshadowed = echo(request(chunks)).await;
+
+        let (req, shadow) = tee_request(request(chunks), 1024);
+        let shadowed = echo(req).await;
+        assert_eq!(unshadowed.status(), shadowed.status());
+        assert_eq!(unshadowed.body(), shadowed.body());
+
+        // The shadow component sees the same request
+        let dispatched = run_shadow(shadow, "shadow", TIMEOUT, |req| async move {
+            let copy = echo(req).await;
+            assert_eq!(unshadowed.body(), copy.body());
+            Ok(Response::new(body::empty()))
+        })
+        .await;
+        assert_eq!(ShadowOutcome::Responded(StatusCode::OK), dispatched);
+    }
+
+    #[tokio::test]
+    async fn shadow_errors_do_not_propagate() {
+        let (req, shadow) = tee_request(request(&[b"order"]), 1024);
+        let primary = echo(req).await;
+        assert_eq!(StatusCode::OK, primary.status());
+
+        let outcome = run_shadow(shadow, "shadow", TIMEOUT, |_| async {
+            anyhow::bail!("shadow component trapped")
+        })
+        .await;
+        assert_eq!(ShadowOutcome::Failed, outcome);
+
+        let (req, shadow) = tee_request(request(&[b"order"]), 1024);
+        echo(req).await;
+        let outcome = run_shadow(shadow, "shadow", Duration::from_millis(10), |_| {
+            std::future::pending()
+        })
+        .await;
+        assert_eq!(ShadowOutcome::TimedOut, outcome);
+    }
+
+    #[tokio::test]
+    async fn oversized_or_unread_bodies_are_not_shadowed() {
+        let dispatches = AtomicUsize::new(0);
+        let dispatch = |_| async {
+            dispatches.fetch_add(1, Ordering::SeqCst);
+            Ok(Response::new(body::empty()))
+        };
+
+        let (req, shadow) = tee_request(request(&[b"12345", b"67890"]), 8);
+        let primary = echo(req).await;
+        assert!(primary.body().ends_with(b"1234567890"));
+        let outcome = run_shadow(shadow, "shadow", TIMEOUT, dispatch).await;
+        assert_eq!(ShadowOutcome::Skipped, outcome);
+
+        let (req, shadow) = tee_request(request(&[b"order"]), 1024);
+        drop(req);
+        let outcome = run_shadow(shadow, "shadow", TIMEOUT, dispatch).await;
+        assert_eq!(ShadowOutcome::Skipped, outcome);
+        assert_eq!(0, dispatches.load(Ordering::SeqCst));
+
+        // Empty bodies need not be read
+        let req = Request::get("http://example.com/")
+            .body(body::empty())
+            .unwrap();
+        let (_req, shadow) = tee_request(req, 1024);
+        let outcome = run_shadow(shadow, "shadow", TIMEOUT, dispatch).await;
+        assert_eq!(ShadowOutcome::Responded(StatusCode::OK), outcome);
+    }
+}
@@ -7,10 +7,12 @@ use spin_factors::RuntimeFactors;
 use spin_http::body;
 use spin_http::routes::RouteMatch;
 use spin_world::v1::http_types;
-use tracing::{instrument, Level};
+use tracing::{field::Empty, instrument, Level};
 
 use crate::{
+    errors::resource_limit_context,
     headers::{append_headers, prepare_request_headers},
+    instrument::record_memory_usage,
     server::HttpExecutor,
     Body, TriggerInstanceBuilder,
 };
@@ -20,7 +22,7 @@ use crate::{
 pub struct SpinHttpExecutor;
 
 impl HttpExecutor for SpinHttpExecutor {
-    #[instrument(name = "spin_trigger_http.execute_wasm", skip_all, err(level = Level::INFO), fields(otel.name = format!("execute_wasm_component {}", route_match.component_id())))]
+    #[instrument(name = "spin_trigger_http.execute_wasm", skip_all, err(level = Level::INFO), fields(otel.name = format!("execute_wasm_component {}", route_match.component_id()), spin.memory_peak_bytes = Empty, spin.memory_grow_failures = Empty))]
     async fn execute<F: RuntimeFactors>(
         &self,
         instance_builder: TriggerInstanceBuilder<'_, F>,
@@ -77,7 +79,10 @@ impl HttpExecutor for SpinHttpExecutor {
             body: Some(bytes),
         };
 
-        let (resp,) = func.call_async(&mut store, (req,)).await?;
+        let result = func.call_async(&mut store, (req,)).await;
+        record_memory_usage(store.data().core_state());
+        let (resp,) =
+            result.map_err(|err| resource_limit_context(err, store.data().core_state()))?;
 
         if resp.status < 100 || resp.status > 600 {
             tracing::error!("malformed HTTP status code");
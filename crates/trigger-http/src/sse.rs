@@ -0,0 +1,261 @@
+//! Keeping server-sent event (SSE) streams alive.
+//!
+//! An SSE response (`content-type: text/event-stream`) stays open for as long
+//! as the component has events to send, which may be rarely. Proxies and load
+//! balancers often close connections which carry no data for a while, so the
+//! server can send comment lines, which clients ignore, while the component
+//! is silent. A route can also end streams on which the component has
+//! written nothing for a while, so that abandoned streams don't keep their
+//! instances alive forever.
+//!
+//! Responses of any other type are never modified.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use http::{header, HeaderValue, Response};
+use http_body_util::BodyExt;
+use hyper::body::{Body as _, Bytes, Frame, SizeHint};
+use serde::Deserialize;
+use tokio::time::{Instant, Sleep};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// The comment sent on an event stream on which the component has been
+/// silent for the keep-alive interval.
+const KEEPALIVE_COMMENT: &[u8] = b": keep-alive\n\n";
+
+/// The HTTP trigger's `[application.trigger.http]` `sse` manifest field, e.g.
+///
+/// ```toml
+/// [application.trigger.http]
+/// sse = { keepalive_secs = 15 }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SseConfig {
+    /// Sends a keep-alive comment on event streams on which the component
+    /// has written nothing for this many seconds.
+    keepalive_secs: Option<u64>,
+}
+
+impl SseConfig {
+    /// Checks that any configured interval is non-zero.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.keepalive_secs != Some(0),
+            "`sse.keepalive_secs` must be at least 1"
+        );
+        Ok(())
+    }
+
+    /// The keep-alive interval, if keep-alive comments are enabled.
+    pub fn keepalive(&self) -> Option<Duration> {
+        self.keepalive_secs.map(Duration::from_secs)
+    }
+}
+
+/// Returns whether the response is an open-ended event stream.
+fn is_event_stream(response: &Response<Body>) -> bool {
+    let headers = response.headers();
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .unwrap_or_default();
+    // A stream with a known length can't have comments injected into it
+    content_type
+        .trim()
+        .eq_ignore_ascii_case("text/event-stream")
+        && !headers.contains_key(header::CONTENT_LENGTH)
+}
+
+/// Applies keep-alive comments and the write idle timeout to the response if
+/// it is an event stream, and otherwise returns it unchanged.
+pub(crate) fn event_stream_response(
+    mut response: Response<Body>,
+    keepalive: Option<Duration>,
+    idle_timeout: Option<Duration>,
+) -> Response<Body> {
+    if !is_event_stream(&response) {
+        return response;
+    }
+    // Ask buffering proxies such as nginx to pass each event on immediately
+    response
+        .headers_mut()
+        .entry("x-accel-buffering")
+        .or_insert(HeaderValue::from_static("no"));
+    if keepalive.is_none() && idle_timeout.is_none() {
+        return response;
+    }
+    response.map(|body| EventStreamBody::new(body, keepalive, idle_timeout).boxed())
+}
+
+/// A response body which sends keep-alive comments while the component is
+/// silent, and ends once the component has been silent for the idle timeout.
+struct EventStreamBody {
+    inner: Body,
+    keepalive: Option<(Duration, Pin<Box<Sleep>>)>,
+    idle_timeout: Option<(Duration, Pin<Box<Sleep>>)>,
+}
+
+impl EventStreamBody {
+    fn new(inner: Body, keepalive: Option<Duration>, idle_timeout: Option<Duration>) -> Self {
+        let timer = |period: Duration| (period, Box::pin(tokio::time::sleep(period)));
+        Self {
+            inner,
+            keepalive: keepalive.map(timer),
+            idle_timeout: idle_timeout.map(timer),
+        }
+    }
+}
+
+/// Restarts the timer, if any, from now.
+fn reset(timer: &mut Option<(Duration, Pin<Box<Sleep>>)>) {
+    if let Some((period, sleep)) = timer {
+        sleep.as_mut().reset(Instant::now() + *period);
+    }
+}
+
+/// Returns whether the timer, if any, has elapsed.
+fn elapsed(timer: &mut Option<(Duration, Pin<Box<Sleep>>)>, cx: &mut Context<'_>) -> bool {
+    timer
+        .as_mut()
+        .is_some_and(|(_, sleep)| sleep.as_mut().poll(cx).is_ready())
+}
+
+impl hyper::body::Body for EventStreamBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if frame.is_data() {
+                    reset(&mut this.keepalive);
+                    reset(&mut this.idle_timeout);
+                }
+                return Poll::Ready(Some(Ok(frame)));
+            }
+            // The stream ended or failed
+            Poll::Ready(other) => return Poll::Ready(other),
+            Poll::Pending => {}
+        }
+        if elapsed(&mut this.idle_timeout, cx) {
+            tracing::info!("Ending event stream on which the component has stopped writing");
+            return Poll::Ready(None);
+        }
+        if elapsed(&mut this.keepalive, cx) {
+            // Only the component's own writes hold off the idle timeout
+            reset(&mut this.keepalive);
+            return Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(KEEPALIVE_COMMENT)))));
+        }
+        Poll::Pending
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        // Comments may be added
+        SizeHint::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::mpsc, SinkExt, StreamExt};
+    use http_body_util::StreamBody;
+
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    /// Returns a response with the given content type whose body is written
+    /// through the returned sender.
+    fn streaming_response(content_type: &str) -> (mpsc::Sender<Bytes>, Response<Body>) {
+        let (tx, rx) = mpsc::channel(8);
+        let body = StreamBody::new(rx.map(|data| Ok::<_, ErrorCode>(Frame::data(data)))).boxed();
+        let response = Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(body)
+            .unwrap();
+        (tx, response)
+    }
+
+    async fn next_data(body: &mut Body) -> Option<Bytes> {
+        let frame = body.frame().await?.unwrap();
+        Some(frame.into_data().unwrap())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keepalive_comments_are_sent_between_events() {
+        let (mut tx, response) = streaming_response("text/event-stream; charset=utf-8");
+        let response = event_stream_response(response, Some(15 * SECOND), None);
+        assert_eq!(response.headers()["x-accel-buffering"], "no");
+        let mut body = response.into_body();
+        let start = Instant::now();
+
+        // Events are passed on as soon as they are written
+        tx.send(Bytes::from_static(b"data: 1\n\n")).await.unwrap();
+        assert_eq!(next_data(&mut body).await.unwrap(), "data: 1\n\n");
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        assert_eq!(next_data(&mut body).await.unwrap(), KEEPALIVE_COMMENT);
+        assert_eq!(start.elapsed(), 15 * SECOND);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(20 * SECOND).await;
+            tx.send(Bytes::from_static(b"data: 2\n\n")).await.unwrap();
+        });
+        assert_eq!(next_data(&mut body).await.unwrap(), KEEPALIVE_COMMENT);
+        assert_eq!(start.elapsed(), 30 * SECOND);
+        assert_eq!(next_data(&mut body).await.unwrap(), "data: 2\n\n");
+        assert_eq!(start.elapsed(), 35 * SECOND);
+
+        // The sender was dropped, ending the stream
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout_ends_silent_stream() {
+        let (_tx, response) = streaming_response("text/event-stream");
+        let mut body = event_stream_response(response, Some(SECOND), Some(3 * SECOND)).into_body();
+        let start = Instant::now();
+
+        // Keep-alive comments don't hold off the idle timeout
+        assert_eq!(next_data(&mut body).await.unwrap(), KEEPALIVE_COMMENT);
+        assert_eq!(next_data(&mut body).await.unwrap(), KEEPALIVE_COMMENT);
+        assert!(body.frame().await.is_none());
+        assert_eq!(start.elapsed(), 3 * SECOND);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_responses_are_unchanged() {
+        let (_tx, response) = streaming_response("text/plain");
+        let response = event_stream_response(response, Some(SECOND), Some(SECOND));
+        assert!(!response.headers().contains_key("x-accel-buffering"));
+        let mut body = response.into_body();
+        let silent = tokio::time::timeout(60 * SECOND, body.frame()).await;
+        assert!(silent.is_err(), "expected no frames");
+    }
+
+    #[test]
+    fn keepalive_must_be_positive() {
+        let config: SseConfig = toml::from_str("keepalive_secs = 0").unwrap();
+        assert!(config.validate().is_err());
+        let config: SseConfig = toml::from_str("keepalive_secs = 15").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.keepalive(), Some(15 * SECOND));
+    }
+}
@@ -20,15 +20,19 @@ pub struct TlsConfig {
 impl TlsConfig {
     // Creates a TLS acceptor from server config.
     pub(super) fn server_config(&self) -> anyhow::Result<TlsAcceptor> {
+        Ok(Arc::new(self.rustls_server_config()?).into())
+    }
+
+    /// Loads the certificate and key into a server config, for servers which
+    /// need to adjust it before accepting connections.
+    pub fn rustls_server_config(&self) -> anyhow::Result<rustls::ServerConfig> {
         let certs = load_certs(&self.cert_path)?;
         let private_key = load_key(&self.key_path)?;
 
-        let cfg = rustls::ServerConfig::builder()
+        rustls::ServerConfig::builder()
             .with_no_client_auth()
             .with_single_cert(certs, private_key)
-            .map_err(|e| anyhow::anyhow!("{}", e))?;
-
-        Ok(Arc::new(cfg).into())
+            .map_err(|e| anyhow::anyhow!("{}", e))
     }
 }
 
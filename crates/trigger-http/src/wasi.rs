@@ -1,7 +1,7 @@
 use std::io::IsTerminal;
 use std::net::SocketAddr;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Result};
 use futures::TryFutureExt;
 use http::{HeaderName, HeaderValue};
 use hyper::{Request, Response};
@@ -11,11 +11,17 @@ use spin_factors::RuntimeFactors;
 use spin_http::routes::RouteMatch;
 use spin_http::trigger::HandlerType;
 use tokio::{sync::oneshot, task};
-use tracing::{instrument, Instrument, Level};
+use tracing::{field::Empty, instrument, Instrument, Level};
 use wasmtime_wasi_http::bindings::http::types::Scheme;
 use wasmtime_wasi_http::{bindings::Proxy, body::HyperIncomingBody as Body, WasiHttpView};
 
-use crate::{headers::prepare_request_headers, server::HttpExecutor, TriggerInstanceBuilder};
+use crate::{
+    errors::{resource_limit_context, HandlerError},
+    headers::prepare_request_headers,
+    instrument::record_memory_usage,
+    server::HttpExecutor,
+    TriggerInstanceBuilder,
+};
 
 /// An [`HttpExecutor`] that uses the `wasi:http/incoming-handler` interface.
 pub struct WasiHttpExecutor<'a> {
@@ -23,7 +29,7 @@ pub struct WasiHttpExecutor<'a> {
 }
 
 impl HttpExecutor for WasiHttpExecutor<'_> {
-    #[instrument(name = "spin_trigger_http.execute_wasm", skip_all, err(level = Level::INFO), fields(otel.name = format!("execute_wasm_component {}", route_match.component_id())))]
+    #[instrument(name = "spin_trigger_http.execute_wasm", skip_all, err(level = Level::INFO), fields(otel.name = format!("execute_wasm_component {}", route_match.component_id()), spin.memory_peak_bytes = Empty, spin.memory_grow_failures = Empty))]
     async fn execute<F: RuntimeFactors>(
         &self,
         instance_builder: TriggerInstanceBuilder<'_, F>,
@@ -123,8 +129,9 @@ impl HttpExecutor for WasiHttpExecutor<'_> {
                     "wasi-http memory consumed: {}",
                     store.data().core_state().memory_consumed()
                 );
+                record_memory_usage(store.data().core_state());
 
-                result
+                result.map_err(|err| resource_limit_context(err, store.data().core_state()))
             }
             .in_current_span(),
         );
@@ -158,9 +165,7 @@ impl HttpExecutor for WasiHttpExecutor<'_> {
                     .context("guest invocation panicked")?
                     .context("guest invocation failed")?;
 
-                Err(anyhow!(
-                    "guest failed to produce a response prior to returning"
-                ))
+                Err(HandlerError("guest failed to produce a response prior to returning").into())
             }
         }
     }
@@ -0,0 +1,602 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use rdkafka::consumer::{
+    BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer,
+};
+use rdkafka::error::KafkaResult;
+use rdkafka::message::{
+    BorrowedMessage, Header as KafkaHeader, Headers, Message as _, OwnedHeaders,
+};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use rdkafka::{ClientConfig, ClientContext, TopicPartitionList};
+use serde::Deserialize;
+use spin_factor_outbound_kafka::runtime_config::{ConnectionConfig, SaslConfig, SecurityProtocol};
+use spin_factor_variables::VariablesFactor;
+use spin_factors::RuntimeFactors;
+use spin_trigger::{cli::NoCliArgs, App, Trigger, TriggerApp};
+use spin_world::exports::spin::kafka::inbound_kafka::{self, Header, Message};
+use tracing::{instrument, Level};
+
+pub struct KafkaTrigger;
+
+/// Kafka trigger metadata.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerMetadata {
+    bootstrap_servers: Option<String>,
+    group_id: Option<String>,
+    security_protocol: Option<SecurityProtocol>,
+    sasl: Option<SaslConfig>,
+    ca_cert_path: Option<PathBuf>,
+}
+
+/// Kafka trigger configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Topics to subscribe to
+    topics: Vec<String>,
+    /// Optionally override bootstrap servers for trigger
+    bootstrap_servers: Option<String>,
+    /// Optionally override consumer group for trigger
+    group_id: Option<String>,
+    /// Optionally override the protocol used to communicate with brokers
+    security_protocol: Option<SecurityProtocol>,
+    /// Optionally override SASL credentials; the username and password may
+    /// reference variables
+    sasl: Option<SaslConfig>,
+    /// Optionally override the CA certificates used to verify brokers
+    ca_cert_path: Option<PathBuf>,
+    /// Number of times a message is delivered before it is dead-lettered
+    max_attempts: Option<u32>,
+    /// Topic to publish messages to once `max_attempts` is exhausted
+    dead_letter_topic: Option<String>,
+}
+
+/// The number of times a message is delivered if the trigger doesn't say.
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// The delay before redelivering a message, multiplied by the attempt number.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How long a dead-lettered record may wait to be delivered.
+const DEAD_LETTER_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl<F: RuntimeFactors> Trigger<F> for KafkaTrigger {
+    const TYPE: &'static str = "kafka";
+
+    type CliArgs = NoCliArgs;
+
+    type InstanceState = ();
+
+    fn new(_cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn run(self, trigger_app: spin_trigger::TriggerApp<Self, F>) -> anyhow::Result<()> {
+        let app_variables = trigger_app
+            .configured_app()
+            .app_state::<VariablesFactor>()
+            .context("KafkaTrigger depends on VariablesFactor")?;
+
+        let app = trigger_app.app();
+        let trigger_type = <Self as Trigger<F>>::TYPE;
+        let metadata = app
+            .get_trigger_metadata::<TriggerMetadata>(trigger_type)?
+            .unwrap_or_default();
+
+        // Resolve trigger configs before starting any consumers
+        let mut consumer_configs = Vec::new();
+        for (trigger_id, config) in app
+            .trigger_configs::<TriggerConfig>(trigger_type)?
+            .into_iter()
+            .collect::<Vec<_>>()
+        {
+            let component_id = config.component;
+
+            let Some(servers_expr) = config
+                .bootstrap_servers
+                .as_ref()
+                .or(metadata.bootstrap_servers.as_ref())
+            else {
+                anyhow::bail!(
+                    "kafka trigger {trigger_id:?} has no bootstrap servers; set `bootstrap_servers` on the trigger or in `[application.trigger.kafka]`"
+                );
+            };
+            let bootstrap_servers = app_variables
+                .resolve_expression(servers_expr.clone())
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to resolve kafka trigger bootstrap servers {servers_expr:?} for component {component_id}"
+                    )
+                })?;
+
+            let group_id = match config.group_id.as_ref().or(metadata.group_id.as_ref()) {
+                Some(group_expr) => app_variables
+                    .resolve_expression(group_expr.clone())
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "failed to resolve kafka trigger group ID {group_expr:?} for component {component_id}"
+                        )
+                    })?,
+                None => default_group_id(app.id(), &component_id),
+            };
+
+            let mut topics = Vec::with_capacity(config.topics.len());
+            for topic_expr in &config.topics {
+                let topic = app_variables
+                    .resolve_expression(topic_expr.clone())
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "failed to resolve kafka trigger topic {topic_expr:?} for component {component_id}"
+                        )
+                    })?;
+                topics.push(topic);
+            }
+            if topics.is_empty() {
+                anyhow::bail!("kafka trigger {trigger_id:?} must subscribe to at least one topic");
+            }
+
+            let mut sasl = config.sasl.or_else(|| metadata.sasl.clone());
+            if let Some(sasl) = &mut sasl {
+                for credential in [&mut sasl.username, &mut sasl.password] {
+                    *credential = app_variables
+                        .resolve_expression(credential.clone())
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "failed to resolve kafka trigger SASL credentials for component {component_id}"
+                            )
+                        })?;
+                }
+            }
+            let connection = ConnectionConfig {
+                security_protocol: config
+                    .security_protocol
+                    .or(metadata.security_protocol)
+                    .unwrap_or_default(),
+                sasl,
+                ca_cert_path: config
+                    .ca_cert_path
+                    .or_else(|| metadata.ca_cert_path.clone()),
+            };
+            connection.validate().with_context(|| {
+                format!("kafka trigger {trigger_id:?} has invalid connection settings")
+            })?;
+
+            let max_attempts = config.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+            if max_attempts == 0 {
+                anyhow::bail!("kafka trigger {trigger_id:?} `max_attempts` must be at least 1");
+            }
+
+            consumer_configs.push(ConsumerConfig {
+                component_id,
+                bootstrap_servers,
+                connection,
+                group_id,
+                topics,
+                max_attempts,
+                dead_letter_topic: config.dead_letter_topic,
+            });
+        }
+
+        // Start consumer(s)
+        let trigger_app = Arc::new(trigger_app);
+        let mut consumer_tasks = Vec::new();
+        for config in consumer_configs {
+            let consumer = TopicConsumer::new(config, trigger_app.clone())?;
+            let task = tokio::spawn(consumer.run());
+            consumer_tasks.push(task);
+        }
+
+        // Wait for any task to complete
+        let (res, _, _) = futures::future::select_all(consumer_tasks).await;
+        res?
+    }
+}
+
+/// The consumer group used by a trigger which doesn't name one.
+fn default_group_id(app_id: &str, component_id: &str) -> String {
+    format!("spin-{app_id}-{component_id}")
+}
+
+/// A resolved trigger config.
+struct ConsumerConfig {
+    component_id: String,
+    bootstrap_servers: String,
+    connection: ConnectionConfig,
+    group_id: String,
+    topics: Vec<String>,
+    max_attempts: u32,
+    dead_letter_topic: Option<String>,
+}
+
+/// Where messages go once a component has failed to handle them
+/// `max_attempts` times.
+struct DeadLetterQueue {
+    producer: FutureProducer,
+    topic: String,
+}
+
+/// Consumes one trigger's topics as a member of its consumer group.
+///
+/// Messages are handled one at a time, and each message's offset is committed
+/// once it has been handled (or dead-lettered), so there is never a message
+/// in flight when the group rebalances.
+struct TopicConsumer<F: RuntimeFactors> {
+    trigger_app: Arc<TriggerApp<KafkaTrigger, F>>,
+    config: ConsumerConfig,
+    consumer: StreamConsumer<GroupContext>,
+    dead_letter_queue: Option<DeadLetterQueue>,
+}
+
+impl<F: RuntimeFactors> TopicConsumer<F> {
+    fn new(
+        config: ConsumerConfig,
+        trigger_app: Arc<TriggerApp<KafkaTrigger, F>>,
+    ) -> anyhow::Result<Self> {
+        let mut consumer_config = ClientConfig::new();
+        consumer_config
+            .set("bootstrap.servers", &config.bootstrap_servers)
+            .set("group.id", &config.group_id)
+            .set("enable.auto.commit", "false")
+            // A new group starts from the beginning of its topics rather than
+            // missing messages published before it first joined.
+            .set("auto.offset.reset", "earliest");
+        config.connection.apply(&mut consumer_config);
+        let consumer = consumer_config
+            .create_with_context(GroupContext {
+                group_id: config.group_id.clone(),
+            })
+            .with_context(|| {
+                format!(
+                    "Kafka trigger failed to create consumer for {}",
+                    config.bootstrap_servers
+                )
+            })?;
+
+        let dead_letter_queue = config
+            .dead_letter_topic
+            .as_ref()
+            .map(|topic| -> anyhow::Result<_> {
+                let mut producer_config = ClientConfig::new();
+                producer_config
+                    .set("bootstrap.servers", &config.bootstrap_servers)
+                    .set(
+                        "message.timeout.ms",
+                        DEAD_LETTER_TIMEOUT.as_millis().to_string(),
+                    );
+                config.connection.apply(&mut producer_config);
+                let producer = producer_config.create().with_context(|| {
+                    format!(
+                        "Kafka trigger failed to create dead letter producer for {}",
+                        config.bootstrap_servers
+                    )
+                })?;
+                Ok(DeadLetterQueue {
+                    producer,
+                    topic: topic.clone(),
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            trigger_app,
+            config,
+            consumer,
+            dead_letter_queue,
+        })
+    }
+
+    async fn run(self) -> anyhow::Result<()> {
+        let servers = &self.config.bootstrap_servers;
+        let group_id = &self.config.group_id;
+
+        tracing::info!(
+            "Subscribing to {:?} on {servers} as {group_id}",
+            self.config.topics
+        );
+        let topics: Vec<&str> = self.config.topics.iter().map(String::as_str).collect();
+        self.consumer.subscribe(&topics).with_context(|| {
+            format!("Kafka trigger failed to subscribe to topics {topics:?} on {servers}")
+        })?;
+
+        println!("Active Topics on {servers} (group {group_id}):");
+        for topic in &self.config.topics {
+            println!("\t{servers}/{topic}: [{}]", self.config.component_id);
+        }
+
+        loop {
+            let msg = match self.consumer.recv().await {
+                Ok(msg) => msg,
+                Err(err) => {
+                    // Errors such as an unreachable broker are retried by
+                    // the client, so keep receiving
+                    tracing::warn!("Error receiving from {servers}: {err}");
+                    continue;
+                }
+            };
+
+            // If the message can be neither handled nor dead-lettered, stop
+            // rather than commit past it.
+            self.handle_message(to_wit_message(&msg)).await?;
+
+            // Commit failures are reported to `GroupContext::commit_callback`;
+            // the message is redelivered if a later commit doesn't cover it.
+            if let Err(err) = self.consumer.commit_message(&msg, CommitMode::Async) {
+                tracing::warn!("Failed to commit offset for message from {servers}: {err}");
+            }
+        }
+    }
+
+    /// Delivers a message to the component, retrying up to the trigger's
+    /// `max_attempts` and then dead-lettering it.
+    ///
+    /// Returns an error only if the message could not be dead-lettered.
+    async fn handle_message(&self, message: Message) -> anyhow::Result<()> {
+        let component_id = &self.config.component_id;
+        let mut attempt = 1;
+        let err = loop {
+            match self.dispatch_handler(&message, attempt).await {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt < self.config.max_attempts => {
+                    tracing::info!(
+                        "Component {component_id} handler failed (attempt {attempt}): {err}"
+                    );
+                    tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+                    attempt += 1;
+                }
+                Err(err) => break err,
+            }
+        };
+
+        let location = format!("{}/{}@{}", message.topic, message.partition, message.offset);
+        let Some(dead_letter_queue) = &self.dead_letter_queue else {
+            tracing::error!(
+                "Component {component_id} failed to handle message {location} after {attempt} attempts; skipping it: {err}"
+            );
+            return Ok(());
+        };
+
+        tracing::warn!(
+            "Component {component_id} failed to handle message {location} after {attempt} attempts; sending it to {}: {err}",
+            dead_letter_queue.topic
+        );
+        let headers = dead_letter_headers(&message, &format!("{err:#}"))
+            .iter()
+            .fold(OwnedHeaders::new(), |all, h| {
+                all.insert(KafkaHeader {
+                    key: &h.key,
+                    value: Some(&h.value),
+                })
+            });
+        let mut record = FutureRecord::<[u8], [u8]>::to(&dead_letter_queue.topic)
+            .payload(&message.value)
+            .headers(headers);
+        if let Some(key) = &message.key {
+            record = record.key(key);
+        }
+        dead_letter_queue
+            .producer
+            .send(record, Timeout::Never)
+            .await
+            .map_err(|(err, _)| err)
+            .with_context(|| {
+                format!(
+                    "failed to send message {location} to dead letter topic {}",
+                    dead_letter_queue.topic
+                )
+            })?;
+        Ok(())
+    }
+
+    #[instrument(name = "spin_trigger_kafka.handle_message", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("{} receive", message.topic),
+        otel.kind = "consumer",
+        messaging.operation = "receive",
+        messaging.system = "kafka",
+        messaging.destination.name = %message.topic,
+        messaging.kafka.destination.partition = message.partition,
+        messaging.kafka.message.offset = message.offset,
+        messaging.kafka.consumer.group = %self.config.group_id,
+        attempt = attempt,
+    ))]
+    async fn dispatch_handler(&self, message: &Message, attempt: u32) -> anyhow::Result<()> {
+        let component_id = &self.config.component_id;
+        tracing::trace!("Executing Kafka component {component_id}");
+
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "kafka",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id.as_str()
+        );
+
+        let (instance, mut store) = self
+            .trigger_app
+            .prepare(component_id)?
+            .instantiate(())
+            .await?;
+
+        let pre = instance.instance_pre(&store);
+        let guest_indices = inbound_kafka::GuestIndices::new(&pre)?;
+        let guest = guest_indices.load(&mut store, &instance)?;
+
+        guest
+            .call_handle_message(&mut store, message)
+            .await?
+            .context("Kafka handler returned an error")
+    }
+}
+
+/// Copies a received message for the component.
+fn to_wit_message(msg: &BorrowedMessage<'_>) -> Message {
+    let headers = msg
+        .headers()
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|h| Header {
+                    key: h.key.to_owned(),
+                    value: h.value.unwrap_or_default().to_vec(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Message {
+        topic: msg.topic().to_owned(),
+        partition: msg.partition(),
+        offset: msg.offset(),
+        key: msg.key().map(<[u8]>::to_vec),
+        value: msg.payload().unwrap_or_default().to_vec(),
+        headers,
+    }
+}
+
+/// The headers of a dead-lettered record: the original message's headers,
+/// followed by where it came from and why it failed.
+fn dead_letter_headers(message: &Message, error: &str) -> Vec<Header> {
+    let mut headers = message.headers.clone();
+    for (key, value) in [
+        ("spin-source-topic", message.topic.clone()),
+        ("spin-source-partition", message.partition.to_string()),
+        ("spin-source-offset", message.offset.to_string()),
+        ("spin-error", error.to_owned()),
+    ] {
+        headers.push(Header {
+            key: key.to_owned(),
+            value: value.into_bytes(),
+        });
+    }
+    headers
+}
+
+/// Reports consumer group activity for a [`TopicConsumer`].
+struct GroupContext {
+    group_id: String,
+}
+
+impl ClientContext for GroupContext {}
+
+impl ConsumerContext for GroupContext {
+    fn pre_rebalance(&self, _consumer: &BaseConsumer<Self>, rebalance: &Rebalance<'_>) {
+        match rebalance {
+            Rebalance::Revoke(partitions) => tracing::info!(
+                "Group {} revoked partitions [{}]",
+                self.group_id,
+                describe_partitions(partitions)
+            ),
+            Rebalance::Error(err) => {
+                tracing::warn!("Group {} rebalance failed: {err}", self.group_id)
+            }
+            Rebalance::Assign(_) => {}
+        }
+    }
+
+    fn post_rebalance(&self, _consumer: &BaseConsumer<Self>, rebalance: &Rebalance<'_>) {
+        if let Rebalance::Assign(partitions) = rebalance {
+            tracing::info!(
+                "Group {} assigned partitions [{}]",
+                self.group_id,
+                describe_partitions(partitions)
+            );
+        }
+    }
+
+    fn commit_callback(&self, result: KafkaResult<()>, offsets: &TopicPartitionList) {
+        if let Err(err) = result {
+            tracing::warn!(
+                "Group {} failed to commit offsets for [{}]: {err}",
+                self.group_id,
+                describe_partitions(offsets)
+            );
+        }
+    }
+}
+
+fn describe_partitions(partitions: &TopicPartitionList) -> String {
+    partitions
+        .elements()
+        .iter()
+        .map(|p| format!("{}/{}", p.topic(), p.partition()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_config_defaults() {
+        let config: TriggerConfig = toml::toml! {
+            component = "orders"
+            topics = ["orders"]
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(config.max_attempts, None);
+        assert_eq!(config.dead_letter_topic, None);
+        assert_eq!(default_group_id("shop", "orders"), "spin-shop-orders");
+    }
+
+    #[test]
+    fn trigger_config_connection_settings() {
+        let config: TriggerConfig = toml::toml! {
+            component = "orders"
+            topics = ["orders"]
+            security_protocol = "sasl_ssl"
+            sasl = { mechanism = "SCRAM-SHA-256", username = "spin", password = "{{ kafka_password }}" }
+            ca_cert_path = "/etc/kafka/ca.pem"
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(config.security_protocol, Some(SecurityProtocol::SaslSsl));
+        let sasl = config.sasl.unwrap();
+        assert_eq!(sasl.mechanism.as_str(), "SCRAM-SHA-256");
+        assert_eq!(sasl.password, "{{ kafka_password }}");
+        assert_eq!(
+            config.ca_cert_path,
+            Some(PathBuf::from("/etc/kafka/ca.pem"))
+        );
+    }
+
+    #[test]
+    fn dead_letter_headers_describe_the_failure() {
+        let message = Message {
+            topic: "orders".to_string(),
+            partition: 2,
+            offset: 42,
+            key: None,
+            value: b"order".to_vec(),
+            headers: vec![Header {
+                key: "source".to_string(),
+                value: b"web".to_vec(),
+            }],
+        };
+        let headers: Vec<_> = dead_letter_headers(&message, "boom")
+            .into_iter()
+            .map(|h| (h.key, String::from_utf8(h.value).unwrap()))
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                ("source", "web"),
+                ("spin-source-topic", "orders"),
+                ("spin-source-partition", "2"),
+                ("spin-source-offset", "42"),
+                ("spin-error", "boom"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+    }
+}
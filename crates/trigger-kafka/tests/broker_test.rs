@@ -0,0 +1,233 @@
+//! Tests against a real Kafka broker.
+//!
+//! These are ignored by default. To run them, start a broker, e.g. with
+//! `docker run -d -p 9092:9092 apache/kafka:3.9.0`, then run
+//! `cargo test -p spin-trigger-kafka -- --ignored`. Set
+//! `KAFKA_BOOTSTRAP_SERVERS` if the broker isn't at `localhost:9092`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
+use rdkafka::consumer::{BaseConsumer, Consumer, StreamConsumer};
+use rdkafka::message::{Headers, Message};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::{ClientConfig, Offset, TopicPartitionList};
+use spin_app::{App, AppComponent};
+use spin_core::{async_trait, Component};
+use spin_factor_variables::VariablesFactor;
+use spin_factors::{anyhow, RuntimeFactors};
+use spin_factors_executor::{ComponentLoader, FactorsExecutor};
+use spin_trigger::Trigger;
+use spin_trigger_kafka::KafkaTrigger;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    variables: VariablesFactor,
+}
+
+/// Loads components which export `spin:kafka/inbound-kafka`, and handle
+/// every message successfully unless their ID is `fail`.
+struct HandlerLoader;
+
+#[async_trait]
+impl ComponentLoader<TestFactors, ()> for HandlerLoader {
+    async fn load_component(
+        &self,
+        engine: &spin_core::wasmtime::Engine,
+        component: &AppComponent,
+    ) -> anyhow::Result<Component> {
+        Component::new(engine, handler_wat(component.id() == "fail"))
+    }
+}
+
+fn handler_wat(fail: bool) -> String {
+    // The return area for `ok`, or for `err(other("boom"))`
+    let result = if fail { 32 } else { 16 };
+    format!(
+        r#"(component
+            (core module $m
+                (memory (export "memory") 1)
+                (global $heap (mut i32) (i32.const 1024))
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr
+                        (i32.and (i32.add (global.get $heap) (i32.const 7)) (i32.const -8)))
+                    (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+                    (local.get $ptr))
+                (func (export "handle-message")
+                    (param i32 i32 i32 i64 i32 i32 i32 i32 i32 i32 i32) (result i32)
+                    (i32.const {result}))
+                (data (i32.const 16)
+                    ;; ok
+                    "\00")
+                (data (i32.const 32)
+                    ;; err
+                    "\01\00\00\00"
+                    ;; other: "boom", at 64
+                    "\03\00\00\00" "\40\00\00\00" "\04\00\00\00")
+                (data (i32.const 64) "boom"))
+            (core instance $i (instantiate $m))
+            (type $header' (record (field "key" string) (field "value" (list u8))))
+            (export $header "header" (type $header'))
+            (type $error' (variant
+                (case "invalid-address")
+                (case "too-many-producers")
+                (case "connection-failed" string)
+                (case "other" string)))
+            (export $error "error" (type $error'))
+            (type $message' (record
+                (field "topic" string)
+                (field "partition" s32)
+                (field "offset" s64)
+                (field "key" (option (list u8)))
+                (field "value" (list u8))
+                (field "headers" (list $header))))
+            (export $message "message" (type $message'))
+            (func $handle (param "message" $message) (result (result (error $error)))
+                (canon lift (core func $i "handle-message")
+                    (memory $i "memory") (realloc (func $i "realloc"))))
+            (instance $inbound
+                (export "header" (type $header))
+                (export "error" (type $error))
+                (export "message" (type $message))
+                (export "handle-message" (func $handle)))
+            (export "spin:kafka/inbound-kafka@3.0.0" (instance $inbound))
+        )"#
+    )
+}
+
+fn bootstrap_servers() -> String {
+    std::env::var("KAFKA_BOOTSTRAP_SERVERS").unwrap_or_else(|_| "localhost:9092".to_string())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a local Kafka broker"]
+async fn messages_are_dispatched_and_failures_dead_lettered() -> anyhow::Result<()> {
+    let servers = bootstrap_servers();
+    // Fresh topics so that earlier runs don't interfere
+    let topic = format!(
+        "spin-trigger-test-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_millis()
+    );
+    let dead_letter_topic = format!("{topic}-failed");
+    let ok_group = format!("{topic}-ok");
+
+    let admin: AdminClient<DefaultClientContext> = ClientConfig::new()
+        .set("bootstrap.servers", &servers)
+        .create()?;
+    for result in admin
+        .create_topics(
+            &[
+                NewTopic::new(&topic, 1, TopicReplication::Fixed(1)),
+                NewTopic::new(&dead_letter_topic, 1, TopicReplication::Fixed(1)),
+            ],
+            &AdminOptions::new(),
+        )
+        .await?
+    {
+        result.map_err(|(topic, err)| anyhow::anyhow!("failed to create {topic}: {err}"))?;
+    }
+
+    let producer: FutureProducer = ClientConfig::new()
+        .set("bootstrap.servers", &servers)
+        .create()?;
+    producer
+        .send(
+            FutureRecord::to(&topic).key("key").payload("order"),
+            Duration::from_secs(30),
+        )
+        .await
+        .map_err(|(err, _)| err)?;
+
+    let manifest = format!(
+        r#"
+        spin_manifest_version = 2
+        [application]
+        name = "kafka-test"
+        [[trigger.kafka]]
+        component = "ok"
+        topics = [{topic:?}]
+        bootstrap_servers = {servers:?}
+        group_id = {ok_group:?}
+        [[trigger.kafka]]
+        component = "fail"
+        topics = [{topic:?}]
+        bootstrap_servers = {servers:?}
+        max_attempts = 2
+        dead_letter_topic = {dead_letter_topic:?}
+        [component.ok]
+        source = "does-not-exist.wasm"
+        [component.fail]
+        source = "does-not-exist.wasm"
+        "#
+    );
+    let locked =
+        spin_factors_test::build_locked_app(&toml::from_str::<toml::Table>(&manifest)?).await?;
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+    };
+    let executor = Arc::new(FactorsExecutor::new(
+        spin_core::Engine::builder(&Default::default())?,
+        factors,
+    )?);
+    let trigger_app = executor
+        .load_app(
+            App::new("kafka-test", locked),
+            Default::default(),
+            &HandlerLoader,
+        )
+        .await?;
+    let trigger = tokio::spawn(KafkaTrigger.run(trigger_app));
+
+    // The failing component's message is dead-lettered once its attempts run out
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &servers)
+        .set("group.id", &dead_letter_topic)
+        .set("auto.offset.reset", "earliest")
+        .create()?;
+    consumer.subscribe(&[&dead_letter_topic])?;
+    let message = tokio::time::timeout(Duration::from_secs(60), consumer.recv()).await??;
+    assert_eq!(message.key(), Some(&b"key"[..]));
+    assert_eq!(message.payload(), Some(&b"order"[..]));
+    let headers: Vec<_> = message
+        .headers()
+        .unwrap()
+        .iter()
+        .map(|h| (h.key.to_string(), h.value.unwrap_or_default().to_vec()))
+        .collect();
+    assert!(headers.contains(&("spin-source-topic".to_string(), topic.clone().into_bytes())));
+    assert!(
+        headers
+            .iter()
+            .any(|(key, value)| key == "spin-error"
+                && String::from_utf8_lossy(value).contains("boom"))
+    );
+
+    // The succeeding component's message is committed
+    let group_consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &servers)
+        .set("group.id", &ok_group)
+        .create()?;
+    let mut partitions = TopicPartitionList::new();
+    partitions.add_partition(&topic, 0);
+    let committed = tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            let offsets =
+                group_consumer.committed_offsets(partitions.clone(), Duration::from_secs(5))?;
+            let offset = offsets.find_partition(&topic, 0).map(|p| p.offset());
+            if offset == Some(Offset::Offset(1)) {
+                return anyhow::Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await;
+    committed??;
+
+    trigger.abort();
+    Ok(())
+}
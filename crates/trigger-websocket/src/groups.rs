@@ -0,0 +1,141 @@
+use std::{collections::HashMap, sync::Arc};
+
+use dashmap::DashMap;
+use tokio::sync::mpsc::{error::TrySendError, Sender};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Identifies an open connection.
+pub(crate) type ConnectionId = u64;
+
+/// The open connections in each connection group, by group label.
+///
+/// Clones share the same groups, so that a message from any connection can
+/// be broadcast to every connection in its group.
+#[derive(Clone, Default)]
+pub(crate) struct ConnectionGroups {
+    groups: Arc<DashMap<String, HashMap<ConnectionId, Sender<Message>>>>,
+}
+
+impl ConnectionGroups {
+    /// Adds a connection to a group, with the queue of messages waiting to be
+    /// sent to it. The connection leaves the group when the returned
+    /// [`Membership`] is dropped.
+    pub(crate) fn join(
+        &self,
+        group: &str,
+        id: ConnectionId,
+        outbox: Sender<Message>,
+    ) -> Membership {
+        self.groups
+            .entry(group.to_owned())
+            .or_default()
+            .insert(id, outbox);
+        Membership {
+            groups: self.clone(),
+            group: group.to_owned(),
+            id,
+        }
+    }
+
+    /// Sends a message to every connection in a group, returning the number of
+    /// connections it was queued for.
+    ///
+    /// Broadcasting never waits: a connection which is too far behind to queue
+    /// the message misses it.
+    pub(crate) fn broadcast(&self, group: &str, message: &Message) -> usize {
+        let Some(members) = self.groups.get(group) else {
+            return 0;
+        };
+        members
+            .iter()
+            .filter(|(id, outbox)| match outbox.try_send(message.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!(
+                        "Dropping broadcast to WebSocket connection {id} in group {group:?}: \
+                         too many messages are waiting to be sent"
+                    );
+                    false
+                }
+                Err(TrySendError::Closed(_)) => false,
+            })
+            .count()
+    }
+
+    #[cfg(test)]
+    fn members(&self, group: &str) -> usize {
+        self.groups.get(group).map_or(0, |members| members.len())
+    }
+}
+
+/// A connection's membership of a group.
+pub(crate) struct Membership {
+    groups: ConnectionGroups,
+    group: String,
+    id: ConnectionId,
+}
+
+impl Drop for Membership {
+    fn drop(&mut self) {
+        // Groups are removed with their last connection, so that labels taken
+        // from request paths don't accumulate
+        self.groups.groups.remove_if_mut(&self.group, |_, members| {
+            members.remove(&self.id);
+            members.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn broadcasts_reach_only_the_group() {
+        let groups = ConnectionGroups::default();
+        let (lobby_1, mut lobby_1_rx) = mpsc::channel(4);
+        let (lobby_2, mut lobby_2_rx) = mpsc::channel(4);
+        let (other, mut other_rx) = mpsc::channel(4);
+        let _lobby_1 = groups.join("lobby", 1, lobby_1);
+        let _lobby_2 = groups.join("lobby", 2, lobby_2);
+        let _other = groups.join("other", 3, other);
+
+        assert_eq!(2, groups.broadcast("lobby", &Message::text("hello")));
+        assert_eq!(Message::text("hello"), lobby_1_rx.try_recv().unwrap());
+        assert_eq!(Message::text("hello"), lobby_2_rx.try_recv().unwrap());
+        assert!(other_rx.try_recv().is_err());
+        assert_eq!(0, groups.broadcast("empty", &Message::text("hello")));
+    }
+
+    #[test]
+    fn connections_leave_when_membership_is_dropped() {
+        let groups = ConnectionGroups::default();
+        let (outbox, _rx) = mpsc::channel(4);
+        let first = groups.join("lobby", 1, outbox.clone());
+        let second = groups.join("lobby", 2, outbox);
+        assert_eq!(2, groups.members("lobby"));
+
+        drop(first);
+        assert_eq!(1, groups.members("lobby"));
+        drop(second);
+        assert!(groups.groups.get("lobby").is_none());
+    }
+
+    #[test]
+    fn full_outboxes_miss_broadcasts() {
+        let groups = ConnectionGroups::default();
+        let (slow, mut slow_rx) = mpsc::channel(1);
+        let (fast, mut fast_rx) = mpsc::channel(4);
+        let _slow = groups.join("lobby", 1, slow);
+        let _fast = groups.join("lobby", 2, fast);
+
+        assert_eq!(2, groups.broadcast("lobby", &Message::text("one")));
+        assert_eq!(1, groups.broadcast("lobby", &Message::text("two")));
+        assert_eq!(Message::text("one"), slow_rx.try_recv().unwrap());
+        assert!(slow_rx.try_recv().is_err());
+        assert_eq!(Message::text("one"), fast_rx.try_recv().unwrap());
+        assert_eq!(Message::text("two"), fast_rx.try_recv().unwrap());
+    }
+}
@@ -0,0 +1,377 @@
+//! Implementation for the Spin WebSocket trigger.
+
+mod groups;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use clap::Args;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use spin_factors::RuntimeFactors;
+use spin_http_routes::{HttpTriggerRouteConfig, Router};
+use spin_trigger::{App, Trigger};
+use spin_world::exports::spin::websocket::inbound_websocket::{self, ConnectionContext};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{instrument, Level};
+
+use groups::{ConnectionGroups, ConnectionId};
+
+/// A [`spin_trigger::TriggerApp`] for the WebSocket trigger.
+type TriggerApp<F> = spin_trigger::TriggerApp<WebSocketTrigger, F>;
+
+/// The address the server listens on if none is given on the command line.
+const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:3001";
+
+/// The number of messages which may wait to be sent to a connection.
+const OUTBOX_CAPACITY: usize = 64;
+
+pub struct WebSocketTrigger {
+    listen_addr: SocketAddr,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// IP address and port to accept WebSocket connections on
+    #[clap(
+        long = "websocket-listen",
+        env = "SPIN_WEBSOCKET_LISTEN_ADDR",
+        default_value = DEFAULT_LISTEN_ADDR
+    )]
+    pub address: SocketAddr,
+}
+
+/// WebSocket trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Route that connections are accepted on
+    route: String,
+    /// Connection group label, if not the request path
+    group: Option<String>,
+    /// Whether replies are sent to the whole connection group
+    #[serde(default)]
+    broadcast: bool,
+}
+
+impl TriggerConfig {
+    /// The connection group of a connection opened on the given path.
+    fn group_for(&self, path: &str) -> String {
+        self.group.clone().unwrap_or_else(|| path.to_owned())
+    }
+}
+
+impl<F: RuntimeFactors> Trigger<F> for WebSocketTrigger {
+    const TYPE: &'static str = "websocket";
+
+    type CliArgs = CliArgs;
+
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        Ok(Self {
+            listen_addr: cli_args.address,
+        })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<F>) -> anyhow::Result<()> {
+        let trigger_type = <Self as Trigger<F>>::TYPE;
+        let configs: HashMap<String, TriggerConfig> = trigger_app
+            .app()
+            .trigger_configs::<TriggerConfig>(trigger_type)?
+            .into_iter()
+            .map(|(trigger_id, config)| (trigger_id.to_owned(), config))
+            .collect();
+
+        // Routes map to trigger IDs rather than components, as a component may
+        // serve several routes with different group settings
+        let routes: Vec<_> = configs
+            .iter()
+            .map(|(trigger_id, config)| {
+                (
+                    trigger_id.as_str(),
+                    HttpTriggerRouteConfig::Route(config.route.clone()),
+                )
+            })
+            .collect();
+        let mut duplicate_routes = Vec::new();
+        let router = Router::build(
+            "/",
+            routes.iter().map(|(id, route)| (*id, route)),
+            Some(&mut duplicate_routes),
+        )?;
+        for dup in &duplicate_routes {
+            tracing::error!(
+                "WebSocket route {} of trigger {} duplicates trigger {} and will never be used",
+                dup.route(),
+                dup.replaced_id,
+                dup.effective_id,
+            );
+        }
+
+        let listener = TcpListener::bind(self.listen_addr).await.with_context(|| {
+            format!("WebSocket trigger failed to listen on {}", self.listen_addr)
+        })?;
+
+        println!("Serving WebSocket connections on ws://{}", self.listen_addr);
+        println!("Available Routes:");
+        for (route, trigger_id) in router.routes() {
+            println!(
+                "  {}: ws://{}{route}",
+                configs[trigger_id].component, self.listen_addr
+            );
+        }
+
+        let state = Arc::new(AppState {
+            trigger_app,
+            router,
+            configs,
+            groups: ConnectionGroups::default(),
+            next_connection_id: AtomicU64::new(1),
+        });
+
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::warn!("Failed to accept WebSocket connection: {err}");
+                    continue;
+                }
+            };
+            tokio::spawn(state.clone().handle_connection(stream, peer));
+        }
+    }
+}
+
+/// State shared by all the trigger's connections.
+struct AppState<F: RuntimeFactors> {
+    trigger_app: TriggerApp<F>,
+    /// Maps request paths to trigger IDs.
+    router: Router,
+    configs: HashMap<String, TriggerConfig>,
+    groups: ConnectionGroups,
+    next_connection_id: AtomicU64,
+}
+
+/// Where a connection was routed to during the handshake.
+struct RoutedConnection {
+    trigger_id: String,
+    path: String,
+    query: Option<String>,
+}
+
+impl<F: RuntimeFactors> AppState<F> {
+    /// Serves a single connection, from the handshake until it is closed.
+    ///
+    /// Messages on a connection are handled one at a time, so that replies
+    /// are sent in the order the messages arrived.
+    async fn handle_connection(self: Arc<Self>, stream: TcpStream, peer: SocketAddr) {
+        let mut routed = None;
+        let accepted = tokio_tungstenite::accept_hdr_async(
+            stream,
+            |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+                let uri = request.uri();
+                let Ok(route_match) = self.router.route(uri.path()) else {
+                    let mut not_found = ErrorResponse::new(None);
+                    *not_found.status_mut() = StatusCode::NOT_FOUND;
+                    return Err(not_found);
+                };
+                routed = Some(RoutedConnection {
+                    trigger_id: route_match.component_id().to_owned(),
+                    path: uri.path().to_owned(),
+                    query: uri.query().map(str::to_owned),
+                });
+                Ok(response)
+            },
+        )
+        .await;
+        let (websocket, routed) = match (accepted, routed) {
+            (Ok(websocket), Some(routed)) => (websocket, routed),
+            (Ok(_), None) => return,
+            (Err(err), _) => {
+                tracing::debug!("WebSocket handshake with {peer} failed: {err}");
+                return;
+            }
+        };
+
+        let config = &self.configs[&routed.trigger_id];
+        let id: ConnectionId = self.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        let ctx = ConnectionContext {
+            connection_id: id.to_string(),
+            group: config.group_for(&routed.path),
+            path: routed.path,
+            query: routed.query,
+        };
+        tracing::debug!(
+            "WebSocket connection {id} from {peer} opened on {} in group {:?}",
+            ctx.path,
+            ctx.group
+        );
+
+        // Everything sent to the connection, whether a reply or a broadcast,
+        // goes through its outbox
+        let (mut sink, mut incoming) = websocket.split();
+        let (outbox, mut outbox_rx) = mpsc::channel(OUTBOX_CAPACITY);
+        let membership = self.groups.join(&ctx.group, id, outbox.clone());
+        let writer = tokio::spawn(async move {
+            while let Some(message) = outbox_rx.recv().await {
+                if sink.send(message).await.is_err() {
+                    return;
+                }
+            }
+            _ = sink.close().await;
+        });
+
+        while let Some(message) = incoming.next().await {
+            let message = match message {
+                Ok(message) => message,
+                Err(err) => {
+                    tracing::debug!("WebSocket connection {id} failed: {err}");
+                    break;
+                }
+            };
+            let is_text = match &message {
+                Message::Text(_) => true,
+                Message::Binary(_) => false,
+                Message::Close(_) => break,
+                // Pings are answered by the WebSocket library
+                _ => continue,
+            };
+
+            let replies = match self
+                .dispatch_handler(&config.component, &ctx, &message.into_data())
+                .await
+            {
+                Ok(replies) => replies,
+                Err(err) => {
+                    tracing::error!(
+                        "Component {} failed to handle message on WebSocket connection {id}: {err:?}",
+                        config.component
+                    );
+                    _ = outbox
+                        .send(Message::Close(Some(CloseFrame {
+                            code: CloseCode::Error,
+                            reason: "the application failed to handle the message".into(),
+                        })))
+                        .await;
+                    break;
+                }
+            };
+            for reply in replies {
+                let reply = reply_message(reply, is_text);
+                if config.broadcast {
+                    self.groups.broadcast(&ctx.group, &reply);
+                } else if outbox.send(reply).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        // Leaving the group drops the last senders to the outbox, so the
+        // writer sends what is queued and closes the connection
+        drop(membership);
+        drop(outbox);
+        _ = writer.await;
+        tracing::debug!("WebSocket connection {id} closed");
+    }
+
+    #[instrument(name = "spin_trigger_websocket.handle_message", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("{} message", ctx.path),
+        otel.kind = "server",
+        websocket.connection.id = %ctx.connection_id,
+        websocket.connection.group = %ctx.group,
+    ))]
+    async fn dispatch_handler(
+        &self,
+        component_id: &str,
+        ctx: &ConnectionContext,
+        message: &[u8],
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        tracing::trace!("Executing WebSocket component {component_id}");
+
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "websocket",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let (instance, mut store) = self
+            .trigger_app
+            .prepare(component_id)?
+            .instantiate(())
+            .await?;
+
+        let pre = instance.instance_pre(&store);
+        let guest_indices = inbound_websocket::GuestIndices::new(&pre)?;
+        let guest = guest_indices.load(&mut store, &instance)?;
+
+        match guest.call_on_message(&mut store, ctx, message).await? {
+            Ok(replies) => Ok(replies),
+            Err(inbound_websocket::Error::Other(err)) => {
+                Err(anyhow!(err).context("WebSocket handler returned an error"))
+            }
+        }
+    }
+}
+
+/// The message sending a reply: text if the message it replies to was text
+/// and the reply is valid UTF-8, binary otherwise.
+fn reply_message(reply: Vec<u8>, is_text: bool) -> Message {
+    if is_text {
+        match String::from_utf8(reply) {
+            Ok(text) => Message::text(text),
+            Err(err) => Message::binary(err.into_bytes()),
+        }
+    } else {
+        Message::binary(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_config_defaults() {
+        let config: TriggerConfig = toml::toml! {
+            component = "chat"
+            route = "/rooms/..."
+        }
+        .try_into()
+        .unwrap();
+        assert!(!config.broadcast);
+        assert_eq!("/rooms/lobby", config.group_for("/rooms/lobby"));
+
+        let config: TriggerConfig = toml::toml! {
+            component = "chat"
+            route = "/rooms/..."
+            group = "everyone"
+            broadcast = true
+        }
+        .try_into()
+        .unwrap();
+        assert!(config.broadcast);
+        assert_eq!("everyone", config.group_for("/rooms/lobby"));
+    }
+
+    #[test]
+    fn replies_match_the_message_type() {
+        assert_eq!(Message::text("hi"), reply_message(b"hi".to_vec(), true));
+        assert_eq!(
+            Message::binary(b"hi".to_vec()),
+            reply_message(b"hi".to_vec(), false)
+        );
+        assert_eq!(Message::binary(vec![0xff]), reply_message(vec![0xff], true));
+    }
+}
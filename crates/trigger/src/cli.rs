@@ -1,6 +1,8 @@
 mod initial_kv_setter;
+mod key_value_copy;
 mod launch_metadata;
-mod max_instance_memory;
+mod memory_warning;
+mod sqlite_migrations;
 mod sqlite_statements;
 mod stdio;
 mod summary;
@@ -10,17 +12,19 @@ use std::{future::Future, sync::Arc};
 
 use anyhow::{Context, Result};
 use clap::{Args, IntoApp, Parser};
-use spin_app::App;
+use spin_app::{limits::LimitsResolver, App};
 use spin_common::sloth;
 use spin_common::ui::quoted_path;
 use spin_common::url::parse_file_url;
 use spin_factors::RuntimeFactors;
-use spin_factors_executor::{ComponentLoader, FactorsExecutor};
+use spin_factors_executor::{ComponentLoader, FactorsExecutor, FactorsExecutorApp};
 
 use crate::{loader::ComponentLoader as ComponentLoaderImpl, Trigger, TriggerApp};
 pub use initial_kv_setter::InitialKvSetterHook;
+pub use key_value_copy::KeyValueCopyHook;
 pub use launch_metadata::LaunchMetadata;
-pub use max_instance_memory::MaxInstanceMemoryHook;
+pub use memory_warning::MemoryWarningHook;
+pub use sqlite_migrations::SqliteMigrationsHook;
 pub use sqlite_statements::SqlStatementExecutorHook;
 use stdio::FollowComponents;
 pub use stdio::StdioLoggingExecutorHooks;
@@ -101,7 +105,8 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
         )]
     pub silence_component_logs: bool,
 
-    /// Configuration file for config providers and wasmtime config.
+    /// Configuration file for config providers and wasmtime config. It may
+    /// include other runtime config files with `include = ["base.toml"]`.
     #[clap(
         name = RUNTIME_CONFIG_FILE,
         long = "runtime-config-file",
@@ -109,6 +114,19 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
     )]
     pub runtime_config_file: Option<PathBuf>,
 
+    /// If set, Spin watches the runtime config file and reloads variables
+    /// providers when it changes.
+    #[clap(
+        long = "watch-runtime-config",
+        requires = RUNTIME_CONFIG_FILE,
+    )]
+    pub watch_runtime_config: bool,
+
+    /// The maximum number of component instances to create at once. Requests
+    /// for further instances wait until one has been created.
+    #[clap(long, env = "SPIN_MAX_PARALLEL_INSTANTIATIONS")]
+    pub max_parallel_instantiations: Option<usize>,
+
     /// Set the application state directory path. This is used in the default
     /// locations for logs, key value stores, etc.
     ///
@@ -138,6 +156,10 @@ pub struct FactorsConfig {
     pub working_dir: PathBuf,
     /// Path to the runtime config file.
     pub runtime_config_file: Option<PathBuf>,
+    /// If set, the runtime config file is watched for changes.
+    pub watch_runtime_config: bool,
+    /// The maximum number of component instances to create at once.
+    pub max_parallel_instantiations: Option<usize>,
     /// Path to the state directory.
     pub state_dir: UserProvidedPath,
     /// Path to the local app directory.
@@ -225,6 +247,8 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
         let common_options = FactorsConfig {
             working_dir: PathBuf::from(working_dir),
             runtime_config_file: self.runtime_config_file.clone(),
+            watch_runtime_config: self.watch_runtime_config,
+            max_parallel_instantiations: self.max_parallel_instantiations,
             state_dir,
             local_app_dir: local_app_dir.clone(),
             follow_components,
@@ -285,6 +309,17 @@ fn warn_if_wasm_build_slothful() -> sloth::SlothGuard {
     sloth::warn_if_slothful(SLOTH_WARNING_DELAY_MILLIS, format!("{message}\n"))
 }
 
+/// Prints the resolved limits of each component which has any.
+fn summarize_limits<F: RuntimeFactors, U: Send + 'static>(app: &FactorsExecutorApp<F, U>) {
+    for component_id in app.list_components() {
+        if let Ok(limits) = app.limits(component_id) {
+            if !limits.is_empty() {
+                println!("Limits for component {component_id:?}: {limits}");
+            }
+        }
+    }
+}
+
 fn help_heading<T: Trigger<F>, F: RuntimeFactors>() -> Option<&'static str> {
     if T::TYPE == <help::HelpArgsOnlyTrigger as Trigger<F>>::TYPE {
         Some("TRIGGER OPTIONS")
@@ -295,6 +330,16 @@ fn help_heading<T: Trigger<F>, F: RuntimeFactors>() -> Option<&'static str> {
     }
 }
 
+/// Returns whether any of the app's components has a fuel limit. A component
+/// whose limits are invalid is reported when the app is loaded.
+fn limits_fuel(app: &App, limits: &LimitsResolver) -> bool {
+    app.components().any(|component| {
+        limits
+            .resolve(&component)
+            .is_ok_and(|limits| limits.max_fuel.is_some())
+    })
+}
+
 /// A builder for a [`TriggerApp`].
 pub struct TriggerAppBuilder<T, B> {
     engine_config: spin_core::Config,
@@ -323,16 +368,34 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         options: B::CliArgs,
         loader: &impl ComponentLoader<B::Factors, T::InstanceState>,
     ) -> anyhow::Result<TriggerApp<T, B::Factors>> {
+        let (factors, runtime_config) = B::build(&common_options, &options)?;
+        let limits = B::limits(&runtime_config, &options);
+
         let mut core_engine_builder = {
             self.trigger.update_core_config(&mut self.engine_config)?;
+            // Metering fuel slows every instance down, so is only enabled if
+            // some component's fuel is limited
+            if limits_fuel(&app, &limits) {
+                self.engine_config.consume_fuel(true);
+            }
 
             spin_core::Engine::builder(&self.engine_config)?
         };
         self.trigger.add_to_linker(core_engine_builder.linker())?;
 
-        let (factors, runtime_config) = B::build(&common_options, &options)?;
+        if let Some(config) = B::trigger_runtime_config(&runtime_config, T::TYPE) {
+            self.trigger
+                .update_runtime_config(config)
+                .with_context(|| format!("invalid runtime config for the {} trigger", T::TYPE))?;
+        }
+        self.trigger
+            .set_state_dir(B::state_dir(&runtime_config).as_deref());
 
         let mut executor = FactorsExecutor::new(core_engine_builder, factors)?;
+        executor.set_limits(limits);
+        if let Some(n) = common_options.max_parallel_instantiations {
+            executor = executor.with_max_parallelism(n);
+        }
         B::configure_app(&mut executor, &runtime_config, &common_options, &options)?;
         let executor = Arc::new(executor);
 
@@ -342,6 +405,7 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
                 .load_app(app, runtime_config.into(), loader)
                 .await?
         };
+        summarize_limits(&configured_app);
 
         Ok(configured_app)
     }
@@ -355,7 +419,16 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         loader: &impl ComponentLoader<B::Factors, T::InstanceState>,
     ) -> anyhow::Result<impl Future<Output = anyhow::Result<()>>> {
         let configured_app = self.build(app, common_options, options, loader).await?;
-        Ok(self.trigger.run(configured_app))
+        // A background task failing under `TaskPolicy::FailApp` stops the trigger
+        let background_task_failure = configured_app.background_task_failure();
+        let run = self.trigger.run(configured_app);
+        Ok(async move {
+            futures::pin_mut!(run, background_task_failure);
+            match futures::future::select(run, background_task_failure).await {
+                futures::future::Either::Left((result, _)) => result,
+                futures::future::Either::Right((err, _)) => Err(err),
+            }
+        })
     }
 }
 
@@ -374,6 +447,29 @@ pub trait RuntimeFactorsBuilder {
         args: &Self::CliArgs,
     ) -> anyhow::Result<(Self::Factors, Self::RuntimeConfig)>;
 
+    /// Returns the section of the runtime config for the given trigger type,
+    /// e.g. `[trigger.http]`, if there is one.
+    fn trigger_runtime_config<'a>(
+        runtime_config: &'a Self::RuntimeConfig,
+        trigger_type: &str,
+    ) -> Option<&'a toml::Table> {
+        let _ = (runtime_config, trigger_type);
+        None
+    }
+
+    /// Returns the resolved state directory, if there is one.
+    fn state_dir(runtime_config: &Self::RuntimeConfig) -> Option<PathBuf> {
+        let _ = runtime_config;
+        None
+    }
+
+    /// Returns the resolver of the limits which apply to the app's
+    /// components.
+    fn limits(runtime_config: &Self::RuntimeConfig, args: &Self::CliArgs) -> LimitsResolver {
+        let _ = (runtime_config, args);
+        LimitsResolver::default()
+    }
+
     /// Configure the factors in the executor.
     fn configure_app<U: Send + 'static>(
         executor: &mut FactorsExecutor<Self::Factors, U>,
@@ -0,0 +1,64 @@
+use anyhow::Context as _;
+use spin_core::async_trait;
+use spin_factor_key_value::{CopyOptions, KeyValueFactor};
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::ExecutorHooks;
+
+/// The number of failed keys named in the error when a copy fails.
+const FAILED_KEYS_SHOWN: usize = 5;
+
+/// An [`ExecutorHooks`] that copies keys between key-value stores before any
+/// component runs, e.g. to migrate data from the default local store to a
+/// remote one.
+///
+/// Keys already present in the destination store are left as they are, so a
+/// copy which failed part way can be retried.
+pub struct KeyValueCopyHook {
+    copies: Vec<(String, String)>,
+}
+
+impl KeyValueCopyHook {
+    /// Creates a hook copying each `(from, to)` pair of store labels, in order.
+    pub fn new(copies: Vec<(String, String)>) -> Self {
+        Self { copies }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for KeyValueCopyHook {
+    async fn configure_app(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> anyhow::Result<()> {
+        if self.copies.is_empty() {
+            return Ok(());
+        }
+        let kv = configured_app.app_state::<KeyValueFactor>().context(
+            "attempted to copy key-value stores but the key-value factor was not configured",
+        )?;
+        for (from, to) in &self.copies {
+            let report = kv.copy_store(from, to, CopyOptions::default()).await?;
+            println!(
+                "Copied key-value store {from:?} to {to:?}: {} copied, {} skipped.",
+                report.copied, report.skipped
+            );
+            if !report.failed.is_empty() {
+                let mut failed = report
+                    .failed
+                    .iter()
+                    .take(FAILED_KEYS_SHOWN)
+                    .map(|(key, err)| format!("{key:?} ({err:?})"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if report.failed.len() > FAILED_KEYS_SHOWN {
+                    failed.push_str(", ...");
+                }
+                anyhow::bail!(
+                    "failed to copy {} keys from key-value store {from:?} to {to:?}: {failed}",
+                    report.failed.len()
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use spin_core::async_trait;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
+
+/// An [`ExecutorHooks`] that warns the first time an instance's memory use
+/// reaches a percentage of its component's `max_memory_bytes` limit.
+pub struct MemoryWarningHook {
+    warning_percent: u8,
+}
+
+impl MemoryWarningHook {
+    pub fn new(warning_percent: u8) -> Self {
+        Self { warning_percent }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for MemoryWarningHook {
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        // Without a limit there is nothing to take a percentage of
+        if builder.limits().max_memory_bytes.is_some() {
+            builder
+                .store_builder()
+                .memory_warning_threshold(self.warning_percent);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use spin_core::async_trait;
+use spin_factor_sqlite::SqliteFactor;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::ExecutorHooks;
+
+/// An [`ExecutorHooks`] that applies the app's SQLite migrations, as declared
+/// in `[application.sqlite_migrations]`, before any component runs.
+///
+/// The hook is ignored if the app does not have access to `SqliteFactor`.
+pub struct SqliteMigrationsHook;
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for SqliteMigrationsHook {
+    async fn configure_app(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> anyhow::Result<()> {
+        let Ok(sqlite) = configured_app.app_state::<SqliteFactor>() else {
+            return Ok(());
+        };
+        sqlite.run_migrations().await
+    }
+}
@@ -109,7 +109,7 @@ mod tests {
 
     use spin_core::async_trait;
     use spin_factor_sqlite::{Connection, ConnectionCreator};
-    use spin_world::spin::sqlite::sqlite as v3;
+    use spin_world::spin::sqlite3_0_0::sqlite as v3;
     use tempfile::NamedTempFile;
 
     use super::*;
@@ -7,7 +7,7 @@ use std::{
 use anyhow::{Context, Result};
 use spin_common::ui::quoted_path;
 use spin_core::async_trait;
-use spin_factor_wasi::WasiFactor;
+use spin_factor_wasi::{PipeOptions, WasiFactor};
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::ExecutorHooks;
 use tokio::io::AsyncWrite;
@@ -15,6 +15,9 @@ use tokio::io::AsyncWrite;
 pub const STDOUT_LOG_FILE_SUFFIX: &str = "stdout";
 pub const STDERR_LOG_FILE_SUFFIX: &str = "stderr";
 
+/// The longest line of component stdio logged before it is truncated.
+pub const MAX_STDIO_LINE_BYTES: usize = 16 * 1024;
+
 /// Which components should have their logs followed on stdout/stderr.
 #[derive(Clone, Debug, Default)]
 pub enum FollowComponents {
@@ -144,16 +147,22 @@ impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for StdioLoggingExecutorHooks {
         let Some(wasi_builder) = builder.factor_builder::<WasiFactor>() else {
             return Ok(());
         };
-        wasi_builder.stdout_pipe(self.component_stdio_writer(
-            &component_id,
-            STDOUT_LOG_FILE_SUFFIX,
-            self.log_dir.as_deref(),
-        )?);
-        wasi_builder.stderr_pipe(self.component_stdio_writer(
-            &component_id,
-            STDERR_LOG_FILE_SUFFIX,
-            self.log_dir.as_deref(),
-        )?);
+        wasi_builder.stdout_pipe_with(
+            self.component_stdio_writer(
+                &component_id,
+                STDOUT_LOG_FILE_SUFFIX,
+                self.log_dir.as_deref(),
+            )?,
+            PipeOptions::line_mode(MAX_STDIO_LINE_BYTES),
+        );
+        wasi_builder.stderr_pipe_with(
+            self.component_stdio_writer(
+                &component_id,
+                STDERR_LOG_FILE_SUFFIX,
+                self.log_dir.as_deref(),
+            )?,
+            PipeOptions::line_mode(MAX_STDIO_LINE_BYTES),
+        );
         Ok(())
     }
 }
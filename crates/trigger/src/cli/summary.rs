@@ -1,10 +1,11 @@
 use spin_core::async_trait;
-use spin_factor_key_value::KeyValueFactor;
+use spin_factor_key_value::{CasSupport, KeyValueFactor};
 use spin_factor_sqlite::SqliteFactor;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::ExecutorHooks;
 
-/// An [`ExecutorHooks`] that prints information about the default KV store.
+/// An [`ExecutorHooks`] that prints information about the default KV store
+/// and any store aliases.
 pub struct KeyValueDefaultStoreSummaryHook;
 
 #[async_trait]
@@ -16,6 +17,14 @@ impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for KeyValueDefaultStoreSummaryHo
         let Ok(kv_app_state) = configured_app.app_state::<KeyValueFactor>() else {
             return Ok(());
         };
+        for chain in kv_app_state.used_store_aliases() {
+            let chain = chain
+                .iter()
+                .map(|label| format!("{label:?}"))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            println!("Key-value store alias {chain}.");
+        }
         if !kv_app_state.store_is_used("default") {
             // We don't talk about unused default stores
             return Ok(());
@@ -23,6 +32,9 @@ impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for KeyValueDefaultStoreSummaryHo
         if let Some(default_store_summary) = kv_app_state.store_summary("default") {
             println!("Storing default key-value data to {default_store_summary}.");
         }
+        if kv_app_state.cas_support("default").await == Some(CasSupport::Emulated) {
+            println!("Emulating compare-and-swap for the default key-value store.");
+        }
         Ok(())
     }
 }
@@ -1,7 +1,7 @@
 pub mod cli;
 pub mod loader;
 
-use std::future::Future;
+use std::{future::Future, path::Path};
 
 use clap::Args;
 use spin_core::Linker;
@@ -50,6 +50,22 @@ pub trait Trigger<F: RuntimeFactors>: Sized + Send {
         Ok(())
     }
 
+    /// Update this trigger from its section of the runtime config, e.g.
+    /// `[trigger.http]` for the trigger with [`Trigger::TYPE`] `"http"`.
+    ///
+    /// This is only called if the runtime config has such a section; by
+    /// default, triggers don't accept any runtime config.
+    fn update_runtime_config(&mut self, config: &toml::Table) -> anyhow::Result<()> {
+        let _ = config;
+        anyhow::bail!("the {} trigger has no runtime config options", Self::TYPE)
+    }
+
+    /// Tells this trigger the app's state directory, if it has one, e.g. for
+    /// temporary files the trigger keeps while handling events.
+    fn set_state_dir(&mut self, state_dir: Option<&Path>) {
+        let _ = state_dir;
+    }
+
     /// Update the [`Linker`] for this trigger.
     fn add_to_linker(
         &mut self,
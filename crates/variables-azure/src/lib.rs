@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use anyhow::Context as _;
-use azure_core::{auth::TokenCredential, Url};
+use azure_core::{auth::TokenCredential, error::ErrorKind, StatusCode, Url};
 use azure_security_keyvault::SecretClient;
 use serde::Deserialize;
 use spin_expressions::{Key, Provider};
@@ -21,6 +21,8 @@ pub struct AzureKeyVaultVariablesConfig {
     pub client_secret: Option<String>,
     pub tenant_id: Option<String>,
     pub authority_host: Option<AzureAuthorityHost>,
+    /// A prefix added to the secret name for every variable, e.g. `myapp-`.
+    pub prefix: Option<String>,
 }
 
 #[derive(Debug, Copy, Clone, Deserialize, Default)]
@@ -104,9 +106,23 @@ pub enum AzureKeyVaultAuthOptions {
 }
 
 /// A provider that fetches variables from Azure Key Vault.
+///
+/// Key Vault secret names may only contain alphanumerics and dashes, so a
+/// variable is read from the secret named after the variable's key with
+/// underscores replaced by dashes, following any configured prefix. For
+/// example, with the prefix `myapp-` the variable `db_password` is read from
+/// the secret `myapp-db-password`.
+///
+/// A secret which doesn't exist resolves to `None`, so that the next provider
+/// is tried; other failures, such as authentication or network errors, are
+/// returned as errors.
+///
+/// Secrets are fetched every time a variable is resolved; lookups are cached
+/// for all providers by the `variables_cache_ttl_secs` runtime config.
 #[derive(Debug)]
 pub struct AzureKeyVaultProvider {
-    secret_client: SecretClient,
+    secrets: Box<dyn SecretSource>,
+    prefix: String,
 }
 
 impl AzureKeyVaultProvider {
@@ -134,9 +150,44 @@ impl AzureKeyVaultProvider {
             AzureKeyVaultAuthOptions::Environmental => azure_identity::create_default_credential()?,
         };
 
-        Ok(Self {
-            secret_client: SecretClient::new(&vault_url.into(), token_credential)?,
-        })
+        let secret_client = SecretClient::new(&vault_url.into(), token_credential)?;
+        Ok(Self::with_source(secret_client))
+    }
+
+    /// Creates a provider from its runtime config.
+    pub fn from_config(config: AzureKeyVaultVariablesConfig) -> anyhow::Result<Self> {
+        let vault_url = config.vault_url.clone();
+        let prefix = config.prefix.clone();
+        let mut provider = Self::create(vault_url, config.try_into()?)?;
+        if let Some(prefix) = prefix {
+            provider = provider.with_prefix(prefix)?;
+        }
+        Ok(provider)
+    }
+
+    fn with_source(secrets: impl SecretSource + 'static) -> Self {
+        Self {
+            secrets: Box::new(secrets),
+            prefix: String::new(),
+        }
+    }
+
+    /// Sets a prefix added to the secret name for every variable.
+    ///
+    /// The prefix may only contain alphanumerics and dashes.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> anyhow::Result<Self> {
+        let prefix = prefix.into();
+        anyhow::ensure!(
+            prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+            "Invalid Azure Key Vault secret prefix {prefix:?}: secret names may only contain alphanumerics and dashes"
+        );
+        self.prefix = prefix;
+        Ok(self)
+    }
+
+    /// The name of the secret holding the given variable.
+    fn secret_name(&self, key: &Key) -> String {
+        format!("{}{}", self.prefix, key.as_str().replace('_', "-"))
     }
 }
 
@@ -144,12 +195,39 @@ impl AzureKeyVaultProvider {
 impl Provider for AzureKeyVaultProvider {
     #[instrument(name = "spin_variables.get_from_azure_key_vault", level = Level::DEBUG, skip(self), err(level = Level::INFO), fields(otel.kind = "client"))]
     async fn get(&self, key: &Key) -> anyhow::Result<Option<String>> {
-        let secret = self
-            .secret_client
-            .get(key.as_str())
+        self.secrets
+            .get_secret(&self.secret_name(key))
             .await
-            .context("Failed to read variable from Azure Key Vault")?;
-        Ok(Some(secret.value))
+            .context("Failed to read variable from Azure Key Vault")
+    }
+}
+
+/// A source of Key Vault secrets, abstracted for testing.
+#[async_trait]
+trait SecretSource: std::fmt::Debug + Send + Sync {
+    /// Fetches the secret with the given name, returning `None` if it doesn't
+    /// exist.
+    async fn get_secret(&self, name: &str) -> anyhow::Result<Option<String>>;
+}
+
+#[async_trait]
+impl SecretSource for SecretClient {
+    async fn get_secret(&self, name: &str) -> anyhow::Result<Option<String>> {
+        match self.get(name).await {
+            Ok(secret) => Ok(Some(secret.value)),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::HttpResponse {
+                        status: StatusCode::NotFound,
+                        ..
+                    }
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
     }
 }
 
@@ -164,3 +242,63 @@ impl From<AzureAuthorityHost> for Url {
         Url::parse(url).unwrap()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// A stand-in for the Key Vault HTTP API.
+    #[derive(Debug, Default)]
+    struct MockSecrets {
+        secrets: HashMap<String, String>,
+        unavailable: bool,
+    }
+
+    #[async_trait]
+    impl SecretSource for MockSecrets {
+        async fn get_secret(&self, name: &str) -> anyhow::Result<Option<String>> {
+            anyhow::ensure!(!self.unavailable, "401 Unauthorized");
+            Ok(self.secrets.get(name).cloned())
+        }
+    }
+
+    fn mock_provider(secrets: &[(&str, &str)]) -> AzureKeyVaultProvider {
+        AzureKeyVaultProvider::with_source(MockSecrets {
+            secrets: secrets
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn keys_are_mapped_to_secret_names() {
+        let provider = mock_provider(&[("myapp-db-password", "hunter2")]);
+        let provider = provider.with_prefix("myapp-").unwrap();
+        let key = Key::new("db_password").unwrap();
+        assert_eq!(
+            provider.get(&key).await.unwrap().as_deref(),
+            Some("hunter2")
+        );
+
+        assert!(AzureKeyVaultProvider::with_source(MockSecrets::default())
+            .with_prefix("my_app")
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn missing_secrets_are_none_but_failures_are_errors() {
+        let provider = mock_provider(&[]);
+        let key = Key::new("db_password").unwrap();
+        assert_eq!(provider.get(&key).await.unwrap(), None);
+
+        let provider = AzureKeyVaultProvider::with_source(MockSecrets {
+            unavailable: true,
+            ..Default::default()
+        });
+        assert!(provider.get(&key).await.is_err());
+    }
+}
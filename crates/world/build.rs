@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+/// The WIT packages encoded into the crate, so that hosts can describe the
+/// worlds they support without needing the WIT sources at runtime.
+const ENCODED_PACKAGES: &[&str] = &["spin:up@3.5.0", "fermyon:spin@2.0.0", "fermyon:spin"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=../../wit");
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+
+    let mut resolve = wit_parser::Resolve::default();
+    resolve
+        .push_dir("../../wit")
+        .expect("failed to parse WIT directory");
+
+    let mut entries = String::new();
+    for name in ENCODED_PACKAGES {
+        let (package_id, _) = resolve
+            .packages
+            .iter()
+            .find(|(_, package)| package.name.to_string() == *name)
+            .unwrap_or_else(|| panic!("WIT package {name} not found"));
+        let bytes = wit_component::encode(&resolve, package_id)
+            .unwrap_or_else(|e| panic!("failed to encode WIT package {name}: {e:?}"));
+        let path = out_dir.join(format!("{}.wasm", name.replace([':', '@'], "_")));
+        std::fs::write(&path, bytes).expect("failed to write encoded WIT package");
+        entries.push_str(&format!(
+            "    ({name:?}, include_bytes!({:?})),\n",
+            path.display().to_string()
+        ));
+    }
+    std::fs::write(out_dir.join("wit_packages.rs"), format!("&[\n{entries}]\n"))
+        .expect("failed to write WIT package list");
+}
@@ -63,6 +63,28 @@ mod rdbms_types {
         }
     }
 
+    impl From<v1::rdbms_types::DbValue> for v2::rdbms_types::DbValue {
+        fn from(value: v1::rdbms_types::DbValue) -> v2::rdbms_types::DbValue {
+            match value {
+                v1::rdbms_types::DbValue::Boolean(b) => v2::rdbms_types::DbValue::Boolean(b),
+                v1::rdbms_types::DbValue::Int8(i) => v2::rdbms_types::DbValue::Int8(i),
+                v1::rdbms_types::DbValue::Int16(i) => v2::rdbms_types::DbValue::Int16(i),
+                v1::rdbms_types::DbValue::Int32(i) => v2::rdbms_types::DbValue::Int32(i),
+                v1::rdbms_types::DbValue::Int64(i) => v2::rdbms_types::DbValue::Int64(i),
+                v1::rdbms_types::DbValue::Uint8(u) => v2::rdbms_types::DbValue::Uint8(u),
+                v1::rdbms_types::DbValue::Uint16(u) => v2::rdbms_types::DbValue::Uint16(u),
+                v1::rdbms_types::DbValue::Uint32(u) => v2::rdbms_types::DbValue::Uint32(u),
+                v1::rdbms_types::DbValue::Uint64(u) => v2::rdbms_types::DbValue::Uint64(u),
+                v1::rdbms_types::DbValue::Floating32(r) => v2::rdbms_types::DbValue::Floating32(r),
+                v1::rdbms_types::DbValue::Floating64(r) => v2::rdbms_types::DbValue::Floating64(r),
+                v1::rdbms_types::DbValue::Str(s) => v2::rdbms_types::DbValue::Str(s),
+                v1::rdbms_types::DbValue::Binary(b) => v2::rdbms_types::DbValue::Binary(b),
+                v1::rdbms_types::DbValue::DbNull => v2::rdbms_types::DbValue::DbNull,
+                v1::rdbms_types::DbValue::Unsupported => v2::rdbms_types::DbValue::Unsupported,
+            }
+        }
+    }
+
     impl From<pg4::DbValue> for v1::rdbms_types::DbValue {
         fn from(value: pg4::DbValue) -> v1::rdbms_types::DbValue {
             match value {
@@ -76,8 +98,22 @@ mod rdbms_types {
                 pg4::DbValue::Str(s) => v1::rdbms_types::DbValue::Str(s),
                 pg4::DbValue::Binary(b) => v1::rdbms_types::DbValue::Binary(b),
                 pg4::DbValue::DbNull => v1::rdbms_types::DbValue::DbNull,
-                pg4::DbValue::Unsupported(_) => v1::rdbms_types::DbValue::Unsupported,
-                _ => v1::rdbms_types::DbValue::Unsupported,
+                pg4::DbValue::Date(_)
+                | pg4::DbValue::Time(_)
+                | pg4::DbValue::Datetime(_)
+                | pg4::DbValue::Timestamp(_)
+                | pg4::DbValue::Uuid(_)
+                | pg4::DbValue::Jsonb(_)
+                | pg4::DbValue::Decimal(_)
+                | pg4::DbValue::RangeInt32(_)
+                | pg4::DbValue::RangeInt64(_)
+                | pg4::DbValue::RangeDecimal(_)
+                | pg4::DbValue::ArrayInt32(_)
+                | pg4::DbValue::ArrayInt64(_)
+                | pg4::DbValue::ArrayDecimal(_)
+                | pg4::DbValue::ArrayStr(_)
+                | pg4::DbValue::Interval(_)
+                | pg4::DbValue::Unsupported(_) => v1::rdbms_types::DbValue::Unsupported,
             }
         }
     }
@@ -95,8 +131,22 @@ mod rdbms_types {
                 pg4::DbValue::Str(s) => v2::rdbms_types::DbValue::Str(s),
                 pg4::DbValue::Binary(b) => v2::rdbms_types::DbValue::Binary(b),
                 pg4::DbValue::DbNull => v2::rdbms_types::DbValue::DbNull,
-                pg4::DbValue::Unsupported(_) => v2::rdbms_types::DbValue::Unsupported,
-                _ => v2::rdbms_types::DbValue::Unsupported,
+                pg4::DbValue::Date(_)
+                | pg4::DbValue::Time(_)
+                | pg4::DbValue::Datetime(_)
+                | pg4::DbValue::Timestamp(_)
+                | pg4::DbValue::Uuid(_)
+                | pg4::DbValue::Jsonb(_)
+                | pg4::DbValue::Decimal(_)
+                | pg4::DbValue::RangeInt32(_)
+                | pg4::DbValue::RangeInt64(_)
+                | pg4::DbValue::RangeDecimal(_)
+                | pg4::DbValue::ArrayInt32(_)
+                | pg4::DbValue::ArrayInt64(_)
+                | pg4::DbValue::ArrayDecimal(_)
+                | pg4::DbValue::ArrayStr(_)
+                | pg4::DbValue::Interval(_)
+                | pg4::DbValue::Unsupported(_) => v2::rdbms_types::DbValue::Unsupported,
             }
         }
     }
@@ -134,6 +184,29 @@ mod rdbms_types {
         }
     }
 
+    impl From<pg3::DbValue> for pg4::DbValue {
+        fn from(value: pg3::DbValue) -> pg4::DbValue {
+            match value {
+                pg3::DbValue::Boolean(b) => pg4::DbValue::Boolean(b),
+                pg3::DbValue::Int8(i) => pg4::DbValue::Int8(i),
+                pg3::DbValue::Int16(i) => pg4::DbValue::Int16(i),
+                pg3::DbValue::Int32(i) => pg4::DbValue::Int32(i),
+                pg3::DbValue::Int64(i) => pg4::DbValue::Int64(i),
+                pg3::DbValue::Floating32(r) => pg4::DbValue::Floating32(r),
+                pg3::DbValue::Floating64(r) => pg4::DbValue::Floating64(r),
+                pg3::DbValue::Str(s) => pg4::DbValue::Str(s),
+                pg3::DbValue::Binary(b) => pg4::DbValue::Binary(b),
+                pg3::DbValue::Date(d) => pg4::DbValue::Date(d),
+                pg3::DbValue::Datetime(dt) => pg4::DbValue::Datetime(dt),
+                pg3::DbValue::Time(t) => pg4::DbValue::Time(t),
+                pg3::DbValue::Timestamp(t) => pg4::DbValue::Timestamp(t),
+                pg3::DbValue::DbNull => pg4::DbValue::DbNull,
+                // pg3 doesn't keep the raw bytes of unsupported values
+                pg3::DbValue::Unsupported => pg4::DbValue::Unsupported(vec![]),
+            }
+        }
+    }
+
     impl From<pg4::DbDataType> for v1::rdbms_types::DbDataType {
         fn from(value: pg4::DbDataType) -> v1::rdbms_types::DbDataType {
             match value {
@@ -146,8 +219,22 @@ mod rdbms_types {
                 pg4::DbDataType::Floating64 => v1::rdbms_types::DbDataType::Floating64,
                 pg4::DbDataType::Str => v1::rdbms_types::DbDataType::Str,
                 pg4::DbDataType::Binary => v1::rdbms_types::DbDataType::Binary,
-                pg4::DbDataType::Other(_) => v1::rdbms_types::DbDataType::Other,
-                _ => v1::rdbms_types::DbDataType::Other,
+                pg4::DbDataType::Date
+                | pg4::DbDataType::Time
+                | pg4::DbDataType::Datetime
+                | pg4::DbDataType::Timestamp
+                | pg4::DbDataType::Uuid
+                | pg4::DbDataType::Jsonb
+                | pg4::DbDataType::Decimal
+                | pg4::DbDataType::RangeInt32
+                | pg4::DbDataType::RangeInt64
+                | pg4::DbDataType::RangeDecimal
+                | pg4::DbDataType::ArrayInt32
+                | pg4::DbDataType::ArrayInt64
+                | pg4::DbDataType::ArrayDecimal
+                | pg4::DbDataType::ArrayStr
+                | pg4::DbDataType::Interval
+                | pg4::DbDataType::Other(_) => v1::rdbms_types::DbDataType::Other,
             }
         }
     }
@@ -164,8 +251,22 @@ mod rdbms_types {
                 pg4::DbDataType::Floating64 => v2::rdbms_types::DbDataType::Floating64,
                 pg4::DbDataType::Str => v2::rdbms_types::DbDataType::Str,
                 pg4::DbDataType::Binary => v2::rdbms_types::DbDataType::Binary,
-                pg4::DbDataType::Other(_) => v2::rdbms_types::DbDataType::Other,
-                _ => v2::rdbms_types::DbDataType::Other,
+                pg4::DbDataType::Date
+                | pg4::DbDataType::Time
+                | pg4::DbDataType::Datetime
+                | pg4::DbDataType::Timestamp
+                | pg4::DbDataType::Uuid
+                | pg4::DbDataType::Jsonb
+                | pg4::DbDataType::Decimal
+                | pg4::DbDataType::RangeInt32
+                | pg4::DbDataType::RangeInt64
+                | pg4::DbDataType::RangeDecimal
+                | pg4::DbDataType::ArrayInt32
+                | pg4::DbDataType::ArrayInt64
+                | pg4::DbDataType::ArrayDecimal
+                | pg4::DbDataType::ArrayStr
+                | pg4::DbDataType::Interval
+                | pg4::DbDataType::Other(_) => v2::rdbms_types::DbDataType::Other,
             }
         }
     }
@@ -357,6 +458,90 @@ mod rdbms_types {
         }
     }
 
+    impl From<v2::rdbms_types::ParameterValue> for v1::rdbms_types::ParameterValue {
+        fn from(value: v2::rdbms_types::ParameterValue) -> v1::rdbms_types::ParameterValue {
+            match value {
+                v2::rdbms_types::ParameterValue::Boolean(b) => {
+                    v1::rdbms_types::ParameterValue::Boolean(b)
+                }
+                v2::rdbms_types::ParameterValue::Int8(i) => {
+                    v1::rdbms_types::ParameterValue::Int8(i)
+                }
+                v2::rdbms_types::ParameterValue::Int16(i) => {
+                    v1::rdbms_types::ParameterValue::Int16(i)
+                }
+                v2::rdbms_types::ParameterValue::Int32(i) => {
+                    v1::rdbms_types::ParameterValue::Int32(i)
+                }
+                v2::rdbms_types::ParameterValue::Int64(i) => {
+                    v1::rdbms_types::ParameterValue::Int64(i)
+                }
+                v2::rdbms_types::ParameterValue::Uint8(u) => {
+                    v1::rdbms_types::ParameterValue::Uint8(u)
+                }
+                v2::rdbms_types::ParameterValue::Uint16(u) => {
+                    v1::rdbms_types::ParameterValue::Uint16(u)
+                }
+                v2::rdbms_types::ParameterValue::Uint32(u) => {
+                    v1::rdbms_types::ParameterValue::Uint32(u)
+                }
+                v2::rdbms_types::ParameterValue::Uint64(u) => {
+                    v1::rdbms_types::ParameterValue::Uint64(u)
+                }
+                v2::rdbms_types::ParameterValue::Floating32(r) => {
+                    v1::rdbms_types::ParameterValue::Floating32(r)
+                }
+                v2::rdbms_types::ParameterValue::Floating64(r) => {
+                    v1::rdbms_types::ParameterValue::Floating64(r)
+                }
+                v2::rdbms_types::ParameterValue::Str(s) => v1::rdbms_types::ParameterValue::Str(s),
+                v2::rdbms_types::ParameterValue::Binary(b) => {
+                    v1::rdbms_types::ParameterValue::Binary(b)
+                }
+                v2::rdbms_types::ParameterValue::DbNull => v1::rdbms_types::ParameterValue::DbNull,
+            }
+        }
+    }
+
+    impl TryFrom<pg4::ParameterValue> for pg3::ParameterValue {
+        type Error = pg3::Error;
+
+        fn try_from(value: pg4::ParameterValue) -> Result<pg3::ParameterValue, Self::Error> {
+            let converted = match value {
+                pg4::ParameterValue::Boolean(b) => pg3::ParameterValue::Boolean(b),
+                pg4::ParameterValue::Int8(i) => pg3::ParameterValue::Int8(i),
+                pg4::ParameterValue::Int16(i) => pg3::ParameterValue::Int16(i),
+                pg4::ParameterValue::Int32(i) => pg3::ParameterValue::Int32(i),
+                pg4::ParameterValue::Int64(i) => pg3::ParameterValue::Int64(i),
+                pg4::ParameterValue::Floating32(r) => pg3::ParameterValue::Floating32(r),
+                pg4::ParameterValue::Floating64(r) => pg3::ParameterValue::Floating64(r),
+                pg4::ParameterValue::Str(s) => pg3::ParameterValue::Str(s),
+                pg4::ParameterValue::Binary(b) => pg3::ParameterValue::Binary(b),
+                pg4::ParameterValue::Date(d) => pg3::ParameterValue::Date(d),
+                pg4::ParameterValue::Datetime(dt) => pg3::ParameterValue::Datetime(dt),
+                pg4::ParameterValue::Time(t) => pg3::ParameterValue::Time(t),
+                pg4::ParameterValue::Timestamp(t) => pg3::ParameterValue::Timestamp(t),
+                pg4::ParameterValue::DbNull => pg3::ParameterValue::DbNull,
+                pg4::ParameterValue::Uuid(_)
+                | pg4::ParameterValue::Jsonb(_)
+                | pg4::ParameterValue::Decimal(_)
+                | pg4::ParameterValue::RangeInt32(_)
+                | pg4::ParameterValue::RangeInt64(_)
+                | pg4::ParameterValue::RangeDecimal(_)
+                | pg4::ParameterValue::ArrayInt32(_)
+                | pg4::ParameterValue::ArrayInt64(_)
+                | pg4::ParameterValue::ArrayDecimal(_)
+                | pg4::ParameterValue::ArrayStr(_)
+                | pg4::ParameterValue::Interval(_) => {
+                    return Err(pg3::Error::ValueConversionFailed(
+                        "Postgres 3.0.0 parameters cannot have this type".to_owned(),
+                    ));
+                }
+            };
+            Ok(converted)
+        }
+    }
+
     impl From<v2::rdbms_types::Error> for v1::mysql::MysqlError {
         fn from(error: v2::rdbms_types::Error) -> v1::mysql::MysqlError {
             match error {
@@ -492,6 +677,15 @@ mod redis {
         }
     }
 
+    impl From<v2::redis::RedisParameter> for v1::redis::RedisParameter {
+        fn from(value: v2::redis::RedisParameter) -> Self {
+            match value {
+                v2::redis::RedisParameter::Int64(i) => v1::redis::RedisParameter::Int64(i),
+                v2::redis::RedisParameter::Binary(b) => v1::redis::RedisParameter::Binary(b),
+            }
+        }
+    }
+
     impl From<v2::redis::RedisResult> for v1::redis::RedisResult {
         fn from(value: v2::redis::RedisResult) -> Self {
             match value {
@@ -502,10 +696,22 @@ mod redis {
             }
         }
     }
+
+    impl From<v1::redis::RedisResult> for v2::redis::RedisResult {
+        fn from(value: v1::redis::RedisResult) -> Self {
+            match value {
+                v1::redis::RedisResult::Nil => v2::redis::RedisResult::Nil,
+                v1::redis::RedisResult::Status(s) => v2::redis::RedisResult::Status(s),
+                v1::redis::RedisResult::Int64(i) => v2::redis::RedisResult::Int64(i),
+                v1::redis::RedisResult::Binary(b) => v2::redis::RedisResult::Binary(b),
+            }
+        }
+    }
 }
 
 mod llm {
     use super::*;
+    use spin::llm::llm as v3;
 
     impl From<v1::llm::InferencingParams> for v2::llm::InferencingParams {
         fn from(value: v1::llm::InferencingParams) -> Self {
@@ -520,6 +726,19 @@ mod llm {
         }
     }
 
+    impl From<v2::llm::InferencingParams> for v1::llm::InferencingParams {
+        fn from(value: v2::llm::InferencingParams) -> Self {
+            Self {
+                max_tokens: value.max_tokens,
+                repeat_penalty: value.repeat_penalty,
+                repeat_penalty_last_n_token_count: value.repeat_penalty_last_n_token_count,
+                temperature: value.temperature,
+                top_k: value.top_k,
+                top_p: value.top_p,
+            }
+        }
+    }
+
     impl From<v2::llm::InferencingResult> for v1::llm::InferencingResult {
         fn from(value: v2::llm::InferencingResult) -> Self {
             Self {
@@ -552,4 +771,479 @@ mod llm {
             }
         }
     }
+
+    impl From<v2::llm::InferencingParams> for v3::InferencingParams {
+        fn from(value: v2::llm::InferencingParams) -> Self {
+            Self {
+                max_tokens: value.max_tokens,
+                repeat_penalty: value.repeat_penalty,
+                repeat_penalty_last_n_token_count: value.repeat_penalty_last_n_token_count,
+                temperature: value.temperature,
+                top_k: value.top_k,
+                top_p: value.top_p,
+                cache_prompt_prefix: false,
+            }
+        }
+    }
+
+    impl From<v3::InferencingParams> for v2::llm::InferencingParams {
+        fn from(value: v3::InferencingParams) -> Self {
+            Self {
+                max_tokens: value.max_tokens,
+                repeat_penalty: value.repeat_penalty,
+                repeat_penalty_last_n_token_count: value.repeat_penalty_last_n_token_count,
+                temperature: value.temperature,
+                top_k: value.top_k,
+                top_p: value.top_p,
+            }
+        }
+    }
+
+    /// Converts a result produced without prompt caching, so every prompt
+    /// token counts as a cache miss.
+    impl From<v2::llm::InferencingResult> for v3::InferencingResult {
+        fn from(value: v2::llm::InferencingResult) -> Self {
+            Self {
+                text: value.text,
+                usage: v3::InferencingUsage {
+                    prompt_token_count: value.usage.prompt_token_count,
+                    generated_token_count: value.usage.generated_token_count,
+                    cache_hit_tokens: 0,
+                    cache_miss_tokens: value.usage.prompt_token_count,
+                },
+            }
+        }
+    }
+
+    impl From<v3::InferencingResult> for v2::llm::InferencingResult {
+        fn from(value: v3::InferencingResult) -> Self {
+            Self {
+                text: value.text,
+                usage: v2::llm::InferencingUsage {
+                    prompt_token_count: value.usage.prompt_token_count,
+                    generated_token_count: value.usage.generated_token_count,
+                },
+            }
+        }
+    }
+
+    impl From<v2::llm::EmbeddingsResult> for v3::EmbeddingsResult {
+        fn from(value: v2::llm::EmbeddingsResult) -> Self {
+            Self {
+                embeddings: value.embeddings,
+                usage: v3::EmbeddingsUsage {
+                    prompt_token_count: value.usage.prompt_token_count,
+                },
+            }
+        }
+    }
+
+    impl From<v2::llm::Error> for v3::Error {
+        fn from(value: v2::llm::Error) -> Self {
+            match value {
+                v2::llm::Error::ModelNotSupported => Self::ModelNotSupported,
+                v2::llm::Error::RuntimeError(s) => Self::RuntimeError(s),
+                v2::llm::Error::InvalidInput(s) => Self::InvalidInput(s),
+            }
+        }
+    }
+}
+
+mod key_value {
+    use super::*;
+    use spin::key_value::batch;
+    use wasi::keyvalue::store;
+
+    impl From<store::Error> for batch::BatchError {
+        fn from(value: store::Error) -> Self {
+            Self::StoreError(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use proptest::collection::vec;
+    use proptest::option;
+    use proptest::prelude::*;
+    use spin::llm::llm as v3;
+    use spin::postgres3_0_0::postgres as pg3;
+    use spin::postgres4_0_0::postgres as pg4;
+
+    use super::*;
+
+    type V1Param = v1::rdbms_types::ParameterValue;
+    type V2Param = v2::rdbms_types::ParameterValue;
+    type V1Value = v1::rdbms_types::DbValue;
+    type V2Value = v2::rdbms_types::DbValue;
+
+    /// Bindgen types don't implement `PartialEq`, so values are compared by
+    /// their debug output. This also treats NaN floats as equal.
+    fn debug(value: &impl Debug) -> String {
+        format!("{value:?}")
+    }
+
+    /// Any value of a v1 or v2 rdbms-types value enum, which share a shape.
+    macro_rules! rdbms_value {
+        ($ty:ident $(, $extra:expr)?) => {
+            prop_oneof![
+                any::<bool>().prop_map($ty::Boolean),
+                any::<i8>().prop_map($ty::Int8),
+                any::<i16>().prop_map($ty::Int16),
+                any::<i32>().prop_map($ty::Int32),
+                any::<i64>().prop_map($ty::Int64),
+                any::<u8>().prop_map($ty::Uint8),
+                any::<u16>().prop_map($ty::Uint16),
+                any::<u32>().prop_map($ty::Uint32),
+                any::<u64>().prop_map($ty::Uint64),
+                any::<f32>().prop_map($ty::Floating32),
+                any::<f64>().prop_map($ty::Floating64),
+                any::<String>().prop_map($ty::Str),
+                any::<Vec<u8>>().prop_map($ty::Binary),
+                Just($ty::DbNull),
+                $($extra,)?
+            ]
+        };
+    }
+
+    /// Any value of a Postgres 3 or 4 value enum, limited to the types that
+    /// Postgres 3 has.
+    macro_rules! pg3_value {
+        ($ty:path $(, $extra:expr)?) => {{
+            type T = $ty;
+            prop_oneof![
+                any::<bool>().prop_map(T::Boolean),
+                any::<i8>().prop_map(T::Int8),
+                any::<i16>().prop_map(T::Int16),
+                any::<i32>().prop_map(T::Int32),
+                any::<i64>().prop_map(T::Int64),
+                any::<f32>().prop_map(T::Floating32),
+                any::<f64>().prop_map(T::Floating64),
+                any::<String>().prop_map(T::Str),
+                any::<Vec<u8>>().prop_map(T::Binary),
+                any::<(i32, u8, u8)>().prop_map(T::Date),
+                any::<(u8, u8, u8, u32)>().prop_map(T::Time),
+                any::<(i32, u8, u8, u8, u8, u8, u32)>().prop_map(T::Datetime),
+                any::<i64>().prop_map(T::Timestamp),
+                Just(T::DbNull),
+                $($extra,)?
+            ]
+        }};
+    }
+
+    fn range_bound<T: Arbitrary>() -> impl Strategy<Value = Option<(T, pg4::RangeBoundKind)>> {
+        option::of((
+            any::<T>(),
+            prop_oneof![
+                Just(pg4::RangeBoundKind::Inclusive),
+                Just(pg4::RangeBoundKind::Exclusive),
+            ],
+        ))
+    }
+
+    /// Any Postgres 4 value, of any type.
+    fn pg4_value() -> impl Strategy<Value = pg4::DbValue> {
+        prop_oneof![
+            pg3_value!(pg4::DbValue),
+            any::<String>().prop_map(pg4::DbValue::Uuid),
+            any::<Vec<u8>>().prop_map(pg4::DbValue::Jsonb),
+            any::<String>().prop_map(pg4::DbValue::Decimal),
+            (range_bound(), range_bound()).prop_map(pg4::DbValue::RangeInt32),
+            (range_bound(), range_bound()).prop_map(pg4::DbValue::RangeInt64),
+            (range_bound(), range_bound()).prop_map(pg4::DbValue::RangeDecimal),
+            vec(option::of(any::<i32>()), 0..4).prop_map(pg4::DbValue::ArrayInt32),
+            vec(option::of(any::<i64>()), 0..4).prop_map(pg4::DbValue::ArrayInt64),
+            vec(option::of(any::<String>()), 0..4).prop_map(pg4::DbValue::ArrayDecimal),
+            vec(option::of(any::<String>()), 0..4).prop_map(pg4::DbValue::ArrayStr),
+            any::<(i64, i32, i32)>().prop_map(|(micros, days, months)| {
+                pg4::DbValue::Interval(pg4::Interval {
+                    micros,
+                    days,
+                    months,
+                })
+            }),
+            any::<Vec<u8>>().prop_map(pg4::DbValue::Unsupported),
+        ]
+    }
+
+    /// An interface version which receives Postgres 4 values converted to its
+    /// own types.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Older {
+        V1,
+        V2,
+        Pg3,
+    }
+
+    /// The allowlist of Postgres 4 values that older versions can't
+    /// represent, so which they receive as `Unsupported` (or data type
+    /// `Other`). Every other value must convert to a real value.
+    ///
+    /// The match is deliberately exhaustive: a variant added to Postgres 4
+    /// doesn't compile here until it has been decided how older versions see
+    /// it. Add it to `pg4_value` and `PG4_DATA_TYPES` too.
+    fn unsupported_in(value: &pg4::DbValue) -> &'static [Older] {
+        match value {
+            pg4::DbValue::Boolean(_)
+            | pg4::DbValue::Int8(_)
+            | pg4::DbValue::Int16(_)
+            | pg4::DbValue::Int32(_)
+            | pg4::DbValue::Int64(_)
+            | pg4::DbValue::Floating32(_)
+            | pg4::DbValue::Floating64(_)
+            | pg4::DbValue::Str(_)
+            | pg4::DbValue::Binary(_)
+            | pg4::DbValue::DbNull => &[],
+            // The shared v1 and v2 rdbms-types have no date or time types;
+            // Postgres 3 added them
+            pg4::DbValue::Date(_)
+            | pg4::DbValue::Time(_)
+            | pg4::DbValue::Datetime(_)
+            | pg4::DbValue::Timestamp(_) => &[Older::V1, Older::V2],
+            // Added in Postgres 4. Older versions could have been given
+            // these as strings or bytes, but they have always reported them
+            // as unsupported, and guests may rely on that
+            pg4::DbValue::Uuid(_)
+            | pg4::DbValue::Jsonb(_)
+            | pg4::DbValue::Decimal(_)
+            | pg4::DbValue::RangeInt32(_)
+            | pg4::DbValue::RangeInt64(_)
+            | pg4::DbValue::RangeDecimal(_)
+            | pg4::DbValue::ArrayInt32(_)
+            | pg4::DbValue::ArrayInt64(_)
+            | pg4::DbValue::ArrayDecimal(_)
+            | pg4::DbValue::ArrayStr(_)
+            | pg4::DbValue::Interval(_) => &[Older::V1, Older::V2, Older::Pg3],
+            // Unsupported everywhere
+            pg4::DbValue::Unsupported(_) => &[Older::V1, Older::V2, Older::Pg3],
+        }
+    }
+
+    /// Every Postgres 4 column data type.
+    const PG4_DATA_TYPES: &[pg4::DbDataType] = &[
+        pg4::DbDataType::Boolean,
+        pg4::DbDataType::Int8,
+        pg4::DbDataType::Int16,
+        pg4::DbDataType::Int32,
+        pg4::DbDataType::Int64,
+        pg4::DbDataType::Floating32,
+        pg4::DbDataType::Floating64,
+        pg4::DbDataType::Str,
+        pg4::DbDataType::Binary,
+        pg4::DbDataType::Date,
+        pg4::DbDataType::Time,
+        pg4::DbDataType::Datetime,
+        pg4::DbDataType::Timestamp,
+        pg4::DbDataType::Uuid,
+        pg4::DbDataType::Jsonb,
+        pg4::DbDataType::Decimal,
+        pg4::DbDataType::RangeInt32,
+        pg4::DbDataType::RangeInt64,
+        pg4::DbDataType::RangeDecimal,
+        pg4::DbDataType::ArrayInt32,
+        pg4::DbDataType::ArrayInt64,
+        pg4::DbDataType::ArrayDecimal,
+        pg4::DbDataType::ArrayStr,
+        pg4::DbDataType::Interval,
+    ];
+
+    /// The data type allowlist, matching [`unsupported_in`] for the values of
+    /// each type.
+    fn other_in(data_type: &pg4::DbDataType) -> &'static [Older] {
+        match data_type {
+            pg4::DbDataType::Boolean
+            | pg4::DbDataType::Int8
+            | pg4::DbDataType::Int16
+            | pg4::DbDataType::Int32
+            | pg4::DbDataType::Int64
+            | pg4::DbDataType::Floating32
+            | pg4::DbDataType::Floating64
+            | pg4::DbDataType::Str
+            | pg4::DbDataType::Binary => &[],
+            pg4::DbDataType::Date
+            | pg4::DbDataType::Time
+            | pg4::DbDataType::Datetime
+            | pg4::DbDataType::Timestamp => &[Older::V1, Older::V2],
+            pg4::DbDataType::Uuid
+            | pg4::DbDataType::Jsonb
+            | pg4::DbDataType::Decimal
+            | pg4::DbDataType::RangeInt32
+            | pg4::DbDataType::RangeInt64
+            | pg4::DbDataType::RangeDecimal
+            | pg4::DbDataType::ArrayInt32
+            | pg4::DbDataType::ArrayInt64
+            | pg4::DbDataType::ArrayDecimal
+            | pg4::DbDataType::ArrayStr
+            | pg4::DbDataType::Interval => &[Older::V1, Older::V2, Older::Pg3],
+            // Types the interface doesn't name are `Other` everywhere
+            pg4::DbDataType::Other(_) => &[Older::V1, Older::V2, Older::Pg3],
+        }
+    }
+
+    #[test]
+    fn newest_data_types_reach_older_versions() {
+        let mut data_types = PG4_DATA_TYPES.to_vec();
+        data_types.push(pg4::DbDataType::Other("tsvector".into()));
+        for data_type in data_types {
+            let other_in = other_in(&data_type);
+            let converted = [
+                (
+                    Older::V1,
+                    matches!(
+                        v1::rdbms_types::DbDataType::from(data_type.clone()),
+                        v1::rdbms_types::DbDataType::Other
+                    ),
+                ),
+                (
+                    Older::V2,
+                    matches!(
+                        v2::rdbms_types::DbDataType::from(data_type.clone()),
+                        v2::rdbms_types::DbDataType::Other
+                    ),
+                ),
+                (
+                    Older::Pg3,
+                    matches!(
+                        pg3::DbDataType::from(data_type.clone()),
+                        pg3::DbDataType::Other
+                    ),
+                ),
+            ];
+            for (version, is_other) in converted {
+                assert_eq!(
+                    other_in.contains(&version),
+                    is_other,
+                    "{data_type:?} in {version:?}"
+                );
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn newest_values_reach_older_versions(value in pg4_value()) {
+            let unsupported_in = unsupported_in(&value);
+            let converted = [
+                (Older::V1, matches!(V1Value::from(value.clone()), V1Value::Unsupported)),
+                (Older::V2, matches!(V2Value::from(value.clone()), V2Value::Unsupported)),
+                (
+                    Older::Pg3,
+                    matches!(pg3::DbValue::from(value.clone()), pg3::DbValue::Unsupported),
+                ),
+            ];
+            for (version, is_unsupported) in converted {
+                prop_assert_eq!(
+                    unsupported_in.contains(&version),
+                    is_unsupported,
+                    "{:?} in {:?}",
+                    value,
+                    version
+                );
+            }
+        }
+
+        #[test]
+        fn rdbms_parameters_round_trip(value in rdbms_value!(V1Param)) {
+            let round_tripped = V1Param::from(V2Param::from(value.clone()));
+            prop_assert_eq!(debug(&value), debug(&round_tripped));
+        }
+
+        #[test]
+        fn rdbms_values_round_trip(value in rdbms_value!(V2Value, Just(V2Value::Unsupported))) {
+            let round_tripped = V2Value::from(V1Value::from(value.clone()));
+            prop_assert_eq!(debug(&value), debug(&round_tripped));
+        }
+
+        #[test]
+        fn pg_parameters_round_trip(value in pg3_value!(pg3::ParameterValue)) {
+            let round_tripped = pg3::ParameterValue::try_from(pg4::ParameterValue::from(value.clone()));
+            prop_assert!(round_tripped.is_ok());
+            prop_assert_eq!(debug(&value), debug(&round_tripped.unwrap()));
+        }
+
+        #[test]
+        fn pg_values_round_trip(
+            value in pg3_value!(pg3::DbValue, Just(pg3::DbValue::Unsupported))
+        ) {
+            let round_tripped = pg3::DbValue::from(pg4::DbValue::from(value.clone()));
+            prop_assert_eq!(debug(&value), debug(&round_tripped));
+        }
+
+        #[test]
+        fn unsigned_parameters_are_rejected_by_postgres(value in rdbms_value!(V2Param)) {
+            let is_unsigned = matches!(
+                value,
+                V2Param::Uint8(_) | V2Param::Uint16(_) | V2Param::Uint32(_) | V2Param::Uint64(_)
+            );
+            prop_assert_eq!(is_unsigned, pg4::ParameterValue::try_from(value).is_err());
+        }
+
+        #[test]
+        fn redis_values_round_trip(
+            parameter in prop_oneof![
+                any::<i64>().prop_map(v2::redis::RedisParameter::Int64),
+                any::<Vec<u8>>().prop_map(v2::redis::RedisParameter::Binary),
+            ],
+            result in prop_oneof![
+                Just(v2::redis::RedisResult::Nil),
+                any::<String>().prop_map(v2::redis::RedisResult::Status),
+                any::<i64>().prop_map(v2::redis::RedisResult::Int64),
+                any::<Vec<u8>>().prop_map(v2::redis::RedisResult::Binary),
+            ],
+        ) {
+            let round_tripped =
+                v2::redis::RedisParameter::from(v1::redis::RedisParameter::from(parameter.clone()));
+            prop_assert_eq!(debug(&parameter), debug(&round_tripped));
+            let round_tripped =
+                v2::redis::RedisResult::from(v1::redis::RedisResult::from(result.clone()));
+            prop_assert_eq!(debug(&result), debug(&round_tripped));
+        }
+
+        #[test]
+        fn llm_params_round_trip(
+            (max_tokens, repeat_penalty, repeat_penalty_last_n_token_count) in any::<(u32, f32, u32)>(),
+            (temperature, top_k, top_p) in any::<(f32, u32, f32)>(),
+            cache_prompt_prefix in any::<bool>(),
+        ) {
+            let params = v3::InferencingParams {
+                max_tokens,
+                repeat_penalty,
+                repeat_penalty_last_n_token_count,
+                temperature,
+                top_k,
+                top_p,
+                cache_prompt_prefix,
+            };
+            let v2_params = v2::llm::InferencingParams::from(params.clone());
+            let v1_params = v1::llm::InferencingParams::from(v2_params.clone());
+            let round_tripped = v2::llm::InferencingParams::from(v1_params);
+            prop_assert_eq!(debug(&v2_params), debug(&round_tripped));
+
+            // Older versions can't ask for prompt caching
+            let round_tripped = v3::InferencingParams::from(v2_params);
+            prop_assert_eq!(
+                debug(&v3::InferencingParams { cache_prompt_prefix: false, ..params }),
+                debug(&round_tripped)
+            );
+        }
+
+        #[test]
+        fn llm_results_round_trip(
+            text in any::<String>(),
+            (prompt_token_count, generated_token_count) in any::<(u32, u32)>(),
+        ) {
+            let result = v2::llm::InferencingResult {
+                text,
+                usage: v2::llm::InferencingUsage {
+                    prompt_token_count,
+                    generated_token_count,
+                },
+            };
+            let round_tripped =
+                v2::llm::InferencingResult::from(v3::InferencingResult::from(result.clone()));
+            prop_assert_eq!(debug(&result), debug(&round_tripped));
+        }
+    }
 }
@@ -12,7 +12,11 @@ wasmtime::component::bindgen!({
         include fermyon:spin/platform@3.0.0;
         include spin:up/platform@3.2.0;
         include spin:up/platform@3.4.0;
+        include spin:up/platform@3.5.0;
         include wasi:keyvalue/imports@0.2.0-draft2;
+        export spin:kafka/inbound-kafka@3.0.0;
+        export spin:websocket/inbound-websocket@3.0.0;
+        export spin:grpc/inbound-grpc@3.0.0;
     }
     "#,
     path: "../../wit",
@@ -22,6 +26,7 @@ wasmtime::component::bindgen!({
     trappable_error_type: {
         "fermyon:spin/config/error" => v1::config::Error,
         "fermyon:spin/http-types/http-error" => v1::http_types::HttpError,
+        "spin:kafka/kafka/error" => spin::kafka::kafka::Error,
         "fermyon:spin/llm@2.0.0/error" => v2::llm::Error,
         "fermyon:spin/llm/error" => v1::llm::Error,
         "fermyon:spin/mqtt@2.0.0/error" => v2::mqtt::Error,
@@ -33,9 +38,14 @@ wasmtime::component::bindgen!({
         "fermyon:spin/sqlite@2.0.0/error" => v2::sqlite::Error,
         "fermyon:spin/sqlite/error" => v1::sqlite::Error,
         "fermyon:spin/variables@2.0.0/error" => v2::variables::Error,
+        "spin:key-value/batch/batch-error" => spin::key_value::batch::BatchError,
+        "spin:key-value/transactions/transaction-error" => spin::key_value::transactions::TransactionError,
+        "spin:llm/llm/error" => spin::llm::llm::Error,
         "spin:postgres/postgres@3.0.0/error" => spin::postgres3_0_0::postgres::Error,
         "spin:postgres/postgres@4.0.0/error" => spin::postgres4_0_0::postgres::Error,
-        "spin:sqlite/sqlite/error" => spin::sqlite::sqlite::Error,
+        "spin:sqlite/attach/attach-error" => spin::sqlite3_1_0::attach::AttachError,
+        "spin:sqlite/backup/error" => spin::sqlite3_1_0::backup::Error,
+        "spin:sqlite/sqlite@3.0.0/error" => spin::sqlite3_0_0::sqlite::Error,
         "wasi:config/store@0.2.0-draft-2024-09-27/error" => wasi::config::store::Error,
         "wasi:keyvalue/store/error" => wasi::keyvalue::store::Error,
         "wasi:keyvalue/atomics/cas-error" => wasi::keyvalue::atomics::CasError,
@@ -46,3 +56,16 @@ pub use fermyon::spin as v1;
 pub use fermyon::spin2_0_0 as v2;
 
 mod conversions;
+
+/// The Wasm-encoded WIT packages which define the worlds supported by this
+/// version of Spin, keyed by package name (e.g. `spin:up@3.5.0`).
+pub const WIT_PACKAGES: &[(&str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/wit_packages.rs"));
+
+/// Returns the Wasm-encoded WIT package with the given name, if it is one
+/// of the [`WIT_PACKAGES`].
+pub fn wit_package(name: &str) -> Option<&'static [u8]> {
+    WIT_PACKAGES
+        .iter()
+        .find(|(package_name, _)| *package_name == name)
+        .map(|(_, bytes)| *bytes)
+}
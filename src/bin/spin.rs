@@ -19,8 +19,11 @@ use spin_cli::{build_info::*, subprocess::ExitStatusError};
 use spin_runtime_factors::FactorsBuilder;
 use spin_trigger::cli::help::HelpArgsOnlyTrigger;
 use spin_trigger::cli::FactorsTriggerCommand;
+use spin_trigger_grpc::GrpcTrigger;
 use spin_trigger_http::HttpTrigger;
+use spin_trigger_kafka::KafkaTrigger;
 use spin_trigger_redis::RedisTrigger;
+use spin_trigger_websocket::WebSocketTrigger;
 
 #[tokio::main]
 async fn main() {
@@ -44,6 +47,7 @@ async fn main() {
 
 async fn _main() -> anyhow::Result<()> {
     spin_telemetry::init(VERSION.to_string()).context("Failed to initialize telemetry")?;
+    register_local_environment().context("Failed to register local target environment")?;
 
     let plugin_help_entries = plugin_help_entries();
 
@@ -80,6 +84,64 @@ async fn _main() -> anyhow::Result<()> {
         .inspect_err(|err| tracing::debug!(?err))
 }
 
+/// Registers the worlds supported by the built-in triggers, for validating
+/// applications which target the `spin-up:local` environment.
+fn register_local_environment() -> anyhow::Result<()> {
+    let spin_up = spin_world::wit_package("spin:up@3.5.0").context("missing spin:up package")?;
+    let spin_2 =
+        spin_world::wit_package("fermyon:spin@2.0.0").context("missing fermyon:spin@2.0.0")?;
+    let spin_1 = spin_world::wit_package("fermyon:spin").context("missing fermyon:spin")?;
+
+    let http_capabilities = [spin_locked_app::locked::SERVICE_CHAINING_KEY];
+    spin_environments::register_local_trigger(
+        "http",
+        spin_up,
+        &["spin:up/http-trigger@3.5.0"],
+        &http_capabilities,
+    )?;
+    spin_environments::register_local_trigger(
+        "http",
+        spin_2,
+        &[
+            "fermyon:spin/http-trigger@2.0.0",
+            "fermyon:spin/http-trigger-rc20231018@2.0.0",
+        ],
+        &[],
+    )?;
+    spin_environments::register_local_trigger("http", spin_1, &["fermyon:spin/http-trigger"], &[])?;
+    spin_environments::register_local_trigger(
+        "redis",
+        spin_up,
+        &["spin:up/redis-trigger@3.5.0"],
+        &[],
+    )?;
+    spin_environments::register_local_trigger(
+        "redis",
+        spin_1,
+        &["fermyon:spin/redis-trigger"],
+        &[],
+    )?;
+    spin_environments::register_local_trigger(
+        "kafka",
+        spin_up,
+        &["spin:up/kafka-trigger@3.5.0"],
+        &[],
+    )?;
+    spin_environments::register_local_trigger(
+        "websocket",
+        spin_up,
+        &["spin:up/websocket-trigger@3.5.0"],
+        &[],
+    )?;
+    spin_environments::register_local_trigger(
+        "grpc",
+        spin_up,
+        &["spin:up/grpc-trigger@3.5.0"],
+        &[],
+    )?;
+    Ok(())
+}
+
 fn print_error_chain(err: anyhow::Error) {
     if let Some(cause) = err.source() {
         let is_multiple = cause.source().is_some();
@@ -144,6 +206,11 @@ enum SpinApp {
 enum TriggerCommands {
     Http(FactorsTriggerCommand<HttpTrigger, FactorsBuilder>),
     Redis(FactorsTriggerCommand<RedisTrigger, FactorsBuilder>),
+    Kafka(FactorsTriggerCommand<KafkaTrigger, FactorsBuilder>),
+    #[clap(name = "websocket")]
+    WebSocket(FactorsTriggerCommand<WebSocketTrigger, FactorsBuilder>),
+    #[clap(name = "grpc")]
+    Grpc(FactorsTriggerCommand<GrpcTrigger, FactorsBuilder>),
     #[clap(name = spin_cli::HELP_ARGS_ONLY_TRIGGER_TYPE, hide = true)]
     HelpArgsOnly(FactorsTriggerCommand<HelpArgsOnlyTrigger, FactorsBuilder>),
 }
@@ -162,6 +229,9 @@ impl SpinApp {
             Self::Build(cmd) => cmd.run().await,
             Self::Trigger(TriggerCommands::Http(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::Redis(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Kafka(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::WebSocket(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Grpc(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::HelpArgsOnly(cmd)) => cmd.run().await,
             Self::Plugins(cmd) => cmd.run().await,
             Self::External(cmd) => execute_external_subcommand(cmd, app).await,
@@ -39,6 +39,22 @@ pub struct BuildCommand {
     )]
     skip_target_checks: bool,
 
+    /// Resolve the deployment targets afresh, rather than using the versions pinned
+    /// in the application's `target-environments.lock` file, and update the lockfile
+    /// to match.
+    #[clap(
+        long = "refresh-targets",
+        conflicts_with = "skip-target-checks",
+        takes_value = false
+    )]
+    refresh_targets: bool,
+
+    /// Save the output of each build command to a log file under
+    /// `.spin/build-logs`, as well as printing it. If a command fails, the
+    /// error includes the last lines of its output.
+    #[clap(long = "capture-logs", takes_value = false, env = spin_build::CAPTURE_LOGS_ENV)]
+    capture_logs: bool,
+
     /// Run the application after building.
     #[clap(name = BUILD_UP_OPT, short = 'u', long = "up")]
     pub up: bool,
@@ -57,6 +73,7 @@ impl BuildCommand {
             &manifest_file,
             &self.component_id,
             self.target_checking(),
+            self.log_capture(),
             None,
         )
         .await?;
@@ -76,9 +93,19 @@ impl BuildCommand {
         }
     }
 
+    fn log_capture(&self) -> spin_build::LogCapture {
+        if self.capture_logs {
+            spin_build::LogCapture::Capture
+        } else {
+            spin_build::LogCapture::Inherit
+        }
+    }
+
     fn target_checking(&self) -> spin_build::TargetChecking {
         if self.skip_target_checks {
             spin_build::TargetChecking::Skip
+        } else if self.refresh_targets {
+            spin_build::TargetChecking::Refresh
         } else {
             spin_build::TargetChecking::Check
         }
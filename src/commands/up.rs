@@ -100,11 +100,13 @@ pub struct UpCommand {
     #[clap(long)]
     pub cache_dir: Option<PathBuf>,
 
-    /// For local apps with directory mounts and no excluded files, mount them directly instead of using a temporary
+    /// For local apps with directory mounts, mount them directly instead of using a temporary
     /// directory.
     ///
     /// This allows you to update the assets on the host filesystem such that the updates are visible to the guest
-    /// without a restart.  This cannot be used with registry apps or apps which use file patterns and/or exclusions.
+    /// without a restart.  This cannot be used with registry apps or apps which use file patterns. Directories
+    /// containing files excluded by `exclude_files` are still copied, without the excluded files, so updates to
+    /// them require a restart.
     #[clap(long, takes_value = false)]
     pub direct_mounts: bool,
 
@@ -114,6 +116,16 @@ pub struct UpCommand {
     #[clap(long, takes_value = false, env = ALWAYS_BUILD_ENV)]
     pub build: bool,
 
+    /// For local apps, fail rather than warn if the manifest defines components
+    /// which no trigger uses.
+    #[clap(long, takes_value = false)]
+    pub strict_components: bool,
+
+    /// For local apps, the manifest profile to apply, e.g. `--profile dev` applies
+    /// the manifest's `[profile.dev]` section. By default no profile is applied.
+    #[clap(long = "profile")]
+    pub profile: Option<String>,
+
     /// [Experimental] Component ID to run. This can be specified multiple times. The default is all components.
     #[clap(short = 'c', long = "component-id")]
     pub components: Vec<String>,
@@ -488,19 +500,31 @@ impl UpCommand {
     ) -> anyhow::Result<LockedApp> {
         match resolved {
             ResolvedAppSource::File { manifest_path, .. } => {
-                let files_mount_strategy = if self.direct_mounts {
-                    FilesMountStrategy::Direct
+                let assets_dir = working_dir.join("assets");
+                let (files_mount_strategy, direct_mount_staging_root) = if self.direct_mounts {
+                    (FilesMountStrategy::Direct, Some(assets_dir))
                 } else {
-                    FilesMountStrategy::Copy(working_dir.join("assets"))
+                    (FilesMountStrategy::Copy(assets_dir), None)
                 };
-                spin_loader::from_file(&manifest_path, files_mount_strategy, self.cache_dir.clone())
-                    .await
-                    .with_context(|| {
-                        format!(
-                            "Failed to load manifest from {}",
-                            quoted_path(&manifest_path)
-                        )
-                    })
+                let cache_dir = self.cache_dir.clone();
+                let options = spin_loader::ManifestLoadOptions {
+                    strict_components: self.strict_components,
+                    profile: self.profile.clone(),
+                    direct_mount_staging_root,
+                };
+                spin_loader::from_file_with_options(
+                    &manifest_path,
+                    files_mount_strategy,
+                    cache_dir,
+                    options,
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to load manifest from {}",
+                        quoted_path(&manifest_path)
+                    )
+                })
             }
             ResolvedAppSource::OciRegistry { locked_app } => Ok(locked_app),
             ResolvedAppSource::BareWasm { wasm_path } => spin_loader::from_wasm_file(&wasm_path)
@@ -671,7 +695,7 @@ fn trigger_commands_for_trigger_types(trigger_types: Vec<&str>) -> Result<Vec<Ve
     trigger_types
         .iter()
         .map(|&t| match t {
-            "http" | "redis" => Ok(trigger_command(t)),
+            "http" | "redis" | "kafka" | "websocket" | "grpc" => Ok(trigger_command(t)),
             _ => {
                 let cmd = resolve_trigger_plugin(t)?;
                 Ok(vec![cmd])
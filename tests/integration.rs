@@ -454,6 +454,41 @@ mod integration_tests {
         Ok(())
     }
 
+    #[test]
+    /// Test that the http trigger serves requests on every `--listen` address
+    fn http_listens_on_multiple_addresses() -> anyhow::Result<()> {
+        let second_port = std::net::TcpListener::bind("127.0.0.1:0")?
+            .local_addr()?
+            .port();
+        run_test(
+            "outbound-http-to-same-app",
+            SpinConfig {
+                binary_path: spin_binary(),
+                spin_up_args: vec!["--listen".into(), format!("127.0.0.1:{second_port}")],
+                app_type: SpinAppType::Http,
+            },
+            ServicesConfig::none(),
+            move |env| {
+                // Served on the first address, including self requests
+                let spin = env.runtime_mut();
+                assert_spin_request(
+                    spin,
+                    Request::new(Method::Get, "/outbound-allowed"),
+                    Response::new_with_body(200, "Hello World!\n"),
+                )?;
+
+                // Served on the second address
+                let response = Request::new(Method::Get, "/outbound-allowed")
+                    .send("127.0.0.1", second_port)?;
+                assert_eq!(response.status(), 200);
+                assert_eq!(response.text()?, "Hello World!\n");
+                Ok(())
+            },
+        )?;
+
+        Ok(())
+    }
+
     #[test]
     fn http_rust_template_smoke_test() -> anyhow::Result<()> {
         http_smoke_test_template(
@@ -0,0 +1,50 @@
+use helper::{ensure_eq, ensure_matches, ensure_ok};
+
+use helper::http_trigger_bindings::spin::key_value::batch::{delete_many, get_many, set_many};
+use helper::http_trigger_bindings::wasi::keyvalue::store::open;
+
+helper::define_component!(Component);
+
+impl Component {
+    fn main() -> Result<(), String> {
+        let store = ensure_ok!(open("default"));
+
+        // Set several keys, one of them twice
+        ensure_ok!(set_many(
+            &store,
+            &[
+                ("a".to_string(), b"1".to_vec()),
+                ("b".to_string(), b"2".to_vec()),
+                ("a".to_string(), b"3".to_vec()),
+            ]
+        ));
+        ensure_matches!(store.get("a"), Ok(Some(v)) if v == b"3");
+
+        // Get them back, along with a missing key
+        let values = ensure_ok!(get_many(
+            &store,
+            &["a".to_string(), "b".to_string(), "missing".to_string()]
+        ));
+        ensure_eq!(values.len(), 3);
+        ensure_matches!(values.as_slice(), [
+            (a, Some(a_value)),
+            (b, Some(b_value)),
+            (missing, None),
+        ] if a == "a" && a_value == b"3" && b == "b" && b_value == b"2" && missing == "missing");
+
+        // Delete them, including the missing key
+        ensure_ok!(delete_many(
+            &store,
+            &["a".to_string(), "b".to_string(), "missing".to_string()]
+        ));
+        ensure_matches!(store.exists("a"), Ok(false));
+        ensure_matches!(store.exists("b"), Ok(false));
+
+        // Empty batches are allowed
+        ensure_matches!(get_many(&store, &[]), Ok(v) if v.is_empty());
+        ensure_ok!(set_many(&store, &[]));
+        ensure_ok!(delete_many(&store, &[]));
+
+        Ok(())
+    }
+}
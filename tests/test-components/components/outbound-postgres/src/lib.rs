@@ -37,14 +37,83 @@ impl Component {
 
         let rowset = ensure_ok!(json_types(&conn));
         ensure!(rowset.rows.iter().all(|r| r.len() == 2));
-        ensure_matches!(&rowset.rows[0][1], postgres::DbValue::Jsonb(v) if String::from_utf8_lossy(v) == r#"{"s":"hello","n":123,"b":true,"x":null}"#);
-        ensure_matches!(&rowset.rows[1][1], postgres::DbValue::Jsonb(v) if String::from_utf8_lossy(v) == r#"{"s":"world","n":234,"b":false,"x":null}"#);
+        ensure_matches!(rowset.columns[1].data_type, postgres::DbDataType::Jsonb);
+        ensure_matches!(&rowset.rows[0][1], postgres::DbValue::Jsonb(v) if String::from_utf8_lossy(v) == r#"{"b": true, "n": 123, "s": "hello", "x": null}"#);
+        ensure_matches!(&rowset.rows[1][1], postgres::DbValue::Jsonb(v) if String::from_utf8_lossy(v) == r#"{"b": false, "n": 234, "s": "world", "x": null}"#);
+        ensure_matches!(rowset.rows[2][1], postgres::DbValue::DbNull);
+        let result = invalid_json(&conn);
+        ensure_matches!(result, Err(postgres::Error::BadParameter(_)));
 
         let rowset = ensure_ok!(uuid_type(&conn));
         ensure!(rowset.rows.iter().all(|r| r.len() == 2));
         ensure_matches!(&rowset.rows[0][1], postgres::DbValue::Uuid(v) if v == "12345678-1234-1234-1234-123456789abc");
         ensure_matches!(&rowset.rows[1][1], postgres::DbValue::Uuid(v) if v == "fedcba98-fedc-fedc-fedc-fedcba987654");
 
+        let rowset = ensure_ok!(decimal_type(&conn));
+        ensure_matches!(rowset.columns[1].data_type, postgres::DbDataType::Decimal);
+        ensure_matches!(&rowset.rows[0][1], postgres::DbValue::Decimal(v) if v == "123456789012345678901234567890.1234567890");
+        ensure_matches!(&rowset.rows[1][1], postgres::DbValue::Decimal(v) if v == "-0.000150");
+        ensure_matches!(rowset.rows[2][1], postgres::DbValue::DbNull);
+
+        let rowset = ensure_ok!(range_types(&conn));
+        ensure_matches!(
+            rowset.columns[1].data_type,
+            postgres::DbDataType::RangeInt32
+        );
+        ensure_matches!(
+            rowset.columns[2].data_type,
+            postgres::DbDataType::RangeInt64
+        );
+        // Postgres normalizes discrete ranges to [lower, upper)
+        ensure_matches!(
+            rowset.rows[0][1],
+            postgres::DbValue::RangeInt32((
+                Some((2, postgres::RangeBoundKind::Inclusive)),
+                Some((11, postgres::RangeBoundKind::Exclusive))
+            ))
+        );
+        ensure_matches!(
+            rowset.rows[0][2],
+            postgres::DbValue::RangeInt64((Some((5, postgres::RangeBoundKind::Inclusive)), None))
+        );
+        ensure_matches!(
+            rowset.rows[1][1],
+            postgres::DbValue::RangeInt32((
+                Some((1, postgres::RangeBoundKind::Inclusive)),
+                Some((10, postgres::RangeBoundKind::Exclusive))
+            ))
+        );
+        ensure_matches!(
+            rowset.rows[1][2],
+            postgres::DbValue::RangeInt64((
+                None,
+                Some((6_000_000_000, postgres::RangeBoundKind::Exclusive))
+            ))
+        );
+        ensure_matches!(rowset.rows[2][1], postgres::DbValue::DbNull);
+
+        let rowset = ensure_ok!(array_types(&conn));
+        ensure_matches!(
+            rowset.columns[1].data_type,
+            postgres::DbDataType::ArrayInt32
+        );
+        ensure_matches!(
+            rowset.columns[2].data_type,
+            postgres::DbDataType::ArrayInt64
+        );
+        ensure_matches!(rowset.columns[3].data_type, postgres::DbDataType::ArrayStr);
+        ensure_matches!(
+            rowset.columns[4].data_type,
+            postgres::DbDataType::ArrayDecimal
+        );
+        for row in &rowset.rows[0..2] {
+            ensure_matches!(&row[1], postgres::DbValue::ArrayInt32(v) if v == &[Some(1), None, Some(3)]);
+            ensure_matches!(&row[2], postgres::DbValue::ArrayInt64(v) if v.is_empty());
+            ensure_matches!(&row[3], postgres::DbValue::ArrayStr(v) if v == &[Some("a".to_owned()), None]);
+            ensure_matches!(&row[4], postgres::DbValue::ArrayDecimal(v) if v == &[Some("1.50".to_owned()), None]);
+        }
+        ensure_matches!(rowset.rows[2][1], postgres::DbValue::DbNull);
+
         let rowset = ensure_ok!(nullable(&conn));
         ensure!(rowset.rows.iter().all(|r| r.len() == 1));
         ensure!(matches!(rowset.rows[0][0], postgres::DbValue::DbNull));
@@ -191,7 +260,7 @@ fn json_types(conn: &postgres::Connection) -> Result<postgres::RowSet, postgres:
     let create_table_sql = r#"
         CREATE TEMPORARY TABLE test_json_types (
             index int2,
-            j jsonb NOT NULL
+            j jsonb
          );
     "#;
 
@@ -219,9 +288,22 @@ fn json_types(conn: &postgres::Connection) -> Result<postgres::RowSet, postgres:
             (2, $1);
         "#;
 
-    let jsonb_pv = postgres::ParameterValue::Jsonb(r#"{ "s": "world", "n": 234, "b": false, "x": null }"#.as_bytes().to_vec());
+    let jsonb_pv = postgres::ParameterValue::Jsonb(
+        r#"{ "s": "world", "n": 234, "b": false, "x": null }"#
+            .as_bytes()
+            .to_vec(),
+    );
     conn.execute(insert_sql_spin_parameters, &[jsonb_pv])?;
 
+    let insert_sql_null = r#"
+        INSERT INTO test_json_types
+            (index, j)
+        VALUES
+            (3, $1);
+        "#;
+
+    conn.execute(insert_sql_null, &[postgres::ParameterValue::DbNull])?;
+
     let sql = r#"
         SELECT
             index,
@@ -233,6 +315,172 @@ fn json_types(conn: &postgres::Connection) -> Result<postgres::RowSet, postgres:
     conn.query(sql, &[])
 }
 
+fn invalid_json(conn: &postgres::Connection) -> Result<u64, postgres::Error> {
+    let sql = "SELECT $1::jsonb";
+    let jsonb_pv = postgres::ParameterValue::Jsonb(br#"{ "s": "#.to_vec());
+    conn.execute(sql, &[jsonb_pv])
+}
+
+fn decimal_type(conn: &postgres::Connection) -> Result<postgres::RowSet, postgres::Error> {
+    let create_table_sql = r#"
+        CREATE TEMPORARY TABLE test_decimal_type (
+            index int2,
+            d numeric
+         );
+    "#;
+
+    conn.execute(create_table_sql, &[])?;
+
+    // Decoding of "known good" values, beyond the precision of a 128-bit decimal
+    let insert_sql_pg_literals = r#"
+        INSERT INTO test_decimal_type
+            (index, d)
+        VALUES
+            (1, 123456789012345678901234567890.1234567890);
+    "#;
+
+    conn.execute(insert_sql_pg_literals, &[])?;
+
+    // Encoding, including trailing zeros which are part of the scale
+    let insert_sql_spin_parameters = r#"
+        INSERT INTO test_decimal_type
+            (index, d)
+        VALUES
+            (2, $1),
+            (3, $2);
+        "#;
+
+    let decimal_pv = postgres::ParameterValue::Decimal("-0.000150".to_owned());
+    conn.execute(
+        insert_sql_spin_parameters,
+        &[decimal_pv, postgres::ParameterValue::DbNull],
+    )?;
+
+    let sql = r#"
+        SELECT
+            index,
+            d
+        FROM test_decimal_type
+        ORDER BY index;
+    "#;
+
+    conn.query(sql, &[])
+}
+
+fn range_types(conn: &postgres::Connection) -> Result<postgres::RowSet, postgres::Error> {
+    let create_table_sql = r#"
+        CREATE TEMPORARY TABLE test_range_types (
+            index int2,
+            r32 int4range,
+            r64 int8range
+         );
+    "#;
+
+    conn.execute(create_table_sql, &[])?;
+
+    let insert_sql_pg_literals = r#"
+        INSERT INTO test_range_types
+            (index, r32, r64)
+        VALUES
+            (1, int4range(1, 10, '(]'), int8range(5, NULL));
+    "#;
+
+    conn.execute(insert_sql_pg_literals, &[])?;
+
+    let insert_sql_spin_parameters = r#"
+        INSERT INTO test_range_types
+            (index, r32, r64)
+        VALUES
+            (2, $1, $2),
+            (3, $3, $4);
+        "#;
+
+    let r32_pv = postgres::ParameterValue::RangeInt32((
+        Some((1, postgres::RangeBoundKind::Inclusive)),
+        Some((10, postgres::RangeBoundKind::Exclusive)),
+    ));
+    let r64_pv = postgres::ParameterValue::RangeInt64((
+        None,
+        Some((6_000_000_000, postgres::RangeBoundKind::Exclusive)),
+    ));
+    conn.execute(
+        insert_sql_spin_parameters,
+        &[
+            r32_pv,
+            r64_pv,
+            postgres::ParameterValue::DbNull,
+            postgres::ParameterValue::DbNull,
+        ],
+    )?;
+
+    let sql = r#"
+        SELECT
+            index,
+            r32,
+            r64
+        FROM test_range_types
+        ORDER BY index;
+    "#;
+
+    conn.query(sql, &[])
+}
+
+fn array_types(conn: &postgres::Connection) -> Result<postgres::RowSet, postgres::Error> {
+    let create_table_sql = r#"
+        CREATE TEMPORARY TABLE test_array_types (
+            index int2,
+            a32 int4[],
+            a64 int8[],
+            astr text[],
+            adec numeric[]
+         );
+    "#;
+
+    conn.execute(create_table_sql, &[])?;
+
+    let insert_sql_pg_literals = r#"
+        INSERT INTO test_array_types
+            (index, a32, a64, astr, adec)
+        VALUES
+            (1, ARRAY[1, NULL, 3], '{}', ARRAY['a', NULL], ARRAY[1.50, NULL]);
+    "#;
+
+    conn.execute(insert_sql_pg_literals, &[])?;
+
+    let insert_sql_spin_parameters = r#"
+        INSERT INTO test_array_types
+            (index, a32, a64, astr, adec)
+        VALUES
+            (2, $1, $2, $3, $4),
+            (3, $5, $6, $7, $8);
+        "#;
+
+    let params = [
+        postgres::ParameterValue::ArrayInt32(vec![Some(1), None, Some(3)]),
+        postgres::ParameterValue::ArrayInt64(vec![]),
+        postgres::ParameterValue::ArrayStr(vec![Some("a".to_owned()), None]),
+        postgres::ParameterValue::ArrayDecimal(vec![Some("1.50".to_owned()), None]),
+        postgres::ParameterValue::DbNull,
+        postgres::ParameterValue::DbNull,
+        postgres::ParameterValue::DbNull,
+        postgres::ParameterValue::DbNull,
+    ];
+    conn.execute(insert_sql_spin_parameters, &params)?;
+
+    let sql = r#"
+        SELECT
+            index,
+            a32,
+            a64,
+            astr,
+            adec
+        FROM test_array_types
+        ORDER BY index;
+    "#;
+
+    conn.query(sql, &[])
+}
+
 fn uuid_type(conn: &postgres::Connection) -> Result<postgres::RowSet, postgres::Error> {
     let create_table_sql = r#"
         CREATE TEMPORARY TABLE test_uuid_type (